@@ -0,0 +1,570 @@
+//! Read-only views of the intermediate stages of a build, e.g. to visualize why a region is missing
+use crate::recast::SysInput;
+use crate::{
+    BuildConfig, Error, Heightfield, InputGeometry, Point, PolyFlags, RecastQuery, Result, Span,
+    TileData, TileGrid,
+};
+use std::os::raw::c_void;
+use std::{ptr, slice};
+
+/// No vertex past the last one of a poly, or no neighbour poly
+pub const MESH_NULL_IDX: u16 = sys::RECASTC_MESH_NULL_IDX;
+
+/// The views point into the C side arrays, which are empty without a length
+unsafe fn view<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+fn area(area: u8) -> Option<u8> {
+    if area == sys::RECASTC_SPAN_NOT_WALKABLE {
+        None
+    } else {
+        Some(area)
+    }
+}
+
+/// The objects of one Recast build, from the solid heightfield to the poly mesh.
+///
+/// Every stage of the same build as `RecastQuery::build` or `TileGrid::build_tile`,
+/// without the conversion to a navmesh.
+#[derive(Debug)]
+pub struct BuildArtifacts {
+    build: ptr::NonNull<c_void>,
+    views: sys::RecastBuildArtifacts,
+}
+
+impl Drop for BuildArtifacts {
+    fn drop(&mut self) {
+        unsafe { sys::recastc_free_build(self.build.as_ptr()) }
+    }
+}
+
+impl BuildArtifacts {
+    /// Run the build over the whole geometry
+    pub fn build(geometry: &InputGeometry, config: &BuildConfig) -> Result<BuildArtifacts> {
+        BuildArtifacts::new(geometry, config, None)
+    }
+
+    /// Run the build of one tile, the stages include the border around the tile
+    /// except for the contours and the poly mesh
+    pub fn build_tile(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        grid: &TileGrid,
+        tx: i32,
+        ty: i32,
+    ) -> Result<BuildArtifacts> {
+        BuildArtifacts::new(geometry, config, Some(grid.tile_input(tx, ty)))
+    }
+
+    fn new(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile: Option<sys::RecastTileInput>,
+    ) -> Result<BuildArtifacts> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_input = SysInput::new(geometry);
+        let input = sys_input.as_sys();
+        let config = config.as_sys();
+        let tile_ptr = tile.as_ref().map_or(ptr::null(), |t| t as *const _);
+
+        let mut views = sys::RecastBuildArtifacts::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let build = unsafe {
+            ptr::NonNull::new(sys::recastc_build_artifacts(
+                &input as *const _,
+                &config as *const _,
+                tile_ptr,
+                &mut views as *mut _,
+                &mut err as *mut _,
+            ) as *mut c_void)
+        };
+
+        let build = build.ok_or_else(|| Error::BuildError(err.msg().into_owned()))?;
+        Ok(BuildArtifacts { build, views })
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.views.cell_size
+    }
+
+    pub fn cell_height(&self) -> f32 {
+        self.views.cell_height
+    }
+
+    /// The rasterized solid spans, after the span filters
+    pub fn heightfield(&self) -> HeightfieldView<'_> {
+        let hf = &self.views.heightfield;
+        let ncols = (hf.width * hf.height) as usize;
+
+        HeightfieldView {
+            bmin: Point(hf.bmin),
+            bmax: Point(hf.bmax),
+            width: hf.width,
+            height: hf.height,
+            columns: unsafe { view(hf.columns, ncols + 1) },
+            spans: unsafe { view(hf.spans, hf.span_count as usize) },
+        }
+    }
+
+    /// The walkable spans with their regions, after the erosion and the area marking
+    pub fn compact_heightfield(&self) -> CompactHeightfieldView<'_> {
+        let chf = &self.views.compact;
+        let ncols = (chf.width * chf.height) as usize;
+
+        CompactHeightfieldView {
+            bmin: Point(chf.bmin),
+            width: chf.width,
+            height: chf.height,
+            border_size: chf.border_size,
+            max_regions: chf.max_regions,
+            columns: unsafe { view(chf.columns, ncols + 1) },
+            spans: unsafe { view(chf.spans, chf.span_count as usize) },
+        }
+    }
+
+    /// The simplified outlines of the regions
+    pub fn contours(&self) -> Vec<Contour<'_>> {
+        let cset = &self.views.contours;
+        let contours = unsafe { view(cset.contours, cset.contour_count as usize) };
+
+        contours
+            .iter()
+            .map(|c| unsafe {
+                Contour {
+                    region: c.reg,
+                    area: area(c.area),
+                    verts: view(c.verts as *const [i32; 4], c.vert_count as usize),
+                    raw_verts: view(c.raw_verts as *const [i32; 4], c.raw_vert_count as usize),
+                }
+            })
+            .collect()
+    }
+
+    /// World position of the contour vertices, whose coordinates are in cells
+    pub fn contour_origin(&self) -> Point {
+        Point(self.views.contours.bmin)
+    }
+
+    /// The polys before the conversion to navmesh data
+    pub fn poly_mesh(&self) -> PolyMeshView<'_> {
+        let mesh = &self.views.poly_mesh;
+        let nvp = mesh.nvp as usize;
+        let npolys = mesh.poly_count as usize;
+
+        PolyMeshView {
+            bmin: Point(mesh.bmin),
+            cell_size: self.views.cell_size,
+            cell_height: self.views.cell_height,
+            nvp,
+            verts: unsafe { view(mesh.verts as *const [u16; 3], mesh.vert_count as usize) },
+            polys: unsafe { view(mesh.polys, npolys * nvp * 2) },
+            regions: unsafe { view(mesh.regs, npolys) },
+            flags: unsafe { view(mesh.flags, npolys) },
+            areas: unsafe { view(mesh.areas, npolys) },
+        }
+    }
+
+    /// The tile data of the poly mesh and the detail mesh, at the coordinates of the tile for
+    /// `build_tile` and (0, 0) otherwise, as `TileGrid::build_tile` gives it for the same tile.
+    /// None when no poly is walkable
+    pub fn tile_data(&self) -> Result<Option<TileData>> {
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_build_tile_data(
+                self.build.as_ptr(),
+                &mut data as *mut _,
+                &mut len as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::BuildError(err.msg().into_owned()));
+        }
+        Ok(TileData::from_raw(data, len as usize))
+    }
+}
+
+impl RecastQuery {
+    /// A query over the poly mesh and the detail mesh of a build over the whole geometry,
+    /// the same navmesh as `RecastQuery::build` without running the build again. The polys keep
+    /// their areas, flags and neighbours, unlike a `NavMeshData` of the triangles of the polys.
+    /// A tile build gives its `tile_data` to a tiled query instead
+    pub fn from_build_artifacts(artifacts: &BuildArtifacts) -> Result<RecastQuery> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let q = unsafe {
+            ptr::NonNull::new(sys::recastc_create_query_from_build(
+                artifacts.build.as_ptr(),
+                &mut err as *mut _,
+            ) as *mut c_void)
+        };
+
+        let q = q.ok_or_else(|| Error::BuildError(err.msg().into_owned()))?;
+        Ok(RecastQuery::from_ptr(q))
+    }
+}
+
+/// See `BuildArtifacts::heightfield`
+#[derive(Debug, Copy, Clone)]
+pub struct HeightfieldView<'a> {
+    bmin: Point,
+    bmax: Point,
+    width: i32,
+    height: i32,
+    columns: &'a [u32],
+    spans: &'a [sys::RecastSpan],
+}
+
+impl<'a> HeightfieldView<'a> {
+    pub fn bmin(&self) -> Point {
+        self.bmin
+    }
+
+    pub fn bmax(&self) -> Point {
+        self.bmax
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn span_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Spans of a column, bottom to top
+    pub fn column(&self, x: i32, z: i32) -> impl Iterator<Item = Span> + 'a {
+        let col = (x + z * self.width) as usize;
+        let (start, end) = (self.columns[col] as usize, self.columns[col + 1] as usize);
+
+        self.spans[start..end].iter().map(|s| Span {
+            smin: s.smin,
+            smax: s.smax,
+            area: area(s.area),
+        })
+    }
+
+    /// A copy of the spans, e.g. to edit them and build again with `RecastQuery::build_from_heightfield`
+    pub fn to_heightfield(&self) -> Heightfield {
+        let mut hf = Heightfield::new(self.bmin, self.width, self.height)
+            .expect("the size of a build is valid");
+        for z in 0..self.height {
+            for x in 0..self.width {
+                for span in self.column(x, z) {
+                    hf.add_span(x, z, span)
+                        .expect("spans of a build are in range");
+                }
+            }
+        }
+        hf
+    }
+}
+
+/// A span of open space above a walkable floor, see `rcCompactSpan`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompactSpan {
+    /// Floor height in cells
+    pub y: u16,
+    /// Open height above the floor in cells
+    pub h: u16,
+    /// 0 when the span is in no region
+    pub region: u16,
+    /// Distance to the region border, 0 without distance field (monotone and layer partitions)
+    pub dist: u16,
+    pub area: Option<u8>,
+    /// Index within the neighbour column of the connected span, toward -x, +z, +x and -z
+    pub neighbours: [Option<u8>; 4],
+}
+
+/// See `BuildArtifacts::compact_heightfield`
+#[derive(Debug, Copy, Clone)]
+pub struct CompactHeightfieldView<'a> {
+    bmin: Point,
+    width: i32,
+    height: i32,
+    border_size: i32,
+    max_regions: u16,
+    columns: &'a [u32],
+    spans: &'a [sys::RecastCompactSpan],
+}
+
+impl<'a> CompactHeightfieldView<'a> {
+    pub fn bmin(&self) -> Point {
+        self.bmin
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Cells of the tile border on each side, 0 for a whole build
+    pub fn border_size(&self) -> i32 {
+        self.border_size
+    }
+
+    /// Region ids are below this
+    pub fn max_regions(&self) -> u16 {
+        self.max_regions
+    }
+
+    pub fn span_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Spans of a column, bottom to top
+    pub fn column(&self, x: i32, z: i32) -> impl Iterator<Item = CompactSpan> + 'a {
+        let col = (x + z * self.width) as usize;
+        let (start, end) = (self.columns[col] as usize, self.columns[col + 1] as usize);
+
+        self.spans[start..end].iter().map(|s| {
+            let mut neighbours = [None; 4];
+            for (n, &con) in neighbours.iter_mut().zip(s.con.iter()) {
+                if con != sys::RECASTC_NOT_CONNECTED {
+                    *n = Some(con);
+                }
+            }
+
+            CompactSpan {
+                y: s.y,
+                h: s.h,
+                region: s.reg,
+                dist: s.dist,
+                area: area(s.area),
+                neighbours,
+            }
+        })
+    }
+}
+
+/// The outline of a region, vertices as x, y, z in cells from `BuildArtifacts::contour_origin`
+/// and the `rcContour` vertex flags
+#[derive(Debug, Copy, Clone)]
+pub struct Contour<'a> {
+    pub region: u16,
+    pub area: Option<u8>,
+    pub verts: &'a [[i32; 4]],
+    /// Before the simplification
+    pub raw_verts: &'a [[i32; 4]],
+}
+
+/// A poly of the poly mesh
+#[derive(Debug, Copy, Clone)]
+pub struct MeshPoly<'a> {
+    /// Indices in `PolyMeshView::verts`
+    pub verts: &'a [u16],
+    /// Per edge, the neighbour poly or `MESH_NULL_IDX`. With the high bit set,
+    /// the edge is on the tile border and the low bits give its side
+    pub neighbours: &'a [u16],
+    pub region: u16,
+    pub flags: PolyFlags,
+    pub area: u8,
+}
+
+/// See `BuildArtifacts::poly_mesh`
+#[derive(Debug, Copy, Clone)]
+pub struct PolyMeshView<'a> {
+    bmin: Point,
+    cell_size: f32,
+    cell_height: f32,
+    nvp: usize,
+    verts: &'a [[u16; 3]],
+    polys: &'a [u16],
+    regions: &'a [u16],
+    flags: &'a [u16],
+    areas: &'a [u8],
+}
+
+impl<'a> PolyMeshView<'a> {
+    /// Vertices as x, y, z in cells
+    pub fn verts(&self) -> &'a [[u16; 3]] {
+        self.verts
+    }
+
+    /// World position of a vertex
+    pub fn vert_pos(&self, i: usize) -> Point {
+        let v = self.verts[i];
+        (
+            self.bmin.x() + v[0] as f32 * self.cell_size,
+            self.bmin.y() + v[1] as f32 * self.cell_height,
+            self.bmin.z() + v[2] as f32 * self.cell_size,
+        )
+            .into()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Maximum vertices per poly
+    pub fn max_verts_per_poly(&self) -> usize {
+        self.nvp
+    }
+
+    pub fn poly(&self, i: usize) -> MeshPoly<'a> {
+        let p = &self.polys[i * self.nvp * 2..(i + 1) * self.nvp * 2];
+        let n = p[..self.nvp]
+            .iter()
+            .position(|&v| v == MESH_NULL_IDX)
+            .unwrap_or(self.nvp);
+
+        MeshPoly {
+            verts: &p[..n],
+            neighbours: &p[self.nvp..self.nvp + n],
+            region: self.regions[i],
+            flags: PolyFlags(self.flags[i]),
+            area: self.areas[i],
+        }
+    }
+
+    pub fn polys(&self) -> impl Iterator<Item = MeshPoly<'a>> + '_ {
+        (0..self.len()).map(move |i| self.poly(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::RecastQuery;
+
+    /// A 10x10 plane with a tall box in the middle
+    fn level() -> InputGeometry {
+        let mut geom = plane(10.0);
+        for &(x, z) in &[(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0)] {
+            geom.vertices.extend_from_slice(&[x, 3.0, z]);
+        }
+        geom.indices.extend_from_slice(&[4, 6, 5, 4, 7, 6]);
+        geom
+    }
+
+    #[test]
+    fn test_build_stages() {
+        let config = BuildConfig::default();
+        let artifacts = BuildArtifacts::build(&level(), &config).unwrap();
+        assert_eq!(artifacts.cell_size(), config.cell_size);
+
+        let hf = artifacts.heightfield();
+        assert!(hf.span_count() > 0);
+        let spans: Vec<_> = hf.column(hf.width() / 2, hf.height() / 2).collect();
+        assert_eq!(spans.len(), 2, "floor and box top");
+
+        let chf = artifacts.compact_heightfield();
+        assert_eq!(chf.border_size(), 0);
+        let span = chf
+            .column(1 + chf.width() / 10, chf.height() / 2)
+            .next()
+            .unwrap();
+        assert!(span.region > 0 && span.region < chf.max_regions());
+        assert!(span.neighbours.iter().all(|n| n.is_some()));
+
+        let contours = artifacts.contours();
+        assert!(!contours.is_empty());
+        assert!(contours.iter().all(|c| c.verts.len() >= 3));
+
+        let mesh = artifacts.poly_mesh();
+        assert!(!mesh.is_empty());
+        for poly in mesh.polys() {
+            assert!(poly.verts.len() >= 3 && poly.verts.len() <= mesh.max_verts_per_poly());
+            assert_eq!(poly.area, 0);
+            let p = mesh.vert_pos(poly.verts[0] as usize);
+            assert!(p.x() >= -0.01 && p.x() <= 10.01);
+        }
+    }
+
+    #[test]
+    fn test_heightfield_roundtrip() {
+        let config = BuildConfig::default();
+        let artifacts = BuildArtifacts::build(&level(), &config).unwrap();
+        let hf = artifacts.heightfield().to_heightfield();
+        assert_eq!(hf.span_count(), artifacts.heightfield().span_count());
+
+        let q = RecastQuery::build_from_heightfield(&hf, &Default::default(), &config).unwrap();
+        q.find_poly((1.0, 0.0, 1.0).into(), (0.2, 1.0, 0.2))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_tile_stages() {
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&level(), &config, 32).unwrap();
+        let artifacts = BuildArtifacts::build_tile(&level(), &config, &grid, 0, 0).unwrap();
+
+        let chf = artifacts.compact_heightfield();
+        assert!(chf.border_size() > 0);
+        assert_eq!(chf.width(), 32 + 2 * chf.border_size());
+        assert!(!artifacts.poly_mesh().is_empty());
+
+        assert!(RecastQuery::from_build_artifacts(&artifacts).is_err());
+        let tile = artifacts.tile_data().unwrap().unwrap();
+        assert_eq!(
+            tile.as_bytes(),
+            grid.build_tile(&level(), &config, 0, 0)
+                .unwrap()
+                .unwrap()
+                .as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_query_from_build() {
+        // Two strips meeting at x = 5, the second one in area 5
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 5.0, 0.0, 0.0, 5.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                10.0, 0.0, 0.0, 10.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 1, 5, 4, 1, 2, 5],
+            triangle_areas: vec![0, 0, 5, 5],
+            ..Default::default()
+        };
+        let config = BuildConfig::default();
+        let artifacts = BuildArtifacts::build(&geom, &config).unwrap();
+
+        let q = RecastQuery::from_build_artifacts(&artifacts).unwrap();
+        let built = RecastQuery::build(&geom, &config).unwrap();
+        assert_eq!(q.polys().unwrap(), built.polys().unwrap());
+        assert_eq!(q.polys().unwrap().len(), artifacts.poly_mesh().len());
+
+        let area = |x: f32| {
+            let (_, poly) = q.find_poly((x, 0.0, 5.0).into(), (0.2, 0.5, 0.2)).unwrap();
+            q.poly_area(poly).unwrap()
+        };
+        assert_eq!(area(2.0), 0);
+        assert_eq!(area(8.0), 5);
+
+        // A path ending short of the goal would mean unlinked polys
+        let path = q
+            .find_path(
+                (1.0, 0.0, 5.0).into(),
+                (9.0, 0.0, 5.0).into(),
+                (0.2, 0.5, 0.2),
+            )
+            .unwrap();
+        let end = path.last().unwrap();
+        assert!((end.x() - 9.0).abs() < 0.1, "{:?}", path);
+    }
+}
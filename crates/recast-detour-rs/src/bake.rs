@@ -0,0 +1,141 @@
+//! Reproducible bakes, the same geometry and config give the same tile data on every platform
+use crate::recast::SysInput;
+use crate::tiled::build_tile;
+use crate::{BuildConfig, InputGeometry, Result, TileData, TileGrid};
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+impl TileData {
+    /// FNV-1a hash of the data, e.g. for asset hashes of a bake.
+    ///
+    /// Only the data as built is reproducible, Detour writes the links of a tile into its data
+    /// when the tile is added, so the data read back from a navmesh depends on its neighbours
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(FNV_OFFSET, self.as_bytes())
+    }
+}
+
+impl TileGrid {
+    /// Build every tile with a walkable surface, in the order of `tiles`
+    pub fn build_tiles(
+        &self,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+    ) -> Result<Vec<TileData>> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_input = SysInput::new(geometry);
+        let input = sys_input.as_sys();
+        let sys_config = config.as_sys();
+
+        let mut tiles = vec![];
+        for (tx, ty) in self.tiles() {
+            tiles.extend(build_tile(&input, &sys_config, self, tx, ty)?);
+        }
+        Ok(tiles)
+    }
+}
+
+/// Hash of a whole bake, the tiles in the order they were built, e.g. by `TileGrid::build_tiles`
+pub fn bake_hash(tiles: &[TileData]) -> u64 {
+    tiles.iter().fold(FNV_OFFSET, |hash, tile| {
+        let hash = fnv1a(hash, &(tile.as_bytes().len() as u64).to_le_bytes());
+        fnv1a(hash, tile.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConvexVolume;
+
+    fn level() -> InputGeometry {
+        // A floor with a raised block in the middle
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 20.0, 0.0, 0.0, 20.0, //
+                8.0, 1.5, 8.0, 12.0, 1.5, 8.0, 12.0, 1.5, 12.0, 8.0, 1.5, 12.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            volumes: vec![ConvexVolume {
+                verts: vec![
+                    (2.0, 0.0, 2.0).into(),
+                    (6.0, 0.0, 2.0).into(),
+                    (6.0, 0.0, 6.0).into(),
+                    (2.0, 0.0, 6.0).into(),
+                ],
+                hmin: -1.0,
+                hmax: 1.0,
+                area: 2,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        // Reference values of FNV-1a 64
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_bake_hash_format() {
+        // The length of each tile, little endian u64, then its data
+        let tiles = [
+            TileData::from_bytes(b"tile a").unwrap(),
+            TileData::from_bytes(b"second tile").unwrap(),
+        ];
+        assert_eq!(bake_hash(&tiles), 0x7d22_63c5_c659_bd31);
+        assert_eq!(bake_hash(&[]), FNV_OFFSET);
+    }
+
+    /// `bake_hash` of `level()` baked with the default config in tiles of 32 cells, the same
+    /// on every platform. Recorded from a reference build, again when Recast or the tile
+    /// format changes
+    const LEVEL_BAKE_HASH: u64 = 0;
+
+    #[test]
+    #[ignore = "LEVEL_BAKE_HASH is to be recorded from a reference build"]
+    fn test_golden_bake_hash() {
+        let geom = level();
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 32).unwrap();
+
+        let hash = bake_hash(&grid.build_tiles(&geom, &config).unwrap());
+        assert_eq!(hash, LEVEL_BAKE_HASH, "bake hash 0x{:016x}", hash);
+    }
+
+    #[test]
+    fn test_reproducible_bake() {
+        let geom = level();
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 32).unwrap();
+
+        let a = grid.build_tiles(&geom, &config).unwrap();
+        let b = grid.build_tiles(&geom, &config).unwrap();
+        assert!(!a.is_empty());
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.as_bytes(), b.as_bytes());
+            assert_eq!(a.content_hash(), b.content_hash());
+        }
+        assert_eq!(bake_hash(&a), bake_hash(&b));
+
+        // Another config bakes other data
+        let other = grid
+            .build_tiles(&geom, &config.clone().agent(2.0, 1.0, 0.9, 45.0))
+            .unwrap();
+        assert_ne!(bake_hash(&a), bake_hash(&other));
+    }
+}
@@ -0,0 +1,304 @@
+//! Settings of an offline bake read from a config file, what the `recast-bake` binary takes
+use crate::{
+    BuildConfig, Error, InputGeometry, ObjOptions, Partition, RecastQuery, Result, UpAxis,
+};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+fn settings_error(msg: String) -> Error {
+    Error::BakeSettingsError(msg)
+}
+
+/// A build config with the tiling and the input options of a bake, needs the `bake-cli` feature.
+///
+/// The config file is a JSON object or a TOML file of `key = value` lines, with any of the
+/// number and bool fields of `BuildConfig` as keys, `partition` as `"watershed"`, `"monotone"`
+/// or `"layers"`, `tile_size` in cells for a tiled build, and `scale` and `up_axis` (`"y"` or
+/// `"z"`) for .obj input. The missing keys keep their default, unknown keys are an error
+#[derive(Debug, Clone, Default)]
+pub struct BakeSettings {
+    pub config: BuildConfig,
+    /// Tiles of `tile_size` cells, None for a single mesh
+    pub tile_size: Option<u32>,
+    pub obj: ObjOptions,
+}
+
+impl BakeSettings {
+    /// The settings of a .json or .toml file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<BakeSettings> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| settings_error(e.to_string()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => BakeSettings::from_json(&text),
+            Some("toml") => BakeSettings::from_toml(&text),
+            _ => Err(settings_error(format!(
+                "{} is not a .json or .toml file",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<BakeSettings> {
+        let json: Value = serde_json::from_str(text)
+            .map_err(|e| settings_error(format!("invalid json: {}", e)))?;
+        let object = json
+            .as_object()
+            .ok_or_else(|| settings_error("the settings are not a json object".into()))?;
+
+        let mut settings = BakeSettings::default();
+        for (key, value) in object {
+            settings.set(key, value)?;
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// The flat subset of TOML made of `key = value` lines and `#` comments, no tables
+    pub fn from_toml(text: &str) -> Result<BakeSettings> {
+        let mut settings = BakeSettings::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                // A string value has no '#', none of the keys takes one
+                Some(i) => &line[..i],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(settings_error(format!(
+                    "line {}: tables are not supported, put the keys at the top level",
+                    n + 1
+                )));
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => {
+                    return Err(settings_error(format!(
+                        "line {}: expected `key = value`",
+                        n + 1
+                    )))
+                }
+            };
+            let value = match value {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                v if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') => {
+                    Value::String(v[1..v.len() - 1].to_string())
+                }
+                v => v
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(Value::Number)
+                    .ok_or_else(|| {
+                        settings_error(format!("line {}: invalid value `{}`", n + 1, v))
+                    })?,
+            };
+            settings.set(key, &value)?;
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<()> {
+        let number = || {
+            value
+                .as_f64()
+                .ok_or_else(|| settings_error(format!("`{}` is not a number", key)))
+        };
+        let count = || match value.as_u64() {
+            Some(v) if v <= u32::MAX as u64 => Ok(v as u32),
+            // 8.0 in TOML read as a float
+            _ => match value.as_f64() {
+                Some(v) if v >= 0.0 && v.fract() == 0.0 && v <= u32::MAX as f64 => Ok(v as u32),
+                _ => Err(settings_error(format!(
+                    "`{}` is not a positive integer",
+                    key
+                ))),
+            },
+        };
+        let flag = || {
+            value
+                .as_bool()
+                .ok_or_else(|| settings_error(format!("`{}` is not a bool", key)))
+        };
+        let area = || match count()? {
+            a if a <= u8::MAX as u32 => Ok(a as u8),
+            a => Err(settings_error(format!("`{}` {} is not an area", key, a))),
+        };
+        let string = || {
+            value
+                .as_str()
+                .ok_or_else(|| settings_error(format!("`{}` is not a string", key)))
+        };
+
+        let c = &mut self.config;
+        match key {
+            "cell_size" => c.cell_size = number()? as f32,
+            "cell_height" => c.cell_height = number()? as f32,
+            "walkable_height" => c.walkable_height = number()? as f32,
+            "walkable_radius" => c.walkable_radius = number()? as f32,
+            "walkable_climb" => c.walkable_climb = number()? as f32,
+            "walkable_slope_angle" => c.walkable_slope_angle = number()? as f32,
+            "max_edge_len" => c.max_edge_len = number()? as f32,
+            "max_simplification_error" => c.max_simplification_error = number()? as f32,
+            "min_region_size" => c.min_region_size = count()?,
+            "merge_region_size" => c.merge_region_size = count()?,
+            "max_verts_per_poly" => c.max_verts_per_poly = count()?,
+            "detail_sample_dist" => c.detail_sample_dist = number()? as f32,
+            "detail_sample_max_error" => c.detail_sample_max_error = number()? as f32,
+            "partition" => {
+                c.partition = match string()? {
+                    "watershed" => Partition::Watershed,
+                    "monotone" => Partition::Monotone,
+                    "layers" => Partition::Layers,
+                    p => return Err(settings_error(format!("unknown partition `{}`", p))),
+                }
+            }
+            "filter_low_hanging_obstacles" => c.filter_low_hanging_obstacles = flag()?,
+            "filter_ledge_spans" => c.filter_ledge_spans = flag()?,
+            "filter_walkable_low_height_spans" => c.filter_walkable_low_height_spans = flag()?,
+            "shallow_water_depth" => c.shallow_water_depth = number()? as f32,
+            "shallow_water_area" => c.shallow_water_area = area()?,
+            "deep_water_area" => c.deep_water_area = area()?,
+            "tile_border_size" => c.tile_border_size = count()?,
+            "border_stitch_tolerance" => c.border_stitch_tolerance = number()? as f32,
+            "tile_size" => self.tile_size = Some(count()?),
+            "scale" => self.obj.scale = number()? as f32,
+            "up_axis" => {
+                self.obj.up_axis = match string()? {
+                    "y" => UpAxis::Y,
+                    "z" => UpAxis::Z,
+                    a => return Err(settings_error(format!("unknown up axis `{}`", a))),
+                }
+            }
+            _ => return Err(settings_error(format!("unknown key `{}`", key))),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.tile_size == Some(0) {
+            return Err(settings_error("tile_size must be positive".into()));
+        }
+        if !(self.obj.scale > 0.0 && self.obj.scale.is_finite()) {
+            return Err(settings_error("scale must be positive".into()));
+        }
+        self.config.validate()
+    }
+
+    /// The input geometry of a .obj, .gltf or .glb file
+    pub fn load_geometry<P: AsRef<Path>>(&self, path: P) -> Result<InputGeometry> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("obj") => InputGeometry::from_obj_file(path, &self.obj),
+            Some("gltf") | Some("glb") => InputGeometry::from_gltf_file(path),
+            _ => Err(settings_error(format!(
+                "{} is not a .obj, .gltf or .glb file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Build the navmesh of the geometry, saved with the build parameters as
+    /// `RecastQuery::serialize_with_config` does, for `RecastQuery::from_serialized_for`
+    pub fn bake(&self, geometry: &InputGeometry) -> Result<(RecastQuery, Vec<u8>)> {
+        let q = match self.tile_size {
+            Some(tile_size) => RecastQuery::build_tiled(geometry, &self.config, tile_size)?,
+            None => RecastQuery::build(geometry, &self.config)?,
+        };
+        let bytes = q.serialize_with_config(&self.config)?;
+        Ok((q, bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_json_and_toml() {
+        let json = BakeSettings::from_json(
+            r#"{"walkable_radius": 0.4, "min_region_size": 4, "partition": "monotone",
+                "filter_ledge_spans": false, "tile_size": 32, "up_axis": "z"}"#,
+        )
+        .unwrap();
+        let toml = BakeSettings::from_toml(
+            "# agent\nwalkable_radius = 0.4\nmin_region_size = 4\n\n\
+             partition = \"monotone\" # fastest\nfilter_ledge_spans = false\n\
+             tile_size = 32\nup_axis = \"z\"\n",
+        )
+        .unwrap();
+
+        for s in &[json, toml] {
+            assert_eq!(s.config.walkable_radius, 0.4);
+            assert_eq!(s.config.min_region_size, 4);
+            assert_eq!(s.config.partition, Partition::Monotone);
+            assert!(!s.config.filter_ledge_spans);
+            assert_eq!(s.config.cell_size, BuildConfig::default().cell_size);
+            assert_eq!(s.tile_size, Some(32));
+            assert_eq!(s.obj.up_axis, UpAxis::Z);
+        }
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let msg = |res: Result<BakeSettings>| match res {
+            Err(Error::BakeSettingsError(msg)) | Err(Error::BuildError(msg)) => msg,
+            res => panic!("{:?}", res),
+        };
+        assert_eq!(
+            msg(BakeSettings::from_json(r#"{"walkable_raduis": 0.4}"#)),
+            "unknown key `walkable_raduis`"
+        );
+        assert_eq!(
+            msg(BakeSettings::from_json(r#"{"min_region_size": 2.5}"#)),
+            "`min_region_size` is not a positive integer"
+        );
+        assert_eq!(
+            msg(BakeSettings::from_toml("[build]\ncell_size = 0.2")),
+            "line 1: tables are not supported, put the keys at the top level"
+        );
+        assert_eq!(
+            msg(BakeSettings::from_toml("cell_size 0.2")),
+            "line 1: expected `key = value`"
+        );
+        assert_eq!(
+            msg(BakeSettings::from_toml("partition = \"grid\"")),
+            "unknown partition `grid`"
+        );
+        assert_eq!(
+            msg(BakeSettings::from_toml("max_verts_per_poly = 8")),
+            "max_verts_per_poly must be between 3 and 6, got 8"
+        );
+    }
+
+    #[test]
+    fn test_bake() {
+        let settings = BakeSettings::from_toml("tile_size = 32").unwrap();
+        let (q, bytes) = settings.bake(&floor()).unwrap();
+        assert!(q.tile_coords().unwrap().len() > 1);
+
+        let loaded = RecastQuery::from_serialized_for(&bytes, &settings.config).unwrap();
+        assert_eq!(loaded.polys().unwrap(), q.polys().unwrap());
+        assert!(RecastQuery::from_serialized_for(&bytes, &BuildConfig::default()).is_ok());
+
+        let single = BakeSettings::default().bake(&floor()).unwrap().0;
+        assert_eq!(single.tile_coords().unwrap().len(), 1);
+        assert!(settings.load_geometry("level.fbx").is_err());
+    }
+}
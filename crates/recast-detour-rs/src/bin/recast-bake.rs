@@ -0,0 +1,45 @@
+//! Bake a navmesh offline: `recast-bake <level.obj|.gltf|.glb> <settings.json|.toml> <out.navmesh>`
+//!
+//! The output is the navmesh as `RecastQuery::serialize_with_config` saves it, for the game to
+//! load with `RecastQuery::from_serialized_for`. See `BakeSettings` for the keys of the settings
+use recast_detour_rs::{BakeSettings, Error};
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "usage: recast-bake <level.obj|.gltf|.glb> <settings.json|.toml> <out.navmesh>";
+
+fn bake(input: &str, settings: &str, output: &str) -> Result<String, Error> {
+    let settings = BakeSettings::open(settings)?;
+    let geometry = settings.load_geometry(input)?;
+    let (q, bytes) = settings.bake(&geometry)?;
+
+    fs::write(output, &bytes).map_err(|e| Error::SerializeError(format!("{}: {}", output, e)))?;
+    Ok(format!(
+        "{}: {} tiles, {} polys, {} bytes",
+        output,
+        q.tile_coords()?.len(),
+        q.polys()?.len(),
+        bytes.len()
+    ))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    if args.len() != 3 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    match bake(&args[0], &args[1], &args[2]) {
+        Ok(summary) => println!("{}", summary),
+        Err(e) => {
+            eprintln!("recast-bake: {}", e);
+            process::exit(1);
+        }
+    }
+}
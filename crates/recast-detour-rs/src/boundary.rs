@@ -0,0 +1,212 @@
+//! The walls around an agent from Detour's local boundary, for custom steering without a `Crowd`
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Most wall segments kept by a `LocalBoundary`, the nearest ones
+pub const MAX_BOUNDARY_SEGMENTS: usize = sys::RECASTC_MAX_BOUNDARY_SEGMENTS;
+
+/// A navmesh edge without neighbour or with a neighbour the filter excludes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WallSegment {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl WallSegment {
+    /// The point of the segment nearest to `pos` on the xz plane, the height is the segment's
+    pub fn closest_point(&self, pos: Point) -> Point {
+        let (a, b) = (self.start, self.end);
+        let (dx, dz) = (b.x() - a.x(), b.z() - a.z());
+        let len2 = dx * dx + dz * dz;
+        let t = if len2 > 0.0 {
+            (((pos.x() - a.x()) * dx + (pos.z() - a.z()) * dz) / len2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Point::new((a.x() + dx * t, a.y() + (b.y() - a.y()) * t, a.z() + dz * t))
+    }
+
+    /// Distance from `pos` to the segment on the xz plane
+    pub fn distance(&self, pos: Point) -> f32 {
+        let c = self.closest_point(pos);
+        ((pos.x() - c.x()).powi(2) + (pos.z() - c.z()).powi(2)).sqrt()
+    }
+}
+
+/// The wall segments near an agent, refreshed when it moved far enough.
+///
+/// Like the `Corridor` it does not keep the query, `update` takes it and uses its filter.
+/// The crowd updates it when the agent moved a quarter of its collision range, or when
+/// `is_valid` turns false after a navmesh change
+pub struct LocalBoundary {
+    boundary: ptr::NonNull<c_void>,
+}
+
+// The boundary only owns its segments, the navmesh is given to each call
+unsafe impl Send for LocalBoundary {}
+
+impl std::fmt::Debug for LocalBoundary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LocalBoundary")
+            .field("boundary", &self.boundary)
+            .finish()
+    }
+}
+
+impl Default for LocalBoundary {
+    fn default() -> LocalBoundary {
+        LocalBoundary::new()
+    }
+}
+
+impl LocalBoundary {
+    /// A boundary without segment
+    pub fn new() -> LocalBoundary {
+        let boundary = unsafe { sys::recastc_create_local_boundary() };
+        LocalBoundary {
+            boundary: ptr::NonNull::new(boundary as *mut c_void).expect("Out of memory"),
+        }
+    }
+
+    /// Collect the nearest walls within `range` of `pos`, on the polys reachable from `poly`
+    /// without leaving the range
+    pub fn update(&mut self, q: &RecastQuery, poly: PolyRef, pos: Point, range: f32) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+        let res = unsafe {
+            sys::recastc_local_boundary_update(
+                self.boundary.as_ptr(),
+                q.q.as_ptr(),
+                poly,
+                pos.0.as_ptr(),
+                range,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the polys of the segments still exist and pass the query filter
+    pub fn is_valid(&self, q: &RecastQuery) -> bool {
+        let mut valid = 0;
+        let mut err = sys::RecastNavError::zeros();
+        unsafe {
+            sys::recastc_local_boundary_is_valid(
+                self.boundary.as_ptr(),
+                q.q.as_ptr(),
+                &mut valid,
+                &mut err as *mut _,
+            )
+        };
+        valid != 0
+    }
+
+    /// The position of the last update
+    pub fn center(&self) -> Point {
+        self.read().0
+    }
+
+    /// The segments of the last update, at most `MAX_BOUNDARY_SEGMENTS`
+    pub fn segments(&self) -> Vec<WallSegment> {
+        self.read().1
+    }
+
+    /// Drop the segments, e.g. when the agent teleports
+    pub fn reset(&mut self) {
+        unsafe { sys::recastc_local_boundary_reset(self.boundary.as_ptr()) }
+    }
+
+    fn read(&self) -> (Point, Vec<WallSegment>) {
+        let mut center = [0.0; 3];
+        let mut segs = [0.0; MAX_BOUNDARY_SEGMENTS * 6];
+        let mut count = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        // Never more than MAX_BOUNDARY_SEGMENTS
+        unsafe {
+            sys::recastc_local_boundary_get_segments(
+                self.boundary.as_ptr(),
+                center.as_mut_ptr(),
+                segs.as_mut_ptr(),
+                MAX_BOUNDARY_SEGMENTS as i32,
+                &mut count,
+                &mut err as *mut _,
+            )
+        };
+
+        let segments = segs
+            .chunks(6)
+            .take(count as usize)
+            .map(|s| WallSegment {
+                start: (s[0], s[1], s[2]).into(),
+                end: (s[3], s[4], s[5]).into(),
+            })
+            .collect();
+        (Point(center), segments)
+    }
+}
+
+impl Drop for LocalBoundary {
+    fn drop(&mut self) {
+        unsafe { sys::recastc_free_local_boundary(self.boundary.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane_query;
+
+    #[test]
+    fn test_walls_near_the_edge() {
+        let q = plane_query();
+        let (pos, poly) = q
+            .find_poly((1.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        let mut boundary = LocalBoundary::new();
+
+        boundary.update(&q, poly, pos, 2.0).unwrap();
+        assert_eq!(boundary.center(), pos);
+        let segments = boundary.segments();
+        assert!(!segments.is_empty());
+        // The eroded edge along x = 0
+        let nearest = segments
+            .iter()
+            .map(|s| s.distance(pos))
+            .fold(f32::MAX, f32::min);
+        assert!(nearest > 0.1 && nearest < 1.0, "{}", nearest);
+        assert!(boundary.is_valid(&q));
+
+        // No wall within reach of the middle
+        let (mid, poly) = q
+            .find_poly((5.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        boundary.update(&q, poly, mid, 1.0).unwrap();
+        assert!(boundary.segments().is_empty());
+
+        boundary.reset();
+        assert!(boundary.update(&q, 0, mid, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let wall = WallSegment {
+            start: (0.0, 0.0, 0.0).into(),
+            end: (4.0, 0.0, 0.0).into(),
+        };
+        assert_eq!(
+            wall.closest_point((2.0, 0.0, 3.0).into()),
+            (2.0, 0.0, 0.0).into()
+        );
+        assert_eq!(
+            wall.closest_point((-2.0, 0.0, 1.0).into()),
+            (0.0, 0.0, 0.0).into()
+        );
+        assert!((wall.distance((5.0, 0.0, 0.0).into()) - 1.0).abs() < 1e-6);
+    }
+}
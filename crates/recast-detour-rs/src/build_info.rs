@@ -0,0 +1,80 @@
+//! Versions and compile time flags of the Detour build, e.g. embedded in serialized assets
+use std::ffi::CStr;
+
+/// What tile data and tile cache data depend on, see `build_info`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Version of the C api, same as `version`
+    pub recastc_version: String,
+    /// Commit of the vendored recastnavigation, "unknown" when not built from a git checkout
+    pub recastnavigation_commit: String,
+    pub navmesh_magic: i32,
+    /// Version of the Detour tile data
+    pub navmesh_version: i32,
+    pub tile_cache_magic: i32,
+    pub tile_cache_version: i32,
+    /// Width of the poly refs in the C library, 32 or 64
+    pub poly_ref_bits: u32,
+    /// Tile data are stored in the byte order of the machine building them
+    pub little_endian: bool,
+    pub virtual_query_filter: bool,
+}
+
+impl BuildInfo {
+    /// Whether tile data written by the `other` build load in this one.
+    /// The commit and the api version may differ
+    pub fn is_data_compatible(&self, other: &BuildInfo) -> bool {
+        self.navmesh_magic == other.navmesh_magic
+            && self.navmesh_version == other.navmesh_version
+            && self.tile_cache_magic == other.tile_cache_magic
+            && self.tile_cache_version == other.tile_cache_version
+            && self.poly_ref_bits == other.poly_ref_bits
+            && self.little_endian == other.little_endian
+    }
+}
+
+/// The versions and flags of the linked C library
+pub fn build_info() -> BuildInfo {
+    let mut info = sys::RecastBuildInfo::default();
+    unsafe { sys::recastc_build_info(&mut info as *mut _) };
+    let version = unsafe { CStr::from_ptr(sys::recastc_version()) };
+
+    BuildInfo {
+        recastc_version: version.to_string_lossy().into_owned(),
+        recastnavigation_commit: sys::RECASTNAVIGATION_COMMIT.to_string(),
+        navmesh_magic: info.navmesh_magic,
+        navmesh_version: info.navmesh_version,
+        tile_cache_magic: info.tile_cache_magic,
+        tile_cache_version: info.tile_cache_version,
+        poly_ref_bits: info.poly_ref_bits,
+        little_endian: info.little_endian != 0,
+        virtual_query_filter: info.virtual_query_filter != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.recastc_version, crate::version());
+        assert_eq!(info.navmesh_version, 7);
+        assert_eq!(info.poly_ref_bits, 32);
+        assert_eq!(info.little_endian, cfg!(target_endian = "little"));
+        assert!(info.virtual_query_filter);
+        assert!(!info.recastnavigation_commit.is_empty());
+
+        let other = BuildInfo {
+            recastnavigation_commit: "0000000".into(),
+            ..info.clone()
+        };
+        assert!(info.is_data_compatible(&other));
+        let other = BuildInfo {
+            poly_ref_bits: 64,
+            ..info.clone()
+        };
+        assert!(!info.is_data_compatible(&other));
+    }
+}
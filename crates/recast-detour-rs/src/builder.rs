@@ -0,0 +1,196 @@
+//! Validated construction of `NavMeshData`
+use crate::{Error, NavMeshData, OffMeshConnection, Result, UpAxis, SOFT_AREA_FIRST};
+
+impl NavMeshData {
+    pub fn builder() -> NavMeshDataBuilder {
+        NavMeshDataBuilder::default()
+    }
+
+    /// Check the lengths, the values and the index bounds of the data
+    pub fn validate(&self) -> Result<()> {
+        if self.vertices.is_empty() {
+            return Err(Error::BuildError("navmesh data has no vertices".into()));
+        }
+        if !self.vertices.len().is_multiple_of(3) {
+            return Err(Error::BuildError(format!(
+                "vertices length {} is not a multiple of 3",
+                self.vertices.len()
+            )));
+        }
+        if let Some(i) = self.vertices.iter().position(|v| !v.is_finite()) {
+            return Err(Error::BuildError(format!(
+                "coordinate {} of vertex {} is not finite",
+                i % 3,
+                i / 3
+            )));
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(Error::BuildError(format!(
+                "indices length {} is not a multiple of 3",
+                self.indices.len()
+            )));
+        }
+
+        let n_verts = self.vertices.len() / 3;
+        if let Some(t) = self.indices.iter().position(|&i| i as usize >= n_verts) {
+            return Err(Error::BuildError(format!(
+                "index {} of triangle {} out of bounds, only {} vertices",
+                self.indices[t],
+                t / 3,
+                n_verts
+            )));
+        }
+        let n_tris = self.indices.len() / 3;
+        if !self.triangle_areas.is_empty() && self.triangle_areas.len() != n_tris {
+            return Err(Error::BuildError(format!(
+                "{} triangle areas for {} triangles",
+                self.triangle_areas.len(),
+                n_tris
+            )));
+        }
+        if let Some(a) = self
+            .triangle_areas
+            .iter()
+            .find(|&&a| a >= SOFT_AREA_FIRST)
+        {
+            return Err(Error::BuildError(format!(
+                "triangle area {} is reserved for soft areas",
+                a
+            )));
+        }
+
+        for (name, v) in [
+            ("cell_size", self.cell_size),
+            ("cell_height", self.cell_height),
+        ] {
+            if !(v.is_finite() && v > 0.0) {
+                return Err(Error::BuildError(format!(
+                    "{} must be positive, got {}",
+                    name, v
+                )));
+            }
+        }
+        for (name, v) in [
+            ("walkable_height", self.walkable_height),
+            ("walkable_radius", self.walkable_radius),
+            ("walkable_climb", self.walkable_climb),
+        ] {
+            if !(v.is_finite() && v >= 0.0) {
+                return Err(Error::BuildError(format!(
+                    "{} must not be negative, got {}",
+                    name, v
+                )));
+            }
+        }
+
+        for (i, c) in self.off_mesh_connections.iter().enumerate() {
+            let finite = c
+                .start
+                .0
+                .iter()
+                .chain(c.end.0.iter())
+                .all(|v| v.is_finite());
+            if !finite || !(c.radius.is_finite() && c.radius > 0.0) {
+                return Err(Error::BuildError(format!(
+                    "off-mesh connection {} needs finite points and a positive radius",
+                    i
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder of `NavMeshData`, the data is validated by `build`
+#[derive(Debug, Clone, Default)]
+pub struct NavMeshDataBuilder {
+    data: NavMeshData,
+}
+
+impl NavMeshDataBuilder {
+    /// Vertices in world unit, 3 floats each
+    pub fn vertices(mut self, vertices: Vec<f32>) -> NavMeshDataBuilder {
+        self.data.vertices = vertices;
+        self
+    }
+
+    /// Triangle indices, 3 per triangle
+    pub fn indices(mut self, indices: Vec<u32>) -> NavMeshDataBuilder {
+        self.data.indices = indices;
+        self
+    }
+
+    /// Area id of each triangle, by default all are in area 0
+    pub fn triangle_areas(mut self, areas: Vec<u8>) -> NavMeshDataBuilder {
+        self.data.triangle_areas = areas;
+        self
+    }
+
+    /// Walkable height, radius and climb of the agents in world unit
+    pub fn walkable(mut self, height: f32, radius: f32, climb: f32) -> NavMeshDataBuilder {
+        self.data.walkable_height = height;
+        self.data.walkable_radius = radius;
+        self.data.walkable_climb = climb;
+        self
+    }
+
+    /// Cell size and cell height in world unit
+    pub fn cells(mut self, cell_size: f32, cell_height: f32) -> NavMeshDataBuilder {
+        self.data.cell_size = cell_size;
+        self.data.cell_height = cell_height;
+        self
+    }
+
+    pub fn off_mesh_connection(mut self, connection: OffMeshConnection) -> NavMeshDataBuilder {
+        self.data.off_mesh_connections.push(connection);
+        self
+    }
+
+    pub fn up_axis(mut self, up_axis: UpAxis) -> NavMeshDataBuilder {
+        self.data.up_axis = up_axis;
+        self
+    }
+
+    pub fn build(self) -> Result<NavMeshData> {
+        self.data.validate()?;
+        Ok(self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecastQuery;
+
+    fn quad() -> NavMeshDataBuilder {
+        NavMeshData::builder()
+            .vertices(vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ])
+            .indices(vec![0, 1, 2, 0, 2, 3])
+            .walkable(0.2, 0.2, 0.2)
+            .cells(0.1, 0.1)
+    }
+
+    #[test]
+    fn test_builder() {
+        let data = quad().build().unwrap();
+        assert_eq!(data.cell_size, 0.1);
+        assert!(RecastQuery::new_from_mesh(data).is_ok());
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let err = |b: NavMeshDataBuilder| b.build().unwrap_err().to_string();
+
+        assert!(err(quad().indices(vec![0, 1, 4])).contains("index 4 of triangle 0"));
+        assert!(err(quad().indices(vec![0, 1])).contains("multiple of 3"));
+        assert!(err(quad().triangle_areas(vec![1])).contains("1 triangle areas for 2"));
+        assert!(err(quad().triangle_areas(vec![1, SOFT_AREA_FIRST])).contains("reserved"));
+        assert!(err(quad().vertices(vec![0.0, f32::NAN, 0.0])).contains("vertex 0"));
+        assert!(err(quad().cells(0.0, 0.1)).contains("cell_size"));
+        assert!(err(quad().walkable(-1.0, 0.2, 0.2)).contains("walkable_height"));
+        assert!(err(NavMeshData::builder()).contains("no vertices"));
+    }
+}
@@ -0,0 +1,473 @@
+//! Baked navmeshes cached by the hash of their geometry and config, built only on a miss
+use crate::bake::{fnv1a, FNV_OFFSET};
+use crate::pack::{read_f32, read_u32};
+use crate::{
+    build_info, BuildConfig, Error, InputGeometry, NavMeshParams, RecastQuery, Result, TileData,
+    TileGrid,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+
+const CACHE_MAGIC: &[u8; 4] = b"RDNC";
+/// Version 1 had no checksum
+const CACHE_VERSION: u32 = 2;
+
+/// The key of a bake, a hash of everything the tiles depend on: the geometry, the config,
+/// the tile size and the Detour data format of the linked library
+pub fn bake_key(geometry: &InputGeometry, config: &BuildConfig, tile_size: u32) -> u64 {
+    let info = build_info();
+    let mut hash = fnv1a(FNV_OFFSET, info.recastc_version.as_bytes());
+    for v in &[
+        info.navmesh_magic,
+        info.navmesh_version,
+        info.poly_ref_bits as i32,
+    ] {
+        hash = fnv1a(hash, &v.to_le_bytes());
+    }
+    hash = fnv1a(hash, &tile_size.to_le_bytes());
+
+    for f in &geometry.vertices {
+        hash = fnv1a(hash, &f.to_bits().to_le_bytes());
+    }
+    hash = fnv1a(hash, &(geometry.indices.len() as u64).to_le_bytes());
+    for i in &geometry.indices {
+        hash = fnv1a(hash, &i.to_le_bytes());
+    }
+    hash = fnv1a(hash, &(geometry.triangle_areas.len() as u64).to_le_bytes());
+    hash = fnv1a(hash, &geometry.triangle_areas);
+
+    // The debug output of the small parts covers every field, a new field changes the key.
+    // Floats print exactly, the shortest text reading back the same value
+    let rest = format!(
+        "{:?}{:?}{:?}{:?}",
+        geometry.volumes, geometry.off_mesh_connections, geometry.water, config
+    );
+    fnv1a(hash, rest.as_bytes())
+}
+
+/// The tiles of a tiled bake with the layout of their navmesh
+#[derive(Debug, Clone)]
+pub struct BakedNavMesh {
+    key: u64,
+    params: NavMeshParams,
+    tiles: Vec<Vec<u8>>,
+}
+
+impl BakedNavMesh {
+    /// Build every tile of the geometry
+    pub fn bake(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<BakedNavMesh> {
+        let grid = TileGrid::for_geometry(geometry, config, tile_size)?;
+        let tiles = grid.build_tiles(geometry, config)?;
+
+        Ok(BakedNavMesh {
+            key: bake_key(geometry, config, tile_size),
+            params: grid.nav_mesh_params()?,
+            tiles: tiles.iter().map(|t| t.as_bytes().to_vec()).collect(),
+        })
+    }
+
+    /// The `bake_key` of the geometry and config baked
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn params(&self) -> &NavMeshParams {
+        &self.params
+    }
+
+    /// The data of the tiles with a walkable surface
+    pub fn tiles(&self) -> impl Iterator<Item = &[u8]> {
+        self.tiles.iter().map(|t| t.as_slice())
+    }
+
+    /// A new query over the tiles, each call gets its own navmesh
+    pub fn query(&self) -> Result<RecastQuery> {
+        let mut q = RecastQuery::create_tiled_with_params(&self.params)?;
+        for tile in &self.tiles {
+            q.add_tile(TileData::from_bytes(tile)?)?;
+        }
+        Ok(q)
+    }
+
+    /// Layout (little endian): magic, version, key, the `NavMeshParams`, the tile count, each
+    /// tile as u32 length + data, and last the u64 FNV-1a checksum of all the bytes before it
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut out = vec![];
+        out.extend_from_slice(CACHE_MAGIC);
+        out.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.key.to_le_bytes());
+
+        let p = &self.params;
+        for f in p.origin.iter().chain(&[p.tile_width, p.tile_height]) {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        out.extend_from_slice(&p.max_tiles.to_le_bytes());
+        out.extend_from_slice(&p.max_polys.to_le_bytes());
+
+        out.extend_from_slice(&(self.tiles.len() as u32).to_le_bytes());
+        for tile in &self.tiles {
+            out.extend_from_slice(&(tile.len() as u32).to_le_bytes());
+            out.extend_from_slice(tile);
+        }
+        let checksum = fnv1a(FNV_OFFSET, &out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        w.write_all(&out)
+    }
+
+    /// Fails for another version of the format, a wrong checksum or truncated data, the tiles
+    /// themselves are checked when they are added to a query
+    pub fn read<R: Read>(r: &mut R) -> io::Result<BakedNavMesh> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let r = &mut Checksummed {
+            inner: r,
+            hash: FNV_OFFSET,
+        };
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != CACHE_MAGIC || read_u32(r)? != CACHE_VERSION {
+            return Err(invalid("not a cached navmesh of this version"));
+        }
+        let mut key = [0; 8];
+        r.read_exact(&mut key)?;
+
+        let params = NavMeshParams {
+            origin: [read_f32(r)?, read_f32(r)?, read_f32(r)?],
+            tile_width: read_f32(r)?,
+            tile_height: read_f32(r)?,
+            max_tiles: read_u32(r)?,
+            max_polys: read_u32(r)?,
+        };
+
+        let n = read_u32(r)?;
+        let mut tiles = vec![];
+        for _ in 0..n {
+            // The buffer grows with the bytes read, a damaged length does not allocate it at once
+            let len = read_u32(r)? as u64;
+            let mut tile = vec![];
+            r.take(len).read_to_end(&mut tile)?;
+            if tile.len() as u64 != len {
+                return Err(invalid("truncated cached navmesh"));
+            }
+            tiles.push(tile);
+        }
+
+        let hash = r.hash;
+        let mut checksum = [0; 8];
+        r.inner.read_exact(&mut checksum)?;
+        if u64::from_le_bytes(checksum) != hash {
+            return Err(invalid("checksum mismatch, the cached navmesh is corrupt"));
+        }
+
+        Ok(BakedNavMesh {
+            key: u64::from_le_bytes(key),
+            params,
+            tiles,
+        })
+    }
+}
+
+/// The FNV-1a hash of the bytes read through it
+struct Checksummed<'a, R> {
+    inner: &'a mut R,
+    hash: u64,
+}
+
+impl<R: Read> Read for Checksummed<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hash = fnv1a(self.hash, &buf[..n]);
+        Ok(n)
+    }
+}
+
+type Slot = Arc<Mutex<Option<Arc<BakedNavMesh>>>>;
+
+/// Bakes shared by the threads of a game or a tool, keyed by `bake_key`.
+///
+/// A bake is built once per key even when several threads ask for it at the same time, the
+/// others wait for it. With a directory the bakes are also stored there as `<key>.navcache`
+/// and read back by the next run, a file which does not read back or whose tiles do not load
+/// is built again
+#[derive(Debug, Default)]
+pub struct NavMeshCache {
+    dir: Option<PathBuf>,
+    slots: Mutex<HashMap<u64, Slot>>,
+}
+
+impl NavMeshCache {
+    /// A cache kept in memory only
+    pub fn new() -> NavMeshCache {
+        NavMeshCache::default()
+    }
+
+    /// A cache kept in memory and in the directory, created when missing
+    pub fn with_dir<P: Into<PathBuf>>(dir: P) -> NavMeshCache {
+        NavMeshCache {
+            dir: Some(dir.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Where the bake of a key is stored, `None` without directory
+    pub fn path(&self, key: u64) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.navcache", key)))
+    }
+
+    /// The bake of the geometry and config, from memory, from the directory or built now.
+    /// Fails when the build fails or the bake cannot be stored
+    pub fn get_or_bake(
+        &self,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<Arc<BakedNavMesh>> {
+        let key = bake_key(geometry, config, tile_size);
+        let slot = self.slots().entry(key).or_default().clone();
+
+        // Only the threads asking for this key wait here. A thread which panicked while
+        // baking left the slot empty, the next one bakes again
+        let mut baked = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(baked) = &*baked {
+            return Ok(baked.clone());
+        }
+
+        let mesh = match self.load(key) {
+            Some(mesh) => mesh,
+            None => {
+                let mesh = BakedNavMesh::bake(geometry, config, tile_size)?;
+                self.store(&mesh)?;
+                mesh
+            }
+        };
+        let mesh = Arc::new(mesh);
+        *baked = Some(mesh.clone());
+        Ok(mesh)
+    }
+
+    /// `get_or_bake` then a new query over the tiles
+    pub fn query(
+        &self,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<RecastQuery> {
+        self.get_or_bake(geometry, config, tile_size)?.query()
+    }
+
+    /// Whether the bake of the key is in memory, false while it is being baked
+    pub fn contains(&self, key: u64) -> bool {
+        let slot = self.slots().get(&key).cloned();
+        slot.is_some_and(|slot| match slot.try_lock() {
+            Ok(baked) => baked.is_some(),
+            Err(TryLockError::Poisoned(baked)) => baked.into_inner().is_some(),
+            Err(TryLockError::WouldBlock) => false,
+        })
+    }
+
+    /// Drop the bakes kept in memory, the files stay
+    pub fn clear(&self) {
+        self.slots().clear();
+    }
+
+    fn slots(&self) -> MutexGuard<'_, HashMap<u64, Slot>> {
+        // The map is only changed by single calls which do not panic halfway
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn load(&self, key: u64) -> Option<BakedNavMesh> {
+        let file = fs::File::open(self.path(key)?).ok()?;
+        let mesh = BakedNavMesh::read(&mut io::BufReader::new(file)).ok()?;
+        // Detour checks the tiles as they are added
+        Some(mesh).filter(|m| m.key == key && m.query().is_ok())
+    }
+
+    fn store(&self, mesh: &BakedNavMesh) -> Result<()> {
+        let (dir, path) = match (&self.dir, self.path(mesh.key)) {
+            (Some(dir), Some(path)) => (dir, path),
+            _ => return Ok(()),
+        };
+        let cache_error = |e: io::Error| Error::BuildError(format!("{}: {}", path.display(), e));
+
+        fs::create_dir_all(dir).map_err(cache_error)?;
+        // Renamed once written, another process never reads half a file
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        let stored = fs::File::create(&tmp)
+            .and_then(|file| {
+                let mut file = io::BufWriter::new(file);
+                mesh.write(&mut file)?;
+                file.flush()
+            })
+            .and_then(|_| fs::rename(&tmp, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        stored.map_err(cache_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::Point;
+    use std::thread;
+
+    #[test]
+    fn test_bake_key() {
+        let config = BuildConfig::default();
+        let key = bake_key(&plane(20.0), &config, 32);
+        assert_eq!(key, bake_key(&plane(20.0), &config, 32));
+        assert_ne!(key, bake_key(&plane(20.0), &config, 64));
+        assert_ne!(
+            key,
+            bake_key(
+                &plane(20.0),
+                &BuildConfig::default()
+                    .area_flags(3, crate::PolyFlags::SWIM)
+                    .unwrap(),
+                32
+            )
+        );
+
+        let mut moved = plane(20.0);
+        moved.vertices[1] = 0.1;
+        assert_ne!(key, bake_key(&moved, &config, 32));
+    }
+
+    #[test]
+    fn test_baked_once_across_threads() {
+        let cache = Arc::new(NavMeshCache::new());
+        let geom = Arc::new(plane(20.0));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let (cache, geom) = (cache.clone(), geom.clone());
+                thread::spawn(move || {
+                    cache
+                        .get_or_bake(&geom, &BuildConfig::default(), 32)
+                        .unwrap()
+                })
+            })
+            .collect();
+        let meshes: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(meshes.iter().all(|m| Arc::ptr_eq(m, &meshes[0])));
+        assert!(cache.contains(meshes[0].key()));
+
+        let q = meshes[0].query().unwrap();
+        let pos: Point = (10.0, 0.0, 10.0).into();
+        assert!(q.find_poly(pos, (0.5, 1.0, 0.5)).is_ok());
+    }
+
+    #[test]
+    fn test_dir_cache() {
+        let dir = std::env::temp_dir().join(format!("recast-cache-{}", std::process::id()));
+        let config = BuildConfig::default();
+
+        let baked = NavMeshCache::with_dir(&dir)
+            .get_or_bake(&plane(20.0), &config, 32)
+            .unwrap();
+        let cache = NavMeshCache::with_dir(&dir);
+        assert!(cache.path(baked.key()).unwrap().exists());
+        assert!(!cache.contains(baked.key()));
+
+        // A new cache reads the file back
+        let loaded = cache.get_or_bake(&plane(20.0), &config, 32).unwrap();
+        assert_eq!(loaded.params(), baked.params());
+        assert!(loaded.tiles().eq(baked.tiles()));
+
+        // A broken file is baked again
+        fs::write(cache.path(baked.key()).unwrap(), b"RDNC").unwrap();
+        cache.clear();
+        let rebaked = cache.get_or_bake(&plane(20.0), &config, 32).unwrap();
+        assert!(rebaked.tiles().eq(baked.tiles()));
+
+        // So is a file with a flipped byte, or with tiles Detour does not load
+        let path = cache.path(baked.key()).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 1;
+        assert!(BakedNavMesh::read(&mut &bytes[..]).is_err());
+        fs::write(&path, &bytes).unwrap();
+        cache.clear();
+        let rebaked = cache.get_or_bake(&plane(20.0), &config, 32).unwrap();
+        assert!(rebaked.tiles().eq(baked.tiles()));
+
+        let broken = BakedNavMesh {
+            key: baked.key(),
+            params: *baked.params(),
+            tiles: vec![vec![0; 64]],
+        };
+        broken.write(&mut fs::File::create(&path).unwrap()).unwrap();
+        cache.clear();
+        let rebaked = cache.get_or_bake(&plane(20.0), &config, 32).unwrap();
+        assert!(rebaked.tiles().eq(baked.tiles()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bake_after_panic() {
+        let cache = Arc::new(NavMeshCache::new());
+        let config = BuildConfig::default();
+        let key = bake_key(&plane(20.0), &config, 32);
+        let slot = cache.slots().entry(key).or_default().clone();
+
+        // As if the bake had panicked, the slot mutex is poisoned
+        let poisoned = thread::spawn(move || {
+            let _baked = slot.lock().unwrap();
+            panic!("bake failed");
+        });
+        assert!(poisoned.join().is_err());
+        assert!(!cache.contains(key));
+
+        let baked = cache.get_or_bake(&plane(20.0), &config, 32).unwrap();
+        assert_eq!(baked.key(), key);
+        assert!(cache.contains(key));
+    }
+
+    #[test]
+    fn test_store_failure_removes_tmp() {
+        let dir = std::env::temp_dir().join(format!("recast-cache-tmp-{}", std::process::id()));
+        let config = BuildConfig::default();
+        let cache = NavMeshCache::with_dir(&dir);
+        let path = cache.path(bake_key(&plane(20.0), &config, 32)).unwrap();
+
+        // The file can not be renamed over a directory
+        fs::create_dir_all(&path).unwrap();
+        assert!(cache.get_or_bake(&plane(20.0), &config, 32).is_err());
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(names, vec![path]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let baked = BakedNavMesh::bake(&plane(20.0), &BuildConfig::default(), 32).unwrap();
+        let mut bytes = vec![];
+        baked.write(&mut bytes).unwrap();
+        let read = BakedNavMesh::read(&mut &bytes[..]).unwrap();
+        assert!(read.tiles().eq(baked.tiles()));
+        assert!(BakedNavMesh::read(&mut &bytes[..bytes.len() - 1]).is_err());
+
+        // A tile length past the end of the data, read without allocating it
+        let header = 4 + 4 + 8 + 5 * 4 + 2 * 4;
+        let mut huge = bytes[..header].to_vec();
+        huge.extend_from_slice(&1u32.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(b"tile");
+        let err = BakedNavMesh::read(&mut &huge[..]).unwrap_err();
+        assert_eq!(err.to_string(), "truncated cached navmesh");
+    }
+}
@@ -0,0 +1,320 @@
+//! Triangle navmeshes too large for the 16-bit vertex indices of Detour, split into tiles
+use crate::off_mesh;
+use crate::tiled::TileData;
+use crate::{Error, NavMeshData, RecastQuery, Result, TileGrid, MAX_MESH_VERTS};
+use std::collections::HashMap;
+use std::ptr;
+
+/// Smallest tile width in cells before giving up on fitting the triangles
+const MIN_TILE_SIZE: u32 = 8;
+
+/// The triangles of one tile, vertices in cells from the tile corner
+#[derive(Default)]
+struct TileMesh {
+    verts: Vec<u16>,
+    tris: Vec<u16>,
+    /// Area of each triangle of `tris`
+    areas: Vec<u8>,
+    lookup: HashMap<[u16; 3], u16>,
+}
+
+impl TileMesh {
+    /// None once the tile is out of vertex indices
+    fn vert(&mut self, v: [u16; 3]) -> Option<u16> {
+        if let Some(&i) = self.lookup.get(&v) {
+            return Some(i);
+        }
+        let i = self.verts.len() / 3;
+        if i >= MAX_MESH_VERTS {
+            return None;
+        }
+
+        self.verts.extend_from_slice(&v);
+        self.lookup.insert(v, i as u16);
+        Some(i as u16)
+    }
+}
+
+/// Keep the part of the polygon on one side of the plane `v[axis] == value`.
+///
+/// The intersection of an edge is computed from its lower end, so the polygons sharing the edge
+/// (in this tile and the neighbour one) get the same vertex.
+fn clip(poly: &[[f64; 3]], axis: usize, value: f64, keep_above: bool) -> Vec<[f64; 3]> {
+    let inside = |v: &[f64; 3]| {
+        if keep_above {
+            v[axis] >= value
+        } else {
+            v[axis] <= value
+        }
+    };
+
+    let mut out = Vec::with_capacity(poly.len() + 2);
+    for (i, a) in poly.iter().enumerate() {
+        let b = &poly[(i + 1) % poly.len()];
+        if inside(a) {
+            out.push(*a);
+        }
+        if inside(a) != inside(b) {
+            let (p, q) = if a < b { (a, b) } else { (b, a) };
+            let t = (value - p[axis]) / (q[axis] - p[axis]);
+            let mut v = [0.0; 3];
+            for k in 0..3 {
+                v[k] = p[k] + (q[k] - p[k]) * t;
+            }
+            v[axis] = value;
+            out.push(v);
+        }
+    }
+    out
+}
+
+fn area_2d(poly: &[[f64; 3]]) -> f64 {
+    let mut area = 0.0;
+    for (i, a) in poly.iter().enumerate() {
+        let b = &poly[(i + 1) % poly.len()];
+        area += a[0] * b[2] - b[0] * a[2];
+    }
+    area.abs() * 0.5
+}
+
+/// Clip the triangles to the tiles of the grid, None when a tile has too many vertices or polys
+fn split(
+    verts: &[u32],
+    indices: &[u32],
+    areas: &[u8],
+    grid: &TileGrid,
+    max_polys: usize,
+) -> Option<HashMap<(i32, i32), TileMesh>> {
+    let ts = grid.tile_size as f64;
+    let mut tiles: HashMap<(i32, i32), TileMesh> = HashMap::new();
+
+    for (t, tri) in indices.chunks(3).enumerate() {
+        let area = areas.get(t).copied().unwrap_or(0);
+        let poly: Vec<[f64; 3]> = tri
+            .iter()
+            .map(|&i| {
+                let v = &verts[i as usize * 3..i as usize * 3 + 3];
+                [v[0] as f64, v[1] as f64, v[2] as f64]
+            })
+            .collect();
+        if area_2d(&poly) <= 0.0 {
+            continue;
+        }
+
+        let tile_range = |axis: usize, n: i32| {
+            let min = poly.iter().map(|v| v[axis]).fold(f64::MAX, f64::min);
+            let max = poly.iter().map(|v| v[axis]).fold(f64::MIN, f64::max);
+            let clamp = |t: f64| (t as i32).max(0).min(n - 1);
+            (clamp((min / ts).floor()), clamp((max / ts).floor()))
+        };
+        let (tx0, tx1) = tile_range(0, grid.cols);
+        let (ty0, ty1) = tile_range(2, grid.rows);
+
+        for ty in ty0..=ty1 {
+            for tx in tx0..=tx1 {
+                let (x0, z0) = (tx as f64 * ts, ty as f64 * ts);
+                let mut part = poly.clone();
+                // The last row and column keep what is beyond the grid bounds
+                if tx > 0 {
+                    part = clip(&part, 0, x0, true);
+                }
+                if tx < grid.cols - 1 {
+                    part = clip(&part, 0, x0 + ts, false);
+                }
+                if ty > 0 {
+                    part = clip(&part, 2, z0, true);
+                }
+                if ty < grid.rows - 1 {
+                    part = clip(&part, 2, z0 + ts, false);
+                }
+                if part.len() < 3 || area_2d(&part) < 1e-6 {
+                    continue;
+                }
+
+                let tile = tiles.entry((tx, ty)).or_default();
+                let mut ids = Vec::with_capacity(part.len());
+                for v in &part {
+                    let local = [
+                        (v[0] - x0).round().max(0.0).min(ts) as u16,
+                        v[1].round() as u16,
+                        (v[2] - z0).round().max(0.0).min(ts) as u16,
+                    ];
+                    ids.push(tile.vert(local)?);
+                }
+
+                // The clipped triangle is convex, a fan covers it
+                for i in 1..ids.len() - 1 {
+                    let t = [ids[0], ids[i], ids[i + 1]];
+                    if t[0] != t[1] && t[1] != t[2] && t[0] != t[2] {
+                        tile.tris.extend_from_slice(&t);
+                        tile.areas.push(area);
+                    }
+                }
+                if tile.tris.len() / 3 > max_polys {
+                    return None;
+                }
+            }
+        }
+    }
+
+    Some(tiles)
+}
+
+/// Build a tiled navmesh from the welded mesh, vertices in cells from `bmin`.
+/// The tiles are halved until every tile fits the 16-bit indices
+pub(crate) fn build_chunked(
+    data: &NavMeshData,
+    bmin: [f32; 3],
+    bmax: [f32; 3],
+    verts: &[u32],
+    indices: &[u32],
+) -> Result<RecastQuery> {
+    if verts.iter().skip(1).step_by(3).any(|&y| y > 0xffff) {
+        return Err(Error::CreateQueryError(
+            "mesh height exceeds 65535 cells, increase the cell size".into(),
+        ));
+    }
+
+    let max_x = verts.iter().step_by(3).copied().max().unwrap_or(0);
+    let max_z = verts.iter().skip(2).step_by(3).copied().max().unwrap_or(0);
+    let mut tile_size = (max_x.max(max_z) + 1).min(0xffff);
+
+    let (grid, tiles) = loop {
+        let mut grid = TileGrid::new(bmin, bmax, data.cell_size, tile_size);
+        // The rounding of the grid size may leave the last cells out
+        grid.cols = grid.cols.max(max_x.div_ceil(tile_size).max(1) as i32);
+        grid.rows = grid.rows.max(max_z.div_ceil(tile_size).max(1) as i32);
+
+        let params = grid.mesh_params(1)?;
+        if let Some(tiles) = split(verts, indices, &data.triangle_areas, &grid, params.max_polys as usize) {
+            break (grid, tiles);
+        }
+
+        tile_size /= 2;
+        if tile_size < MIN_TILE_SIZE {
+            return Err(Error::CreateQueryError(
+                "mesh too dense to split into tiles".into(),
+            ));
+        }
+    };
+
+    let mut q = RecastQuery::create_tiled(&grid)?;
+    let off_mesh = off_mesh::sys_connections(&data.off_mesh_connections);
+    let size = grid.tile_world_size();
+
+    for ((tx, ty), tile) in tiles {
+        if tile.tris.is_empty() {
+            continue;
+        }
+
+        let tile_min = [
+            bmin[0] + tx as f32 * size,
+            bmin[1],
+            bmin[2] + ty as f32 * size,
+        ];
+        let sys_data = sys::RecastNavMeshData {
+            verts: tile.verts.as_ptr(),
+            vert_count: (tile.verts.len() / 3) as u32,
+            indices: tile.tris.as_ptr(),
+            triangles_count: (tile.tris.len() / 3) as u32,
+            bmin: tile_min,
+            bmax: [tile_min[0] + size, bmax[1], tile_min[2] + size],
+            walkable_height: data.walkable_height,
+            walkable_radius: data.walkable_radius,
+            walkable_climb: data.walkable_climb,
+            cell_size: data.cell_size,
+            cell_height: data.cell_height,
+            off_mesh_connections: off_mesh.as_ptr(),
+            off_mesh_connection_count: off_mesh.len() as u32,
+            areas: tile.areas.as_ptr(),
+        };
+        let input = sys::RecastMeshTileInput {
+            tile_size: grid.tile_size as i32,
+            tx,
+            ty,
+        };
+
+        let mut tile_data = ptr::null_mut();
+        let mut len = 0;
+        let mut err = sys::RecastNavError::zeros();
+        let res = unsafe {
+            sys::recastc_create_mesh_tile(
+                &sys_data as *const _,
+                &input as *const _,
+                &mut tile_data as *mut _,
+                &mut len as *mut _,
+                &mut err as *mut _,
+            )
+        };
+        if res == 0 {
+            return Err(Error::CreateQueryError(format!(
+                "tile ({}, {}): {}",
+                tx,
+                ty,
+                err.msg()
+            )));
+        }
+
+        if let Some(tile) = TileData::from_raw(tile_data, len as usize) {
+            q.add_tile(tile)?;
+        }
+    }
+
+    Ok(q)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_shares_edge_vertices() {
+        let a = [[0.0, 0.0, 0.0], [10.0, 3.0, 0.0], [10.0, 0.0, 10.0]];
+        let b = [[0.0, 0.0, 0.0], [10.0, 0.0, 10.0], [0.0, 0.0, 10.0]];
+
+        let left_a = clip(&a, 0, 4.0, false);
+        let right_b = clip(&b, 0, 4.0, true);
+        assert!(left_a.iter().all(|v| v[0] <= 4.0));
+        assert!(right_b.iter().all(|v| v[0] >= 4.0));
+
+        // The diagonal crosses x = 4 at the same point for both triangles
+        let on_diagonal = |v: &&[f64; 3]| v[0] == 4.0 && v[2] == 4.0;
+        assert_eq!(left_a.iter().filter(on_diagonal).count(), 1);
+        assert_eq!(right_b.iter().filter(on_diagonal).count(), 1);
+        assert!((area_2d(&left_a) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_large_mesh_is_tiled() {
+        // A 300x300 grid of quads, more vertices than a single mesh allows
+        let n = 300;
+        let mut data = NavMeshData {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            ..crate::test_util::simple_mesh()
+        };
+        for z in 0..=n {
+            for x in 0..=n {
+                data.vertices
+                    .extend_from_slice(&[x as f32 * 0.5, 0.0, z as f32 * 0.5]);
+            }
+        }
+        for z in 0..n {
+            for x in 0..n {
+                let i = z * (n + 1) + x;
+                let j = i + n + 1;
+                data.indices
+                    .extend_from_slice(&[i, j, j + 1, i, j + 1, i + 1]);
+            }
+        }
+        assert!(data.vertices.len() / 3 > MAX_MESH_VERTS);
+
+        let q = RecastQuery::new_from_mesh(data).unwrap();
+        let (start, end) = ((1.0, 0.0, 1.0).into(), (149.0, 0.0, 148.0).into());
+        let path = q.find_path(start, end, (0.2, 0.2, 0.2)).unwrap();
+
+        // Straight across the tile borders
+        assert_eq!(path.len(), 2, "{:?}", path);
+        assert!((path[1].x() - 149.0).abs() < 0.1 && (path[1].z() - 148.0).abs() < 0.1);
+    }
+}
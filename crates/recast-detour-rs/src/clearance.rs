@@ -0,0 +1,144 @@
+//! Vertical clearance above the navmesh, so tall agents can avoid low tunnels on a mesh built for the smallest one
+use crate::{BuildArtifacts, BuildConfig, InputGeometry, Point, RecastQuery, Result};
+
+/// The open height above the walkable floors of a build, kept from its compact heightfield.
+///
+/// Only the walkable spans are kept, their clearance is at least the walkable height of the build.
+/// Recast stores at most 255 cells of open height, higher ceilings saturate there
+#[derive(Debug, Clone)]
+pub struct ClearanceMap {
+    bmin: Point,
+    cell_size: f32,
+    cell_height: f32,
+    width: i32,
+    height: i32,
+    /// Start of the spans of each column, with the end of the last column
+    columns: Vec<u32>,
+    /// (floor, open height) in cells
+    spans: Vec<(u16, u16)>,
+}
+
+impl ClearanceMap {
+    pub fn from_artifacts(artifacts: &BuildArtifacts) -> ClearanceMap {
+        let chf = artifacts.compact_heightfield();
+        let mut columns = Vec::with_capacity((chf.width() * chf.height()) as usize + 1);
+        let mut spans = vec![];
+
+        for z in 0..chf.height() {
+            for x in 0..chf.width() {
+                columns.push(spans.len() as u32);
+                spans.extend(
+                    chf.column(x, z)
+                        .filter(|s| s.area.is_some())
+                        .map(|s| (s.y, s.h)),
+                );
+            }
+        }
+        columns.push(spans.len() as u32);
+
+        ClearanceMap {
+            bmin: chf.bmin(),
+            cell_size: artifacts.cell_size(),
+            cell_height: artifacts.cell_height(),
+            width: chf.width(),
+            height: chf.height(),
+            columns,
+            spans,
+        }
+    }
+
+    /// The open height in world unit above the walkable floor nearest to `pos` in its column,
+    /// `None` outside of the build or without walkable floor
+    pub fn clearance(&self, pos: Point) -> Option<f32> {
+        let x = ((pos.x() - self.bmin.x()) / self.cell_size).floor() as i32;
+        let z = ((pos.z() - self.bmin.z()) / self.cell_size).floor() as i32;
+        if x < 0 || z < 0 || x >= self.width || z >= self.height {
+            return None;
+        }
+
+        let col = (x + z * self.width) as usize;
+        let (start, end) = (self.columns[col] as usize, self.columns[col + 1] as usize);
+        self.spans[start..end]
+            .iter()
+            .map(|&(y, h)| {
+                let floor = self.bmin.y() + y as f32 * self.cell_height;
+                ((floor - pos.y()).abs(), h as f32 * self.cell_height)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, h)| h)
+    }
+
+    /// The lowest clearance along a path sampled every cell, e.g. to tell if a tall agent fits
+    /// through the path found for the small ones. `None` when a sample has no walkable floor
+    pub fn min_clearance(&self, path: &[Point]) -> Option<f32> {
+        let mut min = self.clearance(*path.first()?)?;
+        for w in path.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let len = ((b.x() - a.x()).powi(2) + (b.z() - a.z()).powi(2)).sqrt();
+            let n = (len / self.cell_size).ceil().max(1.0) as usize;
+            for i in 1..=n {
+                let t = i as f32 / n as f32;
+                let p = Point::new((
+                    a.x() + (b.x() - a.x()) * t,
+                    a.y() + (b.y() - a.y()) * t,
+                    a.z() + (b.z() - a.z()) * t,
+                ));
+                min = min.min(self.clearance(p)?);
+            }
+        }
+        Some(min)
+    }
+}
+
+impl RecastQuery {
+    /// `RecastQuery::build` keeping the clearance of the walkable floors, from the same build
+    pub fn build_with_clearance(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+    ) -> Result<(RecastQuery, ClearanceMap)> {
+        let artifacts = BuildArtifacts::build(geometry, config)?;
+        let q = RecastQuery::from_build_artifacts(&artifacts)?;
+        Ok((q, ClearanceMap::from_artifacts(&artifacts)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tunnel() -> InputGeometry {
+        // A floor with a roof at 2.6 over x < 5, facing down so it is not walkable
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                0.0, 2.6, 0.0, 5.0, 2.6, 0.0, 5.0, 2.6, 10.0, 0.0, 2.6, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 5, 6, 4, 6, 7],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clearance() {
+        let (q, map) =
+            RecastQuery::build_with_clearance(&tunnel(), &BuildConfig::default()).unwrap();
+
+        let (inside, _) = q
+            .find_poly((2.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        let low = map.clearance(inside).unwrap();
+        assert!((low - 2.6).abs() < 0.3, "{}", low);
+
+        let (outside, _) = q
+            .find_poly((8.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        assert!(map.clearance(outside).unwrap() > 10.0);
+        assert!(map.clearance((50.0, 0.0, 50.0).into()).is_none());
+        assert!(map.clearance((2.0, f32::NAN, 5.0).into()).is_some());
+
+        // Walking into the tunnel
+        let path = q.find_path(outside, inside, (0.5, 1.0, 0.5)).unwrap();
+        let min = map.min_clearance(&path).unwrap();
+        assert!((min - low).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,243 @@
+/// Time of the simulation driving the agents, e.g. slow motion and fast forward in strategy games.
+///
+/// Scaled time is split into equal steps of at most `max_step`, so fast forward does not make
+/// steering and avoidance overshoot with one large step
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimClock {
+    scale: f32,
+    paused: bool,
+    max_step: f32,
+    max_steps: u32,
+    time: f64,
+}
+
+impl Default for SimClock {
+    /// Real time, steps of at most 1/30 s and 8 steps per update
+    fn default() -> SimClock {
+        SimClock {
+            scale: 1.0,
+            paused: false,
+            max_step: 1.0 / 30.0,
+            max_steps: 8,
+            time: 0.0,
+        }
+    }
+}
+
+impl SimClock {
+    pub fn new() -> SimClock {
+        SimClock::default()
+    }
+
+    /// The longest step in simulated seconds
+    pub fn with_max_step(mut self, max_step: f32) -> SimClock {
+        self.max_step = max_step.max(f32::EPSILON);
+        self
+    }
+
+    /// The most steps of an update, the simulated time beyond is dropped so a long frame
+    /// at a large scale does not stall the next ones
+    pub fn with_max_steps(mut self, max_steps: u32) -> SimClock {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Simulated seconds per real second, negative scales are 0
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Simulated seconds since the clock was created
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The simulation steps of a frame of `real_dt` seconds, none while paused
+    pub fn steps(&mut self, real_dt: f32) -> SimSteps {
+        let total = if self.paused {
+            0.0
+        } else {
+            real_dt.max(0.0) * self.scale
+        };
+        if total <= 0.0 {
+            return SimSteps { dt: 0.0, count: 0 };
+        }
+
+        // A frame of exactly n steps must not round up to n + 1
+        let count = (total / self.max_step - 1e-4).ceil().max(1.0);
+        let (dt, count) = if count > self.max_steps as f32 {
+            (self.max_step, self.max_steps)
+        } else {
+            (total / count, count as u32)
+        };
+
+        self.time += dt as f64 * count as f64;
+        SimSteps { dt, count }
+    }
+}
+
+/// The equal steps of a `SimClock` update, in simulated seconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimSteps {
+    dt: f32,
+    count: u32,
+}
+
+impl SimSteps {
+    /// Simulated seconds of all the steps
+    pub fn total(&self) -> f32 {
+        self.dt * self.count as f32
+    }
+}
+
+impl Iterator for SimSteps {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.count == 0 {
+            return None;
+        }
+        self.count -= 1;
+        Some(self.dt)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.count as usize, Some(self.count as usize))
+    }
+}
+
+impl ExactSizeIterator for SimSteps {}
+
+/// Steps of a fixed length fed with real time, for lockstep games where every peer must run the
+/// same steps. The time short of a step carries over to the next frames
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FixedTimestep {
+    dt: f32,
+    max_steps: u32,
+    accumulator: f64,
+    ticks: u64,
+}
+
+impl FixedTimestep {
+    /// Steps of `dt` seconds, at most 8 per frame
+    pub fn new(dt: f32) -> FixedTimestep {
+        FixedTimestep {
+            dt: dt.max(f32::EPSILON),
+            max_steps: 8,
+            accumulator: 0.0,
+            ticks: 0,
+        }
+    }
+
+    /// The most steps of a frame, the time beyond is dropped so a hitch does not make the next
+    /// frames fall further behind
+    pub fn with_max_steps(mut self, max_steps: u32) -> FixedTimestep {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Steps since the timestep was created
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// The number of steps to run for a frame of `real_dt` seconds
+    pub fn advance(&mut self, real_dt: f32) -> u32 {
+        self.accumulator += real_dt.max(0.0) as f64;
+        let dt = self.dt as f64;
+        // A frame of exactly n steps must not round down to n - 1
+        let mut steps = (self.accumulator / dt + 1e-4).floor() as u64;
+        if steps > self.max_steps as u64 {
+            // Keeps the time into the next step
+            steps = self.max_steps as u64;
+            self.accumulator = dt * steps as f64 + self.accumulator % dt;
+        }
+        self.accumulator = (self.accumulator - dt * steps as f64).max(0.0);
+        self.ticks += steps;
+        steps as u32
+    }
+
+    /// How far the time is into the next step, from 0 to 1, to draw between the last two steps
+    pub fn alpha(&self) -> f32 {
+        ((self.accumulator / self.dt as f64) as f32).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_and_pause() {
+        let mut clock = SimClock::new().with_max_step(0.1);
+        let steps: Vec<_> = clock.steps(0.05).collect();
+        assert_eq!(steps, vec![0.05]);
+
+        // Fast forward splits the frame
+        clock.set_scale(4.0);
+        let steps = clock.steps(0.05);
+        assert_eq!(steps.len(), 2);
+        assert!((steps.total() - 0.2).abs() < 1e-6);
+
+        clock.pause();
+        assert_eq!(clock.steps(0.05).count(), 0);
+        clock.resume();
+        clock.set_scale(0.5);
+        assert!((clock.steps(0.05).total() - 0.025).abs() < 1e-6);
+        assert!((clock.time() - 0.275).abs() < 1e-6);
+
+        clock.set_scale(-1.0);
+        assert_eq!(clock.scale(), 0.0);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut clock = SimClock::new().with_max_step(0.1).with_max_steps(3);
+        clock.set_scale(10.0);
+        // A one second hitch only simulates the 3 longest steps
+        let steps = clock.steps(1.0);
+        assert_eq!(steps.len(), 3);
+        assert!((steps.total() - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fixed_timestep() {
+        let mut step = FixedTimestep::new(0.1).with_max_steps(4);
+        assert_eq!(step.advance(0.05), 0);
+        assert!((step.alpha() - 0.5).abs() < 1e-5);
+        // The half step carries over
+        assert_eq!(step.advance(0.05), 1);
+        assert!(step.alpha() < 1e-5);
+        assert_eq!(step.advance(0.25), 2);
+        assert!((step.alpha() - 0.5).abs() < 1e-5);
+
+        // Exactly 3 steps
+        let mut other = FixedTimestep::new(0.1);
+        assert_eq!(other.advance(0.3), 3);
+
+        // A hitch runs the most steps and drops the rest
+        assert_eq!(step.advance(2.0), 4);
+        assert_eq!(step.advance(0.0), 0);
+        assert_eq!(step.ticks(), 7);
+    }
+}
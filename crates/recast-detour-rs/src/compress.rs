@@ -0,0 +1,105 @@
+//! Saved navmeshes compressed with zstd, for patches of baked tiled meshes
+use crate::{Error, RecastQuery, Result};
+use std::io::{self, Read, Write};
+
+/// The level of `compress_serialized` when in doubt, the zstd default
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+fn compress_error(err: io::Error) -> Error {
+    Error::SerializeError(err.to_string())
+}
+
+/// Compress a navmesh saved by `RecastQuery::serialize` or `serialize_with_config` into `w`,
+/// needs the `zstd` feature. Levels go from 1, fastest, to 22, smallest
+pub fn compress_serialized<W: Write>(bytes: &[u8], w: W, level: i32) -> Result<W> {
+    let mut encoder = zstd::stream::Encoder::new(w, level).map_err(compress_error)?;
+    // Recorded in the frame header, e.g. for `zstd -l`. The streamed decompression does not
+    // use it, `read_to_end` grows its buffer as it reads
+    encoder
+        .set_pledged_src_size(Some(bytes.len() as u64))
+        .map_err(compress_error)?;
+    encoder.write_all(bytes).map_err(compress_error)?;
+    encoder.finish().map_err(compress_error)
+}
+
+/// The saved navmesh compressed by `compress_serialized`, decompressed as it is read from `r`
+/// so the compressed data is never whole in memory. Fails early when the data decompresses
+/// to something else than a saved navmesh, the checksum is checked when it is loaded
+pub fn decompress_serialized<R: Read>(r: R) -> Result<Vec<u8>> {
+    let mut decoder = zstd::stream::Decoder::new(r).map_err(compress_error)?;
+
+    let mut bytes = vec![0; 4];
+    decoder.read_exact(&mut bytes).map_err(compress_error)?;
+    if &bytes[..] != b"RDNM" {
+        return Err(Error::SerializeError(
+            "not a compressed serialized navmesh".into(),
+        ));
+    }
+    decoder.read_to_end(&mut bytes).map_err(compress_error)?;
+    Ok(bytes)
+}
+
+impl RecastQuery {
+    /// A query over a navmesh compressed by `compress_serialized`, decompressed as it is read
+    /// and loaded without a copy per tile when the tiles are aligned for Detour where they are,
+    /// with a copy per tile otherwise. Needs the `zstd` feature. See `from_serialized_in_place`
+    pub fn from_compressed<R: Read>(r: R) -> Result<RecastQuery> {
+        let bytes = decompress_serialized(r)?;
+        match RecastQuery::try_from_serialized_in_place(bytes)? {
+            Ok(q) => Ok(q),
+            Err(bytes) => RecastQuery::from_serialized(&bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry};
+
+    fn geometry() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 40.0, 0.0, 0.0, 40.0, 0.0, 40.0, 0.0, 0.0, 40.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let config = BuildConfig::default();
+        let q = RecastQuery::build_tiled(&geometry(), &config, 32).unwrap();
+        let bytes = q.serialize_with_config(&config).unwrap();
+
+        let compressed = compress_serialized(&bytes, vec![], DEFAULT_COMPRESSION_LEVEL).unwrap();
+        assert!(compressed.len() < bytes.len() / 2);
+        assert_eq!(decompress_serialized(&compressed[..]).unwrap(), bytes);
+
+        let loaded = RecastQuery::from_compressed(&compressed[..]).unwrap();
+        assert_eq!(loaded.polys().unwrap(), q.polys().unwrap());
+        assert_eq!(loaded.serialize_with_config(&config).unwrap(), bytes);
+
+        // Streamed from a reader giving a few bytes at a time
+        let slow = io::BufReader::with_capacity(7, &compressed[..]);
+        assert_eq!(decompress_serialized(slow).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let q = RecastQuery::build(&geometry(), &BuildConfig::default()).unwrap();
+        let bytes = q.serialize().unwrap();
+
+        // Not zstd, and zstd of something else
+        assert!(decompress_serialized(&bytes[..]).is_err());
+        let other = compress_serialized(b"MSET....", vec![], 1).unwrap();
+        assert!(matches!(
+            decompress_serialized(&other[..]),
+            Err(Error::SerializeError(ref msg)) if msg == "not a compressed serialized navmesh"
+        ));
+
+        let compressed = compress_serialized(&bytes, vec![], 1).unwrap();
+        assert!(RecastQuery::from_compressed(&compressed[..compressed.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! Detour's path corridor on its own, for agents moved by their own locomotion instead of a `Crowd`
+use crate::{
+    Error, OffMeshTraversal, PathOptions, PathPoint, Point, PolyRef, RecastQuery, Result,
+    StraightPathFlags,
+};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// The polys from the position of an agent to its target, kept up to date as both move.
+///
+/// The corridor does not keep the query, each call moving over the navmesh takes it and uses
+/// its filter. Moving the position trims the polys left behind, so a path is planned once and
+/// the agent follows `corners` while its physics decide where it actually goes
+pub struct Corridor {
+    corridor: ptr::NonNull<c_void>,
+    max_path: u32,
+}
+
+// The corridor only owns its polys, the navmesh is given to each call
+unsafe impl Send for Corridor {}
+
+impl std::fmt::Debug for Corridor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Corridor")
+            .field("corridor", &self.corridor)
+            .field("max_path", &self.max_path)
+            .finish()
+    }
+}
+
+impl Corridor {
+    /// A corridor of at most `max_path` polys, `reset` places it on the navmesh
+    pub fn new(max_path: u32) -> Result<Corridor> {
+        let mut err = sys::RecastNavError::zeros();
+        let corridor = unsafe { sys::recastc_create_corridor(max_path as i32, &mut err as *mut _) };
+
+        let corridor = ptr::NonNull::new(corridor as *mut c_void)
+            .ok_or_else(|| Error::FindPathError(err.msg().into_owned()))?;
+        Ok(Corridor { corridor, max_path })
+    }
+
+    pub fn max_path(&self) -> u32 {
+        self.max_path
+    }
+
+    /// Start over on the poly of `pos`, the target is `pos` too
+    pub fn reset(&mut self, poly: PolyRef, pos: Point) -> Result<()> {
+        self.call(|c, err| unsafe { sys::recastc_corridor_reset(c, poly, pos.0.as_ptr(), err) })
+    }
+
+    /// Replace the polys toward `target`, e.g. from a custom planner. The first poly must be the
+    /// poly of the position and the path shorter than `max_path`
+    pub fn set_path(&mut self, target: Point, polys: &[PolyRef]) -> Result<()> {
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_set_path(
+                c,
+                target.0.as_ptr(),
+                polys.as_ptr(),
+                polys.len() as i32,
+                err,
+            )
+        })
+    }
+
+    /// Reset the corridor at `start` and plan the polys to `end`. A path ending short of `end`
+    /// fails with `Error::PartialResult`
+    pub fn plan(
+        &mut self,
+        q: &RecastQuery,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        options: &PathOptions,
+    ) -> Result<()> {
+        let start = q.find_poly(start, r)?;
+        let end = q.find_poly(end, r)?;
+        // Only the polys are needed, the corners come from `corners`
+        let (polys, _) = q.plan_corridor(start, end, options, 1)?;
+        if polys.is_empty() {
+            return Err(Error::FindPathError("No Path".to_string()));
+        }
+
+        self.reset(start.1, start.0)?;
+        self.set_path(end.0, &polys)
+    }
+
+    /// Move the position toward `pos` along the navmesh surface, e.g. where the physics moved
+    /// the agent. Returns the position constrained to the navmesh
+    pub fn move_position(&mut self, q: &RecastQuery, pos: Point) -> Result<Point> {
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_move_position(c, q.q.as_ptr(), pos.0.as_ptr(), err)
+        })?;
+        Ok(self.pos())
+    }
+
+    /// Move the target toward `pos` along the navmesh surface, e.g. following a target that
+    /// moves a little each frame without replanning. Returns the target constrained to the navmesh
+    pub fn move_target_position(&mut self, q: &RecastQuery, pos: Point) -> Result<Point> {
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_move_target_position(c, q.q.as_ptr(), pos.0.as_ptr(), err)
+        })?;
+        Ok(self.target())
+    }
+
+    /// Shortcut the corridor toward `next` when visible within `range`, `next` is usually the
+    /// corner after the next one. Cheap enough for every frame
+    pub fn optimize_visibility(&mut self, q: &RecastQuery, next: Point, range: f32) -> Result<()> {
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_optimize_visibility(c, q.q.as_ptr(), next.0.as_ptr(), range, err)
+        })
+    }
+
+    /// Replan the start of the corridor with a short local search, returns whether it changed.
+    /// Costlier than `optimize_visibility`, e.g. every half second
+    pub fn optimize_topology(&mut self, q: &RecastQuery) -> Result<bool> {
+        let mut optimized = 0;
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_optimize_topology(c, q.q.as_ptr(), &mut optimized, err)
+        })?;
+        Ok(optimized != 0)
+    }
+
+    /// The next corners from the position, at most `max_corners`. The corners stop at an
+    /// off-mesh connection, the last one is then its start
+    pub fn corners(&self, q: &RecastQuery, max_corners: u32) -> Result<Vec<PathPoint>> {
+        let n = max_corners as usize;
+        let mut verts = vec![0.0; n * 3];
+        let mut flags = vec![0; n];
+        let mut polys = vec![0; n];
+        let mut count = 0;
+
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_find_corners(
+                c,
+                q.q.as_ptr(),
+                verts.as_mut_ptr(),
+                flags.as_mut_ptr(),
+                polys.as_mut_ptr(),
+                max_corners as i32,
+                &mut count,
+                err,
+            )
+        })?;
+
+        Ok((0..count as usize)
+            .map(|i| {
+                let flags = StraightPathFlags(flags[i]);
+                PathPoint {
+                    pos: (verts[i * 3], verts[i * 3 + 1], verts[i * 3 + 2]).into(),
+                    flags,
+                    poly: polys[i],
+                    off_mesh_id: if flags.is_off_mesh_connection() {
+                        q.off_mesh_connection(polys[i]).ok().map(|c| c.user_id)
+                    } else {
+                        None
+                    },
+                }
+            })
+            .collect())
+    }
+
+    /// Step over the off-mesh connection `link` reached by the position, the poly of the last
+    /// corner. Returns both ends of the connection, the position is at its end once traversed
+    pub fn move_over_off_mesh_connection(
+        &mut self,
+        q: &RecastQuery,
+        link: PolyRef,
+    ) -> Result<(Point, Point)> {
+        let mut start = [0.0; 3];
+        let mut end = [0.0; 3];
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_move_over_off_mesh_connection(
+                c,
+                q.q.as_ptr(),
+                link,
+                start.as_mut_ptr(),
+                end.as_mut_ptr(),
+                err,
+            )
+        })?;
+        Ok((Point(start), Point(end)))
+    }
+
+    /// Step over the off-mesh connection `link` like `move_over_off_mesh_connection`, the returned
+    /// traversal is timed like the crowd for an agent of `max_speed`, e.g. to drive an animation
+    pub fn traverse_off_mesh_connection(
+        &mut self,
+        q: &RecastQuery,
+        link: PolyRef,
+        max_speed: f32,
+    ) -> Result<OffMeshTraversal> {
+        let user_id = q.off_mesh_connection(link)?.user_id;
+        let (start, end) = self.move_over_off_mesh_connection(q, link)?;
+        Ok(OffMeshTraversal::new(link, user_id, start, end, max_speed))
+    }
+
+    /// Whether the next `max_look_ahead` polys still exist and pass the query filter,
+    /// e.g. after a tile was replaced or a door closed
+    pub fn is_valid(&self, q: &RecastQuery, max_look_ahead: u32) -> Result<bool> {
+        let mut valid = 0;
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_is_valid(c, q.q.as_ptr(), max_look_ahead as i32, &mut valid, err)
+        })?;
+        Ok(valid != 0)
+    }
+
+    /// Cut the corridor before its first invalid poly, it restarts at `safe_pos` when the poly
+    /// of the position itself is invalid. The target then has to be planned again
+    pub fn trim_invalid(
+        &mut self,
+        q: &RecastQuery,
+        safe_poly: PolyRef,
+        safe_pos: Point,
+    ) -> Result<()> {
+        self.call(|c, err| unsafe {
+            sys::recastc_corridor_trim_invalid_path(
+                c,
+                q.q.as_ptr(),
+                safe_poly,
+                safe_pos.0.as_ptr(),
+                err,
+            )
+        })
+    }
+
+    pub fn pos(&self) -> Point {
+        Point(self.state().pos)
+    }
+
+    pub fn target(&self) -> Point {
+        Point(self.state().target)
+    }
+
+    /// The poly of the position, 0 before `reset`
+    pub fn first_poly(&self) -> PolyRef {
+        self.state().first_poly
+    }
+
+    /// The poly of the target, 0 before `reset`
+    pub fn last_poly(&self) -> PolyRef {
+        self.state().last_poly
+    }
+
+    /// The polys from the position to the target
+    pub fn path(&self) -> Vec<PolyRef> {
+        let mut polys = vec![0; self.state().path_count as usize];
+        let mut count = 0;
+        // Sized from the state, it fits
+        let _ = self.call(|c, err| unsafe {
+            sys::recastc_corridor_get_path(
+                c,
+                polys.as_mut_ptr(),
+                polys.len() as i32,
+                &mut count,
+                err,
+            )
+        });
+        polys.truncate(count as usize);
+        polys
+    }
+
+    fn state(&self) -> sys::RecastCorridorState {
+        let mut state = sys::RecastCorridorState::default();
+        // Only fails on a null state
+        let _ = self.call(|c, err| unsafe {
+            sys::recastc_corridor_get_state(c, &mut state as *mut _, err)
+        });
+        state
+    }
+
+    fn call<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(*const c_void, *mut sys::RecastNavError) -> i32,
+    {
+        let mut err = sys::RecastNavError::zeros();
+        if f(self.corridor.as_ptr(), &mut err as *mut _) == 0 {
+            Err(Error::FindPathError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Corridor {
+    fn drop(&mut self) {
+        unsafe { sys::recastc_free_corridor(self.corridor.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rooms;
+    use crate::{BuildConfig, InputGeometry, OffMeshConnection, OffMeshPhase};
+
+    #[test]
+    fn test_follow_corners() {
+        let q = rooms();
+        let ext = (0.5, 1.0, 0.5);
+        let end: Point = (23.0, 0.0, 9.0).into();
+        let mut corridor = Corridor::new(256).unwrap();
+        corridor
+            .plan(
+                &q,
+                (1.0, 0.0, 1.0).into(),
+                end,
+                ext,
+                &PathOptions::default(),
+            )
+            .unwrap();
+        let planned = corridor.path().len();
+        assert!(planned > 1);
+        assert_eq!(corridor.last_poly(), q.find_poly(end, ext).unwrap().1);
+
+        // Step at most 0.5 toward the next corner, as a physics driven agent would
+        let mut steps = 0;
+        loop {
+            // The corners reached are pruned, none are left at the end
+            let corners = corridor.corners(&q, 2).unwrap();
+            let next = match corners.first() {
+                Some(next) => next,
+                None => break,
+            };
+            let pos = corridor.pos();
+            let (dx, dz) = (next.pos.x() - pos.x(), next.pos.z() - pos.z());
+            let d = (dx * dx + dz * dz).sqrt();
+            let t = (0.5 / d).min(1.0);
+            corridor
+                .move_position(&q, (pos.x() + dx * t, pos.y(), pos.z() + dz * t).into())
+                .unwrap();
+            steps += 1;
+            assert!(steps < 200);
+        }
+        assert_eq!(corridor.path().len(), 1);
+        assert!(corridor.is_valid(&q, 8).unwrap());
+    }
+
+    #[test]
+    fn test_constrained_moves() {
+        let q = rooms();
+        let ext = (0.5, 1.0, 0.5);
+        let mut corridor = Corridor::new(64).unwrap();
+        assert_eq!(corridor.first_poly(), 0);
+        assert!(corridor.corners(&q, 4).is_err());
+
+        let (start, poly) = q.find_poly((5.0, 0.0, 5.0).into(), ext).unwrap();
+        corridor.reset(poly, start).unwrap();
+        // Into the wall of the first room
+        let pos = corridor.move_position(&q, (5.0, 0.0, -5.0).into()).unwrap();
+        assert!(pos.z() > -0.1);
+
+        // The target follows across the passage
+        let target = corridor
+            .move_target_position(&q, (12.0, 0.0, 5.0).into())
+            .unwrap();
+        assert!((target.x() - 12.0).abs() < 0.1);
+        assert!(corridor.path().len() > 1);
+        assert!(corridor.set_path(target, &[12345]).is_err());
+    }
+
+    #[test]
+    fn test_traverse_off_mesh_connection() {
+        // Two platforms with a gap between x = 4 and x = 6, crossed by a link
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                6.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 6.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            off_mesh_connections: vec![OffMeshConnection {
+                user_id: 42,
+                ..OffMeshConnection::new((3.0, 0.0, 5.0).into(), (7.0, 0.0, 5.0).into(), 0.5)
+            }],
+            ..Default::default()
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let ext = (0.5, 1.0, 0.5);
+        let mut corridor = Corridor::new(64).unwrap();
+        corridor
+            .plan(
+                &q,
+                (1.0, 0.0, 5.0).into(),
+                (9.0, 0.0, 5.0).into(),
+                ext,
+                &PathOptions::default(),
+            )
+            .unwrap();
+
+        let link = corridor
+            .corners(&q, 4)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.flags.is_off_mesh_connection())
+            .unwrap();
+        assert_eq!(link.off_mesh_id, Some(42));
+        // Not a connection of the corridor
+        assert!(corridor
+            .traverse_off_mesh_connection(&q, corridor.last_poly(), 2.0)
+            .is_err());
+
+        corridor.move_position(&q, link.pos).unwrap();
+        let mut tr = corridor
+            .traverse_off_mesh_connection(&q, link.poly, 2.0)
+            .unwrap();
+        assert_eq!((tr.link, tr.user_id), (link.poly, 42));
+        assert!(tr.start.x() < 4.0 && tr.end.x() > 6.0);
+        assert!(corridor.pos().x() > 6.0);
+
+        tr.advance(tr.duration * 2.0);
+        assert_eq!(tr.phase, OffMeshPhase::Exiting);
+    }
+}
@@ -0,0 +1,1515 @@
+//! Agents moved over the navmesh by DetourCrowd, with path following and local avoidance
+use crate::{
+    AgentState, DirtyTiles, Error, FixedTimestep, Migration, OffMeshTraversal, Point, PolyFlags,
+    PolyRef, RebuiltTiles, RecastQuery, Result, SimClock, SnapStrategy, SoftArea, SoftAreaId,
+    Stranded, TileData,
+};
+use std::ops::BitOr;
+use std::os::raw::c_void;
+use std::ptr;
+
+/// The slot of an agent in its crowd, reused by the next agent once removed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AgentId(i32);
+
+impl AgentId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// What the crowd does for an agent on each update
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CrowdUpdateFlags(pub u8);
+
+impl CrowdUpdateFlags {
+    pub const EMPTY: CrowdUpdateFlags = CrowdUpdateFlags(0);
+    /// Start turning before reaching a corner
+    pub const ANTICIPATE_TURNS: CrowdUpdateFlags = CrowdUpdateFlags(sys::DT_CROWD_ANTICIPATE_TURNS);
+    /// Steer around the neighbour agents and the walls
+    pub const OBSTACLE_AVOIDANCE: CrowdUpdateFlags =
+        CrowdUpdateFlags(sys::DT_CROWD_OBSTACLE_AVOIDANCE);
+    /// Keep a distance from the neighbour agents, see `CrowdAgentParams::separation_weight`
+    pub const SEPARATION: CrowdUpdateFlags = CrowdUpdateFlags(sys::DT_CROWD_SEPARATION);
+    /// Shortcut the corridor where the next corners are visible
+    pub const OPTIMIZE_VIS: CrowdUpdateFlags = CrowdUpdateFlags(sys::DT_CROWD_OPTIMIZE_VIS);
+    /// Replan the start of the corridor from time to time
+    pub const OPTIMIZE_TOPO: CrowdUpdateFlags = CrowdUpdateFlags(sys::DT_CROWD_OPTIMIZE_TOPO);
+    pub const ALL: CrowdUpdateFlags = CrowdUpdateFlags(0x1f);
+
+    pub fn contains(self, other: CrowdUpdateFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for CrowdUpdateFlags {
+    type Output = CrowdUpdateFlags;
+    fn bitor(self, rhs: CrowdUpdateFlags) -> CrowdUpdateFlags {
+        CrowdUpdateFlags(self.0 | rhs.0)
+    }
+}
+
+/// Shape and steering of a crowd agent, see `dtCrowdAgentParams`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdAgentParams {
+    /// At most the max agent radius of the crowd
+    pub radius: f32,
+    pub height: f32,
+    pub max_acceleration: f32,
+    pub max_speed: f32,
+    /// Neighbours within this range are steered around
+    pub collision_query_range: f32,
+    /// Range of the visibility shortcuts of the corridor
+    pub path_optimization_range: f32,
+    pub separation_weight: f32,
+    pub update_flags: CrowdUpdateFlags,
+    /// Slot of the `ObstacleAvoidanceParams` of the crowd, 0 (low) to 3 (high) quality unless changed
+    pub avoidance_slot: u8,
+}
+
+impl CrowdAgentParams {
+    /// The settings of the RecastDemo crowd tool for an agent of this size and speed
+    pub fn new(radius: f32, height: f32, max_speed: f32) -> CrowdAgentParams {
+        CrowdAgentParams {
+            radius,
+            height,
+            max_acceleration: 8.0,
+            max_speed,
+            collision_query_range: radius * 12.0,
+            path_optimization_range: radius * 30.0,
+            separation_weight: 2.0,
+            update_flags: CrowdUpdateFlags::ALL,
+            avoidance_slot: 3,
+        }
+    }
+
+    fn as_sys(&self) -> sys::RecastCrowdAgentParams {
+        sys::RecastCrowdAgentParams {
+            radius: self.radius,
+            height: self.height,
+            max_acceleration: self.max_acceleration,
+            max_speed: self.max_speed,
+            collision_query_range: self.collision_query_range,
+            path_optimization_range: self.path_optimization_range,
+            separation_weight: self.separation_weight,
+            update_flags: self.update_flags.0,
+            obstacle_avoidance_type: self.avoidance_slot,
+            query_filter_type: 0,
+        }
+    }
+
+    fn from_sys(p: &sys::RecastCrowdAgentParams) -> CrowdAgentParams {
+        CrowdAgentParams {
+            radius: p.radius,
+            height: p.height,
+            max_acceleration: p.max_acceleration,
+            max_speed: p.max_speed,
+            collision_query_range: p.collision_query_range,
+            path_optimization_range: p.path_optimization_range,
+            separation_weight: p.separation_weight,
+            update_flags: CrowdUpdateFlags(p.update_flags),
+            avoidance_slot: p.obstacle_avoidance_type,
+        }
+    }
+}
+
+/// Slots of `ObstacleAvoidanceParams` in a crowd
+pub const MAX_AVOIDANCE_SLOTS: u8 = sys::DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS;
+
+/// How the obstacle avoidance samples the velocities it tries, see `dtObstacleAvoidanceParams`.
+/// More divisions, rings and depth avoid better for more CPU
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObstacleAvoidanceParams {
+    /// Bias of the samples towards the desired velocity
+    pub vel_bias: f32,
+    pub weight_desired_vel: f32,
+    pub weight_current_vel: f32,
+    /// Preference for passing on the same side
+    pub weight_side: f32,
+    /// Weight of the time to impact
+    pub weight_toi: f32,
+    /// Seconds ahead to look for collisions
+    pub horizon_time: f32,
+    /// Only used by grid sampling, the crowd samples adaptively
+    pub grid_size: u8,
+    pub adaptive_divs: u8,
+    pub adaptive_rings: u8,
+    pub adaptive_depth: u8,
+}
+
+impl Default for ObstacleAvoidanceParams {
+    /// The params of `dtCrowd::init`, the slots above 3 start with these
+    fn default() -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams {
+            vel_bias: 0.4,
+            weight_desired_vel: 2.0,
+            weight_current_vel: 0.75,
+            weight_side: 0.75,
+            weight_toi: 2.5,
+            horizon_time: 2.5,
+            grid_size: 33,
+            adaptive_divs: 7,
+            adaptive_rings: 2,
+            adaptive_depth: 5,
+        }
+    }
+}
+
+impl ObstacleAvoidanceParams {
+    /// Slot 0 of a new crowd, the presets are the ones of the RecastDemo crowd tool
+    pub fn low() -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams::preset(5, 2, 1)
+    }
+
+    /// Slot 1 of a new crowd
+    pub fn medium() -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams::preset(5, 2, 2)
+    }
+
+    /// Slot 2 of a new crowd
+    pub fn good() -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams::preset(7, 2, 3)
+    }
+
+    /// Slot 3 of a new crowd
+    pub fn high() -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams::preset(7, 3, 3)
+    }
+
+    fn preset(divs: u8, rings: u8, depth: u8) -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams {
+            vel_bias: 0.5,
+            adaptive_divs: divs,
+            adaptive_rings: rings,
+            adaptive_depth: depth,
+            ..Default::default()
+        }
+    }
+
+    fn as_sys(&self) -> sys::RecastObstacleAvoidanceParams {
+        sys::RecastObstacleAvoidanceParams {
+            vel_bias: self.vel_bias,
+            weight_desired_vel: self.weight_desired_vel,
+            weight_current_vel: self.weight_current_vel,
+            weight_side: self.weight_side,
+            weight_toi: self.weight_toi,
+            horizon_time: self.horizon_time,
+            grid_size: self.grid_size,
+            adaptive_divs: self.adaptive_divs,
+            adaptive_rings: self.adaptive_rings,
+            adaptive_depth: self.adaptive_depth,
+        }
+    }
+
+    fn from_sys(p: &sys::RecastObstacleAvoidanceParams) -> ObstacleAvoidanceParams {
+        ObstacleAvoidanceParams {
+            vel_bias: p.vel_bias,
+            weight_desired_vel: p.weight_desired_vel,
+            weight_current_vel: p.weight_current_vel,
+            weight_side: p.weight_side,
+            weight_toi: p.weight_toi,
+            horizon_time: p.horizon_time,
+            grid_size: p.grid_size,
+            adaptive_divs: p.adaptive_divs,
+            adaptive_rings: p.adaptive_rings,
+            adaptive_depth: p.adaptive_depth,
+        }
+    }
+}
+
+/// Where the agent is moving to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MoveTarget {
+    #[default]
+    None,
+    /// No path to the target was found
+    Failed,
+    /// Following the path to the target
+    Valid,
+    /// The path is planned by the next updates
+    Pending,
+    /// Steering with the velocity of `Crowd::request_move_velocity`
+    Velocity,
+}
+
+impl MoveTarget {
+    fn from_sys(state: u8) -> MoveTarget {
+        match state {
+            sys::DT_CROWDAGENT_TARGET_FAILED => MoveTarget::Failed,
+            sys::DT_CROWDAGENT_TARGET_VALID => MoveTarget::Valid,
+            sys::DT_CROWDAGENT_TARGET_REQUESTING
+            | sys::DT_CROWDAGENT_TARGET_WAITING_FOR_QUEUE
+            | sys::DT_CROWDAGENT_TARGET_WAITING_FOR_PATH => MoveTarget::Pending,
+            sys::DT_CROWDAGENT_TARGET_VELOCITY => MoveTarget::Velocity,
+            _ => MoveTarget::None,
+        }
+    }
+}
+
+/// An agent after the last update
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdAgent {
+    pub pos: Point,
+    /// Velocity after avoidance and collisions
+    pub vel: Point,
+    /// Velocity along the path before avoidance
+    pub desired_vel: Point,
+    /// The poly under the agent
+    pub poly: PolyRef,
+    /// Crossing an off-mesh connection
+    pub off_mesh: bool,
+    /// The agent could not be placed on the navmesh and does not move
+    pub invalid: bool,
+    pub target: MoveTarget,
+    /// End of the path once the target is valid, the requested target before. The origin for
+    /// velocity targets
+    pub target_pos: Point,
+    /// The path only gets near the target
+    pub partial: bool,
+    /// Out of crowd control, see `Crowd::detach_agent`
+    pub detached: bool,
+}
+
+/// An agent near another one, as found by the proximity grid of the crowd
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdNeighbour {
+    pub id: AgentId,
+    /// In world unit
+    pub distance: f32,
+}
+
+/// An agent before and after the last step of `Crowd::update_fixed`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InterpolatedAgent {
+    pub id: AgentId,
+    /// Where the last step started, the same as `pos` for an agent added since
+    pub prev: Point,
+    pub pos: Point,
+}
+
+impl InterpolatedAgent {
+    /// The position `alpha` of the way through the last step
+    pub fn lerp(&self, alpha: f32) -> Point {
+        let (a, b) = (self.prev, self.pos);
+        Point::new((
+            a.x() + (b.x() - a.x()) * alpha,
+            a.y() + (b.y() - a.y()) * alpha,
+            a.z() + (b.z() - a.z()) * alpha,
+        ))
+    }
+}
+
+/// What the rendering needs to draw the agents between two fixed steps, kept by the caller
+/// across the frames of `Crowd::update_fixed`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CrowdInterpolation {
+    /// Steps of the last frame
+    pub steps: u32,
+    /// How far the time is into the next step, see `FixedTimestep::alpha`
+    pub alpha: f32,
+    /// The agents as of the last step, in slot order
+    pub agents: Vec<InterpolatedAgent>,
+}
+
+impl CrowdInterpolation {
+    /// The positions to draw
+    pub fn positions(&self) -> impl Iterator<Item = (AgentId, Point)> + '_ {
+        self.agents.iter().map(move |a| (a.id, a.lerp(self.alpha)))
+    }
+}
+
+/// A crowd of agents over the navmesh of the query it owns.
+///
+/// The crowd plans the paths of its agents over several updates and steers them around each
+/// other, `update` moves every agent at once
+pub struct Crowd {
+    crowd: ptr::NonNull<c_void>,
+    query: RecastQuery,
+    max_agents: u32,
+    max_agent_radius: f32,
+    /// Agents added to each slot so far, tells a reused slot from the agent it had before
+    generations: Vec<u32>,
+}
+
+impl std::fmt::Debug for Crowd {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Crowd")
+            .field("crowd", &self.crowd)
+            .field("query", &self.query)
+            .field("max_agents", &self.max_agents)
+            .finish()
+    }
+}
+
+impl Crowd {
+    /// A crowd of at most `max_agents` agents, none larger than `max_agent_radius`.
+    /// The agents use the query filter as it is now
+    pub fn new(query: RecastQuery, max_agents: u32, max_agent_radius: f32) -> Result<Crowd> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let crowd = unsafe {
+            sys::recastc_create_crowd(
+                query.q.as_ptr(),
+                max_agents as i32,
+                max_agent_radius,
+                &mut err as *mut _,
+            )
+        };
+
+        let crowd = ptr::NonNull::new(crowd as *mut c_void)
+            .ok_or_else(|| Error::CrowdError(err.msg().into_owned()))?;
+
+        Ok(Crowd {
+            crowd,
+            query,
+            max_agents,
+            max_agent_radius,
+            generations: vec![0; max_agents as usize],
+        })
+    }
+
+    pub fn query(&self) -> &RecastQuery {
+        &self.query
+    }
+
+    // No `query_mut`: the crowd keeps the navmesh of the query, replacing the query would free
+    // it under the crowd. The changes below keep the navmesh, agents on removed or replaced
+    // tiles replan on the next update
+
+    /// See `RecastQuery::add_tile`
+    pub fn add_tile(&mut self, tile: TileData) -> Result<()> {
+        self.query.add_tile(tile)
+    }
+
+    /// See `RecastQuery::remove_tile`
+    pub fn remove_tile(&mut self, tx: i32, ty: i32) -> Result<()> {
+        self.query.remove_tile(tx, ty)
+    }
+
+    /// See `RecastQuery::swap_tiles`
+    pub fn swap_tiles(&mut self, rebuilt: RebuiltTiles) -> Result<usize> {
+        self.query.swap_tiles(rebuilt)
+    }
+
+    /// See `DirtyTiles::swap_rebuilt`
+    pub fn swap_rebuilt(&mut self, dirty: &mut DirtyTiles, rebuilt: RebuiltTiles) -> Result<usize> {
+        dirty.swap_rebuilt(&mut self.query, rebuilt)
+    }
+
+    /// See `RecastQuery::set_poly_flags`, e.g. to close a door in front of the agents
+    pub fn set_poly_flags(&mut self, poly: PolyRef, flags: PolyFlags) -> Result<()> {
+        self.query.set_poly_flags(poly, flags)
+    }
+
+    /// Move the agents onto the navmesh of another query, e.g. a variant of the level with a
+    /// destroyed bridge. The agents are placed on the nearest poly and the paths to their targets
+    /// are planned again, they keep their ids and velocity targets. The crowd gets the filter of
+    /// the new query
+    pub fn switch_navmesh(&mut self, query: RecastQuery) -> Result<Migration> {
+        let before = self.agents()?;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_set_navmesh(crowd, query.q.as_ptr(), err)
+        })?;
+        let previous = std::mem::replace(&mut self.query, query);
+
+        let mut stranded = vec![];
+        for (id, agent) in before {
+            let placed = self.agent(id)?;
+            if placed.invalid {
+                stranded.push((id, Stranded::OffMesh));
+                continue;
+            }
+            if agent.target != MoveTarget::Valid && agent.target != MoveTarget::Pending {
+                continue;
+            }
+
+            let reachable = self
+                .query
+                .find_path(placed.pos, agent.target_pos, self.search_extents())
+                .is_ok();
+            if reachable {
+                self.request_move_target(id, agent.target_pos)?;
+            } else {
+                stranded.push((id, Stranded::Unreachable));
+            }
+        }
+
+        Ok(Migration { previous, stranded })
+    }
+
+    // The crowd has its own copy of the query filter, the cost changes below copy it again. The
+    // agents use the new costs for the paths they plan from then on
+
+    /// See `RecastQuery::set_area_cost`
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) -> Result<()> {
+        let res = self.query.set_area_cost(area, cost);
+        self.sync_filter()?;
+        res
+    }
+
+    /// See `RecastQuery::add_soft_area`
+    pub fn add_soft_area(&mut self, area: SoftArea) -> Result<SoftAreaId> {
+        let res = self.query.add_soft_area(area);
+        self.sync_filter()?;
+        res
+    }
+
+    /// See `RecastQuery::remove_soft_area`
+    pub fn remove_soft_area(&mut self, id: SoftAreaId) -> Result<()> {
+        let res = self.query.remove_soft_area(id);
+        self.sync_filter()?;
+        res
+    }
+
+    /// See `RecastQuery::update_soft_areas`
+    pub fn update_soft_areas(&mut self, dt: f32) -> Result<()> {
+        let res = self.query.update_soft_areas(dt);
+        self.sync_filter()?;
+        res
+    }
+
+    fn sync_filter(&mut self) -> Result<()> {
+        let query = self.query.q.as_ptr();
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_set_filter(crowd, query, err) })
+    }
+
+    pub fn max_agents(&self) -> u32 {
+        self.max_agents
+    }
+
+    pub fn max_agent_radius(&self) -> f32 {
+        self.max_agent_radius
+    }
+
+    /// Room for `max_agents` agents up to `max_agent_radius` large, neither can shrink. The crowd is
+    /// recreated, the agents keep their ids, corridors, velocities and targets. Agents crossing an
+    /// off-mesh connection land at its end
+    pub fn set_capacity(&mut self, max_agents: u32, max_agent_radius: f32) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_set_capacity(crowd, max_agents as i32, max_agent_radius, err)
+        })?;
+        self.max_agents = max_agents;
+        self.max_agent_radius = max_agent_radius;
+        self.generations.resize(max_agents as usize, 0);
+        Ok(())
+    }
+
+    /// Room for `additional` more agents, e.g. before spawning a wave. The capacity at least doubles
+    /// when it grows, so adding agents one by one does not recreate the crowd each time
+    pub fn reserve(&mut self, additional: u32) -> Result<()> {
+        let needed = self.agents()?.len() as u32 + additional;
+        if needed > self.max_agents {
+            self.set_capacity(needed.max(self.max_agents * 2), self.max_agent_radius)?;
+        }
+        Ok(())
+    }
+
+    /// Add an agent on the navmesh near `pos`
+    pub fn add_agent(&mut self, pos: Point, params: &CrowdAgentParams) -> Result<AgentId> {
+        let mut agent = 0;
+        let params = params.as_sys();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_add_agent(
+                crowd,
+                pos.0.as_ptr(),
+                &params as *const _,
+                &mut agent,
+                err,
+            )
+        })?;
+        self.generations[agent as usize] += 1;
+        Ok(AgentId(agent))
+    }
+
+    /// How many agents were added to the slot of `id`, changes when the slot is reused
+    pub(crate) fn generation(&self, id: AgentId) -> u32 {
+        self.generations.get(id.index()).copied().unwrap_or(0)
+    }
+
+    /// Change the parameters of an agent at runtime, e.g. for a speed buff or crouching.
+    /// It keeps its position, velocity and move target
+    pub fn update_agent(&mut self, id: AgentId, params: &CrowdAgentParams) -> Result<()> {
+        let params = params.as_sys();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_update_agent(crowd, id.0, &params as *const _, err)
+        })
+    }
+
+    /// The parameters the agent currently has
+    pub fn agent_params(&self, id: AgentId) -> Result<CrowdAgentParams> {
+        let mut params = sys::RecastCrowdAgentParams::default();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_agent_params(crowd, id.0, &mut params as *mut _, err)
+        })?;
+        Ok(CrowdAgentParams::from_sys(&params))
+    }
+
+    /// Change some of the parameters of an agent, the others are kept
+    pub fn modify_agent<F>(&mut self, id: AgentId, f: F) -> Result<CrowdAgentParams>
+    where
+        F: FnOnce(&mut CrowdAgentParams),
+    {
+        let mut params = self.agent_params(id)?;
+        f(&mut params);
+        self.update_agent(id, &params)?;
+        Ok(params)
+    }
+
+    pub fn remove_agent(&mut self, id: AgentId) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_remove_agent(crowd, id.0, err) })
+    }
+
+    /// The search box around targets, the same as the crowd uses for the agents
+    pub fn search_extents(&self) -> (f32, f32, f32) {
+        let r = self.max_agent_radius;
+        (r * 2.0, r * 1.5, r * 2.0)
+    }
+
+    /// Move the agent to the point of the navmesh nearest to `target`, the path is planned by the next updates.
+    /// Request a moving target again to follow it, e.g. another agent, the path is replanned from the agent
+    pub fn request_move_target(&mut self, id: AgentId, target: Point) -> Result<()> {
+        let (pos, poly) = self.query.find_poly(target, self.search_extents())?;
+        self.request_move_target_on_poly(id, pos, poly)
+    }
+
+    /// Move the agent to a point already on `poly`, e.g. resolved by a `SnapStrategy`
+    pub fn request_move_target_on_poly(
+        &mut self,
+        id: AgentId,
+        pos: Point,
+        poly: PolyRef,
+    ) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_request_move_target(crowd, id.0, poly, pos.0.as_ptr(), err)
+        })
+    }
+
+    /// The move orders of many agents in one call, e.g. a box selection in a strategy game.
+    /// Each order gets its own result, an order off the navmesh does not fail the others
+    pub fn request_move_targets<S: SnapStrategy + ?Sized>(
+        &mut self,
+        orders: &[(AgentId, Point)],
+        strategy: &mut S,
+    ) -> Vec<Result<()>> {
+        let targets: Vec<Point> = orders.iter().map(|(_, target)| *target).collect();
+        let resolved = self
+            .query
+            .resolve_positions(&targets, self.search_extents(), strategy);
+
+        orders
+            .iter()
+            .zip(resolved)
+            .map(|(&(id, _), target)| {
+                let (pos, poly) = target?;
+                self.request_move_target_on_poly(id, pos, poly)
+            })
+            .collect()
+    }
+
+    /// Steer the agent with a velocity, e.g. for an agent controlled by the player or fleeing.
+    /// The velocity is kept until another request
+    pub fn request_move_velocity(&mut self, id: AgentId, vel: Point) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_request_move_velocity(crowd, id.0, vel.0.as_ptr(), err)
+        })
+    }
+
+    /// Stop moving to the target or with the velocity
+    pub fn reset_move_target(&mut self, id: AgentId) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_reset_move_target(crowd, id.0, err) })
+    }
+
+    /// Take the agent out of crowd control and keep its slot, e.g. during a cutscene or as a ragdoll.
+    /// The crowd does not move it and still steers the other agents around it.
+    /// Fails while the agent crosses an off-mesh connection
+    pub fn detach_agent(&mut self, id: AgentId) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_detach_agent(crowd, id.0, err) })
+    }
+
+    /// Move a detached agent with its external transform, the other agents steer around the new position
+    pub fn move_detached_agent(&mut self, id: AgentId, pos: Point) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_move_detached_agent(crowd, id.0, pos.0.as_ptr(), err)
+        })
+    }
+
+    /// Give a detached agent back to the crowd on the navmesh near `pos`.
+    /// Its corridor starts over there and it has no target
+    pub fn attach_agent(&mut self, id: AgentId, pos: Point) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_attach_agent(crowd, id.0, pos.0.as_ptr(), err)
+        })
+    }
+
+    /// Move an agent to the navmesh near `pos`, e.g. a respawn or a portal. It keeps its id and
+    /// params, its corridor starts over there and it stands still without target.
+    /// Fails for detached agents, see `attach_agent`, and while crossing an off-mesh connection
+    pub fn teleport_agent(&mut self, id: AgentId, pos: Point) -> Result<()> {
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_teleport_agent(crowd, id.0, pos.0.as_ptr(), err)
+        })
+    }
+
+    /// Let the agents take the off-mesh connections of `user_id` again, see `close_gate`
+    pub fn open_gate(&mut self, user_id: u32) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_set_gate(crowd, user_id, 1, err) })
+    }
+
+    /// Hold the agents about to take an off-mesh connection of `user_id` until `open_gate`, e.g. an
+    /// elevator away from their floor, see `OffMeshConnection::elevator`. They stand still within
+    /// reach of the link and keep their target. Agents already on the link finish crossing it
+    pub fn close_gate(&mut self, user_id: u32) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_set_gate(crowd, user_id, 0, err) })
+    }
+
+    /// The user id of the closed off-mesh connection the agent waited at in the last update
+    pub fn waiting_at_gate(&self, id: AgentId) -> Result<Option<u32>> {
+        let mut user_id = 0;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_agent_gate(crowd, id.0, &mut user_id, err)
+        })?;
+        Ok(Some(user_id).filter(|&g| g != 0))
+    }
+
+    /// Replace the avoidance params of a slot, e.g. a cheap slot for the crowds of background agents.
+    /// Agents pick their slot with `CrowdAgentParams::avoidance_slot`
+    pub fn set_avoidance_params(
+        &mut self,
+        slot: u8,
+        params: &ObstacleAvoidanceParams,
+    ) -> Result<()> {
+        let params = params.as_sys();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_set_avoidance_params(crowd, slot as i32, &params as *const _, err)
+        })
+    }
+
+    pub fn avoidance_params(&self, slot: u8) -> Result<ObstacleAvoidanceParams> {
+        let mut params = sys::RecastObstacleAvoidanceParams::default();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_avoidance_params(crowd, slot as i32, &mut params as *mut _, err)
+        })?;
+        Ok(ObstacleAvoidanceParams::from_sys(&params))
+    }
+
+    /// Move every agent by `dt` seconds
+    pub fn update(&mut self, dt: f32) -> Result<()> {
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_update(crowd, dt, err) })
+    }
+
+    /// Run the simulation steps of a frame of `real_dt` seconds, returns the number of steps
+    pub fn update_with_clock(&mut self, clock: &mut SimClock, real_dt: f32) -> Result<usize> {
+        let steps = clock.steps(real_dt);
+        let n = steps.len();
+        for dt in steps {
+            self.update(dt)?;
+        }
+        Ok(n)
+    }
+
+    /// Run the fixed steps of a frame of `real_dt` seconds. `interpolation` gets the positions
+    /// before and after the last step, it keeps them through frames without a step
+    pub fn update_fixed(
+        &mut self,
+        timestep: &mut FixedTimestep,
+        real_dt: f32,
+        interpolation: &mut CrowdInterpolation,
+    ) -> Result<u32> {
+        let steps = timestep.advance(real_dt);
+        for i in 0..steps {
+            if i + 1 == steps {
+                let mut prev = vec![None; self.max_agents as usize];
+                for (id, agent) in self.agents()? {
+                    prev[id.index()] = Some(agent.pos);
+                }
+                self.update(timestep.dt())?;
+
+                interpolation.agents = self
+                    .agents()?
+                    .into_iter()
+                    .map(|(id, agent)| InterpolatedAgent {
+                        id,
+                        prev: prev.get(id.index()).copied().flatten().unwrap_or(agent.pos),
+                        pos: agent.pos,
+                    })
+                    .collect();
+            } else {
+                self.update(timestep.dt())?;
+            }
+        }
+
+        interpolation.steps = steps;
+        interpolation.alpha = timestep.alpha();
+        Ok(steps)
+    }
+
+    pub fn agent(&self, id: AgentId) -> Result<CrowdAgent> {
+        self.read_agent(id.0)?
+            .ok_or_else(|| Error::CrowdError("Unknown agent".to_string()))
+    }
+
+    /// Every agent of the crowd, in the order of their slots
+    pub fn agents(&self) -> Result<Vec<(AgentId, CrowdAgent)>> {
+        let mut agents = vec![];
+        for i in 0..self.max_agents as i32 {
+            if let Some(agent) = self.read_agent(i)? {
+                agents.push((AgentId(i), agent));
+            }
+        }
+        Ok(agents)
+    }
+
+    /// Position and velocity of every agent, e.g. to publish with a `SnapshotWriter`
+    pub fn agent_states(&self) -> Result<Vec<AgentState>> {
+        Ok(self
+            .agents()?
+            .into_iter()
+            .map(|(id, agent)| AgentState {
+                index: id.index(),
+                pos: agent.pos,
+                vel: agent.vel,
+            })
+            .collect())
+    }
+
+    /// The nearest agents within the collision query range of the agent as of the last update,
+    /// nearest first and at most 6, e.g. to tell if the agent is crowded.
+    /// Detached agents have no neighbours but are neighbours of the others
+    pub fn neighbours(&self, id: AgentId) -> Result<Vec<CrowdNeighbour>> {
+        let mut neighbours =
+            [sys::RecastCrowdNeighbour::default(); sys::DT_CROWDAGENT_MAX_NEIGHBOURS];
+        let mut count = 0;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_neighbours(
+                crowd,
+                id.0,
+                neighbours.as_mut_ptr(),
+                neighbours.len() as i32,
+                &mut count,
+                err,
+            )
+        })?;
+
+        Ok(neighbours[..count as usize]
+            .iter()
+            .map(|n| CrowdNeighbour {
+                id: AgentId(n.agent),
+                distance: n.distance,
+            })
+            .collect())
+    }
+
+    /// The agents within `radius` of `center` on the xz plane, nearest first, e.g. for an aggro radius.
+    /// Found with the proximity grid of the crowd, which the updates fill
+    pub fn agents_within(&self, center: Point, radius: f32) -> Result<Vec<CrowdNeighbour>> {
+        let mut agents = vec![sys::RecastCrowdNeighbour::default(); self.max_agents as usize];
+        let mut count = 0;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_query_agents(
+                crowd,
+                center.0.as_ptr(),
+                radius,
+                agents.as_mut_ptr(),
+                agents.len() as i32,
+                &mut count,
+                err,
+            )
+        })?;
+
+        agents.truncate(count as usize);
+        Ok(agents
+            .iter()
+            .map(|n| CrowdNeighbour {
+                id: AgentId(n.agent),
+                distance: n.distance,
+            })
+            .collect())
+    }
+
+    /// The off-mesh connection the agent crosses, or left in the last update with `OffMeshPhase::Exiting`,
+    /// e.g. to play a jump or climb animation while the crowd moves the agent over it
+    pub fn off_mesh_traversal(&self, id: AgentId) -> Result<Option<OffMeshTraversal>> {
+        let mut traversal = sys::RecastOffMeshTraversal::default();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_off_mesh_traversal(crowd, id.0, &mut traversal as *mut _, err)
+        })?;
+        Ok(OffMeshTraversal::from_sys(&traversal))
+    }
+
+    fn read_agent(&self, agent: i32) -> Result<Option<CrowdAgent>> {
+        let mut state = sys::RecastCrowdAgentState::default();
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_agent(crowd, agent, &mut state as *mut _, err)
+        })?;
+
+        if state.active == 0 {
+            return Ok(None);
+        }
+        Ok(Some(CrowdAgent {
+            pos: Point(state.pos),
+            vel: Point(state.vel),
+            desired_vel: Point(state.desired_vel),
+            poly: state.poly,
+            off_mesh: state.state == sys::DT_CROWDAGENT_STATE_OFFMESH,
+            invalid: state.state == sys::DT_CROWDAGENT_STATE_INVALID,
+            target: MoveTarget::from_sys(state.target_state),
+            target_pos: Point(state.target_pos),
+            partial: state.partial != 0,
+            detached: state.detached != 0,
+        }))
+    }
+
+    pub(crate) fn call<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(*const c_void, *mut sys::RecastNavError) -> i32,
+    {
+        let mut err = sys::RecastNavError::zeros();
+        if f(self.crowd.as_ptr(), &mut err as *mut _) == 0 {
+            Err(Error::CrowdError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Crowd {
+    fn drop(&mut self) {
+        // Before the query which owns the navmesh
+        unsafe { sys::recastc_free_crowd(self.crowd.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{plane, plane_query};
+    use crate::{BuildConfig, Closest, InputGeometry, OffMeshConnection, OffMeshPhase};
+
+    fn crowd() -> Crowd {
+        Crowd::new(plane_query(), 8, 0.6).unwrap()
+    }
+
+    fn distance(a: Point, b: Point) -> f32 {
+        ((a.x() - b.x()).powi(2) + (a.z() - b.z()).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn test_move_to_target() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let target: Point = (9.0, 0.0, 9.0).into();
+        crowd.request_move_target(id, target).unwrap();
+
+        for _ in 0..100 {
+            crowd.update(0.1).unwrap();
+        }
+        let agent = crowd.agent(id).unwrap();
+        assert_eq!(agent.target, MoveTarget::Valid);
+        assert!(distance(agent.pos, target) < 1.0, "{:?}", agent.pos);
+        assert_eq!(crowd.agent_states().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_agents_avoid_each_other() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        let b = crowd.add_agent((9.0, 0.0, 5.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(a, (9.0, 0.0, 5.0).into())
+            .unwrap();
+        crowd
+            .request_move_target(b, (1.0, 0.0, 5.0).into())
+            .unwrap();
+
+        let mut clock = SimClock::new();
+        for _ in 0..60 {
+            crowd.update_with_clock(&mut clock, 0.1).unwrap();
+            let (pa, pb) = (crowd.agent(a).unwrap().pos, crowd.agent(b).unwrap().pos);
+            assert!(distance(pa, pb) > 0.5, "{:?} {:?}", pa, pb);
+        }
+    }
+
+    #[test]
+    fn test_velocity_and_stop() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((5.0, 0.0, 5.0).into(), &params).unwrap();
+
+        // Flee towards +x
+        crowd
+            .request_move_velocity(id, (2.0, 0.0, 0.0).into())
+            .unwrap();
+        for _ in 0..10 {
+            crowd.update(0.1).unwrap();
+        }
+        let agent = crowd.agent(id).unwrap();
+        assert_eq!(agent.target, MoveTarget::Velocity);
+        assert!(agent.pos.x() > 6.0, "{:?}", agent.pos);
+
+        crowd.reset_move_target(id).unwrap();
+        for _ in 0..20 {
+            crowd.update(0.1).unwrap();
+        }
+        let stopped = crowd.agent(id).unwrap();
+        assert_eq!(stopped.target, MoveTarget::None);
+        crowd.update(0.1).unwrap();
+        assert!(distance(crowd.agent(id).unwrap().pos, stopped.pos) < 0.01);
+    }
+
+    #[test]
+    fn test_bulk_move_orders() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let b = crowd.add_agent((1.0, 0.0, 3.0).into(), &params).unwrap();
+
+        let results = crowd.request_move_targets(
+            &[(a, (8.0, 0.0, 8.0).into()), (b, (50.0, 0.0, 50.0).into())],
+            &mut Closest,
+        );
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert_eq!(crowd.agent(b).unwrap().target, MoveTarget::None);
+    }
+
+    #[test]
+    fn test_detach_and_attach() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (9.0, 0.0, 9.0).into())
+            .unwrap();
+        crowd.update(0.1).unwrap();
+
+        crowd.detach_agent(id).unwrap();
+        assert!(crowd
+            .request_move_target(id, (9.0, 0.0, 1.0).into())
+            .is_err());
+        // Moved by a cutscene, not by the crowd
+        let cutscene: Point = (3.0, 0.0, 7.0).into();
+        crowd.move_detached_agent(id, cutscene).unwrap();
+        for _ in 0..10 {
+            crowd.update(0.1).unwrap();
+        }
+        let agent = crowd.agent(id).unwrap();
+        assert!(agent.detached);
+        assert_eq!(agent.pos, cutscene);
+
+        // Same slot, new corridor
+        crowd.attach_agent(id, (8.0, 0.0, 2.0).into()).unwrap();
+        let agent = crowd.agent(id).unwrap();
+        assert!(!agent.detached && !agent.invalid);
+        assert_eq!(agent.target, MoveTarget::None);
+        assert!(distance(agent.pos, (8.0, 0.0, 2.0).into()) < 0.1);
+        assert!(crowd.attach_agent(id, (8.0, 0.0, 2.0).into()).is_err());
+
+        let target: Point = (2.0, 0.0, 2.0).into();
+        crowd.request_move_target(id, target).unwrap();
+        for _ in 0..50 {
+            crowd.update(0.1).unwrap();
+        }
+        assert!(distance(crowd.agent(id).unwrap().pos, target) < 1.0);
+    }
+
+    #[test]
+    fn test_teleport() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (9.0, 0.0, 9.0).into())
+            .unwrap();
+        for _ in 0..5 {
+            crowd.update(0.1).unwrap();
+        }
+        assert!(distance(crowd.agent(id).unwrap().vel, (0.0, 0.0, 0.0).into()) > 0.0);
+
+        let portal: Point = (8.0, 0.0, 2.0).into();
+        crowd.teleport_agent(id, portal).unwrap();
+        let agent = crowd.agent(id).unwrap();
+        assert!(distance(agent.pos, portal) < 0.1);
+        assert_eq!(agent.vel, (0.0, 0.0, 0.0).into());
+        assert_eq!(agent.target, MoveTarget::None);
+        assert_eq!(crowd.agent_params(id).unwrap(), params);
+        for _ in 0..5 {
+            crowd.update(0.1).unwrap();
+        }
+        assert!(distance(crowd.agent(id).unwrap().pos, portal) < 0.1);
+
+        // Off the navmesh
+        assert!(crowd.teleport_agent(id, (50.0, 0.0, 50.0).into()).is_err());
+        crowd.detach_agent(id).unwrap();
+        assert!(crowd.teleport_agent(id, portal).is_err());
+    }
+
+    #[test]
+    fn test_avoidance_params() {
+        let mut crowd = crowd();
+        assert_eq!(
+            crowd.avoidance_params(0).unwrap(),
+            ObstacleAvoidanceParams::low()
+        );
+        assert_eq!(
+            crowd.avoidance_params(1).unwrap(),
+            ObstacleAvoidanceParams::medium()
+        );
+        assert_eq!(
+            crowd.avoidance_params(2).unwrap(),
+            ObstacleAvoidanceParams::good()
+        );
+        assert_eq!(
+            crowd.avoidance_params(3).unwrap(),
+            ObstacleAvoidanceParams::high()
+        );
+        assert_eq!(
+            crowd.avoidance_params(4).unwrap(),
+            ObstacleAvoidanceParams::default()
+        );
+        assert!(crowd.avoidance_params(MAX_AVOIDANCE_SLOTS).is_err());
+
+        // A cheap slot for background agents
+        let cheap = ObstacleAvoidanceParams {
+            adaptive_divs: 3,
+            adaptive_rings: 1,
+            adaptive_depth: 1,
+            ..ObstacleAvoidanceParams::low()
+        };
+        crowd.set_avoidance_params(4, &cheap).unwrap();
+        assert_eq!(crowd.avoidance_params(4).unwrap(), cheap);
+        assert!(crowd
+            .set_avoidance_params(
+                4,
+                &ObstacleAvoidanceParams {
+                    adaptive_divs: 0,
+                    ..cheap
+                }
+            )
+            .is_err());
+
+        let params = CrowdAgentParams {
+            avoidance_slot: 4,
+            ..CrowdAgentParams::new(0.5, 2.0, 3.5)
+        };
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (9.0, 0.0, 9.0).into())
+            .unwrap();
+        for _ in 0..100 {
+            crowd.update(0.1).unwrap();
+        }
+        assert!(distance(crowd.agent(id).unwrap().pos, (9.0, 0.0, 9.0).into()) < 1.0);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((2.0, 0.0, 2.0).into(), &params).unwrap();
+        let b = crowd.add_agent((3.5, 0.0, 2.0).into(), &params).unwrap();
+        let c = crowd.add_agent((5.0, 0.0, 2.0).into(), &params).unwrap();
+        // Beyond the collision query range of 6
+        let far = crowd.add_agent((9.0, 0.0, 9.0).into(), &params).unwrap();
+        crowd.update(0.01).unwrap();
+
+        let neighbours = crowd.neighbours(a).unwrap();
+        let ids: Vec<_> = neighbours.iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![b, c]);
+        let d = distance(crowd.agent(a).unwrap().pos, crowd.agent(b).unwrap().pos);
+        assert!((neighbours[0].distance - d).abs() < 0.05);
+        assert!(neighbours[0].distance < neighbours[1].distance);
+        assert!(crowd.neighbours(far).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_neighbours_after_remove() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((2.0, 0.0, 2.0).into(), &params).unwrap();
+        let b = crowd.add_agent((3.5, 0.0, 2.0).into(), &params).unwrap();
+        let c = crowd.add_agent((5.0, 0.0, 2.0).into(), &params).unwrap();
+        crowd.update(0.01).unwrap();
+
+        // The removed agent is left out until the next update
+        crowd.remove_agent(a).unwrap();
+        let ids: Vec<_> = crowd.neighbours(c).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![b]);
+
+        // The crowd keeps positions in its active list, which no longer starts with `a`
+        crowd.update(0.01).unwrap();
+        let ids: Vec<_> = crowd.neighbours(c).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![b]);
+        let ids: Vec<_> = crowd.neighbours(b).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![c]);
+
+        // A new agent in the slot of `a` is a neighbour after the next update
+        let d = crowd.add_agent((2.0, 0.0, 2.0).into(), &params).unwrap();
+        assert_eq!(d, a);
+        let ids: Vec<_> = crowd.neighbours(c).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![b]);
+        crowd.update(0.01).unwrap();
+        let ids: Vec<_> = crowd.neighbours(c).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![b, d]);
+    }
+
+    #[test]
+    fn test_agents_within() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((2.0, 0.0, 2.0).into(), &params).unwrap();
+        let b = crowd.add_agent((4.0, 0.0, 2.0).into(), &params).unwrap();
+        let far = crowd.add_agent((8.0, 0.0, 8.0).into(), &params).unwrap();
+        // The grid is filled by the updates
+        assert!(crowd
+            .agents_within((2.0, 0.0, 2.0).into(), 3.0)
+            .unwrap()
+            .is_empty());
+        crowd.update(0.01).unwrap();
+
+        let found = crowd.agents_within((2.5, 0.0, 2.0).into(), 3.0).unwrap();
+        assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, b]);
+        assert!(found[0].distance < 1.0 && found[1].distance > 1.0);
+        let found = crowd.agents_within((8.0, 0.0, 8.0).into(), 1.0).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, far);
+
+        crowd.remove_agent(far).unwrap();
+        assert!(crowd
+            .agents_within((8.0, 0.0, 8.0).into(), 1.0)
+            .unwrap()
+            .is_empty());
+        assert!(crowd.agents_within((8.0, 0.0, 8.0).into(), -1.0).is_err());
+    }
+
+    #[test]
+    fn test_agents_within_after_remove() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let b = crowd.add_agent((5.0, 0.0, 2.0).into(), &params).unwrap();
+        let c = crowd.add_agent((8.0, 0.0, 8.0).into(), &params).unwrap();
+        crowd.remove_agent(a).unwrap();
+        crowd.update(0.01).unwrap();
+
+        // The grid of the crowd keeps positions in its active list, which starts with `b`
+        let found = crowd.agents_within((8.0, 0.0, 8.0).into(), 1.0).unwrap();
+        assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), vec![c]);
+        let found = crowd.agents_within((5.0, 0.0, 2.0).into(), 1.0).unwrap();
+        assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), vec![b]);
+
+        // A new agent in a freed slot is found after the next update only
+        crowd.remove_agent(b).unwrap();
+        let d = crowd.add_agent((5.0, 0.0, 2.0).into(), &params).unwrap();
+        assert!(crowd
+            .agents_within((5.0, 0.0, 2.0).into(), 1.0)
+            .unwrap()
+            .is_empty());
+        crowd.update(0.01).unwrap();
+        let found = crowd.agents_within((5.0, 0.0, 2.0).into(), 1.0).unwrap();
+        assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), vec![d]);
+    }
+
+    #[test]
+    fn test_grow_capacity() {
+        let mut crowd = Crowd::new(plane_query(), 2, 0.6).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let gone = crowd.add_agent((1.0, 0.0, 9.0).into(), &params).unwrap();
+        assert!(crowd.add_agent((5.0, 0.0, 5.0).into(), &params).is_err());
+        crowd.remove_agent(gone).unwrap();
+        let target: Point = (9.0, 0.0, 9.0).into();
+        crowd.request_move_target(a, target).unwrap();
+        for _ in 0..10 {
+            crowd.update(0.1).unwrap();
+        }
+        let before = crowd.agent(a).unwrap();
+
+        assert!(crowd.set_capacity(1, 0.6).is_err());
+        assert!(crowd.set_capacity(4, 0.5).is_err());
+        crowd.reserve(8).unwrap();
+        assert_eq!(crowd.max_agents(), 9);
+        assert_eq!(crowd.agent(a).unwrap(), before);
+        assert!(crowd.agent(gone).is_err());
+        assert_eq!(crowd.agent_params(a).unwrap(), params);
+
+        // The free slot is reused first, then the new ones
+        let ids: Vec<_> = (0..8)
+            .map(|i| {
+                let pos = (1.0 + i as f32, 0.0, 5.0).into();
+                crowd.add_agent(pos, &params).unwrap()
+            })
+            .collect();
+        assert_eq!(ids[0], gone);
+        assert!(crowd.add_agent((5.0, 0.0, 8.0).into(), &params).is_err());
+
+        // Still on its way
+        for _ in 0..100 {
+            crowd.update(0.1).unwrap();
+        }
+        let agent = crowd.agent(a).unwrap();
+        assert_eq!(agent.target, MoveTarget::Valid);
+        assert!(distance(agent.pos, target) < 1.5, "{:?}", agent.pos);
+
+        crowd.set_capacity(9, 1.0).unwrap();
+        crowd.remove_agent(ids[1]).unwrap();
+        crowd
+            .add_agent(
+                (5.0, 0.0, 5.0).into(),
+                &CrowdAgentParams::new(1.0, 2.0, 3.5),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fixed_update() {
+        let run = |frames: &[f32]| {
+            let mut crowd = crowd();
+            let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+            let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+            crowd
+                .request_move_target(id, (9.0, 0.0, 9.0).into())
+                .unwrap();
+
+            let mut timestep = FixedTimestep::new(1.0 / 30.0);
+            let mut interpolation = CrowdInterpolation::default();
+            for &dt in frames {
+                crowd
+                    .update_fixed(&mut timestep, dt, &mut interpolation)
+                    .unwrap();
+            }
+            (
+                timestep.ticks(),
+                crowd.agent(id).unwrap().pos,
+                interpolation,
+            )
+        };
+
+        // The same steps however the time is split into frames
+        let (ticks, pos, _) = run(&[0.1; 10]);
+        let (other_ticks, other_pos, interpolation) = run(&[0.05; 20]);
+        assert_eq!(ticks, 30);
+        assert_eq!(other_ticks, ticks);
+        assert_eq!(other_pos, pos);
+
+        let agent = interpolation.agents[0];
+        assert_eq!(agent.pos, pos);
+        assert!(distance(agent.prev, agent.pos) > 0.0);
+        let (_, drawn) = interpolation.positions().next().unwrap();
+        assert!(distance(drawn, agent.prev) <= distance(agent.pos, agent.prev) + 1e-5);
+
+        // No step, the last interpolation is kept
+        let (_, _, short) = run(&[0.1, 0.01]);
+        assert_eq!(short.steps, 0);
+        assert_eq!(short.agents.len(), 1);
+        assert!(short.alpha > 0.2 && short.alpha < 0.4, "{}", short.alpha);
+    }
+
+    #[test]
+    fn test_change_tiles() {
+        let geom = plane(10.0);
+        let q = RecastQuery::build_tiled(&geom, &BuildConfig::default(), 16).unwrap();
+        let mut crowd = Crowd::new(q, 8, 0.6).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (9.0, 0.0, 9.0).into())
+            .unwrap();
+        crowd.update(0.1).unwrap();
+
+        // The crowd keeps running on the same navmesh while the tile leaves and comes back
+        let saved = crowd.query().tile_data(1, 1).unwrap();
+        crowd.remove_tile(1, 1).unwrap();
+        crowd.update(0.1).unwrap();
+        crowd.add_tile(saved).unwrap();
+        for _ in 0..100 {
+            crowd.update(0.1).unwrap();
+        }
+        let pos = crowd.agent(id).unwrap().pos;
+        assert!(distance(pos, (9.0, 0.0, 9.0).into()) < 1.0, "{:?}", pos);
+    }
+
+    #[test]
+    fn test_agent_slots() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        assert!(crowd
+            .add_agent(
+                (5.0, 0.0, 5.0).into(),
+                &CrowdAgentParams::new(1.0, 2.0, 3.5)
+            )
+            .is_err());
+
+        let id = crowd.add_agent((5.0, 0.0, 5.0).into(), &params).unwrap();
+        crowd.remove_agent(id).unwrap();
+        assert!(crowd.agent(id).is_err());
+        assert!(crowd.remove_agent(id).is_err());
+        assert!(crowd.agents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_modify_agent() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        assert_eq!(crowd.agent_params(id).unwrap(), params);
+        crowd
+            .request_move_target(id, (9.0, 0.0, 5.0).into())
+            .unwrap();
+        for _ in 0..10 {
+            crowd.update(0.1).unwrap();
+        }
+
+        // Slowed down without losing its target
+        let slow = crowd
+            .modify_agent(id, |p| {
+                p.max_speed = 1.0;
+                p.avoidance_slot = 0;
+            })
+            .unwrap();
+        assert_eq!(crowd.agent_params(id).unwrap(), slow);
+        assert_eq!(slow.radius, params.radius);
+        for _ in 0..10 {
+            crowd.update(0.1).unwrap();
+        }
+        let agent = crowd.agent(id).unwrap();
+        assert_eq!(agent.target, MoveTarget::Valid);
+        assert!(distance(agent.vel, (0.0, 0.0, 0.0).into()) <= 1.01);
+
+        // Validated like a new agent
+        assert!(crowd.modify_agent(id, |p| p.radius = 1.0).is_err());
+        assert_eq!(crowd.agent_params(id).unwrap(), slow);
+        crowd.remove_agent(id).unwrap();
+        assert!(crowd.agent_params(id).is_err());
+    }
+
+    /// Two platforms with a gap between x = 4 and x = 6, crossed by a link
+    fn gap() -> RecastQuery {
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                6.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 6.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            off_mesh_connections: vec![OffMeshConnection {
+                user_id: 42,
+                ..OffMeshConnection::new((3.0, 0.0, 5.0).into(), (7.0, 0.0, 5.0).into(), 0.5)
+            }],
+            ..Default::default()
+        };
+        RecastQuery::build(&geom, &BuildConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_off_mesh_traversal() {
+        let mut crowd = Crowd::new(gap(), 8, 0.6).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        assert_eq!(crowd.off_mesh_traversal(id).unwrap(), None);
+        let target: Point = (9.0, 0.0, 5.0).into();
+        crowd.request_move_target(id, target).unwrap();
+
+        let mut phases = vec![];
+        for _ in 0..100 {
+            crowd.update(0.05).unwrap();
+            if let Some(tr) = crowd.off_mesh_traversal(id).unwrap() {
+                assert_eq!(tr.user_id, 42);
+                assert!(tr.start.x() < 4.0 && tr.end.x() > 6.0, "{:?}", tr);
+                assert_eq!(
+                    crowd.agent(id).unwrap().off_mesh,
+                    tr.phase != OffMeshPhase::Exiting
+                );
+                if phases.last() != Some(&tr.phase) {
+                    phases.push(tr.phase);
+                }
+            }
+        }
+        assert_eq!(
+            phases,
+            vec![
+                OffMeshPhase::Entering,
+                OffMeshPhase::Traversing,
+                OffMeshPhase::Exiting
+            ]
+        );
+        assert_eq!(crowd.off_mesh_traversal(id).unwrap(), None);
+        assert!(distance(crowd.agent(id).unwrap().pos, target) < 1.0);
+    }
+
+    #[test]
+    fn test_off_mesh_traversal_on_first_update() {
+        let mut crowd = Crowd::new(gap(), 8, 0.6).unwrap();
+        // Close enough to the link to step over it in the update which finds the path
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((3.3, 0.0, 5.0).into(), &params).unwrap();
+        let still = CrowdAgentParams {
+            max_speed: 0.0,
+            ..params
+        };
+        let still = crowd.add_agent((3.3, 0.0, 6.0).into(), &still).unwrap();
+        for &agent in &[id, still] {
+            crowd
+                .request_move_target(agent, (9.0, 0.0, 5.0).into())
+                .unwrap();
+        }
+        crowd.update(0.05).unwrap();
+
+        let tr = crowd.off_mesh_traversal(id).unwrap().unwrap();
+        assert_eq!(tr.user_id, 42);
+        assert_ne!(tr.link, 0);
+        assert!(tr.duration > 0.0);
+        let tr = crowd.off_mesh_traversal(still).unwrap().unwrap();
+        assert_eq!(tr.user_id, 42);
+        assert_eq!(tr.duration, 0.0);
+    }
+
+    #[test]
+    fn test_elevator_gate() {
+        // A floor at y = 0 and one at y = 4 above x > 6, with an elevator between them
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                6.0, 4.0, 0.0, 10.0, 4.0, 0.0, 10.0, 4.0, 10.0, 6.0, 4.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            off_mesh_connections: OffMeshConnection::elevator(
+                (3.0, 0.0, 5.0).into(),
+                (7.0, 4.0, 5.0).into(),
+                0.5,
+                1,
+                2,
+            )
+            .to_vec(),
+            ..Default::default()
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let mut crowd = Crowd::new(q, 8, 0.6).unwrap();
+        crowd.close_gate(1).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        let target: Point = (9.0, 4.0, 5.0).into();
+        crowd.request_move_target(id, target).unwrap();
+
+        for _ in 0..100 {
+            crowd.update(0.05).unwrap();
+            assert!(!crowd.agent(id).unwrap().off_mesh);
+        }
+        let agent = crowd.agent(id).unwrap();
+        assert_eq!(crowd.waiting_at_gate(id).unwrap(), Some(1));
+        assert_eq!(agent.target, MoveTarget::Valid);
+        assert!(
+            distance(agent.pos, (3.0, 0.0, 5.0).into()) < 2.0,
+            "{:?}",
+            agent
+        );
+
+        crowd.open_gate(1).unwrap();
+        for _ in 0..100 {
+            crowd.update(0.05).unwrap();
+        }
+        assert_eq!(crowd.waiting_at_gate(id).unwrap(), None);
+        assert!(distance(crowd.agent(id).unwrap().pos, target) < 1.0);
+        assert!(crowd.close_gate(0).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Steering internals of crowd agents for debug overlays, e.g. to see why an agent steers oddly
+use crate::{AgentId, Crowd, Point, PolyRef, Result, WallSegment, MAX_BOUNDARY_SEGMENTS};
+
+/// Most polys of the corridor of a crowd agent, the path size of `dtCrowd`
+const MAX_CORRIDOR_POLYS: usize = 256;
+
+/// A velocity tried by the obstacle avoidance, see `dtObstacleAvoidanceDebugData`.
+/// The penalties are normalized to 0..1 over the samples of the update
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AvoidanceSample {
+    pub vel: Point,
+    /// Radius of the sample, to draw it
+    pub size: f32,
+    /// The avoidance picks the sample with the lowest penalty
+    pub penalty: f32,
+    pub desired_vel_penalty: f32,
+    pub current_vel_penalty: f32,
+    pub side_penalty: f32,
+    /// Penalty of the time to impact
+    pub toi_penalty: f32,
+}
+
+/// The steering of a crowd agent as of the last update, plain data for a debug overlay
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentDebug {
+    pub id: AgentId,
+    /// Polys of the corridor from the agent to its target
+    pub corridor: Vec<PolyRef>,
+    /// The walls the obstacle avoidance steers around
+    pub walls: Vec<WallSegment>,
+    /// The velocities the obstacle avoidance tried, only for the agent of `Crowd::set_debug_agent`
+    pub samples: Vec<AvoidanceSample>,
+    /// The shortcut the visibility optimization of the corridor tried, only for the agent of
+    /// `Crowd::set_debug_agent`
+    pub visibility_optimization: Option<(Point, Point)>,
+}
+
+impl AgentDebug {
+    /// The sample the obstacle avoidance picked
+    pub fn best_sample(&self) -> Option<&AvoidanceSample> {
+        self.samples
+            .iter()
+            .min_by(|a, b| a.penalty.total_cmp(&b.penalty))
+    }
+}
+
+impl Crowd {
+    /// Record the avoidance samples and visibility optimization of the agent in the next updates,
+    /// `None` to stop. One agent at a time, like the crowd tool of RecastDemo
+    pub fn set_debug_agent(&mut self, id: Option<AgentId>) -> Result<()> {
+        let agent = id.map_or(-1, |id| id.index() as i32);
+        self.call(|crowd, err| unsafe { sys::recastc_crowd_set_debug_agent(crowd, agent, err) })
+    }
+
+    /// The corridor and walls of the agent, with the samples of the debug agent
+    pub fn debug_agent(&self, id: AgentId) -> Result<AgentDebug> {
+        let agent = id.index() as i32;
+
+        let mut polys = vec![0; MAX_CORRIDOR_POLYS];
+        let mut poly_count = 0;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_agent_corridor(
+                crowd,
+                agent,
+                polys.as_mut_ptr(),
+                polys.len() as i32,
+                &mut poly_count,
+                err,
+            )
+        })?;
+        polys.truncate(poly_count as usize);
+
+        let mut segs = [0.0; MAX_BOUNDARY_SEGMENTS * 6];
+        let mut seg_count = 0;
+        self.call(|crowd, err| unsafe {
+            sys::recastc_crowd_get_agent_walls(
+                crowd,
+                agent,
+                segs.as_mut_ptr(),
+                MAX_BOUNDARY_SEGMENTS as i32,
+                &mut seg_count,
+                err,
+            )
+        })?;
+        let walls = segs
+            .chunks(6)
+            .take(seg_count as usize)
+            .map(|s| WallSegment {
+                start: (s[0], s[1], s[2]).into(),
+                end: (s[3], s[4], s[5]).into(),
+            })
+            .collect();
+
+        let (info, samples) = self.read_debug_info()?;
+        let (samples, visibility_optimization) = if info.agent == agent {
+            let opt = if info.optimized != 0 {
+                Some((Point(info.opt_start), Point(info.opt_end)))
+            } else {
+                None
+            };
+            (samples, opt)
+        } else {
+            (vec![], None)
+        };
+
+        Ok(AgentDebug {
+            id,
+            corridor: polys,
+            walls,
+            samples,
+            visibility_optimization,
+        })
+    }
+
+    fn read_debug_info(&self) -> Result<(sys::RecastCrowdDebugInfo, Vec<AvoidanceSample>)> {
+        let mut samples: Vec<sys::RecastAvoidanceSample> = vec![];
+        loop {
+            let mut info = sys::RecastCrowdDebugInfo::default();
+            let mut count = 0;
+            let res = self.call(|crowd, err| unsafe {
+                sys::recastc_crowd_get_debug_info(
+                    crowd,
+                    &mut info as *mut _,
+                    samples.as_mut_ptr(),
+                    samples.len() as i32,
+                    &mut count,
+                    err,
+                )
+            });
+
+            let count = count as usize;
+            match res {
+                Ok(()) => {
+                    let samples = samples[..count]
+                        .iter()
+                        .map(|s| AvoidanceSample {
+                            vel: Point(s.vel),
+                            size: s.size,
+                            penalty: s.penalty,
+                            desired_vel_penalty: s.desired_vel_penalty,
+                            current_vel_penalty: s.current_vel_penalty,
+                            side_penalty: s.side_penalty,
+                            toi_penalty: s.toi_penalty,
+                        })
+                        .collect();
+                    return Ok((info, samples));
+                }
+                Err(e) if count <= samples.len() => return Err(e),
+                Err(_) => samples.resize(count, sys::RecastAvoidanceSample::default()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane_query;
+    use crate::CrowdAgentParams;
+
+    fn crowd() -> Crowd {
+        Crowd::new(plane_query(), 8, 0.6).unwrap()
+    }
+
+    #[test]
+    fn test_debug_agent() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        let b = crowd.add_agent((9.0, 0.0, 5.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(a, (9.0, 0.0, 5.0).into())
+            .unwrap();
+        crowd
+            .request_move_target(b, (1.0, 0.0, 5.0).into())
+            .unwrap();
+
+        crowd.set_debug_agent(Some(a)).unwrap();
+        for _ in 0..5 {
+            crowd.update(0.1).unwrap();
+        }
+
+        let debug = crowd.debug_agent(a).unwrap();
+        assert_eq!(debug.id, a);
+        assert!(!debug.corridor.is_empty());
+        assert_eq!(debug.corridor[0], crowd.agent(a).unwrap().poly);
+        // The edge along x = 0 is within the collision query range
+        assert!(!debug.walls.is_empty());
+        assert!(!debug.samples.is_empty());
+        assert!(debug.best_sample().unwrap().penalty <= debug.samples[0].penalty);
+        assert!(debug.visibility_optimization.is_some());
+
+        // Only the corridor and walls of the other agents
+        let other = crowd.debug_agent(b).unwrap();
+        assert!(!other.corridor.is_empty());
+        assert!(other.samples.is_empty() && other.visibility_optimization.is_none());
+
+        crowd.set_debug_agent(None).unwrap();
+        crowd.update(0.1).unwrap();
+        assert!(crowd.debug_agent(a).unwrap().samples.is_empty());
+
+        crowd.remove_agent(a).unwrap();
+        assert!(crowd.debug_agent(a).is_err());
+        assert!(crowd.set_debug_agent(Some(a)).is_err());
+    }
+
+    #[test]
+    fn test_debug_agent_after_remove() {
+        let mut crowd = crowd();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let a = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let b = crowd.add_agent((1.0, 0.0, 5.0).into(), &params).unwrap();
+        let c = crowd.add_agent((9.0, 0.0, 5.0).into(), &params).unwrap();
+        crowd.remove_agent(a).unwrap();
+        for &(id, x) in &[(b, 9.0), (c, 1.0)] {
+            crowd.request_move_target(id, (x, 0.0, 5.0).into()).unwrap();
+        }
+
+        // The crowd counts `c` second in its active list, not at its slot
+        crowd.set_debug_agent(Some(c)).unwrap();
+        for _ in 0..5 {
+            crowd.update(0.1).unwrap();
+        }
+        assert!(!crowd.debug_agent(c).unwrap().samples.is_empty());
+        assert!(crowd.debug_agent(b).unwrap().samples.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Movement events of crowd agents, from the changes of their state between updates
+use crate::{
+    AgentId, Crowd, MoveTarget, OffMeshPhase, Point, PolyRef, Result, StuckDetector, StuckEvent,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Something that happened to an agent during an update
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CrowdEvent {
+    /// The agent got within the arrival distance of the end of a full path, once per target
+    TargetReached {
+        agent: AgentId,
+        pos: Point,
+    },
+    /// The path found for the target only gets near it
+    PathPartial {
+        agent: AgentId,
+    },
+    /// No path to the target, or the target left the navmesh
+    PathFailed {
+        agent: AgentId,
+    },
+    /// The agent wants to move but made no progress for the stuck window
+    Stuck {
+        agent: AgentId,
+        pos: Point,
+    },
+    Unstuck {
+        agent: AgentId,
+    },
+    OffMeshStarted {
+        agent: AgentId,
+        link: PolyRef,
+        user_id: u32,
+    },
+    OffMeshFinished {
+        agent: AgentId,
+        link: PolyRef,
+        user_id: u32,
+    },
+}
+
+/// What the events of an agent were derived from so far
+#[derive(Debug, Copy, Clone)]
+struct Tracked {
+    /// `Crowd::generation` of the slot, another agent added to it starts over
+    generation: u32,
+    target: MoveTarget,
+    target_pos: Point,
+    partial: bool,
+    reached: bool,
+    /// The connection being crossed and its user id
+    link: Option<(PolyRef, u32)>,
+}
+
+/// Turns the agent states after each update into events.
+///
+/// The crowd itself does not report arrivals or failures, this compares every agent with its
+/// state after the previous call. Agents removed since are forgotten without event, as are
+/// those whose slot went to a new agent in between
+#[derive(Debug, Clone)]
+pub struct CrowdEvents {
+    /// Distance on the xz plane to the end of the path to count as arrived
+    pub arrival_distance: f32,
+    stuck: StuckDetector<AgentId>,
+    agents: HashMap<AgentId, Tracked>,
+}
+
+impl CrowdEvents {
+    /// `stuck_window` seconds without progress make an agent stuck, see `StuckDetector`
+    pub fn new(arrival_distance: f32, stuck_window: f32) -> CrowdEvents {
+        CrowdEvents::with_stuck_detector(arrival_distance, StuckDetector::new(stuck_window, 0.1))
+    }
+
+    pub fn with_stuck_detector(
+        arrival_distance: f32,
+        stuck: StuckDetector<AgentId>,
+    ) -> CrowdEvents {
+        CrowdEvents {
+            arrival_distance,
+            stuck,
+            agents: HashMap::new(),
+        }
+    }
+
+    pub fn is_stuck(&self, agent: AgentId) -> bool {
+        self.stuck.is_stuck(agent)
+    }
+
+    /// The events of the agents since the last call, after an update of `dt` seconds
+    pub fn update(&mut self, crowd: &Crowd, dt: f32) -> Result<Vec<CrowdEvent>> {
+        let mut events = vec![];
+        let agents = crowd.agents()?;
+
+        let alive: HashSet<(AgentId, u32)> = agents
+            .iter()
+            .map(|(id, _)| (*id, crowd.generation(*id)))
+            .collect();
+        let stuck = &mut self.stuck;
+        self.agents.retain(|id, tracked| {
+            let keep = alive.contains(&(*id, tracked.generation));
+            if !keep {
+                stuck.reset(*id);
+            }
+            keep
+        });
+
+        for (id, agent) in agents {
+            let prev = self.agents.get(&id).copied().unwrap_or(Tracked {
+                generation: crowd.generation(id),
+                target: MoveTarget::None,
+                target_pos: agent.target_pos,
+                partial: false,
+                reached: false,
+                link: None,
+            });
+            let same_target = prev.target == agent.target && prev.target_pos == agent.target_pos;
+            let mut reached = prev.reached && same_target;
+
+            match agent.target {
+                MoveTarget::Valid if agent.partial && !(same_target && prev.partial) => {
+                    events.push(CrowdEvent::PathPartial { agent: id })
+                }
+                MoveTarget::Valid if !agent.partial && !reached => {
+                    let (dx, dz) = (
+                        agent.pos.x() - agent.target_pos.x(),
+                        agent.pos.z() - agent.target_pos.z(),
+                    );
+                    if (dx * dx + dz * dz).sqrt() <= self.arrival_distance {
+                        reached = true;
+                        events.push(CrowdEvent::TargetReached {
+                            agent: id,
+                            pos: agent.pos,
+                        });
+                    }
+                }
+                MoveTarget::Failed if prev.target != MoveTarget::Failed => {
+                    events.push(CrowdEvent::PathFailed { agent: id })
+                }
+                _ => {}
+            }
+
+            let traversal = crowd.off_mesh_traversal(id)?;
+            let on_link = traversal.filter(|t| t.phase != OffMeshPhase::Exiting);
+            let link = match (prev.link, on_link) {
+                (None, Some(t)) => {
+                    events.push(CrowdEvent::OffMeshStarted {
+                        agent: id,
+                        link: t.link,
+                        user_id: t.user_id,
+                    });
+                    Some((t.link, t.user_id))
+                }
+                (Some((link, user_id)), None) => {
+                    events.push(CrowdEvent::OffMeshFinished {
+                        agent: id,
+                        link,
+                        user_id,
+                    });
+                    None
+                }
+                (link, _) => link,
+            };
+
+            // Not stuck on purpose: arrived, jumping or moved by something else
+            let desired_speed = if reached || agent.off_mesh || agent.detached {
+                0.0
+            } else {
+                (agent.desired_vel.x().powi(2) + agent.desired_vel.z().powi(2)).sqrt()
+            };
+            match self.stuck.update(id, agent.pos, desired_speed, dt) {
+                Some(StuckEvent::Stuck { agent, position }) => events.push(CrowdEvent::Stuck {
+                    agent,
+                    pos: position,
+                }),
+                Some(StuckEvent::Unstuck { agent }) => events.push(CrowdEvent::Unstuck { agent }),
+                None => {}
+            }
+
+            self.agents.insert(
+                id,
+                Tracked {
+                    generation: prev.generation,
+                    target: agent.target,
+                    target_pos: agent.target_pos,
+                    partial: agent.partial,
+                    reached,
+                    link,
+                },
+            );
+        }
+        Ok(events)
+    }
+}
+
+impl Crowd {
+    /// Update the crowd and return the events of its agents
+    pub fn update_with_events(
+        &mut self,
+        dt: f32,
+        events: &mut CrowdEvents,
+    ) -> Result<Vec<CrowdEvent>> {
+        self.update(dt)?;
+        events.update(self, dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, CrowdAgentParams, InputGeometry, RecastQuery};
+
+    fn crowd() -> Crowd {
+        // A room and an island
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                20.0, 0.0, 0.0, 24.0, 0.0, 0.0, 24.0, 0.0, 4.0, 20.0, 0.0, 4.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            ..Default::default()
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        Crowd::new(q, 8, 0.6).unwrap()
+    }
+
+    fn run(crowd: &mut Crowd, events: &mut CrowdEvents, updates: usize) -> Vec<CrowdEvent> {
+        (0..updates)
+            .flat_map(|_| crowd.update_with_events(0.1, events).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_target_reached_once() {
+        let mut crowd = crowd();
+        let mut events = CrowdEvents::new(0.5, 1.0);
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (8.0, 0.0, 8.0).into())
+            .unwrap();
+
+        let all = run(&mut crowd, &mut events, 100);
+        let reached: Vec<_> = all
+            .iter()
+            .filter(|e| matches!(e, CrowdEvent::TargetReached { .. }))
+            .collect();
+        assert_eq!(reached.len(), 1, "{:?}", all);
+        assert!(!all.iter().any(|e| matches!(e, CrowdEvent::Stuck { .. })));
+
+        // A new target can be reached again
+        crowd
+            .request_move_target(id, (2.0, 0.0, 2.0).into())
+            .unwrap();
+        let all = run(&mut crowd, &mut events, 100);
+        assert_eq!(
+            all.iter()
+                .filter(|e| matches!(e, CrowdEvent::TargetReached { agent, .. } if *agent == id))
+                .count(),
+            1
+        );
+
+        crowd.remove_agent(id).unwrap();
+        assert!(run(&mut crowd, &mut events, 1).is_empty());
+    }
+
+    #[test]
+    fn test_reused_slot() {
+        let mut crowd = crowd();
+        let mut events = CrowdEvents::new(0.5, 1.0);
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        crowd
+            .request_move_target(id, (8.0, 0.0, 8.0).into())
+            .unwrap();
+        run(&mut crowd, &mut events, 100);
+
+        // Replaced between two calls by an agent with the same target, which has not arrived
+        crowd.remove_agent(id).unwrap();
+        let new = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        assert_eq!(new, id);
+        crowd
+            .request_move_target(new, (8.0, 0.0, 8.0).into())
+            .unwrap();
+        let all = run(&mut crowd, &mut events, 100);
+        assert_eq!(
+            all.iter()
+                .filter(|e| matches!(e, CrowdEvent::TargetReached { .. }))
+                .count(),
+            1,
+            "{:?}",
+            all
+        );
+    }
+
+    #[test]
+    fn test_partial_path() {
+        let mut crowd = crowd();
+        let mut events = CrowdEvents::new(0.5, 1.0);
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let id = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        // On the island, found but not reachable
+        crowd
+            .request_move_target(id, (22.0, 0.0, 2.0).into())
+            .unwrap();
+
+        let all = run(&mut crowd, &mut events, 100);
+        assert_eq!(
+            all.iter()
+                .filter(|e| **e == CrowdEvent::PathPartial { agent: id })
+                .count(),
+            1,
+            "{:?}",
+            all
+        );
+        // Stays at the end of the partial path without arriving
+        assert!(!all
+            .iter()
+            .any(|e| matches!(e, CrowdEvent::TargetReached { .. })));
+    }
+}
@@ -0,0 +1,251 @@
+//! Navmesh overlays as plain triangles and lines, for any renderer
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+use std::collections::HashSet;
+
+/// What a debug line is the edge of
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DebugEdgeKind {
+    /// Nothing walkable across, a wall or a tile border without neighbour tile
+    Boundary,
+    /// Between two polys of the same tile
+    Internal,
+    /// Between two tiles
+    Portal,
+    /// From the start to the end of an off-mesh connection
+    OffMeshConnection,
+}
+
+impl DebugEdgeKind {
+    /// The color of RecastDemo for the edges of this kind, RGBA
+    pub fn color(self) -> [u8; 4] {
+        match self {
+            DebugEdgeKind::Boundary => [0, 48, 64, 220],
+            DebugEdgeKind::Internal => [0, 48, 64, 32],
+            DebugEdgeKind::Portal => [0, 0, 0, 128],
+            DebugEdgeKind::OffMeshConnection => [255, 196, 0, 220],
+        }
+    }
+}
+
+/// The color of RecastDemo for the polys of an area, RGBA and translucent for an overlay.
+/// The ground area 0 is light blue, the others get a color from the bits of their id
+pub fn area_color(area: u8) -> [u8; 4] {
+    if area == 0 {
+        return [0, 192, 255, 64];
+    }
+    // duIntToCol
+    let bit = |b: u8| (area >> b) & 1;
+    let r = bit(1) + bit(3) * 2 + 1;
+    let g = bit(2) + bit(4) * 2 + 1;
+    let b = bit(0) + bit(5) * 2 + 1;
+    [r * 63, g * 63, b * 63, 64]
+}
+
+/// A triangle of the detail mesh of a poly
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugTriangle {
+    pub poly: PolyRef,
+    pub area: u8,
+    pub verts: [Point; 3],
+    /// `area_color` of the area
+    pub color: [u8; 4],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugLine {
+    pub start: Point,
+    pub end: Point,
+    pub kind: DebugEdgeKind,
+    /// `DebugEdgeKind::color` of the kind
+    pub color: [u8; 4],
+}
+
+/// The navmesh as a triangle list and a line list, see `RecastQuery::debug_draw`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NavMeshDebugDraw {
+    pub triangles: Vec<DebugTriangle>,
+    pub lines: Vec<DebugLine>,
+}
+
+impl NavMeshDebugDraw {
+    pub fn lines_of(&self, kind: DebugEdgeKind) -> impl Iterator<Item = &DebugLine> {
+        self.lines.iter().filter(move |l| l.kind == kind)
+    }
+}
+
+/// The same key for both directions of an edge
+fn edge_key(a: Point, b: Point) -> [u32; 6] {
+    let bits = |p: Point| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+    let (a, b) = (bits(a), bits(b));
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    [a[0], a[1], a[2], b[0], b[1], b[2]]
+}
+
+impl RecastQuery {
+    /// What is across each edge of a poly, edge `i` goes from vertex `i` of `poly_vertices` to
+    /// the next one. None for an off-mesh connection
+    pub fn poly_edge_kinds(&self, poly: PolyRef) -> Result<Vec<DebugEdgeKind>> {
+        let mut kinds = [0; sys::DT_VERTS_PER_POLYGON];
+        let mut count = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_edge_kinds(
+                self.q.as_ptr(),
+                poly,
+                kinds.as_mut_ptr(),
+                &mut count as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::PolyError(err.msg().to_string()));
+        }
+
+        Ok(kinds[..count as usize]
+            .iter()
+            .map(|k| match *k {
+                sys::RECASTC_EDGE_INTERNAL => DebugEdgeKind::Internal,
+                sys::RECASTC_EDGE_PORTAL => DebugEdgeKind::Portal,
+                _ => DebugEdgeKind::Boundary,
+            })
+            .collect())
+    }
+
+    /// The overlay of the polys passing the query filter: the detail triangles colored by area,
+    /// the poly edges and the off-mesh connections. An edge shared by two polys is one line.
+    ///
+    /// The triangles follow the surface the queries snap to, the lines are at the heights of the
+    /// poly vertices and may dip under it on rough ground. Lift them a little when drawing
+    pub fn debug_draw(&self) -> Result<NavMeshDebugDraw> {
+        let mut draw = NavMeshDebugDraw::default();
+        let mut seen = HashSet::new();
+
+        for poly in self.polys()? {
+            let verts = self.poly_vertices(poly)?;
+            let kinds = self.poly_edge_kinds(poly)?;
+
+            let mut line = |start: Point, end: Point, kind: DebugEdgeKind| {
+                if seen.insert((edge_key(start, end), kind)) {
+                    draw.lines.push(DebugLine {
+                        start,
+                        end,
+                        kind,
+                        color: kind.color(),
+                    });
+                }
+            };
+
+            if kinds.is_empty() {
+                if let [start, end] = verts[..] {
+                    line(start, end, DebugEdgeKind::OffMeshConnection);
+                }
+                continue;
+            }
+            for (i, kind) in kinds.into_iter().enumerate() {
+                line(verts[i], verts[(i + 1) % verts.len()], kind);
+            }
+
+            let area = self.poly_area(poly)?;
+            for tri in self.poly_detail_triangles(poly)? {
+                draw.triangles.push(DebugTriangle {
+                    poly,
+                    area,
+                    verts: tri,
+                    color: area_color(area),
+                });
+            }
+        }
+        Ok(draw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, ConvexVolume, InputGeometry, OffMeshConnection};
+
+    fn geometry() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            volumes: vec![ConvexVolume {
+                verts: vec![
+                    (16.0, 0.0, 0.0).into(),
+                    (20.0, 0.0, 0.0).into(),
+                    (20.0, 0.0, 10.0).into(),
+                    (16.0, 0.0, 10.0).into(),
+                ],
+                hmin: -1.0,
+                hmax: 1.0,
+                area: 3,
+            }],
+            off_mesh_connections: vec![OffMeshConnection::new(
+                (2.0, 0.0, 2.0).into(),
+                (2.0, 0.0, 8.0).into(),
+                0.5,
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_area_colors() {
+        assert_eq!(area_color(0), [0, 192, 255, 64]);
+        assert_eq!(area_color(1), [63, 63, 126, 64]);
+        assert_ne!(area_color(3), area_color(4));
+        assert_eq!(area_color(63), [252, 252, 252, 64]);
+    }
+
+    #[test]
+    fn test_single_mesh() {
+        let q = RecastQuery::build(&geometry(), &BuildConfig::default()).unwrap();
+        let draw = q.debug_draw().unwrap();
+
+        assert!(draw.triangles.iter().any(|t| t.area == 0));
+        let area3: Vec<_> = draw.triangles.iter().filter(|t| t.area == 3).collect();
+        assert!(!area3.is_empty());
+        assert!(area3
+            .iter()
+            .all(|t| t.color == area_color(3) && t.verts[0].x() > 15.0));
+
+        assert_eq!(draw.lines_of(DebugEdgeKind::Portal).count(), 0);
+        assert_eq!(draw.lines_of(DebugEdgeKind::OffMeshConnection).count(), 1);
+        assert!(draw.lines_of(DebugEdgeKind::Internal).count() > 0);
+
+        // The outline of the walkable surface, eroded by the agent radius
+        let outline: f32 = draw
+            .lines_of(DebugEdgeKind::Boundary)
+            .map(|l| ((l.end.x() - l.start.x()).powi(2) + (l.end.z() - l.start.z()).powi(2)).sqrt())
+            .sum();
+        assert!(outline > 50.0 && outline < 60.0, "{}", outline);
+    }
+
+    #[test]
+    fn test_tile_portals() {
+        let q = RecastQuery::build_tiled(&geometry(), &BuildConfig::default(), 32).unwrap();
+        let draw = q.debug_draw().unwrap();
+        assert!(draw.lines_of(DebugEdgeKind::Portal).count() > 0);
+
+        // Each edge once
+        let keys: HashSet<_> = draw
+            .lines
+            .iter()
+            .map(|l| edge_key(l.start, l.end))
+            .collect();
+        assert_eq!(keys.len(), draw.lines.len());
+
+        // Without its neighbour the border of a tile is a boundary
+        let mut q = q;
+        let (tx, ty) = q.tile_coords().unwrap()[0];
+        q.remove_tile(tx, ty).unwrap();
+        let fewer = q.debug_draw().unwrap();
+        assert!(
+            fewer.lines_of(DebugEdgeKind::Portal).count()
+                < draw.lines_of(DebugEdgeKind::Portal).count()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! The navmesh structure dumped as JSON, for external tools and bug reports
+use crate::{DebugEdgeKind, Point, RecastQuery, Result};
+use std::fmt::Write as _;
+
+/// JSON has no NaN or infinity
+pub(crate) fn write_number(line: &mut String, v: f32) {
+    if v.is_finite() {
+        let _ = write!(line, "{}", v);
+    } else {
+        line.push_str("null");
+    }
+}
+
+pub(crate) fn write_point(line: &mut String, p: Point) {
+    line.push('[');
+    write_number(line, p.x());
+    line.push(',');
+    write_number(line, p.y());
+    line.push(',');
+    write_number(line, p.z());
+    line.push(']');
+}
+
+pub(crate) fn write_points(line: &mut String, points: &[Point]) {
+    line.push('[');
+    for (i, p) in points.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        write_point(line, *p);
+    }
+    line.push(']');
+}
+
+impl RecastQuery {
+    /// The layout, tiles and polys of the navmesh as one JSON object, e.g. to attach to a bug
+    /// report or to inspect in a script.
+    ///
+    /// `params` is the `NavMeshParams` and `filter` the flags of the query filter. Each of the
+    /// `tiles`, sorted by coordinates, has its `x`, `y`, `layer`, bounds and the counts of its
+    /// header, then its `polys` passing the filter: `ref`, `area`, `flags`, `off_mesh`, the
+    /// `verts`, the `edges` (`boundary`, `internal` or `portal`, from vertex `i` to the next)
+    /// and the `neighbours` refs. A `poly_count` above the listed polys means some are filtered
+    pub fn dump_debug_json(&self) -> Result<String> {
+        let params = self.nav_mesh_params()?;
+        let filter = self.filter();
+
+        let mut json = String::from(r#"{"params":{"origin":"#);
+        write_point(&mut json, Point(params.origin));
+        let _ = write!(
+            json,
+            r#","tile_width":{},"tile_height":{},"max_tiles":{},"max_polys":{}}},"#,
+            params.tile_width, params.tile_height, params.max_tiles, params.max_polys
+        );
+        let _ = write!(
+            json,
+            r#""filter":{{"include_flags":{},"exclude_flags":{}}},"tiles":["#,
+            filter.include_flags.0, filter.exclude_flags.0
+        );
+
+        let mut coords = self.tile_coords()?;
+        coords.sort_unstable();
+        for (i, (tx, ty)) in coords.into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let h = self.tile_data(tx, ty)?.header()?;
+            let _ = write!(
+                json,
+                r#"{{"x":{},"y":{},"layer":{},"bmin":"#,
+                h.tx, h.ty, h.layer
+            );
+            write_point(&mut json, Point(h.bmin));
+            json.push_str(r#","bmax":"#);
+            write_point(&mut json, Point(h.bmax));
+            let _ = write!(
+                json,
+                r#","poly_count":{},"vert_count":{},"off_mesh_connection_count":{},"polys":["#,
+                h.poly_count, h.vert_count, h.off_mesh_connection_count
+            );
+
+            for (j, poly) in self.tile_polys(tx, ty)?.into_iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                let kinds = self.poly_edge_kinds(poly)?;
+                let _ = write!(
+                    json,
+                    r#"{{"ref":{},"area":{},"flags":{},"off_mesh":{},"verts":"#,
+                    poly,
+                    self.poly_area(poly)?,
+                    self.poly_flags(poly)?.0,
+                    kinds.is_empty()
+                );
+                write_points(&mut json, &self.poly_vertices(poly)?);
+
+                json.push_str(r#","edges":["#);
+                for (k, kind) in kinds.into_iter().enumerate() {
+                    if k > 0 {
+                        json.push(',');
+                    }
+                    json.push_str(match kind {
+                        DebugEdgeKind::Boundary => r#""boundary""#,
+                        DebugEdgeKind::Internal => r#""internal""#,
+                        DebugEdgeKind::Portal => r#""portal""#,
+                        DebugEdgeKind::OffMeshConnection => r#""off_mesh""#,
+                    });
+                }
+                json.push_str(r#"],"neighbours":["#);
+                for (k, n) in self.poly_neighbours(poly)?.into_iter().enumerate() {
+                    if k > 0 {
+                        json.push(',');
+                    }
+                    let _ = write!(json, "{}", n);
+                }
+                json.push_str("]}");
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        Ok(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, OffMeshConnection, PolyFlags};
+
+    fn level() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            off_mesh_connections: vec![OffMeshConnection::new(
+                (2.0, 0.0, 2.0).into(),
+                (2.0, 0.0, 8.0).into(),
+                0.5,
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_numbers() {
+        let mut json = String::new();
+        write_points(
+            &mut json,
+            &[Point([1.0, -0.5, 2.0]), Point([f32::NAN, 0.0, 1.0])],
+        );
+        assert_eq!(json, "[[1,-0.5,2],[null,0,1]]");
+    }
+
+    #[test]
+    fn test_dump_debug_json() {
+        let mut q = RecastQuery::build_tiled(&level(), &BuildConfig::default(), 32).unwrap();
+        let json = q.dump_debug_json().unwrap();
+        assert!(
+            json.starts_with(r#"{"params":{"origin":[0,0,0],"#),
+            "{}",
+            json
+        );
+        assert!(json.ends_with("]}]}]}"));
+
+        let polys = q.polys().unwrap();
+        assert_eq!(json.matches(r#""ref":"#).count(), polys.len());
+        assert_eq!(
+            json.matches(r#""x":"#).count(),
+            q.tile_coords().unwrap().len()
+        );
+        assert_eq!(json.matches(r#""off_mesh":true"#).count(), 1);
+        assert!(json.contains(r#""portal""#));
+        // Balanced, with no string holding a bracket
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+
+        // The polys filtered out are missing, their tile keeps its count
+        q.set_poly_flags(polys[0], PolyFlags::DISABLED).unwrap();
+        let filtered = q.dump_debug_json().unwrap();
+        assert_eq!(filtered.matches(r#""ref":"#).count(), polys.len() - 1);
+        assert_eq!(
+            filtered.matches(r#""poly_count":"#).count(),
+            json.matches(r#""poly_count":"#).count()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! A local server streaming the navmesh, agent states and paths to an external viewer
+use crate::debug_json::{write_point, write_points};
+use crate::{AgentState, Point, RecastQuery, Result};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+
+/// Paths kept for the viewers connecting later
+pub const DEBUG_SERVER_RECENT_PATHS: usize = 16;
+
+/// Lines kept for a viewer reading slower than they are published, past that it is dropped
+pub const DEBUG_SERVER_QUEUED_LINES: usize = 256;
+
+/// Streams navigation state as JSON lines to the viewers connected over TCP. Needs the
+/// `debug-server` feature.
+///
+/// Nothing runs in the background: `poll` accepts the new viewers from the game loop, and each
+/// `publish_*` call queues one line for all of them. A viewer gets the navmesh and the recent
+/// paths when it connects, then the updates. The lines are objects with a `type` of `navmesh`,
+/// `agents` or `path`.
+///
+/// The sockets never block the game: each call writes what they take and keeps the rest of the
+/// lines for the next `poll` or `publish_*`, a viewer with more than `DEBUG_SERVER_QUEUED_LINES`
+/// lines waiting is dropped
+#[derive(Debug)]
+pub struct DebugServer {
+    listener: TcpListener,
+    viewers: Vec<Viewer>,
+    navmesh: Option<Arc<str>>,
+    paths: VecDeque<Arc<str>>,
+}
+
+/// A connected viewer with the lines it has not read yet, shared with the other viewers
+#[derive(Debug)]
+struct Viewer {
+    stream: TcpStream,
+    queue: VecDeque<Arc<str>>,
+    /// Bytes of the first line already written
+    written: usize,
+}
+
+impl Viewer {
+    /// Queue a line and write what the socket takes, false once the viewer is to be dropped
+    fn send(&mut self, line: &Arc<str>) -> bool {
+        if self.queue.len() == DEBUG_SERVER_QUEUED_LINES {
+            return false;
+        }
+        self.queue.push_back(line.clone());
+        self.flush()
+    }
+
+    /// Write the queued lines without blocking, false once the viewer is gone
+    fn flush(&mut self) -> bool {
+        while let Some(line) = self.queue.front() {
+            match self.stream.write(&line.as_bytes()[self.written..]) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.written += n;
+                    if self.written == line.len() {
+                        self.queue.pop_front();
+                        self.written = 0;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+impl DebugServer {
+    /// Listen on `addr`, e.g. `127.0.0.1:0` for a free port on the local host only
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<DebugServer> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(DebugServer {
+            listener,
+            viewers: Vec::new(),
+            navmesh: None,
+            paths: VecDeque::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    /// Accept the viewers waiting to connect, returns how many, and write the lines left to the
+    /// others. Call once per frame
+    pub fn poll(&mut self) -> io::Result<usize> {
+        self.viewers.retain_mut(Viewer::flush);
+        let mut accepted = 0;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if self.greet(stream).is_ok() {
+                        accepted += 1;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(accepted),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send the detail triangles of every poly, again to each new viewer. Call after the
+    /// navmesh changed
+    pub fn publish_navmesh(&mut self, q: &RecastQuery) -> Result<()> {
+        let mut line = String::from(r#"{"type":"navmesh","polys":["#);
+        for (i, poly) in q.polys()?.into_iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            let _ = write!(
+                line,
+                r#"{{"ref":{},"area":{},"triangles":["#,
+                poly,
+                q.poly_area(poly)?
+            );
+            for (j, tri) in q.poly_detail_triangles(poly)?.iter().enumerate() {
+                if j > 0 {
+                    line.push(',');
+                }
+                write_points(&mut line, tri);
+            }
+            line.push_str("]}");
+        }
+        line.push_str("]}\n");
+
+        let line = Arc::from(line);
+        self.broadcast(&line);
+        self.navmesh = Some(line);
+        Ok(())
+    }
+
+    /// Send the agents of one update, e.g. the agents of a `Snapshot`
+    pub fn publish_agents(&mut self, frame: u64, agents: &[AgentState]) {
+        let mut line = format!(r#"{{"type":"agents","frame":{},"agents":["#, frame);
+        for (i, agent) in agents.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            let _ = write!(line, r#"{{"index":{},"pos":"#, agent.index);
+            write_point(&mut line, agent.pos);
+            line.push_str(r#","vel":"#);
+            write_point(&mut line, agent.vel);
+            line.push('}');
+        }
+        line.push_str("]}\n");
+        self.broadcast(&Arc::from(line));
+    }
+
+    /// Send a path found for `id`, a viewer usually replaces the previous path of the same id.
+    /// The last `DEBUG_SERVER_RECENT_PATHS` paths are sent to the new viewers
+    pub fn publish_path(&mut self, id: u64, points: &[Point]) {
+        let mut line = format!(r#"{{"type":"path","id":{},"points":"#, id);
+        write_points(&mut line, points);
+        line.push_str("}\n");
+
+        let line = Arc::from(line);
+        self.broadcast(&line);
+        if self.paths.len() == DEBUG_SERVER_RECENT_PATHS {
+            self.paths.pop_front();
+        }
+        self.paths.push_back(line);
+    }
+
+    fn greet(&mut self, stream: TcpStream) -> io::Result<()> {
+        // Accepted streams do not inherit the non-blocking listener on every platform
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let mut viewer = Viewer {
+            stream,
+            queue: self.navmesh.iter().chain(&self.paths).cloned().collect(),
+            written: 0,
+        };
+        if !viewer.flush() {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+        self.viewers.push(viewer);
+        Ok(())
+    }
+
+    /// A viewer failing a write may have got half a line, it is dropped
+    fn broadcast(&mut self, line: &Arc<str>) {
+        self.viewers.retain_mut(|viewer| viewer.send(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane_query;
+    use std::io::{BufRead, BufReader};
+    use std::thread;
+    use std::time::Duration;
+
+    fn connect(server: &mut DebugServer) -> BufReader<TcpStream> {
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        // The connection may take a moment to be ready to accept
+        while server.poll().unwrap() == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        BufReader::new(stream)
+    }
+
+    fn next_line(viewer: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        viewer.read_line(&mut line).unwrap();
+        assert!(line.ends_with('\n'));
+        line
+    }
+
+    #[test]
+    fn test_stream_to_viewers() {
+        let q = plane_query();
+        let mut server = DebugServer::bind("127.0.0.1:0").unwrap();
+        server.publish_navmesh(&q).unwrap();
+        server.publish_path(
+            7,
+            &q.find_path(
+                (1.0, 0.0, 1.0).into(),
+                (9.0, 0.0, 9.0).into(),
+                (0.5, 1.0, 0.5),
+            )
+            .unwrap(),
+        );
+
+        // A late viewer gets the navmesh and the recent paths first
+        let mut viewer = connect(&mut server);
+        assert_eq!(server.viewer_count(), 1);
+        let navmesh = next_line(&mut viewer);
+        assert!(navmesh.starts_with(r#"{"type":"navmesh","polys":[{"ref":"#));
+        assert!(navmesh.contains(r#""triangles":[[["#));
+        assert!(next_line(&mut viewer).starts_with(r#"{"type":"path","id":7,"points":[["#));
+
+        server.publish_agents(
+            3,
+            &[AgentState {
+                index: 0,
+                pos: (1.0, 0.0, 2.5).into(),
+                vel: (0.0, 0.0, f32::NAN).into(),
+            }],
+        );
+        assert_eq!(
+            next_line(&mut viewer),
+            "{\"type\":\"agents\",\"frame\":3,\"agents\":[{\"index\":0,\"pos\":[1,0,2.5],\"vel\":[0,0,null]}]}\n"
+        );
+
+        // Gone viewers are dropped on the next publish
+        drop(viewer);
+        for frame in 4..100 {
+            server.publish_agents(frame, &[]);
+            if server.viewer_count() == 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(server.viewer_count(), 0);
+    }
+
+    #[test]
+    fn test_recent_paths() {
+        let mut server = DebugServer::bind("127.0.0.1:0").unwrap();
+        for id in 0..DEBUG_SERVER_RECENT_PATHS as u64 + 4 {
+            server.publish_path(id, &[(0.0, 0.0, 0.0).into()]);
+        }
+        let mut viewer = connect(&mut server);
+        assert!(next_line(&mut viewer).starts_with(r#"{"type":"path","id":4,"#));
+    }
+
+    #[test]
+    fn test_slow_viewer() {
+        let mut server = DebugServer::bind("127.0.0.1:0").unwrap();
+        let fast = connect(&mut server);
+        let slow = connect(&mut server);
+        assert_eq!(server.viewer_count(), 2);
+        let reader = thread::spawn(move || fast.lines().map_while(|l| l.ok()).count());
+
+        // Lines far larger than the socket buffers, the slow viewer never reads
+        let points = vec![Point::new((1.5, 2.5, 3.5)); 10_000];
+        let lines = DEBUG_SERVER_QUEUED_LINES * 2;
+        for id in 0..lines as u64 {
+            server.publish_path(id, &points);
+            // The first viewer keeps up with the lines
+            while !server.viewers[0].queue.is_empty() {
+                server.poll().unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        assert_eq!(server.viewer_count(), 1);
+
+        drop(server);
+        assert_eq!(reader.join().unwrap(), lines);
+        drop(slow);
+    }
+}
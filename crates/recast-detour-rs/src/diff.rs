@@ -0,0 +1,285 @@
+//! Navmeshes compared tile by tile, for a hot reload replacing only the tiles that changed
+use crate::merge::check_same_grid;
+use crate::stitch::check_on_grid;
+use crate::{Error, RecastQuery, Result, TileData};
+use std::collections::HashMap;
+
+impl TileData {
+    /// Hash of the polys, detail mesh, BV tree and off-mesh connections of the tile, without the
+    /// links Detour writes into the data when the tile is added. Unlike `content_hash` it is the
+    /// same for the tile as built and as read back from a navmesh, whatever its neighbours.
+    ///
+    /// The top of the tile bounds is left out too: a tiled build gives every tile the top of the
+    /// whole geometry, raised by a change anywhere, and Detour only clamps queries to it
+    pub fn mesh_hash(&self) -> Result<u64> {
+        let bytes = self.as_bytes();
+        let mut hash = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_tile_mesh_hash(
+                bytes.as_ptr(),
+                bytes.len() as i32,
+                &mut hash as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::BuildError(err.msg().to_string()));
+        }
+        Ok(hash)
+    }
+}
+
+/// The tiles of a navmesh compared with a newer build on the same grid, by tile coordinates
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NavMeshDiff {
+    /// Only in the newer build
+    pub added: Vec<(i32, i32)>,
+    /// Only in the navmesh, no walkable surface left there in the newer build
+    pub removed: Vec<(i32, i32)>,
+    /// In both with other polys
+    pub changed: Vec<(i32, i32)>,
+    /// In both with the same polys, their poly refs stay valid across a hot reload
+    pub unchanged: Vec<(i32, i32)>,
+}
+
+impl NavMeshDiff {
+    /// True when the newer build has the same tiles
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The tiles added, removed or changed, e.g. to repath the agents with polys in them
+    pub fn affected(&self) -> impl Iterator<Item = &(i32, i32)> {
+        self.added.iter().chain(&self.removed).chain(&self.changed)
+    }
+
+    fn between(old: &HashMap<(i32, i32), u64>, new: &HashMap<(i32, i32), u64>) -> NavMeshDiff {
+        let mut diff = NavMeshDiff::default();
+        for (coords, hash) in old {
+            match new.get(coords) {
+                None => diff.removed.push(*coords),
+                Some(h) if h != hash => diff.changed.push(*coords),
+                Some(_) => diff.unchanged.push(*coords),
+            }
+        }
+        diff.added = new
+            .keys()
+            .filter(|c| !old.contains_key(c))
+            .copied()
+            .collect();
+
+        for tiles in &mut [
+            &mut diff.added,
+            &mut diff.removed,
+            &mut diff.changed,
+            &mut diff.unchanged,
+        ] {
+            tiles.sort_unstable();
+        }
+        diff
+    }
+}
+
+impl RecastQuery {
+    /// The `TileData::mesh_hash` of each tile by tile coordinates
+    fn tile_mesh_hashes(&self) -> Result<HashMap<(i32, i32), u64>> {
+        let mut hashes = HashMap::new();
+        for (tx, ty) in self.tile_coords()? {
+            hashes.insert((tx, ty), self.tile_data(tx, ty)?.mesh_hash()?);
+        }
+        Ok(hashes)
+    }
+
+    /// The tiles of this navmesh against the ones of `other`, a newer build tiled on the same grid.
+    /// Tiles differing only in their links to neighbours are unchanged
+    pub fn diff(&self, other: &RecastQuery) -> Result<NavMeshDiff> {
+        check_same_grid(&self.nav_mesh_params()?, &other.nav_mesh_params()?)?;
+        Ok(NavMeshDiff::between(
+            &self.tile_mesh_hashes()?,
+            &other.tile_mesh_hashes()?,
+        ))
+    }
+
+    /// `diff` against the tiles of a whole newer bake, e.g. of `TileGrid::build_tiles`, the tiles
+    /// of the navmesh missing from `tiles` are removed
+    pub fn diff_tiles(&self, tiles: &[TileData]) -> Result<NavMeshDiff> {
+        let params = self.nav_mesh_params()?;
+        let mut hashes = HashMap::new();
+        for tile in tiles {
+            let h = tile.header()?;
+            check_on_grid(&params, &h)?;
+            if hashes.insert((h.tx, h.ty), tile.mesh_hash()?).is_some() {
+                return Err(Error::BuildError(format!(
+                    "tile ({}, {}) is twice in the bake",
+                    h.tx, h.ty
+                )));
+            }
+        }
+        Ok(NavMeshDiff::between(&self.tile_mesh_hashes()?, &hashes))
+    }
+
+    /// Update the navmesh to the tiles of a whole newer bake, touching only the tiles of the diff.
+    ///
+    /// The unchanged tiles stay in the navmesh as they are, with the same poly refs, so the
+    /// corridors and crowd agents on them need no repath. The polys of the affected tiles get
+    /// new refs. On an error the navmesh may be left with part of the diff applied
+    pub fn hot_reload(&mut self, tiles: Vec<TileData>) -> Result<NavMeshDiff> {
+        let diff = self.diff_tiles(&tiles)?;
+        let mut by_coords = HashMap::new();
+        for tile in tiles {
+            let h = tile.header()?;
+            by_coords.insert((h.tx, h.ty), tile);
+        }
+
+        self.apply_diff(&diff, |coords| {
+            by_coords
+                .remove(&coords)
+                .ok_or_else(|| Error::BuildError("tile missing from the bake".into()))
+        })?;
+        Ok(diff)
+    }
+
+    /// `hot_reload` with the tiles of `other`, a newer build tiled on the same grid
+    pub fn hot_reload_from(&mut self, other: &RecastQuery) -> Result<NavMeshDiff> {
+        let diff = self.diff(other)?;
+        self.apply_diff(&diff, |(tx, ty)| other.tile_data(tx, ty))?;
+        Ok(diff)
+    }
+
+    fn apply_diff<F>(&mut self, diff: &NavMeshDiff, mut tile: F) -> Result<()>
+    where
+        F: FnMut((i32, i32)) -> Result<TileData>,
+    {
+        for &(tx, ty) in &diff.removed {
+            self.remove_tile(tx, ty)?;
+        }
+        for &coords in &diff.changed {
+            self.replace_tile(coords.0, coords.1, Some(tile(coords)?))?;
+        }
+        for &coords in &diff.added {
+            self.add_tile(tile(coords)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, TileGrid};
+
+    /// A floor strip of `length`, with a raised block near its east end when `block`
+    fn level(length: f32, block: bool) -> InputGeometry {
+        let mut geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, length, 0.0, 0.0, length, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        };
+        if block {
+            geom.vertices.extend_from_slice(&[
+                34.0, 1.5, 3.0, 38.0, 1.5, 3.0, 38.0, 1.5, 7.0, 34.0, 1.5, 7.0,
+            ]);
+            geom.indices.extend_from_slice(&[4, 6, 5, 4, 7, 6]);
+        }
+        geom
+    }
+
+    fn build(geom: &InputGeometry) -> RecastQuery {
+        RecastQuery::build_tiled(geom, &BuildConfig::default(), 32).unwrap()
+    }
+
+    #[test]
+    fn test_mesh_hash_ignores_links() {
+        let config = BuildConfig::default();
+        let geom = level(40.0, false);
+        let grid = TileGrid::for_geometry(&geom, &config, 32).unwrap();
+        let built = grid.build_tiles(&geom, &config).unwrap();
+
+        let hashes: Vec<_> = built
+            .iter()
+            .map(|t| {
+                let h = t.header().unwrap();
+                ((h.tx, h.ty), t.content_hash(), t.mesh_hash().unwrap())
+            })
+            .collect();
+
+        let q = RecastQuery::stitch_tiles(&grid.nav_mesh_params().unwrap(), built).unwrap();
+        for ((tx, ty), content_hash, mesh_hash) in hashes {
+            let linked = q.tile_data(tx, ty).unwrap();
+            assert_ne!(linked.content_hash(), content_hash);
+            assert_eq!(linked.mesh_hash().unwrap(), mesh_hash);
+        }
+        assert!(TileData::from_bytes(&[0; 8]).unwrap().mesh_hash().is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let q = build(&level(40.0, false));
+        assert!(q.diff(&build(&level(40.0, false))).unwrap().is_empty());
+
+        let diff = q.diff(&build(&level(40.0, true))).unwrap();
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(!diff.changed.is_empty());
+        // The block is at the east end, the tiles of the west end are untouched
+        assert!(diff.changed.iter().all(|&(tx, _)| tx >= 2), "{:?}", diff);
+        assert!(diff.unchanged.iter().any(|&(tx, _)| tx == 0));
+        assert_eq!(
+            diff.affected().count() + diff.unchanged.len(),
+            q.tile_coords().unwrap().len()
+        );
+
+        let shorter = q.diff(&build(&level(20.0, false))).unwrap();
+        assert!(!shorter.removed.is_empty() && shorter.added.is_empty());
+        let longer = build(&level(20.0, false)).diff(&q).unwrap();
+        assert_eq!(longer.added, shorter.removed);
+
+        let other_grid =
+            RecastQuery::build_tiled(&level(40.0, false), &BuildConfig::default(), 48).unwrap();
+        assert!(q.diff(&other_grid).is_err());
+    }
+
+    #[test]
+    fn test_hot_reload_keeps_refs() {
+        let config = BuildConfig::default();
+        let mut q = build(&level(40.0, false));
+        let west: Vec<_> = q
+            .tile_coords()
+            .unwrap()
+            .into_iter()
+            .filter(|&(tx, _)| tx == 0)
+            .collect();
+        let west_polys: Vec<_> = west
+            .iter()
+            .map(|&(tx, ty)| q.tile_polys(tx, ty).unwrap())
+            .collect();
+
+        let geom = level(40.0, true);
+        let grid = TileGrid::for_geometry(&geom, &config, 32).unwrap();
+        let diff = q
+            .hot_reload(grid.build_tiles(&geom, &config).unwrap())
+            .unwrap();
+        assert!(!diff.changed.is_empty());
+
+        for (&(tx, ty), polys) in west.iter().zip(&west_polys) {
+            assert_eq!(&q.tile_polys(tx, ty).unwrap(), polys);
+        }
+        // The same navmesh as a fresh build, across the seams as well
+        let fresh = build(&geom);
+        assert!(q.diff(&fresh).unwrap().is_empty());
+        let (start, end) = ((1.0, 0.0, 1.0).into(), (39.0, 0.0, 9.0).into());
+        assert_eq!(
+            q.find_path(start, end, (0.5, 1.0, 0.5)).unwrap().len(),
+            fresh.find_path(start, end, (0.5, 1.0, 0.5)).unwrap().len()
+        );
+
+        // And back, from a query
+        let diff = q.hot_reload_from(&build(&level(20.0, false))).unwrap();
+        assert!(!diff.removed.is_empty());
+        assert!(q.diff(&build(&level(20.0, false))).unwrap().is_empty());
+    }
+}
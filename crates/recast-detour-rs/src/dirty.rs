@@ -0,0 +1,190 @@
+//! Incremental rebuilds of the tiles of a tiled navmesh whose geometry changed
+use crate::recast::SysInput;
+use crate::tiled::build_tile;
+use crate::{BuildConfig, InputGeometry, Point, RecastQuery, Result, TileGrid};
+use std::collections::BTreeMap;
+
+/// The tiles waiting for a rebuild, e.g. after destructible terrain changed.
+///
+/// The geometry given to `rebuild` must stay within the bounds of the grid,
+/// changes outside of it are not covered by any tile.
+#[derive(Debug, Clone)]
+pub struct DirtyTiles {
+    grid: TileGrid,
+    /// The number of the last mark of each tile
+    tiles: BTreeMap<(i32, i32), u64>,
+    marks: u64,
+}
+
+impl DirtyTiles {
+    pub fn new(grid: TileGrid) -> DirtyTiles {
+        DirtyTiles {
+            grid,
+            tiles: BTreeMap::new(),
+            marks: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    /// Mark the tiles reading geometry in the world space box
+    pub fn mark(&mut self, config: &BuildConfig, bmin: Point, bmax: Point) {
+        let (min, max) =
+            self.grid
+                .tiles_overlapping(config, (bmin.x(), bmin.z()), (bmax.x(), bmax.z()));
+
+        self.marks += 1;
+        for ty in min.1..=max.1 {
+            for tx in min.0..=max.0 {
+                self.tiles.insert((tx, ty), self.marks);
+            }
+        }
+    }
+
+    pub fn mark_tile(&mut self, tx: i32, ty: i32) {
+        if tx >= 0 && ty >= 0 && tx < self.grid.cols && ty < self.grid.rows {
+            self.marks += 1;
+            self.tiles.insert((tx, ty), self.marks);
+        }
+    }
+
+    pub fn is_dirty(&self, tx: i32, ty: i32) -> bool {
+        self.tiles.contains_key(&(tx, ty))
+    }
+
+    /// Dirty tiles, row by row
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.tiles.keys().copied()
+    }
+
+    /// The number of the last mark, a rebuild started now covers the marks up to it
+    pub(crate) fn last_mark(&self) -> u64 {
+        self.marks
+    }
+
+    /// The tiles are clean unless marked again after `last_mark`
+    pub(crate) fn clean(&mut self, tiles: impl Iterator<Item = (i32, i32)>, last_mark: u64) {
+        for tile in tiles {
+            if self.tiles.get(&tile).is_some_and(|&mark| mark <= last_mark) {
+                self.tiles.remove(&tile);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Rebuild every dirty tile, returns the number of rebuilt tiles
+    pub fn rebuild(
+        &mut self,
+        q: &mut RecastQuery,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+    ) -> Result<usize> {
+        self.rebuild_some(q, geometry, config, usize::MAX)
+    }
+
+    /// Rebuild at most `max_tiles` dirty tiles, e.g. to spread the work over frames.
+    /// A tile failing to build stays dirty
+    pub fn rebuild_some(
+        &mut self,
+        q: &mut RecastQuery,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        max_tiles: usize,
+    ) -> Result<usize> {
+        if self.tiles.is_empty() {
+            return Ok(0);
+        }
+
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_input = SysInput::new(geometry);
+        let input = sys_input.as_sys();
+        let sys_config = config.as_sys();
+
+        let mut rebuilt = 0;
+        while rebuilt < max_tiles {
+            let (tx, ty) = match self.tiles.keys().next() {
+                Some(&tile) => tile,
+                None => break,
+            };
+
+            let tile = build_tile(&input, &sys_config, &self.grid, tx, ty)?;
+            q.replace_tile(tx, ty, tile)?;
+
+            self.tiles.remove(&(tx, ty));
+            rebuilt += 1;
+        }
+
+        Ok(rebuilt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+
+    #[test]
+    fn test_mark_region() {
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&plane(20.0), &config, 16).unwrap();
+        let mut dirty = DirtyTiles::new(grid);
+
+        // Inside one tile but close to its border
+        let size = grid.tile_world_size();
+        let x = size * 1.5;
+        let z = size + 0.1;
+        dirty.mark(&config, (x, 0.0, z).into(), (x, 0.0, z).into());
+        assert_eq!(dirty.tiles().collect::<Vec<_>>(), vec![(1, 0), (1, 1)]);
+
+        dirty.mark_tile(-1, 0);
+        dirty.mark_tile(100, 0);
+        assert_eq!(dirty.len(), 2);
+    }
+
+    #[test]
+    fn test_rebuild_changed_geometry() {
+        let config = BuildConfig::default();
+        let mut geom = plane(20.0);
+        let mut q = RecastQuery::build_tiled(&geom, &config, 16).unwrap();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let mut dirty = DirtyTiles::new(grid);
+        let pos = (10.0, 0.0, 10.0).into();
+        assert!(q.find_poly(pos, (0.1, 1.0, 0.1)).is_ok());
+
+        // A crater in the middle of the plane
+        geom.volumes.push(crate::ConvexVolume {
+            verts: vec![
+                (8.0, 0.0, 8.0).into(),
+                (8.0, 0.0, 12.0).into(),
+                (12.0, 0.0, 12.0).into(),
+                (12.0, 0.0, 8.0).into(),
+            ],
+            hmin: -1.0,
+            hmax: 1.0,
+            area: 0,
+        });
+        dirty.mark(&config, (8.0, -1.0, 8.0).into(), (12.0, 1.0, 12.0).into());
+        let n = dirty.len();
+
+        assert_eq!(dirty.rebuild_some(&mut q, &geom, &config, 1).unwrap(), 1);
+        assert_eq!(dirty.len(), n - 1);
+        assert_eq!(dirty.rebuild(&mut q, &geom, &config).unwrap(), n - 1);
+        assert!(dirty.is_empty());
+        assert!(q.find_poly(pos, (0.1, 1.0, 0.1)).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! How far agents drift away from their planned paths, e.g. pushed around by local avoidance
+use crate::Point;
+
+/// Squared distance in the xz-plane from `p` to the segment `a`-`b`, see dtDistancePtSegSqr2D
+fn dist_pt_seg_sqr_2d(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dz) = (b.x() - a.x(), b.z() - a.z());
+    let len = dx * dx + dz * dz;
+
+    let t = if len > 0.0 {
+        (((p.x() - a.x()) * dx + (p.z() - a.z()) * dz) / len).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let (x, z) = (a.x() + t * dx - p.x(), a.z() + t * dz - p.z());
+    x * x + z * z
+}
+
+/// Distance in the xz-plane from the position to the closest segment of the path,
+/// the height is left out so slopes and steps do not count as deviation
+pub fn lateral_deviation(path: &[Point], pos: Point) -> f32 {
+    match path {
+        [] => 0.0,
+        [p] => dist_pt_seg_sqr_2d(pos, *p, *p).sqrt(),
+        _ => path
+            .windows(2)
+            .map(|s| dist_pt_seg_sqr_2d(pos, s[0], s[1]))
+            .fold(f32::MAX, f32::min)
+            .sqrt(),
+    }
+}
+
+/// The max lateral deviation of the trajectory samples from the path, 0 for an empty trajectory
+pub fn max_lateral_deviation(path: &[Point], trajectory: &[Point]) -> f32 {
+    trajectory
+        .iter()
+        .map(|p| lateral_deviation(path, *p))
+        .fold(0.0, f32::max)
+}
+
+/// Follows an agent along its planned path and keeps the max lateral deviation seen since the plan.
+///
+/// Positions are matched against the segments from the last matched one onward, so a path passing
+/// close to itself does not hide a deviation. Deviations below `tolerance` are left to local avoidance,
+/// above it the path should be replanned.
+#[derive(Debug, Clone)]
+pub struct DivergenceMonitor {
+    /// Max lateral deviation in world unit before asking for a replan
+    pub tolerance: f32,
+    path: Vec<Point>,
+    segment: usize,
+    current: f32,
+    max: f32,
+}
+
+impl DivergenceMonitor {
+    pub fn new(path: Vec<Point>, tolerance: f32) -> DivergenceMonitor {
+        DivergenceMonitor {
+            tolerance,
+            path,
+            segment: 0,
+            current: 0.0,
+            max: 0.0,
+        }
+    }
+
+    /// Start over with a new plan
+    pub fn replan(&mut self, path: Vec<Point>) {
+        self.path = path;
+        self.segment = 0;
+        self.current = 0.0;
+        self.max = 0.0;
+    }
+
+    pub fn path(&self) -> &[Point] {
+        &self.path
+    }
+
+    /// Record the agent position, returns its lateral deviation
+    pub fn update(&mut self, pos: Point) -> f32 {
+        let deviation = if self.path.len() < 2 {
+            lateral_deviation(&self.path, pos)
+        } else {
+            let (segment, d) = self.path[self.segment..]
+                .windows(2)
+                .enumerate()
+                .map(|(i, s)| (self.segment + i, dist_pt_seg_sqr_2d(pos, s[0], s[1])))
+                .fold((self.segment, f32::MAX), |best, s| {
+                    if s.1 < best.1 {
+                        s
+                    } else {
+                        best
+                    }
+                });
+            self.segment = segment;
+            d.sqrt()
+        };
+
+        self.current = deviation;
+        self.max = self.max.max(deviation);
+        deviation
+    }
+
+    /// Lateral deviation of the last recorded position
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Max lateral deviation since the last plan
+    pub fn max_deviation(&self) -> f32 {
+        self.max
+    }
+
+    /// Index of the path segment the agent was last matched to
+    pub fn segment(&self) -> usize {
+        self.segment
+    }
+
+    /// True once the agent strayed further than the tolerance from its plan
+    pub fn should_replan(&self) -> bool {
+        self.max > self.tolerance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lateral_deviation() {
+        let path: Vec<Point> = vec![(0.0, 0.0, 0.0).into(), (10.0, 0.0, 0.0).into()];
+
+        assert_eq!(lateral_deviation(&path, (5.0, 3.0, 2.0).into()), 2.0);
+        assert_eq!(lateral_deviation(&path, (13.0, 0.0, 4.0).into()), 5.0);
+        assert_eq!(lateral_deviation(&[], (1.0, 0.0, 1.0).into()), 0.0);
+
+        let trajectory = vec![
+            (1.0, 0.0, 0.5).into(),
+            (4.0, 0.0, -1.5).into(),
+            (8.0, 0.0, 0.0).into(),
+        ];
+        assert_eq!(max_lateral_deviation(&path, &trajectory), 1.5);
+    }
+
+    #[test]
+    fn test_monitor_follows_the_path() {
+        // A U-turn, the way back passes 1 unit from the way out
+        let path = vec![
+            (0.0, 0.0, 0.0).into(),
+            (10.0, 0.0, 0.0).into(),
+            (10.0, 0.0, 1.0).into(),
+            (0.0, 0.0, 1.0).into(),
+        ];
+        let mut monitor = DivergenceMonitor::new(path, 0.4);
+
+        assert!((monitor.update((5.0, 0.0, 0.2).into()) - 0.2).abs() < 1e-5);
+        assert!(!monitor.should_replan());
+        monitor.update((10.0, 0.0, 0.5).into());
+        assert_eq!(monitor.segment(), 1);
+
+        // Back on the way out while it should be on the way back
+        let d = monitor.update((5.0, 0.0, 0.2).into());
+        assert!((d - 0.8).abs() < 1e-5);
+        assert!(monitor.should_replan());
+
+        monitor.replan(vec![(5.0, 0.0, 0.2).into(), (0.0, 0.0, 0.2).into()]);
+        assert_eq!(monitor.max_deviation(), 0.0);
+        assert!(!monitor.should_replan());
+    }
+}
@@ -0,0 +1,226 @@
+//! Brush editing of a tiled navmesh for in-engine tools
+use crate::{BuildConfig, ConvexVolume, InputGeometry, Point, RecastQuery, Result, TileGrid};
+
+/// Shape of a brush stroke in world unit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Brush {
+    Sphere { center: Point, radius: f32 },
+    Box { min: Point, max: Point },
+}
+
+/// What a stroke does to the walkable surface under the brush
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BrushMode {
+    /// Give the surface an area id, 0 is the same as `Unmark`
+    Mark(u8),
+    /// Remove the walkable surface, only `undo` brings it back
+    Unmark,
+}
+
+impl Brush {
+    /// Sphere brushes become the octagon prism around the sphere
+    pub fn to_volume(&self, mode: BrushMode) -> ConvexVolume {
+        let area = match mode {
+            BrushMode::Mark(area) => area,
+            BrushMode::Unmark => 0,
+        };
+
+        match *self {
+            Brush::Sphere { center: c, radius } => {
+                // Circumscribe the circle of the sphere
+                let r = radius / std::f32::consts::FRAC_PI_8.cos();
+                let verts = (0..8)
+                    .map(|i| {
+                        let a = i as f32 * std::f32::consts::FRAC_PI_4;
+                        (c.x() + a.cos() * r, c.y(), c.z() + a.sin() * r).into()
+                    })
+                    .collect();
+
+                ConvexVolume {
+                    verts,
+                    hmin: c.y() - radius,
+                    hmax: c.y() + radius,
+                    area,
+                }
+            }
+            Brush::Box { min, max } => ConvexVolume {
+                verts: vec![
+                    (min.x(), min.y(), min.z()).into(),
+                    (min.x(), min.y(), max.z()).into(),
+                    (max.x(), min.y(), max.z()).into(),
+                    (max.x(), min.y(), min.z()).into(),
+                ],
+                hmin: min.y(),
+                hmax: max.y(),
+                area,
+            },
+        }
+    }
+}
+
+/// A tiled navmesh edited with brush strokes, each stroke rebakes the tiles it touches.
+///
+/// Strokes are recorded as convex volumes of the geometry and applied in order,
+/// so `geometry()` rebuilds the edited navmesh from scratch.
+#[derive(Debug)]
+pub struct NavMeshEditor {
+    geometry: InputGeometry,
+    config: BuildConfig,
+    grid: TileGrid,
+    query: RecastQuery,
+    /// Number of volumes of the geometry before the first stroke
+    base_volumes: usize,
+}
+
+impl NavMeshEditor {
+    pub fn new(
+        geometry: InputGeometry,
+        config: BuildConfig,
+        tile_size: u32,
+    ) -> Result<NavMeshEditor> {
+        let query = RecastQuery::build_tiled(&geometry, &config, tile_size)?;
+        let grid = TileGrid::for_geometry(&geometry, &config, tile_size)?;
+
+        Ok(NavMeshEditor {
+            base_volumes: geometry.volumes.len(),
+            geometry,
+            config,
+            grid,
+            query,
+        })
+    }
+
+    pub fn query(&self) -> &RecastQuery {
+        &self.query
+    }
+
+    pub fn query_mut(&mut self) -> &mut RecastQuery {
+        &mut self.query
+    }
+
+    /// The input geometry with the strokes as convex volumes
+    pub fn geometry(&self) -> &InputGeometry {
+        &self.geometry
+    }
+
+    pub fn grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    /// The stroke volumes, oldest first
+    pub fn strokes(&self) -> &[ConvexVolume] {
+        &self.geometry.volumes[self.base_volumes..]
+    }
+
+    /// Apply a stroke and rebake the tiles under it
+    pub fn stroke(&mut self, brush: Brush, mode: BrushMode) -> Result<()> {
+        let volume = brush.to_volume(mode);
+        self.geometry.volumes.push(volume);
+
+        let tiles = self.affected_tiles(self.geometry.volumes.last().unwrap());
+        if let Err(err) = self.rebake(tiles) {
+            self.geometry.volumes.pop();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Revert the last stroke, false when there is none
+    pub fn undo(&mut self) -> Result<bool> {
+        if self.geometry.volumes.len() == self.base_volumes {
+            return Ok(false);
+        }
+
+        let volume = self.geometry.volumes.pop().unwrap();
+        if let Err(err) = self.rebake(self.affected_tiles(&volume)) {
+            self.geometry.volumes.push(volume);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Tiles whose build reads the volume
+    fn affected_tiles(&self, volume: &ConvexVolume) -> ((i32, i32), (i32, i32)) {
+        let min_x = volume.verts.iter().map(|v| v.x()).fold(f32::MAX, f32::min);
+        let min_z = volume.verts.iter().map(|v| v.z()).fold(f32::MAX, f32::min);
+        let max_x = volume.verts.iter().map(|v| v.x()).fold(f32::MIN, f32::max);
+        let max_z = volume.verts.iter().map(|v| v.z()).fold(f32::MIN, f32::max);
+
+        self.grid
+            .tiles_overlapping(&self.config, (min_x, min_z), (max_x, max_z))
+    }
+
+    /// Build every tile before swapping any, the navmesh keeps its previous tiles on a failure
+    fn rebake(&mut self, (min, max): ((i32, i32), (i32, i32))) -> Result<()> {
+        let mut tiles = vec![];
+        for ty in min.1..=max.1 {
+            for tx in min.0..=max.0 {
+                let tile = self.grid.build_tile(&self.geometry, &self.config, tx, ty)?;
+                tiles.push(((tx, ty), tile));
+            }
+        }
+        self.query.replace_tiles(tiles).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+
+    #[test]
+    fn test_sphere_brush_volume() {
+        let brush = Brush::Sphere {
+            center: (1.0, 2.0, 3.0).into(),
+            radius: 1.0,
+        };
+        let volume = brush.to_volume(BrushMode::Unmark);
+
+        assert_eq!(volume.verts.len(), 8);
+        assert_eq!((volume.hmin, volume.hmax), (1.0, 3.0));
+        assert_eq!(volume.area, 0);
+    }
+
+    #[test]
+    fn test_stroke_and_undo() {
+        let mut editor = NavMeshEditor::new(plane(20.0), BuildConfig::default(), 16).unwrap();
+        let pos = (10.0, 0.0, 10.0).into();
+        let ext = (0.1, 1.0, 0.1);
+        assert!(editor.query().find_poly(pos, ext).is_ok());
+
+        let brush = Brush::Box {
+            min: (8.0, -1.0, 8.0).into(),
+            max: (12.0, 1.0, 12.0).into(),
+        };
+        editor.stroke(brush, BrushMode::Mark(3)).unwrap();
+        let (_, poly) = editor.query().find_poly(pos, ext).unwrap();
+        assert_eq!(editor.query().poly_area(poly).unwrap(), 3);
+
+        editor.stroke(brush, BrushMode::Unmark).unwrap();
+        assert!(editor.query().find_poly(pos, ext).is_err());
+        assert_eq!(editor.strokes().len(), 2);
+
+        assert!(editor.undo().unwrap());
+        assert!(editor.query().find_poly(pos, ext).is_ok());
+        assert!(editor.undo().unwrap());
+        assert!(!editor.undo().unwrap());
+    }
+
+    #[test]
+    fn test_failed_stroke_keeps_the_navmesh() {
+        let mut editor = NavMeshEditor::new(plane(20.0), BuildConfig::default(), 16).unwrap();
+        let pos = (10.0, 0.0, 10.0).into();
+        let ext = (0.1, 1.0, 0.1);
+        let (_, before) = editor.query().find_poly(pos, ext).unwrap();
+
+        let brush = Brush::Box {
+            min: (8.0, -1.0, 8.0).into(),
+            max: (12.0, 1.0, 12.0).into(),
+        };
+        assert!(editor
+            .stroke(brush, BrushMode::Mark(crate::SOFT_AREA_FIRST))
+            .is_err());
+        assert!(editor.strokes().is_empty());
+        assert_eq!(editor.query().find_poly(pos, ext).unwrap().1, before);
+    }
+}
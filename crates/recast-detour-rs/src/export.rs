@@ -0,0 +1,180 @@
+//! The navmesh written as a Wavefront .obj, e.g. to check the walkable coverage in Blender
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+impl RecastQuery {
+    /// The vertices of a poly in their order in the navmesh, the 2 ends of an off-mesh connection
+    pub fn poly_vertices(&self, poly: PolyRef) -> Result<Vec<Point>> {
+        let mut verts = [0.0; 3 * sys::DT_VERTS_PER_POLYGON];
+        let mut count = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_vertices(
+                self.q.as_ptr(),
+                poly,
+                verts.as_mut_ptr(),
+                &mut count as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::PolyError(err.msg().to_string()));
+        }
+
+        Ok(verts[..count as usize * 3]
+            .chunks(3)
+            .map(|v| Point([v[0], v[1], v[2]]))
+            .collect())
+    }
+
+    /// The polys passing the query filter as an .obj, Y-up as the navmesh.
+    ///
+    /// Each poly is a face facing up, grouped by area with a `usemtl area_<id>` so the areas
+    /// get their own material when imported. The vertices shared by polys are written once.
+    /// Off-mesh connections are lines of the `off_mesh_connections` object. The heights are the
+    /// ones of the poly vertices, not of the detail mesh
+    pub fn export_obj(&self) -> Result<String> {
+        let mut vertices: Vec<Point> = vec![];
+        let mut indices: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut faces: Vec<(u8, Vec<usize>)> = vec![];
+        let mut links: Vec<[usize; 2]> = vec![];
+
+        for poly in self.polys()? {
+            let mut face: Vec<usize> = self
+                .poly_vertices(poly)?
+                .into_iter()
+                .map(|p| {
+                    let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+                    *indices.entry(key).or_insert_with(|| {
+                        vertices.push(p);
+                        vertices.len()
+                    })
+                })
+                .collect();
+
+            if face.len() == 2 {
+                links.push([face[0], face[1]]);
+                continue;
+            }
+
+            // The sign of the normal along y, Newell's method
+            let up: f32 = (0..face.len())
+                .map(|i| {
+                    let (a, b) = (
+                        vertices[face[i] - 1],
+                        vertices[face[(i + 1) % face.len()] - 1],
+                    );
+                    (a.z() - b.z()) * (a.x() + b.x())
+                })
+                .sum();
+            if up < 0.0 {
+                face.reverse();
+            }
+            faces.push((self.poly_area(poly)?, face));
+        }
+        faces.sort_by_key(|(area, _)| *area);
+
+        let mut obj = format!(
+            "# navmesh, {} polys and {} off-mesh connections\no navmesh\n",
+            faces.len(),
+            links.len()
+        );
+        for v in &vertices {
+            let _ = writeln!(obj, "v {} {} {}", v.x(), v.y(), v.z());
+        }
+
+        let mut current = None;
+        for (area, face) in &faces {
+            if current != Some(*area) {
+                let _ = writeln!(obj, "usemtl area_{}", area);
+                current = Some(*area);
+            }
+            obj.push('f');
+            for i in face {
+                let _ = write!(obj, " {}", i);
+            }
+            obj.push('\n');
+        }
+
+        if !links.is_empty() {
+            obj.push_str("o off_mesh_connections\n");
+            for [a, b] in &links {
+                let _ = writeln!(obj, "l {} {}", a, b);
+            }
+        }
+        Ok(obj)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::{BuildConfig, ConvexVolume, InputGeometry, OffMeshConnection};
+
+    #[test]
+    fn test_export_obj() {
+        let geom = InputGeometry {
+            volumes: vec![ConvexVolume {
+                verts: vec![
+                    (6.0, 0.0, 0.0).into(),
+                    (10.0, 0.0, 0.0).into(),
+                    (10.0, 0.0, 10.0).into(),
+                    (6.0, 0.0, 10.0).into(),
+                ],
+                hmin: -1.0,
+                hmax: 1.0,
+                area: 3,
+            }],
+            off_mesh_connections: vec![OffMeshConnection::new(
+                (2.0, 0.0, 2.0).into(),
+                (2.0, 0.0, 8.0).into(),
+                0.5,
+            )],
+            ..plane(10.0)
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let obj = q.export_obj().unwrap();
+
+        let lines: Vec<&str> = obj.lines().collect();
+        let vertices: Vec<Point> = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("v "))
+            .map(|l| {
+                let c: Vec<f32> = l.split(' ').map(|c| c.parse().unwrap()).collect();
+                Point([c[0], c[1], c[2]])
+            })
+            .collect();
+        let faces: Vec<Vec<usize>> = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("f "))
+            .map(|l| l.split(' ').map(|i| i.parse().unwrap()).collect())
+            .collect();
+
+        assert_eq!(faces.len() + 1, q.polys().unwrap().len());
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with("usemtl "))
+                .collect::<Vec<_>>(),
+            vec![&"usemtl area_0", &"usemtl area_3"]
+        );
+        assert_eq!(lines.iter().filter(|l| l.starts_with("l ")).count(), 1);
+
+        // Welded, and every face facing up
+        assert!(vertices.len() < faces.iter().map(|f| f.len()).sum());
+        for face in &faces {
+            let p: Vec<Point> = face.iter().map(|i| vertices[i - 1]).collect();
+            let up: f32 = (0..p.len())
+                .map(|i| {
+                    let (a, b) = (p[i], p[(i + 1) % p.len()]);
+                    (a.z() - b.z()) * (a.x() + b.x())
+                })
+                .sum();
+            assert!(up > 0.0, "{:?}", p);
+        }
+    }
+}
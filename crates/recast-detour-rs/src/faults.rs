@@ -0,0 +1,224 @@
+//! Forced query failures for exercising the error handling of game code, with the `fault-injection` feature
+#[cfg(any(test, feature = "fault-injection"))]
+use crate::RecastQuery;
+use crate::{Error, Result};
+#[cfg(any(test, feature = "fault-injection"))]
+use std::cell::RefCell;
+
+/// The queries faults can be injected into
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    /// `find_poly` and every query snapping its points with it
+    FindPoly,
+    /// `find_path` and `find_path_with_options`
+    FindPath,
+    /// `straight_path` and `straight_path_with_options`
+    StraightPath,
+    /// `find_random_point` and `find_random_point_around_circle`
+    RandomPoint,
+}
+
+/// What an injected fault does to the query
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// The query returns the error it returns on failure
+    Fail,
+    /// Path queries return the first half of their path, ending short of the goal,
+    /// the other queries fail
+    Partial,
+}
+
+#[cfg(any(test, feature = "fault-injection"))]
+#[derive(Debug, Clone)]
+struct FaultRule {
+    kind: QueryKind,
+    fault: Fault,
+    /// None to fault every call until cleared
+    remaining: Option<u32>,
+}
+
+/// The rules of a query, nothing without the feature
+#[derive(Debug, Default)]
+pub(crate) struct Faults {
+    #[cfg(any(test, feature = "fault-injection"))]
+    rules: RefCell<Vec<FaultRule>>,
+}
+
+impl Faults {
+    /// The injected fault of a call, consuming the first matching rule
+    #[cfg(any(test, feature = "fault-injection"))]
+    pub(crate) fn take(&self, kind: QueryKind) -> Option<Fault> {
+        let mut rules = self.rules.borrow_mut();
+        let i = rules.iter().position(|r| r.kind == kind)?;
+
+        let fault = rules[i].fault;
+        if let Some(remaining) = &mut rules[i].remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                rules.remove(i);
+            }
+        }
+        Some(fault)
+    }
+
+    #[cfg(not(any(test, feature = "fault-injection")))]
+    #[inline]
+    pub(crate) fn take(&self, _kind: QueryKind) -> Option<Fault> {
+        None
+    }
+
+    /// The injected error of a call
+    pub(crate) fn check(&self, kind: QueryKind) -> Result<()> {
+        match self.take(kind) {
+            Some(_) => Err(injected_error(kind)),
+            None => Ok(()),
+        }
+    }
+
+    /// The injected error of a path query call, true when the path is to be cut with `partial_len`
+    pub(crate) fn check_path(&self, kind: QueryKind) -> Result<bool> {
+        match self.take(kind) {
+            Some(Fault::Partial) => Ok(true),
+            Some(Fault::Fail) => Err(injected_error(kind)),
+            None => Ok(false),
+        }
+    }
+}
+
+/// The points kept of a path of `len` points by `Fault::Partial`, at least the start
+pub(crate) fn partial_len(len: usize) -> usize {
+    len.min((len / 2).max(1))
+}
+
+fn injected_error(kind: QueryKind) -> Error {
+    let msg = "injected fault".to_string();
+    match kind {
+        QueryKind::FindPath | QueryKind::StraightPath => Error::FindPathError(msg),
+        QueryKind::FindPoly | QueryKind::RandomPoint => Error::FindPointError(msg),
+    }
+}
+
+#[cfg(any(test, feature = "fault-injection"))]
+impl RecastQuery {
+    /// Make every call of the `kind` queries fail until `clear_faults`
+    pub fn inject_fault(&self, kind: QueryKind, fault: Fault) {
+        self.push_fault(kind, fault, None);
+    }
+
+    /// Make the next `times` calls of the `kind` queries fail.
+    /// Rules are consumed in the order they were injected
+    pub fn inject_fault_times(&self, kind: QueryKind, fault: Fault, times: u32) {
+        if times > 0 {
+            self.push_fault(kind, fault, Some(times));
+        }
+    }
+
+    pub fn clear_faults(&self) {
+        self.faults.rules.borrow_mut().clear();
+    }
+
+    fn push_fault(&self, kind: QueryKind, fault: Fault, remaining: Option<u32>) {
+        self.faults.rules.borrow_mut().push(FaultRule {
+            kind,
+            fault,
+            remaining,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::simple_mesh;
+    use crate::{NavMeshData, PathCrossings, PathOptions};
+
+    fn quad() -> RecastQuery {
+        RecastQuery::new_from_mesh(simple_mesh()).unwrap()
+    }
+
+    /// Three quads turning around the corner (10, 10)
+    fn corner() -> RecastQuery {
+        RecastQuery::new_from_mesh(NavMeshData {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 20.0, 0.0, 20.0, 10.0, 0.0, 20.0,
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2, 2, 5, 6, 2, 6, 7],
+            ..simple_mesh()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_partial_len() {
+        assert_eq!(partial_len(0), 0);
+        assert_eq!(partial_len(1), 1);
+        assert_eq!(partial_len(3), 1);
+        assert_eq!(partial_len(8), 4);
+    }
+
+    #[test]
+    fn test_inject_fault() {
+        let q = corner();
+        let ext = (0.5, 0.5, 0.5);
+        let (a, b) = ((2.0, 0.0, 2.0).into(), (12.0, 0.0, 18.0).into());
+        let full = q.find_path(a, b, ext).unwrap();
+        assert!(full.len() >= 3, "{:?}", full);
+
+        q.inject_fault(QueryKind::FindPath, Fault::Partial);
+        for _ in 0..3 {
+            let path = q.find_path(a, b, ext).unwrap();
+            assert_eq!(path, full[..partial_len(full.len())]);
+        }
+        let path = q
+            .find_path_with_options(a, b, ext, &PathOptions::default())
+            .unwrap();
+        assert_eq!(path.len(), partial_len(full.len()));
+        // Other queries are untouched
+        assert!(q.find_poly(a, ext).is_ok());
+
+        q.clear_faults();
+        assert_eq!(q.find_path(a, b, ext).unwrap(), full);
+    }
+
+    #[test]
+    fn test_inject_straight_path_fault() {
+        let q = quad();
+        let ext = (0.2, 0.2, 0.2);
+        let (start, end) = ((1.0, 0.0, 2.0).into(), (2.0, 0.0, 1.0).into());
+        let (_, start_poly) = q.find_poly(start, ext).unwrap();
+        let (_, end_poly) = q.find_poly(end, ext).unwrap();
+        let corridor = [start_poly, end_poly];
+        let full = q.straight_path(&corridor, start, end).unwrap();
+        assert_eq!(full.len(), 2);
+
+        q.inject_fault_times(QueryKind::StraightPath, Fault::Partial, 1);
+        let path = q
+            .straight_path_with_options(&corridor, start, end, PathCrossings::Area)
+            .unwrap();
+        assert_eq!(path.len(), 1);
+        assert_eq!(path[0].pos, full[0]);
+
+        q.inject_fault_times(QueryKind::StraightPath, Fault::Fail, 1);
+        assert!(matches!(
+            q.straight_path(&corridor, start, end),
+            Err(Error::FindPathError(_))
+        ));
+        assert_eq!(q.straight_path(&corridor, start, end).unwrap(), full);
+    }
+
+    #[test]
+    fn test_inject_fault_times() {
+        let q = quad();
+        let ext = (0.5, 0.5, 0.5);
+        let p = (1.0, 0.0, 1.0).into();
+
+        q.inject_fault_times(QueryKind::FindPoly, Fault::Fail, 2);
+        match q.find_poly(p, ext) {
+            Err(Error::FindPointError(msg)) => assert_eq!(msg, "injected fault"),
+            r => panic!("{:?}", r),
+        }
+        assert!(q.find_poly(p, ext).is_err());
+        assert!(q.find_poly(p, ext).is_ok());
+    }
+}
@@ -0,0 +1,277 @@
+//! Poly flags and the query filter deciding which polys queries may use
+use crate::{Error, PolyRef, RecastQuery, Result, SOFT_AREA_FIRST};
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+/// Abilities needed to traverse a poly, the same flags as the RecastDemo sample
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PolyFlags(pub u16);
+
+impl PolyFlags {
+    pub const EMPTY: PolyFlags = PolyFlags(0);
+    /// Ground, grass, road
+    pub const WALK: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_WALK);
+    /// Water
+    pub const SWIM: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_SWIM);
+    pub const DOOR: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_DOOR);
+    pub const JUMP: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_JUMP);
+    /// Excluded by the default filter
+    pub const DISABLED: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_DISABLED);
+    /// One way links down ledges, see `OffMeshConnection::drop_down`
+    pub const DROP: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_DROP);
+    pub const ALL: PolyFlags = PolyFlags(sys::RECASTC_POLYFLAGS_ALL);
+
+    pub fn contains(self, other: PolyFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: PolyFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for PolyFlags {
+    type Output = PolyFlags;
+    fn bitor(self, rhs: PolyFlags) -> PolyFlags {
+        PolyFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PolyFlags {
+    fn bitor_assign(&mut self, rhs: PolyFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for PolyFlags {
+    type Output = PolyFlags;
+    fn bitand(self, rhs: PolyFlags) -> PolyFlags {
+        PolyFlags(self.0 & rhs.0)
+    }
+}
+
+impl Not for PolyFlags {
+    type Output = PolyFlags;
+    fn not(self) -> PolyFlags {
+        PolyFlags(!self.0)
+    }
+}
+
+/// Filter applied by every query, see `dtQueryFilter`
+#[derive(Debug, Clone)]
+pub struct QueryFilter {
+    /// Polys must have at least one of these flags
+    pub include_flags: PolyFlags,
+    /// Polys must have none of these flags
+    pub exclude_flags: PolyFlags,
+    /// Traversal cost multiplier per area id
+    pub area_costs: [f32; 64],
+    /// Extra cost per world unit of height gained from poly to poly, added to the distance.
+    /// Makes paths wind up slopes instead of climbing them straight. Negative costs count as 0
+    pub uphill_cost: f32,
+    /// Extra cost per world unit of height lost, negative costs count as 0
+    pub downhill_cost: f32,
+}
+
+impl Default for QueryFilter {
+    /// Everything but disabled polys, all areas at cost 1.0, no cost for heights
+    fn default() -> QueryFilter {
+        QueryFilter {
+            include_flags: PolyFlags::ALL & !PolyFlags::DISABLED,
+            exclude_flags: PolyFlags::EMPTY,
+            area_costs: [1.0; 64],
+            uphill_cost: 0.0,
+            downhill_cost: 0.0,
+        }
+    }
+}
+
+impl QueryFilter {
+    pub fn include(mut self, flags: PolyFlags) -> QueryFilter {
+        self.include_flags = flags;
+        self
+    }
+
+    pub fn exclude(mut self, flags: PolyFlags) -> QueryFilter {
+        self.exclude_flags = flags;
+        self
+    }
+
+    /// Fails for areas from 64, the number of area ids of the navmesh
+    pub fn area_cost(mut self, area: u8, cost: f32) -> Result<QueryFilter> {
+        let c = self
+            .area_costs
+            .get_mut(area as usize)
+            .ok_or_else(|| Error::FilterError(format!("area {} out of range", area)))?;
+        *c = cost;
+        Ok(self)
+    }
+
+    /// Fails for negative costs, the path search needs costs of at least the distance
+    pub fn slope_costs(mut self, uphill: f32, downhill: f32) -> Result<QueryFilter> {
+        if !(uphill >= 0.0 && downhill >= 0.0) {
+            return Err(Error::FilterError(format!(
+                "invalid slope costs {} and {}",
+                uphill, downhill
+            )));
+        }
+        self.uphill_cost = uphill;
+        self.downhill_cost = downhill;
+        Ok(self)
+    }
+
+    fn as_sys(&self) -> sys::RecastQueryFilter {
+        sys::RecastQueryFilter {
+            include_flags: self.include_flags.0,
+            exclude_flags: self.exclude_flags.0,
+            area_costs: self.area_costs,
+            uphill_cost: self.uphill_cost.max(0.0),
+            downhill_cost: self.downhill_cost.max(0.0),
+        }
+    }
+}
+
+impl RecastQuery {
+    pub fn filter(&self) -> &QueryFilter {
+        &self.filter
+    }
+
+    /// Replace the query filter, the costs of the soft areas are kept
+    pub fn set_filter(&mut self, mut filter: QueryFilter) {
+        let soft = SOFT_AREA_FIRST as usize;
+        filter.area_costs[soft..].copy_from_slice(&self.filter.area_costs[soft..]);
+
+        let sys_filter = filter.as_sys();
+        unsafe { sys::recastc_set_filter(self.q.as_ptr(), &sys_filter as *const _) };
+        self.filter = filter;
+    }
+
+    pub fn poly_flags(&self, poly: PolyRef) -> Result<PolyFlags> {
+        let mut flags = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_flags(self.q.as_ptr(), poly, &mut flags, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(PolyFlags(flags))
+        }
+    }
+
+    /// Change the flags of a poly at runtime, e.g. to close a door
+    pub fn set_poly_flags(&mut self, poly: PolyRef, flags: PolyFlags) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_set_poly_flags(self.q.as_ptr(), poly, flags.0, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::{BuildConfig, ConvexVolume, InputGeometry};
+
+    fn plane_with_pool() -> InputGeometry {
+        InputGeometry {
+            volumes: vec![ConvexVolume {
+                verts: vec![
+                    (4.0, 0.0, 4.0).into(),
+                    (6.0, 0.0, 4.0).into(),
+                    (6.0, 0.0, 6.0).into(),
+                    (4.0, 0.0, 6.0).into(),
+                ],
+                hmin: -1.0,
+                hmax: 1.0,
+                area: 2,
+            }],
+            ..plane(10.0)
+        }
+    }
+
+    #[test]
+    fn test_poly_flags_ops() {
+        let f = PolyFlags::WALK | PolyFlags::DOOR;
+        assert!(f.contains(PolyFlags::WALK));
+        assert!(!f.contains(PolyFlags::WALK | PolyFlags::SWIM));
+        assert!(f.intersects(PolyFlags::DOOR | PolyFlags::SWIM));
+        assert_eq!(QueryFilter::default().include_flags, PolyFlags(0xffef));
+        assert!(QueryFilter::default().area_cost(64, 2.0).is_err());
+        assert!(BuildConfig::default().area_flags(64, PolyFlags::SWIM).is_err());
+    }
+
+    #[test]
+    fn test_area_flags_and_filter() {
+        let config = BuildConfig::default()
+            .area_flags(2, PolyFlags::SWIM)
+            .unwrap();
+        let mut q = RecastQuery::build(&plane_with_pool(), &config).unwrap();
+
+        let (_, pool) = q.find_poly((5.0, 0.0, 5.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_area(pool).unwrap(), 2);
+        assert_eq!(q.poly_flags(pool).unwrap(), PolyFlags::SWIM);
+        let (_, ground) = q.find_poly((1.0, 0.0, 1.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_flags(ground).unwrap(), PolyFlags::WALK);
+
+        q.set_filter(QueryFilter::default().exclude(PolyFlags::SWIM));
+        assert!(q.find_poly((5.0, 0.0, 5.0).into(), (0.1, 1.0, 0.1)).is_err());
+
+        q.set_poly_flags(pool, PolyFlags::WALK).unwrap();
+        assert!(q.find_poly((5.0, 0.0, 5.0).into(), (0.1, 1.0, 0.1)).is_ok());
+    }
+
+    /// Two pads joined by a hill rising to 1.5 and by a longer flat strip
+    fn hill_or_around() -> InputGeometry {
+        let mut geom = InputGeometry::default();
+        let mut quad = |x0: f32, x1: f32, z0: f32, z1: f32, y0: f32, y1: f32| {
+            let a = (geom.vertices.len() / 3) as u32;
+            geom.vertices.extend_from_slice(&[
+                x0, y0, z0, x1, y1, z0, x1, y1, z1, x0, y0, z1,
+            ]);
+            let (b, c, d) = (a + 1, a + 2, a + 3);
+            geom.indices.extend_from_slice(&[a, c, b, a, d, c]);
+        };
+        quad(0.0, 4.0, 0.0, 12.0, 0.0, 0.0);
+        quad(16.0, 20.0, 0.0, 12.0, 0.0, 0.0);
+        quad(4.0, 10.0, 0.0, 4.0, 0.0, 1.5);
+        quad(10.0, 16.0, 0.0, 4.0, 1.5, 0.0);
+        quad(4.0, 16.0, 8.0, 12.0, 0.0, 0.0);
+        geom
+    }
+
+    #[test]
+    fn test_slope_costs() {
+        let mut q = RecastQuery::build(&hill_or_around(), &BuildConfig::default()).unwrap();
+        let (start, end) = ((2.0, 0.0, 2.0).into(), (18.0, 0.0, 2.0).into());
+        let ext = (0.5, 1.0, 0.5);
+
+        let path = q.find_path(start, end, ext).unwrap();
+        assert!(path.iter().all(|p| p.z() < 6.0), "{:?}", path);
+
+        assert!(QueryFilter::default().slope_costs(-1.0, 2.0).is_err());
+        assert!(QueryFilter::default().slope_costs(1.0, f32::NAN).is_err());
+        q.set_filter(QueryFilter::default().slope_costs(10.0, 2.0).unwrap());
+        let path = q.find_path(start, end, ext).unwrap();
+        assert!(path.iter().any(|p| p.z() > 6.0), "{:?}", path);
+    }
+
+    #[test]
+    fn test_set_filter_keeps_soft_area_costs() {
+        let mut q = RecastQuery::build(&plane_with_pool(), &BuildConfig::default()).unwrap();
+        q.set_area_cost(SOFT_AREA_FIRST, 5.0).unwrap();
+
+        q.set_filter(QueryFilter::default().area_cost(2, 10.0).unwrap());
+        assert_eq!(q.filter().area_costs[2], 10.0);
+        assert_eq!(q.filter().area_costs[SOFT_AREA_FIRST as usize], 5.0);
+    }
+}
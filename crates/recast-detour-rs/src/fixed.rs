@@ -0,0 +1,340 @@
+//! Queries into buffers allocated once at startup, for console certification and hard real-time loops
+use crate::{
+    faults, Error, Fault, PathOptions, PathPoint, Point, PolyRef, QueryKind, RecastQuery, Result,
+    StraightPathFlags,
+};
+
+/// Most polys of the corridor of a `FixedQuery` path
+pub const FIXED_MAX_PATH_POLYS: usize = 1024;
+/// Most points of the straight path of a `FixedQuery` path
+pub const FIXED_MAX_PATH_POINTS: usize = 2048;
+/// Most polys found by `FixedQuery::polys_around`
+pub const FIXED_MAX_POLYS_AROUND: usize = 256;
+
+/// A query doing no heap allocation after `new`, neither on success nor on failure.
+///
+/// The results are written to buffers of fixed capacity kept by the query and borrowed until
+/// the next call, a path longer than the buffers fails with `BUFFER_TOO_SMALL`. Detour itself
+/// only allocates its node pool when the navmesh query is created. The errors carry static
+/// messages in `Error::FixedQueryError`. The paths found are not counted in the visit stats of
+/// the query, counting allocates
+pub struct FixedQuery {
+    query: RecastQuery,
+    path: Box<sys::RecastPathResult>,
+    polys: Box<sys::RecastPolysResult>,
+}
+
+impl std::fmt::Debug for FixedQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FixedQuery")
+            .field("query", &self.query)
+            .finish()
+    }
+}
+
+/// A path in the buffers of a `FixedQuery`
+#[derive(Debug, Copy, Clone)]
+pub struct FixedPath<'a> {
+    query: &'a RecastQuery,
+    result: &'a sys::RecastPathResult,
+}
+
+impl<'a> FixedPath<'a> {
+    /// Number of straight path points
+    pub fn len(&self) -> usize {
+        self.result.path2_count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The polys from the start to the end of the path
+    pub fn corridor(&self) -> &'a [PolyRef] {
+        &self.result.path[..self.result.path_count as usize]
+    }
+
+    /// The straight path point `i`
+    pub fn point(&self, i: usize) -> PathPoint {
+        let r = self.result;
+        let flags = StraightPathFlags(r.path2_flags[i]);
+        PathPoint {
+            pos: (r.path2[i * 3], r.path2[i * 3 + 1], r.path2[i * 3 + 2]).into(),
+            flags,
+            poly: r.path2_polys[i],
+            off_mesh_id: if flags.is_off_mesh_connection() {
+                self.query
+                    .off_mesh_connection(r.path2_polys[i])
+                    .ok()
+                    .map(|c| c.user_id)
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn points(&self) -> impl ExactSizeIterator<Item = PathPoint> + 'a {
+        let path = *self;
+        (0..self.len()).map(move |i| path.point(i))
+    }
+}
+
+impl FixedQuery {
+    /// Allocate the result buffers of the query once
+    pub fn new(query: RecastQuery) -> FixedQuery {
+        FixedQuery {
+            query,
+            path: Box::default(),
+            polys: Box::default(),
+        }
+    }
+
+    pub fn query(&self) -> &RecastQuery {
+        &self.query
+    }
+
+    /// The query back, e.g. to change its filter
+    pub fn into_inner(self) -> RecastQuery {
+        self.query
+    }
+
+    /// The point of the navmesh nearest to `pos` within the box of half extents `r`
+    pub fn find_poly(&self, pos: Point, r: (f32, f32, f32)) -> Result<(Point, PolyRef)> {
+        injected_fault(&self.query, QueryKind::FindPoly)?;
+        let mut result = sys::RecastNearestPolyResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastNearestPolyInput {
+            center: pos.0,
+            half_extents: [r.0, r.1, r.2],
+        };
+
+        let res = unsafe {
+            sys::recastc_find_nearest_poly(
+                self.query.q.as_ptr(),
+                &input as *const _,
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        match res {
+            0 => Err(static_error(&err)),
+            _ if result.poly == 0 => Err(Error::FixedQueryError("No poly found")),
+            _ => Ok((Point(result.pos), result.poly)),
+        }
+    }
+
+    /// Find a path like `RecastQuery::find_path_with_options`, into the buffers of the query
+    pub fn find_path(
+        &mut self,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        options: &PathOptions,
+    ) -> Result<FixedPath<'_>> {
+        let partial = injected_fault(&self.query, QueryKind::FindPath)?;
+        let (start_p, start_poly) = self.find_poly(start, r)?;
+        let (end_p, end_poly) = self.find_poly(end, r)?;
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastPathInput {
+            start_poly,
+            start_pos: start_p.0,
+            end_poly,
+            end_pos: end_p.0,
+            straight_path_options: options.crossings.as_sys(),
+            find_path_options: if options.raycast_shortcut {
+                sys::DT_FINDPATH_ANY_ANGLE
+            } else {
+                0
+            },
+            max_points: 0,
+        };
+
+        let res = unsafe {
+            sys::recastc_find_path(
+                self.query.q.as_ptr(),
+                &input as *const _,
+                &mut *self.path as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(match static_error(&err) {
+                Error::FixedQueryError("PARTIAL_RESULT") => Error::PartialResult,
+                e => e,
+            });
+        }
+
+        if partial {
+            self.path.path2_count = faults::partial_len(self.path.path2_count as usize) as _;
+        }
+        let path = FixedPath {
+            query: &self.query,
+            result: &self.path,
+        };
+        if path.is_empty() {
+            return Err(Error::FixedQueryError("No Path"));
+        }
+        Ok(path)
+    }
+
+    /// The polys reachable from the poly nearest to `center` without leaving the circle
+    pub fn polys_around(
+        &mut self,
+        center: Point,
+        radius: f32,
+        r: (f32, f32, f32),
+    ) -> Result<&[PolyRef]> {
+        injected_fault(&self.query, QueryKind::FindPoly)?;
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastPolysAroundCircleInput {
+            center: center.0,
+            radius,
+            half_extents: [r.0, r.1, r.2],
+        };
+
+        let res = unsafe {
+            sys::recastc_find_polys_around_circle(
+                self.query.q.as_ptr(),
+                &input as *const _,
+                &mut *self.polys as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(static_error(&err))
+        } else {
+            Ok(&self.polys.polys[..self.polys.poly_count as usize])
+        }
+    }
+}
+
+/// The messages of the query functions of the C api, matched without allocating
+fn static_error(err: &sys::RecastNavError) -> Error {
+    const MESSAGES: &[&str] = &[
+        "FAIL_TO_FIND_PATH",
+        "INVALID_PARAM",
+        "BUFFER_TOO_SMALL",
+        "OUT_OF_NODES",
+        "PARTIAL_RESULT",
+    ];
+    let msg = err.msg();
+    let msg = MESSAGES
+        .iter()
+        .find(|m| **m == msg)
+        .copied()
+        .unwrap_or("QUERY_FAILED");
+    Error::FixedQueryError(msg)
+}
+
+/// The fault injected into a call, true when the path is to be cut with `partial_len`. The
+/// error is static, the one of `Faults::check` allocates its message
+fn injected_fault(query: &RecastQuery, kind: QueryKind) -> Result<bool> {
+    match query.faults.take(kind) {
+        Some(Fault::Partial) if kind == QueryKind::FindPath => Ok(true),
+        Some(_) => Err(Error::FixedQueryError("injected fault")),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rooms;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// Counts the allocations of the current thread, the tests run on their own threads
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(|n| n.get())
+    }
+
+    #[test]
+    fn test_fixed_path_matches() {
+        let (start, end) = ((1.0, 0.0, 1.0).into(), (23.0, 0.0, 9.0).into());
+        let ext = (0.5, 1.0, 0.5);
+        let options = PathOptions::default();
+        let expected = rooms()
+            .find_path_with_options(start, end, ext, &options)
+            .unwrap();
+
+        let mut fixed = FixedQuery::new(rooms());
+        let path = fixed.find_path(start, end, ext, &options).unwrap();
+        assert_eq!(path.len(), expected.len());
+        assert!(path.corridor().len() > 1);
+        assert_eq!(path.points().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_no_allocation_per_query() {
+        let ext = (0.5, 1.0, 0.5);
+        let options = PathOptions::default();
+        let mut q = rooms();
+        q.enable_visit_stats();
+        let mut fixed = FixedQuery::new(q);
+
+        let before = allocations();
+        for _ in 0..10 {
+            let path = fixed
+                .find_path(
+                    (1.0, 0.0, 1.0).into(),
+                    (23.0, 0.0, 9.0).into(),
+                    ext,
+                    &options,
+                )
+                .unwrap();
+            let last = path.point(path.len() - 1);
+            assert!((last.pos.x() - 23.0).abs() < 0.1);
+
+            assert!(!fixed
+                .polys_around((5.0, 0.0, 5.0).into(), 3.0, ext)
+                .unwrap()
+                .is_empty());
+
+            // Failures too
+            let err = fixed.find_poly((50.0, 0.0, 50.0).into(), ext).unwrap_err();
+            assert!(matches!(err, Error::FixedQueryError("No poly found")));
+        }
+        assert_eq!(allocations(), before);
+        assert_eq!(fixed.into_inner().visit_stats().unwrap().path_count(), 0);
+    }
+
+    #[test]
+    fn test_inject_fault() {
+        let ext = (0.5, 1.0, 0.5);
+        let q = rooms();
+        q.inject_fault_times(QueryKind::FindPoly, crate::Fault::Fail, 1);
+        let mut fixed = FixedQuery::new(q);
+        let center = (5.0, 0.0, 5.0).into();
+        let before = allocations();
+        let err = fixed.polys_around(center, 3.0, ext).unwrap_err();
+        assert_eq!(allocations(), before);
+        assert!(matches!(err, Error::FixedQueryError("injected fault")));
+        assert!(fixed.polys_around(center, 3.0, ext).is_ok());
+    }
+}
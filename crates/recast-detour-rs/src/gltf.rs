@@ -0,0 +1,544 @@
+//! glTF 2.0 .gltf and .glb files loaded as the input geometry of a build
+use crate::{Error, InputGeometry, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const MODE_TRIANGLES: u64 = 4;
+const MODE_TRIANGLE_STRIP: u64 = 5;
+const MODE_TRIANGLE_FAN: u64 = 6;
+
+/// Most elements of an accessor without buffer view, which are zeros the file does not store
+const MAX_ZEROED_ELEMENTS: usize = 1 << 24;
+
+/// Column major, as the `matrix` of a glTF node
+type Matrix = [f32; 16];
+
+const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+fn gltf_error<S: Into<String>>(msg: S) -> Error {
+    Error::GltfError(msg.into())
+}
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[c * 4 + r] = (0..4).map(|k| a[k * 4 + r] * b[c * 4 + k]).sum();
+        }
+    }
+    m
+}
+
+fn transform(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    let mut out = [0.0; 3];
+    for (r, o) in out.iter_mut().enumerate() {
+        *o = m[r] * p[0] + m[4 + r] * p[1] + m[8 + r] * p[2] + m[12 + r];
+    }
+    out
+}
+
+/// A negative determinant mirrors the mesh and flips the winding of its faces
+fn is_mirrored(m: &Matrix) -> bool {
+    let det = m[0] * (m[5] * m[10] - m[9] * m[6]) - m[4] * (m[1] * m[10] - m[9] * m[2])
+        + m[8] * (m[1] * m[6] - m[5] * m[2]);
+    det < 0.0
+}
+
+fn floats<const N: usize>(v: &Value, default: [f32; N]) -> Result<[f32; N]> {
+    let a = match v.as_array() {
+        None if v.is_null() => return Ok(default),
+        Some(a) if a.len() == N => a,
+        _ => return Err(gltf_error("invalid node transform")),
+    };
+    let mut out = default;
+    for (o, x) in out.iter_mut().zip(a) {
+        *o = x
+            .as_f64()
+            .ok_or_else(|| gltf_error("invalid node transform"))? as f32;
+    }
+    Ok(out)
+}
+
+/// The local transform of a node, its `matrix` or its translation, rotation and scale
+fn node_matrix(node: &Value) -> Result<Matrix> {
+    if !node["matrix"].is_null() {
+        return floats(&node["matrix"], IDENTITY);
+    }
+    let [tx, ty, tz] = floats(&node["translation"], [0.0; 3])?;
+    let [x, y, z, w] = floats(&node["rotation"], [0.0, 0.0, 0.0, 1.0])?;
+    let [sx, sy, sz] = floats(&node["scale"], [1.0; 3])?;
+
+    // The columns of the rotation
+    let r = [
+        1.0 - 2.0 * (y * y + z * z),
+        2.0 * (x * y + z * w),
+        2.0 * (x * z - y * w),
+        2.0 * (x * y - z * w),
+        1.0 - 2.0 * (x * x + z * z),
+        2.0 * (y * z + x * w),
+        2.0 * (x * z + y * w),
+        2.0 * (y * z - x * w),
+        1.0 - 2.0 * (x * x + y * y),
+    ];
+    let mut m = IDENTITY;
+    for (c, scale) in [sx, sy, sz].iter().enumerate() {
+        for row in 0..3 {
+            m[c * 4 + row] = r[c * 3 + row] * scale;
+        }
+    }
+    m[12..15].copy_from_slice(&[tx, ty, tz]);
+    Ok(m)
+}
+
+fn index(v: &Value, what: &str) -> Result<usize> {
+    v.as_u64()
+        .map(|i| i as usize)
+        .ok_or_else(|| gltf_error(format!("invalid {} index", what)))
+}
+
+/// The json and the buffers of a file
+struct Document {
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document {
+    fn parse(bytes: &[u8], base: Option<&Path>) -> Result<Document> {
+        let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+            split_glb(bytes)?
+        } else {
+            (bytes, None)
+        };
+        let json: Value =
+            serde_json::from_slice(json).map_err(|e| gltf_error(format!("invalid json: {}", e)))?;
+
+        let mut buffers = vec![];
+        for buffer in json["buffers"].as_array().into_iter().flatten() {
+            let data = match buffer["uri"].as_str() {
+                None => bin
+                    .map(|b| b.to_vec())
+                    .ok_or_else(|| gltf_error("buffer without uri or binary chunk"))?,
+                Some(uri) if uri.starts_with("data:") => {
+                    let data = uri
+                        .split_once(";base64,")
+                        .map(|(_, data)| data)
+                        .ok_or_else(|| gltf_error("data uri is not base64"))?;
+                    base64::decode(data).map_err(|e| gltf_error(e.to_string()))?
+                }
+                Some(uri) => {
+                    let base =
+                        base.ok_or_else(|| gltf_error("external buffer without a directory"))?;
+                    fs::read(base.join(uri.replace("%20", " ")))
+                        .map_err(|e| gltf_error(format!("{}: {}", uri, e)))?
+                }
+            };
+            let len = buffer["byteLength"].as_u64().unwrap_or(0) as usize;
+            if data.len() < len {
+                return Err(gltf_error("buffer shorter than its byteLength"));
+            }
+            buffers.push(data);
+        }
+
+        Ok(Document { json, buffers })
+    }
+
+    /// The elements of an accessor as u32 or f32 components, `components` per element
+    fn read_accessor(&self, accessor: usize, components: usize) -> Result<Vec<f64>> {
+        let a = &self.json["accessors"][accessor];
+        if a.is_null() {
+            return Err(gltf_error("accessor out of range"));
+        }
+        if !a["sparse"].is_null() {
+            return Err(gltf_error("sparse accessors are not supported"));
+        }
+        let expected = match components {
+            1 => "SCALAR",
+            3 => "VEC3",
+            _ => unreachable!(),
+        };
+        if a["type"].as_str() != Some(expected) {
+            return Err(gltf_error(format!("accessor is not {}", expected)));
+        }
+
+        let (size, read): (usize, fn(&[u8]) -> f64) = match a["componentType"].as_u64() {
+            Some(5121) => (1, |b| b[0] as f64),
+            Some(5123) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f64),
+            Some(5125) => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            Some(5126) => (4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64),
+            _ => return Err(gltf_error("unsupported accessor component type")),
+        };
+        let count = a["count"].as_u64().unwrap_or(0) as usize;
+        let len = count
+            .checked_mul(components)
+            .ok_or_else(|| gltf_error("accessor count too large"))?;
+        let view_index = match a["bufferView"].as_u64() {
+            Some(view) => view as usize,
+            // No view is all zeros
+            None if count > MAX_ZEROED_ELEMENTS => {
+                return Err(gltf_error("accessor count too large"))
+            }
+            None => return Ok(vec![0.0; len]),
+        };
+
+        let view = &self.json["bufferViews"][view_index];
+        let buffer = self
+            .buffers
+            .get(index(&view["buffer"], "buffer")?)
+            .ok_or_else(|| gltf_error("buffer out of range"))?;
+        let view_offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let view_len = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        let element = size * components;
+        let stride = view["byteStride"].as_u64().unwrap_or(element as u64) as usize;
+        if stride < element {
+            return Err(gltf_error("byteStride smaller than the accessor elements"));
+        }
+        let offset = a["byteOffset"].as_u64().unwrap_or(0) as usize;
+
+        // Saturated, a huge count fails the bounds check below, so the count is bound by the
+        // length of the view before anything is allocated
+        let end = if count == 0 {
+            offset
+        } else {
+            offset.saturating_add(stride.saturating_mul(count - 1).saturating_add(element))
+        };
+        if end > view_len || view_offset.saturating_add(view_len) > buffer.len() {
+            return Err(gltf_error("accessor out of its buffer"));
+        }
+        let data = &buffer[view_offset + offset..view_offset + end];
+
+        let mut out = Vec::with_capacity(len);
+        for i in 0..count {
+            for c in 0..components {
+                out.push(read(&data[i * stride + c * size..]));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Append the triangles of the mesh transformed by `m` to the geometry
+    fn add_mesh(&self, mesh: usize, m: &Matrix, geometry: &mut InputGeometry) -> Result<()> {
+        let primitives = self.json["meshes"][mesh]["primitives"]
+            .as_array()
+            .ok_or_else(|| gltf_error("mesh out of range"))?;
+        let mirrored = is_mirrored(m);
+
+        for primitive in primitives {
+            let mode = primitive["mode"].as_u64().unwrap_or(MODE_TRIANGLES);
+            if ![MODE_TRIANGLES, MODE_TRIANGLE_STRIP, MODE_TRIANGLE_FAN].contains(&mode) {
+                // Points and lines
+                continue;
+            }
+
+            let positions =
+                self.read_accessor(index(&primitive["attributes"]["POSITION"], "POSITION")?, 3)?;
+            let vertex_count = positions.len() / 3;
+            let first = (geometry.vertices.len() / 3) as u32;
+            for p in positions.chunks(3) {
+                let p = transform(m, [p[0] as f32, p[1] as f32, p[2] as f32]);
+                geometry.vertices.extend_from_slice(&p);
+            }
+
+            let indices: Vec<u32> = match primitive["indices"].as_u64() {
+                Some(accessor) => self
+                    .read_accessor(accessor as usize, 1)?
+                    .into_iter()
+                    .map(|i| i as u32)
+                    .collect(),
+                None => (0..vertex_count as u32).collect(),
+            };
+            if indices.iter().any(|i| *i as usize >= vertex_count) {
+                return Err(gltf_error("index out of the vertices of its primitive"));
+            }
+
+            let triangles: Vec<[u32; 3]> = match mode {
+                MODE_TRIANGLES => indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+                // Every other triangle of a strip is wound the other way
+                MODE_TRIANGLE_STRIP => (2..indices.len())
+                    .map(|i| {
+                        let (a, b, c) = (indices[i - 2], indices[i - 1], indices[i]);
+                        if i % 2 == 0 {
+                            [a, b, c]
+                        } else {
+                            [b, a, c]
+                        }
+                    })
+                    .collect(),
+                _ => (2..indices.len())
+                    .map(|i| [indices[0], indices[i - 1], indices[i]])
+                    .collect(),
+            };
+            for [a, b, c] in triangles {
+                let (b, c) = if mirrored { (c, b) } else { (b, c) };
+                geometry
+                    .indices
+                    .extend_from_slice(&[first + a, first + b, first + c]);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_node(
+        &self,
+        node: usize,
+        parent: &Matrix,
+        depth: usize,
+        geometry: &mut InputGeometry,
+    ) -> Result<()> {
+        let n = &self.json["nodes"][node];
+        // The node hierarchy is a forest, a deeper one has a cycle
+        let node_count = self.json["nodes"].as_array().map_or(0, |a| a.len());
+        if n.is_null() || depth > node_count {
+            return Err(gltf_error("invalid node hierarchy"));
+        }
+
+        let m = mul(parent, &node_matrix(n)?);
+        if !n["mesh"].is_null() {
+            self.add_mesh(index(&n["mesh"], "mesh")?, &m, geometry)?;
+        }
+        for child in n["children"].as_array().into_iter().flatten() {
+            self.add_node(index(child, "node")?, &m, depth + 1, geometry)?;
+        }
+        Ok(())
+    }
+}
+
+/// The json chunk and the binary chunk of a .glb
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| gltf_error("truncated glb"))
+    };
+    if u32_at(4)? != 2 {
+        return Err(gltf_error("not a glTF 2.0 glb"));
+    }
+    let len = (u32_at(8)? as usize).min(bytes.len());
+
+    let (mut json, mut bin) = (None, None);
+    let mut at = 12;
+    while at + 8 <= len {
+        let (chunk_len, kind) = (u32_at(at)? as usize, u32_at(at + 4)?);
+        let chunk = bytes
+            .get(at + 8..at + 8 + chunk_len)
+            .ok_or_else(|| gltf_error("truncated glb"))?;
+        match kind {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        at += 8 + chunk_len;
+    }
+    Ok((json.ok_or_else(|| gltf_error("glb without json"))?, bin))
+}
+
+impl InputGeometry {
+    /// The triangles of the default scene of a .gltf or .glb file with the transforms of
+    /// their nodes applied, needs the `gltf` feature.
+    ///
+    /// glTF is Y-up in meters like the build. External buffers are read next to the file,
+    /// sparse accessors and quantized positions other than the standard integer types are not
+    /// supported, and points and lines are skipped. The faces keep their winding, mirrored nodes
+    /// included
+    pub fn from_gltf_file<P: AsRef<Path>>(path: P) -> Result<InputGeometry> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| gltf_error(e.to_string()))?;
+        InputGeometry::from_gltf(&bytes, path.parent())
+    }
+
+    /// The triangles of a .gltf or .glb in memory, `base` is the directory of its external
+    /// buffers. See `from_gltf_file`
+    pub fn from_gltf(bytes: &[u8], base: Option<&Path>) -> Result<InputGeometry> {
+        let doc = Document::parse(bytes, base)?;
+        let mut geometry = InputGeometry::default();
+
+        let roots: Vec<usize> = match doc.json["scenes"].as_array() {
+            Some(scenes) if !scenes.is_empty() => {
+                let scene = doc.json["scene"].as_u64().unwrap_or(0) as usize;
+                let scene = scenes
+                    .get(scene)
+                    .ok_or_else(|| gltf_error("scene out of range"))?;
+                scene["nodes"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|n| index(n, "node"))
+                    .collect::<Result<_>>()?
+            }
+            // Without scenes, every node which is not a child
+            _ => {
+                let nodes = doc.json["nodes"].as_array().cloned().unwrap_or_default();
+                let children: Vec<u64> = nodes
+                    .iter()
+                    .flat_map(|n| n["children"].as_array().cloned().unwrap_or_default())
+                    .filter_map(|c| c.as_u64())
+                    .collect();
+                (0..nodes.len())
+                    .filter(|i| !children.contains(&(*i as u64)))
+                    .collect()
+            }
+        };
+
+        for root in roots {
+            doc.add_node(root, &IDENTITY, 0, &mut geometry)?;
+        }
+        Ok(geometry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, RecastQuery};
+
+    /// A 10 by 10 quad of 4 positions and u16 indices, facing up
+    fn quad_buffer() -> Vec<u8> {
+        let mut data = vec![];
+        for f in &[
+            0.0f32, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+        ] {
+            data.extend_from_slice(&f.to_le_bytes());
+        }
+        for i in &[0u16, 2, 1, 0, 3, 2] {
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+        data
+    }
+
+    fn quad_json(buffer: &str, nodes: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": {},
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}] }}],
+                "buffers": [{}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 12 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+                ]
+            }}"#,
+            nodes, buffer
+        )
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().div_ceil(4) * 4, 0);
+
+        let mut out = GLB_MAGIC.to_vec();
+        let len = 12 + 8 + json.len() + 8 + bin.len();
+        for v in &[2, len as u32, json.len() as u32, GLB_CHUNK_JSON] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out.extend_from_slice(&json);
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+        out.extend_from_slice(&bin);
+        out
+    }
+
+    #[test]
+    fn test_node_transforms() {
+        let buffer = format!(
+            r#"{{ "byteLength": 60, "uri": "data:application/octet-stream;base64,{}" }}"#,
+            base64::encode(&quad_buffer())
+        );
+        // The quad moved by the parent, and a second time scaled down by its child
+        let nodes = r#"[
+            { "translation": [20, 1, 0], "mesh": 0, "children": [1] },
+            { "scale": [0.5, 1, 0.5], "rotation": [0, 0, 0, 1], "mesh": 0 }
+        ]"#;
+        let geom = InputGeometry::from_gltf(quad_json(&buffer, nodes).as_bytes(), None).unwrap();
+
+        assert_eq!(geom.vertices.len(), 24);
+        assert_eq!(&geom.vertices[6..9], &[30.0, 1.0, 10.0]);
+        assert_eq!(&geom.vertices[18..21], &[25.0, 1.0, 5.0]);
+        assert_eq!(geom.indices, vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6]);
+
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        q.find_path(
+            (21.0, 1.0, 1.0).into(),
+            (29.0, 1.0, 9.0).into(),
+            (0.5, 1.0, 0.5),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_glb_mirrored() {
+        let nodes = r#"[{ "matrix": [-1,0,0,0, 0,1,0,0, 0,0,1,0, 0,0,0,1], "mesh": 0 }]"#;
+        let json = quad_json(r#"{ "byteLength": 60 }"#, nodes);
+        let geom = InputGeometry::from_gltf(&glb(&json, &quad_buffer()), None).unwrap();
+
+        assert_eq!(&geom.vertices[3..6], &[-10.0, 0.0, 0.0]);
+        // Still facing up
+        assert_eq!(geom.indices, vec![0, 1, 2, 0, 2, 3]);
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        assert!(!q.polys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_gltf() {
+        let nodes = r#"[{ "mesh": 0 }]"#;
+        assert!(InputGeometry::from_gltf(b"{", None).is_err());
+        assert!(InputGeometry::from_gltf(b"glTF", None).is_err());
+
+        // Short buffer, external buffer without directory, node cycle
+        let json = quad_json(r#"{ "byteLength": 60 }"#, nodes);
+        assert!(InputGeometry::from_gltf(&glb(&json, &quad_buffer()[..40]), None).is_err());
+        let json = quad_json(r#"{ "byteLength": 60, "uri": "quad.bin" }"#, nodes);
+        assert!(InputGeometry::from_gltf(json.as_bytes(), None).is_err());
+        let json = quad_json(r#"{ "byteLength": 60 }"#, r#"[{ "children": [0] }]"#);
+        assert!(InputGeometry::from_gltf(&glb(&json, &quad_buffer()), None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_accessors() {
+        let json = quad_json(r#"{ "byteLength": 60 }"#, r#"[{ "mesh": 0 }]"#);
+        let edited = |from: &str, to: &str| {
+            assert!(json.contains(from));
+            let json = json.replace(from, to);
+            InputGeometry::from_gltf(&glb(&json, &quad_buffer()), None)
+        };
+        let positions = r#""bufferView": 0, "componentType": 5126, "count": 4"#;
+
+        // Overlapping positions, a count past the view or overflowing the element count
+        let view = r#""byteOffset": 0, "byteLength": 48"#;
+        let strided = r#""byteOffset": 0, "byteLength": 48, "byteStride": 4"#;
+        let err = edited(view, strided).unwrap_err();
+        assert!(err.to_string().contains("byteStride"), "{}", err);
+        let many = r#""bufferView": 0, "componentType": 5126, "count": 5"#;
+        assert!(edited(positions, many).is_err());
+        let huge = format!(
+            r#""bufferView": 0, "componentType": 5126, "count": {}"#,
+            u64::MAX / 2
+        );
+        assert!(edited(positions, &huge).is_err());
+
+        // Zeros without a view, within the cap
+        let zeros = r#""componentType": 5126, "count": 4"#;
+        let geom = edited(positions, zeros).unwrap();
+        assert!(geom.vertices.iter().all(|&v| v == 0.0));
+        let zeros = format!(r#""componentType": 5126, "count": {}"#, u64::MAX / 2);
+        let err = edited(positions, &zeros).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+    }
+}
@@ -0,0 +1,245 @@
+//! The poly adjacency of a navmesh as a graph, e.g. for betweenness or chokepoint analysis
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+
+/// A link from a poly to a neighbour poly
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PolyEdge {
+    pub from: PolyRef,
+    pub to: PolyRef,
+    /// Midpoint of the portal crossed from `from` to `to`, see `RecastQuery::portal`
+    pub portal: Point,
+    /// Between the poly centers in world unit
+    pub distance: f32,
+    /// `distance` times the area cost of the query filter for the area of `to`
+    pub cost: f32,
+}
+
+/// A poly as a node of `RecastQuery::as_portal_graph`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PolyNode {
+    pub poly: PolyRef,
+    pub center: Point,
+    pub area: u8,
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((a.x() - b.x()).powi(2) + (a.y() - b.y()).powi(2) + (a.z() - b.z()).powi(2)).sqrt()
+}
+
+impl RecastQuery {
+    /// The left and right ends of the edge of `from` crossed to reach the neighbour poly `to`,
+    /// looking from `from`. The part of the edge facing `to` on a tile border, both ends at the
+    /// ground end of an off-mesh connection
+    pub fn portal(&self, from: PolyRef, to: PolyRef) -> Result<(Point, Point)> {
+        let mut left = [0.0; 3];
+        let mut right = [0.0; 3];
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_portal(
+                self.q.as_ptr(),
+                from,
+                to,
+                left.as_mut_ptr(),
+                right.as_mut_ptr(),
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::PolyError(err.msg().to_string()));
+        }
+        Ok((Point(left), Point(right)))
+    }
+
+    /// Every link between polys passing the query filter, in the order of `polys`.
+    /// Links are directed, one way off-mesh connections have a single edge
+    pub fn poly_edges(&self) -> Result<Vec<PolyEdge>> {
+        let mut edges = vec![];
+        for from in self.polys()? {
+            let center = self.poly_center(from)?;
+            for to in self.poly_neighbours(from)? {
+                let d = distance(center, self.poly_center(to)?);
+                let area_cost = self.filter().area_costs[self.poly_area(to)? as usize];
+                let (left, right) = self.portal(from, to)?;
+                edges.push(PolyEdge {
+                    from,
+                    to,
+                    portal: Point([
+                        (left.x() + right.x()) * 0.5,
+                        (left.y() + right.y()) * 0.5,
+                        (left.z() + right.z()) * 0.5,
+                    ]),
+                    distance: d,
+                    cost: d * area_cost,
+                });
+            }
+        }
+        Ok(edges)
+    }
+
+    /// The polys passing the query filter as the nodes of a directed graph, weighted by `PolyEdge::cost`.
+    /// Needs the `petgraph` feature
+    #[cfg(feature = "petgraph")]
+    pub fn as_graph(&self) -> Result<petgraph::graph::DiGraph<PolyRef, f32>> {
+        use std::collections::HashMap;
+
+        let mut graph = petgraph::graph::DiGraph::new();
+        let nodes: HashMap<PolyRef, _> = self
+            .polys()?
+            .into_iter()
+            .map(|poly| (poly, graph.add_node(poly)))
+            .collect();
+
+        for edge in self.poly_edges()? {
+            if let (Some(&a), Some(&b)) = (nodes.get(&edge.from), nodes.get(&edge.to)) {
+                graph.add_edge(a, b, edge.cost);
+            }
+        }
+        Ok(graph)
+    }
+
+    /// `as_graph` with the polys and their links as they are, e.g. for k-shortest paths or
+    /// chokepoint centrality weighted by `PolyEdge::cost`, drawn at the centers and portals.
+    /// Needs the `petgraph` feature
+    #[cfg(feature = "petgraph")]
+    pub fn as_portal_graph(&self) -> Result<petgraph::graph::DiGraph<PolyNode, PolyEdge>> {
+        use std::collections::HashMap;
+
+        let mut graph = petgraph::graph::DiGraph::new();
+        let mut nodes = HashMap::new();
+        for poly in self.polys()? {
+            let node = PolyNode {
+                poly,
+                center: self.poly_center(poly)?,
+                area: self.poly_area(poly)?,
+            };
+            nodes.insert(poly, graph.add_node(node));
+        }
+
+        for edge in self.poly_edges()? {
+            if let (Some(&a), Some(&b)) = (nodes.get(&edge.from), nodes.get(&edge.to)) {
+                graph.add_edge(a, b, edge);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{plane, rooms};
+    use crate::{BuildConfig, InputGeometry, OffMeshConnection};
+
+    /// Distance from `p` to the segment `a` `b` on the ground plane
+    fn segment_distance(p: Point, a: Point, b: Point) -> f32 {
+        let (dx, dz) = (b.x() - a.x(), b.z() - a.z());
+        let len = dx * dx + dz * dz;
+        let t = if len > 0.0 {
+            (((p.x() - a.x()) * dx + (p.z() - a.z()) * dz) / len).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        ((p.x() - a.x() - t * dx).powi(2) + (p.z() - a.z() - t * dz).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn test_poly_edges() {
+        let q = rooms();
+        let polys = q.polys().unwrap();
+        let edges = q.poly_edges().unwrap();
+        assert!(!edges.is_empty());
+
+        for e in &edges {
+            assert!(polys.contains(&e.from) && polys.contains(&e.to));
+            assert!(e.distance > 0.0 && e.cost == e.distance);
+            // Ground links go both ways
+            assert!(edges.iter().any(|r| r.from == e.to && r.to == e.from));
+        }
+    }
+
+    #[test]
+    fn test_portals() {
+        let q = rooms();
+        for e in q.poly_edges().unwrap() {
+            // On the boundary of both polys, the same edge seen from either side
+            let (left, right) = q.portal(e.from, e.to).unwrap();
+            for poly in &[e.from, e.to] {
+                let verts = q.poly_vertices(*poly).unwrap();
+                let on_edge = |p: Point| {
+                    (0..verts.len())
+                        .any(|i| segment_distance(p, verts[i], verts[(i + 1) % verts.len()]) < 1e-3)
+                };
+                assert!(on_edge(left) && on_edge(right) && on_edge(e.portal));
+            }
+            assert_eq!(q.portal(e.to, e.from).unwrap(), (right, left));
+        }
+
+        let polys = q.polys().unwrap();
+        let far = polys
+            .iter()
+            .find(|p| !q.poly_neighbours(polys[0]).unwrap().contains(p) && **p != polys[0])
+            .unwrap();
+        assert!(matches!(
+            q.portal(polys[0], *far),
+            Err(Error::PolyError(ref msg)) if msg == "NOT_NEIGHBOURS"
+        ));
+    }
+
+    #[test]
+    fn test_off_mesh_portal() {
+        let geom = InputGeometry {
+            off_mesh_connections: vec![OffMeshConnection::new(
+                (2.0, 0.0, 2.0).into(),
+                (8.0, 0.0, 8.0).into(),
+                0.5,
+            )],
+            ..plane(10.0)
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let edges = q.poly_edges().unwrap();
+        let link = q
+            .polys()
+            .unwrap()
+            .into_iter()
+            .find(|&p| q.poly_vertices(p).unwrap().len() == 2)
+            .unwrap();
+
+        let from_link: Vec<_> = edges.iter().filter(|e| e.from == link).collect();
+        assert!(!from_link.is_empty());
+        for e in from_link {
+            let (left, right) = q.portal(e.from, e.to).unwrap();
+            assert_eq!(left, right);
+            assert_eq!(e.portal, left);
+            assert!(left.x() < 3.0 || left.x() > 7.0);
+        }
+    }
+
+    #[cfg(feature = "petgraph")]
+    #[test]
+    fn test_as_graph() {
+        let q = rooms();
+        let graph = q.as_graph().unwrap();
+        assert_eq!(graph.node_count(), q.polys().unwrap().len());
+        assert_eq!(graph.edge_count(), q.poly_edges().unwrap().len());
+
+        let portals = q.as_portal_graph().unwrap();
+        assert_eq!(portals.node_count(), graph.node_count());
+        assert_eq!(portals.edge_count(), graph.edge_count());
+        for e in portals.edge_indices() {
+            let (a, b) = portals.edge_endpoints(e).unwrap();
+            let edge = portals[e];
+            assert_eq!((portals[a].poly, portals[b].poly), (edge.from, edge.to));
+            assert_eq!(portals[a].center, q.poly_center(edge.from).unwrap());
+        }
+
+        // Both rooms reached through the passage
+        let start = portals
+            .node_indices()
+            .find(|&n| portals[n].center.x() < 5.0)
+            .unwrap();
+        let costs = petgraph::algo::dijkstra(&portals, start, None, |e| e.weight().cost);
+        assert_eq!(costs.len(), portals.node_count());
+    }
+}
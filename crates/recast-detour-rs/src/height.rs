@@ -0,0 +1,85 @@
+//! How the height of snapped points is picked, e.g. to not fight the ground snapping of physics
+use crate::{Point, PolyRef, RecastQuery, Result};
+
+/// The y of a point clamped to the navmesh, the x and z always come from the navmesh
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum HeightMode {
+    /// The height of the detail mesh of the poly
+    #[default]
+    Poly,
+    /// The y of the queried point, clamping on the xz plane only
+    Keep,
+    /// From the queried y at 0 to the poly height at 1
+    Blend(f32),
+    /// The queried y while within `epsilon` of the poly height, the poly height otherwise
+    Epsilon(f32),
+}
+
+impl HeightMode {
+    pub fn apply(self, query_y: f32, poly_y: f32) -> f32 {
+        match self {
+            HeightMode::Poly => poly_y,
+            HeightMode::Keep => query_y,
+            HeightMode::Blend(t) => {
+                let t = t.clamp(0.0, 1.0);
+                query_y + (poly_y - query_y) * t
+            }
+            HeightMode::Epsilon(epsilon) if (query_y - poly_y).abs() <= epsilon => query_y,
+            HeightMode::Epsilon(_) => poly_y,
+        }
+    }
+}
+
+impl RecastQuery {
+    /// The point of `poly` closest to `pos` with the height picked by `mode`
+    pub fn find_closest_point(&self, pos: Point, poly: PolyRef, mode: HeightMode) -> Result<Point> {
+        let p = self.find_closest(pos, poly)?;
+        Ok((p.x(), mode.apply(pos.y(), p.y()), p.z()).into())
+    }
+
+    /// `find_poly` with the height of the point picked by `mode`
+    pub fn find_poly_with_height(
+        &self,
+        pos: Point,
+        r: (f32, f32, f32),
+        mode: HeightMode,
+    ) -> Result<(Point, PolyRef)> {
+        let (p, poly) = self.find_poly(pos, r)?;
+        Ok(((p.x(), mode.apply(pos.y(), p.y()), p.z()).into(), poly))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::simple_mesh;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(HeightMode::Poly.apply(1.0, 0.0), 0.0);
+        assert_eq!(HeightMode::Keep.apply(1.0, 0.0), 1.0);
+        assert_eq!(HeightMode::Blend(0.25).apply(1.0, 0.0), 0.75);
+        assert_eq!(HeightMode::Blend(2.0).apply(1.0, 0.0), 0.0);
+        assert_eq!(HeightMode::Epsilon(0.1).apply(0.05, 0.0), 0.05);
+        assert_eq!(HeightMode::Epsilon(0.1).apply(0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_find_closest_point() {
+        let q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+
+        // Above the square and beyond its edge
+        let pos = (12.0, 0.4, 5.0).into();
+        let (p, poly) = q
+            .find_poly_with_height(pos, (3.0, 1.0, 3.0), HeightMode::Keep)
+            .unwrap();
+        assert!((p.x() - 10.0).abs() < 1e-4 && p.y() == 0.4, "{:?}", p);
+
+        let p = q.find_closest_point(pos, poly, HeightMode::Poly).unwrap();
+        assert!(p.y().abs() < 0.2, "{:?}", p);
+        let p = q
+            .find_closest_point(pos, poly, HeightMode::Epsilon(0.5))
+            .unwrap();
+        assert_eq!(p.y(), 0.4);
+    }
+}
@@ -0,0 +1,228 @@
+//! Build a navmesh from voxel occupancy instead of triangles
+use crate::recast::SysInput;
+use crate::{BuildConfig, Error, InputGeometry, Point, RecastQuery, Result, SOFT_AREA_FIRST};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Highest span height of Recast, `RC_SPAN_MAX_HEIGHT`
+pub const MAX_SPAN_HEIGHT: u16 = (1 << 13) - 1;
+
+/// A solid span of a column, heights in `cell_height` above the heightfield origin
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub smin: u16,
+    pub smax: u16,
+    /// Area id of the top of the span, None when agents cannot stand on it
+    pub area: Option<u8>,
+}
+
+/// Solid spans per column of cells, e.g. from the occupancy data of a voxel engine.
+///
+/// The columns are `cell_size` wide, column (x, z) starts at
+/// `origin + (x * cell_size, 0, z * cell_size)`. The walkable slope of the config
+/// does not apply, the spans give the walkable tops directly.
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    pub origin: Point,
+    width: i32,
+    height: i32,
+    columns: Vec<Vec<Span>>,
+}
+
+impl Heightfield {
+    /// An empty heightfield of `width` columns along x and `height` along z
+    pub fn new(origin: Point, width: i32, height: i32) -> Result<Heightfield> {
+        let columns = match width.checked_mul(height) {
+            Some(n) if width > 0 && height > 0 => n as usize,
+            _ => {
+                return Err(Error::BuildError(format!(
+                    "invalid heightfield size {}x{}",
+                    width, height
+                )))
+            }
+        };
+
+        Ok(Heightfield {
+            origin,
+            width,
+            height,
+            columns: vec![Vec::new(); columns],
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Add a span to a column, overlapping spans of a column are merged by the build
+    pub fn add_span(&mut self, x: i32, z: i32, span: Span) -> Result<()> {
+        if x < 0 || z < 0 || x >= self.width || z >= self.height {
+            return Err(Error::BuildError(format!(
+                "column ({}, {}) out of the heightfield",
+                x, z
+            )));
+        }
+        if span.smin > span.smax || span.smax > MAX_SPAN_HEIGHT {
+            return Err(Error::BuildError(format!(
+                "span heights {}..{} out of range",
+                span.smin, span.smax
+            )));
+        }
+        if let Some(area) = span.area {
+            if area >= SOFT_AREA_FIRST {
+                return Err(Error::BuildError(format!(
+                    "span area {} is reserved for soft areas",
+                    area
+                )));
+            }
+        }
+
+        self.columns[(x + z * self.width) as usize].push(span);
+        Ok(())
+    }
+
+    /// Spans of a column, in insertion order
+    pub fn column(&self, x: i32, z: i32) -> &[Span] {
+        &self.columns[(x + z * self.width) as usize]
+    }
+
+    pub fn span_count(&self) -> usize {
+        self.columns.iter().map(|c| c.len()).sum()
+    }
+}
+
+impl RecastQuery {
+    /// Build a navmesh from the spans of a heightfield, skipping the rasterization.
+    ///
+    /// The volumes, water and off-mesh connections of `extras` are applied as in `build`,
+    /// its triangles are ignored.
+    pub fn build_from_heightfield(
+        heightfield: &Heightfield,
+        extras: &InputGeometry,
+        config: &BuildConfig,
+    ) -> Result<RecastQuery> {
+        extras.validate()?;
+        config.validate()?;
+
+        let mut columns = Vec::with_capacity(heightfield.columns.len() + 1);
+        let mut spans = Vec::with_capacity(heightfield.span_count());
+        let mut top = 0;
+        for column in &heightfield.columns {
+            columns.push(spans.len() as u32);
+            // Recast expects the spans of a column bottom to top
+            let first = spans.len();
+            spans.extend(column.iter().map(|s| sys::RecastSpan {
+                smin: s.smin,
+                smax: s.smax,
+                area: s.area.unwrap_or(sys::RECASTC_SPAN_NOT_WALKABLE),
+            }));
+            spans[first..].sort_by_key(|s| s.smin);
+            top = column.iter().map(|s| s.smax).fold(top, u16::max);
+        }
+        columns.push(spans.len() as u32);
+
+        let origin = heightfield.origin;
+        let input = sys::RecastHeightfieldInput {
+            bmin: origin.0,
+            bmax: [
+                origin.x() + heightfield.width as f32 * config.cell_size,
+                // Leave room for the agent above the highest span
+                origin.y() + top as f32 * config.cell_height + config.walkable_height,
+                origin.z() + heightfield.height as f32 * config.cell_size,
+            ],
+            width: heightfield.width,
+            height: heightfield.height,
+            columns: columns.as_ptr(),
+            spans: spans.as_ptr(),
+            span_count: spans.len() as u32,
+        };
+
+        let sys_extras = SysInput::new(extras);
+        let extras = sys_extras.as_sys();
+        let config = config.as_sys();
+
+        let mut err = sys::RecastNavError::zeros();
+
+        let q = unsafe {
+            ptr::NonNull::new(sys::recastc_build_query_from_heightfield(
+                &input as *const _,
+                &extras as *const _,
+                &config as *const _,
+                &mut err as *mut _,
+            ) as *mut c_void)
+        };
+
+        let q = q.ok_or_else(|| Error::BuildError(err.msg().into_owned()))?;
+        Ok(RecastQuery::from_ptr(q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10x10 unit floor with a solid pillar in the middle
+    fn floor(config: &BuildConfig) -> Heightfield {
+        let n = (10.0 / config.cell_size) as i32;
+        let mut hf = Heightfield::new((0.0, 0.0, 0.0).into(), n, n).unwrap();
+
+        for z in 0..n {
+            for x in 0..n {
+                let pillar = (x - n / 2).abs() < n / 10 && (z - n / 2).abs() < n / 10;
+                let span = Span {
+                    smin: 0,
+                    smax: if pillar { 50 } else { 1 },
+                    area: if pillar { None } else { Some(0) },
+                };
+                hf.add_span(x, z, span).unwrap();
+            }
+        }
+        hf
+    }
+
+    #[test]
+    fn test_add_span_validation() {
+        assert!(Heightfield::new((0.0, 0.0, 0.0).into(), 0, 4).is_err());
+        assert!(Heightfield::new((0.0, 0.0, 0.0).into(), 4, -1).is_err());
+        assert!(Heightfield::new((0.0, 0.0, 0.0).into(), i32::MAX, 2).is_err());
+        let mut hf = Heightfield::new((0.0, 0.0, 0.0).into(), 4, 4).unwrap();
+        let span = Span {
+            smin: 0,
+            smax: 2,
+            area: Some(1),
+        };
+
+        assert!(hf.add_span(4, 0, span).is_err());
+        assert!(hf.add_span(0, 0, Span { smin: 3, ..span }).is_err());
+        let soft = Span {
+            area: Some(SOFT_AREA_FIRST),
+            ..span
+        };
+        assert!(hf.add_span(0, 0, soft).is_err());
+        hf.add_span(1, 2, span).unwrap();
+        assert_eq!(hf.column(1, 2), &[span]);
+        assert_eq!(hf.span_count(), 1);
+    }
+
+    #[test]
+    fn test_build_from_heightfield() {
+        let config = BuildConfig::default();
+        let hf = floor(&config);
+        let q = RecastQuery::build_from_heightfield(&hf, &Default::default(), &config).unwrap();
+
+        let ext = (0.2, 1.0, 0.2);
+        let (p, _) = q.find_poly((1.0, 0.0, 1.0).into(), ext).unwrap();
+        assert!((p.y() - config.cell_height).abs() < 0.2);
+
+        // Around the pillar, not over it
+        let path = q
+            .find_path((2.0, 0.0, 5.0).into(), (8.0, 0.0, 5.0).into(), ext)
+            .unwrap();
+        assert!(path.len() > 2);
+        assert!(q.find_poly((5.0, 5.0, 5.0).into(), ext).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Level geometry made of transformed mesh instances, e.g. instanced props and modular pieces
+use crate::{BuildConfig, Error, InputGeometry, Point, RecastQuery, Result};
+
+/// An affine transform of mesh vertices, the layout of glam `Affine3A`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshTransform {
+    /// Columns of the linear part
+    pub x_axis: [f32; 3],
+    pub y_axis: [f32; 3],
+    pub z_axis: [f32; 3],
+    pub translation: [f32; 3],
+}
+
+impl Default for MeshTransform {
+    fn default() -> MeshTransform {
+        MeshTransform::identity()
+    }
+}
+
+impl MeshTransform {
+    pub fn identity() -> MeshTransform {
+        MeshTransform::from_translation([0.0; 3])
+    }
+
+    pub fn from_translation(translation: [f32; 3]) -> MeshTransform {
+        MeshTransform {
+            x_axis: [1.0, 0.0, 0.0],
+            y_axis: [0.0, 1.0, 0.0],
+            z_axis: [0.0, 0.0, 1.0],
+            translation,
+        }
+    }
+
+    /// Scale, then rotate by the unit quaternion `(x, y, z, w)`, then translate
+    pub fn from_scale_rotation_translation(
+        scale: [f32; 3],
+        rotation: [f32; 4],
+        translation: [f32; 3],
+    ) -> MeshTransform {
+        let [x, y, z, w] = rotation;
+        let (x2, y2, z2) = (x + x, y + y, z + z);
+        let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+        let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+        let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+
+        let col = |c: [f32; 3], s: f32| [c[0] * s, c[1] * s, c[2] * s];
+        MeshTransform {
+            x_axis: col([1.0 - (yy + zz), xy + wz, xz - wy], scale[0]),
+            y_axis: col([xy - wz, 1.0 - (xx + zz), yz + wx], scale[1]),
+            z_axis: col([xz + wy, yz - wx, 1.0 - (xx + yy)], scale[2]),
+            translation,
+        }
+    }
+
+    /// The 4 columns one after the other, same as `Affine3A::to_cols_array`
+    pub fn from_cols_array(m: &[f32; 12]) -> MeshTransform {
+        MeshTransform {
+            x_axis: [m[0], m[1], m[2]],
+            y_axis: [m[3], m[4], m[5]],
+            z_axis: [m[6], m[7], m[8]],
+            translation: [m[9], m[10], m[11]],
+        }
+    }
+
+    pub fn transform_point(&self, p: Point) -> Point {
+        let mut out = self.translation;
+        for (i, o) in out.iter_mut().enumerate() {
+            *o += self.x_axis[i] * p.x() + self.y_axis[i] * p.y() + self.z_axis[i] * p.z();
+        }
+        Point(out)
+    }
+
+    /// Negative for mirroring transforms, which flip the winding of the triangles
+    pub fn determinant(&self) -> f32 {
+        let (a, b, c) = (self.x_axis, self.y_axis, self.z_axis);
+        a[0] * (b[1] * c[2] - b[2] * c[1]) - b[0] * (a[1] * c[2] - a[2] * c[1])
+            + c[0] * (a[1] * b[2] - a[2] * b[1])
+    }
+}
+
+/// A mesh placed in the level, the vertices and indices can be shared by many instances
+#[derive(Debug, Clone)]
+pub struct MeshInstance<'a> {
+    /// Vertices in the space of the mesh, 3 floats each
+    pub vertices: &'a [f32],
+    pub indices: &'a [u32],
+    /// Area id of every triangle of the instance, `None` for area 0
+    pub area: Option<u8>,
+    pub transform: MeshTransform,
+}
+
+impl<'a> MeshInstance<'a> {
+    pub fn new(
+        vertices: &'a [f32],
+        indices: &'a [u32],
+        transform: MeshTransform,
+    ) -> MeshInstance<'a> {
+        MeshInstance {
+            vertices,
+            indices,
+            area: None,
+            transform,
+        }
+    }
+}
+
+impl InputGeometry {
+    /// Append the transformed triangles of an instance. Mirroring transforms keep the triangles
+    /// facing up by reversing their winding
+    pub fn add_instance(&mut self, instance: &MeshInstance) -> Result<()> {
+        if !instance.vertices.len().is_multiple_of(3) || !instance.indices.len().is_multiple_of(3) {
+            return Err(Error::BuildError(
+                "instance vertices and indices lengths must be multiples of 3".into(),
+            ));
+        }
+        let n_verts = instance.vertices.len() / 3;
+        if let Some(i) = instance.indices.iter().find(|&&i| i as usize >= n_verts) {
+            return Err(Error::BuildError(format!(
+                "instance index {} out of bounds, only {} vertices",
+                i, n_verts
+            )));
+        }
+
+        let first = (self.vertices.len() / 3) as u32;
+        for v in instance.vertices.chunks(3) {
+            let p = instance
+                .transform
+                .transform_point(Point([v[0], v[1], v[2]]));
+            self.vertices.extend_from_slice(&p.0);
+        }
+
+        let flip = instance.transform.determinant() < 0.0;
+        for t in instance.indices.chunks(3) {
+            let (b, c) = if flip { (t[2], t[1]) } else { (t[1], t[2]) };
+            self.indices
+                .extend_from_slice(&[first + t[0], first + b, first + c]);
+        }
+
+        // Triangle areas are only kept once an instance has one
+        let n_tris = self.indices.len() / 3;
+        if instance.area.is_some() || !self.triangle_areas.is_empty() {
+            self.triangle_areas
+                .resize(n_tris - instance.indices.len() / 3, 0);
+            self.triangle_areas
+                .resize(n_tris, instance.area.unwrap_or(0));
+        }
+
+        Ok(())
+    }
+
+    pub fn from_instances(instances: &[MeshInstance]) -> Result<InputGeometry> {
+        let mut geometry = InputGeometry::default();
+        for instance in instances {
+            geometry.add_instance(instance)?;
+        }
+        Ok(geometry)
+    }
+}
+
+impl RecastQuery {
+    /// Build a navmesh from the instances rasterized together
+    pub fn build_instances(
+        instances: &[MeshInstance],
+        config: &BuildConfig,
+    ) -> Result<RecastQuery> {
+        RecastQuery::build(&InputGeometry::from_instances(instances)?, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD_VERTS: [f32; 12] = [0.0, 0.0, 0.0, 5.0, 0.0, 0.0, 5.0, 0.0, 10.0, 0.0, 0.0, 10.0];
+    const QUAD_INDICES: [u32; 6] = [0, 2, 1, 0, 3, 2];
+
+    #[test]
+    fn test_transform() {
+        // A quarter turn about y
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        let t = MeshTransform::from_scale_rotation_translation(
+            [2.0, 1.0, 1.0],
+            [0.0, h, 0.0, h],
+            [1.0, 0.0, 0.0],
+        );
+        let p = t.transform_point((1.0, 0.0, 0.0).into());
+        assert!(
+            (p.x() - 1.0).abs() < 1e-5 && (p.z() + 2.0).abs() < 1e-5,
+            "{:?}",
+            p
+        );
+        assert!((t.determinant() - 2.0).abs() < 1e-5);
+
+        let cols = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 3.0, 4.0, 5.0];
+        assert_eq!(
+            MeshTransform::from_cols_array(&cols),
+            MeshTransform::from_translation([3.0, 4.0, 5.0])
+        );
+    }
+
+    #[test]
+    fn test_build_instances() {
+        let left = MeshInstance::new(&QUAD_VERTS, &QUAD_INDICES, MeshTransform::identity());
+        // Mirrored about x = 5
+        let right = MeshInstance {
+            area: Some(3),
+            ..MeshInstance::new(
+                &QUAD_VERTS,
+                &QUAD_INDICES,
+                MeshTransform::from_scale_rotation_translation(
+                    [-1.0, 1.0, 1.0],
+                    [0.0, 0.0, 0.0, 1.0],
+                    [10.0, 0.0, 0.0],
+                ),
+            )
+        };
+
+        let geom = InputGeometry::from_instances(&[left.clone(), right.clone()]).unwrap();
+        assert_eq!(geom.vertices.len(), 24);
+        assert_eq!(geom.triangle_areas, vec![0, 0, 3, 3]);
+        assert_eq!(&geom.indices[6..9], &[4, 5, 6]);
+
+        let q = RecastQuery::build_instances(&[left, right], &BuildConfig::default()).unwrap();
+        let ext = (0.5, 1.0, 0.5);
+        let (_, poly) = q.find_poly((8.0, 0.0, 5.0).into(), ext).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+        let path = q
+            .find_path((1.0, 0.0, 5.0).into(), (9.0, 0.0, 5.0).into(), ext)
+            .unwrap();
+        assert!((path.last().unwrap().x() - 9.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,432 @@
+//! Analyses over the poly graph split into bounded steps, to run within a frame budget
+use crate::{Point, PolyRef, RecastQuery, Result};
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::mem;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus<T> {
+    InProgress,
+    Done(T),
+}
+
+/// A resumable analysis, each step does at most `budget` units of work.
+///
+/// A job starts over after it is done, so it can be run again once the navmesh changed.
+/// The navmesh should not change while a job is in progress.
+pub trait Job {
+    type Output;
+
+    fn step(&mut self, q: &RecastQuery, budget: usize) -> Result<JobStatus<Self::Output>>;
+
+    /// Step the job until it is done
+    fn run(&mut self, q: &RecastQuery) -> Result<Self::Output> {
+        loop {
+            if let JobStatus::Done(output) = self.step(q, usize::MAX)? {
+                return Ok(output);
+            }
+        }
+    }
+}
+
+/// Connected poly islands, polys with the same label are reachable from each other
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Islands {
+    pub labels: HashMap<PolyRef, u32>,
+    pub count: u32,
+}
+
+impl Islands {
+    pub fn label(&self, poly: PolyRef) -> Option<u32> {
+        self.labels.get(&poly).copied()
+    }
+
+    pub fn connected(&self, a: PolyRef, b: PolyRef) -> bool {
+        match (self.label(a), self.label(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IslandState {
+    polys: Vec<PolyRef>,
+    next: usize,
+    stack: Vec<PolyRef>,
+    islands: Islands,
+}
+
+/// Label the connected islands of the navmesh, the budget counts visited polys
+#[derive(Debug, Default)]
+pub struct IslandJob {
+    state: Option<IslandState>,
+}
+
+impl IslandJob {
+    pub fn new() -> IslandJob {
+        IslandJob::default()
+    }
+}
+
+impl Job for IslandJob {
+    type Output = Islands;
+
+    fn step(&mut self, q: &RecastQuery, budget: usize) -> Result<JobStatus<Islands>> {
+        if self.state.is_none() {
+            self.state = Some(IslandState {
+                polys: q.polys()?,
+                next: 0,
+                stack: Vec::new(),
+                islands: Islands::default(),
+            });
+        }
+        let state = self.state.as_mut().unwrap();
+
+        let mut work = 0;
+        while work < budget {
+            let poly = match state.stack.pop() {
+                Some(poly) => poly,
+                None => {
+                    // Start a new island from the next unlabeled poly
+                    while state.next < state.polys.len()
+                        && state.islands.labels.contains_key(&state.polys[state.next])
+                    {
+                        state.next += 1;
+                    }
+                    if state.next == state.polys.len() {
+                        let islands = self.state.take().unwrap().islands;
+                        return Ok(JobStatus::Done(islands));
+                    }
+
+                    let poly = state.polys[state.next];
+                    state.islands.labels.insert(poly, state.islands.count);
+                    state.islands.count += 1;
+                    poly
+                }
+            };
+
+            let label = state.islands.count - 1;
+            for n in q.poly_neighbours(poly)? {
+                if let Entry::Vacant(e) = state.islands.labels.entry(n) {
+                    e.insert(label);
+                    state.stack.push(n);
+                }
+            }
+            work += 1;
+        }
+
+        Ok(JobStatus::InProgress)
+    }
+}
+
+/// The cheapest direction towards a goal poly from every poly reaching it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowField {
+    pub goal: PolyRef,
+    /// The next poly on the way to the goal
+    pub next: HashMap<PolyRef, PolyRef>,
+    /// The cost to reach the goal
+    pub cost: HashMap<PolyRef, f32>,
+}
+
+impl FlowField {
+    pub fn next(&self, poly: PolyRef) -> Option<PolyRef> {
+        self.next.get(&poly).copied()
+    }
+
+    pub fn cost(&self, poly: PolyRef) -> Option<f32> {
+        self.cost.get(&poly).copied()
+    }
+
+    /// The polys from `poly` to the goal, None when the goal is not reachable
+    pub fn path(&self, poly: PolyRef) -> Option<Vec<PolyRef>> {
+        self.cost.get(&poly)?;
+
+        let mut path = vec![poly];
+        let mut current = poly;
+        while current != self.goal {
+            current = self.next(current)?;
+            path.push(current);
+        }
+        Some(path)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Open {
+    cost: f32,
+    poly: PolyRef,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Open) -> Ordering {
+        // Reversed so the heap pops the cheapest poly first
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.poly.cmp(&other.poly))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug)]
+struct FlowFieldState {
+    open: BinaryHeap<Open>,
+    centers: HashMap<PolyRef, Point>,
+    field: FlowField,
+}
+
+/// Build the flow field of a goal poly, the cost between polys is the distance between
+/// their centers scaled by the filter cost of the area entered. The field only follows the
+/// links in the direction agents travel them, e.g. one way off-mesh connections toward the goal.
+/// The budget counts expanded polys.
+#[derive(Debug)]
+pub struct FlowFieldJob {
+    goal: PolyRef,
+    state: Option<FlowFieldState>,
+}
+
+impl FlowFieldJob {
+    pub fn new(goal: PolyRef) -> FlowFieldJob {
+        FlowFieldJob { goal, state: None }
+    }
+}
+
+fn center(q: &RecastQuery, centers: &mut HashMap<PolyRef, Point>, poly: PolyRef) -> Result<Point> {
+    if let Some(c) = centers.get(&poly) {
+        return Ok(*c);
+    }
+    let c = q.poly_center(poly)?;
+    centers.insert(poly, c);
+    Ok(c)
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    let d = [a.0[0] - b.0[0], a.0[1] - b.0[1], a.0[2] - b.0[2]];
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
+
+impl Job for FlowFieldJob {
+    type Output = FlowField;
+
+    fn step(&mut self, q: &RecastQuery, budget: usize) -> Result<JobStatus<FlowField>> {
+        if self.state.is_none() {
+            let mut field = FlowField {
+                goal: self.goal,
+                ..Default::default()
+            };
+            field.cost.insert(self.goal, 0.0);
+
+            let mut open = BinaryHeap::new();
+            open.push(Open {
+                cost: 0.0,
+                poly: self.goal,
+            });
+
+            self.state = Some(FlowFieldState {
+                open,
+                centers: HashMap::new(),
+                field,
+            });
+        }
+        let state = self.state.as_mut().unwrap();
+
+        let mut work = 0;
+        while work < budget {
+            let Open { cost, poly } = match state.open.pop() {
+                Some(open) => open,
+                None => {
+                    let field = self.state.take().unwrap().field;
+                    return Ok(JobStatus::Done(field));
+                }
+            };
+            if cost > state.field.cost[&poly] {
+                // Stale entry, the poly was reached cheaper since
+                continue;
+            }
+
+            // The agents at the neighbours move from them into the poly
+            let pos = center(q, &mut state.centers, poly)?;
+            let area_cost = q.filter().area_costs[q.poly_area(poly)? as usize];
+            for n in q.poly_neighbours(poly)? {
+                // The links of one way off-mesh connections only go from their start
+                if !q.poly_neighbours(n)?.contains(&poly) {
+                    continue;
+                }
+                let n_pos = center(q, &mut state.centers, n)?;
+                let n_cost = cost + distance(pos, n_pos) * area_cost;
+
+                if !state.field.cost(n).is_some_and(|c| c <= n_cost) {
+                    state.field.cost.insert(n, n_cost);
+                    state.field.next.insert(n, poly);
+                    state.open.push(Open {
+                        cost: n_cost,
+                        poly: n,
+                    });
+                }
+            }
+            work += 1;
+        }
+
+        Ok(JobStatus::InProgress)
+    }
+}
+
+/// Pick random points on the navmesh, the budget counts sampled points
+#[derive(Debug)]
+pub struct SamplingJob<R: Rng> {
+    rng: R,
+    count: usize,
+    points: Vec<(Point, PolyRef)>,
+}
+
+impl<R: Rng> SamplingJob<R> {
+    pub fn new(count: usize, rng: R) -> SamplingJob<R> {
+        SamplingJob {
+            rng,
+            count,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl<R: Rng> Job for SamplingJob<R> {
+    type Output = Vec<(Point, PolyRef)>;
+
+    fn step(&mut self, q: &RecastQuery, budget: usize) -> Result<JobStatus<Self::Output>> {
+        let todo = (self.count - self.points.len()).min(budget);
+        for _ in 0..todo {
+            let point = q.find_random_point(&mut self.rng)?;
+            self.points.push(point);
+        }
+
+        if self.points.len() == self.count {
+            Ok(JobStatus::Done(mem::take(&mut self.points)))
+        } else {
+            Ok(JobStatus::InProgress)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::simple_mesh;
+    use crate::{NavMeshData, OffMeshConnection};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Two separate quads
+    fn two_islands() -> RecastQuery {
+        RecastQuery::new_from_mesh(two_islands_mesh()).unwrap()
+    }
+
+    fn two_islands_mesh() -> NavMeshData {
+        let vertices = vec![
+            0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+            20.0, 0.0, 0.0, 30.0, 0.0, 0.0, 30.0, 0.0, 10.0, 20.0, 0.0, 10.0,
+        ];
+        let indices = vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+
+        NavMeshData {
+            vertices,
+            indices,
+            ..simple_mesh()
+        }
+    }
+
+    #[test]
+    fn test_island_job_in_steps() {
+        let q = two_islands();
+        let (_, a) = q
+            .find_poly((1.0, 0.0, 1.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+        let (_, b) = q
+            .find_poly((9.0, 0.0, 9.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+        let (_, c) = q
+            .find_poly((21.0, 0.0, 1.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+
+        let mut job = IslandJob::new();
+        let islands = loop {
+            if let JobStatus::Done(islands) = job.step(&q, 1).unwrap() {
+                break islands;
+            }
+        };
+
+        assert_eq!(islands.count, 2);
+        assert!(islands.connected(a, b));
+        assert!(!islands.connected(a, c));
+        assert_eq!(islands, job.run(&q).unwrap());
+    }
+
+    #[test]
+    fn test_flow_field_job() {
+        let q = two_islands();
+        let (_, goal) = q
+            .find_poly((1.0, 0.0, 1.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+        let (_, a) = q
+            .find_poly((9.0, 0.0, 9.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+        let (_, c) = q
+            .find_poly((21.0, 0.0, 1.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+
+        let field = FlowFieldJob::new(goal).run(&q).unwrap();
+
+        assert_eq!(field.cost(goal), Some(0.0));
+        let path = field.path(a).unwrap();
+        assert_eq!(path.first(), Some(&a));
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(field.path(c), None);
+    }
+
+    #[test]
+    fn test_flow_field_one_way_link() {
+        let mut mesh = two_islands_mesh();
+        mesh.off_mesh_connections.push(OffMeshConnection {
+            bidirectional: false,
+            ..OffMeshConnection::new((9.0, 0.0, 5.0).into(), (21.0, 0.0, 5.0).into(), 0.5)
+        });
+        let q = RecastQuery::new_from_mesh(mesh).unwrap();
+        let (_, a) = q
+            .find_poly((9.0, 0.0, 9.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+        let (_, c) = q
+            .find_poly((21.0, 0.0, 1.0).into(), (0.5, 0.5, 0.5))
+            .unwrap();
+
+        // Down the link to the other island but never back
+        let field = FlowFieldJob::new(c).run(&q).unwrap();
+        assert_eq!(field.path(a).unwrap().last(), Some(&c));
+        let field = FlowFieldJob::new(a).run(&q).unwrap();
+        assert_eq!(field.path(c), None);
+    }
+
+    #[test]
+    fn test_sampling_job() {
+        let q = two_islands();
+        let mut job = SamplingJob::new(5, StdRng::seed_from_u64(1));
+
+        for _ in 0..4 {
+            assert_eq!(job.step(&q, 1).unwrap(), JobStatus::InProgress);
+        }
+        match job.step(&q, 10).unwrap() {
+            JobStatus::Done(points) => assert_eq!(points.len(), 5),
+            JobStatus::InProgress => panic!("sampling should be done"),
+        }
+    }
+}
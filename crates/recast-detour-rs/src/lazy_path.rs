@@ -0,0 +1,217 @@
+//! Paths along long corridors refined a few corners at a time, e.g. an agent chasing across an open world
+use crate::{Error, PathOptions, PathPoint, Point, PolyRef, RecastQuery, Result};
+
+/// The corridor of a path with the straight path computed only for the next corners.
+///
+/// Replanning the corridor is the costly part of a long chase, the corners are cheap to
+/// refine from the current position as the agent advances
+#[derive(Debug, Clone)]
+pub struct LazyPath {
+    corridor: Vec<PolyRef>,
+    pos: Point,
+    end: Point,
+    corners: u32,
+    options: PathOptions,
+}
+
+impl LazyPath {
+    /// The polys left from the poly of the last position to the poly of the end
+    pub fn corridor(&self) -> &[PolyRef] {
+        &self.corridor
+    }
+
+    /// The last position on the navmesh
+    pub fn pos(&self) -> Point {
+        self.pos
+    }
+
+    /// The end on the navmesh
+    pub fn end(&self) -> Point {
+        self.end
+    }
+
+    /// The next corners from the position of the agent, the last one is the end when it is
+    /// among them.
+    ///
+    /// The agent moves along the surface from the last position toward `pos`, like
+    /// `dtPathCorridor::movePosition`: it stops at the walls, the polys it passed are trimmed
+    /// from the corridor and the polys it stepped on off the corridor are prepended to it
+    pub fn next_corners(&mut self, q: &RecastQuery, pos: Point) -> Result<Vec<PathPoint>> {
+        let (pos, visited) = q.move_along_surface(self.corridor[0], self.pos, pos)?;
+        merge_corridor_start_moved(&mut self.corridor, &visited);
+        self.pos = pos;
+
+        let path = q.limited_straight_path(
+            &self.corridor,
+            pos,
+            self.end,
+            self.options.crossings,
+            self.corners + 1,
+        )?;
+        // The first point is the position itself
+        Ok(path.into_iter().skip(1).collect())
+    }
+}
+
+/// The corridor from the last poly of `visited` when the agent moved from the first poly of the
+/// corridor over the `visited` polys, see `dtMergeCorridorStartMoved`
+fn merge_corridor_start_moved(corridor: &mut Vec<PolyRef>, visited: &[PolyRef]) {
+    // The furthest poly of the corridor the agent visited
+    let furthest = corridor
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, p)| visited.iter().rposition(|v| v == p).map(|j| (i, j)));
+    let (i, j) = match furthest {
+        Some(furthest) => furthest,
+        None => return,
+    };
+
+    let mut merged: Vec<_> = visited[j..].iter().rev().copied().collect();
+    merged.extend_from_slice(&corridor[i + 1..]);
+    *corridor = merged;
+}
+
+impl RecastQuery {
+    /// Move from the point `start` of `poly` toward `end` constrained to the navmesh surface,
+    /// returns the position reached and the polys from `poly` to the one of that position
+    pub fn move_along_surface(
+        &self,
+        poly: PolyRef,
+        start: Point,
+        end: Point,
+    ) -> Result<(Point, Vec<PolyRef>)> {
+        let mut pos = [0.0; 3];
+        let mut visited = sys::RecastPolysResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_move_along_surface(
+                self.q.as_ptr(),
+                poly,
+                start.0.as_ptr(),
+                end.0.as_ptr(),
+                pos.as_mut_ptr(),
+                &mut visited as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::FindPointError(err.msg().to_string()));
+        }
+
+        let visited = visited.polys[..visited.poly_count as usize].to_vec();
+        Ok((Point(pos), visited))
+    }
+
+    /// Plan the corridor of a path and compute only its first `corners` straight path points,
+    /// the later ones follow from `LazyPath::next_corners` as the agent advances
+    pub fn find_lazy_path(
+        &self,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        corners: u32,
+        options: &PathOptions,
+    ) -> Result<(LazyPath, Vec<PathPoint>)> {
+        let corners = corners.max(1);
+        let start = self.find_poly(start, r)?;
+        let end = self.find_poly(end, r)?;
+
+        let (corridor, path) = self.plan_corridor(start, end, options, corners + 1)?;
+        if corridor.is_empty() {
+            return Err(Error::FindPathError("No Path".to_string()));
+        }
+
+        let lazy = LazyPath {
+            corridor,
+            pos: start.0,
+            end: end.0,
+            corners,
+            options: options.clone(),
+        };
+        Ok((lazy, path.into_iter().skip(1).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rooms;
+
+    #[test]
+    fn test_corners_follow_the_agent() {
+        let q = rooms();
+        let ext = (0.5, 1.0, 0.5);
+        let start: Point = (1.0, 0.0, 1.0).into();
+        let end: Point = (23.0, 0.0, 9.0).into();
+        let full = q
+            .find_path_with_options(start, end, ext, &PathOptions::default())
+            .unwrap();
+        assert!(full.len() > 2);
+
+        let (mut lazy, mut corners) = q
+            .find_lazy_path(start, end, ext, 1, &PathOptions::default())
+            .unwrap();
+        assert_eq!(corners.len(), 1);
+        assert!(!corners[0].flags.is_end());
+
+        // Jump from corner to corner until the end
+        let mut steps = 1;
+        while !corners.last().unwrap().flags.is_end() {
+            let before = lazy.corridor().len();
+            corners = lazy.next_corners(&q, corners[0].pos).unwrap();
+            assert!(lazy.corridor().len() <= before);
+            steps += 1;
+            assert!(steps <= full.len());
+        }
+        assert_eq!(
+            lazy.corridor().last(),
+            Some(&q.find_poly(end, ext).unwrap().1)
+        );
+    }
+
+    #[test]
+    fn test_merge_corridor_start_moved() {
+        // Moved forward along the corridor
+        let mut corridor = vec![1, 2, 3, 4];
+        merge_corridor_start_moved(&mut corridor, &[1, 2, 3]);
+        assert_eq!(corridor, vec![3, 4]);
+
+        // Stepped off the corridor, the way back goes through the polys visited
+        let mut corridor = vec![1, 2, 3];
+        merge_corridor_start_moved(&mut corridor, &[1, 7, 8]);
+        assert_eq!(corridor, vec![8, 7, 1, 2, 3]);
+
+        // Came back onto the corridor further on
+        let mut corridor = vec![1, 2, 3, 4];
+        merge_corridor_start_moved(&mut corridor, &[1, 7, 3]);
+        assert_eq!(corridor, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_moved_off_the_corridor() {
+        let q = rooms();
+        let ext = (0.5, 1.0, 0.5);
+        let end = (8.0, 0.0, 5.0).into();
+        let (mut lazy, _) = q
+            .find_lazy_path((1.0, 0.0, 5.0).into(), end, ext, 2, &PathOptions::default())
+            .unwrap();
+        let before = lazy.corridor().len();
+
+        // Through the passage into the other room, the corridor leads back
+        let corners = lazy.next_corners(&q, (20.0, 0.0, 5.0).into()).unwrap();
+        assert!(!corners.is_empty());
+        assert!(lazy.corridor().len() > before);
+        assert_eq!(
+            lazy.corridor().last(),
+            Some(&q.find_poly(end, ext).unwrap().1)
+        );
+        assert!(lazy.pos().x() > 14.0, "{:?}", lazy.pos());
+
+        // Stops at the wall instead of leaving the navmesh
+        lazy.next_corners(&q, (20.0, 0.0, 30.0).into()).unwrap();
+        assert!(lazy.pos().z() <= 10.0, "{:?}", lazy.pos());
+    }
+}
@@ -1,16 +1,164 @@
+use std::any::Any;
+use std::cell::RefCell;
 use std::ffi::CStr;
 use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
 use std::collections::HashMap;
+use std::hash::Hash;
+use rand::Rng;
 use thiserror::Error as ThisError;
 
+mod artifacts;
+mod bake;
+#[cfg(feature = "bake-cli")]
+mod bake_settings;
+mod boundary;
+mod build_info;
+mod builder;
+mod cache;
+mod chunked;
+mod clearance;
+mod clock;
+#[cfg(feature = "zstd")]
+mod compress;
+mod corridor;
+mod crowd;
+mod crowd_debug;
+mod crowd_events;
+mod debug_draw;
+mod debug_json;
+#[cfg(feature = "debug-server")]
+mod debug_server;
+mod diff;
+mod dirty;
+mod divergence;
+mod editor;
+mod export;
+mod faults;
+mod filter;
+mod fixed;
+#[cfg(feature = "gltf")]
+mod gltf;
+mod graph;
+mod height;
+mod heightfield;
+mod instances;
+mod jobs;
+mod lazy_path;
+mod merge;
 mod nav_obj;
-
+#[cfg(feature = "obj")]
+mod obj;
+mod off_mesh;
+mod pack;
+mod presets;
+mod proximity;
+mod prune;
+mod recast;
+mod rebuild;
+mod road;
+mod serialize;
+mod snapshot;
+mod soft_area;
+mod spawn;
+mod stitch;
+mod streaming;
+mod stuck;
+mod terrain;
+#[cfg(test)]
+mod test_util;
+mod tile_cache;
+mod tiled;
+mod transform;
+mod traversal;
+mod unity;
+mod visits;
+mod zones;
+
+pub use artifacts::{
+    BuildArtifacts, CompactHeightfieldView, CompactSpan, Contour, HeightfieldView, MeshPoly,
+    PolyMeshView, MESH_NULL_IDX,
+};
+pub use bake::bake_hash;
+#[cfg(feature = "bake-cli")]
+pub use bake_settings::BakeSettings;
+pub use boundary::{LocalBoundary, WallSegment, MAX_BOUNDARY_SEGMENTS};
+pub use build_info::{build_info, BuildInfo};
+pub use builder::NavMeshDataBuilder;
+pub use cache::{bake_key, BakedNavMesh, NavMeshCache};
+pub use clearance::ClearanceMap;
+pub use clock::{FixedTimestep, SimClock, SimSteps};
+#[cfg(feature = "zstd")]
+pub use compress::{compress_serialized, decompress_serialized, DEFAULT_COMPRESSION_LEVEL};
+pub use corridor::Corridor;
+pub use crowd::{
+    AgentId, Crowd, CrowdAgent, CrowdAgentParams, CrowdInterpolation, CrowdUpdateFlags,
+    InterpolatedAgent, MoveTarget, ObstacleAvoidanceParams, MAX_AVOIDANCE_SLOTS,
+};
+pub use crowd_debug::{AgentDebug, AvoidanceSample};
+pub use crowd_events::{CrowdEvent, CrowdEvents};
+pub use debug_draw::{area_color, DebugEdgeKind, DebugLine, DebugTriangle, NavMeshDebugDraw};
+#[cfg(feature = "debug-server")]
+pub use debug_server::{DebugServer, DEBUG_SERVER_QUEUED_LINES, DEBUG_SERVER_RECENT_PATHS};
+pub use diff::NavMeshDiff;
+pub use dirty::DirtyTiles;
+pub use divergence::{lateral_deviation, max_lateral_deviation, DivergenceMonitor};
+pub use editor::{Brush, BrushMode, NavMeshEditor};
+pub use faults::{Fault, QueryKind};
+pub use filter::{PolyFlags, QueryFilter};
+pub use fixed::{
+    FixedPath, FixedQuery, FIXED_MAX_PATH_POINTS, FIXED_MAX_PATH_POLYS, FIXED_MAX_POLYS_AROUND,
+};
+pub use graph::{PolyEdge, PolyNode};
+pub use height::HeightMode;
+pub use heightfield::{Heightfield, Span, MAX_SPAN_HEIGHT};
+pub use instances::{MeshInstance, MeshTransform};
+pub use jobs::{FlowField, FlowFieldJob, IslandJob, Islands, Job, JobStatus, SamplingJob};
+pub use lazy_path::LazyPath;
+pub use merge::{MergeReport, TileConflict};
 pub use nav_obj::NavObjFile;
+#[cfg(feature = "obj")]
+pub use obj::ObjOptions;
+pub use off_mesh::{
+    assign_stable_ids, path_segments, OffMeshConnection, OffMeshPhase, OffMeshTraversal,
+    PathSegment,
+};
+pub use pack::{Migration, NavMeshPack, NavMeshVariants, Stranded};
+pub use presets::{FilterPresets, FilterPresetsBuilder};
+pub use proximity::ProximityGrid;
+pub use prune::PruneReport;
+pub use rebuild::{BackgroundRebuild, RebuiltTiles};
+pub use recast::{
+    BuildConfig, ConvexVolume, InputGeometry, Partition, WaterVolume, MAX_CONVEX_VOLUME_VERTS,
+    NOT_WALKABLE,
+};
+pub use road::RoadSpline;
+pub use serialize::{serialized_build_params, SavedBuildParams};
+pub use snapshot::{snapshot_buffer, AgentState, Snapshot, SnapshotReader, SnapshotWriter};
+pub use soft_area::{SoftArea, SoftAreaId, MAX_SOFT_AREAS, SOFT_AREA_FIRST};
+pub use spawn::{Closest, RandomNearby, RayHit, SnapStrategy, WallHit, WithClearance};
+pub use stitch::{BorderEdge, SeamLinks, TileHeader};
+pub use streaming::{DirTileProvider, StreamReport, TileProvider, TileStreamer};
+pub use stuck::{StuckDetector, StuckEvent};
+pub use terrain::TerrainHeightfield;
+pub use tile_cache::{NoCompression, ObstacleId, TileCache, TileCacheConfig, TileCompressor};
+pub use tiled::{NavMeshParams, TileData, TileGrid, MAX_TILES};
+pub use transform::{NavMeshTransform, UpAxis, WorldQuery};
+pub use traversal::{validate_agent_traversal, AgentProfile, TraversalIssue};
+pub use unity::{UnityTriangulation, UNITY_AREA_COUNT};
+pub use visits::VisitStats;
+pub use zones::{Breadcrumb, ZoneMap};
 
 #[derive(Debug)]
 pub struct RecastQuery {
     q: ptr::NonNull<c_void>,
+    soft_areas: soft_area::SoftAreas,
+    filter: QueryFilter,
+    visits: RefCell<Option<VisitStats>>,
+    faults: faults::Faults,
+    /// The tiles of `from_serialized_in_place`, freed after the navmesh
+    in_place: Option<serialize::InPlaceData>,
 }
 
 impl Drop for RecastQuery {
@@ -30,20 +178,68 @@ pub enum Error {
     #[error("error finding path: `{0}`")]
     FindPathError(String),
 
+    #[error("error building navmesh: `{0}`")]
+    BuildError(String),
+
+    #[error("error updating poly: `{0}`")]
+    PolyError(String),
+
+    #[error("error with soft area: `{0}`")]
+    SoftAreaError(String),
+
+    #[error("error with navmesh pack: `{0}`")]
+    PackError(String),
+
+    #[error("error with tile cache: `{0}`")]
+    TileCacheError(String),
+
+    #[error("error with query filter: `{0}`")]
+    FilterError(String),
+
+    #[error("error with crowd: `{0}`")]
+    CrowdError(String),
+
     #[error("partial result")]
-    PartialResult
+    PartialResult,
+
+    #[error("error with fixed query: `{0}`")]
+    FixedQueryError(&'static str),
+
+    #[error("error with proximity grid: `{0}`")]
+    ProximityGridError(String),
+
+    #[error("error with serialized navmesh: `{0}`")]
+    SerializeError(String),
+
+    #[error("error reading obj: `{0}`")]
+    ObjError(String),
+
+    #[error("error reading gltf: `{0}`")]
+    GltfError(String),
+
+    #[error("error reading Unity triangulation: `{0}`")]
+    UnityError(String),
+
+    #[error("error reading bake settings: `{0}`")]
+    BakeSettingsError(String),
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
 
-/// A Navgation Mesh Data
+/// Most vertices of a `NavMeshData` built as a single mesh, see `NavMeshData::indices`
+pub const MAX_MESH_VERTS: usize = 0xfffe;
+
+/// A Navgation Mesh Data, see `NavMeshData::builder` for a validated construction
 #[derive(Debug, Default, Clone)]
 pub struct NavMeshData {
     /// Vertices in world unit, length = 3 * Number of Vertices
     pub vertices: Vec<f32>,
-    /// Indices,  length = 3 * Number of Triangles
-    pub indices: Vec<u16>,
+    /// Indices,  length = 3 * Number of Triangles.
+    /// Meshes with more than `MAX_MESH_VERTS` vertices are split into tiles
+    pub indices: Vec<u32>,
+    /// Area id of the poly of each triangle, or empty for area 0
+    pub triangle_areas: Vec<u8>,
     /// Walkable height in nav mesh in World Unit
     pub walkable_height: f32,
     /// Walkable Radius in nav mesh in World Unit
@@ -55,6 +251,10 @@ pub struct NavMeshData {
     pub cell_size: f32,
     /// Cell height in world unit
     pub cell_height: f32,
+    /// Links between points not connected by the mesh
+    pub off_mesh_connections: Vec<OffMeshConnection>,
+    /// Convention of the vertices and of the off-mesh connections
+    pub up_axis: UpAxis,
 }
 
 fn compute_bb(vertices: &[f32]) -> ([f32; 3], [f32; 3]) {
@@ -75,13 +275,44 @@ fn compute_bb(vertices: &[f32]) -> ([f32; 3], [f32; 3]) {
     (bmin, bmax)
 }
 
+/// Call a C function filling a poly buffer with larger buffers until all polys fit,
+/// the function reports the needed size when failing with BUFFER_TOO_SMALL
+fn read_polys<F>(mut f: F) -> Result<Vec<PolyRef>>
+where
+    F: FnMut(*mut u32, i32, *mut i32, *mut sys::RecastNavError) -> i32,
+{
+    let mut polys = vec![0; 256];
+
+    loop {
+        let mut count = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = f(
+            polys.as_mut_ptr(),
+            polys.len() as i32,
+            &mut count as *mut _,
+            &mut err as *mut _,
+        );
+
+        let count = count as usize;
+        if res != 0 {
+            polys.truncate(count);
+            return Ok(polys);
+        }
+        if count <= polys.len() {
+            return Err(Error::PolyError(err.msg().to_string()));
+        }
+        polys.resize(count, 0);
+    }
+}
+
 #[inline]
-fn world_unit_to_cell_unit(f: f32, bmin: f32, cs: f32) -> u16 {
+fn world_unit_to_cell_unit(f: f32, bmin: f32, cs: f32) -> u32 {
     let f = ((f - bmin) / cs).max(0.0);
-    f.round() as u16
+    f.round() as u32
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Point([f32; 3]);
 
 impl Point {
@@ -106,9 +337,118 @@ impl From<(f32, f32, f32)> for Point {
     }
 }
 
+/// Reference to a polygon in the navmesh
+pub type PolyRef = u32;
+
+/// Extra straight path vertices added where the path crosses polygon edges
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathCrossings {
+    /// Only the corners of the path
+    None,
+    /// A vertex at every area change (e.g. entering water)
+    Area,
+    /// A vertex at every polygon edge
+    All,
+}
+
+impl PathCrossings {
+    fn as_sys(self) -> i32 {
+        match self {
+            PathCrossings::None => 0,
+            PathCrossings::Area => sys::DT_STRAIGHTPATH_AREA_CROSSINGS,
+            PathCrossings::All => sys::DT_STRAIGHTPATH_ALL_CROSSINGS,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathOptions {
+    pub crossings: PathCrossings,
+    /// Let the search shortcut the corridor with raycasts, straighter paths on open terrain
+    pub raycast_shortcut: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> PathOptions {
+        PathOptions {
+            crossings: PathCrossings::Area,
+            raycast_shortcut: false,
+        }
+    }
+}
+
+/// Flags of a straight path point
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct StraightPathFlags(u8);
+
+impl StraightPathFlags {
+    /// The point is the start of the path
+    pub fn is_start(self) -> bool {
+        self.0 & sys::DT_STRAIGHTPATH_START != 0
+    }
+    /// The point is the end of the path
+    pub fn is_end(self) -> bool {
+        self.0 & sys::DT_STRAIGHTPATH_END != 0
+    }
+    /// The point is the start of an off-mesh connection
+    pub fn is_off_mesh_connection(self) -> bool {
+        self.0 & sys::DT_STRAIGHTPATH_OFFMESH_CONNECTION != 0
+    }
+}
+
+/// A point of a straight path
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathPoint {
+    pub pos: Point,
+    pub flags: StraightPathFlags,
+    /// The poly entered at this point
+    pub poly: PolyRef,
+    /// User id of the off-mesh connection starting at this point
+    pub off_mesh_id: Option<u32>,
+}
+
 pub fn remove_dup(verts: &[u16], indices: &[u16]) -> (Vec<u16>, Vec<u16>) {
-    let mut verts_map : HashMap<(u16,u16,u16), u16> = HashMap::new();
-    let mut idx_map : HashMap<u16, u16> = HashMap::new();
+    let indices: Vec<u32> = indices.iter().map(|&i| i as u32).collect();
+    let (rv, ri, _) = weld_vertices(verts, &indices);
+    (rv, ri.into_iter().map(|i| i as u16).collect())
+}
+
+/// What the preprocessing of `RecastQuery::new_from_mesh` changed in the input mesh
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeldReport {
+    /// (kept, merged) input vertex indices which ended in the same cell and were welded
+    pub merged_vertices: Vec<(u32, u32)>,
+    /// Input triangles (index of the triangle, not of the vertex) which have no area after welding
+    pub degenerate_triangles: Vec<usize>,
+    /// The cell height used instead of the one of the mesh, whose height exceeded 65535 cells
+    pub adjusted_cell_height: Option<f32>,
+    /// The mesh exceeded the 16-bit vertices of Detour and was split into tiles
+    pub tiled: bool,
+}
+
+impl WeldReport {
+    /// Nothing of the mesh was changed, a tiled mesh keeps its shape
+    pub fn is_empty(&self) -> bool {
+        self.merged_vertices.is_empty()
+            && self.degenerate_triangles.is_empty()
+            && self.adjusted_cell_height.is_none()
+    }
+}
+
+/// The cell height fitting the height of the mesh in 16-bit cells, when `cell_height` does not
+fn fit_cell_height(bmin: f32, bmax: f32, cell_height: f32) -> Option<f32> {
+    let extent = bmax - bmin;
+    if ((extent / cell_height).round()) <= 0xffff as f32 {
+        return None;
+    }
+    // One cell of headroom against the rounding of the vertices
+    Some(extent / (0xffff - 1) as f32)
+}
+
+fn weld_vertices<T: Copy + Eq + Hash>(verts: &[T], indices: &[u32]) -> (Vec<T>, Vec<u32>, WeldReport) {
+    let mut verts_map : HashMap<(T,T,T), (u32, u32)> = HashMap::new();
+    let mut idx_map : HashMap<u32, u32> = HashMap::new();
+    let mut report = WeldReport::default();
 
     let n_verts = verts.len() / 3;
     let mut rv = Vec::new();
@@ -116,54 +456,84 @@ pub fn remove_dup(verts: &[u16], indices: &[u16]) -> (Vec<u16>, Vec<u16>) {
 
     for i in 0..n_verts {
         let p = (verts[i*3 + 0], verts[i*3 + 1], verts[i*3 + 2]);
-        let i = i as u16;        
-        let new_i = verts_map.entry(p).or_insert_with(||{			
+        let i = i as u32;        
+        let &mut (new_i, first) = verts_map.entry(p).or_insert_with(||{			
             let idx = rv.len() / 3;
 			
 			rv.push(p.0);
             rv.push(p.1);
             rv.push(p.2);
 			
-			idx as u16
+			(idx as u32, i)
 		});
 
-        idx_map.insert(i, *new_i);
+        if first != i {
+            report.merged_vertices.push((first, i));
+        }
+
+        idx_map.insert(i, new_i);
     }
 
     for idx in indices {
         ri.push(*idx_map.get(idx).unwrap());
     }
+
+    for (t, tri) in ri.chunks(3).enumerate() {
+        if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] {
+            report.degenerate_triangles.push(t);
+        }
+    }
             
-    (rv,ri)
+    (rv, ri, report)
 }
 
 impl RecastQuery {
     /// Create a query from NavMesh
     pub fn new_from_mesh(data: NavMeshData) -> Result<RecastQuery> {
-        assert!(data.vertices.len() % 3 == 0);
-        assert!(data.indices.len() % 3 == 0);
+        RecastQuery::new_from_mesh_with_report(data).map(|(q, _)| q)
+    }
+
+    /// Create a query from NavMesh and report the vertices and triangles affected by welding,
+    /// so the source mesh can be fixed instead of relying on the silent cleanup
+    pub fn new_from_mesh_with_report(data: NavMeshData) -> Result<(RecastQuery, WeldReport)> {
+        data.validate()?;
+        let mut data = data.into_y_up();
         
         let (bmin, bmax) = compute_bb(&data.vertices);
 
+        // Heights beyond 16 bits would wrap, coarser heights keep the mesh intact
+        let adjusted_cell_height = fit_cell_height(bmin[1], bmax[1], data.cell_height);
+        if let Some(ch) = adjusted_cell_height {
+            data.cell_height = ch;
+        }
+
         let mut cu_verts = Vec::new();
 
-        // World Unit to Cell Unit
+        // World Unit to Cell Unit, Detour decodes the height with the cell height
         for i in (0..data.vertices.len()).step_by(3) {
             for j in 0..3 {
-                cu_verts.push(world_unit_to_cell_unit(
-                    data.vertices[i + j],
-                    bmin[j],
-                    data.cell_size,
-                ));
+                let cs = if j == 1 { data.cell_height } else { data.cell_size };
+                cu_verts.push(world_unit_to_cell_unit(data.vertices[i + j], bmin[j], cs));
             }
         }
         assert!(data.vertices.len() == cu_verts.len());
         
-        let (cu_verts, indices) = remove_dup(&cu_verts, &data.indices);
+        let (cu_verts, indices, mut report) = weld_vertices(&cu_verts, &data.indices);
+        report.adjusted_cell_height = adjusted_cell_height;
+
+        // Detour polys index their vertices with 16 bits, larger meshes become tiles
+        if cu_verts.len() / 3 > MAX_MESH_VERTS || cu_verts.iter().any(|&c| c > 0xffff) {
+            let q = chunked::build_chunked(&data, bmin, bmax, &cu_verts, &indices)?;
+            report.tiled = true;
+            return Ok((q, report));
+        }
+        let cu_verts: Vec<u16> = cu_verts.into_iter().map(|c| c as u16).collect();
+        let indices: Vec<u16> = indices.into_iter().map(|i| i as u16).collect();
 
         let vert_count = (cu_verts.len() / 3) as u32;
         let triangles_count = (data.indices.len() / 3) as u32;     
 
+        let off_mesh = off_mesh::sys_connections(&data.off_mesh_connections);
         let sys_data = sys::RecastNavMeshData {
             verts: cu_verts.as_ptr(),
             vert_count,
@@ -176,6 +546,13 @@ impl RecastQuery {
             walkable_climb: data.walkable_climb,
             cell_size: data.cell_size,
             cell_height: data.cell_height,
+            off_mesh_connections: off_mesh.as_ptr(),
+            off_mesh_connection_count: off_mesh.len() as u32,
+            areas: if data.triangle_areas.is_empty() {
+                ptr::null()
+            } else {
+                data.triangle_areas.as_ptr()
+            },
         };
 
         let mut err = sys::RecastNavError::zeros();
@@ -187,17 +564,156 @@ impl RecastQuery {
         };
 
         let q = q.ok_or(Error::CreateQueryError(err.msg().into_owned()))?;
-        Ok(RecastQuery { q })
+        Ok((RecastQuery::from_ptr(q), report))
+    }
+
+    fn from_ptr(q: ptr::NonNull<c_void>) -> RecastQuery {
+        RecastQuery {
+            q,
+            soft_areas: soft_area::SoftAreas::new(),
+            filter: QueryFilter::default(),
+            visits: RefCell::new(None),
+            faults: faults::Faults::default(),
+            in_place: None,
+        }
     }
 
     pub fn find_path(&self, start: Point, end: Point, r: (f32, f32, f32)) -> Result<Vec<Point>> {
+        let partial = self.faults.check_path(QueryKind::FindPath)?;
         let (start_p, start_poly) = self.find_poly(start, r)?;
         let (end_p, end_poly) = self.find_poly(end, r)?;
 
         if start_poly == end_poly {
+            self.record_visits(&[start_poly]);
             return Ok(vec![end_p]);
         }        
 
+        let path = self.plan_path(
+            (start_p, start_poly),
+            (end_p, end_poly),
+            &PathOptions::default(),
+        )?;
+
+        let mut path: Vec<Point> = match path.len() {
+            0 => return Err(Error::FindPathError("No Path".to_string())),
+            // Same Poly, so just return the next point
+            1 => vec![end_p],
+            _ => path.into_iter().map(|p| p.pos).collect(),
+        };
+        if partial {
+            path.truncate(faults::partial_len(path.len()));
+        }
+        Ok(path)
+    }
+
+    /// Find a path and return every straight path point with its flags and entered poly
+    pub fn find_path_with_options(
+        &self,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        options: &PathOptions,
+    ) -> Result<Vec<PathPoint>> {
+        let partial = self.faults.check_path(QueryKind::FindPath)?;
+        let start = self.find_poly(start, r)?;
+        let end = self.find_poly(end, r)?;
+
+        let mut path = self.plan_path(start, end, options)?;
+        if path.is_empty() {
+            return Err(Error::FindPathError("No Path".to_string()));
+        }
+
+        if partial {
+            path.truncate(faults::partial_len(path.len()));
+        }
+        Ok(path)
+    }
+
+    /// The straight path along a corridor found by another planner, e.g. a custom search over the polys.
+    ///
+    /// The corridor goes from the poly of `start` to the poly of `end`, consecutive polys must be linked.
+    pub fn straight_path(
+        &self,
+        corridor: &[PolyRef],
+        start: Point,
+        end: Point,
+    ) -> Result<Vec<Point>> {
+        let path = self.straight_path_with_options(corridor, start, end, PathCrossings::Area)?;
+        Ok(path.into_iter().map(|p| p.pos).collect())
+    }
+
+    /// `straight_path` returning every straight path point with its flags and entered poly
+    pub fn straight_path_with_options(
+        &self,
+        corridor: &[PolyRef],
+        start: Point,
+        end: Point,
+        crossings: PathCrossings,
+    ) -> Result<Vec<PathPoint>> {
+        self.limited_straight_path(corridor, start, end, crossings, 0)
+    }
+
+    /// The straight path stopping after `max_points` points, 0 for the whole path
+    pub(crate) fn limited_straight_path(
+        &self,
+        corridor: &[PolyRef],
+        start: Point,
+        end: Point,
+        crossings: PathCrossings,
+        max_points: u32,
+    ) -> Result<Vec<PathPoint>> {
+        let partial = self.faults.check_path(QueryKind::StraightPath)?;
+        let mut result = sys::RecastPathResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastStraightPathInput {
+            corridor: corridor.as_ptr(),
+            corridor_count: corridor.len() as u32,
+            start_pos: start.0,
+            end_pos: end.0,
+            straight_path_options: crossings.as_sys(),
+            max_points,
+        };
+
+        let res = unsafe {
+            sys::recastc_find_straight_path(
+                self.q.as_ptr(),
+                &input as *const _,
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::FindPathError(err.msg().to_string()));
+        }
+
+        self.record_visits(corridor);
+        let mut path = self.read_straight_path(&result);
+        if partial {
+            path.truncate(faults::partial_len(path.len()));
+        }
+        Ok(path)
+    }
+
+    fn plan_path(
+        &self,
+        start: (Point, PolyRef),
+        end: (Point, PolyRef),
+        options: &PathOptions,
+    ) -> Result<Vec<PathPoint>> {
+        Ok(self.plan_corridor(start, end, options, 0)?.1)
+    }
+
+    /// The corridor of a path and its straight path stopping after `max_points` points,
+    /// 0 for the whole path
+    pub(crate) fn plan_corridor(
+        &self,
+        (start_p, start_poly): (Point, PolyRef),
+        (end_p, end_poly): (Point, PolyRef),
+        options: &PathOptions,
+        max_points: u32,
+    ) -> Result<(Vec<PolyRef>, Vec<PathPoint>)> {
         let mut result = sys::RecastPathResult::default();
         let mut err = sys::RecastNavError::zeros();
 
@@ -206,6 +722,13 @@ impl RecastQuery {
             start_pos: start_p.0,
             end_poly,
             end_pos: end_p.0,
+            straight_path_options: options.crossings.as_sys(),
+            find_path_options: if options.raycast_shortcut {
+                sys::DT_FINDPATH_ANY_ANGLE
+            } else {
+                0
+            },
+            max_points,
         };
 
         let res = unsafe {
@@ -226,24 +749,32 @@ impl RecastQuery {
             return Err(Error::FindPathError(error));
         }
 
-        let path = &result.path2[0..(result.path2_count * 3) as usize];
+        let corridor = &result.path[0..result.path_count as usize];
+        self.record_visits(corridor);
+        Ok((corridor.to_vec(), self.read_straight_path(&result)))
+    }
 
-        match path.len() {
-            0 => Err(Error::FindPathError("No Path".to_string())),
-            // Same Poly, so just return the next point
-            1 => Ok(vec![end_p]),
-            _ => {
-                let mut res = vec![];
-                for (i, _) in path.iter().enumerate().step_by(3) {                    
-                    res.push((path[i], path[i + 1], path[i + 2]).into());
+    fn read_straight_path(&self, result: &sys::RecastPathResult) -> Vec<PathPoint> {
+        let n = result.path2_count as usize;
+        let path = &result.path2[0..n * 3];
+
+        (0..n)
+            .map(|i| PathPoint {
+                pos: (path[i * 3], path[i * 3 + 1], path[i * 3 + 2]).into(),
+                flags: StraightPathFlags(result.path2_flags[i]),
+                poly: result.path2_polys[i],
+                off_mesh_id: None,
+            })
+            .map(|mut p| {
+                if p.flags.is_off_mesh_connection() {
+                    p.off_mesh_id = self.off_mesh_connection(p.poly).ok().map(|c| c.user_id);
                 }
-
-                Ok(res)
-            }
-        }
+                p
+            })
+            .collect()
     }
 
-    fn find_closest(&self, pos: Point, target_poly: u32) -> Result<Point> {
+    fn find_closest(&self, pos: Point, target_poly: PolyRef) -> Result<Point> {
         let input = sys::RecastClosestPointInput {
             pos: pos.0,
             poly: target_poly,
@@ -267,7 +798,156 @@ impl RecastQuery {
         }
     }
 
-    pub fn find_poly(&self, pos: Point, r: (f32, f32, f32)) -> Result<(Point, u32)> {
+    fn polys_around_circle(
+        &self,
+        center: Point,
+        radius: f32,
+        r: (f32, f32, f32),
+    ) -> Result<Vec<PolyRef>> {
+        let mut result = sys::RecastPolysResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastPolysAroundCircleInput {
+            center: center.0,
+            radius,
+            half_extents: [r.0, r.1, r.2],
+        };
+
+        let res = unsafe {
+            sys::recastc_find_polys_around_circle(
+                self.q.as_ptr(),
+                &input as *const _,
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::FindPointError(err.msg().to_string()))
+        } else {
+            Ok(result.polys[0..result.poly_count as usize].to_vec())
+        }
+    }
+
+    /// All polys passing the query filter
+    pub fn polys(&self) -> Result<Vec<PolyRef>> {
+        read_polys(|polys, max_polys, count, err| unsafe {
+            sys::recastc_get_polys(self.q.as_ptr(), polys, max_polys, count, err)
+        })
+    }
+
+    /// The polys linked to `poly` passing the query filter, off-mesh connections included
+    pub fn poly_neighbours(&self, poly: PolyRef) -> Result<Vec<PolyRef>> {
+        let mut result = sys::RecastPolysResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_neighbours(
+                self.q.as_ptr(),
+                poly,
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(result.polys[0..result.poly_count as usize].to_vec())
+        }
+    }
+
+    /// The average of the poly vertices
+    pub fn poly_center(&self, poly: PolyRef) -> Result<Point> {
+        let mut center = [0.0; 3];
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_center(
+                self.q.as_ptr(),
+                poly,
+                center.as_mut_ptr(),
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(Point(center))
+        }
+    }
+
+    /// Height of the navmesh surface of `poly` at `pos`, following the detail mesh when there is one
+    pub fn poly_height(&self, poly: PolyRef, pos: Point) -> Result<f32> {
+        let mut height = 0.0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_height(
+                self.q.as_ptr(),
+                poly,
+                pos.0.as_ptr(),
+                &mut height,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::FindPointError(err.msg().to_string()))
+        } else {
+            Ok(height)
+        }
+    }
+
+    /// Area id of a poly
+    pub fn poly_area(&self, poly: PolyRef) -> Result<u8> {
+        let mut area = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_area(self.q.as_ptr(), poly, &mut area, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(area)
+        }
+    }
+
+    fn set_poly_area(&mut self, poly: PolyRef, area: u8) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_set_poly_area(self.q.as_ptr(), poly, area, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Traversal cost multiplier of an area id, areas from `SOFT_AREA_FIRST` are managed by soft areas
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_set_area_cost(self.q.as_ptr(), area, cost, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            self.filter.area_costs[area as usize] = cost;
+            Ok(())
+        }
+    }
+
+    pub fn find_poly(&self, pos: Point, r: (f32, f32, f32)) -> Result<(Point, PolyRef)> {
+        self.faults.check(QueryKind::FindPoly)?;
         let mut result = sys::RecastNearestPolyResult::default();
         let mut err = sys::RecastNavError::zeros();
 
@@ -293,6 +973,107 @@ impl RecastQuery {
     }
 }
 
+/// The rng of a random point query, a panic of the rng must not unwind through C.
+/// It is resumed once the query returns
+struct RandomSource<'a, R> {
+    rng: &'a mut R,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<'a, R: Rng> RandomSource<'a, R> {
+    fn new(rng: &'a mut R) -> RandomSource<'a, R> {
+        RandomSource { rng, panic: None }
+    }
+
+    fn as_user(&mut self) -> *mut c_void {
+        self as *mut RandomSource<R> as *mut c_void
+    }
+
+    fn resume_panic(self) {
+        if let Some(payload) = self.panic {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+extern "C" fn frand<R: Rng>(user: *mut c_void) -> f32 {
+    let source = unsafe { &mut *(user as *mut RandomSource<R>) };
+    if source.panic.is_some() {
+        return 0.0;
+    }
+    let rng = &mut *source.rng;
+    match panic::catch_unwind(AssertUnwindSafe(|| rng.gen::<f32>())) {
+        Ok(f) => f,
+        Err(payload) => {
+            source.panic = Some(payload);
+            0.0
+        }
+    }
+}
+
+impl RecastQuery {
+    /// Pick a random point on the navmesh, using `rng` as the only source of randomness
+    pub fn find_random_point<R: Rng>(&self, rng: &mut R) -> Result<(Point, PolyRef)> {
+        self.faults.check(QueryKind::RandomPoint)?;
+        let mut result = sys::RecastNearestPolyResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let mut source = RandomSource::new(rng);
+        let res = unsafe {
+            sys::recastc_find_random_point(
+                self.q.as_ptr(),
+                frand::<R>,
+                source.as_user(),
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+        source.resume_panic();
+
+        match res {
+            0 => Err(Error::FindPointError(err.msg().to_string())),
+            _ => Ok((Point(result.pos), result.poly)),
+        }
+    }
+
+    /// Pick a random point reachable from the poly nearest to `center` within `radius`
+    pub fn find_random_point_around_circle<R: Rng>(
+        &self,
+        center: Point,
+        radius: f32,
+        r: (f32, f32, f32),
+        rng: &mut R,
+    ) -> Result<(Point, PolyRef)> {
+        self.faults.check(QueryKind::RandomPoint)?;
+        let mut result = sys::RecastNearestPolyResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let input = sys::RecastPolysAroundCircleInput {
+            center: center.0,
+            radius,
+            half_extents: [r.0, r.1, r.2],
+        };
+
+        let mut source = RandomSource::new(rng);
+        let res = unsafe {
+            sys::recastc_find_random_point_around_circle(
+                self.q.as_ptr(),
+                &input as *const _,
+                frand::<R>,
+                source.as_user(),
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+        source.resume_panic();
+
+        match res {
+            0 => Err(Error::FindPointError(err.msg().to_string())),
+            _ => Ok((Point(result.pos), result.poly)),
+        }
+    }
+}
+
 pub fn version() -> String {
     let version = unsafe { sys::recastc_version() };
     assert_ne!(version, ptr::null());
@@ -303,26 +1084,9 @@ pub fn version() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::simple_mesh;
     use insta::*;
 
-    fn simple_mesh() -> NavMeshData {
-        let vertices = vec![
-            0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0,
-        ];
-
-        let indices = vec![0, 1, 2, 0, 2, 3];
-
-        NavMeshData {
-            vertices,
-            indices,
-            walkable_height: 0.2,
-            walkable_radius: 0.2,
-            walkable_climb: 0.2,
-            cell_size: 0.1,
-            cell_height: 0.1,
-        }
-    }
-
     #[test]
     fn smoke_test() {
         assert_eq!("0.0.1", version());
@@ -349,6 +1113,54 @@ mod tests {
         assert_eq!(bmax[2], 2.0);
     }
 
+    #[test]
+    fn test_weld_report() {
+        // vertex 4 is in the same cell as vertex 2
+        let verts = &[0, 0, 0, 10, 0, 0, 10, 0, 10, 0, 0, 10, 10, 0, 10];
+        let indices = &[0, 1, 2, 0, 2, 3, 2, 4, 1];
+
+        let (v, i, report) = weld_vertices(verts, indices);
+
+        assert_eq!(v.len(), 12);
+        assert_eq!(i, vec![0, 1, 2, 0, 2, 3, 2, 2, 1]);
+        assert_eq!(report.merged_vertices, vec![(2, 4)]);
+        assert_eq!(report.degenerate_triangles, vec![2]);
+    }
+
+    #[test]
+    fn test_heights_in_cell_height() {
+        let mut mesh = simple_mesh();
+        mesh.cell_size = 0.3;
+        // A platform 5 units above the floor
+        mesh.vertices.extend_from_slice(&[
+            20.0, 5.0, 0.0, 30.0, 5.0, 0.0, 30.0, 5.0, 10.0, 20.0, 5.0, 10.0,
+        ]);
+        mesh.indices.extend_from_slice(&[4, 5, 6, 4, 6, 7]);
+
+        let (q, report) = RecastQuery::new_from_mesh_with_report(mesh).unwrap();
+        assert!(report.is_empty() && !report.tiled);
+        let (p, _) = q.find_poly((25.0, 5.0, 5.0).into(), (0.2, 0.5, 0.2)).unwrap();
+        assert!((p.y() - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_tall_mesh_adjusts_cell_height() {
+        // A ramp 100 units high, 100000 cells of 0.001
+        let mut mesh = simple_mesh();
+        mesh.vertices = vec![
+            0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 100.0, 10.0, 0.0, 100.0, 10.0,
+        ];
+        mesh.cell_height = 0.001;
+
+        let (q, report) = RecastQuery::new_from_mesh_with_report(mesh).unwrap();
+        let ch = report.adjusted_cell_height.unwrap();
+        assert!(ch > 0.001 && 100.0 / ch <= 65535.0);
+        assert!(!report.is_empty());
+
+        let (p, _) = q.find_poly((5.0, 95.0, 9.5).into(), (0.2, 1.0, 0.2)).unwrap();
+        assert!((p.y() - 95.0).abs() < 0.1);
+    }
+
     #[test]
     fn test_simple_path() {
         assert_eq!("0.0.1", version());
@@ -376,4 +1188,131 @@ mod tests {
     )
 ]"###);
     }
+
+    #[test]
+    fn test_path_flags() {
+        let mesh = simple_mesh();
+
+        let q = RecastQuery::new_from_mesh(mesh).unwrap();
+        let p = q
+            .find_path_with_options(
+                (0.2, 0.1, 0.4).into(),
+                (0.8, 0.1, 0.5).into(),
+                (0.2, 0.2, 0.2),
+                &PathOptions {
+                    crossings: PathCrossings::All,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(p.first().unwrap().flags.is_start());
+        assert!(p.last().unwrap().flags.is_end());
+        assert!(p.iter().all(|p| !p.flags.is_off_mesh_connection()));
+    }
+
+    #[test]
+    fn test_raycast_shortcut() {
+        let q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+        let (start, end) = ((0.2, 0.1, 0.4).into(), (0.8, 0.1, 0.5).into());
+
+        let plain = q
+            .find_path_with_options(start, end, (0.2, 0.2, 0.2), &PathOptions::default())
+            .unwrap();
+        let shortcut = q
+            .find_path_with_options(
+                start,
+                end,
+                (0.2, 0.2, 0.2),
+                &PathOptions {
+                    raycast_shortcut: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(shortcut.last().unwrap().pos, plain.last().unwrap().pos);
+        assert!(shortcut.len() <= plain.len());
+        // Every segment of the shortcut path is a clear ray over the navmesh
+        for w in shortcut.windows(2) {
+            let hit = q.raycast(w[0].poly, w[0].pos, w[1].pos).unwrap();
+            assert_eq!(hit.t, None);
+            assert!((hit.pos.x() - w[1].pos.x()).abs() < 1e-4);
+            assert!((hit.pos.z() - w[1].pos.z()).abs() < 1e-4);
+        }
+
+        // Out of the square through its wall at x = 10
+        let from = (5.0, 0.0, 5.0).into();
+        let (_, poly) = q.find_poly(from, (0.2, 0.2, 0.2)).unwrap();
+        let hit = q.raycast(poly, from, (15.0, 0.0, 5.0).into()).unwrap();
+        assert!((hit.t.unwrap() - 0.5).abs() < 1e-3, "{:?}", hit);
+        assert!((hit.pos.x() - 10.0).abs() < 1e-2 && (hit.pos.z() - 5.0).abs() < 1e-2);
+        assert!((hit.normal.x() + 1.0).abs() < 1e-3 && hit.normal.z().abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_straight_path_along_corridor() {
+        let q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+        let (start, end) = ((1.0, 0.0, 2.0).into(), (2.0, 0.0, 1.0).into());
+        let (_, start_poly) = q.find_poly(start, (0.2, 0.2, 0.2)).unwrap();
+        let (_, end_poly) = q.find_poly(end, (0.2, 0.2, 0.2)).unwrap();
+        assert_ne!(start_poly, end_poly);
+
+        let corridor = [start_poly, end_poly];
+        let path = q.straight_path(&corridor, start, end).unwrap();
+        assert_eq!(path.last(), q.find_path(start, end, (0.2, 0.2, 0.2)).unwrap().last());
+
+        let path = q
+            .straight_path_with_options(&corridor, start, end, PathCrossings::All)
+            .unwrap();
+        assert_eq!(path.last().unwrap().poly, 0);
+        assert!(path.iter().any(|p| p.poly == end_poly));
+
+        assert!(q.straight_path(&[], start, end).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "rng bug")]
+    fn test_panicking_rng() {
+        struct Panicking;
+
+        impl rand::RngCore for Panicking {
+            fn next_u32(&mut self) -> u32 {
+                panic!("rng bug")
+            }
+            fn next_u64(&mut self) -> u64 {
+                panic!("rng bug")
+            }
+            fn fill_bytes(&mut self, _: &mut [u8]) {
+                panic!("rng bug")
+            }
+            fn try_fill_bytes(&mut self, _: &mut [u8]) -> std::result::Result<(), rand::Error> {
+                panic!("rng bug")
+            }
+        }
+
+        // Resumed in Rust once the query is back from C
+        let q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+        let _ = q.find_random_point(&mut Panicking);
+    }
+
+    #[test]
+    fn test_random_point_is_reproducible() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+
+        let a = q.find_random_point(&mut StdRng::seed_from_u64(42)).unwrap();
+        let b = q.find_random_point(&mut StdRng::seed_from_u64(42)).unwrap();
+        assert_eq!(a, b);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let (p, _) = q
+            .find_random_point_around_circle((0.5, 0.0, 0.5).into(), 0.2, (0.2, 0.2, 0.2), &mut rng)
+            .unwrap();
+        // Only the visited polys are limited by the circle, not the point itself
+        assert!(p.x() >= 0.0 && p.x() <= 10.0);
+        assert!(p.z() >= 0.0 && p.z() <= 10.0);
+    }
 }
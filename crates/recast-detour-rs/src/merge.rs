@@ -0,0 +1,367 @@
+//! Merging the tiles of navmeshes, e.g. add-on zones attached to the base navmesh at load time
+use crate::stitch::{check_same_agent, tile_header};
+use crate::{Error, NavMeshParams, RecastQuery, Result, TileData, TileHeader};
+use std::collections::HashSet;
+
+/// What `RecastQuery::merge` does with a tile present in both navmeshes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileConflict {
+    /// Fail before any tile is added
+    Fail,
+    /// Keep the tile of the navmesh merged into
+    KeepExisting,
+    /// Replace the tile with the one of the merged navmesh
+    Replace,
+}
+
+/// The tile coordinates touched by a merge, sorted, once for all the layers of a tile
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: Vec<(i32, i32)>,
+    pub replaced: Vec<(i32, i32)>,
+    /// Tiles kept with `TileConflict::KeepExisting`
+    pub skipped: Vec<(i32, i32)>,
+}
+
+/// Two navmeshes are on the same grid when their tiles at the same coordinates cover the same cell
+pub(crate) fn check_same_grid(params: &NavMeshParams, other: &NavMeshParams) -> Result<()> {
+    let eps = 1e-3 * params.tile_width.max(params.tile_height);
+    let same_grid = (params.tile_width - other.tile_width).abs() <= eps
+        && (params.tile_height - other.tile_height).abs() <= eps
+        && (params.origin[0] - other.origin[0]).abs() <= eps
+        && (params.origin[2] - other.origin[2]).abs() <= eps;
+    if !same_grid {
+        return Err(Error::BuildError(
+            "navmeshes are not tiled on the same grid".into(),
+        ));
+    }
+    Ok(())
+}
+
+impl RecastQuery {
+    /// The layout of the navmesh, a navmesh built as a single mesh is one tile covering its bounds
+    pub fn nav_mesh_params(&self) -> Result<NavMeshParams> {
+        let mut params = sys::RecastTiledMeshParams::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_nav_mesh_params(
+                self.q.as_ptr(),
+                &mut params as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::BuildError(err.msg().to_string()));
+        }
+
+        Ok(NavMeshParams {
+            origin: params.orig,
+            tile_width: params.tile_width,
+            tile_height: params.tile_height,
+            max_tiles: params.max_tiles as u32,
+            max_polys: params.max_polys as u32,
+        })
+    }
+
+    /// The tile coordinates of the tiles in the navmesh, in no particular order
+    pub fn tile_coords(&self) -> Result<Vec<(i32, i32)>> {
+        let mut coords = vec![0; 2 * 64];
+
+        loop {
+            let mut count = 0;
+            let mut err = sys::RecastNavError::zeros();
+
+            let res = unsafe {
+                sys::recastc_get_tiles(
+                    self.q.as_ptr(),
+                    coords.as_mut_ptr(),
+                    (coords.len() / 2) as i32,
+                    &mut count as *mut _,
+                    &mut err as *mut _,
+                )
+            };
+
+            let count = count as usize;
+            if res != 0 {
+                return Ok(coords[..count * 2]
+                    .chunks(2)
+                    .map(|c| (c[0], c[1]))
+                    .collect());
+            }
+            if count * 2 <= coords.len() {
+                return Err(Error::BuildError(err.msg().to_string()));
+            }
+            coords.resize(count * 2, 0);
+        }
+    }
+
+    /// Copy the tiles of `other` into this navmesh, all layers included, the links across the
+    /// seams are made as the tiles are added. Both navmeshes must be tiled on the same grid for
+    /// the same agent, e.g. the tiles of `other` built with the `TileGrid` of this navmesh.
+    ///
+    /// All or nothing: when a tile fails to be added, the navmesh gets its previous tiles back.
+    /// The tiles of both navmeshes are compared by tile coordinates, a replaced tile gets
+    /// exactly the layers of `other`
+    pub fn merge(&mut self, other: &RecastQuery, conflict: TileConflict) -> Result<MergeReport> {
+        let params = self.nav_mesh_params()?;
+        let other_params = other.nav_mesh_params()?;
+        check_same_grid(&params, &other_params)?;
+
+        let mut incoming = Vec::new();
+        for (_, bytes) in other.saved_tiles(other_params.max_tiles)? {
+            let tile = TileData::from_bytes(bytes)?;
+            incoming.push((tile.header()?, tile));
+        }
+        let columns: HashSet<_> = incoming.iter().map(|(h, _)| (h.tx, h.ty)).collect();
+
+        let mut agent = incoming.first().map(|(h, _)| *h);
+        let mut existing = HashSet::new();
+        let mut replaced = Vec::new();
+        for (_, bytes) in self.saved_tiles(params.max_tiles)? {
+            let h = tile_header(bytes)?;
+            if existing.is_empty() {
+                agent = Some(h);
+            }
+            existing.insert((h.tx, h.ty));
+            if conflict == TileConflict::Replace && columns.contains(&(h.tx, h.ty)) {
+                replaced.push((h, TileData::from_bytes(bytes)?));
+            }
+        }
+
+        if let Some(agent) = &agent {
+            for (h, _) in &incoming {
+                check_same_agent(agent, h)?;
+            }
+        }
+        if conflict == TileConflict::Fail {
+            if let Some((tx, ty)) = columns.iter().find(|c| existing.contains(c)) {
+                return Err(Error::BuildError(format!(
+                    "tile ({}, {}) is in both navmeshes",
+                    tx, ty
+                )));
+            }
+        }
+
+        let mut report = MergeReport::default();
+        let mut undo = Vec::new();
+        if let Err(err) = self.merge_tiles(
+            incoming,
+            &existing,
+            conflict,
+            replaced,
+            &mut report,
+            &mut undo,
+        ) {
+            for step in undo.into_iter().rev() {
+                // Each step goes back to a state the navmesh was in a moment ago
+                let _ = match step {
+                    Undo::Added(tx, ty, layer) => self.remove_tile_layer(tx, ty, layer),
+                    Undo::Removed(tile) => self.add_tile(tile),
+                };
+            }
+            return Err(err);
+        }
+
+        for coords in [&mut report.added, &mut report.replaced, &mut report.skipped] {
+            coords.sort_unstable();
+            coords.dedup();
+        }
+        Ok(report)
+    }
+
+    fn merge_tiles(
+        &mut self,
+        incoming: Vec<(TileHeader, TileData)>,
+        existing: &HashSet<(i32, i32)>,
+        conflict: TileConflict,
+        replaced: Vec<(TileHeader, TileData)>,
+        report: &mut MergeReport,
+        undo: &mut Vec<Undo>,
+    ) -> Result<()> {
+        for (h, tile) in replaced {
+            self.remove_tile_layer(h.tx, h.ty, h.layer)?;
+            undo.push(Undo::Removed(tile));
+        }
+
+        for (h, tile) in incoming {
+            let coords = (h.tx, h.ty);
+            let conflicting = existing.contains(&coords);
+            if conflicting && conflict == TileConflict::KeepExisting {
+                report.skipped.push(coords);
+                continue;
+            }
+
+            self.add_tile(tile)?;
+            undo.push(Undo::Added(h.tx, h.ty, h.layer));
+            if conflicting {
+                report.replaced.push(coords);
+            } else {
+                report.added.push(coords);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A change of `RecastQuery::merge` to revert when a later tile fails
+enum Undo {
+    Added(i32, i32, i32),
+    Removed(TileData),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, TileCache, TileCacheConfig, TileGrid};
+
+    fn strip() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        }
+    }
+
+    /// The tiles of the west or east half of the strip
+    fn half_tiles(config: &BuildConfig, west: bool) -> (NavMeshParams, Vec<TileData>) {
+        let geom = strip();
+        let grid = TileGrid::for_geometry(&geom, config, 16).unwrap();
+        let half = grid.cols / 2;
+        let tiles = grid
+            .tiles()
+            .filter(|&(tx, _)| (tx < half) == west)
+            .filter_map(|(tx, ty)| grid.build_tile(&geom, config, tx, ty).unwrap())
+            .collect();
+        (grid.nav_mesh_params().unwrap(), tiles)
+    }
+
+    /// A base navmesh over the west half of the strip and an add-on over the east half
+    fn base_and_addon() -> (RecastQuery, RecastQuery) {
+        let build = |west: bool| -> RecastQuery {
+            let (params, tiles) = half_tiles(&BuildConfig::default(), west);
+            RecastQuery::stitch_tiles(&params, tiles).unwrap()
+        };
+        (build(true), build(false))
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut base, addon) = base_and_addon();
+        let (a, b) = ((1.0, 0.0, 5.0).into(), (19.0, 0.0, 5.0).into());
+        let ext = (0.5, 1.0, 0.5);
+        assert!(base.find_poly(b, ext).is_err());
+
+        let n_base = base.tile_coords().unwrap().len();
+        let report = base.merge(&addon, TileConflict::Fail).unwrap();
+        assert_eq!(report.added.len(), addon.tile_coords().unwrap().len());
+        assert_eq!(
+            base.tile_coords().unwrap().len(),
+            n_base + report.added.len()
+        );
+
+        let path = base.find_path(a, b, ext).unwrap();
+        assert!((path.last().unwrap().x() - 19.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let (mut base, addon) = base_and_addon();
+        base.merge(&addon, TileConflict::Fail).unwrap();
+        let n = addon.tile_coords().unwrap().len();
+
+        let err = base.merge(&addon, TileConflict::Fail).unwrap_err();
+        assert!(err.to_string().contains("in both navmeshes"));
+        assert_eq!(
+            base.merge(&addon, TileConflict::KeepExisting)
+                .unwrap()
+                .skipped
+                .len(),
+            n
+        );
+        assert_eq!(
+            base.merge(&addon, TileConflict::Replace)
+                .unwrap()
+                .replaced
+                .len(),
+            n
+        );
+    }
+
+    #[test]
+    fn test_merge_rolls_back() {
+        let (params, west) = half_tiles(&BuildConfig::default(), true);
+        let n = west.len() as u32;
+        // Room for one more tile only
+        let small = NavMeshParams {
+            max_tiles: n + 1,
+            ..params
+        };
+        let mut base = RecastQuery::stitch_tiles(&small, west).unwrap();
+        let sorted_coords = |q: &RecastQuery| {
+            let mut coords = q.tile_coords().unwrap();
+            coords.sort_unstable();
+            coords
+        };
+        let before = sorted_coords(&base);
+        let (_, addon) = base_and_addon();
+        assert!(addon.tile_coords().unwrap().len() > 1);
+
+        assert!(base.merge(&addon, TileConflict::Fail).is_err());
+        assert_eq!(sorted_coords(&base), before);
+        assert!(base
+            .find_poly((1.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_merge_checks_agent() {
+        let (mut base, _) = base_and_addon();
+        let (params, tiles) = half_tiles(&BuildConfig::default().agent(2.0, 0.3, 0.9, 45.0), false);
+        let addon = RecastQuery::stitch_tiles(&params, tiles).unwrap();
+        let n = base.tile_coords().unwrap().len();
+
+        let err = base.merge(&addon, TileConflict::Fail).unwrap_err();
+        assert!(err.to_string().contains("another agent"), "{}", err);
+        assert_eq!(base.tile_coords().unwrap().len(), n);
+    }
+
+    #[test]
+    fn test_merge_layers() {
+        // Two floors over the same tiles
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                0.0, 5.0, 0.0, 10.0, 5.0, 0.0, 10.0, 5.0, 10.0, 0.0, 5.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            ..Default::default()
+        };
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let cache = TileCache::build(&geom, &BuildConfig::default(), &config).unwrap();
+        let layered = cache.query();
+        let coords = layered.tile_coords().unwrap();
+        let columns: HashSet<_> = coords.iter().collect();
+        assert!(coords.len() > columns.len());
+
+        let params = layered.nav_mesh_params().unwrap();
+        let mut q = RecastQuery::create_tiled_with_params(&params).unwrap();
+        let report = q.merge(layered, TileConflict::Fail).unwrap();
+        assert_eq!(report.added.len(), columns.len());
+        assert_eq!(q.tile_coords().unwrap().len(), coords.len());
+        let ext = (0.5, 0.5, 0.5);
+        assert!(q.find_poly((5.0, 5.0, 5.0).into(), ext).is_ok());
+        assert!(q.find_poly((5.0, 0.0, 5.0).into(), ext).is_ok());
+
+        // Replaced with exactly the layers of the other navmesh
+        let report = q.merge(layered, TileConflict::Replace).unwrap();
+        assert_eq!(report.replaced.len(), columns.len());
+        assert_eq!(q.tile_coords().unwrap().len(), coords.len());
+    }
+}
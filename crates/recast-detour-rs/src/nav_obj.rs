@@ -54,9 +54,9 @@ fn parse_data_line(obj_file: &mut NavObjFile, line: &String) -> Result<()> {
             obj_file.data.vertices.push(f3);
         }
         "f" => {
-            let i1 = second_item.parse::<u16>()?;
-            let i2 = items.next().ok_or(Error::LineError)?.parse::<u16>()?;
-            let i3 = items.next().ok_or(Error::LineError)?.parse::<u16>()?;
+            let i1 = second_item.parse::<u32>()?;
+            let i2 = items.next().ok_or(Error::LineError)?.parse::<u32>()?;
+            let i3 = items.next().ok_or(Error::LineError)?.parse::<u32>()?;
             obj_file.data.indices.push(i1 - 1);
             obj_file.data.indices.push(i2 - 1);
             obj_file.data.indices.push(i3 - 1);
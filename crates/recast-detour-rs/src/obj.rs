@@ -0,0 +1,183 @@
+//! Wavefront .obj files loaded as the input geometry of a build
+use crate::{Error, InputGeometry, Point, Result, UpAxis};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// How the positions of an .obj file become positions of the build
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjOptions {
+    /// Multiplies the positions, e.g. 0.01 for a file in centimeters and a build in meters
+    pub scale: f32,
+    /// Up axis of the file, e.g. `UpAxis::Z` for an export from a Z-up tool
+    pub up_axis: UpAxis,
+}
+
+impl Default for ObjOptions {
+    fn default() -> ObjOptions {
+        ObjOptions {
+            scale: 1.0,
+            up_axis: UpAxis::Y,
+        }
+    }
+}
+
+fn obj_error(line: usize, msg: &str) -> Error {
+    Error::ObjError(format!("line {}: {}", line, msg))
+}
+
+/// The 0 based index of the position of a face vertex `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+/// Negative indices count back from the last position read
+fn face_index(token: &str, vertex_count: usize, line: usize) -> Result<u32> {
+    let v = token.split('/').next().unwrap_or("");
+    let i: i64 = v
+        .parse()
+        .map_err(|_| obj_error(line, "invalid face index"))?;
+    let index = match i {
+        i if i > 0 => i - 1,
+        i if i < 0 => vertex_count as i64 + i,
+        _ => -1,
+    };
+    if index < 0 || index > u32::MAX as i64 {
+        return Err(obj_error(line, "face index out of range"));
+    }
+    Ok(index as u32)
+}
+
+impl InputGeometry {
+    /// The faces of an .obj file, needs the `obj` feature.
+    ///
+    /// Only the positions and the faces are read, polygons become fans of triangles. Objects,
+    /// groups, materials, normals and texture coordinates are ignored. The faces keep their
+    /// winding, the front faces of the file facing up are walkable
+    pub fn from_obj_file<P: AsRef<Path>>(path: P, options: &ObjOptions) -> Result<InputGeometry> {
+        let file = fs::File::open(path).map_err(|e| Error::ObjError(e.to_string()))?;
+        InputGeometry::from_obj(io::BufReader::new(file), options)
+    }
+
+    /// The faces of .obj data, see `from_obj_file`
+    pub fn from_obj<R: BufRead>(reader: R, options: &ObjOptions) -> Result<InputGeometry> {
+        let mut geometry = InputGeometry::default();
+        let mut face = vec![];
+
+        for (n, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| Error::ObjError(e.to_string()))?;
+            let n = n + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let mut items = line.split_whitespace();
+
+            match items.next() {
+                Some("v") => {
+                    let mut coords = [0.0; 3];
+                    for c in coords.iter_mut() {
+                        *c = items
+                            .next()
+                            .and_then(|s| s.parse::<f32>().ok())
+                            .ok_or_else(|| obj_error(n, "invalid vertex"))?;
+                    }
+                    let p = options
+                        .up_axis
+                        .to_recast(Point(coords.map(|c| c * options.scale)));
+                    geometry.vertices.extend_from_slice(&p.0);
+                }
+                Some("f") => {
+                    let vertex_count = geometry.vertices.len() / 3;
+                    face.clear();
+                    for token in items {
+                        face.push(face_index(token, vertex_count, n)?);
+                    }
+                    if face.len() < 3 {
+                        return Err(obj_error(n, "face with less than 3 vertices"));
+                    }
+                    for i in 1..face.len() - 1 {
+                        geometry
+                            .indices
+                            .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Faces may refer to positions listed after them
+        let vertex_count = geometry.vertices.len() / 3;
+        if geometry.indices.iter().any(|i| *i as usize >= vertex_count) {
+            return Err(Error::ObjError("face index out of range".into()));
+        }
+        Ok(geometry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, RecastQuery};
+
+    const ROOM: &str = "\
+# A room as one quad
+mtllib room.mtl
+o room
+v 0 0 0
+v 10 0 0
+v 10 0 10
+v 0 0 10 # last corner
+vn 0 1 0
+vt 0 0
+usemtl floor
+f 1/1/1 4/1/1 3/1/1 2/1/1
+";
+
+    #[test]
+    fn test_load_room() {
+        let geom = InputGeometry::from_obj(ROOM.as_bytes(), &ObjOptions::default()).unwrap();
+        assert_eq!(geom.vertices.len(), 12);
+        assert_eq!(geom.indices, vec![0, 3, 2, 0, 2, 1]);
+
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let path = q
+            .find_path(
+                (1.0, 0.0, 1.0).into(),
+                (9.0, 0.0, 9.0).into(),
+                (0.5, 1.0, 0.5),
+            )
+            .unwrap();
+        assert!(path.len() >= 2);
+    }
+
+    #[test]
+    fn test_scale_and_axis() {
+        let obj = "v 1 2 3\nv 4 5 6\nv 7 8 9\nf -3//1 -2//1 -1//1\n";
+        let options = ObjOptions {
+            scale: 2.0,
+            up_axis: UpAxis::Z,
+        };
+        let geom = InputGeometry::from_obj(obj.as_bytes(), &options).unwrap();
+        assert_eq!(
+            geom.vertices,
+            vec![2.0, 6.0, -4.0, 8.0, 12.0, -10.0, 14.0, 18.0, -16.0]
+        );
+        assert_eq!(geom.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_invalid_obj() {
+        let load = |obj: &str| InputGeometry::from_obj(obj.as_bytes(), &ObjOptions::default());
+        let msg = |obj: &str| match load(obj) {
+            Err(Error::ObjError(msg)) => msg,
+            res => panic!("{:?}", res),
+        };
+
+        assert_eq!(msg("v 0 0 0\nv 1 x 0\n"), "line 2: invalid vertex");
+        assert_eq!(
+            msg("v 0 0 0\nf 1 1\n"),
+            "line 2: face with less than 3 vertices"
+        );
+        assert_eq!(msg("v 0 0 0\nf 1 0 1\n"), "line 2: face index out of range");
+        assert_eq!(
+            msg("v 0 0 0\nf 1 -2 1\n"),
+            "line 2: face index out of range"
+        );
+        assert_eq!(msg("f 1 2 3\nv 0 0 0\n"), "face index out of range");
+        assert!(load("").unwrap().indices.is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+//! Jump links, ladders and teleporters between points of the navmesh
+use crate::{Error, PathPoint, Point, PolyFlags, PolyRef, RecastQuery, Result};
+use std::collections::HashSet;
+
+/// A link the agents can take between two points not connected by the mesh
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffMeshConnection {
+    /// Start point in world unit
+    pub start: Point,
+    /// End point in world unit
+    pub end: Point,
+    /// The endpoints connect to the polys within this radius
+    pub radius: f32,
+    /// Whether the link can also be taken from end to start
+    pub bidirectional: bool,
+    pub area: u8,
+    pub flags: PolyFlags,
+    /// Reported back in path results to tell the links apart,
+    /// 0 is replaced by a `stable_id` when the navmesh is built
+    pub user_id: u32,
+}
+
+impl OffMeshConnection {
+    pub fn new(start: Point, end: Point, radius: f32) -> OffMeshConnection {
+        OffMeshConnection {
+            start,
+            end,
+            radius,
+            bidirectional: true,
+            area: 0,
+            flags: PolyFlags::JUMP,
+            user_id: 0,
+        }
+    }
+
+    /// A one way link from `top` down to `bottom`, flagged `PolyFlags::DROP`
+    pub fn drop_down(top: Point, bottom: Point, radius: f32) -> OffMeshConnection {
+        OffMeshConnection {
+            bidirectional: false,
+            flags: PolyFlags::DROP,
+            ..OffMeshConnection::new(top, bottom, radius)
+        }
+    }
+
+    /// Drop down links every `spacing` along the ledge from `a` to `b`, each one ending at
+    /// its start moved by `drop`, e.g. down and away from the wall below the ledge
+    pub fn along_ledge(
+        a: Point,
+        b: Point,
+        drop: Point,
+        spacing: f32,
+        radius: f32,
+    ) -> Vec<OffMeshConnection> {
+        let d = [b.x() - a.x(), b.y() - a.y(), b.z() - a.z()];
+        let len = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        let n = ((len / spacing.max(f32::EPSILON)) as usize).max(1);
+
+        (0..n)
+            .map(|i| {
+                let t = (i as f32 + 0.5) / n as f32;
+                let top = Point([a.x() + d[0] * t, a.y() + d[1] * t, a.z() + d[2] * t]);
+                let bottom = Point([top.x() + drop.x(), top.y() + drop.y(), top.z() + drop.z()]);
+                OffMeshConnection::drop_down(top, bottom, radius)
+            })
+            .collect()
+    }
+
+    /// Two one way links between the same points, so filters can allow a single direction,
+    /// e.g. everyone drops down a ledge but only the agents with `JUMP` climb it back
+    pub fn pair(
+        start: Point,
+        end: Point,
+        radius: f32,
+        forward: PolyFlags,
+        back: PolyFlags,
+    ) -> [OffMeshConnection; 2] {
+        let link = |start, end, flags| OffMeshConnection {
+            bidirectional: false,
+            flags,
+            ..OffMeshConnection::new(start, end, radius)
+        };
+        [link(start, end, forward), link(end, start, back)]
+    }
+
+    /// The two one way links of an elevator from `bottom` to `top` and back, their user ids are the
+    /// gates `up` and `down` of a `Crowd`, e.g. open `up` and close `down` while the cabin waits at
+    /// the bottom floor. The gates need ids other than 0
+    pub fn elevator(
+        bottom: Point,
+        top: Point,
+        radius: f32,
+        up: u32,
+        down: u32,
+    ) -> [OffMeshConnection; 2] {
+        let [a, b] = OffMeshConnection::pair(bottom, top, radius, PolyFlags::JUMP, PolyFlags::JUMP);
+        [
+            OffMeshConnection { user_id: up, ..a },
+            OffMeshConnection { user_id: down, ..b },
+        ]
+    }
+
+    /// An id derived from the link itself, the same in every bake of the same link.
+    /// The positions and radius count to 1e-3 world unit, never 0
+    pub fn stable_id(&self) -> u32 {
+        // FNV-1a, unlike `DefaultHasher` its output is specified
+        let mut hash: u32 = 0x811c_9dc5;
+        let mut write = |bytes: &[u8]| {
+            for &b in bytes {
+                hash = (hash ^ b as u32).wrapping_mul(0x0100_0193);
+            }
+        };
+
+        for v in self
+            .start
+            .0
+            .iter()
+            .chain(self.end.0.iter())
+            .chain(Some(&self.radius))
+        {
+            write(&((*v as f64 * 1000.0).round() as i64).to_le_bytes());
+        }
+        write(&[self.bidirectional as u8, self.area]);
+        write(&self.flags.0.to_le_bytes());
+
+        hash.max(1)
+    }
+
+    pub(crate) fn as_sys(&self) -> sys::RecastOffMeshConnection {
+        sys::RecastOffMeshConnection {
+            start: self.start.0,
+            end: self.end.0,
+            radius: self.radius,
+            bidirectional: self.bidirectional as u8,
+            area: self.area,
+            flags: self.flags.0,
+            user_id: self.user_id,
+        }
+    }
+
+    fn from_sys(con: &sys::RecastOffMeshConnection) -> OffMeshConnection {
+        OffMeshConnection {
+            start: Point(con.start),
+            end: Point(con.end),
+            radius: con.radius,
+            bidirectional: con.bidirectional != 0,
+            area: con.area,
+            flags: PolyFlags(con.flags),
+            user_id: con.user_id,
+        }
+    }
+}
+
+/// Give the links without user id their `stable_id`, in order. An id already taken by another link
+/// moves to the next free one, so unchanged links keep their ids across bakes
+pub fn assign_stable_ids(connections: &mut [OffMeshConnection]) {
+    let mut taken: HashSet<u32> = connections
+        .iter()
+        .map(|c| c.user_id)
+        .filter(|&id| id != 0)
+        .collect();
+
+    for con in connections.iter_mut().filter(|c| c.user_id == 0) {
+        let mut id = con.stable_id();
+        while !taken.insert(id) {
+            id = id.wrapping_add(1).max(1);
+        }
+        con.user_id = id;
+    }
+}
+
+/// The connections given to the C side, with their stable ids
+pub(crate) fn sys_connections(
+    connections: &[OffMeshConnection],
+) -> Vec<sys::RecastOffMeshConnection> {
+    if connections.iter().all(|c| c.user_id != 0) {
+        return connections.iter().map(|c| c.as_sys()).collect();
+    }
+
+    let mut connections = connections.to_vec();
+    assign_stable_ids(&mut connections);
+    connections.iter().map(|c| c.as_sys()).collect()
+}
+
+impl RecastQuery {
+    /// The off-mesh connection of an off-mesh poly, e.g. the poly of a path point
+    /// flagged with `is_off_mesh_connection`
+    pub fn off_mesh_connection(&self, poly: PolyRef) -> Result<OffMeshConnection> {
+        let mut con = sys::RecastOffMeshConnection::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_off_mesh_connection(
+                self.q.as_ptr(),
+                poly,
+                &mut con as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::PolyError(err.msg().to_string()))
+        } else {
+            Ok(OffMeshConnection::from_sys(&con))
+        }
+    }
+}
+
+/// A segment between two consecutive straight path points
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathSegment {
+    pub start: Point,
+    pub end: Point,
+    /// The segment traverses an off-mesh connection, e.g. a jump to play instead of walking
+    pub off_mesh: bool,
+    /// User id of the traversed off-mesh connection
+    pub off_mesh_id: Option<u32>,
+}
+
+/// The segments of a straight path, e.g. of `RecastQuery::find_path_with_options`.
+/// An off-mesh connection starts at a point flagged with `is_off_mesh_connection` and
+/// ends at the next point
+pub fn path_segments(path: &[PathPoint]) -> impl Iterator<Item = PathSegment> + '_ {
+    path.windows(2).map(|w| PathSegment {
+        start: w[0].pos,
+        end: w[1].pos,
+        off_mesh: w[0].flags.is_off_mesh_connection(),
+        off_mesh_id: w[0].off_mesh_id,
+    })
+}
+
+/// Where an agent is on an off-mesh connection, e.g. to pick the start, loop and landing of a jump animation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OffMeshPhase {
+    /// Moving from where it reached the connection to its start
+    Entering,
+    /// Moving from the start to the end of the connection
+    Traversing,
+    /// Left the connection, walking again since the last update
+    Exiting,
+}
+
+/// An agent crossing an off-mesh connection, timed like `dtCrowd` moves its agents over them
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OffMeshTraversal {
+    pub phase: OffMeshPhase,
+    /// Poly of the connection
+    pub link: PolyRef,
+    pub user_id: u32,
+    /// The end of the connection the agent entered at
+    pub start: Point,
+    pub end: Point,
+    /// Seconds since the agent reached the connection
+    pub elapsed: f32,
+    /// Seconds to cross the connection
+    pub duration: f32,
+}
+
+impl OffMeshTraversal {
+    /// A traversal starting now at the speed of the agent, the crowd takes half the time walking would
+    pub fn new(
+        link: PolyRef,
+        user_id: u32,
+        start: Point,
+        end: Point,
+        max_speed: f32,
+    ) -> OffMeshTraversal {
+        let (dx, dz) = (end.x() - start.x(), end.z() - start.z());
+        OffMeshTraversal {
+            phase: OffMeshPhase::Entering,
+            link,
+            user_id,
+            start,
+            end,
+            elapsed: 0.0,
+            duration: (dx * dx + dz * dz).sqrt() / max_speed * 0.5,
+        }
+    }
+
+    /// Move the traversal on by `dt` seconds, it is `Exiting` once the time is over
+    pub fn advance(&mut self, dt: f32) {
+        if self.phase == OffMeshPhase::Exiting {
+            return;
+        }
+        self.elapsed += dt;
+        self.phase = if self.elapsed > self.duration {
+            OffMeshPhase::Exiting
+        } else if self.elapsed < self.entering_time() {
+            OffMeshPhase::Entering
+        } else {
+            OffMeshPhase::Traversing
+        };
+    }
+
+    /// Fraction of the traversal done, 0 to 1
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            (self.elapsed / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Position of an agent which reached the connection at `entered_at`, as the crowd moves it
+    pub fn position(&self, entered_at: Point) -> Point {
+        let lerp = |a: Point, b: Point, t: f32| {
+            let t = t.clamp(0.0, 1.0);
+            Point::new((
+                a.x() + (b.x() - a.x()) * t,
+                a.y() + (b.y() - a.y()) * t,
+                a.z() + (b.z() - a.z()) * t,
+            ))
+        };
+
+        let ta = self.entering_time();
+        if self.elapsed < ta {
+            lerp(entered_at, self.start, self.elapsed / ta)
+        } else {
+            lerp(
+                self.start,
+                self.end,
+                (self.elapsed - ta) / (self.duration - ta),
+            )
+        }
+    }
+
+    /// The crowd reaches the start of the connection in the first 15% of the time
+    fn entering_time(&self) -> f32 {
+        self.duration * 0.15
+    }
+
+    pub(crate) fn from_sys(tr: &sys::RecastOffMeshTraversal) -> Option<OffMeshTraversal> {
+        let phase = match tr.phase {
+            sys::RECASTC_OFFMESH_ENTERING => OffMeshPhase::Entering,
+            sys::RECASTC_OFFMESH_TRAVERSING => OffMeshPhase::Traversing,
+            sys::RECASTC_OFFMESH_EXITING => OffMeshPhase::Exiting,
+            _ => return None,
+        };
+        Some(OffMeshTraversal {
+            phase,
+            link: tr.poly,
+            user_id: tr.user_id,
+            start: Point(tr.start),
+            end: Point(tr.end),
+            elapsed: tr.elapsed,
+            duration: tr.duration,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, PathOptions, QueryFilter};
+
+    /// Two platforms with a gap between x = 4 and x = 6
+    fn platforms() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                6.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 6.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_off_mesh_connection_path() {
+        let (start, end) = ((1.0, 0.0, 5.0).into(), (9.0, 0.0, 5.0).into());
+
+        let q = RecastQuery::build(&platforms(), &BuildConfig::default()).unwrap();
+        assert!(q.find_path(start, end, (0.5, 1.0, 0.5)).is_err());
+
+        let mut geom = platforms();
+        geom.off_mesh_connections.push(OffMeshConnection {
+            user_id: 42,
+            bidirectional: false,
+            ..OffMeshConnection::new((3.0, 0.0, 5.0).into(), (7.0, 0.0, 5.0).into(), 0.5)
+        });
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+
+        let path = q
+            .find_path_with_options(start, end, (0.5, 1.0, 0.5), &PathOptions::default())
+            .unwrap();
+        let link = path
+            .iter()
+            .find(|p| p.flags.is_off_mesh_connection())
+            .unwrap();
+        assert_eq!(link.off_mesh_id, Some(42));
+
+        // Walking up to the link, jumping, then walking again
+        let segments: Vec<_> = path_segments(&path).collect();
+        assert_eq!(segments.len(), path.len() - 1);
+        let jumps: Vec<_> = segments.iter().filter(|s| s.off_mesh).collect();
+        assert_eq!(jumps.len(), 1);
+        assert_eq!(jumps[0].off_mesh_id, Some(42));
+        assert!(jumps[0].start.x() <= 4.0 && jumps[0].end.x() >= 6.0);
+
+        let con = q.off_mesh_connection(link.poly).unwrap();
+        assert_eq!(con.flags, PolyFlags::JUMP);
+        assert!(!con.bidirectional);
+
+        // One way only
+        assert!(q.find_path(end, start, (0.5, 1.0, 0.5)).is_err());
+    }
+
+    /// An upper floor at height 3 for x < 4, a lower floor for x > 4
+    fn cliff() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 3.0, 0.0, 4.0, 3.0, 0.0, 4.0, 3.0, 10.0, 0.0, 3.0, 10.0, //
+                4.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 4.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_traversal_timing() {
+        let mut tr =
+            OffMeshTraversal::new(7, 42, (3.0, 0.0, 5.0).into(), (7.0, 0.0, 5.0).into(), 2.0);
+        assert_eq!(tr.duration, 1.0);
+        assert_eq!(tr.phase, OffMeshPhase::Entering);
+
+        let entered_at: Point = (2.5, 0.0, 5.0).into();
+        tr.advance(0.1);
+        assert_eq!(tr.phase, OffMeshPhase::Entering);
+        assert!(tr.position(entered_at).x() > 2.5 && tr.position(entered_at).x() < 3.0);
+
+        tr.advance(0.5);
+        assert_eq!(tr.phase, OffMeshPhase::Traversing);
+        assert!((tr.progress() - 0.6).abs() < 1e-6);
+        assert!(tr.position(entered_at).x() > 3.0 && tr.position(entered_at).x() < 7.0);
+
+        tr.advance(0.5);
+        assert_eq!(tr.phase, OffMeshPhase::Exiting);
+        assert_eq!(tr.progress(), 1.0);
+        assert_eq!(tr.position(entered_at), tr.end);
+    }
+
+    #[test]
+    fn test_drop_down_ledge() {
+        let mut geom = cliff();
+        geom.off_mesh_connections = OffMeshConnection::along_ledge(
+            (3.5, 3.0, 1.0).into(),
+            (3.5, 3.0, 9.0).into(),
+            (1.5, -3.0, 0.0).into(),
+            2.0,
+            0.6,
+        );
+        assert_eq!(geom.off_mesh_connections.len(), 4);
+        assert!(geom.off_mesh_connections[0].start.z() > 1.0);
+
+        let mut q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let (top, bottom) = ((1.0, 3.0, 5.0).into(), (8.0, 0.0, 5.0).into());
+        let ext = (0.5, 1.0, 0.5);
+        assert!(q.find_path(top, bottom, ext).is_ok());
+        assert!(q.find_path(bottom, top, ext).is_err());
+
+        q.set_filter(QueryFilter::default().exclude(PolyFlags::DROP));
+        assert!(q.find_path(top, bottom, ext).is_err());
+    }
+
+    #[test]
+    fn test_paired_links() {
+        let mut geom = cliff();
+        geom.off_mesh_connections
+            .extend_from_slice(&OffMeshConnection::pair(
+                (3.5, 3.0, 5.0).into(),
+                (5.0, 0.0, 5.0).into(),
+                0.6,
+                PolyFlags::DROP,
+                PolyFlags::JUMP,
+            ));
+        let mut q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let (top, bottom) = ((1.0, 3.0, 5.0).into(), (8.0, 0.0, 5.0).into());
+        let ext = (0.5, 1.0, 0.5);
+        assert!(q.find_path(bottom, top, ext).is_ok());
+
+        q.set_filter(QueryFilter::default().exclude(PolyFlags::JUMP));
+        assert!(q.find_path(top, bottom, ext).is_ok());
+        assert!(q.find_path(bottom, top, ext).is_err());
+    }
+
+    #[test]
+    fn test_stable_ids() {
+        let a = OffMeshConnection::new((3.0, 0.0, 5.0).into(), (7.0, 0.0, 5.0).into(), 0.5);
+        let b = OffMeshConnection {
+            bidirectional: false,
+            ..a.clone()
+        };
+        assert_eq!(a.stable_id(), a.clone().stable_id());
+        assert_ne!(a.stable_id(), b.stable_id());
+
+        // Below the quantization
+        let moved = OffMeshConnection {
+            start: (3.0001, 0.0, 5.0).into(),
+            ..a.clone()
+        };
+        assert_eq!(a.stable_id(), moved.stable_id());
+
+        let mut links = vec![
+            a.clone(),
+            a.clone(),
+            b.clone(),
+            OffMeshConnection { user_id: 9, ..b },
+        ];
+        let mut again = links.clone();
+        assign_stable_ids(&mut links);
+        assign_stable_ids(&mut again);
+        assert_eq!(links, again);
+        assert_eq!(links[0].user_id, a.stable_id());
+        assert_eq!(links[1].user_id, a.stable_id() + 1);
+        assert_eq!(links[3].user_id, 9);
+    }
+
+    #[test]
+    fn test_unset_ids_across_bakes() {
+        let mut geom = platforms();
+        geom.off_mesh_connections.push(OffMeshConnection::new(
+            (3.0, 0.0, 5.0).into(),
+            (7.0, 0.0, 5.0).into(),
+            0.5,
+        ));
+        let expected = geom.off_mesh_connections[0].stable_id();
+
+        for _ in 0..2 {
+            let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+            let path = q
+                .find_path_with_options(
+                    (1.0, 0.0, 5.0).into(),
+                    (9.0, 0.0, 5.0).into(),
+                    (0.5, 1.0, 0.5),
+                    &PathOptions::default(),
+                )
+                .unwrap();
+            let link = path.iter().find(|p| p.flags.is_off_mesh_connection());
+            assert_eq!(link.unwrap().off_mesh_id, Some(expected));
+        }
+    }
+}
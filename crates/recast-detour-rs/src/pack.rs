@@ -0,0 +1,489 @@
+//! A container bundling several named navmesh variants of the same level
+use crate::{
+    AgentId, Crowd, Error, NavMeshData, OffMeshConnection, PolyFlags, RecastQuery, Result,
+};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const PACK_MAGIC: &[u8; 4] = b"RDPK";
+/// Version 1 had no off-mesh connections, versions 1 and 2 stored the indices as u16,
+/// versions 1 to 3 had no triangle areas
+const PACK_VERSION: u32 = 4;
+
+/// Named navmeshes, e.g. one per agent profile or per level state
+///
+/// Layout (little endian): magic, version, variant count, then per variant its name
+/// (u32 length + utf8), the 5 `NavMeshData` parameters, the vertices, the u32 indices, the
+/// off-mesh connections and the u8 triangle areas (u32 count + values each).
+#[derive(Debug, Default, Clone)]
+pub struct NavMeshPack {
+    variants: Vec<(String, NavMeshData)>,
+}
+
+pub(crate) fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub(crate) fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    read_u32(r).map(f32::from_bits)
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn pack_error(err: io::Error) -> Error {
+    Error::PackError(err.to_string())
+}
+
+impl NavMeshPack {
+    pub fn new() -> NavMeshPack {
+        NavMeshPack::default()
+    }
+
+    /// Add a variant, replacing the one with the same name. Variants are stored Y-up
+    pub fn insert(&mut self, name: &str, data: NavMeshData) {
+        let data = data.into_y_up();
+        match self.variants.iter_mut().find(|(n, _)| n == name) {
+            Some((_, d)) => *d = data,
+            None => self.variants.push((name.to_string(), data)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NavMeshData> {
+        self.variants
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, d)| d)
+    }
+
+    /// Variant names in insertion order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.variants.iter().map(|(n, _)| n.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_io(w).map_err(pack_error)
+    }
+
+    fn write_io<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(PACK_MAGIC)?;
+        w.write_all(&PACK_VERSION.to_le_bytes())?;
+        w.write_all(&(self.variants.len() as u32).to_le_bytes())?;
+
+        for (name, data) in &self.variants {
+            w.write_all(&(name.len() as u32).to_le_bytes())?;
+            w.write_all(name.as_bytes())?;
+
+            for f in &[
+                data.walkable_height,
+                data.walkable_radius,
+                data.walkable_climb,
+                data.cell_size,
+                data.cell_height,
+            ] {
+                w.write_all(&f.to_le_bytes())?;
+            }
+
+            w.write_all(&(data.vertices.len() as u32).to_le_bytes())?;
+            for f in &data.vertices {
+                w.write_all(&f.to_le_bytes())?;
+            }
+            w.write_all(&(data.indices.len() as u32).to_le_bytes())?;
+            for i in &data.indices {
+                w.write_all(&i.to_le_bytes())?;
+            }
+            w.write_all(&(data.off_mesh_connections.len() as u32).to_le_bytes())?;
+            for c in &data.off_mesh_connections {
+                for f in c
+                    .start
+                    .0
+                    .iter()
+                    .chain(c.end.0.iter())
+                    .chain(Some(&c.radius))
+                {
+                    w.write_all(&f.to_le_bytes())?;
+                }
+                w.write_all(&[c.bidirectional as u8, c.area])?;
+                w.write_all(&c.flags.0.to_le_bytes())?;
+                w.write_all(&c.user_id.to_le_bytes())?;
+            }
+            w.write_all(&(data.triangle_areas.len() as u32).to_le_bytes())?;
+            w.write_all(&data.triangle_areas)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> Result<NavMeshPack> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic).map_err(pack_error)?;
+        if &magic != PACK_MAGIC {
+            return Err(Error::PackError("not a navmesh pack".into()));
+        }
+        let version = read_u32(r).map_err(pack_error)?;
+        if version == 0 || version > PACK_VERSION {
+            return Err(Error::PackError(format!(
+                "unsupported pack version {}",
+                version
+            )));
+        }
+
+        let count = read_u32(r).map_err(pack_error)?;
+        let mut pack = NavMeshPack::new();
+        for _ in 0..count {
+            let (name, data) = NavMeshPack::read_variant(r, version).map_err(pack_error)?;
+            if pack.get(&name).is_some() {
+                return Err(Error::PackError(format!("duplicated variant `{}`", name)));
+            }
+            pack.variants.push((name, data));
+        }
+
+        Ok(pack)
+    }
+
+    fn read_variant<R: Read>(r: &mut R, version: u32) -> io::Result<(String, NavMeshData)> {
+        // Read through `take`, a corrupt length fails at the end of the input instead of allocating it
+        let len = read_u32(r)? as usize;
+        let mut name = Vec::new();
+        r.take(len as u64).read_to_end(&mut name)?;
+        if name.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let name = String::from_utf8(name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let mut data = NavMeshData {
+            walkable_height: read_f32(r)?,
+            walkable_radius: read_f32(r)?,
+            walkable_climb: read_f32(r)?,
+            cell_size: read_f32(r)?,
+            cell_height: read_f32(r)?,
+            ..Default::default()
+        };
+
+        let n = read_u32(r)?;
+        for _ in 0..n {
+            data.vertices.push(read_f32(r)?);
+        }
+        let n = read_u32(r)?;
+        for _ in 0..n {
+            let i = if version <= 2 {
+                read_u16(r)? as u32
+            } else {
+                read_u32(r)?
+            };
+            data.indices.push(i);
+        }
+        // Version 1 packs have no off-mesh connections
+        let n = if version >= 2 { read_u32(r)? } else { 0 };
+        for _ in 0..n {
+            let mut p = [0.0; 7];
+            for f in p.iter_mut() {
+                *f = read_f32(r)?;
+            }
+            let mut bytes = [0; 2];
+            r.read_exact(&mut bytes)?;
+
+            data.off_mesh_connections.push(OffMeshConnection {
+                start: (p[0], p[1], p[2]).into(),
+                end: (p[3], p[4], p[5]).into(),
+                radius: p[6],
+                bidirectional: bytes[0] != 0,
+                area: bytes[1],
+                flags: PolyFlags(read_u16(r)?),
+                user_id: read_u32(r)?,
+            });
+        }
+        if version >= 4 {
+            let n = read_u32(r)? as usize;
+            r.take(n as u64).read_to_end(&mut data.triangle_areas)?;
+            if data.triangle_areas.len() != n {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+
+        Ok((name, data))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path).map_err(pack_error)?);
+        self.write(&mut file)
+    }
+
+    pub fn open(path: &Path) -> Result<NavMeshPack> {
+        let mut file = io::BufReader::new(fs::File::open(path).map_err(pack_error)?);
+        NavMeshPack::read(&mut file)
+    }
+}
+
+/// Queries for every variant of a pack, one of them active.
+///
+/// All queries are created up front, so switching never fails halfway and never
+/// leaves the previous variant unusable.
+#[derive(Debug)]
+pub struct NavMeshVariants {
+    queries: Vec<(String, RecastQuery)>,
+    active: usize,
+}
+
+impl NavMeshVariants {
+    /// Create the queries of all variants, the first one is active
+    pub fn from_pack(pack: &NavMeshPack) -> Result<NavMeshVariants> {
+        if pack.is_empty() {
+            return Err(Error::PackError("pack has no variants".into()));
+        }
+
+        let queries = pack
+            .variants
+            .iter()
+            .map(|(name, data)| {
+                RecastQuery::new_from_mesh(data.clone())
+                    .map(|q| (name.clone(), q))
+                    .map_err(|err| Error::PackError(format!("variant `{}`: {}", name, err)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(NavMeshVariants { queries, active: 0 })
+    }
+
+    pub fn active(&self) -> &RecastQuery {
+        &self.queries[self.active].1
+    }
+
+    pub fn active_mut(&mut self) -> &mut RecastQuery {
+        &mut self.queries[self.active].1
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.queries[self.active].0
+    }
+
+    /// Make another variant active, the active one is kept when `name` is unknown
+    pub fn switch(&mut self, name: &str) -> Result<()> {
+        let idx = self
+            .queries
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| Error::PackError(format!("unknown variant `{}`", name)))?;
+        self.active = idx;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RecastQuery> {
+        self.queries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, q)| q)
+    }
+
+    /// Switch variant and move the agents of the crowd onto it, see `Crowd::switch_navmesh`.
+    ///
+    /// The crowd gets its own copy of the navmesh and the filter of the variant, the variants
+    /// and the crowd stay as they were when it fails.
+    pub fn switch_crowd(
+        &mut self,
+        name: &str,
+        crowd: &mut Crowd,
+    ) -> Result<Vec<(AgentId, Stranded)>> {
+        let idx = self
+            .queries
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| Error::PackError(format!("unknown variant `{}`", name)))?;
+
+        let variant = &self.queries[idx].1;
+        let mut query = RecastQuery::from_serialized(&variant.serialize()?)?;
+        query.set_filter(variant.filter().clone());
+        let migration = crowd.switch_navmesh(query)?;
+
+        self.active = idx;
+        Ok(migration.stranded)
+    }
+}
+
+/// Why an agent could not be fully moved to a new variant
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stranded {
+    /// No poly of the new variant near the agent, it stays in the crowd but does not move
+    OffMesh,
+    /// The agent was placed but its target can not be reached anymore, it stands still
+    Unreachable,
+}
+
+/// Outcome of `Crowd::switch_navmesh`
+#[derive(Debug)]
+pub struct Migration {
+    /// The query the crowd ran on before
+    pub previous: RecastQuery,
+    pub stranded: Vec<(AgentId, Stranded)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(size: f32) -> NavMeshData {
+        NavMeshData {
+            vertices: vec![0.0, 0.0, 0.0, size, 0.0, 0.0, size, 0.0, size, 0.0, 0.0, size],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            walkable_height: 2.0,
+            walkable_radius: 0.5,
+            walkable_climb: 0.5,
+            cell_size: 0.1,
+            cell_height: 0.1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let mut pack = NavMeshPack::new();
+        pack.insert("small", quad(5.0));
+        pack.insert("large", quad(10.0));
+        pack.insert("small", quad(4.0));
+        let mut linked = quad(10.0);
+        linked.off_mesh_connections.push(OffMeshConnection {
+            user_id: 3,
+            ..OffMeshConnection::new((1.0, 0.0, 1.0).into(), (9.0, 0.0, 9.0).into(), 0.5)
+        });
+        pack.insert("linked", linked.clone());
+        let mut water = quad(10.0);
+        water.triangle_areas = vec![0, 2];
+        pack.insert("water", water);
+
+        let mut buf = vec![];
+        pack.write(&mut buf).unwrap();
+        let read = NavMeshPack::read(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(
+            read.names().collect::<Vec<_>>(),
+            vec!["small", "large", "linked", "water"]
+        );
+        assert_eq!(read.get("water").unwrap().triangle_areas, vec![0, 2]);
+        assert!(read.get("large").unwrap().triangle_areas.is_empty());
+        assert_eq!(
+            read.get("linked").unwrap().off_mesh_connections,
+            linked.off_mesh_connections
+        );
+        assert_eq!(read.get("small").unwrap().vertices[3], 4.0);
+        assert_eq!(read.get("large").unwrap().indices, vec![0, 2, 1, 0, 3, 2]);
+
+        assert!(NavMeshPack::read(&mut &buf[..buf.len() - 1]).is_err());
+        assert!(NavMeshPack::read(&mut &b"nope"[..]).is_err());
+
+        // A name length past the end of the input
+        let mut corrupt = buf[..12].to_vec();
+        corrupt.extend_from_slice(&u32::MAX.to_le_bytes());
+        corrupt.extend_from_slice(b"small");
+        assert!(NavMeshPack::read(&mut corrupt.as_slice()).is_err());
+    }
+
+    /// A pack of version 1 or 2 with one triangle, u16 indices and no off-mesh connections
+    fn old_pack(version: u32) -> Vec<u8> {
+        let mut buf = PACK_MAGIC.to_vec();
+        for n in &[version, 1, 1] {
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+        buf.push(b'a');
+        for f in &[2.0f32, 0.5, 0.5, 0.1, 0.1] {
+            buf.extend_from_slice(&f.to_le_bytes());
+        }
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&3u32.to_le_bytes());
+        for i in &[0u16, 2, 1] {
+            buf.extend_from_slice(&i.to_le_bytes());
+        }
+        if version >= 2 {
+            buf.extend_from_slice(&0u32.to_le_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_read_old_versions() {
+        for version in 1..=2 {
+            let read = NavMeshPack::read(&mut old_pack(version).as_slice()).unwrap();
+            assert_eq!(read.get("a").unwrap().indices, vec![0, 2, 1]);
+            assert!(read.get("a").unwrap().off_mesh_connections.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_switch_variants() {
+        let mut pack = NavMeshPack::new();
+        pack.insert("intact", quad(10.0));
+        pack.insert("destroyed", quad(5.0));
+        let mut variants = NavMeshVariants::from_pack(&pack).unwrap();
+
+        assert_eq!(variants.active_name(), "intact");
+        assert!(variants.active().find_poly((8.0, 0.0, 8.0).into(), (0.2, 1.0, 0.2)).is_ok());
+
+        variants.switch("destroyed").unwrap();
+        assert!(variants.active().find_poly((8.0, 0.0, 8.0).into(), (0.2, 1.0, 0.2)).is_err());
+
+        assert!(variants.switch("missing").is_err());
+        assert_eq!(variants.active_name(), "destroyed");
+    }
+
+    #[test]
+    fn test_switch_crowd() {
+        use crate::{CrowdAgentParams, MoveTarget, Point};
+
+        let mut pack = NavMeshPack::new();
+        pack.insert("intact", quad(10.0));
+        pack.insert("destroyed", quad(5.0));
+        let mut variants = NavMeshVariants::from_pack(&pack).unwrap();
+
+        let query = RecastQuery::new_from_mesh(quad(10.0)).unwrap();
+        let mut crowd = Crowd::new(query, 8, 0.6).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let near = crowd.add_agent((1.0, 0.0, 1.0).into(), &params).unwrap();
+        let cut_off = crowd.add_agent((2.0, 0.0, 2.0).into(), &params).unwrap();
+        let outside = crowd.add_agent((8.0, 0.0, 8.0).into(), &params).unwrap();
+        let target = Point::new((4.0, 0.0, 4.0));
+        crowd.request_move_target(near, target).unwrap();
+        crowd
+            .request_move_target(cut_off, (8.0, 0.0, 8.0).into())
+            .unwrap();
+        crowd.update(0.1).unwrap();
+
+        let stranded = variants.switch_crowd("destroyed", &mut crowd).unwrap();
+        assert_eq!(variants.active_name(), "destroyed");
+        assert_eq!(
+            stranded,
+            vec![
+                (cut_off, Stranded::Unreachable),
+                (outside, Stranded::OffMesh)
+            ]
+        );
+        assert!(crowd.agent(outside).unwrap().invalid);
+        assert_eq!(crowd.agent(cut_off).unwrap().target, MoveTarget::None);
+
+        for _ in 0..50 {
+            crowd.update(0.1).unwrap();
+        }
+        let pos = crowd.agent(near).unwrap().pos;
+        assert!(
+            (pos.x() - 4.0).abs() < 0.5 && (pos.z() - 4.0).abs() < 0.5,
+            "{:?}",
+            pos
+        );
+
+        assert!(variants.switch_crowd("missing", &mut crowd).is_err());
+        assert_eq!(variants.active_name(), "destroyed");
+    }
+}
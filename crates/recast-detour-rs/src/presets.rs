@@ -0,0 +1,154 @@
+//! Named query filters defined once and shared by the queries, crowds and tools of a game
+use crate::{Error, QueryFilter, RecastQuery, Result};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// An immutable registry of named `QueryFilter`, e.g. "infantry", "vehicle" and "amphibious".
+/// Cloning shares the filters
+#[derive(Debug, Clone, Default)]
+pub struct FilterPresets {
+    presets: Arc<BTreeMap<String, Arc<QueryFilter>>>,
+}
+
+impl FilterPresets {
+    pub fn builder() -> FilterPresetsBuilder {
+        FilterPresetsBuilder::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<QueryFilter>> {
+        self.presets.get(name)
+    }
+
+    /// `get` failing with `Error::FilterError` for an unknown name
+    pub fn require(&self, name: &str) -> Result<&Arc<QueryFilter>> {
+        self.get(name)
+            .ok_or_else(|| Error::FilterError(format!("unknown filter preset {:?}", name)))
+    }
+
+    /// The names of the presets in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.presets.keys().map(|k| k.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+}
+
+/// Builder of `FilterPresets`, the names must be unique
+#[derive(Debug, Clone, Default)]
+pub struct FilterPresetsBuilder {
+    presets: Vec<(String, QueryFilter)>,
+}
+
+impl FilterPresetsBuilder {
+    pub fn preset(mut self, name: &str, filter: QueryFilter) -> FilterPresetsBuilder {
+        self.presets.push((name.to_string(), filter));
+        self
+    }
+
+    pub fn build(self) -> Result<FilterPresets> {
+        let mut presets = BTreeMap::new();
+        for (name, filter) in self.presets {
+            if presets.contains_key(&name) {
+                return Err(Error::FilterError(format!(
+                    "filter preset {:?} defined twice",
+                    name
+                )));
+            }
+            presets.insert(name, Arc::new(filter));
+        }
+
+        Ok(FilterPresets {
+            presets: Arc::new(presets),
+        })
+    }
+}
+
+impl RecastQuery {
+    /// Make the named preset the query filter, see `set_filter`
+    pub fn use_preset(&mut self, presets: &FilterPresets, name: &str) -> Result<()> {
+        let filter = presets.require(name)?;
+        self.set_filter(QueryFilter::clone(filter));
+        Ok(())
+    }
+
+    /// Run queries with `filter` and restore the query filter after, e.g. for agents with
+    /// different presets sharing a query
+    pub fn with_filter<T, F>(&mut self, filter: &QueryFilter, f: F) -> T
+    where
+        F: FnOnce(&RecastQuery) -> T,
+    {
+        let previous = self.filter.clone();
+        self.set_filter(filter.clone());
+        let result = f(self);
+        self.set_filter(previous);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::simple_mesh;
+    use crate::PolyFlags;
+
+    fn presets() -> FilterPresets {
+        FilterPresets::builder()
+            .preset("infantry", QueryFilter::default())
+            .preset(
+                "vehicle",
+                QueryFilter::default().exclude(PolyFlags::DOOR | PolyFlags::JUMP),
+            )
+            .preset(
+                "amphibious",
+                QueryFilter::default().area_cost(1, 0.5).unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_presets() {
+        let presets = presets();
+        assert_eq!(
+            presets.names().collect::<Vec<_>>(),
+            vec!["amphibious", "infantry", "vehicle"]
+        );
+
+        // Clones share the filters
+        let shared = presets.clone();
+        assert!(Arc::ptr_eq(
+            presets.get("vehicle").unwrap(),
+            shared.get("vehicle").unwrap()
+        ));
+        assert!(presets.require("tank").is_err());
+
+        let err = FilterPresets::builder()
+            .preset("infantry", QueryFilter::default())
+            .preset("infantry", QueryFilter::default())
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("defined twice"));
+    }
+
+    #[test]
+    fn test_use_preset() {
+        let mut q = RecastQuery::new_from_mesh(simple_mesh()).unwrap();
+        let presets = presets();
+
+        q.use_preset(&presets, "vehicle").unwrap();
+        assert_eq!(q.filter().exclude_flags, PolyFlags::DOOR | PolyFlags::JUMP);
+
+        let cost = q.with_filter(presets.get("amphibious").unwrap(), |q| {
+            q.filter().area_costs[1]
+        });
+        assert_eq!(cost, 0.5);
+        assert_eq!(q.filter().area_costs[1], 1.0);
+        assert_eq!(q.filter().exclude_flags, PolyFlags::DOOR | PolyFlags::JUMP);
+    }
+}
@@ -0,0 +1,180 @@
+//! Detour's proximity grid on its own, cheap 2D range queries over gameplay objects
+use crate::{Error, Point, Result};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// A hash grid of boxes on the xz plane with 16 bit ids, see `dtProximityGrid`.
+///
+/// The crowd fills one with its agents on each update, `Crowd::agents_within` queries it.
+/// The grid only keeps the ids: a query returns the items of the cells it covers, the caller
+/// tests them against their exact positions. Clear and refill it when the items move
+pub struct ProximityGrid {
+    grid: ptr::NonNull<c_void>,
+    cell_size: f32,
+}
+
+// The grid only owns its pool
+unsafe impl Send for ProximityGrid {}
+
+impl std::fmt::Debug for ProximityGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ProximityGrid")
+            .field("grid", &self.grid)
+            .field("cell_size", &self.cell_size)
+            .finish()
+    }
+}
+
+impl ProximityGrid {
+    /// A grid of `cell_size` cells with room for `pool_size` item cells, below 65535.
+    /// Each item takes one per cell it covers, the crowd uses 4 per agent and 3 agent radii cells
+    pub fn new(pool_size: usize, cell_size: f32) -> Result<ProximityGrid> {
+        let mut err = sys::RecastNavError::zeros();
+        let grid = unsafe {
+            sys::recastc_create_proximity_grid(
+                pool_size.min(i32::MAX as usize) as i32,
+                cell_size,
+                &mut err as *mut _,
+            )
+        };
+
+        match ptr::NonNull::new(grid as *mut c_void) {
+            Some(grid) => Ok(ProximityGrid { grid, cell_size }),
+            None => Err(Error::ProximityGridError(err.msg().to_string())),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Add the item `id` covering the circle of `radius` around `pos`
+    pub fn add(&mut self, id: u16, pos: Point, radius: f32) -> Result<()> {
+        let bounds = circle_bounds(pos, radius);
+        self.call(|grid, err| unsafe {
+            sys::recastc_proximity_grid_add_item(grid, id, bounds.as_ptr(), err)
+        })
+    }
+
+    /// The items in the cells the circle of `radius` around `pos` covers, each once.
+    /// They may be outside of the circle
+    pub fn query(&self, pos: Point, radius: f32) -> Result<Vec<u16>> {
+        let bounds = circle_bounds(pos, radius);
+        let mut ids = Vec::new();
+        let mut count = 0;
+
+        // Grown to the needed size when too small
+        loop {
+            let res = self.call(|grid, err| unsafe {
+                sys::recastc_proximity_grid_query_items(
+                    grid,
+                    bounds.as_ptr(),
+                    ids.as_mut_ptr(),
+                    ids.len() as i32,
+                    &mut count,
+                    err,
+                )
+            });
+            match res {
+                Err(Error::ProximityGridError(msg))
+                    if msg == "BUFFER_TOO_SMALL" && count as usize > ids.len() =>
+                {
+                    ids.resize(count as usize, 0)
+                }
+                res => {
+                    res?;
+                    ids.truncate(count as usize);
+                    return Ok(ids);
+                }
+            }
+        }
+    }
+
+    /// Number of items in the cell of `pos`
+    pub fn item_count_at(&self, pos: Point) -> Result<usize> {
+        let mut count = 0;
+        self.call(|grid, err| unsafe {
+            sys::recastc_proximity_grid_item_count_at(grid, pos.x(), pos.z(), &mut count, err)
+        })?;
+        Ok(count as usize)
+    }
+
+    /// Remove all the items
+    pub fn clear(&mut self) {
+        unsafe { sys::recastc_proximity_grid_clear(self.grid.as_ptr()) }
+    }
+
+    fn call<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(*const c_void, *mut sys::RecastNavError) -> i32,
+    {
+        let mut err = sys::RecastNavError::zeros();
+        if f(self.grid.as_ptr(), &mut err as *mut _) == 0 {
+            Err(Error::ProximityGridError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for ProximityGrid {
+    fn drop(&mut self) {
+        unsafe { sys::recastc_free_proximity_grid(self.grid.as_ptr()) }
+    }
+}
+
+/// Min x, min z, max x, max z
+fn circle_bounds(pos: Point, radius: f32) -> [f32; 4] {
+    [
+        pos.x() - radius,
+        pos.z() - radius,
+        pos.x() + radius,
+        pos.z() + radius,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_query() {
+        let mut grid = ProximityGrid::new(64, 2.0).unwrap();
+        grid.add(1, (1.0, 0.0, 1.0).into(), 0.5).unwrap();
+        grid.add(2, (3.0, 5.0, 1.0).into(), 0.5).unwrap();
+        grid.add(3, (30.0, 0.0, 30.0).into(), 0.5).unwrap();
+        // Over 4 cells, found once
+        grid.add(4, (-2.0, 0.0, -2.0).into(), 0.5).unwrap();
+
+        let mut near = grid.query((2.0, 0.0, 1.0).into(), 1.0).unwrap();
+        near.sort_unstable();
+        assert_eq!(near, vec![1, 2]);
+        assert_eq!(grid.query((-2.0, 0.0, -2.0).into(), 3.0).unwrap().len(), 2);
+        assert_eq!(grid.query((30.0, 0.0, 30.0).into(), 0.1).unwrap(), vec![3]);
+        assert!(grid
+            .query((100.0, 0.0, 0.0).into(), 1.0)
+            .unwrap()
+            .is_empty());
+        assert_eq!(grid.item_count_at((1.5, 0.0, 1.5).into()).unwrap(), 1);
+
+        grid.clear();
+        assert!(grid.query((2.0, 0.0, 1.0).into(), 1.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_full_pool() {
+        assert!(ProximityGrid::new(0, 1.0).is_err());
+        assert!(ProximityGrid::new(16, 0.0).is_err());
+
+        let mut grid = ProximityGrid::new(4, 1.0).unwrap();
+        // 3 by 3 cells
+        assert!(grid.add(1, (0.5, 0.0, 0.5).into(), 1.0).is_err());
+        grid.add(1, (0.5, 0.0, 0.5).into(), 0.4).unwrap();
+        for id in 2..5 {
+            grid.add(id, (0.5, 0.0, 0.5).into(), 0.4).unwrap();
+        }
+        assert!(grid.add(5, (0.5, 0.0, 0.5).into(), 0.4).is_err());
+        assert_eq!(grid.query((0.5, 0.0, 0.5).into(), 0.1).unwrap().len(), 4);
+        assert!(grid.add(6, (1e9, 0.0, 0.0).into(), 1.0).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Disable the polys agents cannot reach from their spawns, like the NavMeshPruneTool of RecastDemo
+use crate::{Point, PolyFlags, PolyRef, RecastQuery, Result};
+use std::collections::{HashSet, VecDeque};
+
+/// The outcome of `RecastQuery::prune_unreachable`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Number of polys reachable from the seeds
+    pub reachable: usize,
+    /// The polys flagged `PolyFlags::DISABLED`, with their previous flags
+    pub disabled: Vec<(PolyRef, PolyFlags)>,
+}
+
+impl RecastQuery {
+    /// The polys reachable from `seeds` through the polys passing the query filter,
+    /// off-mesh connections included. `r` are the half extents of the search box around a seed
+    pub fn reachable_polys(&self, seeds: &[Point], r: (f32, f32, f32)) -> Result<HashSet<PolyRef>> {
+        let mut visited = HashSet::new();
+        let mut open = VecDeque::new();
+        for &seed in seeds {
+            let (_, poly) = self.find_poly(seed, r)?;
+            if visited.insert(poly) {
+                open.push_back(poly);
+            }
+        }
+
+        while let Some(poly) = open.pop_front() {
+            for n in self.poly_neighbours(poly)? {
+                if visited.insert(n) {
+                    open.push_back(n);
+                }
+            }
+        }
+
+        Ok(visited)
+    }
+
+    /// Flag the polys not reachable from any seed with `PolyFlags::DISABLED`, e.g. rooftop slivers,
+    /// so the queries with a filter excluding it (the default one) no longer find them.
+    ///
+    /// The previous flags are in the report to undo the pruning with `set_poly_flags`.
+    pub fn prune_unreachable(
+        &mut self,
+        seeds: &[Point],
+        r: (f32, f32, f32),
+    ) -> Result<PruneReport> {
+        let reachable = self.reachable_polys(seeds, r)?;
+
+        let mut report = PruneReport {
+            reachable: reachable.len(),
+            disabled: Vec::new(),
+        };
+        for poly in self.polys()? {
+            if reachable.contains(&poly) {
+                continue;
+            }
+
+            let flags = self.poly_flags(poly)?;
+            self.set_poly_flags(poly, flags | PolyFlags::DISABLED)?;
+            report.disabled.push((poly, flags));
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, OffMeshConnection};
+
+    /// A ground plane and a disconnected rooftop above its corner
+    fn level() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                5.0, 5.0, 5.0, 10.0, 5.0, 5.0, 10.0, 5.0, 10.0, 5.0, 5.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prune_rooftop() {
+        let mut q = RecastQuery::build(&level(), &BuildConfig::default()).unwrap();
+        let roof = (7.5, 5.0, 7.5).into();
+        let ext = (0.5, 0.5, 0.5);
+        assert!(q.find_poly(roof, ext).is_ok());
+
+        let spawn = (2.0, 0.0, 2.0).into();
+        let report = q.prune_unreachable(&[spawn], ext).unwrap();
+        assert!(report.reachable > 0);
+        assert!(!report.disabled.is_empty());
+        assert!(q.find_poly(roof, ext).is_err());
+        assert!(q.find_poly(spawn, ext).is_ok());
+
+        // Undo
+        for &(poly, flags) in &report.disabled {
+            q.set_poly_flags(poly, flags).unwrap();
+        }
+        assert!(q.find_poly(roof, ext).is_ok());
+    }
+
+    #[test]
+    fn test_reachable_through_links() {
+        let mut geom = level();
+        geom.off_mesh_connections.push(OffMeshConnection::new(
+            (4.0, 0.0, 7.5).into(),
+            (6.5, 5.0, 7.5).into(),
+            0.5,
+        ));
+        let mut q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let all = q.polys().unwrap().len();
+
+        let report = q
+            .prune_unreachable(&[(2.0, 0.0, 2.0).into()], (0.5, 0.5, 0.5))
+            .unwrap();
+        assert_eq!(report.reachable, all);
+        assert!(report.disabled.is_empty());
+    }
+}
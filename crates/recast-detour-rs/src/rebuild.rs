@@ -0,0 +1,236 @@
+//! Tiles rebuilt on a worker thread while the game keeps querying the old ones, then swapped in at once
+use crate::recast::SysInput;
+use crate::tiled::build_tile;
+use crate::{
+    BuildConfig, DirtyTiles, Error, InputGeometry, RecastQuery, Result, TileData, TileGrid,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+/// The tiles of a finished rebuild, `None` for a tile without walkable surface anymore
+#[derive(Debug, Default)]
+pub struct RebuiltTiles {
+    pub tiles: Vec<((i32, i32), Option<TileData>)>,
+    /// The last mark of the `DirtyTiles` the rebuild covers, 0 for none
+    last_mark: u64,
+}
+
+/// A rebuild of tiles running on its own thread, dropping it cancels the rebuild
+#[derive(Debug)]
+pub struct BackgroundRebuild {
+    rx: mpsc::Receiver<Result<RebuiltTiles>>,
+    cancel: Arc<AtomicBool>,
+    result: Option<Result<RebuiltTiles>>,
+}
+
+impl BackgroundRebuild {
+    /// Start rebuilding the tiles of the grid from the geometry, shared with the game thread
+    pub fn spawn(
+        grid: TileGrid,
+        geometry: Arc<InputGeometry>,
+        config: BuildConfig,
+        tiles: Vec<(i32, i32)>,
+    ) -> BackgroundRebuild {
+        BackgroundRebuild::spawn_for_marks(grid, geometry, config, tiles, 0)
+    }
+
+    fn spawn_for_marks(
+        grid: TileGrid,
+        geometry: Arc<InputGeometry>,
+        config: BuildConfig,
+        tiles: Vec<(i32, i32)>,
+        last_mark: u64,
+    ) -> BackgroundRebuild {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancelled = cancel.clone();
+
+        thread::spawn(move || {
+            let build = || -> Result<RebuiltTiles> {
+                geometry.validate()?;
+                config.validate()?;
+
+                let sys_input = SysInput::new(&geometry);
+                let input = sys_input.as_sys();
+                let sys_config = config.as_sys();
+
+                let mut rebuilt = RebuiltTiles {
+                    tiles: Vec::new(),
+                    last_mark,
+                };
+                for (x, y) in tiles {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return Err(Error::BuildError("rebuild cancelled".into()));
+                    }
+                    let tile = build_tile(&input, &sys_config, &grid, x, y)?;
+                    rebuilt.tiles.push(((x, y), tile));
+                }
+                Ok(rebuilt)
+            };
+            // The receiver is gone when the rebuild was dropped
+            let _ = tx.send(build());
+        });
+
+        BackgroundRebuild {
+            rx,
+            cancel,
+            result: None,
+        }
+    }
+
+    /// Whether the rebuild is done, never blocks
+    pub fn is_finished(&mut self) -> bool {
+        if self.result.is_none() {
+            match self.rx.try_recv() {
+                Ok(result) => self.result = Some(result),
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.result = Some(Err(Error::BuildError("rebuild thread panicked".into())))
+                }
+            }
+        }
+        true
+    }
+
+    /// The result once finished, e.g. polled once per frame
+    pub fn try_take(&mut self) -> Option<Result<RebuiltTiles>> {
+        if self.is_finished() {
+            self.result.take()
+        } else {
+            None
+        }
+    }
+
+    /// Block until the rebuild is done
+    pub fn wait(mut self) -> Result<RebuiltTiles> {
+        if let Some(result) = self.result.take() {
+            return result;
+        }
+        self.rx
+            .recv()
+            .unwrap_or_else(|_| Err(Error::BuildError("rebuild thread panicked".into())))
+    }
+
+    /// Stop before the next tile, the result is then an error
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for BackgroundRebuild {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl DirtyTiles {
+    /// Rebuild every dirty tile on a worker thread. The tiles stay dirty until `swap_rebuilt`
+    /// swaps them in, so a failed or dropped rebuild loses nothing
+    pub fn rebuild_in_background(
+        &self,
+        geometry: Arc<InputGeometry>,
+        config: &BuildConfig,
+    ) -> BackgroundRebuild {
+        let tiles: Vec<_> = self.tiles().collect();
+        BackgroundRebuild::spawn_for_marks(
+            *self.grid(),
+            geometry,
+            config.clone(),
+            tiles,
+            self.last_mark(),
+        )
+    }
+
+    /// `RecastQuery::swap_tiles`, then the swapped tiles are not dirty anymore unless they were
+    /// marked again while the rebuild ran
+    pub fn swap_rebuilt(&mut self, q: &mut RecastQuery, rebuilt: RebuiltTiles) -> Result<usize> {
+        let last_mark = rebuilt.last_mark;
+        let tiles: Vec<_> = rebuilt.tiles.iter().map(|(coords, _)| *coords).collect();
+        let walkable = q.swap_tiles(rebuilt)?;
+        self.clean(tiles.into_iter(), last_mark);
+        Ok(walkable)
+    }
+}
+
+impl RecastQuery {
+    /// Replace the tiles with the rebuilt ones in one call between two frames, returns the
+    /// number of tiles with a walkable surface. All or nothing: on a failure the navmesh keeps
+    /// its previous tiles. The poly refs of the replaced tiles become invalid
+    pub fn swap_tiles(&mut self, rebuilt: RebuiltTiles) -> Result<usize> {
+        self.replace_tiles(rebuilt.tiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::{ConvexVolume, Point};
+
+    #[test]
+    fn test_background_rebuild() {
+        let config = BuildConfig::default();
+        let mut geom = plane(10.0);
+        let mut q = RecastQuery::build_tiled(&geom, &config, 16).unwrap();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let pos: Point = (5.0, 0.0, 5.0).into();
+        let ext = (0.2, 1.0, 0.2);
+
+        // Cut a hole in the middle
+        geom.volumes.push(ConvexVolume {
+            verts: vec![
+                (4.0, 0.0, 4.0).into(),
+                (6.0, 0.0, 4.0).into(),
+                (6.0, 0.0, 6.0).into(),
+                (4.0, 0.0, 6.0).into(),
+            ],
+            hmin: -1.0,
+            hmax: 1.0,
+            area: 0,
+        });
+        let mut dirty = DirtyTiles::new(grid);
+        dirty.mark(&config, (4.0, 0.0, 4.0).into(), (6.0, 0.0, 6.0).into());
+
+        let n = dirty.len();
+        let job = dirty.rebuild_in_background(Arc::new(geom.clone()), &config);
+        // Dropped before the swap, the tiles are still dirty
+        drop(job);
+        assert_eq!(dirty.len(), n);
+
+        let mut job = dirty.rebuild_in_background(Arc::new(geom), &config);
+        // The old navmesh answers while the rebuild runs
+        assert!(q.find_poly(pos, ext).is_ok());
+        // Changed again while the rebuild runs
+        let (tx, ty) = dirty.tiles().next().unwrap();
+        dirty.mark_tile(tx, ty);
+
+        let rebuilt = loop {
+            if let Some(result) = job.try_take() {
+                break result.unwrap();
+            }
+            thread::yield_now();
+        };
+        assert!(!rebuilt.tiles.is_empty());
+        dirty.swap_rebuilt(&mut q, rebuilt).unwrap();
+        assert!(q.find_poly(pos, ext).is_err());
+        assert_eq!(dirty.tiles().collect::<Vec<_>>(), vec![(tx, ty)]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let config = BuildConfig::default();
+        let geom = Arc::new(plane(10.0));
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let tiles: Vec<_> = grid.tiles().collect();
+
+        let job = BackgroundRebuild::spawn(grid, geom, config, tiles);
+        job.cancel();
+        // Cancelled before any tile, or done before the flag was seen
+        match job.wait() {
+            Ok(rebuilt) => assert_eq!(rebuilt.tiles.len(), grid.tiles().count()),
+            Err(e) => assert!(e.to_string().contains("cancelled")),
+        }
+    }
+}
@@ -0,0 +1,726 @@
+//! Build a navmesh from raw level geometry with the Recast pipeline
+use crate::off_mesh;
+use crate::{Error, OffMeshConnection, Point, PolyFlags, RecastQuery, Result, SOFT_AREA_FIRST};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Raw level geometry, walkable surfaces are found by the build
+#[derive(Debug, Default, Clone)]
+pub struct InputGeometry {
+    /// Vertices in world unit, length = 3 * Number of Vertices
+    pub vertices: Vec<f32>,
+    /// Indices, length = 3 * Number of Triangles
+    pub indices: Vec<u32>,
+    /// Area id per triangle, e.g. from the materials of the render mesh, or empty for area 0.
+    /// `NOT_WALKABLE` makes a triangle an obstacle, the slope still decides what is walkable
+    pub triangle_areas: Vec<u8>,
+    /// Volumes marking the walkable surface inside them with an area id
+    pub volumes: Vec<ConvexVolume>,
+    /// Links between points not connected by the walkable surface
+    pub off_mesh_connections: Vec<OffMeshConnection>,
+    /// Water bodies, the submerged walkable surface becomes shallow or deep water
+    pub water: Vec<WaterVolume>,
+}
+
+/// Maximum number of vertices of a `ConvexVolume`
+pub const MAX_CONVEX_VOLUME_VERTS: usize = 12;
+
+/// A convex prism, walkable spans inside it get `area` during the build
+#[derive(Debug, Clone)]
+pub struct ConvexVolume {
+    /// Outline in the xz-plane, y is ignored
+    pub verts: Vec<Point>,
+    /// Bottom of the prism in world unit
+    pub hmin: f32,
+    /// Top of the prism in world unit
+    pub hmax: f32,
+    /// Area id below `SOFT_AREA_FIRST`, 0 removes the walkable surface
+    pub area: u8,
+}
+
+impl ConvexVolume {
+    fn validate(&self) -> Result<()> {
+        if self.verts.len() < 3 || self.verts.len() > MAX_CONVEX_VOLUME_VERTS {
+            return Err(Error::BuildError(format!(
+                "convex volume must have between 3 and {} vertices, got {}",
+                MAX_CONVEX_VOLUME_VERTS,
+                self.verts.len()
+            )));
+        }
+        if self.area >= SOFT_AREA_FIRST {
+            return Err(Error::BuildError(format!(
+                "convex volume area {} is reserved for soft areas",
+                self.area
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn as_sys(&self) -> sys::RecastConvexVolume {
+        let mut verts = [0.0; MAX_CONVEX_VOLUME_VERTS * 3];
+        for (i, v) in self.verts.iter().enumerate() {
+            verts[i * 3..i * 3 + 3].copy_from_slice(&v.0);
+        }
+
+        sys::RecastConvexVolume {
+            verts,
+            nverts: self.verts.len() as i32,
+            hmin: self.hmin,
+            hmax: self.hmax,
+            area: self.area,
+        }
+    }
+}
+
+/// A body of water, see `BuildConfig::water` for the areas given to the submerged surface
+#[derive(Debug, Clone)]
+pub struct WaterVolume {
+    /// Convex outline in the xz-plane, empty for a water plane covering the whole level
+    pub outline: Vec<Point>,
+    /// Height of the water surface in world unit
+    pub surface: f32,
+}
+
+impl WaterVolume {
+    /// A water plane covering the whole level
+    pub fn plane(surface: f32) -> WaterVolume {
+        WaterVolume {
+            outline: vec![],
+            surface,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let n = self.outline.len();
+        if n != 0 && !(3..=MAX_CONVEX_VOLUME_VERTS).contains(&n) {
+            return Err(Error::BuildError(format!(
+                "water outline must be empty or have between 3 and {} vertices, got {}",
+                MAX_CONVEX_VOLUME_VERTS, n
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn as_sys(&self) -> sys::RecastWaterVolume {
+        let mut verts = [0.0; MAX_CONVEX_VOLUME_VERTS * 3];
+        for (i, v) in self.outline.iter().enumerate() {
+            verts[i * 3..i * 3 + 3].copy_from_slice(&v.0);
+        }
+
+        sys::RecastWaterVolume {
+            verts,
+            nverts: self.outline.len() as i32,
+            surface: self.surface,
+        }
+    }
+}
+
+/// The area of the triangles which are never walkable, see `InputGeometry::triangle_areas`
+pub const NOT_WALKABLE: u8 = sys::RECASTC_SPAN_NOT_WALKABLE;
+
+impl InputGeometry {
+    /// Set the area of every triangle from its index and vertices, fails for invalid
+    /// vertices or indices
+    pub fn assign_triangle_areas<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, [Point; 3]) -> u8,
+    {
+        self.validate_triangles()?;
+
+        let vertex = |i: u32| {
+            let i = i as usize * 3;
+            Point([self.vertices[i], self.vertices[i + 1], self.vertices[i + 2]])
+        };
+
+        self.triangle_areas = self
+            .indices
+            .chunks(3)
+            .enumerate()
+            .map(|(t, tri)| f(t, [vertex(tri[0]), vertex(tri[1]), vertex(tri[2])]))
+            .collect();
+        Ok(())
+    }
+
+    fn validate_triangles(&self) -> Result<()> {
+        if !self.vertices.len().is_multiple_of(3) {
+            return Err(Error::BuildError(
+                "vertices length is not a multiple of 3".into(),
+            ));
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(Error::BuildError(
+                "indices length is not a multiple of 3".into(),
+            ));
+        }
+
+        let n_verts = self.vertices.len() / 3;
+        if let Some(i) = self.indices.iter().find(|&&i| i as usize >= n_verts) {
+            return Err(Error::BuildError(format!(
+                "index {} out of bounds, only {} vertices",
+                i, n_verts
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        self.validate_triangles()?;
+
+        let n_tris = self.indices.len() / 3;
+        if !self.triangle_areas.is_empty() && self.triangle_areas.len() != n_tris {
+            return Err(Error::BuildError(format!(
+                "{} triangle areas for {} triangles",
+                self.triangle_areas.len(),
+                n_tris
+            )));
+        }
+        if let Some(a) = self
+            .triangle_areas
+            .iter()
+            .find(|&&a| a >= SOFT_AREA_FIRST && a != NOT_WALKABLE)
+        {
+            return Err(Error::BuildError(format!(
+                "triangle area {} is reserved for soft areas",
+                a
+            )));
+        }
+        for volume in &self.volumes {
+            volume.validate()?;
+        }
+        for water in &self.water {
+            water.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An `InputGeometry` converted for the C api, keeps the converted arrays alive
+pub(crate) struct SysInput<'a> {
+    geometry: &'a InputGeometry,
+    volumes: Vec<sys::RecastConvexVolume>,
+    off_mesh: Vec<sys::RecastOffMeshConnection>,
+    water: Vec<sys::RecastWaterVolume>,
+}
+
+impl<'a> SysInput<'a> {
+    pub(crate) fn new(geometry: &'a InputGeometry) -> SysInput<'a> {
+        SysInput {
+            geometry,
+            volumes: geometry.volumes.iter().map(|v| v.as_sys()).collect(),
+            off_mesh: off_mesh::sys_connections(&geometry.off_mesh_connections),
+            water: geometry.water.iter().map(|w| w.as_sys()).collect(),
+        }
+    }
+
+    pub(crate) fn as_sys(&self) -> sys::RecastBuildInput {
+        sys::RecastBuildInput {
+            verts: self.geometry.vertices.as_ptr(),
+            vert_count: (self.geometry.vertices.len() / 3) as u32,
+            tris: self.geometry.indices.as_ptr() as *const i32,
+            tri_count: (self.geometry.indices.len() / 3) as u32,
+            tri_areas: if self.geometry.triangle_areas.is_empty() {
+                ptr::null()
+            } else {
+                self.geometry.triangle_areas.as_ptr()
+            },
+            volumes: self.volumes.as_ptr(),
+            volume_count: self.volumes.len() as u32,
+            off_mesh_connections: self.off_mesh.as_ptr(),
+            off_mesh_connection_count: self.off_mesh.len() as u32,
+            water: self.water.as_ptr(),
+            water_count: self.water.len() as u32,
+        }
+    }
+}
+
+/// How the walkable area is partitioned into regions before polygonization
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Partition {
+    /// Best quality tessellation, slowest, the RecastDemo default
+    Watershed,
+    /// Fastest, may produce long thin polygons
+    Monotone,
+    /// Non-overlapping layers, required for tile cache builds
+    Layers,
+}
+
+impl Partition {
+    fn as_sys(self) -> i32 {
+        match self {
+            Partition::Watershed => sys::RECASTC_PARTITION_WATERSHED,
+            Partition::Monotone => sys::RECASTC_PARTITION_MONOTONE,
+            Partition::Layers => sys::RECASTC_PARTITION_LAYERS,
+        }
+    }
+}
+
+/// Parameters of the Recast build, see `rcConfig`
+///
+/// ```ignore
+/// let config = BuildConfig::default()
+///     .cells(0.2, 0.1)
+///     .agent(1.8, 0.4, 0.5, 50.0)
+///     .regions(4, 16);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BuildConfig {
+    /// Cell size in world unit
+    pub cell_size: f32,
+    /// Cell height in world unit
+    pub cell_height: f32,
+    /// Agent height in World Unit
+    pub walkable_height: f32,
+    /// Agent radius in World Unit
+    pub walkable_radius: f32,
+    /// Maximum ledge height the agent can climb in World Unit
+    pub walkable_climb: f32,
+    /// Maximum slope the agent can walk in degrees
+    pub walkable_slope_angle: f32,
+    /// Maximum contour edge length in World Unit, 0 for unlimited
+    pub max_edge_len: f32,
+    /// Maximum distance the simplified contour may deviate from the raw one, in cells
+    pub max_simplification_error: f32,
+    /// Regions smaller than `min_region_size * min_region_size` cells are removed
+    pub min_region_size: u32,
+    /// Regions smaller than `merge_region_size * merge_region_size` cells are merged into neighbours
+    pub merge_region_size: u32,
+    /// Maximum vertices per navmesh polygon, between 3 and 6
+    pub max_verts_per_poly: u32,
+    /// Detail mesh sampling distance in World Unit, 0 to disable height sampling
+    pub detail_sample_dist: f32,
+    /// Maximum distance the detail mesh may deviate from the heightfield in World Unit
+    pub detail_sample_max_error: f32,
+    /// Region partitioning algorithm
+    pub partition: Partition,
+    /// Let the agent step up onto low obstacles (curbs, stairs) next to walkable spans
+    pub filter_low_hanging_obstacles: bool,
+    /// Remove spans next to drops higher than `walkable_climb`, disable to keep intentional drop-downs
+    pub filter_ledge_spans: bool,
+    /// Remove spans without `walkable_height` clearance above them
+    pub filter_walkable_low_height_spans: bool,
+    /// Poly flags given to the polys of each area id
+    pub area_flags: [PolyFlags; 64],
+    /// Walkable surface submerged up to this depth in world unit is shallow water
+    pub shallow_water_depth: f32,
+    /// Area of the surface submerged up to `shallow_water_depth`
+    pub shallow_water_area: u8,
+    /// Area of the surface submerged deeper, 0 removes it from the navmesh
+    pub deep_water_area: u8,
+    /// Border rasterized around each tile of a tiled build in cells, 0 for the walkable radius and 3 cells
+    pub tile_border_size: u32,
+    /// Height difference in World Unit within which the border edges of neighbour tiles are linked,
+    /// 0 for `walkable_climb`
+    pub border_stitch_tolerance: f32,
+}
+
+impl Default for BuildConfig {
+    /// The defaults of RecastDemo
+    fn default() -> BuildConfig {
+        BuildConfig {
+            cell_size: 0.3,
+            cell_height: 0.2,
+            walkable_height: 2.0,
+            walkable_radius: 0.6,
+            walkable_climb: 0.9,
+            walkable_slope_angle: 45.0,
+            max_edge_len: 12.0,
+            max_simplification_error: 1.3,
+            min_region_size: 8,
+            merge_region_size: 20,
+            max_verts_per_poly: 6,
+            detail_sample_dist: 6.0 * 0.3,
+            detail_sample_max_error: 0.2,
+            partition: Partition::Watershed,
+            filter_low_hanging_obstacles: true,
+            filter_ledge_spans: true,
+            filter_walkable_low_height_spans: true,
+            area_flags: [PolyFlags::WALK; 64],
+            shallow_water_depth: 0.5,
+            shallow_water_area: 1,
+            deep_water_area: 2,
+            tile_border_size: 0,
+            border_stitch_tolerance: 0.0,
+        }
+    }
+}
+
+impl BuildConfig {
+    /// Rasterization cell size and height, the detail sample settings keep their ratio to the cells
+    pub fn cells(mut self, cell_size: f32, cell_height: f32) -> BuildConfig {
+        self.detail_sample_dist *= cell_size / self.cell_size;
+        self.detail_sample_max_error *= cell_height / self.cell_height;
+        self.cell_size = cell_size;
+        self.cell_height = cell_height;
+        self
+    }
+
+    /// Agent dimensions, slope in degrees
+    pub fn agent(mut self, height: f32, radius: f32, climb: f32, slope_angle: f32) -> BuildConfig {
+        self.walkable_height = height;
+        self.walkable_radius = radius;
+        self.walkable_climb = climb;
+        self.walkable_slope_angle = slope_angle;
+        self
+    }
+
+    /// Minimum and merge region sizes in cells
+    pub fn regions(mut self, min_size: u32, merge_size: u32) -> BuildConfig {
+        self.min_region_size = min_size;
+        self.merge_region_size = merge_size;
+        self
+    }
+
+    /// Contour simplification and polygon settings
+    pub fn polygonization(
+        mut self,
+        max_edge_len: f32,
+        max_simplification_error: f32,
+        max_verts_per_poly: u32,
+    ) -> BuildConfig {
+        self.max_edge_len = max_edge_len;
+        self.max_simplification_error = max_simplification_error;
+        self.max_verts_per_poly = max_verts_per_poly;
+        self
+    }
+
+    /// Detail mesh sampling distance and maximum error in world unit
+    pub fn detail(mut self, sample_dist: f32, sample_max_error: f32) -> BuildConfig {
+        self.detail_sample_dist = sample_dist;
+        self.detail_sample_max_error = sample_max_error;
+        self
+    }
+
+    /// Region partitioning algorithm
+    pub fn partition(mut self, partition: Partition) -> BuildConfig {
+        self.partition = partition;
+        self
+    }
+
+    /// Toggle the span filters run after rasterization
+    pub fn filters(
+        mut self,
+        low_hanging_obstacles: bool,
+        ledge_spans: bool,
+        walkable_low_height_spans: bool,
+    ) -> BuildConfig {
+        self.filter_low_hanging_obstacles = low_hanging_obstacles;
+        self.filter_ledge_spans = ledge_spans;
+        self.filter_walkable_low_height_spans = walkable_low_height_spans;
+        self
+    }
+
+    /// Poly flags of the polys with the area id, fails for areas from 64
+    pub fn area_flags(mut self, area: u8, flags: PolyFlags) -> Result<BuildConfig> {
+        let f = self
+            .area_flags
+            .get_mut(area as usize)
+            .ok_or_else(|| Error::BuildError(format!("area {} out of range", area)))?;
+        *f = flags;
+        Ok(self)
+    }
+
+    /// Depth and areas of the water, e.g. give the deep water area the `SWIM` flag with `area_flags`
+    pub fn water(mut self, shallow_depth: f32, shallow_area: u8, deep_area: u8) -> BuildConfig {
+        self.shallow_water_depth = shallow_depth;
+        self.shallow_water_area = shallow_area;
+        self.deep_water_area = deep_area;
+        self
+    }
+
+    /// Tile border in cells and border stitching tolerance in world unit of tiled builds, 0 for the defaults.
+    ///
+    /// A wider border gives each tile more of its neighbours' geometry so their border edges
+    /// agree, a larger tolerance links border edges at different heights, e.g. on steep terrain
+    pub fn tile_border(mut self, size: u32, stitch_tolerance: f32) -> BuildConfig {
+        self.tile_border_size = size;
+        self.border_stitch_tolerance = stitch_tolerance;
+        self
+    }
+
+    fn filter_flags(&self) -> i32 {
+        let mut flags = 0;
+        if self.filter_low_hanging_obstacles {
+            flags |= sys::RECASTC_FILTER_LOW_HANGING_OBSTACLES;
+        }
+        if self.filter_ledge_spans {
+            flags |= sys::RECASTC_FILTER_LEDGE_SPANS;
+        }
+        if self.filter_walkable_low_height_spans {
+            flags |= sys::RECASTC_FILTER_WALKABLE_LOW_HEIGHT_SPANS;
+        }
+        flags
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(self.cell_size > 0.0 && self.cell_height > 0.0) {
+            return Err(Error::BuildError(
+                "cell_size and cell_height must be positive".into(),
+            ));
+        }
+        if self.max_verts_per_poly < 3 || self.max_verts_per_poly > 6 {
+            return Err(Error::BuildError(format!(
+                "max_verts_per_poly must be between 3 and 6, got {}",
+                self.max_verts_per_poly
+            )));
+        }
+        if self.border_stitch_tolerance < 0.0 {
+            return Err(Error::BuildError(
+                "border_stitch_tolerance must not be negative".into(),
+            ));
+        }
+        if self.shallow_water_area >= SOFT_AREA_FIRST || self.deep_water_area >= SOFT_AREA_FIRST {
+            return Err(Error::BuildError(
+                "water areas are reserved for soft areas".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn as_sys(&self) -> sys::RecastBuildConfig {
+        sys::RecastBuildConfig {
+            cell_size: self.cell_size,
+            cell_height: self.cell_height,
+            walkable_slope_angle: self.walkable_slope_angle,
+            walkable_height: self.walkable_height,
+            walkable_radius: self.walkable_radius,
+            walkable_climb: self.walkable_climb,
+            max_edge_len: self.max_edge_len,
+            max_simplification_error: self.max_simplification_error,
+            min_region_size: self.min_region_size as i32,
+            merge_region_size: self.merge_region_size as i32,
+            max_verts_per_poly: self.max_verts_per_poly as i32,
+            detail_sample_dist: self.detail_sample_dist,
+            detail_sample_max_error: self.detail_sample_max_error,
+            partition_type: self.partition.as_sys(),
+            filter_flags: self.filter_flags(),
+            area_flags: {
+                let mut flags = [0; 64];
+                for (f, a) in flags.iter_mut().zip(self.area_flags.iter()) {
+                    *f = a.0;
+                }
+                flags
+            },
+            shallow_water_depth: self.shallow_water_depth,
+            shallow_water_area: self.shallow_water_area,
+            deep_water_area: self.deep_water_area,
+            tile_border_size: self.tile_border_size as i32,
+            border_stitch_tolerance: self.border_stitch_tolerance,
+        }
+    }
+}
+
+impl RecastQuery {
+    /// Build a navmesh from raw geometry and create a query for it
+    pub fn build(geometry: &InputGeometry, config: &BuildConfig) -> Result<RecastQuery> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_input = SysInput::new(geometry);
+        let input = sys_input.as_sys();
+        let config = config.as_sys();
+
+        let mut err = sys::RecastNavError::zeros();
+
+        let q = unsafe {
+            ptr::NonNull::new(sys::recastc_build_query(
+                &input as *const _,
+                &config as *const _,
+                &mut err as *mut _,
+            ) as *mut c_void)
+        };
+
+        let q = q.ok_or_else(|| Error::BuildError(err.msg().into_owned()))?;
+        Ok(RecastQuery::from_ptr(q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+
+    #[test]
+    fn test_build_plane() {
+        let q = RecastQuery::build(&plane(10.0), &BuildConfig::default()).unwrap();
+
+        let p = q
+            .find_path((2.0, 0.0, 2.0).into(), (8.0, 0.0, 8.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+
+        let end = p.last().unwrap();
+        assert!((end.x() - 8.0).abs() < 0.1);
+        assert!((end.z() - 8.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_triangle_areas() {
+        // Two strips meeting at x = 5
+        let mut geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 5.0, 0.0, 0.0, 10.0, 0.0, 0.0, //
+                0.0, 0.0, 10.0, 5.0, 0.0, 10.0, 10.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 4, 1, 0, 3, 4, 1, 5, 2, 1, 4, 5],
+            ..Default::default()
+        };
+        geom.assign_triangle_areas(
+            |_, [a, b, c]| {
+                if a.x() + b.x() + c.x() < 15.0 {
+                    3
+                } else {
+                    0
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(geom.triangle_areas, vec![3, 3, 0, 0]);
+
+        let ext = (0.2, 1.0, 0.2);
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        let (_, road) = q.find_poly((2.0, 0.0, 5.0).into(), ext).unwrap();
+        let (_, grass) = q.find_poly((8.0, 0.0, 5.0).into(), ext).unwrap();
+        assert_eq!(q.poly_area(road).unwrap(), 3);
+        assert_eq!(q.poly_area(grass).unwrap(), 0);
+
+        geom.triangle_areas[2..].copy_from_slice(&[NOT_WALKABLE; 2]);
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+        assert!(q.find_poly((8.0, 0.0, 5.0).into(), ext).is_err());
+
+        geom.triangle_areas.pop();
+        assert!(RecastQuery::build(&geom, &BuildConfig::default()).is_err());
+        geom.triangle_areas = vec![SOFT_AREA_FIRST; 4];
+        assert!(RecastQuery::build(&geom, &BuildConfig::default()).is_err());
+
+        geom.indices.push(6);
+        assert!(geom.assign_triangle_areas(|_, _| 0).is_err());
+        geom.indices.extend_from_slice(&[0, 1]);
+        assert!(geom.assign_triangle_areas(|_, _| 0).is_err());
+        assert_eq!(geom.triangle_areas, vec![SOFT_AREA_FIRST; 4]);
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = BuildConfig::default()
+            .cells(0.15, 0.1)
+            .agent(1.8, 0.4, 0.5, 50.0)
+            .polygonization(12.0, 1.3, 3);
+
+        assert_eq!(config.cell_size, 0.15);
+        assert_eq!(config.walkable_radius, 0.4);
+        assert_eq!(config.max_verts_per_poly, 3);
+        assert!((config.detail_sample_dist - 0.9).abs() < 1e-5);
+
+        RecastQuery::build(&plane(10.0), &config).unwrap();
+        assert!(RecastQuery::build(&plane(10.0), &config.polygonization(12.0, 1.3, 7)).is_err());
+    }
+
+    #[test]
+    fn test_build_partitions() {
+        for &partition in &[Partition::Watershed, Partition::Monotone, Partition::Layers] {
+            let config = BuildConfig::default().partition(partition);
+            let q = RecastQuery::build(&plane(10.0), &config).unwrap();
+            q.find_poly((5.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_filter_flags() {
+        let config = BuildConfig::default().filters(true, false, true);
+        assert_eq!(
+            config.filter_flags(),
+            sys::RECASTC_FILTER_LOW_HANGING_OBSTACLES | sys::RECASTC_FILTER_WALKABLE_LOW_HEIGHT_SPANS
+        );
+
+        RecastQuery::build(&plane(10.0), &config).unwrap();
+    }
+
+    #[test]
+    fn test_detail_height() {
+        // A ramp rising 2 units over 10, walkable with the default slope
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 2.0, 0.0, 10.0, 2.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+
+        let (p, poly) = q.find_poly((5.0, 1.0, 5.0).into(), (0.5, 2.0, 0.5)).unwrap();
+        let h = q.poly_height(poly, p).unwrap();
+        assert!((h - 1.0).abs() < 0.3, "height {}", h);
+    }
+
+    #[test]
+    fn test_convex_volume_area() {
+        let mut geom = plane(10.0);
+        geom.volumes.push(ConvexVolume {
+            verts: vec![
+                (4.0, 0.0, 4.0).into(),
+                (6.0, 0.0, 4.0).into(),
+                (6.0, 0.0, 6.0).into(),
+                (4.0, 0.0, 6.0).into(),
+            ],
+            hmin: -1.0,
+            hmax: 1.0,
+            area: 3,
+        });
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+
+        let (_, poly) = q.find_poly((5.0, 0.0, 5.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+        let (_, poly) = q.find_poly((1.0, 0.0, 1.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 0);
+
+        geom.volumes[0].area = SOFT_AREA_FIRST;
+        assert!(RecastQuery::build(&geom, &BuildConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_water_depth_areas() {
+        // A ramp from y = -2 at x = 0 up to y = 0 at x = 10
+        let mut geom = InputGeometry {
+            vertices: vec![
+                0.0, -2.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, -2.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        };
+        // A lake over the z < 5 half, surface at -0.5
+        geom.water.push(WaterVolume {
+            outline: vec![
+                (-1.0, 0.0, -1.0).into(),
+                (11.0, 0.0, -1.0).into(),
+                (11.0, 0.0, 5.0).into(),
+                (-1.0, 0.0, 5.0).into(),
+            ],
+            surface: -0.5,
+        });
+        let q = RecastQuery::build(&geom, &BuildConfig::default().water(0.5, 3, 4)).unwrap();
+
+        let area = |x: f32, z: f32| {
+            let y = -2.0 + 0.2 * x;
+            let (_, poly) = q.find_poly((x, y, z).into(), (0.1, 1.0, 0.1)).unwrap();
+            q.poly_area(poly).unwrap()
+        };
+        assert_eq!(area(2.0, 2.0), 4);
+        assert_eq!(area(6.2, 2.0), 3);
+        assert_eq!(area(9.0, 2.0), 0);
+        assert_eq!(area(2.0, 8.0), 0);
+    }
+
+    #[test]
+    fn test_build_invalid_index() {
+        let mut geom = plane(10.0);
+        geom.indices[5] = 4;
+
+        match RecastQuery::build(&geom, &BuildConfig::default()) {
+            Err(Error::BuildError(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
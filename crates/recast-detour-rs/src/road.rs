@@ -0,0 +1,128 @@
+//! Road networks painted onto the walkable surface during the build
+use crate::{ConvexVolume, InputGeometry, Point};
+
+/// A ribbon following a sampled center line, the walkable surface under it gets `area`
+#[derive(Debug, Clone)]
+pub struct RoadSpline {
+    /// Center line in world unit, sample curved splines densely enough
+    pub points: Vec<Point>,
+    /// Full width of the road in world unit
+    pub width: f32,
+    /// Vertical distance above and below the center line still marked
+    pub height: f32,
+    /// Area id of the road
+    pub area: u8,
+}
+
+impl RoadSpline {
+    pub fn new(points: Vec<Point>, width: f32, area: u8) -> RoadSpline {
+        RoadSpline {
+            points,
+            width,
+            height: 1.0,
+            area,
+        }
+    }
+
+    /// One quad per segment and an octagon at each inner point to fill the gaps at turns
+    pub fn to_volumes(&self) -> Vec<ConvexVolume> {
+        let hw = self.width * 0.5;
+        let mut volumes = vec![];
+
+        for seg in self.points.windows(2) {
+            let (a, b) = (seg[0], seg[1]);
+            let (dx, dz) = (b.x() - a.x(), b.z() - a.z());
+            let len = (dx * dx + dz * dz).sqrt();
+            if len < 1e-6 {
+                continue;
+            }
+            let (nx, nz) = (-dz / len * hw, dx / len * hw);
+
+            volumes.push(ConvexVolume {
+                verts: vec![
+                    (a.x() + nx, a.y(), a.z() + nz).into(),
+                    (b.x() + nx, b.y(), b.z() + nz).into(),
+                    (b.x() - nx, b.y(), b.z() - nz).into(),
+                    (a.x() - nx, a.y(), a.z() - nz).into(),
+                ],
+                hmin: a.y().min(b.y()) - self.height,
+                hmax: a.y().max(b.y()) + self.height,
+                area: self.area,
+            });
+        }
+
+        let n = self.points.len();
+        if n > 2 {
+            // Circumscribe the circle of radius hw
+            let r = hw / std::f32::consts::FRAC_PI_8.cos();
+            for p in &self.points[1..n - 1] {
+                let verts = (0..8)
+                    .map(|i| {
+                        let a = i as f32 * std::f32::consts::FRAC_PI_4;
+                        (p.x() + a.cos() * r, p.y(), p.z() + a.sin() * r).into()
+                    })
+                    .collect();
+
+                volumes.push(ConvexVolume {
+                    verts,
+                    hmin: p.y() - self.height,
+                    hmax: p.y() + self.height,
+                    area: self.area,
+                });
+            }
+        }
+
+        volumes
+    }
+}
+
+impl InputGeometry {
+    /// Mark the road on the walkable surface during the build
+    pub fn add_road(&mut self, road: &RoadSpline) {
+        self.volumes.extend(road.to_volumes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::{BuildConfig, RecastQuery};
+
+    #[test]
+    fn test_road_volumes() {
+        let road = RoadSpline::new(
+            vec![
+                (0.0, 0.0, 0.0).into(),
+                (4.0, 0.0, 0.0).into(),
+                (4.0, 0.0, 4.0).into(),
+                (4.0, 0.0, 4.0).into(),
+            ],
+            2.0,
+            5,
+        );
+        let volumes = road.to_volumes();
+
+        // 2 quads, the duplicated point is skipped, and 2 joints
+        assert_eq!(volumes.len(), 4);
+        assert_eq!(volumes[0].verts[0], Point::new((0.0, 0.0, 1.0)));
+        assert_eq!(volumes[0].verts[3], Point::new((0.0, 0.0, -1.0)));
+        assert_eq!(volumes[2].verts.len(), 8);
+    }
+
+    #[test]
+    fn test_build_road() {
+        let mut geom = plane(10.0);
+        geom.add_road(&RoadSpline::new(
+            vec![(0.0, 0.0, 5.0).into(), (10.0, 0.0, 5.0).into()],
+            2.0,
+            7,
+        ));
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+
+        let (_, poly) = q.find_poly((5.0, 0.0, 5.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 7);
+        let (_, poly) = q.find_poly((5.0, 0.0, 1.0).into(), (0.1, 1.0, 0.1)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 0);
+    }
+}
@@ -0,0 +1,737 @@
+//! Whole navmeshes saved to bytes, baked offline or in RecastDemo and loaded at startup without a build
+use crate::bake::{fnv1a, FNV_OFFSET};
+use crate::pack::{read_f32, read_u32};
+use crate::{build_info, BuildConfig, Error, NavMeshParams, RecastQuery, Result, TileData};
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::ptr;
+
+const NAVMESH_MAGIC: &[u8; 4] = b"RDNM";
+const NAVMESH_VERSION: u32 = 2;
+
+/// `NAVMESHSET_MAGIC` of RecastDemo, 'MSET' as an int
+const RECAST_DEMO_MAGIC: u32 = u32::from_be_bytes(*b"MSET");
+const RECAST_DEMO_VERSION: u32 = 1;
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn serialize_error(err: io::Error) -> Error {
+    Error::SerializeError(err.to_string())
+}
+
+pub(crate) fn read_params<R: Read>(r: &mut R) -> Result<NavMeshParams> {
+    (|| -> io::Result<NavMeshParams> {
+        Ok(NavMeshParams {
+            origin: [read_f32(r)?, read_f32(r)?, read_f32(r)?],
+            tile_width: read_f32(r)?,
+            tile_height: read_f32(r)?,
+            max_tiles: read_u32(r)?,
+            max_polys: read_u32(r)?,
+        })
+    })()
+    .map_err(serialize_error)
+}
+
+/// Split the next `len` bytes off `r`
+pub(crate) fn read_slice<'a>(r: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if len > r.len() {
+        return Err(Error::SerializeError("truncated tile data".into()));
+    }
+    let (data, rest) = r.split_at(len);
+    *r = rest;
+    Ok(data)
+}
+
+/// The saved navmesh the tiles of a query loaded in place point into
+pub(crate) struct InPlaceData(Vec<u8>);
+
+impl fmt::Debug for InPlaceData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InPlaceData({} bytes)", self.0.len())
+    }
+}
+
+/// The build parameters a navmesh was saved with by `RecastQuery::serialize_with_config`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SavedBuildParams {
+    pub cell_size: f32,
+    pub cell_height: f32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+    /// `BuildConfig::config_hash` of the whole config
+    pub config_hash: u64,
+}
+
+impl From<&BuildConfig> for SavedBuildParams {
+    fn from(config: &BuildConfig) -> SavedBuildParams {
+        SavedBuildParams {
+            cell_size: config.cell_size,
+            cell_height: config.cell_height,
+            walkable_height: config.walkable_height,
+            walkable_radius: config.walkable_radius,
+            walkable_climb: config.walkable_climb,
+            config_hash: config.config_hash(),
+        }
+    }
+}
+
+impl SavedBuildParams {
+    /// Fails when the navmesh was not built with `config`, naming the parameters that differ
+    pub fn check(&self, config: &BuildConfig) -> Result<()> {
+        let expected = SavedBuildParams::from(config);
+        let fields = [
+            ("cell_size", self.cell_size, expected.cell_size),
+            ("cell_height", self.cell_height, expected.cell_height),
+            (
+                "walkable_height",
+                self.walkable_height,
+                expected.walkable_height,
+            ),
+            (
+                "walkable_radius",
+                self.walkable_radius,
+                expected.walkable_radius,
+            ),
+            (
+                "walkable_climb",
+                self.walkable_climb,
+                expected.walkable_climb,
+            ),
+        ];
+        let differ: Vec<String> = fields
+            .iter()
+            .filter(|(_, saved, expected)| saved != expected)
+            .map(|(name, saved, expected)| format!("{} {} instead of {}", name, saved, expected))
+            .collect();
+
+        if !differ.is_empty() {
+            Err(Error::SerializeError(format!(
+                "baked with other build parameters: {}",
+                differ.join(", ")
+            )))
+        } else if self.config_hash != expected.config_hash {
+            Err(Error::SerializeError(
+                "baked with another build config".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl BuildConfig {
+    /// A hash of every field, stored with a navmesh to tell whether it was built with this config
+    pub fn config_hash(&self) -> u64 {
+        // As for `bake_key`, the debug output covers every field and prints floats exactly
+        fnv1a(FNV_OFFSET, format!("{:?}", self).as_bytes())
+    }
+}
+
+/// What `parse_serialized` reads, each tile as its ref and the range of its data in the bytes
+struct SavedNavMesh {
+    params: NavMeshParams,
+    build: Option<SavedBuildParams>,
+    tiles: Vec<(u64, Range<usize>)>,
+}
+
+fn read_build_params<R: Read>(r: &mut R) -> io::Result<Option<SavedBuildParams>> {
+    let has_build = read_u32(r)? != 0;
+    let build = SavedBuildParams {
+        cell_size: read_f32(r)?,
+        cell_height: read_f32(r)?,
+        walkable_height: read_f32(r)?,
+        walkable_radius: read_f32(r)?,
+        walkable_climb: read_f32(r)?,
+        config_hash: read_u64(r)?,
+    };
+    Ok(if has_build { Some(build) } else { None })
+}
+
+/// The navmesh saved by `serialize`, once its version and checksum are checked
+fn parse_serialized(bytes: &[u8]) -> Result<SavedNavMesh> {
+    let info = build_info();
+
+    if bytes.len() < 8 || &bytes[..4] != NAVMESH_MAGIC {
+        return Err(Error::SerializeError("not a serialized navmesh".into()));
+    }
+    let version = read_u32(&mut &bytes[4..]).map_err(serialize_error)?;
+    if version != NAVMESH_VERSION {
+        return Err(Error::SerializeError(format!(
+            "navmesh saved in format version {}, this build reads version {}, save it again",
+            version, NAVMESH_VERSION
+        )));
+    }
+
+    // Checked before anything else is read, a stale or damaged file fails here
+    if bytes.len() < 16 + 8 {
+        return Err(Error::SerializeError("truncated navmesh".into()));
+    }
+    let (mut body, checksum) = bytes.split_at(bytes.len() - 8);
+    if read_u64(&mut &checksum[..]).map_err(serialize_error)? != fnv1a(FNV_OFFSET, body) {
+        return Err(Error::SerializeError(
+            "checksum mismatch, the navmesh data is corrupt or truncated".into(),
+        ));
+    }
+
+    let total = body.len();
+    let r = &mut body;
+    *r = &r[8..];
+    let poly_ref_bits = read_u32(r).map_err(serialize_error)?;
+    let little_endian = read_u32(r).map_err(serialize_error)? != 0;
+    if poly_ref_bits != info.poly_ref_bits || little_endian != info.little_endian {
+        return Err(Error::SerializeError(format!(
+            "saved with {} bit poly refs and {} endian tiles",
+            poly_ref_bits,
+            if little_endian { "little" } else { "big" }
+        )));
+    }
+
+    let build = read_build_params(r).map_err(serialize_error)?;
+    let params = read_params(r)?;
+    let n = read_u32(r).map_err(serialize_error)?;
+    let mut tiles = vec![];
+    for _ in 0..n {
+        let tile_ref = read_u64(r).map_err(serialize_error)?;
+        let len = read_u32(r).map_err(serialize_error)? as usize;
+        let start = total - r.len();
+        read_slice(r, len)?;
+        tiles.push((tile_ref, start..start + len));
+    }
+
+    if !r.is_empty() {
+        return Err(Error::SerializeError(format!(
+            "{} bytes after the last tile",
+            r.len()
+        )));
+    }
+    Ok(SavedNavMesh {
+        params,
+        build,
+        tiles,
+    })
+}
+
+/// The build parameters saved with a navmesh, None when it was saved by `serialize`.
+/// The version and checksum of the whole data are checked, the tiles are not
+pub fn serialized_build_params(bytes: &[u8]) -> Result<Option<SavedBuildParams>> {
+    Ok(parse_serialized(bytes)?.build)
+}
+
+impl RecastQuery {
+    /// The navmesh with all its tiles, loaded back by `from_serialized`.
+    ///
+    /// Each tile keeps its ref, so the poly refs stored along with the navmesh stay valid after
+    /// loading. The poly flags and areas are saved as they are now, the query filter is not.
+    /// Layout (little endian): magic, version, poly ref bits, 1 for little endian tile data,
+    /// the build parameters (u32 1 when saved, the sizes of `SavedBuildParams` and the u64
+    /// config hash, all 0 here), the `NavMeshParams`, the tile count then each tile as its u64
+    /// ref, u32 length + data, and last the u64 FNV-1a checksum of all the bytes before it
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        self.serialize_build(None)
+    }
+
+    /// `serialize` with the parameters of the config the navmesh was built with, for
+    /// `from_serialized_for` to refuse the file once the config changed
+    pub fn serialize_with_config(&self, config: &BuildConfig) -> Result<Vec<u8>> {
+        self.serialize_build(Some(SavedBuildParams::from(config)))
+    }
+
+    fn serialize_build(&self, build: Option<SavedBuildParams>) -> Result<Vec<u8>> {
+        let params = self.nav_mesh_params()?;
+        let info = build_info();
+
+        let mut out = NAVMESH_MAGIC.to_vec();
+        for v in &[
+            NAVMESH_VERSION,
+            info.poly_ref_bits,
+            info.little_endian as u32,
+            build.is_some() as u32,
+        ] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        let b = build.unwrap_or(SavedBuildParams {
+            cell_size: 0.0,
+            cell_height: 0.0,
+            walkable_height: 0.0,
+            walkable_radius: 0.0,
+            walkable_climb: 0.0,
+            config_hash: 0,
+        });
+        for f in &[
+            b.cell_size,
+            b.cell_height,
+            b.walkable_height,
+            b.walkable_radius,
+            b.walkable_climb,
+        ] {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        out.extend_from_slice(&b.config_hash.to_le_bytes());
+
+        for f in params
+            .origin
+            .iter()
+            .chain(&[params.tile_width, params.tile_height])
+        {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        out.extend_from_slice(&params.max_tiles.to_le_bytes());
+        out.extend_from_slice(&params.max_polys.to_le_bytes());
+
+        let tiles = self.saved_tiles(params.max_tiles)?;
+        out.extend_from_slice(&(tiles.len() as u32).to_le_bytes());
+        for (tile_ref, data) in tiles {
+            out.extend_from_slice(&tile_ref.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        let checksum = fnv1a(FNV_OFFSET, &out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        Ok(out)
+    }
+
+    /// A query over a navmesh saved by `serialize`, with the default filter.
+    /// Fails for data saved in another version of the format, with a wrong checksum, or by a
+    /// build with other poly refs or another tile data format
+    pub fn from_serialized(bytes: &[u8]) -> Result<RecastQuery> {
+        RecastQuery::from_saved(bytes, parse_serialized(bytes)?)
+    }
+
+    /// `from_serialized` for a navmesh that must have been built with `config`: fails when it
+    /// was saved without build parameters or with others, instead of loading stale data
+    pub fn from_serialized_for(bytes: &[u8], config: &BuildConfig) -> Result<RecastQuery> {
+        let saved = parse_serialized(bytes)?;
+        match &saved.build {
+            Some(build) => build.check(config)?,
+            None => {
+                return Err(Error::SerializeError(
+                    "saved without build parameters, see `serialize_with_config`".into(),
+                ))
+            }
+        }
+        RecastQuery::from_saved(bytes, saved)
+    }
+
+    fn from_saved(bytes: &[u8], saved: SavedNavMesh) -> Result<RecastQuery> {
+        let mut q = RecastQuery::create_tiled_with_params(&saved.params)?;
+        for (tile_ref, range) in saved.tiles {
+            q.restore_tile(TileData::from_bytes(&bytes[range])?, tile_ref)?;
+        }
+        Ok(q)
+    }
+
+    /// `from_serialized` without copying the tiles: the navmesh keeps `bytes` and its tiles are
+    /// the data where it is in them, with no allocation per tile. As with `from_serialized` the
+    /// tiles are checked before Detour reads them.
+    ///
+    /// The tile data must be aligned for the Detour structs, which the layout of `serialize`
+    /// gives with 32 bit poly refs. With 64 bit refs a tile may be misaligned and this fails,
+    /// `from_serialized` loads it. Removing a tile loaded in place is fine, `tile_data` copies
+    pub fn from_serialized_in_place(bytes: Vec<u8>) -> Result<RecastQuery> {
+        RecastQuery::try_from_serialized_in_place(bytes)?
+            .map_err(|_| Error::SerializeError("MISALIGNED_TILE_DATA".into()))
+    }
+
+    /// `from_serialized_in_place`, giving the bytes back when a tile is misaligned, e.g. to load
+    /// them with `from_serialized` instead
+    pub(crate) fn try_from_serialized_in_place(
+        bytes: Vec<u8>,
+    ) -> Result<std::result::Result<RecastQuery, Vec<u8>>> {
+        let saved = parse_serialized(&bytes)?;
+        // The alignment `recastc_validate_tile_data` checks, that of the links holding poly refs
+        let align = (build_info().poly_ref_bits as usize / 8).max(4);
+        let base = bytes.as_ptr() as usize;
+        if saved
+            .tiles
+            .iter()
+            .any(|(_, range)| !(base + range.start).is_multiple_of(align))
+        {
+            return Ok(Err(bytes));
+        }
+        let mut q = RecastQuery::create_tiled_with_params(&saved.params)?;
+
+        // The heap buffer does not move with the Vec, and is dropped after the navmesh
+        let data = q.in_place.insert(InPlaceData(bytes)).0.as_mut_ptr();
+        for (tile_ref, range) in saved.tiles {
+            let mut err = sys::RecastNavError::zeros();
+            let res = unsafe {
+                sys::recastc_restore_tile_in_place(
+                    q.q.as_ptr(),
+                    data.add(range.start),
+                    range.len() as i32,
+                    tile_ref,
+                    &mut err as *mut _,
+                )
+            };
+
+            if res == 0 {
+                return Err(Error::SerializeError(err.msg().to_string()));
+            }
+        }
+        Ok(Ok(q))
+    }
+
+    /// A query over a navmesh saved by RecastDemo, the "Save" of its samples writing the
+    /// `NavMeshSetHeader` with the `MSET` magic, with the default filter.
+    ///
+    /// The file is the structs of RecastDemo written as they are in memory: little endian as on
+    /// the machines RecastDemo runs on, with the tile refs as wide as the poly refs of this build.
+    /// The tiles keep their refs, like `from_serialized`, and the reading stops at a tile with no
+    /// ref or no data as in RecastDemo
+    pub fn from_recast_demo(mut bytes: &[u8]) -> Result<RecastQuery> {
+        let r = &mut bytes;
+        let ref_bits = build_info().poly_ref_bits;
+
+        let magic = read_u32(r).map_err(serialize_error)?;
+        if magic != RECAST_DEMO_MAGIC
+            || read_u32(r).map_err(serialize_error)? != RECAST_DEMO_VERSION
+        {
+            return Err(Error::SerializeError(
+                "not a RecastDemo navmesh set of this version".into(),
+            ));
+        }
+        let n = read_u32(r).map_err(serialize_error)?;
+        let params = read_params(r)?;
+        let mut q = RecastQuery::create_tiled_with_params(&params)?;
+
+        for _ in 0..n {
+            // NavMeshTileHeader, padded to the alignment of a 64 bit ref
+            let (tile_ref, len) = if ref_bits == 64 {
+                let tile_ref = read_u64(r).map_err(serialize_error)?;
+                let len = read_u32(r).map_err(serialize_error)?;
+                read_u32(r).map_err(serialize_error)?;
+                (tile_ref, len)
+            } else {
+                let tile_ref = read_u32(r).map_err(serialize_error)? as u64;
+                (tile_ref, read_u32(r).map_err(serialize_error)?)
+            };
+            if tile_ref == 0 || len == 0 {
+                break;
+            }
+            let data = read_slice(r, len as usize)?;
+            q.restore_tile(TileData::from_bytes(data)?, tile_ref)?;
+        }
+        Ok(q)
+    }
+
+    /// The refs and data of the tiles in slot order, all layers included
+    pub(crate) fn saved_tiles(&self, max_tiles: u32) -> Result<Vec<(u64, &[u8])>> {
+        let mut tiles = vec![];
+        for index in 0..max_tiles as i32 {
+            let mut tile_ref = 0;
+            let mut data: *const u8 = ptr::null();
+            let mut len = 0;
+            let mut err = sys::RecastNavError::zeros();
+
+            let res = unsafe {
+                sys::recastc_get_tile_at_index(
+                    self.q.as_ptr(),
+                    index,
+                    &mut tile_ref as *mut _,
+                    &mut data as *mut _,
+                    &mut len as *mut _,
+                    &mut err as *mut _,
+                )
+            };
+
+            if res == 0 {
+                return Err(Error::SerializeError(err.msg().to_string()));
+            }
+            if !data.is_null() {
+                // Valid as long as the tile is in the navmesh, the borrow of self keeps it there
+                tiles.push((tile_ref, unsafe {
+                    std::slice::from_raw_parts(data, len as usize)
+                }));
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// Add a saved tile back at its ref
+    fn restore_tile(&mut self, tile: TileData, tile_ref: u64) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+        // The navmesh owns the data from now on, even when adding fails
+        let (data, len) = tile.into_raw();
+
+        let res = unsafe {
+            sys::recastc_restore_tile(
+                self.q.as_ptr(),
+                data,
+                len as i32,
+                tile_ref,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::SerializeError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{rooms, rooms_geometry};
+    use crate::BuildConfig;
+
+    /// Offsets of the params and of the data of the first tile, after the header, the build
+    /// parameters, the params, the count, the ref and the length
+    const PARAMS: usize = 16 + 32;
+    const FIRST_TILE: usize = PARAMS + 28 + 4 + 8 + 4;
+
+    /// The checksum of edited bytes, for the edit to get past it
+    fn reseal(bytes: &mut [u8]) {
+        let body = bytes.len() - 8;
+        let checksum = fnv1a(FNV_OFFSET, &bytes[..body]);
+        bytes[body..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    fn msg(res: Result<RecastQuery>) -> String {
+        match res {
+            Err(Error::SerializeError(msg)) => msg,
+            res => panic!("{:?}", res),
+        }
+    }
+
+    fn assert_same_navmesh(a: &RecastQuery, b: &RecastQuery) {
+        assert_eq!(a.nav_mesh_params().unwrap(), b.nav_mesh_params().unwrap());
+        assert_eq!(a.polys().unwrap(), b.polys().unwrap());
+        let (start, end) = ((1.0, 0.0, 1.0).into(), (23.0, 0.0, 9.0).into());
+        assert_eq!(
+            a.find_path(start, end, (0.5, 1.0, 0.5)).unwrap(),
+            b.find_path(start, end, (0.5, 1.0, 0.5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_single_mesh_roundtrip() {
+        let q = rooms();
+        let bytes = q.serialize().unwrap();
+        assert_eq!(&bytes[..4], b"RDNM");
+
+        let loaded = RecastQuery::from_serialized(&bytes).unwrap();
+        assert_same_navmesh(&q, &loaded);
+        assert_eq!(loaded.serialize().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_tiled_keeps_poly_refs() {
+        let mut q =
+            RecastQuery::build_tiled(&rooms_geometry(), &BuildConfig::default(), 32).unwrap();
+        let coords = q.tile_coords().unwrap();
+        assert!(coords.len() > 2);
+
+        // A tile added again gets a new salt, and a removed one leaves a free slot
+        let (tx, ty) = coords[0];
+        let tile = q.tile_data(tx, ty).unwrap();
+        q.remove_tile(tx, ty).unwrap();
+        q.add_tile(tile).unwrap();
+        let (gx, gy) = coords[1];
+        q.remove_tile(gx, gy).unwrap();
+
+        let loaded = RecastQuery::from_serialized(&q.serialize().unwrap()).unwrap();
+        assert_eq!(loaded.tile_coords().unwrap().len(), coords.len() - 1);
+        assert_eq!(loaded.polys().unwrap(), q.polys().unwrap());
+        assert_eq!(
+            loaded.tile_polys(tx, ty).unwrap(),
+            q.tile_polys(tx, ty).unwrap()
+        );
+    }
+
+    /// What RecastDemo `saveAll` writes for the navmesh
+    fn recast_demo_bytes(q: &RecastQuery) -> Vec<u8> {
+        let params = q.nav_mesh_params().unwrap();
+        let tiles = q.saved_tiles(params.max_tiles).unwrap();
+        let mut out = vec![];
+        for v in &[RECAST_DEMO_MAGIC, RECAST_DEMO_VERSION, tiles.len() as u32] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        // The params and a 32 bit ref are the serialized ones
+        let bytes = q.serialize().unwrap();
+        out.extend_from_slice(&bytes[PARAMS..PARAMS + 28]);
+        for (tile_ref, data) in tiles {
+            out.extend_from_slice(&(tile_ref as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    #[test]
+    fn test_recast_demo_set() {
+        let mut q =
+            RecastQuery::build_tiled(&rooms_geometry(), &BuildConfig::default(), 32).unwrap();
+        let (tx, ty) = q.tile_coords().unwrap()[1];
+        q.remove_tile(tx, ty).unwrap();
+        let mut bytes = recast_demo_bytes(&q);
+        assert_eq!(&bytes[..4], b"TESM");
+
+        let loaded = RecastQuery::from_recast_demo(&bytes).unwrap();
+        assert_eq!(
+            loaded.nav_mesh_params().unwrap(),
+            q.nav_mesh_params().unwrap()
+        );
+        assert_eq!(loaded.polys().unwrap(), q.polys().unwrap());
+
+        // A set of a single mesh, with nothing after the tiles
+        let single = rooms();
+        assert_same_navmesh(
+            &single,
+            &RecastQuery::from_recast_demo(&recast_demo_bytes(&single)).unwrap(),
+        );
+
+        assert!(RecastQuery::from_serialized(&bytes).is_err());
+        assert!(RecastQuery::from_recast_demo(&single.serialize().unwrap()).is_err());
+        bytes.truncate(bytes.len() - 1);
+        assert!(RecastQuery::from_recast_demo(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let q = rooms();
+        let bytes = q.serialize().unwrap();
+
+        assert!(RecastQuery::from_serialized(&[]).is_err());
+        assert!(RecastQuery::from_serialized(&bytes[..bytes.len() - 1]).is_err());
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(RecastQuery::from_serialized(&longer).is_err());
+
+        let mut other_refs = bytes.clone();
+        other_refs[8..12].copy_from_slice(&64u32.to_le_bytes());
+        reseal(&mut other_refs);
+        assert_eq!(
+            msg(RecastQuery::from_serialized(&other_refs)),
+            "saved with 64 bit poly refs and little endian tiles"
+        );
+
+        let mut corrupt = bytes;
+        corrupt[FIRST_TILE] ^= 0xff;
+        assert!(RecastQuery::from_serialized(&corrupt).is_err());
+    }
+
+    #[test]
+    fn test_versions_and_checksum() {
+        let q = rooms();
+        let bytes = q.serialize().unwrap();
+
+        // A file of the first version, without build parameters nor checksum
+        let mut v1 = bytes[..16].to_vec();
+        v1[4..8].copy_from_slice(&1u32.to_le_bytes());
+        v1.extend_from_slice(&bytes[PARAMS..bytes.len() - 8]);
+        assert_eq!(
+            msg(RecastQuery::from_serialized(&v1)),
+            "navmesh saved in format version 1, this build reads version 2, save it again"
+        );
+        assert_eq!(
+            msg(RecastQuery::from_serialized(b"MSET\x01\0\0\0")),
+            "not a serialized navmesh"
+        );
+
+        // A byte flipped anywhere is caught before the tiles are read
+        let checksum = "checksum mismatch, the navmesh data is corrupt or truncated";
+        for i in &[12, PARAMS + 4, FIRST_TILE + 100, bytes.len() - 1] {
+            let mut corrupt = bytes.clone();
+            corrupt[*i] ^= 0x10;
+            assert_eq!(msg(RecastQuery::from_serialized(&corrupt)), checksum);
+            assert_eq!(
+                msg(RecastQuery::from_serialized_in_place(corrupt)),
+                checksum
+            );
+        }
+        assert_eq!(
+            msg(RecastQuery::from_serialized(&bytes[..bytes.len() - 20])),
+            checksum
+        );
+    }
+
+    #[test]
+    fn test_build_params() {
+        let config = BuildConfig::default();
+        let q = RecastQuery::build(&rooms_geometry(), &config).unwrap();
+
+        let bytes = q.serialize_with_config(&config).unwrap();
+        let saved = serialized_build_params(&bytes).unwrap().unwrap();
+        assert_eq!(saved, SavedBuildParams::from(&config));
+        assert_same_navmesh(
+            &q,
+            &RecastQuery::from_serialized_for(&bytes, &config).unwrap(),
+        );
+        assert!(RecastQuery::from_serialized(&bytes).is_ok());
+
+        // Stale once the agent or any other part of the config changed
+        let wider = BuildConfig {
+            walkable_radius: config.walkable_radius * 2.0,
+            ..config.clone()
+        };
+        assert_eq!(
+            msg(RecastQuery::from_serialized_for(&bytes, &wider)),
+            format!(
+                "baked with other build parameters: walkable_radius {} instead of {}",
+                config.walkable_radius, wider.walkable_radius
+            )
+        );
+        let other = BuildConfig {
+            max_edge_len: config.max_edge_len + 1.0,
+            ..config.clone()
+        };
+        assert_ne!(other.config_hash(), config.config_hash());
+        assert_eq!(
+            msg(RecastQuery::from_serialized_for(&bytes, &other)),
+            "baked with another build config"
+        );
+
+        let plain = q.serialize().unwrap();
+        assert_eq!(serialized_build_params(&plain).unwrap(), None);
+        assert_eq!(
+            msg(RecastQuery::from_serialized_for(&plain, &config)),
+            "saved without build parameters, see `serialize_with_config`"
+        );
+    }
+
+    #[test]
+    fn test_in_place() {
+        let q = RecastQuery::build_tiled(&rooms_geometry(), &BuildConfig::default(), 32).unwrap();
+        let bytes = q.serialize().unwrap();
+
+        let mut loaded = RecastQuery::from_serialized_in_place(bytes.clone()).unwrap();
+        assert_same_navmesh(&q, &loaded);
+        assert_eq!(loaded.serialize().unwrap(), bytes);
+
+        // The tiles stay usable as they come and go
+        let (tx, ty) = loaded.tile_coords().unwrap()[0];
+        let tile = loaded.tile_data(tx, ty).unwrap();
+        loaded.remove_tile(tx, ty).unwrap();
+        loaded.add_tile(tile).unwrap();
+        assert_eq!(loaded.polys().unwrap().len(), q.polys().unwrap().len());
+    }
+
+    #[test]
+    fn test_corrupt_counts() {
+        let q = rooms();
+        let mut bytes = q.serialize().unwrap();
+
+        // `polyCount` of the header of the tile, past the magic, version, x, y, layer and user id
+        let poly_count = FIRST_TILE + 24;
+        bytes[poly_count..poly_count + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        reseal(&mut bytes);
+
+        assert_eq!(
+            msg(RecastQuery::from_serialized(&bytes)),
+            "INVALID_TILE_DATA"
+        );
+        assert_eq!(
+            msg(RecastQuery::from_serialized_in_place(bytes)),
+            "INVALID_TILE_DATA"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Agent states published by the simulation thread for readers on other threads, e.g. rendering
+use crate::Point;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+/// Position and velocity of an agent, in the order the agents are published
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct AgentState {
+    pub index: usize,
+    pub pos: Point,
+    pub vel: Point,
+}
+
+/// The agent states of one update
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot<T> {
+    /// Counts the publishes, 0 for the empty snapshot before the first one
+    pub frame: u64,
+    pub agents: Vec<T>,
+}
+
+/// Publishing side of a snapshot buffer, owned by the thread updating the agents
+#[derive(Debug)]
+pub struct SnapshotWriter<T> {
+    front: Arc<Mutex<Arc<Snapshot<T>>>>,
+    /// The previous snapshot, reused once no reader holds it anymore
+    back: Option<Arc<Snapshot<T>>>,
+    frame: u64,
+}
+
+/// Reading side of a snapshot buffer, readers never wait for an update to finish
+#[derive(Debug)]
+pub struct SnapshotReader<T> {
+    front: Arc<Mutex<Arc<Snapshot<T>>>>,
+}
+
+impl<T> Clone for SnapshotReader<T> {
+    fn clone(&self) -> SnapshotReader<T> {
+        SnapshotReader {
+            front: self.front.clone(),
+        }
+    }
+}
+
+/// A double-buffered snapshot: the writer fills the back buffer while readers hold the front one,
+/// the lock is only taken to swap the buffers
+pub fn snapshot_buffer<T>() -> (SnapshotWriter<T>, SnapshotReader<T>) {
+    let front = Arc::new(Mutex::new(Arc::new(Snapshot {
+        frame: 0,
+        agents: Vec::new(),
+    })));
+
+    (
+        SnapshotWriter {
+            front: front.clone(),
+            back: None,
+            frame: 1,
+        },
+        SnapshotReader { front },
+    )
+}
+
+impl<T> SnapshotWriter<T> {
+    /// Replace the snapshot seen by the readers
+    pub fn publish<I: IntoIterator<Item = T>>(&mut self, agents: I) {
+        let mut next = match self.back.take() {
+            Some(back) if Arc::strong_count(&back) == 1 => back,
+            _ => Arc::new(Snapshot {
+                frame: 0,
+                agents: Vec::new(),
+            }),
+        };
+
+        {
+            let snapshot = Arc::get_mut(&mut next).unwrap();
+            snapshot.frame = self.frame;
+            snapshot.agents.clear();
+            snapshot.agents.extend(agents);
+        }
+        self.frame += 1;
+
+        let mut front = self.front.lock().unwrap_or_else(|e| e.into_inner());
+        self.back = Some(mem::replace(&mut *front, next));
+    }
+}
+
+impl<T> SnapshotReader<T> {
+    /// The last published snapshot, it stays valid while the writer publishes new ones
+    pub fn latest(&self) -> Arc<Snapshot<T>> {
+        self.front.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_buffer() {
+        let (mut writer, reader) = snapshot_buffer();
+        assert!(reader.latest().agents.is_empty());
+
+        writer.publish(vec![AgentState {
+            index: 0,
+            pos: (1.0, 0.0, 1.0).into(),
+            vel: (1.0, 0.0, 0.0).into(),
+        }]);
+        let held = reader.latest();
+
+        writer.publish(vec![]);
+        writer.publish(vec![AgentState::default(); 2]);
+
+        // The held snapshot is not reused while it is read
+        assert_eq!(held.frame, 1);
+        assert_eq!(held.agents[0].pos, Point::new((1.0, 0.0, 1.0)));
+        assert_eq!(reader.latest().frame, 3);
+        assert_eq!(reader.clone().latest().agents.len(), 2);
+    }
+
+    #[test]
+    fn test_read_from_other_thread() {
+        let (mut writer, reader) = snapshot_buffer();
+
+        let handle = std::thread::spawn(move || {
+            let mut last = 0;
+            while last < 100 {
+                last = reader.latest().frame;
+            }
+            last
+        });
+
+        for i in 0..100 {
+            writer.publish(vec![i]);
+        }
+        assert_eq!(handle.join().unwrap(), 100);
+    }
+}
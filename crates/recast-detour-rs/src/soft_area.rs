@@ -0,0 +1,384 @@
+//! Temporary areas with a traversal cost that decays over time
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+
+/// First area id reserved for soft areas, areas `SOFT_AREA_FIRST..64` should not be used by the mesh
+pub const SOFT_AREA_FIRST: u8 = 48;
+/// Maximum number of soft areas active at the same time
+pub const MAX_SOFT_AREAS: usize = 64 - SOFT_AREA_FIRST as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SoftAreaId(u32);
+
+/// A circle agents avoid, its cost decays linearly to the cost of the areas it covers
+/// over `duration` seconds
+#[derive(Debug, Copy, Clone)]
+pub struct SoftArea {
+    pub center: Point,
+    pub radius: f32,
+    /// Multiplier of the cost of the covered areas when the area is created
+    pub cost: f32,
+    /// Lifetime in seconds
+    pub duration: f32,
+}
+
+#[derive(Debug)]
+struct ActiveArea {
+    id: SoftAreaId,
+    area: SoftArea,
+    age: f32,
+    /// The marked polys with their area before marking
+    polys: Vec<(PolyRef, u8)>,
+}
+
+impl ActiveArea {
+    /// The cost of the areas of the polys before marking, the most expensive one when they
+    /// differ. Looked up each time, their costs may have changed since
+    fn base_cost(&self, area_costs: &[f32; 64]) -> f32 {
+        if self.polys.is_empty() {
+            1.0
+        } else {
+            self.polys
+                .iter()
+                .map(|&(_, orig)| area_costs[orig as usize])
+                .fold(0.0, f32::max)
+        }
+    }
+
+    fn current_cost(&self, area_costs: &[f32; 64]) -> f32 {
+        let t = (self.age / self.area.duration).min(1.0);
+        self.base_cost(area_costs) * (1.0 + (self.area.cost - 1.0) * (1.0 - t))
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SoftAreas {
+    slots: Vec<Option<ActiveArea>>,
+    next_id: u32,
+}
+
+impl SoftAreas {
+    pub(crate) fn new() -> SoftAreas {
+        SoftAreas {
+            slots: (0..MAX_SOFT_AREAS).map(|_| None).collect(),
+            next_id: 0,
+        }
+    }
+
+    pub(crate) fn original_area(&self, poly: PolyRef, area: u8) -> u8 {
+        if area < SOFT_AREA_FIRST {
+            return area;
+        }
+
+        self.slots[(area - SOFT_AREA_FIRST) as usize]
+            .as_ref()
+            .and_then(|a| a.polys.iter().find(|(p, _)| *p == poly))
+            .map(|&(_, orig)| orig)
+            .unwrap_or(area)
+    }
+
+    /// The area of the most recent other soft area still covering the poly
+    fn covering_area(&self, poly: PolyRef, except: usize) -> Option<u8> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != except)
+            .filter_map(|(i, a)| a.as_ref().map(|a| (i, a)))
+            .filter(|(_, a)| a.polys.iter().any(|(p, _)| *p == poly))
+            .max_by_key(|(_, a)| a.id.0)
+            .map(|(i, _)| SOFT_AREA_FIRST + i as u8)
+    }
+}
+
+impl RecastQuery {
+    /// Add a soft area, the polys around its center are marked and folded into the query filter
+    /// of this query. The agents of a crowd use the copy of the filter in the crowd, see
+    /// `Crowd::add_soft_area`
+    pub fn add_soft_area(&mut self, area: SoftArea) -> Result<SoftAreaId> {
+        let slot = self
+            .soft_areas
+            .slots
+            .iter()
+            .position(|s| s.is_none())
+            .ok_or_else(|| Error::SoftAreaError("too many soft areas".into()))?;
+        let area_id = SOFT_AREA_FIRST + slot as u8;
+
+        let r = area.radius;
+        let polys = self
+            .polys_around_circle(area.center, r, (r, r, r))
+            .map_err(|err| Error::SoftAreaError(err.to_string()))?;
+
+        let mut marked = Vec::with_capacity(polys.len());
+        for poly in polys {
+            let current = self.poly_area(poly)?;
+            let orig = self.soft_areas.original_area(poly, current);
+            self.set_poly_area(poly, area_id)?;
+            marked.push((poly, orig));
+        }
+
+        let id = SoftAreaId(self.soft_areas.next_id);
+        self.soft_areas.next_id += 1;
+
+        let active = ActiveArea {
+            id,
+            area,
+            age: 0.0,
+            polys: marked,
+        };
+        self.set_area_cost(area_id, active.current_cost(&self.filter.area_costs))?;
+        self.soft_areas.slots[slot] = Some(active);
+
+        Ok(id)
+    }
+
+    /// Remove a soft area before it expires
+    pub fn remove_soft_area(&mut self, id: SoftAreaId) -> Result<()> {
+        let slot = self
+            .soft_areas
+            .slots
+            .iter()
+            .position(|s| s.as_ref().map(|a| a.id) == Some(id))
+            .ok_or_else(|| Error::SoftAreaError("unknown soft area".into()))?;
+
+        self.release_soft_area(slot)
+    }
+
+    /// Advance the soft areas by `dt` seconds, decaying their costs and removing expired ones
+    pub fn update_soft_areas(&mut self, dt: f32) -> Result<()> {
+        for slot in 0..MAX_SOFT_AREAS {
+            let area_costs = &self.filter.area_costs;
+            let cost = match &mut self.soft_areas.slots[slot] {
+                Some(active) => {
+                    active.age += dt;
+                    if active.age >= active.area.duration {
+                        None
+                    } else {
+                        Some(active.current_cost(area_costs))
+                    }
+                }
+                None => continue,
+            };
+
+            match cost {
+                Some(cost) => self.set_area_cost(SOFT_AREA_FIRST + slot as u8, cost)?,
+                None => self.release_soft_area(slot)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of active soft areas
+    pub fn soft_area_count(&self) -> usize {
+        self.soft_areas.slots.iter().filter(|s| s.is_some()).count()
+    }
+
+    fn release_soft_area(&mut self, slot: usize) -> Result<()> {
+        let area_id = SOFT_AREA_FIRST + slot as u8;
+        let polys = match &self.soft_areas.slots[slot] {
+            Some(active) => active.polys.clone(),
+            None => return Ok(()),
+        };
+
+        for (poly, orig) in polys {
+            // Polys of removed tiles are gone
+            match self.poly_area(poly) {
+                Ok(area) if area == area_id => {}
+                _ => continue,
+            }
+
+            let area = self.soft_areas.covering_area(poly, slot).unwrap_or(orig);
+            self.set_poly_area(poly, area)?;
+        }
+
+        self.soft_areas.slots[slot] = None;
+        self.set_area_cost(area_id, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane_query;
+    use crate::{BuildConfig, Crowd, CrowdAgentParams, InputGeometry};
+
+    /// A square with a hole in the middle, the left and right sides are joined both above and
+    /// below the hole
+    fn ring() -> RecastQuery {
+        let mut geom = InputGeometry::default();
+        for &(x0, z0, x1, z1) in &[
+            (0.0, 0.0, 12.0, 4.0),
+            (0.0, 8.0, 12.0, 12.0),
+            (0.0, 4.0, 4.0, 8.0),
+            (8.0, 4.0, 12.0, 8.0),
+        ] {
+            let i = geom.vertices.len() as u32 / 3;
+            geom.vertices
+                .extend_from_slice(&[x0, 0.0, z0, x1, 0.0, z0, x1, 0.0, z1, x0, 0.0, z1]);
+            geom.indices
+                .extend_from_slice(&[i, i + 2, i + 1, i, i + 3, i + 2]);
+        }
+        RecastQuery::build(&geom, &BuildConfig::default()).unwrap()
+    }
+
+    fn area(z: f32) -> SoftArea {
+        SoftArea {
+            center: Point::new((6.0, 0.0, z)),
+            radius: 1.5,
+            cost: 100.0,
+            duration: 10.0,
+        }
+    }
+
+    const EXTENTS: (f32, f32, f32) = (1.0, 1.0, 1.0);
+
+    fn poly_at(q: &RecastQuery, x: f32, z: f32) -> PolyRef {
+        q.find_poly(Point::new((x, 0.0, z)), EXTENTS).unwrap().1
+    }
+
+    #[test]
+    fn test_soft_area_decay() {
+        let mut area_costs = [1.0; 64];
+        area_costs[3] = 2.0;
+        let active = ActiveArea {
+            id: SoftAreaId(0),
+            area: SoftArea {
+                center: Point::new((0.0, 0.0, 0.0)),
+                radius: 1.0,
+                cost: 11.0,
+                duration: 10.0,
+            },
+            age: 5.0,
+            polys: vec![(1, 3)],
+        };
+
+        assert_eq!(active.current_cost(&area_costs), 12.0);
+    }
+
+    #[test]
+    fn test_soft_area_over_expensive_area() {
+        let mut q = plane_query();
+        q.set_area_cost(0, 5.0).unwrap();
+
+        q.add_soft_area(SoftArea {
+            center: Point::new((5.0, 0.0, 5.0)),
+            radius: 1.0,
+            cost: 3.0,
+            duration: 10.0,
+        })
+        .unwrap();
+        assert_eq!(q.filter.area_costs[SOFT_AREA_FIRST as usize], 15.0);
+
+        // Never cheaper than the area under it
+        q.update_soft_areas(9.99).unwrap();
+        assert!(q.filter.area_costs[SOFT_AREA_FIRST as usize] >= 5.0);
+    }
+
+    #[test]
+    fn test_base_cost_changed_after_add() {
+        let mut q = plane_query();
+        q.add_soft_area(SoftArea {
+            center: Point::new((5.0, 0.0, 5.0)),
+            radius: 1.0,
+            cost: 3.0,
+            duration: 10.0,
+        })
+        .unwrap();
+        assert_eq!(q.filter.area_costs[SOFT_AREA_FIRST as usize], 3.0);
+
+        q.set_area_cost(0, 5.0).unwrap();
+        q.update_soft_areas(0.0).unwrap();
+        assert_eq!(q.filter.area_costs[SOFT_AREA_FIRST as usize], 15.0);
+    }
+
+    #[test]
+    fn test_expired_area_restores_polys() {
+        let mut q = plane_query();
+        let poly = poly_at(&q, 5.0, 5.0);
+        q.set_poly_area(poly, 3).unwrap();
+
+        q.add_soft_area(area(5.0)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), SOFT_AREA_FIRST);
+
+        q.update_soft_areas(5.0).unwrap();
+        assert_eq!(q.soft_area_count(), 1);
+        q.update_soft_areas(5.0).unwrap();
+        assert_eq!(q.soft_area_count(), 0);
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+        assert_eq!(q.filter.area_costs[SOFT_AREA_FIRST as usize], 1.0);
+    }
+
+    #[test]
+    fn test_removed_area_restores_polys() {
+        let mut q = plane_query();
+        let poly = poly_at(&q, 5.0, 5.0);
+        q.set_poly_area(poly, 3).unwrap();
+
+        let id = q.add_soft_area(area(5.0)).unwrap();
+        q.remove_soft_area(id).unwrap();
+        assert_eq!(q.soft_area_count(), 0);
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+        assert_eq!(q.filter.area_costs[SOFT_AREA_FIRST as usize], 1.0);
+        assert!(q.remove_soft_area(id).is_err());
+    }
+
+    #[test]
+    fn test_overlapping_areas() {
+        let mut q = plane_query();
+        let poly = poly_at(&q, 5.0, 5.0);
+        q.set_poly_area(poly, 3).unwrap();
+
+        let first = q.add_soft_area(area(5.0)).unwrap();
+        let second = q.add_soft_area(area(5.0)).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), SOFT_AREA_FIRST + 1);
+
+        // Back to the area still covering the poly, then to the one before both
+        q.remove_soft_area(second).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), SOFT_AREA_FIRST);
+        q.remove_soft_area(first).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+
+        let first = q.add_soft_area(area(5.0)).unwrap();
+        let second = q.add_soft_area(area(5.0)).unwrap();
+        q.remove_soft_area(first).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), SOFT_AREA_FIRST + 1);
+        q.remove_soft_area(second).unwrap();
+        assert_eq!(q.poly_area(poly).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_path_around_area() {
+        let (start, end) = (Point::new((2.0, 0.0, 6.0)), Point::new((10.0, 0.0, 6.0)));
+
+        let mut q = ring();
+        let below = q.add_soft_area(area(2.0)).unwrap();
+        let path = q.find_path(start, end, EXTENTS).unwrap();
+        assert!(path.iter().all(|p| p.z() > 5.0), "{:?}", path);
+
+        q.remove_soft_area(below).unwrap();
+        q.add_soft_area(area(10.0)).unwrap();
+        let path = q.find_path(start, end, EXTENTS).unwrap();
+        assert!(path.iter().all(|p| p.z() < 7.0), "{:?}", path);
+    }
+
+    #[test]
+    fn test_crowd_walks_around_area() {
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        // The crowd plans with its own copy of the filter
+        for &(z, above) in &[(2.0, true), (10.0, false)] {
+            let mut crowd = Crowd::new(ring(), 8, 0.6).unwrap();
+            crowd.add_soft_area(area(z)).unwrap();
+
+            let id = crowd
+                .add_agent(Point::new((2.0, 0.0, 6.0)), &params)
+                .unwrap();
+            crowd
+                .request_move_target(id, Point::new((10.0, 0.0, 6.0)))
+                .unwrap();
+            for _ in 0..100 {
+                crowd.update(0.1).unwrap();
+                let z = crowd.agent(id).unwrap().pos.z();
+                assert!(if above { z > 5.0 } else { z < 7.0 }, "{}", z);
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Snapping requested positions to the navmesh, e.g. for spawns and teleports
+use crate::{Error, Point, PolyRef, RecastQuery, Result};
+use rand::Rng;
+
+/// The wall nearest to a point, see `dtNavMeshQuery::findDistanceToWall`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WallHit {
+    /// `max_radius` when no wall is closer
+    pub distance: f32,
+    pub pos: Point,
+    /// From the wall to the queried point
+    pub normal: Point,
+}
+
+/// Where a ray along the navmesh stops, see `dtNavMeshQuery::raycast`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    /// Fraction of the ray to the wall hit, None when the ray reaches its end
+    pub t: Option<f32>,
+    pub pos: Point,
+    /// From the wall back into the poly, zero when the ray reaches its end
+    pub normal: Point,
+}
+
+/// How `RecastQuery::resolve_position` picks the navmesh point for a requested position
+pub trait SnapStrategy {
+    fn resolve(
+        &mut self,
+        q: &RecastQuery,
+        pos: Point,
+        r: (f32, f32, f32),
+    ) -> Result<(Point, PolyRef)>;
+}
+
+/// The closest point of the nearest poly
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Closest;
+
+/// The closest point with at least `radius` to the walls, e.g. so a large agent does not spawn in a wall
+#[derive(Debug, Copy, Clone)]
+pub struct WithClearance {
+    pub radius: f32,
+}
+
+/// A random point reachable from the nearest poly within `radius`, e.g. to spread a group spawning together
+#[derive(Debug, Clone)]
+pub struct RandomNearby<R> {
+    pub radius: f32,
+    pub rng: R,
+}
+
+impl SnapStrategy for Closest {
+    fn resolve(
+        &mut self,
+        q: &RecastQuery,
+        pos: Point,
+        r: (f32, f32, f32),
+    ) -> Result<(Point, PolyRef)> {
+        q.find_poly(pos, r)
+    }
+}
+
+fn dist_sqr_2d(a: Point, b: Point) -> f32 {
+    (a.x() - b.x()).powi(2) + (a.z() - b.z()).powi(2)
+}
+
+impl WithClearance {
+    /// Move the point away from the walls until it has clearance, a few steps at most
+    fn push_away(
+        &self,
+        q: &RecastQuery,
+        mut p: Point,
+        mut poly: PolyRef,
+    ) -> Option<(Point, PolyRef)> {
+        for _ in 0..4 {
+            let hit = q.distance_to_wall(poly, p, self.radius).ok()?;
+            if hit.distance >= self.radius {
+                return Some((p, poly));
+            }
+
+            // On the wall itself the normal is undefined, head for the poly center instead
+            let (nx, nz) = if hit.distance > 1e-4 {
+                (hit.normal.x(), hit.normal.z())
+            } else {
+                let c = q.poly_center(poly).ok()?;
+                let len = dist_sqr_2d(c, p).sqrt();
+                if len <= 1e-4 {
+                    return None;
+                }
+                ((c.x() - p.x()) / len, (c.z() - p.z()) / len)
+            };
+
+            // A little further than needed, the nearest wall may change on the way
+            let step = (self.radius - hit.distance) * 1.01;
+            let target: Point = (p.x() + nx * step, p.y(), p.z() + nz * step).into();
+            let (next, next_poly) = q
+                .find_poly(target, (self.radius, self.radius, self.radius))
+                .ok()?;
+            p = next;
+            poly = next_poly;
+        }
+
+        let hit = q.distance_to_wall(poly, p, self.radius).ok()?;
+        if hit.distance >= self.radius {
+            Some((p, poly))
+        } else {
+            None
+        }
+    }
+}
+
+impl SnapStrategy for WithClearance {
+    /// The polys within the horizontal extents of `r` are searched
+    fn resolve(
+        &mut self,
+        q: &RecastQuery,
+        pos: Point,
+        r: (f32, f32, f32),
+    ) -> Result<(Point, PolyRef)> {
+        let (p, poly) = q.find_poly(pos, r)?;
+        if q.distance_to_wall(poly, p, self.radius)?.distance >= self.radius {
+            return Ok((p, poly));
+        }
+
+        let mut best: Option<(f32, Point, PolyRef)> = None;
+        for candidate in q.polys_around_circle(pos, r.0.max(r.2), r)? {
+            let start = q.find_closest(pos, candidate)?;
+            if let Some((p, poly)) = self.push_away(q, start, candidate) {
+                let d = dist_sqr_2d(p, pos);
+                if !best.is_some_and(|(best_d, _, _)| best_d <= d) {
+                    best = Some((d, p, poly));
+                }
+            }
+        }
+
+        best.map(|(_, p, poly)| (p, poly)).ok_or_else(|| {
+            Error::FindPointError(format!("No point with {} clearance found", self.radius))
+        })
+    }
+}
+
+impl<R: Rng> SnapStrategy for RandomNearby<R> {
+    fn resolve(
+        &mut self,
+        q: &RecastQuery,
+        pos: Point,
+        r: (f32, f32, f32),
+    ) -> Result<(Point, PolyRef)> {
+        q.find_random_point_around_circle(pos, self.radius, r, &mut self.rng)
+    }
+}
+
+impl RecastQuery {
+    /// Snap a requested position to the navmesh with the given strategy,
+    /// `r` are the half extents of the search box around `pos`
+    pub fn resolve_position<S: SnapStrategy + ?Sized>(
+        &self,
+        pos: Point,
+        r: (f32, f32, f32),
+        strategy: &mut S,
+    ) -> Result<(Point, PolyRef)> {
+        strategy.resolve(self, pos, r)
+    }
+
+    /// `resolve_position` for many positions in one call, e.g. the move orders of a box selection.
+    /// Each position gets its own result, a position off the navmesh does not fail the others
+    pub fn resolve_positions<S: SnapStrategy + ?Sized>(
+        &self,
+        positions: &[Point],
+        r: (f32, f32, f32),
+        strategy: &mut S,
+    ) -> Vec<Result<(Point, PolyRef)>> {
+        positions
+            .iter()
+            .map(|&pos| strategy.resolve(self, pos, r))
+            .collect()
+    }
+
+    /// The nearest wall within `max_radius` of the point of `poly`
+    pub fn distance_to_wall(&self, poly: PolyRef, pos: Point, max_radius: f32) -> Result<WallHit> {
+        let mut distance = 0.0;
+        let mut hit_pos = [0.0; 3];
+        let mut hit_normal = [0.0; 3];
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_find_distance_to_wall(
+                self.q.as_ptr(),
+                poly,
+                pos.0.as_ptr(),
+                max_radius,
+                &mut distance,
+                hit_pos.as_mut_ptr(),
+                hit_normal.as_mut_ptr(),
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::FindPointError(err.msg().to_string()))
+        } else {
+            Ok(WallHit {
+                distance,
+                pos: Point(hit_pos),
+                normal: Point(hit_normal),
+            })
+        }
+    }
+
+    /// Cast a ray on the xz-plane from the point `start` of `poly` toward `end`, stopping at the
+    /// first wall, e.g. to check a shortcut or a line of sight over the navmesh
+    pub fn raycast(&self, poly: PolyRef, start: Point, end: Point) -> Result<RayHit> {
+        let mut t = 0.0;
+        let mut hit_pos = [0.0; 3];
+        let mut hit_normal = [0.0; 3];
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_raycast(
+                self.q.as_ptr(),
+                poly,
+                start.0.as_ptr(),
+                end.0.as_ptr(),
+                &mut t,
+                hit_pos.as_mut_ptr(),
+                hit_normal.as_mut_ptr(),
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::FindPointError(err.msg().to_string()))
+        } else {
+            Ok(RayHit {
+                t: Some(t).filter(|&t| t != f32::MAX),
+                pos: Point(hit_pos),
+                normal: Point(hit_normal),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn square() -> RecastQuery {
+        RecastQuery::new_from_mesh(crate::test_util::simple_mesh()).unwrap()
+    }
+
+    #[test]
+    fn test_closest_and_clearance() {
+        let q = square();
+        let pos = (0.5, 0.0, 5.0).into();
+        let r = (3.0, 1.0, 3.0);
+
+        let (p, _) = q.resolve_position(pos, r, &mut Closest).unwrap();
+        assert!(dist_sqr_2d(p, pos) < 1e-6);
+
+        let mut clearance = WithClearance { radius: 2.0 };
+        let (p, poly) = q.resolve_position(pos, r, &mut clearance).unwrap();
+        assert!(q.distance_to_wall(poly, p, 2.0).unwrap().distance >= 2.0);
+        assert!(p.x() >= 2.0 && p.x() < 2.5, "{:?}", p);
+        assert!((p.z() - 5.0).abs() < 0.5, "{:?}", p);
+
+        // Wider than the square
+        let mut clearance = WithClearance { radius: 6.0 };
+        assert!(q.resolve_position(pos, r, &mut clearance).is_err());
+    }
+
+    #[test]
+    fn test_random_nearby() {
+        let q = square();
+        let pos = (5.0, 0.0, 5.0).into();
+        let mut random = RandomNearby {
+            radius: 1.0,
+            rng: rand::rngs::StdRng::seed_from_u64(7),
+        };
+
+        let (a, _) = q
+            .resolve_position(pos, (0.5, 1.0, 0.5), &mut random)
+            .unwrap();
+        let (b, _) = q
+            .resolve_position(pos, (0.5, 1.0, 0.5), &mut random)
+            .unwrap();
+        assert_ne!(a, b);
+        // Detour picks a point of the polys touching the circle, both triangles here
+        assert!(a.x() >= 0.0 && a.x() <= 10.0);
+    }
+
+    #[test]
+    fn test_resolve_positions() {
+        let q = square();
+        let targets = [
+            (1.0, 0.0, 1.0).into(),
+            (50.0, 0.0, 50.0).into(),
+            (9.0, 0.0, 9.0).into(),
+        ];
+
+        let resolved = q.resolve_positions(&targets, (0.5, 1.0, 0.5), &mut Closest);
+        assert_eq!(resolved.len(), 3);
+        assert!(resolved[1].is_err());
+        let (p, _) = resolved[2].as_ref().unwrap();
+        assert!(dist_sqr_2d(*p, targets[2]) < 1e-6);
+        assert!(resolved[0].is_ok());
+    }
+}
@@ -0,0 +1,334 @@
+//! Tiles built separately, e.g. per streaming cell on different machines, stitched into one navmesh
+use crate::{Error, NavMeshParams, Point, PolyRef, RecastQuery, Result, TileData};
+
+/// Header of the Detour data of a tile, see `dtMeshHeader`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileHeader {
+    pub tx: i32,
+    pub ty: i32,
+    pub layer: i32,
+    /// Bounds of the tile in world unit
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub poly_count: u32,
+    pub vert_count: u32,
+    pub off_mesh_connection_count: u32,
+    /// Agent the tile is built for in world unit
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+}
+
+impl TileData {
+    pub fn header(&self) -> Result<TileHeader> {
+        tile_header(self.as_bytes())
+    }
+}
+
+/// The header of tile data borrowed from a navmesh
+pub(crate) fn tile_header(bytes: &[u8]) -> Result<TileHeader> {
+    let mut header = sys::RecastTileHeader::default();
+    let mut err = sys::RecastNavError::zeros();
+
+    let res = unsafe {
+        sys::recastc_get_tile_header(
+            bytes.as_ptr(),
+            bytes.len() as i32,
+            &mut header as *mut _,
+            &mut err as *mut _,
+        )
+    };
+
+    if res == 0 {
+        return Err(Error::BuildError(err.msg().to_string()));
+    }
+
+    Ok(TileHeader {
+        tx: header.tx,
+        ty: header.ty,
+        layer: header.layer,
+        bmin: header.bmin,
+        bmax: header.bmax,
+        poly_count: header.poly_count as u32,
+        vert_count: header.vert_count as u32,
+        off_mesh_connection_count: header.off_mesh_connection_count as u32,
+        walkable_height: header.walkable_height,
+        walkable_radius: header.walkable_radius,
+        walkable_climb: header.walkable_climb,
+    })
+}
+
+/// Tiles link with each other when they are built for the same agent
+pub(crate) fn check_same_agent(a: &TileHeader, b: &TileHeader) -> Result<()> {
+    if (a.walkable_height, a.walkable_radius, a.walkable_climb)
+        != (b.walkable_height, b.walkable_radius, b.walkable_climb)
+    {
+        return Err(Error::BuildError(format!(
+            "tile ({}, {}) is built for another agent than tile ({}, {})",
+            b.tx, b.ty, a.tx, a.ty
+        )));
+    }
+    Ok(())
+}
+
+/// Links from the polys of a tile to its neighbour tiles, what paths use to cross the seams
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SeamLinks {
+    /// By Detour side, 0 towards +x, 2 towards +z, 4 towards -x, 6 towards -z and the diagonals in between
+    pub sides: [u32; 8],
+}
+
+impl SeamLinks {
+    /// The links towards the neighbour at the tile offset, each of `dx` and `dz` in -1..=1
+    pub fn towards(&self, dx: i32, dz: i32) -> u32 {
+        let side = match (dx.signum(), dz.signum()) {
+            (1, 0) => 0,
+            (1, 1) => 1,
+            (0, 1) => 2,
+            (-1, 1) => 3,
+            (-1, 0) => 4,
+            (-1, -1) => 5,
+            (0, -1) => 6,
+            (1, -1) => 7,
+            _ => return 0,
+        };
+        self.sides[side]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.sides.iter().sum()
+    }
+}
+
+/// A poly edge on the border of its tile only partly linked to the tile next to it, paths can not
+/// cross the seam along the rest of the edge
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BorderEdge {
+    pub poly: PolyRef,
+    /// Tile coordinates of the poly
+    pub tx: i32,
+    pub ty: i32,
+    /// Detour side of the neighbour tile, as in `SeamLinks::sides`
+    pub side: u8,
+    pub start: Point,
+    pub end: Point,
+    /// Fraction of the edge length linked to the neighbour, 0 when not linked at all
+    pub covered: f32,
+}
+
+impl RecastQuery {
+    /// One navmesh from tiles built separately, the links across the seams are made as the tiles are added.
+    ///
+    /// The tiles must be built on the grid of `params` for the same agent. A build only connects
+    /// with its neighbours when it saw the geometry under its tile border, so each build needs the
+    /// geometry overlapping its cells by the border, not only the geometry inside them
+    pub fn stitch_tiles(params: &NavMeshParams, tiles: Vec<TileData>) -> Result<RecastQuery> {
+        let mut agent: Option<TileHeader> = None;
+        for tile in &tiles {
+            let h = tile.header()?;
+            check_on_grid(params, &h)?;
+
+            if let Some(a) = &agent {
+                check_same_agent(a, &h)?;
+            }
+            agent = Some(h);
+        }
+
+        let mut q = RecastQuery::create_tiled_with_params(params)?;
+        for tile in tiles {
+            q.add_tile(tile)?;
+        }
+        Ok(q)
+    }
+
+    /// The links of the tile at the tile coordinates across its seams, e.g. to check a stitch
+    pub fn tile_seam_links(&self, tx: i32, ty: i32) -> Result<SeamLinks> {
+        let mut counts = [0i32; 8];
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_tile_seam_links(
+                self.q.as_ptr(),
+                tx,
+                ty,
+                counts.as_mut_ptr(),
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::BuildError(err.msg().to_string()));
+        }
+
+        let mut links = SeamLinks::default();
+        for (side, count) in links.sides.iter_mut().zip(counts.iter()) {
+            *side = *count as u32;
+        }
+        Ok(links)
+    }
+
+    /// The tile border edges facing a loaded neighbour tile without being linked to it along their
+    /// whole length, e.g. to validate a tiled bake. Empty when all the seams match
+    ///
+    /// The border edges of neighbours built with different geometry or settings do not line up,
+    /// and the edges too far apart in height for `BuildConfig::border_stitch_tolerance` are not linked
+    pub fn unmatched_border_edges(&self) -> Result<Vec<BorderEdge>> {
+        let mut edges = vec![sys::RecastBorderEdge::default(); 64];
+
+        loop {
+            let mut count = 0;
+            let mut err = sys::RecastNavError::zeros();
+
+            let res = unsafe {
+                sys::recastc_find_unmatched_border_edges(
+                    self.q.as_ptr(),
+                    edges.as_mut_ptr(),
+                    edges.len() as i32,
+                    &mut count as *mut _,
+                    &mut err as *mut _,
+                )
+            };
+
+            let count = count as usize;
+            if res != 0 {
+                return Ok(edges[..count]
+                    .iter()
+                    .map(|e| BorderEdge {
+                        poly: e.poly,
+                        tx: e.tx,
+                        ty: e.ty,
+                        side: e.side as u8,
+                        start: Point(e.start),
+                        end: Point(e.end),
+                        covered: e.covered,
+                    })
+                    .collect());
+            }
+            if count <= edges.len() {
+                return Err(Error::BuildError(err.msg().to_string()));
+            }
+            edges.resize(count, sys::RecastBorderEdge::default());
+        }
+    }
+}
+
+/// The tile bounds must be the cell of the grid at its tile coordinates
+pub(crate) fn check_on_grid(params: &NavMeshParams, h: &TileHeader) -> Result<()> {
+    let x = params.origin[0] + h.tx as f32 * params.tile_width;
+    let z = params.origin[2] + h.ty as f32 * params.tile_height;
+    let eps = 1e-3 * params.tile_width.max(params.tile_height);
+
+    let on_grid = (h.bmin[0] - x).abs() <= eps
+        && (h.bmin[2] - z).abs() <= eps
+        && (h.bmax[0] - (x + params.tile_width)).abs() <= eps
+        && (h.bmax[2] - (z + params.tile_height)).abs() <= eps;
+    if !on_grid {
+        return Err(Error::BuildError(format!(
+            "tile ({}, {}) is not on the grid of the navmesh params",
+            h.tx, h.ty
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, TileGrid};
+
+    fn strip() -> InputGeometry {
+        InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stitch_tiles() {
+        let geom = strip();
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+
+        // Two builds, each one of its own half of the columns
+        let half = grid.cols / 2;
+        let build = |west: bool| -> Vec<TileData> {
+            grid.tiles()
+                .filter(|&(tx, _)| (tx < half) == west)
+                .filter_map(|(tx, ty)| grid.build_tile(&geom, &config, tx, ty).unwrap())
+                .collect()
+        };
+        let mut tiles = build(true);
+        tiles.extend(build(false));
+
+        let h = tiles[0].header().unwrap();
+        assert!(h.poly_count > 0);
+        assert_eq!(h.walkable_radius, config.walkable_radius);
+
+        let q = RecastQuery::stitch_tiles(&grid.nav_mesh_params().unwrap(), tiles).unwrap();
+        let seam = q.tile_seam_links(half - 1, 0).unwrap();
+        assert!(seam.towards(1, 0) > 0, "{:?}", seam);
+        assert_eq!(seam.towards(-1, 0), seam.sides[4]);
+
+        let ext = (0.5, 1.0, 0.5);
+        let path = q
+            .find_path((1.0, 0.0, 5.0).into(), (19.0, 0.0, 5.0).into(), ext)
+            .unwrap();
+        assert!((path.last().unwrap().x() - 19.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_unmatched_border_edges() {
+        let raised = |dy: f32| {
+            let mut geom = strip();
+            for y in geom.vertices.iter_mut().skip(1).step_by(3) {
+                *y += dy;
+            }
+            geom
+        };
+        let (low, high) = (strip(), raised(2.0));
+        let mut bounds = strip();
+        bounds.vertices.extend(raised(2.0).vertices);
+        let grid = TileGrid::for_geometry(&bounds, &BuildConfig::default(), 16).unwrap();
+        let half = grid.cols / 2;
+
+        // The east half 2 units higher than the west half, above the default tolerance of walkable_climb
+        let stitch = |config: &BuildConfig| {
+            let tiles = grid
+                .tiles()
+                .filter_map(|(tx, ty)| {
+                    let geom = if tx < half { &low } else { &high };
+                    grid.build_tile(geom, config, tx, ty).unwrap()
+                })
+                .collect();
+            RecastQuery::stitch_tiles(&grid.nav_mesh_params().unwrap(), tiles).unwrap()
+        };
+
+        let q = stitch(&BuildConfig::default());
+        let edges = q.unmatched_border_edges().unwrap();
+        assert!(!edges.is_empty());
+        assert!(edges.iter().all(|e| e.covered == 0.0), "{:?}", edges);
+        assert!(edges
+            .iter()
+            .any(|e| e.tx == half - 1 && e.side == 0 && e.start.y() < 1.0));
+        assert!(edges.iter().any(|e| e.tx == half && e.side == 4));
+
+        let q = stitch(&BuildConfig::default().tile_border(0, 2.5));
+        assert!(q.unmatched_border_edges().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stitch_off_grid() {
+        let geom = strip();
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let tile = grid.build_tile(&geom, &config, 0, 0).unwrap().unwrap();
+
+        let mut params = grid.nav_mesh_params().unwrap();
+        params.origin[0] += 1.0;
+        let err = RecastQuery::stitch_tiles(&params, vec![tile]).unwrap_err();
+        assert!(err.to_string().contains("not on the grid"));
+    }
+}
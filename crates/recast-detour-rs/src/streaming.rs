@@ -0,0 +1,291 @@
+//! Tiles loaded around focus points on demand, so only a part of an open world navmesh is resident
+use crate::{Error, NavMeshParams, Point, RecastQuery, Result, TileData};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Where a `TileStreamer` gets its tiles from, e.g. files of a baked world or an asset system
+pub trait TileProvider {
+    /// The data of the tile at the tile coordinates, `None` when the tile has no walkable surface
+    fn load(&mut self, tx: i32, ty: i32) -> Result<Option<TileData>>;
+}
+
+impl<F> TileProvider for F
+where
+    F: FnMut(i32, i32) -> Result<Option<TileData>>,
+{
+    fn load(&mut self, tx: i32, ty: i32) -> Result<Option<TileData>> {
+        self(tx, ty)
+    }
+}
+
+/// Tiles stored one per file as `<tx>_<ty>.tile` in a directory, a missing file is a tile
+/// without walkable surface
+#[derive(Debug, Clone)]
+pub struct DirTileProvider {
+    pub dir: PathBuf,
+}
+
+impl DirTileProvider {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DirTileProvider {
+        DirTileProvider { dir: dir.into() }
+    }
+
+    pub fn tile_path(&self, tx: i32, ty: i32) -> PathBuf {
+        self.dir.join(format!("{}_{}.tile", tx, ty))
+    }
+
+    /// Store a tile where `load` finds it
+    pub fn save(&self, tx: i32, ty: i32, tile: &TileData) -> Result<()> {
+        std::fs::write(self.tile_path(tx, ty), tile.as_bytes())
+            .map_err(|e| Error::BuildError(format!("tile ({}, {}): {}", tx, ty, e)))
+    }
+}
+
+impl TileProvider for DirTileProvider {
+    fn load(&mut self, tx: i32, ty: i32) -> Result<Option<TileData>> {
+        match std::fs::read(self.tile_path(tx, ty)) {
+            Ok(bytes) => TileData::from_bytes(&bytes).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::BuildError(format!("tile ({}, {}): {}", tx, ty, e))),
+        }
+    }
+}
+
+/// The tiles changed by a `TileStreamer::update`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamReport {
+    pub loaded: Vec<(i32, i32)>,
+    pub unloaded: Vec<(i32, i32)>,
+    /// Tiles in range not loaded yet because of `max_loads_per_update`
+    pub pending: usize,
+}
+
+/// A tiled navmesh keeping the tiles around focus points resident, e.g. around the players
+/// and the agents moving.
+///
+/// Tiles within `load_radius` of a focus point are loaded and tiles farther than `unload_radius`
+/// from every focus point are unloaded, the gap between both keeps tiles at the edge from
+/// reloading back and forth. The poly refs of an unloaded tile become invalid
+pub struct TileStreamer<P> {
+    query: RecastQuery,
+    params: NavMeshParams,
+    provider: P,
+    load_radius: f32,
+    unload_radius: f32,
+    max_loads_per_update: usize,
+    /// Tiles asked from the provider, with or without data
+    resident: BTreeSet<(i32, i32)>,
+}
+
+impl<P: TileProvider> TileStreamer<P> {
+    /// An empty navmesh with the layout of the baked tiles. `max_tiles` of `params` only needs
+    /// to fit the tiles resident at once
+    pub fn new(
+        params: NavMeshParams,
+        provider: P,
+        load_radius: f32,
+        unload_radius: f32,
+    ) -> Result<TileStreamer<P>> {
+        if !(load_radius >= 0.0 && unload_radius >= load_radius) {
+            return Err(Error::BuildError(
+                "unload_radius must not be below load_radius".into(),
+            ));
+        }
+
+        Ok(TileStreamer {
+            query: RecastQuery::create_tiled_with_params(&params)?,
+            params,
+            provider,
+            load_radius,
+            unload_radius,
+            max_loads_per_update: usize::MAX,
+            resident: BTreeSet::new(),
+        })
+    }
+
+    /// Spread the loads over several updates, the tiles nearest to a focus point load first
+    pub fn with_max_loads_per_update(mut self, max_loads: usize) -> TileStreamer<P> {
+        self.max_loads_per_update = max_loads.max(1);
+        self
+    }
+
+    pub fn query(&self) -> &RecastQuery {
+        &self.query
+    }
+
+    pub fn query_mut(&mut self) -> &mut RecastQuery {
+        &mut self.query
+    }
+
+    pub fn provider_mut(&mut self) -> &mut P {
+        &mut self.provider
+    }
+
+    pub fn is_resident(&self, tx: i32, ty: i32) -> bool {
+        self.resident.contains(&(tx, ty))
+    }
+
+    /// Unload the tiles out of range of every focus point, then load the tiles in range
+    pub fn update(&mut self, focus: &[Point]) -> Result<StreamReport> {
+        let mut report = StreamReport::default();
+
+        let stale: Vec<_> = self
+            .resident
+            .iter()
+            .copied()
+            .filter(|&(tx, ty)| {
+                !focus
+                    .iter()
+                    .any(|p| self.tile_distance(tx, ty, *p) <= self.unload_radius)
+            })
+            .collect();
+        for (tx, ty) in stale {
+            // Tiles without walkable surface were never added
+            let _ = self.query.remove_tile(tx, ty);
+            self.resident.remove(&(tx, ty));
+            report.unloaded.push((tx, ty));
+        }
+
+        let mut wanted: Vec<(f32, (i32, i32))> = vec![];
+        for p in focus {
+            for c in self.tiles_around(*p, self.load_radius) {
+                let d = self.tile_distance(c.0, c.1, *p);
+                if d <= self.load_radius && !self.resident.contains(&c) {
+                    match wanted.iter_mut().find(|(_, w)| *w == c) {
+                        Some(w) => w.0 = w.0.min(d),
+                        None => wanted.push((d, c)),
+                    }
+                }
+            }
+        }
+        wanted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        for &(_, (tx, ty)) in wanted.iter().take(self.max_loads_per_update) {
+            if let Some(tile) = self.provider.load(tx, ty)? {
+                self.query.add_tile(tile)?;
+            }
+            self.resident.insert((tx, ty));
+            report.loaded.push((tx, ty));
+        }
+        report.pending = wanted.len().saturating_sub(self.max_loads_per_update);
+
+        Ok(report)
+    }
+
+    /// The tiles whose bounds may be within `radius` of the point on the xz plane
+    fn tiles_around(&self, p: Point, radius: f32) -> impl Iterator<Item = (i32, i32)> {
+        let o = self.params.origin;
+        let (w, h) = (self.params.tile_width, self.params.tile_height);
+        let x0 = ((p.x() - radius - o[0]) / w).floor() as i32;
+        let x1 = ((p.x() + radius - o[0]) / w).floor() as i32;
+        let y0 = ((p.z() - radius - o[2]) / h).floor() as i32;
+        let y1 = ((p.z() + radius - o[2]) / h).floor() as i32;
+        (y0..=y1).flat_map(move |ty| (x0..=x1).map(move |tx| (tx, ty)))
+    }
+
+    /// Distance on the xz plane from the point to the bounds of the tile, 0 inside
+    fn tile_distance(&self, tx: i32, ty: i32, p: Point) -> f32 {
+        let o = self.params.origin;
+        let (w, h) = (self.params.tile_width, self.params.tile_height);
+        let (x0, z0) = (o[0] + tx as f32 * w, o[2] + ty as f32 * h);
+        let dx = (x0 - p.x()).max(p.x() - (x0 + w)).max(0.0);
+        let dz = (z0 - p.z()).max(p.z() - (z0 + h)).max(0.0);
+        (dx * dx + dz * dz).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, InputGeometry, TileGrid};
+    use std::collections::HashMap;
+
+    fn baked() -> (NavMeshParams, HashMap<(i32, i32), Vec<u8>>) {
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 40.0, 0.0, 0.0, 40.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            ..Default::default()
+        };
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 32).unwrap();
+
+        let mut tiles = HashMap::new();
+        for (tx, ty) in grid.tiles() {
+            if let Some(tile) = grid.build_tile(&geom, &config, tx, ty).unwrap() {
+                tiles.insert((tx, ty), tile.as_bytes().to_vec());
+            }
+        }
+        (grid.nav_mesh_params().unwrap(), tiles)
+    }
+
+    #[test]
+    fn test_stream_around_focus() {
+        let (params, tiles) = baked();
+        let provider = move |tx, ty| match tiles.get(&(tx, ty)) {
+            Some(bytes) => TileData::from_bytes(bytes).map(Some),
+            None => Ok(None),
+        };
+        let mut streamer = TileStreamer::new(params, provider, 2.0, 6.0).unwrap();
+        let ext = (0.5, 1.0, 0.5);
+
+        let report = streamer.update(&[(2.0, 0.0, 5.0).into()]).unwrap();
+        assert!(report.loaded.contains(&(0, 0)));
+        assert!(!streamer.is_resident(4, 0));
+        assert!(streamer
+            .query()
+            .find_poly((2.0, 0.0, 5.0).into(), ext)
+            .is_ok());
+        assert!(streamer
+            .query()
+            .find_poly((38.0, 0.0, 5.0).into(), ext)
+            .is_err());
+
+        // Moving to the other end unloads the start
+        let report = streamer.update(&[(38.0, 0.0, 5.0).into()]).unwrap();
+        assert!(report.unloaded.contains(&(0, 0)));
+        assert!(streamer
+            .query()
+            .find_poly((38.0, 0.0, 5.0).into(), ext)
+            .is_ok());
+        assert!(streamer
+            .query()
+            .find_poly((2.0, 0.0, 5.0).into(), ext)
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_loads_per_update() {
+        let (params, tiles) = baked();
+        let n = tiles.len();
+        let provider = move |tx, ty| match tiles.get(&(tx, ty)) {
+            Some(bytes) => TileData::from_bytes(bytes).map(Some),
+            None => Ok(None),
+        };
+        let mut streamer = TileStreamer::new(params, provider, 100.0, 100.0)
+            .unwrap()
+            .with_max_loads_per_update(1);
+
+        let focus = [(20.0, 0.0, 5.0).into()];
+        let report = streamer.update(&focus).unwrap();
+        assert_eq!(report.loaded.len(), 1);
+        assert!(report.pending >= n - 1);
+    }
+
+    #[test]
+    fn test_dir_provider() {
+        let (_, tiles) = baked();
+        let dir = std::env::temp_dir().join(format!("recast-tiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut provider = DirTileProvider::new(&dir);
+        let tile = TileData::from_bytes(&tiles[&(0, 0)]).unwrap();
+        provider.save(0, 0, &tile).unwrap();
+
+        let loaded = provider.load(0, 0).unwrap().unwrap();
+        assert_eq!(loaded.as_bytes(), tile.as_bytes());
+        assert!(provider.load(7, 7).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
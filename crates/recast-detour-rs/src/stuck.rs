@@ -0,0 +1,242 @@
+//! Stuck detection for agents driven along navmesh paths
+use crate::{AgentId, Crowd, Point, Result};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Emitted when an agent changes between moving and stuck
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StuckEvent<K> {
+    /// The agent wants to move but its positions stayed clustered for a whole window
+    Stuck { agent: K, position: Point },
+    /// A previously stuck agent started making progress again
+    Unstuck { agent: K },
+}
+
+#[derive(Debug, Clone, Default)]
+struct Trail {
+    /// (timestamp, position), oldest first
+    samples: VecDeque<(f32, Point)>,
+    time: f32,
+    stuck: bool,
+    /// `Crowd::generation` of the slot for crowd agents, another agent added to it starts over
+    generation: u32,
+}
+
+impl Trail {
+    fn spread(&self) -> f32 {
+        let n = self.samples.len() as f32;
+        let (mut cx, mut cy, mut cz) = (0.0, 0.0, 0.0);
+        for (_, p) in &self.samples {
+            cx += p.x();
+            cy += p.y();
+            cz += p.z();
+        }
+        let (cx, cy, cz) = (cx / n, cy / n, cz / n);
+
+        let mut variance = 0.0;
+        for (_, p) in &self.samples {
+            variance += (p.x() - cx).powi(2) + (p.y() - cy).powi(2) + (p.z() - cz).powi(2);
+        }
+
+        (variance / n).sqrt()
+    }
+}
+
+/// Keeps a short position history per agent and flags agents making no progress.
+///
+/// An agent moving at `desired_speed` for the whole window spreads its samples over
+/// `desired_speed * window` units. When the standard deviation of the samples stays
+/// below `min_progress` times that distance, the agent is considered stuck. `update_crowd`
+/// samples the agents of a `Crowd`.
+#[derive(Debug, Clone)]
+pub struct StuckDetector<K> {
+    /// History length in seconds
+    pub window: f32,
+    /// Fraction of the expected travel distance the samples have to spread over
+    pub min_progress: f32,
+    agents: HashMap<K, Trail>,
+}
+
+impl<K: Hash + Eq + Copy> StuckDetector<K> {
+    pub fn new(window: f32, min_progress: f32) -> StuckDetector<K> {
+        assert!(window > 0.0);
+
+        StuckDetector {
+            window,
+            min_progress,
+            agents: HashMap::new(),
+        }
+    }
+
+    /// Records the agent position after a simulation step of `dt` seconds.
+    ///
+    /// A `desired_speed` of zero means the agent is idle on purpose and never reported as stuck.
+    pub fn update(
+        &mut self,
+        agent: K,
+        pos: Point,
+        desired_speed: f32,
+        dt: f32,
+    ) -> Option<StuckEvent<K>> {
+        let window = self.window;
+        let trail = self.agents.entry(agent).or_default();
+
+        trail.time += dt;
+        trail.samples.push_back((trail.time, pos));
+        while let Some(&(t, _)) = trail.samples.front() {
+            if trail.time - t <= window {
+                break;
+            }
+            trail.samples.pop_front();
+        }
+
+        let covered = trail
+            .samples
+            .front()
+            .map(|&(t, _)| trail.time - t)
+            .unwrap_or(0.0);
+
+        let stuck = if desired_speed <= 0.0 {
+            false
+        } else if covered < window * 0.9 {
+            // Not enough history yet, keep the previous verdict
+            trail.stuck
+        } else {
+            trail.spread() < self.min_progress * desired_speed * window
+        };
+
+        match (trail.stuck, stuck) {
+            (false, true) => {
+                trail.stuck = true;
+                Some(StuckEvent::Stuck {
+                    agent,
+                    position: pos,
+                })
+            }
+            (true, false) => {
+                trail.stuck = false;
+                Some(StuckEvent::Unstuck { agent })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_stuck(&self, agent: K) -> bool {
+        self.agents.get(&agent).map(|t| t.stuck).unwrap_or(false)
+    }
+
+    /// The recorded positions of the agent, oldest first
+    pub fn history(&self, agent: K) -> impl Iterator<Item = Point> + '_ {
+        self.agents
+            .get(&agent)
+            .into_iter()
+            .flat_map(|t| t.samples.iter().map(|&(_, p)| p))
+    }
+
+    /// Forget the history of an agent, e.g. after it was teleported or given a new path
+    pub fn reset(&mut self, agent: K) {
+        self.agents.remove(&agent);
+    }
+}
+
+impl StuckDetector<AgentId> {
+    /// Samples every agent of the crowd after an update of `dt` seconds. The desired speed is the
+    /// one of the path the agent follows, agents crossing an off-mesh connection or detached are
+    /// idle. Agents removed since the last call are forgotten, as are those whose slot went to a
+    /// new agent in between
+    pub fn update_crowd(&mut self, crowd: &Crowd, dt: f32) -> Result<Vec<StuckEvent<AgentId>>> {
+        let agents = crowd.agents()?;
+
+        let alive: HashSet<(AgentId, u32)> = agents
+            .iter()
+            .map(|(id, _)| (*id, crowd.generation(*id)))
+            .collect();
+        self.agents
+            .retain(|id, trail| alive.contains(&(*id, trail.generation)));
+
+        let mut events = vec![];
+        for (id, agent) in agents {
+            self.agents.entry(id).or_default().generation = crowd.generation(id);
+
+            let desired_speed = if agent.off_mesh || agent.detached {
+                0.0
+            } else {
+                (agent.desired_vel.x().powi(2) + agent.desired_vel.z().powi(2)).sqrt()
+            };
+            events.extend(self.update(id, agent.pos, desired_speed, dt));
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stuck_and_unstuck() {
+        let mut d = StuckDetector::new(1.0, 0.1);
+
+        let mut events = vec![];
+        for _ in 0..20 {
+            events.extend(d.update(7u32, Point::new((1.0, 0.0, 1.0)), 2.0, 0.1));
+        }
+        assert_eq!(events.len(), 1);
+        assert!(d.is_stuck(7));
+
+        let mut x = 1.0;
+        for _ in 0..20 {
+            x += 0.2;
+            events.extend(d.update(7u32, Point::new((x, 0.0, 1.0)), 2.0, 0.1));
+        }
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], StuckEvent::Unstuck { agent: 7 });
+        assert!(!d.is_stuck(7));
+    }
+
+    #[test]
+    fn test_idle_agent_is_not_stuck() {
+        let mut d = StuckDetector::new(1.0, 0.1);
+
+        for _ in 0..20 {
+            assert_eq!(d.update(1u32, Point::new((0.0, 0.0, 0.0)), 0.0, 0.1), None);
+        }
+        assert!(d.history(1).count() >= 10);
+    }
+
+    #[test]
+    fn test_crowd_agent_against_wall() {
+        use crate::test_util::plane_query;
+        use crate::CrowdAgentParams;
+
+        let mut crowd = Crowd::new(plane_query(), 8, 0.6).unwrap();
+        let params = CrowdAgentParams::new(0.5, 2.0, 3.5);
+        let walker = crowd.add_agent((8.0, 0.0, 5.0).into(), &params).unwrap();
+        let idle = crowd.add_agent((2.0, 0.0, 5.0).into(), &params).unwrap();
+
+        // Keeps pushing into the edge of the navmesh
+        crowd
+            .request_move_velocity(walker, (2.0, 0.0, 0.0).into())
+            .unwrap();
+
+        let mut d = StuckDetector::new(1.0, 0.1);
+        let mut events = vec![];
+        for _ in 0..40 {
+            crowd.update(0.1).unwrap();
+            events.extend(d.update_crowd(&crowd, 0.1).unwrap());
+        }
+        assert!(
+            matches!(events[..], [StuckEvent::Stuck { agent, .. }] if agent == walker),
+            "{:?}",
+            events
+        );
+        assert!(!d.is_stuck(idle));
+
+        crowd.remove_agent(walker).unwrap();
+        let id = crowd.add_agent((5.0, 0.0, 5.0).into(), &params).unwrap();
+        assert_eq!(id, walker);
+        d.update_crowd(&crowd, 0.1).unwrap();
+        assert!(!d.is_stuck(id));
+        assert_eq!(d.history(id).count(), 1);
+    }
+}
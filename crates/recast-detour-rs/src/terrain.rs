@@ -0,0 +1,330 @@
+//! Terrain heightfields meshed tile by tile, e.g. the heightfield colliders of parry and bevy_rapier
+use crate::recast::SysInput;
+use crate::tiled::build_tile;
+use crate::{compute_bb, BuildConfig, Error, InputGeometry, Point, RecastQuery, Result, TileGrid};
+
+/// A regular grid of height samples with holes, `rows` along z and `cols` along x.
+///
+/// Only the cells read by a build are triangulated, so a terrain never lives twice in memory
+/// as a whole triangle mesh.
+#[derive(Debug, Clone)]
+pub struct TerrainHeightfield {
+    /// World position of the sample (0, 0)
+    pub origin: Point,
+    /// Distance between two samples along x and along z
+    pub spacing: (f32, f32),
+    rows: usize,
+    cols: usize,
+    /// Row by row, in world unit from the origin
+    heights: Vec<f32>,
+    /// Per cell, row by row
+    holes: Vec<bool>,
+}
+
+impl TerrainHeightfield {
+    /// `heights` row by row, sample (row, col) is at `origin + (col * spacing.0, h, row * spacing.1)`
+    pub fn new(
+        origin: Point,
+        spacing: (f32, f32),
+        rows: usize,
+        cols: usize,
+        heights: Vec<f32>,
+    ) -> Result<TerrainHeightfield> {
+        if rows < 2 || cols < 2 {
+            return Err(Error::BuildError(
+                "terrain needs at least 2 rows and 2 columns of samples".into(),
+            ));
+        }
+        if heights.len() != rows * cols {
+            return Err(Error::BuildError(format!(
+                "terrain of {}x{} samples given {} heights",
+                rows,
+                cols,
+                heights.len()
+            )));
+        }
+        if spacing.0 <= 0.0 || spacing.1 <= 0.0 {
+            return Err(Error::BuildError("terrain spacing must be positive".into()));
+        }
+
+        Ok(TerrainHeightfield {
+            origin,
+            spacing,
+            rows,
+            cols,
+            heights,
+            holes: vec![false; (rows - 1) * (cols - 1)],
+        })
+    }
+
+    /// The layout of parry `HeightField`: `heights` column major with `nrows` samples along z
+    /// and `ncols` along x (`heights().as_slice()`), spread over `scale` and centered on
+    /// `position`, the translation of the collider. Rotated colliders are not supported.
+    ///
+    /// The removed cells of parry (`cell_status`) are set with `set_hole`.
+    pub fn from_parry(
+        heights: &[f32],
+        nrows: usize,
+        ncols: usize,
+        scale: [f32; 3],
+        position: Point,
+    ) -> Result<TerrainHeightfield> {
+        if nrows < 2 || ncols < 2 || heights.len() != nrows * ncols {
+            return Err(Error::BuildError(format!(
+                "parry heightfield of {}x{} samples given {} heights",
+                nrows,
+                ncols,
+                heights.len()
+            )));
+        }
+
+        let mut row_major = vec![0.0; heights.len()];
+        for col in 0..ncols {
+            for row in 0..nrows {
+                row_major[row * ncols + col] = heights[row + col * nrows] * scale[1];
+            }
+        }
+
+        let origin = (
+            position.x() - scale[0] * 0.5,
+            position.y(),
+            position.z() - scale[2] * 0.5,
+        );
+        let spacing = (scale[0] / (ncols - 1) as f32, scale[2] / (nrows - 1) as f32);
+        TerrainHeightfield::new(origin.into(), spacing, nrows, ncols, row_major)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn height(&self, row: usize, col: usize) -> f32 {
+        self.heights[row * self.cols + col]
+    }
+
+    /// Remove the cell between the samples (row, col) and (row + 1, col + 1) from the walkable surface
+    pub fn set_hole(&mut self, row: usize, col: usize, hole: bool) -> Result<()> {
+        if row >= self.rows - 1 || col >= self.cols - 1 {
+            return Err(Error::BuildError(format!(
+                "terrain cell ({}, {}) out of {}x{} cells",
+                row,
+                col,
+                self.rows - 1,
+                self.cols - 1
+            )));
+        }
+        self.holes[row * (self.cols - 1) + col] = hole;
+        Ok(())
+    }
+
+    pub fn is_hole(&self, row: usize, col: usize) -> bool {
+        self.holes[row * (self.cols - 1) + col]
+    }
+
+    /// World bounds of the samples
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let (min, max) = self
+            .heights
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+        let o = self.origin;
+
+        (
+            [o.x(), o.y() + min, o.z()],
+            [
+                o.x() + (self.cols - 1) as f32 * self.spacing.0,
+                o.y() + max,
+                o.z() + (self.rows - 1) as f32 * self.spacing.1,
+            ],
+        )
+    }
+
+    /// Append the triangles of the cells overlapping the xz rectangle to the geometry
+    pub fn append_region(&self, geometry: &mut InputGeometry, min: (f32, f32), max: (f32, f32)) {
+        let cell =
+            |v: f32, o: f32, s: f32, n: usize| (((v - o) / s).floor().max(0.0) as usize).min(n - 2);
+        let (ox, oz) = (self.origin.x(), self.origin.z());
+        let (bmin, bmax) = self.bounds();
+        if max.0 < bmin[0] || max.1 < bmin[2] || min.0 > bmax[0] || min.1 > bmax[2] {
+            return;
+        }
+
+        let (c0, c1) = (
+            cell(min.0, ox, self.spacing.0, self.cols),
+            cell(max.0, ox, self.spacing.0, self.cols),
+        );
+        let (r0, r1) = (
+            cell(min.1, oz, self.spacing.1, self.rows),
+            cell(max.1, oz, self.spacing.1, self.rows),
+        );
+
+        // The samples of the cells, shared by the neighbour cells
+        let first = (geometry.vertices.len() / 3) as u32;
+        let width = (c1 - c0 + 2) as u32;
+        for row in r0..=r1 + 1 {
+            for col in c0..=c1 + 1 {
+                geometry.vertices.extend_from_slice(&[
+                    ox + col as f32 * self.spacing.0,
+                    self.origin.y() + self.height(row, col),
+                    oz + row as f32 * self.spacing.1,
+                ]);
+            }
+        }
+
+        // The terrain is area 0, only set when the geometry has triangle areas
+        let keep_areas = !geometry.triangle_areas.is_empty();
+        for row in r0..=r1 {
+            for col in c0..=c1 {
+                if self.is_hole(row, col) {
+                    continue;
+                }
+
+                let a = first + (row - r0) as u32 * width + (col - c0) as u32;
+                let (b, c, d) = (a + 1, a + width + 1, a + width);
+                // Counter clockwise seen from above, as Recast expects for walkable triangles
+                geometry.indices.extend_from_slice(&[a, c, b, a, d, c]);
+                if keep_areas {
+                    geometry.triangle_areas.extend_from_slice(&[0, 0]);
+                }
+            }
+        }
+    }
+
+    /// Append the cells read by the build of a tile to the geometry
+    pub fn append_tile(
+        &self,
+        geometry: &mut InputGeometry,
+        grid: &TileGrid,
+        config: &BuildConfig,
+        tx: i32,
+        ty: i32,
+    ) {
+        let (min, max) = grid.tile_bounds(config, tx, ty);
+        self.append_region(geometry, min, max);
+    }
+
+    /// The whole terrain as triangles
+    pub fn to_geometry(&self) -> InputGeometry {
+        let mut geometry = InputGeometry::default();
+        let (bmin, bmax) = self.bounds();
+        self.append_region(&mut geometry, (bmin[0], bmin[2]), (bmax[0], bmax[2]));
+        geometry
+    }
+}
+
+impl RecastQuery {
+    /// Build a tiled navmesh over the terrain and the extra geometry, e.g. props and off-mesh
+    /// connections. Each tile is built from a copy of `extras` and the terrain cells of the tile,
+    /// so `extras` should stay small next to the terrain.
+    pub fn build_terrain_tiled(
+        terrain: &TerrainHeightfield,
+        extras: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<RecastQuery> {
+        extras.validate()?;
+        config.validate()?;
+        if tile_size == 0 {
+            return Err(Error::BuildError("tile_size must be positive".into()));
+        }
+
+        let (mut bmin, mut bmax) = terrain.bounds();
+        if !extras.vertices.is_empty() {
+            let (emin, emax) = compute_bb(&extras.vertices);
+            for i in 0..3 {
+                bmin[i] = bmin[i].min(emin[i]);
+                bmax[i] = bmax[i].max(emax[i]);
+            }
+        }
+
+        let grid = TileGrid::new(bmin, bmax, config.cell_size, tile_size);
+        let mut q = RecastQuery::create_tiled(&grid)?;
+        let sys_config = config.as_sys();
+
+        for (tx, ty) in grid.tiles() {
+            let mut geometry = extras.clone();
+            terrain.append_tile(&mut geometry, &grid, config, tx, ty);
+            if geometry.indices.is_empty() {
+                continue;
+            }
+
+            let sys_input = SysInput::new(&geometry);
+            if let Some(tile) = build_tile(&sys_input.as_sys(), &sys_config, &grid, tx, ty)? {
+                q.add_tile(tile)?;
+            }
+        }
+
+        Ok(q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A gentle slope of 21x21 samples 1 unit apart
+    fn slope() -> TerrainHeightfield {
+        let heights = (0..21 * 21).map(|i| (i % 21) as f32 * 0.1).collect();
+        TerrainHeightfield::new((0.0, 0.0, 0.0).into(), (1.0, 1.0), 21, 21, heights).unwrap()
+    }
+
+    #[test]
+    fn test_parry_layout() {
+        // 2 rows along z, 3 columns along x, column major
+        let heights = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let terrain =
+            TerrainHeightfield::from_parry(&heights, 2, 3, [4.0, 2.0, 2.0], (0.0, 1.0, 0.0).into())
+                .unwrap();
+
+        assert_eq!(terrain.height(0, 1), 4.0);
+        assert_eq!(terrain.height(1, 0), 2.0);
+        assert_eq!(terrain.spacing, (2.0, 2.0));
+        let (bmin, bmax) = terrain.bounds();
+        assert_eq!(bmin, [-2.0, 1.0, -1.0]);
+        assert_eq!(bmax, [2.0, 11.0, 1.0]);
+
+        assert!(TerrainHeightfield::from_parry(&heights, 3, 3, [1.0; 3], Point(bmin)).is_err());
+    }
+
+    #[test]
+    fn test_append_region() {
+        let mut terrain = slope();
+        terrain.set_hole(5, 5, true).unwrap();
+        assert!(terrain.set_hole(20, 5, true).is_err());
+
+        let mut geom = InputGeometry::default();
+        terrain.append_region(&mut geom, (4.5, 4.5), (6.5, 5.5));
+        // Cells 4..=6 along x, 4..=5 along z, one of them a hole
+        assert_eq!(geom.vertices.len() / 3, 4 * 3);
+        assert_eq!(geom.indices.len() / 3, 2 * 5);
+
+        terrain.append_region(&mut geom, (50.0, 50.0), (60.0, 60.0));
+        assert_eq!(geom.indices.len() / 3, 2 * 5);
+        assert_eq!(terrain.to_geometry().indices.len() / 3, 2 * 20 * 20 - 2);
+    }
+
+    #[test]
+    fn test_build_terrain_tiled() {
+        let mut terrain = slope();
+        // A hole across the middle, open at z >= 16
+        for row in 0..16 {
+            terrain.set_hole(row, 10, true).unwrap();
+        }
+
+        let config = BuildConfig::default();
+        let q =
+            RecastQuery::build_terrain_tiled(&terrain, &Default::default(), &config, 32).unwrap();
+
+        let ext = (0.2, 2.0, 0.2);
+        assert!(q.find_poly((10.5, 1.0, 5.0).into(), ext).is_err());
+        let path = q
+            .find_path((5.0, 0.5, 5.0).into(), (15.0, 1.5, 5.0).into(), ext)
+            .unwrap();
+        // Around the end of the hole
+        assert!(path.iter().any(|p| p.z() > 15.5), "{:?}", path);
+    }
+}
@@ -0,0 +1,56 @@
+//! Meshes shared by the tests
+use crate::{BuildConfig, InputGeometry, NavMeshData, RecastQuery};
+
+/// A 10x10 square of 2 triangles
+pub(crate) fn simple_mesh() -> NavMeshData {
+    let vertices = vec![
+        0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+    ];
+
+    let indices = vec![0, 1, 2, 0, 2, 3];
+
+    NavMeshData {
+        vertices,
+        indices,
+        walkable_height: 0.2,
+        walkable_radius: 0.2,
+        walkable_climb: 0.2,
+        cell_size: 0.1,
+        cell_height: 0.1,
+        ..Default::default()
+    }
+}
+
+/// A square of `size` from the origin, on the xz plane
+pub(crate) fn plane(size: f32) -> InputGeometry {
+    InputGeometry {
+        vertices: vec![
+            0.0, 0.0, 0.0, size, 0.0, 0.0, size, 0.0, size, 0.0, 0.0, size,
+        ],
+        indices: vec![0, 2, 1, 0, 3, 2],
+        ..Default::default()
+    }
+}
+
+/// The navmesh of the 10x10 plane with the default config
+pub(crate) fn plane_query() -> RecastQuery {
+    RecastQuery::build(&plane(10.0), &BuildConfig::default()).unwrap()
+}
+
+/// Two rooms joined by a narrow passage, from x 0 to 24
+pub(crate) fn rooms_geometry() -> InputGeometry {
+    InputGeometry {
+        vertices: vec![
+            0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+            10.0, 0.0, 4.0, 14.0, 0.0, 4.0, 14.0, 0.0, 6.0, 10.0, 0.0, 6.0, //
+            14.0, 0.0, 0.0, 24.0, 0.0, 0.0, 24.0, 0.0, 10.0, 14.0, 0.0, 10.0,
+        ],
+        indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6, 8, 10, 9, 8, 11, 10],
+        ..Default::default()
+    }
+}
+
+/// The navmesh of the two rooms with the default config
+pub(crate) fn rooms() -> RecastQuery {
+    RecastQuery::build(&rooms_geometry(), &BuildConfig::default()).unwrap()
+}
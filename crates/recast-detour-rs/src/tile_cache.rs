@@ -0,0 +1,837 @@
+//! Tiled navmeshes with temporary obstacles re-meshed incrementally, see the TempObstacles sample
+use crate::pack::{read_f32, read_u32};
+use crate::recast::SysInput;
+use crate::serialize::{read_params, read_slice};
+use crate::{
+    BuildConfig, Error, InputGeometry, Point, PolyFlags, PolyRef, QueryFilter, RecastQuery, Result,
+    TileGrid,
+};
+use std::fmt;
+use std::io;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// `TILECACHESET_MAGIC` of RecastDemo, 'TSET' as an int
+const RECAST_DEMO_MAGIC: u32 = u32::from_be_bytes(*b"TSET");
+const RECAST_DEMO_VERSION: u32 = 1;
+
+fn read_error(err: io::Error) -> Error {
+    Error::SerializeError(err.to_string())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ObstacleId(u32);
+
+/// Compression of the tile cache layers, e.g. to match what an asset pipeline produces
+pub trait TileCompressor {
+    /// Upper bound of the compressed size of `size` bytes
+    fn max_compressed_size(&self, size: usize) -> usize;
+
+    /// Compress `data` into `out`, returns the compressed size
+    fn compress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize>;
+
+    /// Decompress `data` into `out`, returns the decompressed size
+    fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize>;
+}
+
+/// Layers stored as is, trading memory for rebuild speed
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NoCompression;
+
+impl TileCompressor for NoCompression {
+    fn max_compressed_size(&self, size: usize) -> usize {
+        size
+    }
+
+    fn compress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize> {
+        out.get_mut(..data.len())?.copy_from_slice(data);
+        Some(data.len())
+    }
+
+    fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize> {
+        self.compress(data, out)
+    }
+}
+
+/// The compressor of the callbacks, a panic of it must not unwind through C and is reported as a failure
+fn user_compressor<'a>(user: *mut c_void) -> &'a mut Box<dyn TileCompressor> {
+    unsafe { &mut *(user as *mut Box<dyn TileCompressor>) }
+}
+
+extern "C" fn max_compressed_size_cb(user: *mut c_void, size: i32) -> i32 {
+    // No room for the compressed tile, its compression then fails
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        user_compressor(user).max_compressed_size(size as usize) as i32
+    }))
+    .unwrap_or(0)
+}
+
+extern "C" fn compress_cb(
+    user: *mut c_void,
+    buffer: *const u8,
+    size: i32,
+    compressed: *mut u8,
+    max_compressed_size: i32,
+    compressed_size: *mut i32,
+) -> i32 {
+    let data = unsafe { slice::from_raw_parts(buffer, size as usize) };
+    let out = unsafe { slice::from_raw_parts_mut(compressed, max_compressed_size as usize) };
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        user_compressor(user).compress(data, out)
+    }));
+    match res {
+        Ok(Some(n)) if n <= out.len() => {
+            unsafe { *compressed_size = n as i32 };
+            1
+        }
+        _ => 0,
+    }
+}
+
+extern "C" fn decompress_cb(
+    user: *mut c_void,
+    compressed: *const u8,
+    size: i32,
+    buffer: *mut u8,
+    max_size: i32,
+    size_out: *mut i32,
+) -> i32 {
+    let data = unsafe { slice::from_raw_parts(compressed, size as usize) };
+    let out = unsafe { slice::from_raw_parts_mut(buffer, max_size as usize) };
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        user_compressor(user).decompress(data, out)
+    }));
+    match res {
+        Ok(Some(n)) if n <= out.len() => {
+            unsafe { *size_out = n as i32 };
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// Layout of a tile cache
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileCacheConfig {
+    /// Width and depth of a tile in cells, the tile and its border must fit in 255 cells
+    pub tile_size: u32,
+    /// Maximum heightfield layers of a tile, e.g. the floors of a building
+    pub max_layers: u32,
+    pub max_obstacles: u32,
+}
+
+impl Default for TileCacheConfig {
+    fn default() -> TileCacheConfig {
+        TileCacheConfig {
+            tile_size: 48,
+            max_layers: 4,
+            max_obstacles: 128,
+        }
+    }
+}
+
+/// A tiled navmesh keeping the rasterized heightfield layers of its tiles,
+/// so the tiles touched by an obstacle are rebuilt without rasterizing the geometry again.
+///
+/// The layers are partitioned like `Partition::Layers` and the tiles have no detail mesh.
+/// Obstacle changes are queued and applied by `update`.
+pub struct TileCache {
+    cache: ptr::NonNull<c_void>,
+    query: RecastQuery,
+    grid: TileGrid,
+    /// Called by the C side, boxed twice for a thin pointer
+    _compressor: Option<Box<Box<dyn TileCompressor>>>,
+}
+
+impl fmt::Debug for TileCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TileCache")
+            .field("cache", &self.cache)
+            .field("query", &self.query)
+            .field("grid", &self.grid)
+            .finish()
+    }
+}
+
+impl TileCache {
+    /// Build with the layers compressed by fastlz
+    pub fn build(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        cache_config: &TileCacheConfig,
+    ) -> Result<TileCache> {
+        TileCache::build_impl(geometry, config, cache_config, None)
+    }
+
+    pub fn build_with_compressor<C: TileCompressor + 'static>(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        cache_config: &TileCacheConfig,
+        compressor: C,
+    ) -> Result<TileCache> {
+        TileCache::build_impl(
+            geometry,
+            config,
+            cache_config,
+            Some(Box::new(Box::new(compressor))),
+        )
+    }
+
+    fn build_impl(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        cache_config: &TileCacheConfig,
+        mut compressor: Option<Box<Box<dyn TileCompressor>>>,
+    ) -> Result<TileCache> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_compressor = compressor.as_mut().map(|c| sys::RecastCompressor {
+            user: &mut **c as *mut Box<dyn TileCompressor> as *mut c_void,
+            max_compressed_size: max_compressed_size_cb,
+            compress: compress_cb,
+            decompress: decompress_cb,
+        });
+
+        let grid = TileGrid::for_geometry(geometry, config, cache_config.tile_size)?;
+        let params = sys::RecastTileCacheParams {
+            bmin: grid.bmin,
+            bmax: grid.bmax,
+            tile_size: cache_config.tile_size as i32,
+            max_layers: cache_config.max_layers as i32,
+            max_obstacles: cache_config.max_obstacles as i32,
+            mesh: grid.mesh_params(cache_config.max_layers)?.as_sys(),
+            compressor: sys_compressor
+                .as_ref()
+                .map_or(ptr::null(), |c| c as *const _),
+        };
+
+        let sys_input = SysInput::new(geometry);
+        let mut q = ptr::null();
+        let mut err = sys::RecastNavError::zeros();
+
+        let cache = unsafe {
+            sys::recastc_build_tile_cache(
+                &sys_input.as_sys() as *const _,
+                &config.as_sys() as *const _,
+                &params as *const _,
+                &mut q as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        let cache = ptr::NonNull::new(cache as *mut c_void)
+            .ok_or_else(|| Error::TileCacheError(err.msg().into_owned()))?;
+        let query = RecastQuery::from_ptr(ptr::NonNull::new(q as *mut c_void).unwrap());
+
+        Ok(TileCache {
+            cache,
+            query,
+            grid,
+            _compressor: compressor,
+        })
+    }
+
+    /// The query over the navmesh. The C tile cache keeps its navmesh, so changing the query goes
+    /// through the tile cache methods which keep the navmesh and its tiles
+    pub fn query(&self) -> &RecastQuery {
+        &self.query
+    }
+
+    /// See `RecastQuery::set_filter`
+    pub fn set_filter(&mut self, filter: QueryFilter) {
+        self.query.set_filter(filter)
+    }
+
+    /// See `RecastQuery::set_area_cost`
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) -> Result<()> {
+        self.query.set_area_cost(area, cost)
+    }
+
+    /// See `RecastQuery::set_poly_flags`, the flags are lost when an obstacle rebuilds the tile
+    pub fn set_poly_flags(&mut self, poly: PolyRef, flags: PolyFlags) -> Result<()> {
+        self.query.set_poly_flags(poly, flags)
+    }
+
+    pub fn grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    /// Queue a cylinder obstacle standing on `pos`, e.g. a crate or a parked vehicle
+    pub fn add_cylinder_obstacle(
+        &mut self,
+        pos: Point,
+        radius: f32,
+        height: f32,
+    ) -> Result<ObstacleId> {
+        let cache = self.cache.as_ptr();
+        add_obstacle(|obstacle, err| unsafe {
+            sys::recastc_add_cylinder_obstacle(cache, pos.0.as_ptr(), radius, height, obstacle, err)
+        })
+    }
+
+    /// Queue an axis aligned box obstacle
+    pub fn add_box_obstacle(&mut self, bmin: Point, bmax: Point) -> Result<ObstacleId> {
+        let cache = self.cache.as_ptr();
+        add_obstacle(|obstacle, err| unsafe {
+            sys::recastc_add_box_obstacle(cache, bmin.0.as_ptr(), bmax.0.as_ptr(), obstacle, err)
+        })
+    }
+
+    /// Queue a box obstacle rotated by `y_radians` around the y axis, e.g. a door or a barricade
+    pub fn add_oriented_box_obstacle(
+        &mut self,
+        center: Point,
+        half_extents: (f32, f32, f32),
+        y_radians: f32,
+    ) -> Result<ObstacleId> {
+        let cache = self.cache.as_ptr();
+        let half_extents = [half_extents.0, half_extents.1, half_extents.2];
+        add_obstacle(|obstacle, err| unsafe {
+            sys::recastc_add_oriented_box_obstacle(
+                cache,
+                center.0.as_ptr(),
+                half_extents.as_ptr(),
+                y_radians,
+                obstacle,
+                err,
+            )
+        })
+    }
+
+    /// Queue the removal of an obstacle
+    pub fn remove_obstacle(&mut self, id: ObstacleId) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res =
+            unsafe { sys::recastc_remove_obstacle(self.cache.as_ptr(), id.0, &mut err as *mut _) };
+
+        if res == 0 {
+            Err(Error::TileCacheError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Apply the queued obstacle changes and rebuild at most one touched tile,
+    /// returns true when the navmesh is up to date
+    pub fn update(&mut self, dt: f32) -> Result<bool> {
+        let mut up_to_date = 0u8;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_update_tile_cache(
+                self.cache.as_ptr(),
+                dt,
+                &mut up_to_date as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::TileCacheError(err.msg().to_string()))
+        } else {
+            Ok(up_to_date != 0)
+        }
+    }
+
+    /// Rebuild every touched tile now
+    pub fn update_all(&mut self) -> Result<()> {
+        while !self.update(0.0)? {}
+        Ok(())
+    }
+}
+
+impl TileCache {
+    /// A tile cache saved by RecastDemo, the "Save" of its TempObstacles sample writing the
+    /// `TileCacheSetHeader` with the `TSET` magic, ready for obstacles as a built one.
+    ///
+    /// The layers are the ones RecastDemo compressed with fastlz, rebuilt with the parameters
+    /// they were baked with. `area_flags` gives the poly flags of the areas of the layers as
+    /// `BuildConfig::area_flags` does. The off-mesh connections are saved with the input geometry
+    /// rather than in the set and are not loaded. The file is little endian, and the reading
+    /// stops at a tile with no ref or no data as in RecastDemo
+    pub fn from_recast_demo(mut bytes: &[u8], area_flags: &[PolyFlags; 64]) -> Result<TileCache> {
+        let r = &mut bytes;
+
+        let magic = read_u32(r).map_err(read_error)?;
+        if magic != RECAST_DEMO_MAGIC || read_u32(r).map_err(read_error)? != RECAST_DEMO_VERSION {
+            return Err(Error::SerializeError(
+                "not a RecastDemo tile cache set of this version".into(),
+            ));
+        }
+        let n = read_u32(r).map_err(read_error)?;
+        let mesh = read_params(r)?;
+        mesh.validate()?;
+        let params = read_cache_params(r).map_err(read_error)?;
+        if params.width != params.height {
+            return Err(Error::TileCacheError(format!(
+                "tiles of {}x{} cells, only square tiles are supported",
+                params.width, params.height
+            )));
+        }
+
+        let mut flags = [0; 64];
+        for (f, a) in flags.iter_mut().zip(area_flags.iter()) {
+            *f = a.0;
+        }
+        let mut q = ptr::null();
+        let mut err = sys::RecastNavError::zeros();
+
+        let cache = unsafe {
+            sys::recastc_create_tile_cache(
+                &params as *const _,
+                &mesh.as_sys() as *const _,
+                flags.as_ptr(),
+                &mut q as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        let cache = ptr::NonNull::new(cache as *mut c_void)
+            .ok_or_else(|| Error::TileCacheError(err.msg().into_owned()))?;
+        let query = RecastQuery::from_ptr(ptr::NonNull::new(q as *mut c_void).unwrap());
+        let tile_size = params.width as u32;
+        let mut tc = TileCache {
+            cache,
+            query,
+            grid: TileGrid::new(params.orig, params.orig, params.cs, tile_size),
+            _compressor: None,
+        };
+
+        // The grid is not saved, it covers the layers
+        let (mut cols, mut rows, mut top) = (0, 0, params.orig[1]);
+        for _ in 0..n {
+            // TileCacheTileHeader, a dtCompressedTileRef is 32 bits in any build
+            let layer_ref = read_u32(r).map_err(read_error)?;
+            let len = read_u32(r).map_err(read_error)?;
+            if layer_ref == 0 || len == 0 {
+                break;
+            }
+            let data = read_slice(r, len as usize)?;
+            tc.add_layer(data)?;
+
+            // tx, ty then bmax[1] of the dtTileCacheLayerHeader, checked by the C side
+            let mut header = &data[8..];
+            let tx = read_u32(&mut header).map_err(read_error)? as i32;
+            let ty = read_u32(&mut header).map_err(read_error)? as i32;
+            if tx < 0 || ty < 0 || tx == i32::MAX || ty == i32::MAX {
+                return Err(Error::TileCacheError(format!(
+                    "layer of tile ({}, {}) out of the grid",
+                    tx, ty
+                )));
+            }
+            let mut header = &data[36..];
+            top = read_f32(&mut header).map_err(read_error)?.max(top);
+            cols = cols.max(tx + 1);
+            rows = rows.max(ty + 1);
+        }
+
+        let tile_width = tile_size as f32 * params.cs;
+        let bmax = [
+            params.orig[0] + cols as f32 * tile_width,
+            top,
+            params.orig[2] + rows as f32 * tile_width,
+        ];
+        tc.grid = TileGrid::new(params.orig, bmax, params.cs, tile_size);
+        Ok(tc)
+    }
+
+    /// The tile cache as the TempObstacles sample of RecastDemo saves it, for `from_recast_demo`
+    /// or RecastDemo to load. The layers are saved without the obstacles, compressed as they are
+    /// in the tile cache: RecastDemo reads fastlz only
+    pub fn save_recast_demo(&self) -> Result<Vec<u8>> {
+        let mut params = sys::RecastSavedTileCacheParams::default();
+        unsafe { sys::recastc_get_tile_cache_params(self.cache.as_ptr(), &mut params as *mut _) };
+        let mesh = self.query.nav_mesh_params()?;
+        let layers = self.saved_layers(params.max_tiles)?;
+
+        // The dtNavMeshParams then the dtTileCacheParams as they are in memory
+        let p = &params;
+        let mut words = vec![RECAST_DEMO_MAGIC, RECAST_DEMO_VERSION, layers.len() as u32];
+        words.extend(mesh.origin.iter().map(|v| v.to_bits()));
+        words.extend_from_slice(&[
+            mesh.tile_width.to_bits(),
+            mesh.tile_height.to_bits(),
+            mesh.max_tiles,
+            mesh.max_polys,
+        ]);
+        words.extend(p.orig.iter().map(|v| v.to_bits()));
+        words.extend_from_slice(&[
+            p.cs.to_bits(),
+            p.ch.to_bits(),
+            p.width as u32,
+            p.height as u32,
+            p.walkable_height.to_bits(),
+            p.walkable_radius.to_bits(),
+            p.walkable_climb.to_bits(),
+            p.max_simplification_error.to_bits(),
+            p.max_tiles as u32,
+            p.max_obstacles as u32,
+        ]);
+
+        let mut out = vec![];
+        for w in &words {
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        for (layer_ref, data) in layers {
+            out.extend_from_slice(&layer_ref.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        Ok(out)
+    }
+
+    fn add_layer(&mut self, data: &[u8]) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_add_tile_cache_layer(
+                self.cache.as_ptr(),
+                data.as_ptr(),
+                data.len() as i32,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            Err(Error::TileCacheError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The refs and compressed data of the layers in slot order
+    fn saved_layers(&self, max_tiles: i32) -> Result<Vec<(u32, &[u8])>> {
+        let mut layers = vec![];
+        for index in 0..max_tiles {
+            let mut layer_ref = 0;
+            let mut data: *const u8 = ptr::null();
+            let mut len = 0;
+            let mut err = sys::RecastNavError::zeros();
+
+            let res = unsafe {
+                sys::recastc_get_tile_cache_layer(
+                    self.cache.as_ptr(),
+                    index,
+                    &mut layer_ref as *mut _,
+                    &mut data as *mut _,
+                    &mut len as *mut _,
+                    &mut err as *mut _,
+                )
+            };
+
+            if res == 0 {
+                return Err(Error::TileCacheError(err.msg().to_string()));
+            }
+            if !data.is_null() {
+                layers.push((layer_ref, unsafe {
+                    slice::from_raw_parts(data, len as usize)
+                }));
+            }
+        }
+        Ok(layers)
+    }
+}
+
+/// The `dtTileCacheParams` of a `TileCacheSetHeader`
+fn read_cache_params<R: io::Read>(r: &mut R) -> io::Result<sys::RecastSavedTileCacheParams> {
+    Ok(sys::RecastSavedTileCacheParams {
+        orig: [read_f32(r)?, read_f32(r)?, read_f32(r)?],
+        cs: read_f32(r)?,
+        ch: read_f32(r)?,
+        width: read_u32(r)? as i32,
+        height: read_u32(r)? as i32,
+        walkable_height: read_f32(r)?,
+        walkable_radius: read_f32(r)?,
+        walkable_climb: read_f32(r)?,
+        max_simplification_error: read_f32(r)?,
+        max_tiles: read_u32(r)? as i32,
+        max_obstacles: read_u32(r)? as i32,
+    })
+}
+
+fn add_obstacle<F>(f: F) -> Result<ObstacleId>
+where
+    F: FnOnce(*mut u32, *mut sys::RecastNavError) -> i32,
+{
+    let mut obstacle = 0;
+    let mut err = sys::RecastNavError::zeros();
+
+    if f(&mut obstacle as *mut _, &mut err as *mut _) == 0 {
+        Err(Error::TileCacheError(err.msg().to_string()))
+    } else {
+        Ok(ObstacleId(obstacle))
+    }
+}
+
+impl Drop for TileCache {
+    fn drop(&mut self) {
+        // Before the query which owns the navmesh
+        unsafe { sys::recastc_free_tile_cache(self.cache.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+
+    #[test]
+    fn test_cylinder_obstacle() {
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let mut cache = TileCache::build(&plane(10.0), &BuildConfig::default(), &config).unwrap();
+        let center = (5.0, 0.0, 5.0).into();
+        let ext = (0.1, 1.0, 0.1);
+        assert!(cache.query().find_poly(center, ext).is_ok());
+
+        let id = cache.add_cylinder_obstacle(center, 1.0, 2.0).unwrap();
+        cache.update_all().unwrap();
+        assert!(cache.query().find_poly(center, ext).is_err());
+        cache
+            .query()
+            .find_path(
+                (1.0, 0.0, 1.0).into(),
+                (9.0, 0.0, 9.0).into(),
+                (0.5, 1.0, 0.5),
+            )
+            .unwrap();
+
+        cache.remove_obstacle(id).unwrap();
+        cache.update_all().unwrap();
+        assert!(cache.query().find_poly(center, ext).is_ok());
+        assert!(cache.remove_obstacle(id).is_err());
+    }
+
+    #[test]
+    fn test_filter_and_flags() {
+        let mut cache = TileCache::build(
+            &plane(10.0),
+            &BuildConfig::default(),
+            &TileCacheConfig::default(),
+        )
+        .unwrap();
+        let center = (5.0, 0.0, 5.0).into();
+        let ext = (0.1, 1.0, 0.1);
+        let (_, poly) = cache.query().find_poly(center, ext).unwrap();
+
+        cache.set_poly_flags(poly, PolyFlags::DOOR).unwrap();
+        cache.set_filter(QueryFilter::default().exclude(PolyFlags::DOOR));
+        assert!(cache.query().find_poly(center, ext).is_err());
+        cache.set_area_cost(0, 2.0).unwrap();
+        assert_eq!(cache.query().filter().area_costs[0], 2.0);
+    }
+
+    #[test]
+    fn test_box_obstacles() {
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let mut cache = TileCache::build(&plane(10.0), &BuildConfig::default(), &config).unwrap();
+        let ext = (0.1, 1.0, 0.1);
+
+        cache
+            .add_box_obstacle((2.0, -1.0, 2.0).into(), (4.0, 1.0, 4.0).into())
+            .unwrap();
+        // A thin wall along the diagonal
+        cache
+            .add_oriented_box_obstacle(
+                (7.0, 0.0, 7.0).into(),
+                (1.5, 1.0, 0.2),
+                std::f32::consts::FRAC_PI_4,
+            )
+            .unwrap();
+        cache.update_all().unwrap();
+
+        assert!(cache
+            .query()
+            .find_poly((3.0, 0.0, 3.0).into(), ext)
+            .is_err());
+        assert!(cache
+            .query()
+            .find_poly((7.0, 0.0, 7.0).into(), ext)
+            .is_err());
+        // Beside the wall, a cylinder covering the wall would carve it too
+        assert!(cache.query().find_poly((7.0, 0.0, 8.2).into(), ext).is_ok());
+    }
+
+    /// Run length encoding, the layers are mostly runs of the same height and area
+    struct Rle;
+
+    impl TileCompressor for Rle {
+        fn max_compressed_size(&self, size: usize) -> usize {
+            size * 2
+        }
+
+        fn compress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize> {
+            let mut n = 0;
+            let mut i = 0;
+            while i < data.len() {
+                let len = data[i..]
+                    .iter()
+                    .take(255)
+                    .take_while(|b| **b == data[i])
+                    .count();
+                *out.get_mut(n)? = len as u8;
+                *out.get_mut(n + 1)? = data[i];
+                n += 2;
+                i += len;
+            }
+            Some(n)
+        }
+
+        fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Option<usize> {
+            let mut n = 0;
+            for pair in data.chunks(2) {
+                let len = pair[0] as usize;
+                out.get_mut(n..n + len)?
+                    .iter_mut()
+                    .for_each(|b| *b = pair[1]);
+                n += len;
+            }
+            Some(n)
+        }
+    }
+
+    #[test]
+    fn test_custom_compressor() {
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let geom = plane(10.0);
+        let build = BuildConfig::default();
+
+        let mut cache = TileCache::build_with_compressor(&geom, &build, &config, Rle).unwrap();
+        let center = (5.0, 0.0, 5.0).into();
+        cache.add_cylinder_obstacle(center, 1.0, 2.0).unwrap();
+        cache.update_all().unwrap();
+        assert!(cache.query().find_poly(center, (0.1, 1.0, 0.1)).is_err());
+
+        let stored = TileCache::build_with_compressor(&geom, &build, &config, NoCompression);
+        assert!(stored.is_ok());
+    }
+
+    struct Panicking;
+
+    impl TileCompressor for Panicking {
+        fn max_compressed_size(&self, size: usize) -> usize {
+            size
+        }
+
+        fn compress(&mut self, _: &[u8], _: &mut [u8]) -> Option<usize> {
+            panic!("compressor bug")
+        }
+
+        fn decompress(&mut self, _: &[u8], _: &mut [u8]) -> Option<usize> {
+            panic!("compressor bug")
+        }
+    }
+
+    #[test]
+    fn test_panicking_compressor() {
+        // The panic stops at the callback, the build fails
+        let build = TileCache::build_with_compressor(
+            &plane(10.0),
+            &BuildConfig::default(),
+            &TileCacheConfig::default(),
+            Panicking,
+        );
+        assert!(build.is_err());
+    }
+
+    #[test]
+    fn test_tile_size_too_large() {
+        let config = TileCacheConfig {
+            tile_size: 300,
+            ..Default::default()
+        };
+        assert!(TileCache::build(&plane(10.0), &BuildConfig::default(), &config).is_err());
+    }
+
+    #[test]
+    fn test_recast_demo_set() {
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let build = BuildConfig::default();
+        let mut cache = TileCache::build(&plane(10.0), &build, &config).unwrap();
+        let center = (5.0, 0.0, 5.0).into();
+        let ext = (0.1, 1.0, 0.1);
+        cache.add_cylinder_obstacle(center, 1.0, 2.0).unwrap();
+        cache.update_all().unwrap();
+
+        let bytes = cache.save_recast_demo().unwrap();
+        assert_eq!(&bytes[..4], b"TEST");
+
+        // Saved without the obstacle, which can be added again
+        let mut loaded = TileCache::from_recast_demo(&bytes, &build.area_flags).unwrap();
+        let fresh = TileCache::build(&plane(10.0), &build, &config).unwrap();
+        assert_eq!(
+            loaded.query().polys().unwrap().len(),
+            fresh.query().polys().unwrap().len()
+        );
+        assert_eq!(loaded.grid().cols, cache.grid().cols);
+        assert_eq!(loaded.grid().rows, cache.grid().rows);
+        assert!(loaded.query().find_poly(center, ext).is_ok());
+
+        loaded.add_cylinder_obstacle(center, 1.0, 2.0).unwrap();
+        loaded.update_all().unwrap();
+        assert!(loaded.query().find_poly(center, ext).is_err());
+        assert_eq!(loaded.save_recast_demo().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_recast_demo_set() {
+        let config = TileCacheConfig {
+            tile_size: 16,
+            ..Default::default()
+        };
+        let flags = BuildConfig::default().area_flags;
+        let cache = TileCache::build(&plane(10.0), &BuildConfig::default(), &config).unwrap();
+        let bytes = cache.save_recast_demo().unwrap();
+        let msg = |bytes: &[u8]| match TileCache::from_recast_demo(bytes, &flags) {
+            Err(Error::SerializeError(msg)) | Err(Error::TileCacheError(msg)) => msg,
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("loaded"),
+        };
+
+        let navmesh = cache.query().serialize().unwrap();
+        assert_eq!(
+            msg(&navmesh),
+            "not a RecastDemo tile cache set of this version"
+        );
+        assert!(TileCache::from_recast_demo(&bytes[..bytes.len() - 1], &flags).is_err());
+
+        // The magic of the first layer, after the set header and its tile header
+        let mut other_magic = bytes.clone();
+        other_magic[100..104].copy_from_slice(b"LAYR");
+        assert_eq!(msg(&other_magic), "Not tile cache layer data");
+
+        // The tx of the first layer, after its magic and version
+        let mut negative = bytes.clone();
+        negative[108..112].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = msg(&negative);
+        assert!(err.starts_with("layer of tile (-1, ") && err.ends_with("out of the grid"));
+        let mut overflowing = bytes.clone();
+        overflowing[108..112].copy_from_slice(&(i32::MAX as u32).to_le_bytes());
+        assert!(msg(&overflowing).contains("out of the grid"));
+
+        // The width of the dtTileCacheParams
+        let mut not_square = bytes;
+        not_square[60..64].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(
+            msg(&not_square),
+            "tiles of 8x16 cells, only square tiles are supported"
+        );
+    }
+}
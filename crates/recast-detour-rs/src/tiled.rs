@@ -0,0 +1,720 @@
+//! Tiled navmesh builds for worlds too large for a single mesh
+use crate::recast::SysInput;
+use crate::{
+    compute_bb, read_polys, BuildConfig, Error, InputGeometry, PolyRef, RecastQuery, Result,
+};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Largest tile count of the default params of a grid, the remaining poly ref bits are used by the polys
+pub const MAX_TILES: u32 = 1 << 14;
+
+/// Poly ref bits shared by the tile and the poly indices, the 10 others are the salt of the tiles
+const REF_BITS: u32 = 22;
+
+/// Layout of a tiled navmesh, see `dtNavMeshParams`.
+///
+/// `max_tiles` and `max_polys` split the bits of the poly refs, e.g. a streaming world keeps
+/// `max_tiles` to the tiles loaded at once rather than every tile of the world
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NavMeshParams {
+    /// World position of the tile (0, 0) corner
+    pub origin: [f32; 3],
+    /// Width of a tile along x in world unit
+    pub tile_width: f32,
+    /// Width of a tile along z in world unit
+    pub tile_height: f32,
+    /// Most tiles in the navmesh at once, rounded up to a power of two
+    pub max_tiles: u32,
+    /// Most polys per tile, rounded up to a power of two
+    pub max_polys: u32,
+}
+
+impl NavMeshParams {
+    /// Poly ref bits of the tile index
+    pub fn tile_bits(&self) -> u32 {
+        self.max_tiles.max(1).next_power_of_two().trailing_zeros()
+    }
+
+    /// Poly ref bits of the poly index within a tile
+    pub fn poly_bits(&self) -> u32 {
+        self.max_polys.max(1).next_power_of_two().trailing_zeros()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.max_tiles == 0 || self.max_polys == 0 {
+            return Err(Error::BuildError(
+                "max_tiles and max_polys must be positive".into(),
+            ));
+        }
+        if self.tile_width <= 0.0 || self.tile_height <= 0.0 {
+            return Err(Error::BuildError("tile size must be positive".into()));
+        }
+        if self.tile_bits() + self.poly_bits() > REF_BITS {
+            return Err(Error::BuildError(format!(
+                "{} tile bits and {} poly bits exceed the {} bits of a poly ref",
+                self.tile_bits(),
+                self.poly_bits(),
+                REF_BITS
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn as_sys(&self) -> sys::RecastTiledMeshParams {
+        sys::RecastTiledMeshParams {
+            orig: self.origin,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            max_tiles: self.max_tiles as i32,
+            max_polys: self.max_polys as i32,
+        }
+    }
+}
+
+/// The tiles covering the bounds of the input geometry
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileGrid {
+    /// World bounds in world unit
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    /// Cell size of the build in world unit
+    pub cell_size: f32,
+    /// Width and depth of a tile in cells
+    pub tile_size: u32,
+    /// Number of tiles along x
+    pub cols: i32,
+    /// Number of tiles along z
+    pub rows: i32,
+}
+
+impl TileGrid {
+    pub fn new(bmin: [f32; 3], bmax: [f32; 3], cell_size: f32, tile_size: u32) -> TileGrid {
+        // Same as rcCalcGridSize
+        let gw = ((bmax[0] - bmin[0]) / cell_size + 0.5) as i32;
+        let gh = ((bmax[2] - bmin[2]) / cell_size + 0.5) as i32;
+        let ts = tile_size.max(1) as i32;
+
+        TileGrid {
+            bmin,
+            bmax,
+            cell_size,
+            tile_size,
+            cols: (gw + ts - 1) / ts,
+            rows: (gh + ts - 1) / ts,
+        }
+    }
+
+    /// The grid covering the whole geometry
+    pub fn for_geometry(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<TileGrid> {
+        if geometry.vertices.is_empty() {
+            return Err(Error::BuildError("Input geometry is empty".into()));
+        }
+        if tile_size == 0 {
+            return Err(Error::BuildError("tile_size must be positive".into()));
+        }
+
+        let (bmin, bmax) = compute_bb(&geometry.vertices);
+        Ok(TileGrid::new(bmin, bmax, config.cell_size, tile_size))
+    }
+
+    /// Width and depth of a tile in world unit
+    pub fn tile_world_size(&self) -> f32 {
+        self.tile_size as f32 * self.cell_size
+    }
+
+    /// Build the data of one tile of the geometry, `None` when the tile has no walkable surface
+    pub fn build_tile(
+        &self,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tx: i32,
+        ty: i32,
+    ) -> Result<Option<TileData>> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let sys_input = SysInput::new(geometry);
+        build_tile(&sys_input.as_sys(), &config.as_sys(), self, tx, ty)
+    }
+
+    /// Tile coordinates, row by row
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32)> {
+        let cols = self.cols;
+        (0..self.rows).flat_map(move |ty| (0..cols).map(move |tx| (tx, ty)))
+    }
+
+    /// The tile containing the point
+    pub fn tile_at(&self, x: f32, z: f32) -> (i32, i32) {
+        let size = self.tile_world_size();
+        (
+            ((x - self.bmin[0]) / size).floor() as i32,
+            ((z - self.bmin[2]) / size).floor() as i32,
+        )
+    }
+
+    /// The inclusive range of the tiles whose build reads geometry in the xz rectangle,
+    /// a tile build rasterizes a border of `walkable_radius` plus 3 cells around the tile
+    pub fn tiles_overlapping(
+        &self,
+        config: &BuildConfig,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> ((i32, i32), (i32, i32)) {
+        let border = tile_border(config);
+
+        let clamp =
+            |(tx, ty): (i32, i32)| (tx.max(0).min(self.cols - 1), ty.max(0).min(self.rows - 1));
+
+        (
+            clamp(self.tile_at(min.0 - border, min.1 - border)),
+            clamp(self.tile_at(max.0 + border, max.1 + border)),
+        )
+    }
+
+    /// The xz rectangle of the geometry read by the build of a tile, its border included
+    pub fn tile_bounds(&self, config: &BuildConfig, tx: i32, ty: i32) -> ((f32, f32), (f32, f32)) {
+        let size = self.tile_world_size();
+        let border = tile_border(config);
+        let x = self.bmin[0] + tx as f32 * size;
+        let z = self.bmin[2] + ty as f32 * size;
+
+        (
+            (x - border, z - border),
+            (x + size + border, z + size + border),
+        )
+    }
+
+    /// The params fitting every tile of the grid, the remaining poly ref bits go to the polys
+    /// as in Sample_TileMesh
+    pub fn nav_mesh_params(&self) -> Result<NavMeshParams> {
+        self.mesh_params(1)
+    }
+
+    /// The params of the grid layout with a custom split of the poly ref bits
+    pub fn nav_mesh_params_with(&self, max_tiles: u32, max_polys: u32) -> NavMeshParams {
+        let size = self.tile_world_size();
+        NavMeshParams {
+            origin: self.bmin,
+            tile_width: size,
+            tile_height: size,
+            max_tiles,
+            max_polys,
+        }
+    }
+
+    /// A tile cache stores up to `layers` navmesh tiles per grid tile
+    pub(crate) fn mesh_params(&self, layers: u32) -> Result<NavMeshParams> {
+        let n_tiles = (self.cols.max(1) * self.rows.max(1)) as u32 * layers;
+        if n_tiles > MAX_TILES {
+            return Err(Error::BuildError(format!(
+                "{} tiles exceed the maximum of {}, increase the tile size or use custom NavMeshParams",
+                n_tiles, MAX_TILES
+            )));
+        }
+
+        let tile_bits = n_tiles.next_power_of_two().trailing_zeros();
+        Ok(self.nav_mesh_params_with(1 << tile_bits, 1 << (REF_BITS - tile_bits)))
+    }
+
+    pub(crate) fn tile_input(&self, tx: i32, ty: i32) -> sys::RecastTileInput {
+        sys::RecastTileInput {
+            tile_size: self.tile_size as i32,
+            bmin: self.bmin,
+            bmax: self.bmax,
+            tx,
+            ty,
+        }
+    }
+}
+
+/// Border of a tile build in world unit, same as `init_tile_config`
+fn tile_border(config: &BuildConfig) -> f32 {
+    let cs = config.cell_size;
+    if config.tile_border_size > 0 {
+        config.tile_border_size as f32 * cs
+    } else {
+        ((config.walkable_radius / cs).ceil() + 3.0) * cs
+    }
+}
+
+/// Detour data of a single tile, e.g. stored to swap the tile back in later
+#[derive(Debug)]
+pub struct TileData {
+    data: ptr::NonNull<u8>,
+    len: usize,
+}
+
+impl TileData {
+    /// Copy tile data, e.g. read from a file, `add_tile` validates it
+    pub fn from_bytes(bytes: &[u8]) -> Result<TileData> {
+        let data = unsafe { sys::recastc_alloc_tile_data(bytes.len() as i32) };
+        let data = ptr::NonNull::new(data)
+            .ok_or_else(|| Error::BuildError("Out of memory 'tile data'".into()))?;
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), data.as_ptr(), bytes.len()) };
+
+        Ok(TileData {
+            data,
+            len: bytes.len(),
+        })
+    }
+
+    /// Take the ownership of tile data allocated by the C api, `None` for null data
+    pub(crate) fn from_raw(data: *mut u8, len: usize) -> Option<TileData> {
+        ptr::NonNull::new(data).map(|data| TileData { data, len })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+
+    /// Give up the ownership of the data, to the C api
+    pub(crate) fn into_raw(self) -> (*mut u8, usize) {
+        let raw = (self.data.as_ptr(), self.len);
+        std::mem::forget(self);
+        raw
+    }
+}
+
+// The data is a plain buffer owned by the TileData alone
+unsafe impl Send for TileData {}
+
+impl Drop for TileData {
+    fn drop(&mut self) {
+        unsafe { sys::recastc_free_tile_data(self.data.as_ptr()) }
+    }
+}
+
+/// Build the detour data of one tile, `None` when the tile has no walkable surface
+pub(crate) fn build_tile(
+    input: &sys::RecastBuildInput,
+    config: &sys::RecastBuildConfig,
+    grid: &TileGrid,
+    tx: i32,
+    ty: i32,
+) -> Result<Option<TileData>> {
+    let tile = grid.tile_input(tx, ty);
+    let mut data = ptr::null_mut();
+    let mut len = 0;
+    let mut err = sys::RecastNavError::zeros();
+
+    let res = unsafe {
+        sys::recastc_build_tile(
+            input as *const _,
+            config as *const _,
+            &tile as *const _,
+            &mut data as *mut _,
+            &mut len as *mut _,
+            &mut err as *mut _,
+        )
+    };
+
+    if res == 0 {
+        return Err(Error::BuildError(format!(
+            "tile ({}, {}): {}",
+            tx,
+            ty,
+            err.msg()
+        )));
+    }
+
+    Ok(TileData::from_raw(data, len as usize))
+}
+
+impl RecastQuery {
+    /// Build a tiled navmesh, `tile_size` is the width and depth of a tile in cells.
+    ///
+    /// Each tile is a separate Detour tile, so the vertex and poly limits apply per tile.
+    pub fn build_tiled(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<RecastQuery> {
+        geometry.validate()?;
+        config.validate()?;
+
+        let grid = TileGrid::for_geometry(geometry, config, tile_size)?;
+        let mut q = RecastQuery::create_tiled(&grid)?;
+
+        let sys_input = SysInput::new(geometry);
+        let input = sys_input.as_sys();
+        let sys_config = config.as_sys();
+
+        for (tx, ty) in grid.tiles() {
+            if let Some(tile) = build_tile(&input, &sys_config, &grid, tx, ty)? {
+                q.add_tile(tile)?;
+            }
+        }
+
+        Ok(q)
+    }
+
+    /// Same as `build_tiled` with the tiles built on the rayon thread pool, then added in grid order
+    #[cfg(feature = "rayon")]
+    pub fn build_tiles_parallel(
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tile_size: u32,
+    ) -> Result<RecastQuery> {
+        use rayon::prelude::*;
+
+        geometry.validate()?;
+        config.validate()?;
+
+        let grid = TileGrid::for_geometry(geometry, config, tile_size)?;
+        let mut q = RecastQuery::create_tiled(&grid)?;
+
+        let sys_input = SysInput::new(geometry);
+        let sys_config = config.as_sys();
+
+        let coords: Vec<_> = grid.tiles().collect();
+        let tiles = coords
+            .par_iter()
+            .map(|&(tx, ty)| build_tile(&sys_input.as_sys(), &sys_config, &grid, tx, ty))
+            .collect::<Result<Vec<_>>>()?;
+
+        for tile in tiles.into_iter().flatten() {
+            q.add_tile(tile)?;
+        }
+
+        Ok(q)
+    }
+
+    /// A query over an empty tiled navmesh laid out by the grid
+    pub(crate) fn create_tiled(grid: &TileGrid) -> Result<RecastQuery> {
+        RecastQuery::create_tiled_with_params(&grid.nav_mesh_params()?)
+    }
+
+    /// A query over an empty tiled navmesh, the tiles are added with `add_tile`.
+    /// Tiles must be built by a grid with the same origin and tile size
+    pub fn create_tiled_with_params(params: &NavMeshParams) -> Result<RecastQuery> {
+        params.validate()?;
+        let params = params.as_sys();
+        let mut err = sys::RecastNavError::zeros();
+
+        let q = unsafe {
+            ptr::NonNull::new(sys::recastc_create_tiled_query(
+                &params as *const _,
+                &mut err as *mut _,
+            ) as *mut c_void)
+        };
+
+        let q = q.ok_or_else(|| Error::CreateQueryError(err.msg().into_owned()))?;
+        Ok(RecastQuery::from_ptr(q))
+    }
+
+    /// Add a tile to a tiled navmesh, the tile must be built with the same `TileGrid`
+    pub fn add_tile(&mut self, tile: TileData) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+        // The navmesh owns the data from now on, even when adding fails
+        let (data, len) = tile.into_raw();
+
+        let res =
+            unsafe { sys::recastc_add_tile(self.q.as_ptr(), data, len as i32, &mut err as *mut _) };
+
+        if res == 0 {
+            Err(Error::BuildError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remove the tile at the tile coordinates, the poly refs of the tile become invalid
+    pub fn remove_tile(&mut self, tx: i32, ty: i32) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe { sys::recastc_remove_tile(self.q.as_ptr(), tx, ty, &mut err as *mut _) };
+
+        if res == 0 {
+            Err(Error::BuildError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remove one layer of the tile at the tile coordinates
+    pub(crate) fn remove_tile_layer(&mut self, tx: i32, ty: i32, layer: i32) -> Result<()> {
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_remove_tile_layer(self.q.as_ptr(), tx, ty, layer, &mut err as *mut _)
+        };
+
+        if res == 0 {
+            Err(Error::BuildError(err.msg().to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// A copy of the data of the tile at the tile coordinates
+    pub fn tile_data(&self, tx: i32, ty: i32) -> Result<TileData> {
+        let mut data = ptr::null();
+        let mut len = 0;
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_tile_data(
+                self.q.as_ptr(),
+                tx,
+                ty,
+                &mut data as *mut _,
+                &mut len as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::BuildError(err.msg().to_string()));
+        }
+
+        TileData::from_bytes(unsafe { std::slice::from_raw_parts(data, len as usize) })
+    }
+
+    /// Replace the tile at the tile coordinates with a fresh build of the geometry,
+    /// returns false when the tile has no walkable surface anymore
+    pub fn rebuild_tile(
+        &mut self,
+        grid: &TileGrid,
+        geometry: &InputGeometry,
+        config: &BuildConfig,
+        tx: i32,
+        ty: i32,
+    ) -> Result<bool> {
+        let tile = grid.build_tile(geometry, config, tx, ty)?;
+        self.replace_tile(tx, ty, tile)
+    }
+
+    pub(crate) fn replace_tile(
+        &mut self,
+        tx: i32,
+        ty: i32,
+        tile: Option<TileData>,
+    ) -> Result<bool> {
+        // The tile is missing when it had no walkable surface
+        let _ = self.remove_tile(tx, ty);
+        match tile {
+            Some(tile) => self.add_tile(tile).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Replace several tiles, all or none: on a failure the tiles replaced so far get their
+    /// previous data back. Returns the number of new tiles with a walkable surface
+    pub(crate) fn replace_tiles(
+        &mut self,
+        tiles: Vec<((i32, i32), Option<TileData>)>,
+    ) -> Result<usize> {
+        let mut previous = Vec::with_capacity(tiles.len());
+        let mut walkable = 0;
+        for ((tx, ty), tile) in tiles {
+            // No previous data for a tile without walkable surface
+            previous.push(((tx, ty), self.tile_data(tx, ty).ok()));
+            match self.replace_tile(tx, ty, tile) {
+                Ok(true) => walkable += 1,
+                Ok(false) => {}
+                Err(err) => {
+                    for ((tx, ty), old) in previous.into_iter().rev() {
+                        // The data was in the navmesh a moment ago, it goes back in
+                        let _ = self.replace_tile(tx, ty, old);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(walkable)
+    }
+
+    /// The polys of the tile passing the query filter, empty when there is no tile
+    pub fn tile_polys(&self, tx: i32, ty: i32) -> Result<Vec<PolyRef>> {
+        read_polys(|polys, max_polys, count, err| unsafe {
+            sys::recastc_get_tile_polys(self.q.as_ptr(), tx, ty, polys, max_polys, count, err)
+        })
+    }
+
+    /// The polys of the tiles in the inclusive tile rectangle, e.g. to analyse a district of the world
+    pub fn polys_in_tiles(&self, min: (i32, i32), max: (i32, i32)) -> Result<Vec<PolyRef>> {
+        let mut polys = vec![];
+        for ty in min.1..=max.1 {
+            for tx in min.0..=max.0 {
+                polys.extend(self.tile_polys(tx, ty)?);
+            }
+        }
+        Ok(polys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+
+    #[test]
+    fn test_tile_grid() {
+        let grid = TileGrid::new([0.0; 3], [10.0, 1.0, 10.0], 0.3, 16);
+
+        assert_eq!((grid.cols, grid.rows), (3, 3));
+        assert_eq!(grid.tiles().count(), 9);
+        assert_eq!(grid.tile_at(5.0, 9.9), (1, 2));
+
+        let params = grid.nav_mesh_params().unwrap();
+        assert_eq!(params.max_tiles, 16);
+        assert_eq!(params.max_polys, 1 << 18);
+        assert_eq!((params.tile_bits(), params.poly_bits()), (4, 18));
+
+        let huge = TileGrid::new([0.0; 3], [4000.0, 1.0, 4000.0], 0.3, 16);
+        assert!(huge.nav_mesh_params().is_err());
+        assert!(huge
+            .nav_mesh_params_with(1 << 12, 1 << 10)
+            .validate()
+            .is_ok());
+        assert!(huge
+            .nav_mesh_params_with(1 << 12, 1 << 11)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_custom_params() {
+        // Fewer tiles than the grid, as if streaming, and more polys per tile
+        let geom = plane(10.0);
+        let config = BuildConfig::default();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let params = grid.nav_mesh_params_with(2, 1 << 16);
+        let mut q = RecastQuery::create_tiled_with_params(&params).unwrap();
+
+        q.add_tile(grid.build_tile(&geom, &config, 0, 0).unwrap().unwrap())
+            .unwrap();
+        q.add_tile(grid.build_tile(&geom, &config, 1, 0).unwrap().unwrap())
+            .unwrap();
+        assert!(q.find_poly((1.0, 0.0, 1.0).into(), (0.5, 1.0, 0.5)).is_ok());
+        assert!(q
+            .add_tile(grid.build_tile(&geom, &config, 0, 1).unwrap().unwrap())
+            .is_err());
+
+        q.remove_tile(1, 0).unwrap();
+        q.add_tile(grid.build_tile(&geom, &config, 0, 1).unwrap().unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_remove_and_add_tile() {
+        let geom = plane(10.0);
+        let config = BuildConfig::default();
+        let mut q = RecastQuery::build_tiled(&geom, &config, 16).unwrap();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let center = (7.0, 0.0, 7.0).into();
+        assert_eq!(grid.tile_at(7.0, 7.0), (1, 1));
+
+        let saved = q.tile_data(1, 1).unwrap();
+        q.remove_tile(1, 1).unwrap();
+        assert!(q.find_poly(center, (0.1, 1.0, 0.1)).is_err());
+        assert!(q.remove_tile(1, 1).is_err());
+
+        // `polyCount` of the header, past the magic, version, x, y, layer and user id
+        let mut corrupt = saved.as_bytes().to_vec();
+        corrupt[24..28].copy_from_slice(&i32::MAX.to_le_bytes());
+        let err = q.add_tile(TileData::from_bytes(&corrupt).unwrap());
+        assert!(err.unwrap_err().to_string().contains("INVALID_TILE_DATA"));
+
+        q.add_tile(TileData::from_bytes(saved.as_bytes()).unwrap())
+            .unwrap();
+        assert!(q.find_poly(center, (0.1, 1.0, 0.1)).is_ok());
+        assert!(q.add_tile(saved).is_err());
+
+        q.remove_tile(1, 1).unwrap();
+        let rebuilt = grid.build_tile(&geom, &config, 1, 1).unwrap().unwrap();
+        q.add_tile(rebuilt).unwrap();
+        q.find_path(
+            (1.0, 0.0, 1.0).into(),
+            (9.0, 0.0, 9.0).into(),
+            (0.5, 1.0, 0.5),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_replace_tiles_rolls_back() {
+        let geom = plane(10.0);
+        let config = BuildConfig::default();
+        let mut q = RecastQuery::build_tiled(&geom, &config, 16).unwrap();
+        let grid = TileGrid::for_geometry(&geom, &config, 16).unwrap();
+        let ext = (0.1, 1.0, 0.1);
+
+        let rebuilt = grid.build_tile(&geom, &config, 1, 1).unwrap();
+        let mut corrupt = q.tile_data(0, 0).unwrap().as_bytes().to_vec();
+        corrupt[24..28].copy_from_slice(&i32::MAX.to_le_bytes());
+        let tiles = vec![
+            ((1, 1), rebuilt),
+            ((0, 0), Some(TileData::from_bytes(&corrupt).unwrap())),
+        ];
+        assert!(q.replace_tiles(tiles).is_err());
+
+        // Both tiles are back
+        assert!(q.find_poly((7.0, 0.0, 7.0).into(), ext).is_ok());
+        assert!(q.find_poly((1.0, 0.0, 1.0).into(), ext).is_ok());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_build_tiles_parallel() {
+        let config = BuildConfig::default();
+        let serial = RecastQuery::build_tiled(&plane(10.0), &config, 16).unwrap();
+        let parallel = RecastQuery::build_tiles_parallel(&plane(10.0), &config, 16).unwrap();
+
+        for ty in 0..3 {
+            for tx in 0..3 {
+                assert_eq!(
+                    serial.tile_data(tx, ty).unwrap().as_bytes(),
+                    parallel.tile_data(tx, ty).unwrap().as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_polys_in_tiles() {
+        let q = RecastQuery::build_tiled(&plane(10.0), &BuildConfig::default(), 16).unwrap();
+
+        let corner = q.tile_polys(0, 0).unwrap();
+        let center = q.tile_polys(1, 1).unwrap();
+        assert!(!corner.is_empty());
+        assert!(corner.iter().all(|p| !center.contains(p)));
+
+        let all = q.polys_in_tiles((0, 0), (2, 2)).unwrap();
+        let sum: usize = (0..3)
+            .flat_map(|ty| (0..3).map(move |tx| (tx, ty)))
+            .map(|(tx, ty)| q.tile_polys(tx, ty).unwrap().len())
+            .sum();
+        assert_eq!(all.len(), sum);
+
+        assert!(q.tile_polys(5, 5).unwrap().is_empty());
+
+        let (_, poly) = q
+            .find_poly((7.0, 0.0, 7.0).into(), (0.1, 1.0, 0.1))
+            .unwrap();
+        assert!(center.contains(&poly));
+    }
+
+    #[test]
+    fn test_build_tiled() {
+        let q = RecastQuery::build_tiled(&plane(10.0), &BuildConfig::default(), 16).unwrap();
+
+        let p = q
+            .find_path(
+                (1.0, 0.0, 1.0).into(),
+                (9.0, 0.0, 9.0).into(),
+                (0.5, 1.0, 0.5),
+            )
+            .unwrap();
+
+        let end = p.last().unwrap();
+        assert!((end.x() - 9.0).abs() < 0.1);
+        assert!((end.z() - 9.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,380 @@
+//! Navmeshes built around a local origin and placed in a larger world, in the up axis convention of the caller
+use crate::{
+    NavMeshData, PathOptions, PathPoint, Point, PolyRef, RecastQuery, Result, SnapStrategy, WallHit,
+};
+use rand::Rng;
+
+/// Coordinate convention of the positions of the caller, Recast itself is Y-up
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    /// Right handed Z-up, e.g. Blender: `(x, y, z)` is `(x, z, -y)` for Recast
+    Z,
+}
+
+impl UpAxis {
+    /// From the convention to Recast
+    pub fn to_recast(self, p: Point) -> Point {
+        match self {
+            UpAxis::Y => p,
+            UpAxis::Z => Point([p.x(), p.z(), -p.y()]),
+        }
+    }
+
+    /// From Recast to the convention
+    pub fn from_recast(self, p: Point) -> Point {
+        match self {
+            UpAxis::Y => p,
+            UpAxis::Z => Point([p.x(), -p.z(), p.y()]),
+        }
+    }
+
+    /// The height of a position in the convention
+    pub fn up(self, p: Point) -> f32 {
+        match self {
+            UpAxis::Y => p.y(),
+            UpAxis::Z => p.z(),
+        }
+    }
+
+    /// Swizzle vertices, 3 floats each, to Recast in place. The winding of triangles is kept
+    pub fn vertices_to_recast(self, vertices: &mut [f32]) {
+        if self == UpAxis::Y {
+            return;
+        }
+        for v in vertices.chunks_mut(3) {
+            let p = self.to_recast(Point([v[0], v[1], v[2]]));
+            v.copy_from_slice(&p.0);
+        }
+    }
+
+    fn extents_to_recast(self, r: (f32, f32, f32)) -> (f32, f32, f32) {
+        match self {
+            UpAxis::Y => r,
+            UpAxis::Z => (r.0, r.2, r.1),
+        }
+    }
+}
+
+impl NavMeshData {
+    /// The same mesh in the Y-up convention of Recast
+    pub fn into_y_up(mut self) -> NavMeshData {
+        let up = self.up_axis;
+        if up == UpAxis::Y {
+            return self;
+        }
+
+        up.vertices_to_recast(&mut self.vertices);
+        for c in &mut self.off_mesh_connections {
+            c.start = up.to_recast(c.start);
+            c.end = up.to_recast(c.end);
+        }
+        self.up_axis = UpAxis::Y;
+        self
+    }
+}
+
+/// Placement of a navmesh in the world, a rotation about the up axis then a translation.
+///
+/// Only yaw is supported, Recast needs the up axis of the navmesh to stay the up axis of the world.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NavMeshTransform {
+    /// World position of the local origin, in the `up_axis` convention
+    pub translation: Point,
+    /// Right handed rotation about the up axis in radians
+    pub yaw: f32,
+    /// Convention of the world positions
+    pub up_axis: UpAxis,
+}
+
+impl Default for NavMeshTransform {
+    fn default() -> NavMeshTransform {
+        NavMeshTransform::identity()
+    }
+}
+
+impl NavMeshTransform {
+    pub fn identity() -> NavMeshTransform {
+        NavMeshTransform::new(Point::default(), 0.0)
+    }
+
+    pub fn from_translation(translation: Point) -> NavMeshTransform {
+        NavMeshTransform::new(translation, 0.0)
+    }
+
+    pub fn new(translation: Point, yaw: f32) -> NavMeshTransform {
+        NavMeshTransform {
+            translation,
+            yaw,
+            up_axis: UpAxis::Y,
+        }
+    }
+
+    pub fn with_up_axis(mut self, up_axis: UpAxis) -> NavMeshTransform {
+        self.up_axis = up_axis;
+        self
+    }
+
+    fn rotate(&self, p: Point, yaw: f32) -> Point {
+        let (sin, cos) = yaw.sin_cos();
+        Point([cos * p.x() + sin * p.z(), p.y(), -sin * p.x() + cos * p.z()])
+    }
+
+    pub fn to_world(&self, local: Point) -> Point {
+        let p = self.vector_to_world(local);
+        let t = self.translation;
+        Point([p.x() + t.x(), p.y() + t.y(), p.z() + t.z()])
+    }
+
+    pub fn to_local(&self, world: Point) -> Point {
+        let t = self.translation;
+        let p = Point([world.x() - t.x(), world.y() - t.y(), world.z() - t.z()]);
+        self.rotate(self.up_axis.to_recast(p), -self.yaw)
+    }
+
+    /// A direction, e.g. a normal, without the translation
+    pub fn vector_to_world(&self, local: Point) -> Point {
+        self.up_axis.from_recast(self.rotate(local, self.yaw))
+    }
+
+    /// Half extents of a local box covering the world box of half extents `r`
+    pub fn extents_to_local(&self, r: (f32, f32, f32)) -> (f32, f32, f32) {
+        let r = self.up_axis.extents_to_recast(r);
+        let (sin, cos) = self.yaw.sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        (cos * r.0 + sin * r.2, r.1, sin * r.0 + cos * r.2)
+    }
+
+    /// Bring geometry authored in world space into the local space of the navmesh
+    pub fn vertices_to_local(&self, vertices: &mut [f32]) {
+        for v in vertices.chunks_mut(3) {
+            let p = self.to_local(Point([v[0], v[1], v[2]]));
+            v.copy_from_slice(&p.0);
+        }
+    }
+}
+
+/// The queries of a placed navmesh, taking and returning world positions
+#[derive(Debug, Copy, Clone)]
+pub struct WorldQuery<'a> {
+    q: &'a RecastQuery,
+    transform: NavMeshTransform,
+}
+
+impl RecastQuery {
+    /// Query the navmesh placed in the world with `transform`
+    pub fn in_world(&self, transform: NavMeshTransform) -> WorldQuery<'_> {
+        WorldQuery { q: self, transform }
+    }
+}
+
+impl<'a> WorldQuery<'a> {
+    /// The navmesh in its local space
+    pub fn local(&self) -> &'a RecastQuery {
+        self.q
+    }
+
+    pub fn transform(&self) -> &NavMeshTransform {
+        &self.transform
+    }
+
+    pub fn find_poly(&self, pos: Point, r: (f32, f32, f32)) -> Result<(Point, PolyRef)> {
+        let t = &self.transform;
+        let (p, poly) = self.q.find_poly(t.to_local(pos), t.extents_to_local(r))?;
+        Ok((t.to_world(p), poly))
+    }
+
+    pub fn find_path(&self, start: Point, end: Point, r: (f32, f32, f32)) -> Result<Vec<Point>> {
+        let t = &self.transform;
+        let path = self
+            .q
+            .find_path(t.to_local(start), t.to_local(end), t.extents_to_local(r))?;
+        Ok(path.into_iter().map(|p| t.to_world(p)).collect())
+    }
+
+    pub fn find_path_with_options(
+        &self,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        options: &PathOptions,
+    ) -> Result<Vec<PathPoint>> {
+        let t = &self.transform;
+        let path = self.q.find_path_with_options(
+            t.to_local(start),
+            t.to_local(end),
+            t.extents_to_local(r),
+            options,
+        )?;
+        Ok(self.path_to_world(path))
+    }
+
+    pub fn straight_path(
+        &self,
+        corridor: &[PolyRef],
+        start: Point,
+        end: Point,
+    ) -> Result<Vec<Point>> {
+        let t = &self.transform;
+        let path = self
+            .q
+            .straight_path(corridor, t.to_local(start), t.to_local(end))?;
+        Ok(path.into_iter().map(|p| t.to_world(p)).collect())
+    }
+
+    pub fn poly_center(&self, poly: PolyRef) -> Result<Point> {
+        Ok(self.transform.to_world(self.q.poly_center(poly)?))
+    }
+
+    /// World height of the navmesh surface of `poly` at `pos`, along the up axis
+    pub fn poly_height(&self, poly: PolyRef, pos: Point) -> Result<f32> {
+        let t = &self.transform;
+        Ok(self.q.poly_height(poly, t.to_local(pos))? + t.up_axis.up(t.translation))
+    }
+
+    pub fn find_random_point<R: Rng>(&self, rng: &mut R) -> Result<(Point, PolyRef)> {
+        let (p, poly) = self.q.find_random_point(rng)?;
+        Ok((self.transform.to_world(p), poly))
+    }
+
+    pub fn find_random_point_around_circle<R: Rng>(
+        &self,
+        center: Point,
+        radius: f32,
+        r: (f32, f32, f32),
+        rng: &mut R,
+    ) -> Result<(Point, PolyRef)> {
+        let t = &self.transform;
+        let (p, poly) = self.q.find_random_point_around_circle(
+            t.to_local(center),
+            radius,
+            t.extents_to_local(r),
+            rng,
+        )?;
+        Ok((t.to_world(p), poly))
+    }
+
+    pub fn resolve_position<S: SnapStrategy + ?Sized>(
+        &self,
+        pos: Point,
+        r: (f32, f32, f32),
+        strategy: &mut S,
+    ) -> Result<(Point, PolyRef)> {
+        let t = &self.transform;
+        let (p, poly) =
+            self.q
+                .resolve_position(t.to_local(pos), t.extents_to_local(r), strategy)?;
+        Ok((t.to_world(p), poly))
+    }
+
+    pub fn distance_to_wall(&self, poly: PolyRef, pos: Point, max_radius: f32) -> Result<WallHit> {
+        let t = &self.transform;
+        let hit = self.q.distance_to_wall(poly, t.to_local(pos), max_radius)?;
+        Ok(WallHit {
+            distance: hit.distance,
+            pos: t.to_world(hit.pos),
+            normal: t.vector_to_world(hit.normal),
+        })
+    }
+
+    fn path_to_world(&self, path: Vec<PathPoint>) -> Vec<PathPoint> {
+        path.into_iter()
+            .map(|p| PathPoint {
+                pos: self.transform.to_world(p.pos),
+                ..p
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NavMeshData;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a.x() - b.x()).abs() < 1e-3 && (a.y() - b.y()).abs() < 1e-3 && (a.z() - b.z()).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let t = NavMeshTransform::new((100.0, 5.0, -20.0).into(), std::f32::consts::FRAC_PI_2);
+        let p = (1.0, 2.0, 3.0).into();
+        assert!(close(t.to_local(t.to_world(p)), p));
+        // +x turns to -z
+        assert!(close(
+            t.to_world((1.0, 0.0, 0.0).into()),
+            (100.0, 5.0, -21.0).into()
+        ));
+
+        let r = t.extents_to_local((1.0, 2.0, 3.0));
+        assert!((r.0 - 3.0).abs() < 1e-3 && (r.2 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_z_up() {
+        let p = (1.0, 2.0, 3.0).into();
+        assert_eq!(UpAxis::Z.to_recast(p), (1.0, 3.0, -2.0).into());
+        assert_eq!(UpAxis::Z.from_recast(UpAxis::Z.to_recast(p)), p);
+
+        // A quad at height 1 in a Z-up engine
+        let data = NavMeshData {
+            vertices: vec![
+                0.0, 0.0, 1.0, 10.0, 0.0, 1.0, 10.0, 10.0, 1.0, 0.0, 10.0, 1.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            walkable_height: 2.0,
+            walkable_radius: 0.2,
+            walkable_climb: 0.9,
+            cell_size: 0.1,
+            cell_height: 0.1,
+            up_axis: UpAxis::Z,
+            ..Default::default()
+        };
+        let q = RecastQuery::new_from_mesh(data).unwrap();
+        let world = q.in_world(NavMeshTransform::identity().with_up_axis(UpAxis::Z));
+
+        let ext = (0.5, 0.5, 0.5);
+        let (p, poly) = world.find_poly((2.0, 3.0, 1.2).into(), ext).unwrap();
+        assert!(close(p, (2.0, 3.0, 1.0).into()));
+        assert!((world.poly_height(poly, p).unwrap() - 1.0).abs() < 1e-3);
+
+        let end = (8.0, 9.0, 1.0).into();
+        let path = world.find_path((1.0, 1.0, 1.0).into(), end, ext).unwrap();
+        assert!(close(*path.last().unwrap(), end));
+    }
+
+    #[test]
+    fn test_world_queries() {
+        let data = NavMeshData {
+            vertices: vec![
+                0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            walkable_height: 2.0,
+            walkable_radius: 0.0,
+            walkable_climb: 0.9,
+            cell_size: 0.1,
+            cell_height: 0.1,
+            ..Default::default()
+        };
+        let q = RecastQuery::new_from_mesh(data).unwrap();
+        let t = NavMeshTransform::from_translation((1000.0, 50.0, 1000.0).into());
+        let world = q.in_world(t);
+
+        let ext = (0.5, 0.5, 0.5);
+        assert!(world.find_poly((2.0, 0.0, 2.0).into(), ext).is_err());
+        let (p, _) = world.find_poly((1002.0, 50.0, 1002.0).into(), ext).unwrap();
+        assert!(close(p, (1002.0, 50.0, 1002.0).into()));
+
+        let path = world
+            .find_path(
+                (1001.0, 50.0, 1001.0).into(),
+                (1009.0, 50.0, 1009.0).into(),
+                ext,
+            )
+            .unwrap();
+        assert!(close(*path.last().unwrap(), (1009.0, 50.0, 1009.0).into()));
+    }
+}
@@ -0,0 +1,222 @@
+//! Check a baked navmesh against the climbing abilities of the agents using it
+use crate::{BuildConfig, Error, Point, PolyRef, RecastQuery, Result};
+
+/// What an agent can climb, e.g. from the character controller, which may differ from the bake config
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AgentProfile {
+    /// Highest step the agent takes in world unit
+    pub max_climb: f32,
+    /// Steepest slope the agent walks in degrees
+    pub max_slope_angle: f32,
+}
+
+impl AgentProfile {
+    /// The agent the config bakes for
+    pub fn from_config(config: &BuildConfig) -> AgentProfile {
+        AgentProfile {
+            max_climb: config.walkable_climb,
+            max_slope_angle: config.walkable_slope_angle,
+        }
+    }
+}
+
+/// A part of the navmesh steeper than the agent walks and higher than it climbs,
+/// e.g. stairs with steps taller than the agent climbs or a ramp baked with a larger slope
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraversalIssue {
+    pub poly: PolyRef,
+    /// Center of the steepest part of the poly
+    pub pos: Point,
+    /// Slope in degrees
+    pub angle: f32,
+    /// Height covered by the steep part in world unit
+    pub rise: f32,
+}
+
+impl RecastQuery {
+    /// The triangles of the detail mesh of a poly, the surface followed by the heights of the queries.
+    /// Empty for off-mesh connections
+    pub fn poly_detail_triangles(&self, poly: PolyRef) -> Result<Vec<[Point; 3]>> {
+        let mut result = sys::RecastDetailTrianglesResult::default();
+        let mut err = sys::RecastNavError::zeros();
+
+        let res = unsafe {
+            sys::recastc_get_poly_detail_triangles(
+                self.q.as_ptr(),
+                poly,
+                &mut result as *mut _,
+                &mut err as *mut _,
+            )
+        };
+
+        if res == 0 {
+            return Err(Error::PolyError(err.msg().to_string()));
+        }
+
+        let verts = &result.verts[..result.tri_count as usize * 9];
+        Ok(verts
+            .chunks(9)
+            .map(|t| {
+                [
+                    (t[0], t[1], t[2]).into(),
+                    (t[3], t[4], t[5]).into(),
+                    (t[6], t[7], t[8]).into(),
+                ]
+            })
+            .collect())
+    }
+}
+
+/// Slope in degrees and rise of a triangle
+fn steepness(t: &[Point; 3]) -> (f32, f32) {
+    let e0 = [
+        t[1].x() - t[0].x(),
+        t[1].y() - t[0].y(),
+        t[1].z() - t[0].z(),
+    ];
+    let e1 = [
+        t[2].x() - t[0].x(),
+        t[2].y() - t[0].y(),
+        t[2].z() - t[0].z(),
+    ];
+    let n = [
+        e0[1] * e1[2] - e0[2] * e1[1],
+        e0[2] * e1[0] - e0[0] * e1[2],
+        e0[0] * e1[1] - e0[1] * e1[0],
+    ];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if len <= f32::EPSILON {
+        return (0.0, 0.0);
+    }
+
+    let angle = (n[1].abs() / len).min(1.0).acos().to_degrees();
+    let ys = [t[0].y(), t[1].y(), t[2].y()];
+    let rise =
+        ys.iter().cloned().fold(f32::MIN, f32::max) - ys.iter().cloned().fold(f32::MAX, f32::min);
+    (angle, rise)
+}
+
+/// Sample the detail surface of every poly passing the query filter and report the polys
+/// the agent cannot traverse everywhere, at most one issue per poly.
+///
+/// Recast smooths steps into short ramps, so a step shows as a part both steeper than
+/// `max_slope_angle` and higher than `max_climb`. Steep parts below `max_climb` are steps
+/// the agent takes and are not reported.
+pub fn validate_agent_traversal(
+    q: &RecastQuery,
+    profile: &AgentProfile,
+) -> Result<Vec<TraversalIssue>> {
+    let mut issues = Vec::new();
+
+    for poly in q.polys()? {
+        let mut worst: Option<TraversalIssue> = None;
+        for t in q.poly_detail_triangles(poly)? {
+            let (angle, rise) = steepness(&t);
+            if angle <= profile.max_slope_angle || rise <= profile.max_climb {
+                continue;
+            }
+            if worst.is_some_and(|w| w.angle >= angle) {
+                continue;
+            }
+
+            let pos = (
+                (t[0].x() + t[1].x() + t[2].x()) / 3.0,
+                (t[0].y() + t[1].y() + t[2].y()) / 3.0,
+                (t[0].z() + t[1].z() + t[2].z()) / 3.0,
+            );
+            worst = Some(TraversalIssue {
+                poly,
+                pos: pos.into(),
+                angle,
+                rise,
+            });
+        }
+        issues.extend(worst);
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::plane;
+    use crate::{InputGeometry, OffMeshConnection};
+
+    #[test]
+    fn test_steepness() {
+        let flat = [
+            (0.0, 1.0, 0.0).into(),
+            (0.0, 1.0, 1.0).into(),
+            (1.0, 1.0, 0.0).into(),
+        ];
+        assert_eq!(steepness(&flat), (0.0, 0.0));
+
+        let ramp = [
+            (0.0, 0.0, 0.0).into(),
+            (0.0, 0.0, 1.0).into(),
+            (1.0, 1.0, 0.0).into(),
+        ];
+        let (angle, rise) = steepness(&ramp);
+        assert!((angle - 45.0).abs() < 1e-3);
+        assert_eq!(rise, 1.0);
+    }
+
+    #[test]
+    fn test_steep_ramp() {
+        // A lower floor, a 50 degrees ramp rising 2 units and an upper floor
+        let run = 2.0 / 50f32.to_radians().tan();
+        let x = 4.0 + run;
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 4.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+                x, 2.0, 0.0, 10.0, 2.0, 0.0, 10.0, 2.0, 10.0, x, 2.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6, 1, 7, 4, 1, 2, 7],
+            ..Default::default()
+        };
+        let config = BuildConfig::default().agent(2.0, 0.6, 0.9, 60.0);
+        let q = RecastQuery::build(&geom, &config).unwrap();
+
+        let baked = validate_agent_traversal(&q, &AgentProfile::from_config(&config)).unwrap();
+        assert!(baked.is_empty(), "{:?}", baked);
+
+        let profile = AgentProfile {
+            max_climb: 0.3,
+            max_slope_angle: 30.0,
+        };
+        let issues = validate_agent_traversal(&q, &profile).unwrap();
+        assert!(!issues.is_empty());
+        for issue in &issues {
+            assert!(issue.angle > 30.0 && issue.rise > 0.3);
+            assert!(
+                issue.pos.x() > 3.0 && issue.pos.x() < x + 1.0,
+                "{:?}",
+                issue
+            );
+        }
+    }
+
+    #[test]
+    fn test_off_mesh_detail_triangles() {
+        let geom = InputGeometry {
+            off_mesh_connections: vec![OffMeshConnection::new(
+                (2.0, 0.0, 2.0).into(),
+                (8.0, 0.0, 8.0).into(),
+                0.5,
+            )],
+            ..plane(10.0)
+        };
+        let q = RecastQuery::build(&geom, &BuildConfig::default()).unwrap();
+
+        let polys = q.polys().unwrap();
+        let (links, ground): (Vec<_>, Vec<_>) = polys
+            .iter()
+            .partition(|&&p| q.off_mesh_connection(p).is_ok());
+        assert_eq!(links.len(), 1);
+        assert!(q.poly_detail_triangles(links[0]).unwrap().is_empty());
+        assert!(ground
+            .iter()
+            .all(|&p| !q.poly_detail_triangles(p).unwrap().is_empty()));
+    }
+}
@@ -0,0 +1,215 @@
+//! Navmeshes baked in Unity, reused as they are instead of built again from the level geometry
+use crate::pack::{read_f32, read_u32};
+use crate::{Error, NavMeshData, RecastQuery, Result};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+const UNITY_MAGIC: &[u8; 4] = b"RDUN";
+const UNITY_VERSION: u32 = 1;
+
+/// Areas of a Unity navmesh, `NavMesh.GetAreaCost` takes indices below it
+pub const UNITY_AREA_COUNT: usize = 32;
+
+fn unity_error(err: io::Error) -> Error {
+    Error::UnityError(err.to_string())
+}
+
+/// The output of `NavMesh.CalculateTriangulation()`, as saved by the "Export Triangulation"
+/// button of `exporters/NavMeshExporter.cs`.
+///
+/// The positions stay in the coordinates of Unity, the queries take and return Unity positions.
+/// The Unity areas are the area ids of the polys, e.g. 2 for `Jump`.
+///
+/// Layout (little endian): magic, version, the agent height, radius and climb and the voxel
+/// size of the bake, the vertices (u32 count + 3 f32 each), the triangles (u32 count + 3 u32
+/// indices each) then the u32 area of each triangle, and the area costs (u32 count + f32 each)
+#[derive(Debug, Clone, Default)]
+pub struct UnityTriangulation {
+    /// The triangles with their area, the cells are the voxels of the bake
+    pub data: NavMeshData,
+    /// Cost of each Unity area by its index, as set in the Navigation window
+    pub area_costs: Vec<f32>,
+}
+
+impl UnityTriangulation {
+    pub fn read<R: Read>(r: &mut R) -> Result<UnityTriangulation> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic).map_err(unity_error)?;
+        if &magic != UNITY_MAGIC || read_u32(r).map_err(unity_error)? != UNITY_VERSION {
+            return Err(Error::UnityError(
+                "not a Unity triangulation of this version".into(),
+            ));
+        }
+
+        let triangulation = UnityTriangulation::read_body(r).map_err(unity_error)?;
+        let data = &triangulation.data;
+        if let Some(t) = data
+            .triangle_areas
+            .iter()
+            .position(|&a| a as usize >= UNITY_AREA_COUNT)
+        {
+            return Err(Error::UnityError(format!(
+                "area {} of triangle {} is not a Unity area",
+                data.triangle_areas[t], t
+            )));
+        }
+        if triangulation.area_costs.len() > UNITY_AREA_COUNT {
+            return Err(Error::UnityError(format!(
+                "{} area costs for {} Unity areas",
+                triangulation.area_costs.len(),
+                UNITY_AREA_COUNT
+            )));
+        }
+        data.validate()?;
+        Ok(triangulation)
+    }
+
+    fn read_body<R: Read>(r: &mut R) -> io::Result<UnityTriangulation> {
+        let agent_height = read_f32(r)?;
+        let agent_radius = read_f32(r)?;
+        let agent_climb = read_f32(r)?;
+        let voxel_size = read_f32(r)?;
+
+        let mut data = NavMeshData {
+            walkable_height: agent_height,
+            walkable_radius: agent_radius,
+            walkable_climb: agent_climb,
+            // Unity voxels are cubes
+            cell_size: voxel_size,
+            cell_height: voxel_size,
+            ..Default::default()
+        };
+
+        let n = read_u32(r)?;
+        for _ in 0..n as u64 * 3 {
+            data.vertices.push(read_f32(r)?);
+        }
+        let n = read_u32(r)?;
+        for _ in 0..n as u64 * 3 {
+            data.indices.push(read_u32(r)?);
+        }
+        for _ in 0..n {
+            // Out of range areas are reported once the whole file is read
+            data.triangle_areas
+                .push(read_u32(r)?.min(u8::MAX as u32) as u8);
+        }
+
+        let n = read_u32(r)?;
+        let mut area_costs = vec![];
+        for _ in 0..n {
+            area_costs.push(read_f32(r)?);
+        }
+
+        Ok(UnityTriangulation { data, area_costs })
+    }
+
+    pub fn open(path: &Path) -> Result<UnityTriangulation> {
+        let mut file = io::BufReader::new(fs::File::open(path).map_err(unity_error)?);
+        UnityTriangulation::read(&mut file)
+    }
+
+    /// A query over the triangles of the navmesh, with the area costs of Unity
+    pub fn into_query(self) -> Result<RecastQuery> {
+        let mut q = RecastQuery::new_from_mesh(self.data)?;
+        for (area, cost) in self.area_costs.into_iter().enumerate() {
+            q.set_area_cost(area as u8, cost)?;
+        }
+        Ok(q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the exporter writes for two quads, the second one in the `Jump` area
+    fn unity_bytes() -> Vec<u8> {
+        let mut out = UNITY_MAGIC.to_vec();
+        out.extend_from_slice(&UNITY_VERSION.to_le_bytes());
+        for f in &[2.0f32, 0.5, 0.4, 0.1666667] {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+
+        let vertices = [
+            0.0f32, 0.0, 0.0, 10.0, 0.0, 0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 10.0, //
+            20.0, 0.0, 0.0, 20.0, 0.0, 10.0,
+        ];
+        out.extend_from_slice(&6u32.to_le_bytes());
+        for f in &vertices {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        // Wound as Unity does for faces seen from above
+        let indices = [0u32, 2, 1, 0, 3, 2, 1, 5, 4, 1, 2, 5];
+        out.extend_from_slice(&4u32.to_le_bytes());
+        for i in &indices {
+            out.extend_from_slice(&i.to_le_bytes());
+        }
+        for a in &[0u32, 0, 2, 2] {
+            out.extend_from_slice(&a.to_le_bytes());
+        }
+
+        out.extend_from_slice(&3u32.to_le_bytes());
+        for f in &[1.0f32, 1.0, 2.0] {
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_load_triangulation() {
+        let t = UnityTriangulation::read(&mut unity_bytes().as_slice()).unwrap();
+        assert_eq!(t.data.vertices.len(), 18);
+        assert_eq!(t.data.triangle_areas, vec![0, 0, 2, 2]);
+        assert_eq!(t.data.walkable_radius, 0.5);
+        assert_eq!(t.area_costs, vec![1.0, 1.0, 2.0]);
+
+        let q = t.into_query().unwrap();
+        let area = |x: f32| {
+            let (_, poly) = q.find_poly((x, 0.0, 5.0).into(), (0.2, 1.0, 0.2)).unwrap();
+            q.poly_area(poly).unwrap()
+        };
+        assert_eq!(area(5.0), 0);
+        assert_eq!(area(15.0), 2);
+
+        let path = q
+            .find_path(
+                (1.0, 0.0, 1.0).into(),
+                (19.0, 0.0, 9.0).into(),
+                (0.2, 1.0, 0.2),
+            )
+            .unwrap();
+        assert_eq!(path.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_triangulation() {
+        let msg = |bytes: &[u8]| match UnityTriangulation::read(&mut &bytes[..]) {
+            Err(Error::UnityError(msg)) => msg,
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("loaded"),
+        };
+        let bytes = unity_bytes();
+
+        assert_eq!(
+            msg(b"RDNM\x01\0\0\0"),
+            "not a Unity triangulation of this version"
+        );
+        assert!(UnityTriangulation::read(&mut &bytes[..bytes.len() - 1]).is_err());
+
+        // The area of the last triangle, before the 3 area costs and their count
+        let mut other_area = bytes.clone();
+        let last = bytes.len() - 16 - 4;
+        other_area[last..last + 4].copy_from_slice(&40u32.to_le_bytes());
+        assert_eq!(
+            msg(&other_area),
+            "area 40 of triangle 3 is not a Unity area"
+        );
+
+        // The first index of the triangles, after the 6 vertices
+        let mut out_of_bounds = bytes;
+        let first = 8 + 16 + 4 + 72 + 4;
+        out_of_bounds[first..first + 4].copy_from_slice(&9u32.to_le_bytes());
+        assert!(msg(&out_of_bounds).contains("index 9 of triangle 0"));
+    }
+}
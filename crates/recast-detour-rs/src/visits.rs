@@ -0,0 +1,121 @@
+//! Per-poly traffic counters, e.g. for heatmaps of the paths found
+use crate::{PolyRef, RecastQuery};
+use std::collections::HashMap;
+
+/// How many recorded paths went through each poly
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisitStats {
+    counts: HashMap<PolyRef, u32>,
+    paths: u32,
+}
+
+impl VisitStats {
+    pub fn new() -> VisitStats {
+        VisitStats::default()
+    }
+
+    /// Count a path corridor, a poly visited twice by the same path counts once
+    pub fn record(&mut self, corridor: &[PolyRef]) {
+        for (i, poly) in corridor.iter().enumerate() {
+            if !corridor[..i].contains(poly) {
+                *self.counts.entry(*poly).or_insert(0) += 1;
+            }
+        }
+        self.paths += 1;
+    }
+
+    pub fn count(&self, poly: PolyRef) -> u32 {
+        self.counts.get(&poly).copied().unwrap_or(0)
+    }
+
+    /// Number of recorded paths
+    pub fn path_count(&self) -> u32 {
+        self.paths
+    }
+
+    /// The visited polys with their counts, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (PolyRef, u32)> + '_ {
+        self.counts.iter().map(|(p, c)| (*p, *c))
+    }
+
+    /// The `n` most visited polys, most visited first
+    pub fn hottest(&self, n: usize) -> Vec<(PolyRef, u32)> {
+        let mut polys: Vec<_> = self.iter().collect();
+        polys.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        polys.truncate(n);
+        polys
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.paths = 0;
+    }
+}
+
+impl RecastQuery {
+    /// Start counting the corridors of the paths found, recording is off by default
+    pub fn enable_visit_stats(&mut self) {
+        let visits = self.visits.get_mut();
+        if visits.is_none() {
+            *visits = Some(VisitStats::new());
+        }
+    }
+
+    /// Stop recording and return the counters
+    pub fn disable_visit_stats(&mut self) -> Option<VisitStats> {
+        self.visits.get_mut().take()
+    }
+
+    /// A copy of the counters, None when recording is off
+    pub fn visit_stats(&self) -> Option<VisitStats> {
+        self.visits.borrow().clone()
+    }
+
+    pub fn reset_visit_stats(&mut self) {
+        if let Some(visits) = self.visits.get_mut() {
+            visits.clear();
+        }
+    }
+
+    pub(crate) fn record_visits(&self, corridor: &[PolyRef]) {
+        if let Some(visits) = self.visits.borrow_mut().as_mut() {
+            visits.record(corridor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_corridor() {
+        let mut stats = VisitStats::new();
+        stats.record(&[1, 2, 3]);
+        stats.record(&[3, 2, 3]);
+
+        assert_eq!(stats.path_count(), 2);
+        assert_eq!(stats.count(3), 2);
+        assert_eq!(stats.count(1), 1);
+        assert_eq!(stats.count(4), 0);
+        assert_eq!(stats.hottest(2), vec![(2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn test_find_path_records_visits() {
+        let mut q = RecastQuery::new_from_mesh(crate::test_util::simple_mesh()).unwrap();
+        let (start, end) = ((1.0, 0.0, 1.0).into(), (9.0, 0.0, 9.0).into());
+
+        q.find_path(start, end, (0.5, 0.5, 0.5)).unwrap();
+        assert_eq!(q.visit_stats(), None);
+
+        q.enable_visit_stats();
+        q.find_path(start, end, (0.5, 0.5, 0.5)).unwrap();
+        q.find_path(end, start, (0.5, 0.5, 0.5)).unwrap();
+
+        let stats = q.disable_visit_stats().unwrap();
+        let (_, poly) = q.find_poly(start, (0.5, 0.5, 0.5)).unwrap();
+        assert_eq!(stats.path_count(), 2);
+        assert_eq!(stats.count(poly), 2);
+    }
+}
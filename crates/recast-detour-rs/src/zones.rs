@@ -0,0 +1,178 @@
+//! Paths summarized by the named zones they cross, e.g. for quest breadcrumbs without a waypoint graph
+use crate::{PathCrossings, PathOptions, Point, PolyFlags, PolyRef, RecastQuery, Result};
+use std::collections::BTreeMap;
+
+/// Names of the zones of a navmesh, given by the area of the polys or by their user flags.
+///
+/// A flag name wins over an area name, e.g. a "Market Gate" flag on the gate polys of the
+/// market area. Polys without name belong to no zone and are skipped by the breadcrumbs
+#[derive(Debug, Clone, Default)]
+pub struct ZoneMap {
+    areas: BTreeMap<u8, String>,
+    flags: Vec<(PolyFlags, String)>,
+}
+
+impl ZoneMap {
+    pub fn new() -> ZoneMap {
+        ZoneMap::default()
+    }
+
+    /// Name the polys of an area
+    pub fn area(mut self, area: u8, name: &str) -> ZoneMap {
+        self.areas.insert(area, name.to_string());
+        self
+    }
+
+    /// Name the polys with all of the flags, the first matching flags in the order given win
+    pub fn flags(mut self, flags: PolyFlags, name: &str) -> ZoneMap {
+        self.flags.push((flags, name.to_string()));
+        self
+    }
+
+    /// The zone of a poly. The area under a soft area is the one of the mesh
+    pub fn zone_of(&self, q: &RecastQuery, poly: PolyRef) -> Result<Option<&str>> {
+        let flags = q.poly_flags(poly)?;
+        if let Some((_, name)) = self.flags.iter().find(|(f, _)| flags.contains(*f)) {
+            return Ok(Some(name));
+        }
+
+        let area = q.soft_areas.original_area(poly, q.poly_area(poly)?);
+        Ok(self.areas.get(&area).map(|n| n.as_str()))
+    }
+}
+
+/// A zone crossed by a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breadcrumb {
+    pub zone: String,
+    /// Where the path enters the zone, the start for the first zone
+    pub entry: Point,
+    /// Length of the path within the zone, counted again when the path comes back
+    pub distance: f32,
+}
+
+impl RecastQuery {
+    /// The zones crossed by the path from `start` to `end` in order, e.g. "Plaza, Market
+    /// Gate, Docks". A zone left through unnamed polys and entered again shows once
+    pub fn find_breadcrumbs(
+        &self,
+        start: Point,
+        end: Point,
+        r: (f32, f32, f32),
+        zones: &ZoneMap,
+        options: &PathOptions,
+    ) -> Result<Vec<Breadcrumb>> {
+        let options = PathOptions {
+            crossings: PathCrossings::All,
+            ..options.clone()
+        };
+        let path = self.find_path_with_options(start, end, r, &options)?;
+
+        let mut crumbs: Vec<Breadcrumb> = vec![];
+        for w in path.windows(2) {
+            // Each point is on the edge entering its poly
+            let zone = match zones.zone_of(self, w[0].poly)? {
+                Some(zone) => zone,
+                None => continue,
+            };
+            let (a, b) = (w[0].pos, w[1].pos);
+            let d = ((b.x() - a.x()).powi(2) + (b.y() - a.y()).powi(2) + (b.z() - a.z()).powi(2))
+                .sqrt();
+
+            match crumbs.last_mut() {
+                Some(last) if last.zone == zone => last.distance += d,
+                _ => crumbs.push(Breadcrumb {
+                    zone: zone.to_string(),
+                    entry: a,
+                    distance: d,
+                }),
+            }
+        }
+        Ok(crumbs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildConfig, ConvexVolume, InputGeometry};
+
+    fn volume(x0: f32, x1: f32, area: u8) -> ConvexVolume {
+        ConvexVolume {
+            verts: vec![
+                (x0, 0.0, 0.0).into(),
+                (x1, 0.0, 0.0).into(),
+                (x1, 0.0, 10.0).into(),
+                (x0, 0.0, 10.0).into(),
+            ],
+            hmin: -1.0,
+            hmax: 1.0,
+            area,
+        }
+    }
+
+    fn town() -> RecastQuery {
+        // Three districts side by side
+        let geom = InputGeometry {
+            vertices: vec![
+                0.0, 0.0, 0.0, 30.0, 0.0, 0.0, 30.0, 0.0, 10.0, 0.0, 0.0, 10.0,
+            ],
+            indices: vec![0, 2, 1, 0, 3, 2],
+            volumes: vec![
+                volume(0.0, 10.0, 3),
+                volume(10.0, 20.0, 4),
+                volume(20.0, 30.0, 5),
+            ],
+            ..Default::default()
+        };
+        RecastQuery::build(&geom, &BuildConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        let q = town();
+        let zones = ZoneMap::new()
+            .area(3, "Plaza")
+            .area(4, "Market")
+            .area(5, "Docks");
+        let start: Point = (2.0, 0.0, 5.0).into();
+        let end: Point = (28.0, 0.0, 5.0).into();
+
+        let crumbs = q
+            .find_breadcrumbs(start, end, (0.5, 1.0, 0.5), &zones, &PathOptions::default())
+            .unwrap();
+        let names: Vec<_> = crumbs.iter().map(|c| c.zone.as_str()).collect();
+        assert_eq!(names, ["Plaza", "Market", "Docks"]);
+        assert!((crumbs[1].entry.x() - 10.0).abs() < 1.0);
+        let total: f32 = crumbs.iter().map(|c| c.distance).sum();
+        assert!((total - 26.0).abs() < 0.5, "{}", total);
+
+        // The unnamed market is skipped
+        let zones = ZoneMap::new().area(3, "Plaza").area(5, "Docks");
+        let crumbs = q
+            .find_breadcrumbs(start, end, (0.5, 1.0, 0.5), &zones, &PathOptions::default())
+            .unwrap();
+        assert_eq!(crumbs.len(), 2);
+        assert_eq!(crumbs[1].zone, "Docks");
+    }
+
+    #[test]
+    fn test_flag_names_win() {
+        let mut q = town();
+        let (_, gate) = q
+            .find_poly((15.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        q.set_poly_flags(gate, PolyFlags::WALK | PolyFlags::DOOR)
+            .unwrap();
+
+        let zones = ZoneMap::new()
+            .area(4, "Market")
+            .flags(PolyFlags::DOOR, "Market Gate");
+        assert_eq!(zones.zone_of(&q, gate).unwrap(), Some("Market Gate"));
+
+        let (_, plaza) = q
+            .find_poly((2.0, 0.0, 5.0).into(), (0.5, 1.0, 0.5))
+            .unwrap();
+        assert_eq!(zones.zone_of(&q, plaza).unwrap(), None);
+    }
+}
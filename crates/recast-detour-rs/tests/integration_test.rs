@@ -4,7 +4,7 @@ use rand::SeedableRng;
 use recast_detour_rs::{NavMeshData, NavObjFile, Point, RecastQuery};
 use std::path::Path;
 
-fn get_point(i: u16, verts: &[f32]) -> Point {
+fn get_point(i: u32, verts: &[f32]) -> Point {
     let i = i as usize;
     Point::new((verts[i * 3 + 0], verts[i * 3 + 1], verts[i * 3 + 2]))
 }
@@ -1,4 +1,60 @@
+fn git(args: &[&str]) -> Option<String> {
+    std::process::Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|out| out.trim().to_string())
+}
+
+/// Rerun when the HEAD of the repo at `dir` moves, the branch it points to included
+fn rerun_if_head_changed(dir: &str) {
+    let git_dir = match git(&["-C", dir, "rev-parse", "--absolute-git-dir"]) {
+        Some(git_dir) => std::path::PathBuf::from(git_dir),
+        None => return,
+    };
+
+    let mut paths = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    if let Some(branch) = git(&["-C", dir, "symbolic-ref", "-q", "HEAD"]) {
+        paths.push(git_dir.join(branch));
+    }
+    // A missing path would rerun the build script on every build
+    for path in paths.iter().filter(|p| p.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+/// The commit of the recastnavigation submodule, from its checkout or from the parent repo
+fn recastnavigation_commit() -> String {
+    // Without a checkout, git -C would report the commit of the parent repo
+    let checkout = std::path::Path::new("recast/recastnavigation/.git").exists();
+
+    let commit = if checkout {
+        rerun_if_head_changed("recast/recastnavigation");
+        git(&["-C", "recast/recastnavigation", "rev-parse", "HEAD"])
+    } else {
+        None
+    };
+
+    commit
+        .or_else(|| {
+            rerun_if_head_changed(".");
+            // "<mode> commit <sha>\t<path>"
+            git(&["ls-tree", "HEAD", "recast/recastnavigation"])
+                .and_then(|line| line.split_whitespace().nth(2).map(str::to_string))
+        })
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
 fn main() {
+    println!(
+        "cargo:rustc-env=RECASTNAVIGATION_COMMIT={}",
+        recastnavigation_commit()
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+
     #[cfg(not(feature = "skip-build-recast"))]
     {
         use std::fs;
@@ -37,6 +93,9 @@ fn main() {
 
         println!("cargo:rustc-link-search=native={}/lib", dst.display());
         println!("cargo:rustc-link-lib=static=RecastC");
+        println!("cargo:rustc-link-lib=static=DetourCrowd");
+        println!("cargo:rustc-link-lib=static=DetourTileCache");
         println!("cargo:rustc-link-lib=static=Detour");
+        println!("cargo:rustc-link-lib=static=Recast");
     }
 }
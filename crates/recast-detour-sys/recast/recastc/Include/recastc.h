@@ -5,6 +5,17 @@
 
 extern "C"
 {
+    /// A link between two points of the navmesh, e.g. a jump, a ladder or a teleporter
+    typedef struct {
+        float start[3];                 ///< in world unit
+        float end[3];                   ///< in world unit
+        float radius;                   ///< Endpoints connect to polys within this radius
+        uint8_t bidirectional;          ///< 0 for start to end only
+        uint8_t area;
+        uint16_t flags;
+        uint32_t user_id;
+    } recastc_OffMeshConnection;
+
     typedef struct  {
         uint16_t *verts;                ///< in voxel unit
         uint32_t vert_count;           
@@ -18,6 +29,9 @@ extern "C"
         float walkable_climb;           ///< The agent maximum traversable ledge in world unit
         float cell_size;				///< The xz-plane cell size of the polygon mesh in world unit
 	    float cell_height;				///< The y-axis cell height of the polygon mesh in world unit
+        const recastc_OffMeshConnection *off_mesh_connections;
+        uint32_t off_mesh_connection_count;
+        const uint8_t *areas;           ///< The area of each triangle, null for all in area 0
     } recastc_NavMesh;
 
     typedef struct {
@@ -44,23 +58,821 @@ extern "C"
         uint32_t end_poly;        
         float start_pos[3];
         float end_pos[3];    
+        int32_t straight_path_options;  ///< dtStraightPathOptions
+        int32_t find_path_options;      ///< dtFindPathOptions, DT_FINDPATH_ANY_ANGLE shortcuts the corridor with raycasts
+        uint32_t max_points;            ///< Stop the straight path after this many points, 0 for as many as fit
     } recastc_PathInput;
 
+    typedef struct {
+        const uint32_t *corridor;       ///< Polys from the start to the end poly, e.g. from a custom planner
+        uint32_t corridor_count;        ///< At most the size of `recastc_PathResult.path`
+        float start_pos[3];
+        float end_pos[3];
+        int32_t straight_path_options;  ///< dtStraightPathOptions
+        uint32_t max_points;            ///< Stop the straight path after this many points, 0 for as many as fit
+    } recastc_StraightPathInput;
+
     typedef struct {
         uint32_t path[1024];        
         float path2[2048 * 3];
+        uint32_t path2_polys[2048];     ///< The poly each straight path point enters
+        uint8_t path2_flags[2048];      ///< dtStraightPathFlags per straight path point
         uint32_t path_count;
         uint32_t path2_count;
     } recastc_PathResult;
 
+    typedef struct {
+        float center[3];
+        float radius;
+        float half_extents[3];          ///< Search extents for the poly containing the center
+    } recastc_PolysAroundCircleInput;
+
+    /// Returns a random number in [0, 1)
+    typedef float (*recastc_Rand)(void* user);
+
+    typedef struct {
+        uint32_t polys[256];
+        uint32_t poly_count;
+    } recastc_PolysResult;
+
+    typedef struct {
+        float verts[255 * 9];           ///< 3 vertices per triangle
+        uint32_t tri_count;
+    } recastc_DetailTrianglesResult;
+
     typedef struct {
         // FIXME: Just random choose size
         char msg[256];                  
     } recastc_Error;
 
+    /// Region partitioning, see Sample_SoloMesh.cpp for the trade-offs
+    typedef enum {
+        RECASTC_PARTITION_WATERSHED = 0,
+        RECASTC_PARTITION_MONOTONE = 1,
+        RECASTC_PARTITION_LAYERS = 2,
+    } recastc_PartitionType;
+
+    typedef enum {
+        RECASTC_FILTER_LOW_HANGING_OBSTACLES = 0x1,     ///< rcFilterLowHangingWalkableObstacles
+        RECASTC_FILTER_LEDGE_SPANS = 0x2,               ///< rcFilterLedgeSpans
+        RECASTC_FILTER_WALKABLE_LOW_HEIGHT_SPANS = 0x4, ///< rcFilterWalkableLowHeightSpans
+    } recastc_FilterFlags;
+
+    /// A prism marking the walkable spans inside it with an area id, like RecastDemo's ConvexVolume
+    typedef struct {
+        float verts[12 * 3];            ///< Convex polygon in the xz-plane, y is ignored
+        int32_t nverts;
+        float hmin;
+        float hmax;
+        uint8_t area;                   ///< RC_NULL_AREA (0) removes the spans
+    } recastc_ConvexVolume;
+
+    /// A body of water, walkable spans below its surface become shallow or deep water
+    typedef struct {
+        float verts[12 * 3];            ///< Convex outline in the xz-plane, y is ignored
+        int32_t nverts;                 ///< 0 for an unbounded water plane
+        float surface;                  ///< Height of the water surface in world unit
+    } recastc_WaterVolume;
+
+    typedef struct {
+        const float *verts;             ///< (x, y, z) * vert_count in world unit
+        uint32_t vert_count;
+        const int32_t *tris;            ///< (i0, i1, i2) * tri_count
+        uint32_t tri_count;
+        const uint8_t *tri_areas;       ///< Area id per triangle, null for area 0. RECASTC_SPAN_NOT_WALKABLE for obstacles
+        const recastc_ConvexVolume *volumes;
+        uint32_t volume_count;
+        const recastc_OffMeshConnection *off_mesh_connections;
+        uint32_t off_mesh_connection_count;
+        const recastc_WaterVolume *water;
+        uint32_t water_count;
+    } recastc_BuildInput;
+
+    enum recastc_SpanArea {
+        RECASTC_SPAN_NOT_WALKABLE = 0xff,   ///< Area of a span whose top is solid but not walkable
+    };
+
+    /// A solid span of a heightfield column, heights in `cell_height` above the heightfield bmin
+    typedef struct {
+        uint16_t smin;
+        uint16_t smax;                  ///< At most RC_SPAN_MAX_HEIGHT
+        uint8_t area;                   ///< Area id of the span top, 0 for the default walkable area
+    } recastc_Span;
+
+    /// Voxel occupancy given directly instead of triangles, the columns are `cell_size` wide
+    typedef struct {
+        float bmin[3];
+        float bmax[3];                  ///< Only the height is read, x and z follow from the columns
+        int32_t width;                  ///< Columns along x
+        int32_t height;                 ///< Columns along z
+        const uint32_t *columns;        ///< width * height + 1 offsets, column x + z * width holds spans[columns[i]..columns[i + 1]]
+        const recastc_Span *spans;      ///< Bottom to top within a column
+        uint32_t span_count;
+    } recastc_HeightfieldInput;
+
+    enum recastc_ArtifactConstants {
+        RECASTC_NOT_CONNECTED = 0xff,       ///< No neighbour span in that direction
+        RECASTC_MESH_NULL_IDX = 0xffff,     ///< Past the last vertex of a poly, or no neighbour poly
+    };
+
+    /// A span of walkable space, see rcCompactSpan
+    typedef struct {
+        uint16_t y;                     ///< Floor height in cells
+        uint16_t h;                     ///< Open height above the floor in cells
+        uint16_t reg;                   ///< Region id, 0 for none
+        uint16_t dist;                  ///< Distance to the region border, 0 without distance field
+        uint8_t area;                   ///< Area id, RECASTC_SPAN_NOT_WALKABLE when not walkable
+        uint8_t con[4];                 ///< Per direction, index of the neighbour span within its column or RECASTC_NOT_CONNECTED
+    } recastc_CompactSpan;
+
+    typedef struct {
+        float bmin[3];
+        float bmax[3];
+        int32_t width;                  ///< Columns along x
+        int32_t height;                 ///< Columns along z
+        int32_t border_size;            ///< Cells around a tile build
+        uint16_t max_regions;
+        const uint32_t *columns;        ///< width * height + 1 offsets, as in recastc_HeightfieldInput
+        const recastc_CompactSpan *spans;
+        uint32_t span_count;
+    } recastc_CompactHeightfieldView;
+
+    /// A simplified region outline, see rcContour
+    typedef struct {
+        const int32_t *verts;           ///< x, y, z in cells and the rcContour vertex flags
+        int32_t vert_count;
+        const int32_t *raw_verts;       ///< Same layout, before the simplification
+        int32_t raw_vert_count;
+        uint16_t reg;
+        uint8_t area;
+    } recastc_Contour;
+
+    typedef struct {
+        float bmin[3];
+        float bmax[3];
+        int32_t border_size;
+        const recastc_Contour *contours;
+        int32_t contour_count;
+    } recastc_ContourSetView;
+
+    /// See rcPolyMesh, before the conversion to Detour tile data
+    typedef struct {
+        float bmin[3];
+        float bmax[3];
+        const uint16_t *verts;          ///< x, y, z in cells
+        int32_t vert_count;
+        const uint16_t *polys;          ///< 2 * nvp per poly, the vertices then the neighbour polys, RECASTC_MESH_NULL_IDX padded
+        const uint16_t *regs;
+        const uint16_t *flags;
+        const uint8_t *areas;
+        int32_t poly_count;
+        int32_t nvp;
+    } recastc_PolyMeshView;
+
+    /// Read-only views of every stage of a build, valid until `recastc_free_build`
+    typedef struct {
+        float cell_size;
+        float cell_height;
+        recastc_HeightfieldInput heightfield;   ///< The solid heightfield after the span filters
+        recastc_CompactHeightfieldView compact;
+        recastc_ContourSetView contours;
+        recastc_PolyMeshView poly_mesh;
+    } recastc_BuildArtifacts;
+
+    typedef struct {
+        float cell_size;                ///< The xz-plane cell size in world unit
+        float cell_height;              ///< The y-axis cell height in world unit
+        float walkable_slope_angle;     ///< The maximum walkable slope in degrees
+        float walkable_height;          ///< The agent height in world unit
+        float walkable_radius;          ///< The agent radius in world unit
+        float walkable_climb;           ///< The agent maximum traversable ledge in world unit
+        float max_edge_len;             ///< The maximum contour edge length in world unit
+        float max_simplification_error; ///< The maximum contour deviation in cells
+        int32_t min_region_size;        ///< Regions smaller than size * size cells are removed
+        int32_t merge_region_size;      ///< Regions smaller than size * size cells are merged
+        int32_t max_verts_per_poly;     ///< [Limit: 3..=6]
+        float detail_sample_dist;       ///< The detail mesh sampling distance in world unit, 0 to disable
+        float detail_sample_max_error;  ///< The detail mesh maximum surface deviation in world unit
+        int32_t partition_type;         ///< recastc_PartitionType
+        int32_t filter_flags;           ///< recastc_FilterFlags, the span filters to run
+        uint16_t area_flags[64];        ///< Poly flags of each area id
+        float shallow_water_depth;      ///< Spans submerged up to this depth in world unit are shallow water
+        uint8_t shallow_water_area;
+        uint8_t deep_water_area;
+        int32_t tile_border_size;       ///< The border of the tile builds in cells, 0 for the walkable radius and 3 cells
+        float border_stitch_tolerance;  ///< The height difference of border edges Detour connects in world unit, 0 for walkable_climb
+    } recastc_BuildConfig;
+
+    /// Layout of a tiled navmesh, see dtNavMeshParams
+    typedef struct {
+        float orig[3];                  ///< World position of the tile (0, 0) corner
+        float tile_width;               ///< in world unit
+        float tile_height;              ///< in world unit
+        int32_t max_tiles;
+        int32_t max_polys;              ///< Maximum polys per tile
+    } recastc_TiledMeshParams;
+
+    typedef struct {
+        int32_t tile_size;              ///< Width and depth of a tile in cells
+        float bmin[3];                  ///< Bounds of the whole world in world unit
+        float bmax[3];
+        int32_t tx;                     ///< Tile coordinates
+        int32_t ty;
+    } recastc_TileInput;
+
+    typedef struct {
+        int32_t tile_size;              ///< Width and depth of the tile in cells, at most 65535
+        int32_t tx;                     ///< Tile coordinates
+        int32_t ty;
+    } recastc_MeshTileInput;
+
+    /// Header of Detour tile data, see dtMeshHeader
+    typedef struct {
+        int32_t tx;                     ///< Tile coordinates
+        int32_t ty;
+        int32_t layer;
+        float bmin[3];                  ///< Bounds of the tile in world unit
+        float bmax[3];
+        int32_t poly_count;
+        int32_t vert_count;
+        int32_t off_mesh_connection_count;
+        float walkable_height;          ///< in world unit
+        float walkable_radius;
+        float walkable_climb;
+    } recastc_TileHeader;
+
+    /// A poly edge on the border of its tile not fully connected to the neighbour tile
+    typedef struct {
+        uint32_t poly;
+        int32_t tx;                     ///< Tile coordinates of the poly
+        int32_t ty;
+        int32_t side;                   ///< Detour side of the neighbour tile, 0 towards +x, 2 towards +z, 4 towards -x, 6 towards -z
+        float start[3];
+        float end[3];
+        float covered;                  ///< Fraction of the edge length linked to the neighbour, 0..1
+    } recastc_BorderEdge;
+
+    /// Tile cache layer compression, the callbacks return 0 on failure
+    typedef struct {
+        void* user;
+        int32_t (*max_compressed_size)(void* user, int32_t size);
+        int32_t (*compress)(void* user, const uint8_t* buffer, int32_t size, uint8_t* compressed, int32_t max_compressed_size, int32_t* compressed_size);
+        int32_t (*decompress)(void* user, const uint8_t* compressed, int32_t size, uint8_t* buffer, int32_t max_size, int32_t* size_out);
+    } recastc_Compressor;
+
+    /// Layout of a tile cache, see dtTileCacheParams
+    typedef struct {
+        float bmin[3];                  ///< Bounds of the whole world in world unit
+        float bmax[3];
+        int32_t tile_size;              ///< Width and depth of a tile in cells
+        int32_t max_layers;             ///< Maximum heightfield layers of a tile
+        int32_t max_obstacles;
+        recastc_TiledMeshParams mesh;   ///< max_tiles must fit `max_layers` per tile
+        const recastc_Compressor* compressor;   ///< Null for fastlz, `user` must outlive the tile cache
+    } recastc_TileCacheParams;
+
+    /// dtTileCacheParams as RecastDemo saves them, the layers keep the params they were built with
+    typedef struct {
+        float orig[3];                  ///< World position of the tile (0, 0) corner
+        float cs;
+        float ch;
+        int32_t width;                  ///< Width and depth of a tile in cells
+        int32_t height;
+        float walkable_height;          ///< In world unit
+        float walkable_radius;
+        float walkable_climb;
+        float max_simplification_error;
+        int32_t max_tiles;              ///< Most layers in the tile cache
+        int32_t max_obstacles;
+    } recastc_SavedTileCacheParams;
+
+    /// Poly flags, same as the RecastDemo sample flags
+    enum recastc_PolyFlags {
+        RECASTC_POLYFLAGS_WALK = 0x01,      ///< Ability to walk (ground, grass, road)
+        RECASTC_POLYFLAGS_SWIM = 0x02,      ///< Ability to swim (water)
+        RECASTC_POLYFLAGS_DOOR = 0x04,      ///< Ability to move through doors
+        RECASTC_POLYFLAGS_JUMP = 0x08,      ///< Ability to jump
+        RECASTC_POLYFLAGS_DISABLED = 0x10,  ///< Disabled polygon
+        RECASTC_POLYFLAGS_DROP = 0x20,      ///< Ability to drop down ledges
+        RECASTC_POLYFLAGS_ALL = 0xffff,
+    };
+
+    typedef struct {
+        uint16_t include_flags;         ///< Polys must have one of these flags
+        uint16_t exclude_flags;         ///< Polys must have none of these flags
+        float area_costs[64];           ///< Traversal cost multiplier per area id
+        float uphill_cost;              ///< Extra cost per world unit of height gained
+        float downhill_cost;            ///< Extra cost per world unit of height lost
+    } recastc_QueryFilter;
+
     const char *recastc_version();
 
+    /// Compile time constants of the Detour build, the tile data only load in a build with the same ones
+    typedef struct {
+        int32_t navmesh_magic;          ///< DT_NAVMESH_MAGIC
+        int32_t navmesh_version;        ///< DT_NAVMESH_VERSION
+        int32_t tile_cache_magic;       ///< DT_TILECACHE_MAGIC
+        int32_t tile_cache_version;     ///< DT_TILECACHE_VERSION
+        uint32_t poly_ref_bits;         ///< 64 with DT_POLYREF64, 32 otherwise
+        uint8_t little_endian;
+        uint8_t virtual_query_filter;   ///< Built with DT_VIRTUAL_QUERYFILTER
+    } recastc_BuildInfo;
+
+    void recastc_build_info(recastc_BuildInfo* info);
+
     struct recastc_Query *recastc_create_query(recastc_NavMesh* qparam, recastc_Error* error);
+
+    /// Create the data of one tile of a triangle navmesh, the verts and bounds of `mesh` are the ones of the tile.
+    /// Unconnected edges on the tile bounds become portals to the neighbour tiles.
+    /// The data is freed with `recastc_free_tile_data` or given to `recastc_add_tile`
+    int32_t recastc_create_mesh_tile(const recastc_NavMesh* mesh, const recastc_MeshTileInput* tile, uint8_t** data, int32_t* data_size, recastc_Error* error);
+
+    /// Create a query for an empty tiled navmesh, tiles are added with `recastc_add_tile`
+    struct recastc_Query *recastc_create_tiled_query(const recastc_TiledMeshParams* params, recastc_Error* error);
+
+    /// Run the Recast build pipeline over the triangles overlapping a tile,
+    /// returns 1 with `*data` set to null when the tile has no walkable surface.
+    /// The data is freed with `recastc_free_tile_data` or given to `recastc_add_tile`
+    int32_t recastc_build_tile(const recastc_BuildInput* input,
+        const recastc_BuildConfig* config,
+        const recastc_TileInput* tile,
+        uint8_t** data,
+        int32_t* data_size,
+        recastc_Error* error);
+
+    /// Add the tile to the navmesh, the navmesh takes the ownership of `data` even on failure.
+    /// The data is checked with `recastc_validate_tile_data` first
+    int32_t recastc_add_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, recastc_Error* error);
+
+    /// Remove the tile at the tile coordinates, its data is freed
+    int32_t recastc_remove_tile(struct recastc_Query* query, int32_t tx, int32_t ty, recastc_Error* error);
+
+    /// Remove the layer `layer` of the tile at the tile coordinates, its data is freed
+    int32_t recastc_remove_tile_layer(struct recastc_Query* query, int32_t tx, int32_t ty, int32_t layer, recastc_Error* error);
+
+    /// The data of the tile at the tile coordinates, valid until the tile is removed
+    int32_t recastc_get_tile_data(struct recastc_Query* query, int32_t tx, int32_t ty, const uint8_t** data, int32_t* data_size, recastc_Error* error);
+
+    /// The tile in the slot `index` of the navmesh below its max tiles, with all its layers in the slots.
+    /// `*data` is null for an empty slot, the data is valid until the tile is removed
+    int32_t recastc_get_tile_at_index(struct recastc_Query* query, int32_t index, uint64_t* tile_ref, const uint8_t** data, int32_t* data_size, recastc_Error* error);
+
+    /// Add the tile back at the ref it had when saved, the poly refs of the tile stay the same.
+    /// The navmesh takes the ownership of `data` even on failure
+    int32_t recastc_restore_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, recastc_Error* error);
+
+    /// `recastc_restore_tile` without a copy or the ownership: the navmesh uses and writes `data` where it is.
+    /// `data` must stay valid until the tile is removed or the query freed
+    int32_t recastc_restore_tile_in_place(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, recastc_Error* error);
+
+    /// The layout of the navmesh of the query, a single mesh is one tile covering its bounds
+    int32_t recastc_get_nav_mesh_params(struct recastc_Query* query, recastc_TiledMeshParams* params, recastc_Error* error);
+
+    /// The tile coordinates of the tiles of the navmesh, `tx` then `ty` for each tile.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_tiles`, `tile_count` is set to the needed size
+    int32_t recastc_get_tiles(struct recastc_Query* query, int32_t* coords, int32_t max_tiles, int32_t* tile_count, recastc_Error* error);
+
+    /// Read the header of tile data, fails with INVALID_TILE_DATA for data of another Detour version
+    int32_t recastc_get_tile_header(const uint8_t* data, int32_t data_size, recastc_TileHeader* header, recastc_Error* error);
+
+    /// Check that the counts of the header fit `data_size` and the indices of the polys, detail meshes,
+    /// BV tree and off-mesh connections stay in the tile, as Detour does not check them when adding it.
+    /// Fails with MISALIGNED_TILE_DATA when `data` is not aligned for the structs of the tile
+    int32_t recastc_validate_tile_data(const uint8_t* data, int32_t data_size, recastc_Error* error);
+
+    /// FNV-1a hash of the tile data without the links Detour writes into it when adding the tile,
+    /// the same for the data as built and as read back from a navmesh, and without the top of the
+    /// tile bounds. Validates the data first
+    int32_t recastc_get_tile_mesh_hash(const uint8_t* data, int32_t data_size, uint64_t* hash, recastc_Error* error);
+
+    /// Number of links from the polys of the tile to each neighbour tile, by Detour side:
+    /// 0 towards +x, 2 towards +z, 4 towards -x, 6 towards -z and the diagonals in between
+    int32_t recastc_get_tile_seam_links(struct recastc_Query* query, int32_t tx, int32_t ty, int32_t* counts, recastc_Error* error);
+
+    /// The tile border edges of all tiles facing a neighbour tile with less than their whole length linked to it.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_edges`, `edge_count` is set to the needed size
+    int32_t recastc_find_unmatched_border_edges(struct recastc_Query* query, recastc_BorderEdge* edges, int32_t max_edges, int32_t* edge_count, recastc_Error* error);
+
+    /// The polys of the tile at the tile coordinates passing the query filter, none when there is no tile.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_polys`, `poly_count` is set to the needed size
+    int32_t recastc_get_tile_polys(struct recastc_Query* query, int32_t tx, int32_t ty, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error);
+
+    /// All polys of the navmesh passing the query filter, same buffer protocol as `recastc_get_tile_polys`
+    int32_t recastc_get_polys(struct recastc_Query* query, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error);
+
+    /// The polys linked to the poly passing the query filter, off-mesh connections included
+    int32_t recastc_get_poly_neighbours(struct recastc_Query* query, uint32_t poly, recastc_PolysResult* result, recastc_Error* error);
+
+    /// The triangles of the detail mesh of the poly, none for an off-mesh connection
+    int32_t recastc_get_poly_detail_triangles(struct recastc_Query* query, uint32_t poly, recastc_DetailTrianglesResult* result, recastc_Error* error);
+
+    /// The average of the poly vertices
+    int32_t recastc_get_poly_center(struct recastc_Query* query, uint32_t poly, float* center, recastc_Error* error);
+
+    /// The vertices of the poly in their order in the navmesh, `verts` has room for DT_VERTS_PER_POLYGON.
+    /// The 2 ends for an off-mesh connection
+    int32_t recastc_get_poly_vertices(struct recastc_Query* query, uint32_t poly, float* verts, int32_t* vert_count, recastc_Error* error);
+
+    /// What is across an edge of a poly, edge `i` goes from vertex `i` to the next one
+    typedef enum {
+        RECASTC_EDGE_BOUNDARY = 0,  ///< Nothing, a wall or a tile border without linked neighbour tile
+        RECASTC_EDGE_INTERNAL = 1,  ///< A poly of the same tile
+        RECASTC_EDGE_PORTAL = 2,    ///< A poly of a neighbour tile, linked on at least part of the edge
+    } recastc_EdgeKind;
+
+    /// The `recastc_EdgeKind` of each edge of the poly, `kinds` has room for DT_VERTS_PER_POLYGON.
+    /// No edge for an off-mesh connection
+    int32_t recastc_get_poly_edge_kinds(struct recastc_Query* query, uint32_t poly, uint8_t* kinds, int32_t* edge_count, recastc_Error* error);
+
+    /// The ends of the edge of `from` crossed to reach `to`, the part facing `to` for a tile border edge.
+    /// Both are the end at the ground poly for an off-mesh connection. Fails with NOT_NEIGHBOURS without a link
+    int32_t recastc_get_portal(struct recastc_Query* query, uint32_t from, uint32_t to, float* left, float* right, recastc_Error* error);
+
+    /// Cast a ray along the surface from the point `start` of `poly` toward `end`. `t` is the fraction of the
+    /// ray to the wall hit, FLT_MAX when the ray reaches `end`. The normal of the wall points back into the poly
+    int32_t recastc_raycast(struct recastc_Query* query, uint32_t poly, const float* start, const float* end,
+        float* t, float* hit_pos, float* hit_normal, recastc_Error* error);
+
+    /// Move from the point `start` of `poly` toward `end` constrained to the surface, like
+    /// dtPathCorridor::movePosition. `visited` gets the polys from `poly` to the poly of `*result_pos`
+    int32_t recastc_move_along_surface(struct recastc_Query* query, uint32_t poly, const float* start, const float* end,
+        float* result_pos, recastc_PolysResult* visited, recastc_Error* error);
+
+    /// Distance from the point of `poly` to the nearest wall within `max_radius`, `max_radius` when there is none.
+    /// The normal points from the wall hit to `pos`
+    int32_t recastc_find_distance_to_wall(struct recastc_Query* query, uint32_t poly, const float* pos, float max_radius,
+        float* distance, float* hit_pos, float* hit_normal, recastc_Error* error);
+
+    /// Allocate tile data to be filled by the caller and given to `recastc_add_tile`
+    uint8_t* recastc_alloc_tile_data(int32_t data_size);
+
+    void recastc_free_tile_data(uint8_t* data);
+
+    struct recastc_Build;
+
+    /// Run the Recast build pipeline and keep the intermediate objects, over one tile or the whole input
+    /// when `tile` is null. The views of `artifacts` stay valid until `recastc_free_build`
+    struct recastc_Build* recastc_build_artifacts(const recastc_BuildInput* input,
+        const recastc_BuildConfig* config,
+        const recastc_TileInput* tile,
+        recastc_BuildArtifacts* artifacts,
+        recastc_Error* error);
+
+    /// The Detour tile data of the poly mesh and detail mesh of a finished build, at the coordinates of its tile or (0, 0),
+    /// without building again. `*data` is null when no poly is walkable, the data is owned by the caller
+    int32_t recastc_build_tile_data(const struct recastc_Build* build, uint8_t** data, int32_t* data_size, recastc_Error* error);
+
+    /// Create the query over the navmesh of the poly mesh and detail mesh of a finished build over the whole input.
+    /// The build stays valid and is still freed by `recastc_free_build`
+    struct recastc_Query* recastc_create_query_from_build(const struct recastc_Build* build, recastc_Error* error);
+
+    void recastc_free_build(struct recastc_Build* build);
+
+    struct recastc_TileCache;
+
+    /// Rasterize every tile into the heightfield layers kept by the tile cache, build the navmesh tiles
+    /// of the layers and create the query over that navmesh in `*query`.
+    /// The tile cache must be freed before the query
+    struct recastc_TileCache* recastc_build_tile_cache(const recastc_BuildInput* input,
+        const recastc_BuildConfig* config,
+        const recastc_TileCacheParams* params,
+        struct recastc_Query** query,
+        recastc_Error* error);
+
+    /// Create an empty tile cache with fastlz compression and the query over its empty navmesh in `*query`,
+    /// for saved layers added by `recastc_add_tile_cache_layer`. The tile cache must be freed before the query
+    struct recastc_TileCache* recastc_create_tile_cache(const recastc_SavedTileCacheParams* params,
+        const recastc_TiledMeshParams* mesh,
+        const uint16_t* area_flags,
+        struct recastc_Query** query,
+        recastc_Error* error);
+
+    /// Add a compressed layer saved from a tile cache and build its navmesh tile, the data is copied
+    int32_t recastc_add_tile_cache_layer(struct recastc_TileCache* cache, const uint8_t* data, int32_t size, recastc_Error* error);
+
+    void recastc_get_tile_cache_params(const struct recastc_TileCache* cache, recastc_SavedTileCacheParams* params);
+
+    /// The ref and compressed data of the layer in slot `index`, below max_tiles, owned by the tile cache.
+    /// `*data` is null for an empty slot
+    int32_t recastc_get_tile_cache_layer(const struct recastc_TileCache* cache, int32_t index, uint32_t* layer_ref, const uint8_t** data, int32_t* size, recastc_Error* error);
+
+    /// Queue a cylinder obstacle standing on `pos`, the touched tiles are rebuilt by `recastc_update_tile_cache`
+    int32_t recastc_add_cylinder_obstacle(struct recastc_TileCache* cache, const float* pos, float radius, float height, uint32_t* obstacle, recastc_Error* error);
+
+    /// Queue an axis aligned box obstacle
+    int32_t recastc_add_box_obstacle(struct recastc_TileCache* cache, const float* bmin, const float* bmax, uint32_t* obstacle, recastc_Error* error);
+
+    /// Queue a box obstacle rotated by `y_radians` around the y axis
+    int32_t recastc_add_oriented_box_obstacle(struct recastc_TileCache* cache, const float* center, const float* half_extents, float y_radians, uint32_t* obstacle, recastc_Error* error);
+
+    /// Queue the removal of an obstacle
+    int32_t recastc_remove_obstacle(struct recastc_TileCache* cache, uint32_t obstacle, recastc_Error* error);
+
+    /// Process the queued obstacle changes and rebuild at most one touched tile,
+    /// `up_to_date` is set to 1 when no work is left
+    int32_t recastc_update_tile_cache(struct recastc_TileCache* cache, float dt, uint8_t* up_to_date, recastc_Error* error);
+
+    void recastc_free_tile_cache(struct recastc_TileCache* cache);
+
+    /// Parameters of a crowd agent, see dtCrowdAgentParams
+    typedef struct {
+        float radius;                   ///< At most the max agent radius of the crowd
+        float height;
+        float max_acceleration;
+        float max_speed;
+        float collision_query_range;    ///< Neighbours within this range are steered around
+        float path_optimization_range;  ///< Range of the visibility shortcuts of the corridor
+        float separation_weight;
+        uint8_t update_flags;           ///< UpdateFlags of DetourCrowd.h
+        uint8_t obstacle_avoidance_type;    ///< Avoidance params slot, 0 (low) to 3 (high) quality unless changed
+        uint8_t query_filter_type;      ///< Filter of the crowd, 0 is the query filter when the crowd was created
+    } recastc_CrowdAgentParams;
+
+    /// Sampling of the obstacle avoidance, see dtObstacleAvoidanceParams
+    typedef struct {
+        float vel_bias;
+        float weight_desired_vel;
+        float weight_current_vel;
+        float weight_side;
+        float weight_toi;               ///< Weight of the time to impact
+        float horizon_time;             ///< Seconds ahead to look for collisions
+        uint8_t grid_size;              ///< Only used by grid sampling, the crowd samples adaptively
+        uint8_t adaptive_divs;
+        uint8_t adaptive_rings;
+        uint8_t adaptive_depth;
+    } recastc_ObstacleAvoidanceParams;
+
+    /// State of a crowd agent after the last update
+    typedef struct {
+        float pos[3];
+        float vel[3];                   ///< Velocity after avoidance and collisions
+        float desired_vel[3];           ///< Velocity along the corridor before avoidance
+        float target_pos[3];            ///< End of the corridor once the target is valid, else the requested target
+        uint32_t poly;                  ///< First poly of the corridor
+        uint8_t active;                 ///< 0 for a free agent slot
+        uint8_t state;                  ///< CrowdAgentState
+        uint8_t target_state;           ///< MoveRequestState
+        uint8_t partial;                ///< The path only gets near the target
+        uint8_t detached;               ///< Out of crowd control, see `recastc_crowd_detach_agent`
+    } recastc_CrowdAgentState;
+
+    /// A neighbour of a crowd agent
+    typedef struct {
+        int32_t agent;
+        float distance;                 ///< In world unit
+    } recastc_CrowdNeighbour;
+
+    struct recastc_Crowd;
+
+    /// A crowd moving agents over the navmesh of the query, with local avoidance.
+    /// The crowd must be freed before the query
+    struct recastc_Crowd* recastc_create_crowd(struct recastc_Query* query, int32_t max_agents, float max_agent_radius, recastc_Error* error);
+
+    /// Move the agents to a crowd of more agents or a larger max agent radius, in the same slots.
+    /// They keep their corridor, velocity and target. The path requests waiting in the queue are made again,
+    /// the agents crossing an off-mesh connection land at its end
+    int32_t recastc_crowd_set_capacity(struct recastc_Crowd* crowd, int32_t max_agents, float max_agent_radius, recastc_Error* error);
+
+    /// Move the agents to the navmesh of another query, in the same slots. They are placed on the nearest poly
+    /// and keep their velocity targets, the crowd gets the filter of the query. The other targets are dropped,
+    /// the caller requests them again. The crowd must be freed before the query
+    int32_t recastc_crowd_set_navmesh(struct recastc_Crowd* crowd, struct recastc_Query* query, recastc_Error* error);
+
+    /// Copy the filter of the query to the agents again, e.g. after changing area costs.
+    /// The query must have the navmesh of the crowd
+    int32_t recastc_crowd_set_filter(struct recastc_Crowd* crowd, const struct recastc_Query* query, recastc_Error* error);
+
+    /// Add an agent near `pos`, `agent` is its slot which is reused once it is removed
+    int32_t recastc_crowd_add_agent(struct recastc_Crowd* crowd, const float* pos, const recastc_CrowdAgentParams* params, int32_t* agent, recastc_Error* error);
+
+    /// Change the parameters of an agent in place, it keeps its position, velocity and target
+    int32_t recastc_crowd_update_agent(struct recastc_Crowd* crowd, int32_t agent, const recastc_CrowdAgentParams* params, recastc_Error* error);
+
+    /// The parameters the agent currently has
+    int32_t recastc_crowd_get_agent_params(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdAgentParams* params, recastc_Error* error);
+
+    int32_t recastc_crowd_remove_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error);
+
+    /// Plan a path to `pos` on `poly`, the path is found asynchronously by the next updates
+    int32_t recastc_crowd_request_move_target(struct recastc_Crowd* crowd, int32_t agent, uint32_t poly, const float* pos, recastc_Error* error);
+
+    /// Steer with a velocity instead of a target, e.g. for player controlled agents
+    int32_t recastc_crowd_request_move_velocity(struct recastc_Crowd* crowd, int32_t agent, const float* vel, recastc_Error* error);
+
+    /// Stop following the target or velocity
+    int32_t recastc_crowd_reset_move_target(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error);
+
+    /// Move every agent by `dt` seconds
+    int32_t recastc_crowd_update(struct recastc_Crowd* crowd, float dt, recastc_Error* error);
+
+    /// Take the agent out of crowd control, e.g. for a cutscene or a ragdoll, keeping its slot.
+    /// The crowd does not move it and still steers the others around it
+    int32_t recastc_crowd_detach_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error);
+
+    /// Move a detached agent, the others steer around the new position
+    int32_t recastc_crowd_move_detached_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error);
+
+    /// Give a detached agent back to the crowd near `pos`, with a new corridor and no target
+    int32_t recastc_crowd_attach_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error);
+
+    /// Move an agent near `pos` in the same slot, with a new corridor and boundary and no target.
+    /// Fails for detached agents and while crossing an off-mesh connection
+    int32_t recastc_crowd_teleport_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error);
+
+    /// Open or close the off-mesh connections of `user_id`, all open at first. The crowd holds the
+    /// agents about to take a closed connection until it opens, e.g. an elevator away from their floor
+    int32_t recastc_crowd_set_gate(struct recastc_Crowd* crowd, uint32_t user_id, int32_t open, recastc_Error* error);
+
+    /// The user id of the closed connection the agent waited at in the last update, 0 for none
+    int32_t recastc_crowd_get_agent_gate(struct recastc_Crowd* crowd, int32_t agent, uint32_t* user_id, recastc_Error* error);
+
+    /// Replace the avoidance params of a slot, [0, DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS)
+    int32_t recastc_crowd_set_avoidance_params(struct recastc_Crowd* crowd, int32_t slot, const recastc_ObstacleAvoidanceParams* params, recastc_Error* error);
+
+    int32_t recastc_crowd_get_avoidance_params(struct recastc_Crowd* crowd, int32_t slot, recastc_ObstacleAvoidanceParams* params, recastc_Error* error);
+
+    /// The neighbours of the agent found by the last update within its collision query range, nearest first,
+    /// without the agents removed since.
+    /// Fails with BUFFER_TOO_SMALL and the needed size in `count` when they do not fit
+    int32_t recastc_crowd_get_neighbours(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdNeighbour* neighbours, int32_t max_neighbours, int32_t* count, recastc_Error* error);
+
+    /// The agents within `radius` of `center` on the xz plane, nearest first, from the proximity grid
+    /// the last update filled, without the agents removed since.
+    /// Fails with BUFFER_TOO_SMALL and the needed size in `count` when they do not fit
+    int32_t recastc_crowd_query_agents(struct recastc_Crowd* crowd, const float* center, float radius, recastc_CrowdNeighbour* agents, int32_t max_agents, int32_t* count, recastc_Error* error);
+
+    /// The state of an agent slot, `state->active` is 0 for a free slot
+    int32_t recastc_crowd_get_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdAgentState* state, recastc_Error* error);
+
+    /// Where a crowd agent is on an off-mesh connection, the phases of dtCrowdAgentAnimation
+    typedef enum {
+        RECASTC_OFFMESH_NONE = 0,
+        RECASTC_OFFMESH_ENTERING = 1,   ///< Moving from its position to the start of the connection
+        RECASTC_OFFMESH_TRAVERSING = 2, ///< Moving from the start to the end of the connection
+        RECASTC_OFFMESH_EXITING = 3,    ///< Left the connection in the last update, walking again
+    } recastc_OffMeshPhase;
+
+    /// The off-mesh connection a crowd agent crosses or just crossed
+    typedef struct {
+        uint8_t phase;                  ///< recastc_OffMeshPhase
+        uint32_t poly;                  ///< Poly of the connection
+        uint32_t user_id;
+        float start[3];                 ///< Connection end the agent entered at
+        float end[3];
+        float elapsed;                  ///< Seconds since the agent reached the connection
+        float duration;                 ///< Seconds the crowd takes to cross it
+    } recastc_OffMeshTraversal;
+
+    /// The off-mesh connection of the agent, `traversal->phase` is RECASTC_OFFMESH_NONE when not on one
+    int32_t recastc_crowd_get_off_mesh_traversal(struct recastc_Crowd* crowd, int32_t agent, recastc_OffMeshTraversal* traversal, recastc_Error* error);
+
+    /// A velocity tried by the obstacle avoidance of the debug agent, see dtObstacleAvoidanceDebugData.
+    /// The penalties are normalized to 0..1 over the samples of the update
+    typedef struct {
+        float vel[3];
+        float size;                     ///< Radius of the sample
+        float penalty;                  ///< The avoidance picks the velocity with the lowest penalty
+        float desired_vel_penalty;
+        float current_vel_penalty;
+        float side_penalty;
+        float toi_penalty;              ///< Time to impact
+    } recastc_AvoidanceSample;
+
+    /// What the last update recorded for the debug agent
+    typedef struct {
+        int32_t agent;                  ///< -1 without debug agent
+        uint8_t optimized;              ///< The visibility optimization of the corridor ran
+        float opt_start[3];             ///< The shortcut it tried
+        float opt_end[3];
+    } recastc_CrowdDebugInfo;
+
+    /// Record the avoidance samples and visibility optimization of the agent in the next updates, -1 to stop
+    int32_t recastc_crowd_set_debug_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error);
+
+    /// The recording of the last update for the debug agent.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_samples`, `sample_count` is set to the needed size
+    int32_t recastc_crowd_get_debug_info(struct recastc_Crowd* crowd, recastc_CrowdDebugInfo* info, recastc_AvoidanceSample* samples, int32_t max_samples, int32_t* sample_count, recastc_Error* error);
+
+    /// The polys of the corridor of the agent.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_polys`, `poly_count` is set to the needed size
+    int32_t recastc_crowd_get_agent_corridor(struct recastc_Crowd* crowd, int32_t agent, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error);
+
+    /// The wall segments of the local boundary the avoidance of the agent steers around, 6 floats each.
+    /// Fails with BUFFER_TOO_SMALL when more than `max_segments`, `count` is set to the needed size
+    int32_t recastc_crowd_get_agent_walls(struct recastc_Crowd* crowd, int32_t agent, float* segments, int32_t max_segments, int32_t* count, recastc_Error* error);
+
+    void recastc_free_crowd(struct recastc_Crowd* crowd);
+
+    /// Position, target and polys of a path corridor
+    typedef struct {
+        float pos[3];
+        float target[3];
+        uint32_t first_poly;
+        uint32_t last_poly;
+        int32_t path_count;
+    } recastc_CorridorState;
+
+    struct recastc_Corridor;
+
+    /// A path corridor moved by the caller, see dtPathCorridor. It does not keep the query,
+    /// each call moving over the navmesh takes the query and its filter
+    struct recastc_Corridor* recastc_create_corridor(int32_t max_path, recastc_Error* error);
+
+    /// A corridor of the single poly under `pos`, with the target at `pos`
+    int32_t recastc_corridor_reset(struct recastc_Corridor* corridor, uint32_t poly, const float* pos, recastc_Error* error);
+
+    /// Replace the polys of the corridor, the first one must be the poly of the current position
+    int32_t recastc_corridor_set_path(struct recastc_Corridor* corridor, const float* target, const uint32_t* polys, int32_t count, recastc_Error* error);
+
+    /// Move the position along the navmesh surface toward `pos`, the corridor is trimmed to the polys left
+    int32_t recastc_corridor_move_position(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* pos, recastc_Error* error);
+
+    /// Move the target along the navmesh surface toward `pos`, the corridor is extended with the polys crossed
+    int32_t recastc_corridor_move_target_position(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* pos, recastc_Error* error);
+
+    /// Shortcut the corridor toward `next` with a raycast of at most `range`, `next` is usually the next corner
+    int32_t recastc_corridor_optimize_visibility(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* next, float range, recastc_Error* error);
+
+    /// Replan the start of the corridor with a short local search, `optimized` is 1 when it changed
+    int32_t recastc_corridor_optimize_topology(struct recastc_Corridor* corridor, struct recastc_Query* query, uint8_t* optimized, recastc_Error* error);
+
+    /// The next corners with DT_STRAIGHTPATH flags, the corners past an off-mesh connection are cut
+    int32_t recastc_corridor_find_corners(struct recastc_Corridor* corridor, struct recastc_Query* query,
+        float* corners, uint8_t* flags, uint32_t* polys, int32_t max_corners, int32_t* count, recastc_Error* error);
+
+    /// Step over the off-mesh connection `link` that the corridor reached, `start` and `end` are set to its ends
+    int32_t recastc_corridor_move_over_off_mesh_connection(struct recastc_Corridor* corridor, struct recastc_Query* query,
+        uint32_t link, float* start, float* end, recastc_Error* error);
+
+    /// `valid` is 1 when the first `max_look_ahead` polys still exist and pass the filter
+    int32_t recastc_corridor_is_valid(struct recastc_Corridor* corridor, struct recastc_Query* query, int32_t max_look_ahead, uint8_t* valid, recastc_Error* error);
+
+    /// Cut the corridor at the first invalid poly, restarting from `safe_poly` when the first one is invalid
+    int32_t recastc_corridor_trim_invalid_path(struct recastc_Corridor* corridor, struct recastc_Query* query, uint32_t safe_poly, const float* safe_pos, recastc_Error* error);
+
+    int32_t recastc_corridor_get_state(struct recastc_Corridor* corridor, recastc_CorridorState* state, recastc_Error* error);
+
+    /// The polys of the corridor. Fails with BUFFER_TOO_SMALL and the needed size in `count` when they do not fit
+    int32_t recastc_corridor_get_path(struct recastc_Corridor* corridor, uint32_t* polys, int32_t max_polys, int32_t* count, recastc_Error* error);
+
+    void recastc_free_corridor(struct recastc_Corridor* corridor);
+
+    enum recastc_BoundaryConstants {
+        RECASTC_MAX_BOUNDARY_SEGMENTS = 8,  ///< The nearest segments kept by a local boundary, MAX_LOCAL_SEGS
+    };
+
+    struct recastc_LocalBoundary;
+
+    /// The walls around an agent, see dtLocalBoundary. Like the corridor it does not keep the query
+    struct recastc_LocalBoundary* recastc_create_local_boundary();
+
+    /// Collect the wall segments within `range` of `pos` on the polys around `poly`
+    int32_t recastc_local_boundary_update(struct recastc_LocalBoundary* boundary, struct recastc_Query* query, uint32_t poly, const float* pos, float range, recastc_Error* error);
+
+    /// `valid` is 0 when a poly the segments came from was removed or no longer passes the filter
+    int32_t recastc_local_boundary_is_valid(struct recastc_LocalBoundary* boundary, struct recastc_Query* query, uint8_t* valid, recastc_Error* error);
+
+    /// The position of the last update and the segments as 6 floats each, start then end.
+    /// Fails with BUFFER_TOO_SMALL and the needed size in `count` when they do not fit
+    int32_t recastc_local_boundary_get_segments(struct recastc_LocalBoundary* boundary, float* center, float* segments, int32_t max_segments, int32_t* count, recastc_Error* error);
+
+    /// Drop the segments
+    void recastc_local_boundary_reset(struct recastc_LocalBoundary* boundary);
+
+    void recastc_free_local_boundary(struct recastc_LocalBoundary* boundary);
+
+    struct recastc_ProximityGrid;
+
+    /// A hash grid of 2D boxes with 16 bit ids, see dtProximityGrid. Each box takes one of the
+    /// `pool_size` entries per cell it covers, `pool_size` is below 0xffff
+    struct recastc_ProximityGrid* recastc_create_proximity_grid(int32_t pool_size, float cell_size, recastc_Error* error);
+
+    /// Add the box `bounds` as min x, min y, max x, max y. Fails when the pool has no room left
+    int32_t recastc_proximity_grid_add_item(struct recastc_ProximityGrid* grid, uint16_t id, const float* bounds, recastc_Error* error);
+
+    /// The ids of the items in the cells the box `bounds` covers, each once. The items are not tested
+    /// against the box. Fails with BUFFER_TOO_SMALL and the needed size in `count` when they do not fit
+    int32_t recastc_proximity_grid_query_items(struct recastc_ProximityGrid* grid, const float* bounds, uint16_t* ids, int32_t max_ids, int32_t* count, recastc_Error* error);
+
+    /// Number of items in the cell of the point
+    int32_t recastc_proximity_grid_item_count_at(struct recastc_ProximityGrid* grid, float x, float y, int32_t* count, recastc_Error* error);
+
+    /// Remove all the items
+    void recastc_proximity_grid_clear(struct recastc_ProximityGrid* grid);
+
+    void recastc_free_proximity_grid(struct recastc_ProximityGrid* grid);
+
+    /// Run the Recast build pipeline over raw triangles and create a query for the result
+    struct recastc_Query *recastc_build_query(const recastc_BuildInput* input, 
+        const recastc_BuildConfig* config, 
+        recastc_Error* error);
+
+    /// Run the Recast build pipeline from the spans of a heightfield, skipping the rasterization.
+    /// The volumes, water and off-mesh connections of `input` are applied, its triangles are ignored
+    struct recastc_Query *recastc_build_query_from_heightfield(const recastc_HeightfieldInput* heightfield,
+        const recastc_BuildInput* input,
+        const recastc_BuildConfig* config,
+        recastc_Error* error);
     
     /// This function returns the nearest poly
     int32_t recastc_find_nearest_poly(struct recastc_Query* query, 
@@ -79,6 +891,53 @@ extern "C"
         recastc_PathResult* result,
         recastc_Error* error);
 
+    /// The straight path along a corridor found elsewhere, `result->path` is a copy of the corridor
+    int32_t recastc_find_straight_path(struct recastc_Query* query,
+        const recastc_StraightPathInput* input,
+        recastc_PathResult* result,
+        recastc_Error* error);
+
+
+    /// This function returns the polys touched by a circle around the poly nearest to the center
+    int32_t recastc_find_polys_around_circle(struct recastc_Query* query,
+        const recastc_PolysAroundCircleInput* input,
+        recastc_PolysResult* result,
+        recastc_Error* error);
+
+    /// This function returns a random point on the navmesh
+    int32_t recastc_find_random_point(struct recastc_Query* query,
+        recastc_Rand frand,
+        void* frand_user,
+        recastc_NearestPolyResult* result,
+        recastc_Error* error);
+
+    /// This function returns a random point connected to the poly nearest to the center within the radius
+    int32_t recastc_find_random_point_around_circle(struct recastc_Query* query,
+        const recastc_PolysAroundCircleInput* input,
+        recastc_Rand frand,
+        void* frand_user,
+        recastc_NearestPolyResult* result,
+        recastc_Error* error);
+
+    /// This function returns the height of the detail mesh of the poly at pos
+    int32_t recastc_get_poly_height(struct recastc_Query* query, uint32_t poly, const float* pos, float* height, recastc_Error* error);
+
+    int32_t recastc_get_poly_area(struct recastc_Query* query, uint32_t poly, uint8_t* area, recastc_Error* error);
+
+    int32_t recastc_set_poly_area(struct recastc_Query* query, uint32_t poly, uint8_t area, recastc_Error* error);
+
+    /// Set the traversal cost multiplier of an area in the query filter
+    int32_t recastc_set_area_cost(struct recastc_Query* query, uint8_t area, float cost, recastc_Error* error);
+
+    int32_t recastc_get_poly_flags(struct recastc_Query* query, uint32_t poly, uint16_t* flags, recastc_Error* error);
+
+    int32_t recastc_set_poly_flags(struct recastc_Query* query, uint32_t poly, uint16_t flags, recastc_Error* error);
+
+    /// Replace the query filter used by every query
+    void recastc_set_filter(struct recastc_Query* query, const recastc_QueryFilter* filter);
+
+    /// Look up the off-mesh connection of an off-mesh poly, e.g. one from a path
+    int32_t recastc_get_off_mesh_connection(struct recastc_Query* query, uint32_t poly, recastc_OffMeshConnection* connection, recastc_Error* error);
 
     void recastc_free_query(struct recastc_Query* query);
 }
@@ -1,33 +1,36 @@
-#include "recastc.h"
-#include <DetourNavMeshQuery.h>
+#include "recastc_internal.h"
 #include <DetourNavMeshBuilder.h>
 #include <DetourAlloc.h>
+#include <DetourCommon.h>
+#include <DetourTileCacheBuilder.h>
 
 #include <memory>
-#include <assert.h>
-#include <cstring>
-
-struct recastc_Query
-{
-	dtNavMeshQuery *q;
-	dtNavMesh* mesh;
-	dtQueryFilter filter;
-};
 
 static bool buildMeshAdjacency(unsigned short* polys, const int npolys,
 							   const int nverts, const int vertsPerPoly);
+static bool create_mesh_data(const recastc_NavMesh* qparam, const recastc_MeshTileInput* tile, unsigned char** navData, int* navDataSize, recastc_Error* error);
+static bool restore_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, int flags, recastc_Error* error);
+static bool tile_sections(const dtMeshHeader* h, int64_t* offsets);
 
 /// Reference Doc:
 /// * A discussion how to NavMesh from pre-cull triangles soup
 /// 	* https://groups.google.com/forum/#!topic/recastnavigation/Tjq7G-KUxt8
 
-static void write_error(const char* msg, recastc_Error* error) {
-	assert(strlen(msg) < 255);
-	strcpy(error->msg, msg);	
+/// Detour only accepts a plain function pointer as random source,
+/// so the user callback is passed through thread local storage for the duration of a query
+static thread_local recastc_Rand t_frand = 0;
+static thread_local void* t_frand_user = 0;
+
+static float frand_trampoline()
+{
+	return t_frand(t_frand_user);
 }
 
-#define RETURN_ERROR(MSG)		do {  write_error(MSG, error); return 0;  } while(0)
-#define POLYFLAGS_WALK			0x1
+struct ScopedRand
+{
+	ScopedRand(recastc_Rand frand, void* user) { t_frand = frand; t_frand_user = user; }
+	~ScopedRand() { t_frand = 0; t_frand_user = 0; }
+};
 
 extern "C"
 {	
@@ -36,118 +39,983 @@ extern "C"
 		return "0.0.1";
 	}
 
+	void recastc_build_info(recastc_BuildInfo* info)
+	{
+		assert(info);
+
+		const uint16_t one = 1;
+		info->navmesh_magic = DT_NAVMESH_MAGIC;
+		info->navmesh_version = DT_NAVMESH_VERSION;
+		info->tile_cache_magic = DT_TILECACHE_MAGIC;
+		info->tile_cache_version = DT_TILECACHE_VERSION;
+		info->poly_ref_bits = sizeof(dtPolyRef) * 8;
+		info->little_endian = *(const uint8_t*)&one == 1;
+#ifdef DT_VIRTUAL_QUERYFILTER
+		info->virtual_query_filter = 1;
+#else
+		info->virtual_query_filter = 0;
+#endif
+	}
+
 	/// Create Query Objects
 	struct recastc_Query *recastc_create_query(recastc_NavMesh* qparam, recastc_Error* error)
 	{
-		dtNavMeshCreateParams params;
-		memset(&params, 0, sizeof(params));
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+
+		if (!create_mesh_data(qparam, 0, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		return recastc_create_query_from_data(navData, navDataSize, error);
+	}
+
+	int32_t recastc_create_mesh_tile(const recastc_NavMesh* mesh, const recastc_MeshTileInput* tile, uint8_t** data, int32_t* data_size, recastc_Error* error)
+	{
+		assert(mesh);
+		assert(tile);
+		assert(data);
+		assert(data_size);
+
+		*data = 0;
+		*data_size = 0;
+
+		if (tile->tile_size <= 0 || tile->tile_size > 0xffff) {
+			RETURN_ERROR("tile_size must be in 1..=65535");
+		}
+
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+		if (!create_mesh_data(mesh, tile, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		*data = navData;
+		*data_size = navDataSize;
+		return 1;
+	}
+
+	struct recastc_Query *recastc_create_query_from_data(unsigned char* navData, int navDataSize, recastc_Error* error)
+	{
+		dtNavMesh *mesh = dtAllocNavMesh();
+
+		dtStatus status = mesh->init(navData, navDataSize, DT_TILE_FREE_DATA);
+		if (dtStatusFailed(status))
+		{
+			dtFree(navData);
+			dtFreeNavMesh(mesh);
+
+			RETURN_ERROR("Could not init Detour navmesh");
+		}
+
+		return recastc_create_query_from_mesh(mesh, error);
+	}
+
+	struct recastc_Query *recastc_create_query_from_mesh(dtNavMesh* mesh, recastc_Error* error)
+	{
+		auto q = dtAllocNavMeshQuery();
+		dtStatus status = q->init(mesh, 4096);
+		if (dtStatusFailed(status))
+		{
+			dtFreeNavMeshQuery(q);
+			dtFreeNavMesh(mesh);		
+
+			RETURN_ERROR("Could not init Detour navmesh");
+		}
+			
+		auto query = new recastc_Query();
+		query->q = q;
+		query->mesh = mesh;
+		query->filter.setIncludeFlags(RECASTC_POLYFLAGS_ALL ^ RECASTC_POLYFLAGS_DISABLED);
+		query->filter.setExcludeFlags(0);
+
+		return query;
+	}
+
+	int32_t recastc_get_off_mesh_connection(struct recastc_Query* query, uint32_t poly, recastc_OffMeshConnection* connection, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(connection);
+
+		const dtOffMeshConnection* con = query->mesh->getOffMeshConnectionByRef(poly);
+		if (!con)
+		{
+			RETURN_ERROR("NOT_OFF_MESH_CONNECTION");
+		}
+
+		memcpy(connection->start, &con->pos[0], sizeof(connection->start));
+		memcpy(connection->end, &con->pos[3], sizeof(connection->end));
+		connection->radius = con->rad;
+		connection->bidirectional = (con->flags & DT_OFFMESH_CON_BIDIR) ? 1 : 0;
+		connection->user_id = con->userId;
+		query->mesh->getPolyArea(poly, &connection->area);
+		query->mesh->getPolyFlags(poly, &connection->flags);
+
+		return 1;
+	}
+
+	struct recastc_Query *recastc_create_tiled_query(const recastc_TiledMeshParams* params, recastc_Error* error)
+	{
+		assert(params);
+
+		dtNavMeshParams mparams;
+		memcpy(mparams.orig, params->orig, sizeof(mparams.orig));
+		mparams.tileWidth = params->tile_width;
+		mparams.tileHeight = params->tile_height;
+		mparams.maxTiles = params->max_tiles;
+		mparams.maxPolys = params->max_polys;
+
+		dtNavMesh *mesh = dtAllocNavMesh();
+		if (dtStatusFailed(mesh->init(&mparams)))
+		{
+			dtFreeNavMesh(mesh);
+			RETURN_ERROR("Could not init tiled Detour navmesh");
+		}
+
+		return recastc_create_query_from_mesh(mesh, error);
+	}
+
+	int32_t recastc_add_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(data);
+
+		// Detour trusts the counts of the header, the data may come from a file
+		if (!recastc_validate_tile_data(data, data_size, error))
+		{
+			dtFree(data);
+			return 0;
+		}
+
+		dtStatus status = query->mesh->addTile(data, data_size, DT_TILE_FREE_DATA, 0, 0);
+		if (dtStatusFailed(status))
+		{
+			dtFree(data);
+			if (dtStatusDetail(status, DT_ALREADY_OCCUPIED))
+				RETURN_ERROR("TILE_ALREADY_OCCUPIED");
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_remove_tile(struct recastc_Query* query, int32_t tx, int32_t ty, recastc_Error* error)
+	{
+		return recastc_remove_tile_layer(query, tx, ty, 0, error);
+	}
+
+	int32_t recastc_remove_tile_layer(struct recastc_Query* query, int32_t tx, int32_t ty, int32_t layer, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+
+		dtTileRef ref = query->mesh->getTileRefAt(tx, ty, layer);
+		if (!ref)
+		{
+			RETURN_ERROR("NO_TILE");
+		}
+
+		// The tile was added with DT_TILE_FREE_DATA, its data is freed here
+		if (dtStatusFailed(query->mesh->removeTile(ref, 0, 0)))
+		{
+			RETURN_ERROR("FAIL_TO_REMOVE_TILE");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_tile_data(struct recastc_Query* query, int32_t tx, int32_t ty, const uint8_t** data, int32_t* data_size, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(data);
+		assert(data_size);
+
+		const dtMeshTile* tile = query->mesh->getTileAt(tx, ty, 0);
+		if (!tile || !tile->header)
+		{
+			RETURN_ERROR("NO_TILE");
+		}
+
+		*data = tile->data;
+		*data_size = tile->dataSize;
+		return 1;
+	}
+
+	int32_t recastc_get_tile_at_index(struct recastc_Query* query, int32_t index, uint64_t* tile_ref, const uint8_t** data, int32_t* data_size, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(tile_ref);
+		assert(data);
+		assert(data_size);
+
+		const dtNavMesh* mesh = query->mesh;
+		if (index < 0 || index >= mesh->getMaxTiles())
+		{
+			RETURN_ERROR("INVALID_PARAM");
+		}
+
+		const dtMeshTile* tile = mesh->getTile(index);
+		if (!tile || !tile->header)
+		{
+			*tile_ref = 0;
+			*data = nullptr;
+			*data_size = 0;
+			return 1;
+		}
+
+		*tile_ref = mesh->getTileRef(tile);
+		*data = tile->data;
+		*data_size = tile->dataSize;
+		return 1;
+	}
+
+	int32_t recastc_restore_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, recastc_Error* error)
+	{
+		if (!restore_tile(query, data, data_size, tile_ref, DT_TILE_FREE_DATA, error))
+		{
+			dtFree(data);
+			return 0;
+		}
+		return 1;
+	}
+
+	int32_t recastc_restore_tile_in_place(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, recastc_Error* error)
+	{
+		// Without DT_TILE_FREE_DATA removing the tile or freeing the navmesh leaves the data alone
+		return restore_tile(query, data, data_size, tile_ref, 0, error);
+	}
+
+	int32_t recastc_get_nav_mesh_params(struct recastc_Query* query, recastc_TiledMeshParams* params, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(params);
+
+		const dtNavMeshParams* mparams = query->mesh->getParams();
+		memcpy(params->orig, mparams->orig, sizeof(params->orig));
+		params->tile_width = mparams->tileWidth;
+		params->tile_height = mparams->tileHeight;
+		params->max_tiles = mparams->maxTiles;
+		params->max_polys = mparams->maxPolys;
+		return 1;
+	}
+
+	int32_t recastc_get_tiles(struct recastc_Query* query, int32_t* coords, int32_t max_tiles, int32_t* tile_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(tile_count);
+
+		const dtNavMesh* mesh = query->mesh;
+		int n = 0;
+		for (int t = 0; t < mesh->getMaxTiles(); t++)
+		{
+			const dtMeshTile* tile = mesh->getTile(t);
+			if (!tile || !tile->header)
+				continue;
+			if (n < max_tiles)
+			{
+				coords[n * 2] = tile->header->x;
+				coords[n * 2 + 1] = tile->header->y;
+			}
+			n++;
+		}
+
+		*tile_count = n;
+		if (n > max_tiles)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_tile_header(const uint8_t* data, int32_t data_size, recastc_TileHeader* header, recastc_Error* error)
+	{
+		assert(data);
+		assert(header);
+
+		if (data_size < (int32_t)sizeof(dtMeshHeader))
+		{
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		dtMeshHeader h;
+		memcpy(&h, data, sizeof(dtMeshHeader));
+		if (h.magic != DT_NAVMESH_MAGIC || h.version != DT_NAVMESH_VERSION)
+		{
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		header->tx = h.x;
+		header->ty = h.y;
+		header->layer = h.layer;
+		memcpy(header->bmin, h.bmin, sizeof(header->bmin));
+		memcpy(header->bmax, h.bmax, sizeof(header->bmax));
+		header->poly_count = h.polyCount;
+		header->vert_count = h.vertCount;
+		header->off_mesh_connection_count = h.offMeshConCount;
+		header->walkable_height = h.walkableHeight;
+		header->walkable_radius = h.walkableRadius;
+		header->walkable_climb = h.walkableClimb;
+		return 1;
+	}
+
+	int32_t recastc_validate_tile_data(const uint8_t* data, int32_t data_size, recastc_Error* error)
+	{
+		assert(data);
+
+		// Detour reads the structs of the tile where they are
+		if ((uintptr_t)data % alignof(dtLink) != 0 || (uintptr_t)data % alignof(dtMeshHeader) != 0)
+		{
+			RETURN_ERROR("MISALIGNED_TILE_DATA");
+		}
+		if (data_size < (int32_t)sizeof(dtMeshHeader))
+		{
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		const dtMeshHeader* h = (const dtMeshHeader*)data;
+		if (h->magic != DT_NAVMESH_MAGIC || h->version != DT_NAVMESH_VERSION)
+		{
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		int64_t offsets[10];
+		if (!tile_sections(h, offsets) || offsets[9] > data_size)
+		{
+			RETURN_ERROR("INVALID_TILE_DATA");
+		}
+
+		const dtPoly* polys = (const dtPoly*)(data + offsets[2]);
+		const dtPolyDetail* details = (const dtPolyDetail*)(data + offsets[4]);
+		const uint8_t* detailTris = data + offsets[6];
+		const dtBVNode* nodes = (const dtBVNode*)(data + offsets[7]);
+		const dtOffMeshConnection* cons = (const dtOffMeshConnection*)(data + offsets[8]);
+
+		// Every index Detour follows without checking
+		for (int i = 0; i < h->polyCount; i++)
+		{
+			const dtPoly& p = polys[i];
+			if (p.vertCount > DT_VERTS_PER_POLYGON)
+			{
+				RETURN_ERROR("INVALID_TILE_DATA");
+			}
+			for (int j = 0; j < p.vertCount; j++)
+			{
+				if (p.verts[j] >= h->vertCount)
+				{
+					RETURN_ERROR("INVALID_TILE_DATA");
+				}
+			}
+			if (p.getType() == DT_POLYTYPE_OFFMESH_CONNECTION)
+			{
+				if (p.vertCount != 2)
+				{
+					RETURN_ERROR("INVALID_TILE_DATA");
+				}
+				continue;
+			}
+
+			if (p.vertCount < 3 || i >= h->detailMeshCount)
+			{
+				RETURN_ERROR("INVALID_TILE_DATA");
+			}
+			for (int j = 0; j < p.vertCount; j++)
+			{
+				const unsigned short nei = p.neis[j];
+				if (!(nei & DT_EXT_LINK) && nei > h->polyCount)
+				{
+					RETURN_ERROR("INVALID_TILE_DATA");
+				}
+			}
+
+			const dtPolyDetail& pd = details[i];
+			if ((int64_t)pd.vertBase + pd.vertCount > h->detailVertCount || (int64_t)pd.triBase + pd.triCount > h->detailTriCount)
+			{
+				RETURN_ERROR("INVALID_TILE_DATA");
+			}
+			for (int t = 0; t < pd.triCount; t++)
+			{
+				const uint8_t* tri = detailTris + ((int64_t)pd.triBase + t) * 4;
+				for (int k = 0; k < 3; k++)
+				{
+					if (tri[k] >= p.vertCount + pd.vertCount)
+					{
+						RETURN_ERROR("INVALID_TILE_DATA");
+					}
+				}
+			}
+		}
+
+		for (int i = 0; i < h->bvNodeCount; i++)
+		{
+			if (nodes[i].i >= h->polyCount)
+			{
+				RETURN_ERROR("INVALID_TILE_DATA");
+			}
+		}
+
+		for (int i = 0; i < h->offMeshConCount; i++)
+		{
+			if (cons[i].poly >= h->polyCount)
+			{
+				RETURN_ERROR("INVALID_TILE_DATA");
+			}
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_tile_mesh_hash(const uint8_t* data, int32_t data_size, uint64_t* hash, recastc_Error* error)
+	{
+		assert(hash);
+
+		if (!recastc_validate_tile_data(data, data_size, error))
+		{
+			return 0;
+		}
+
+		const dtMeshHeader* h = (const dtMeshHeader*)data;
+		int64_t offsets[10];
+		tile_sections(h, offsets);
+
+		// FNV-1a of the sections, without the links and the first link of each poly Detour
+		// writes when adding the tile
+		uint64_t hv = 0xcbf29ce484222325ull;
+		auto feed = [&hv](const uint8_t* bytes, int64_t len) {
+			for (int64_t i = 0; i < len; i++)
+			{
+				hv ^= bytes[i];
+				hv *= 0x100000001b3ull;
+			}
+		};
+		// The top of a tile of a tiled build is the top of the whole geometry, it only clamps
+		// the queries and changes with geometry far from the tile
+		dtMeshHeader header = *h;
+		header.bmax[1] = 0;
+		feed((const uint8_t*)&header, sizeof(header));
+		feed(data + sizeof(dtMeshHeader), offsets[2] - (int64_t)sizeof(dtMeshHeader));
+		const dtPoly* polys = (const dtPoly*)(data + offsets[2]);
+		for (int i = 0; i < h->polyCount; i++)
+		{
+			const uint8_t* p = (const uint8_t*)&polys[i];
+			feed(p + sizeof(polys[i].firstLink), sizeof(dtPoly) - sizeof(polys[i].firstLink));
+		}
+		feed(data + offsets[4], offsets[9] - offsets[4]);
+
+		*hash = hv;
+		return 1;
+	}
+
+	int32_t recastc_get_tile_seam_links(struct recastc_Query* query, int32_t tx, int32_t ty, int32_t* counts, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(counts);
+
+		const dtMeshTile* tile = query->mesh->getTileAt(tx, ty, 0);
+		if (!tile || !tile->header)
+		{
+			RETURN_ERROR("NO_TILE");
+		}
+
+		for (int i = 0; i < 8; ++i)
+			counts[i] = 0;
+
+		for (int i = 0; i < tile->header->polyCount; ++i)
+		{
+			const dtPoly* poly = &tile->polys[i];
+			for (unsigned int j = poly->firstLink; j != DT_NULL_LINK; j = tile->links[j].next)
+			{
+				// Links inside the tile have no side
+				const unsigned char side = tile->links[j].side;
+				if (side < 8)
+					counts[side]++;
+			}
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_find_unmatched_border_edges(struct recastc_Query* query, recastc_BorderEdge* edges, int32_t max_edges, int32_t* edge_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(edge_count);
+
+		// Neighbour tile offsets by Detour side, as in dtNavMesh::getNeighbourTilesAt
+		static const int offsets[8][2] = { {1, 0}, {1, 1}, {0, 1}, {-1, 1}, {-1, 0}, {-1, -1}, {0, -1}, {1, -1} };
+
+		const dtNavMesh* mesh = query->mesh;
+		int n = 0;
+		for (int t = 0; t < mesh->getMaxTiles(); t++)
+		{
+			const dtMeshTile* tile = mesh->getTile(t);
+			if (!tile || !tile->header)
+				continue;
+
+			const dtPolyRef base = mesh->getPolyRefBase(tile);
+			for (int i = 0; i < tile->header->polyCount; i++)
+			{
+				const dtPoly* poly = &tile->polys[i];
+				if (poly->getType() == DT_POLYTYPE_OFFMESH_CONNECTION)
+					continue;
+
+				for (int j = 0; j < (int)poly->vertCount; j++)
+				{
+					// Recast marks the poly edges on the tile border with the side of the neighbour
+					if (!(poly->neis[j] & DT_EXT_LINK))
+						continue;
+					const int side = poly->neis[j] & 0xff;
+					if (side > 7)
+						continue;
+
+					const dtMeshTile* neighbour = nullptr;
+					if (!mesh->getTilesAt(tile->header->x + offsets[side][0], tile->header->y + offsets[side][1], &neighbour, 1))
+						continue;
+
+					// Each link covers [bmin, bmax] of the edge in 1/255, truncated so a whole edge may sum to 254
+					int covered = 0;
+					for (unsigned int k = poly->firstLink; k != DT_NULL_LINK; k = tile->links[k].next)
+					{
+						const dtLink& link = tile->links[k];
+						if (link.edge == j && link.side == side)
+							covered += link.bmax - link.bmin;
+					}
+					if (covered >= 254)
+						continue;
+
+					if (n < max_edges)
+					{
+						recastc_BorderEdge& edge = edges[n];
+						edge.poly = base | (dtPolyRef)i;
+						edge.tx = tile->header->x;
+						edge.ty = tile->header->y;
+						edge.side = side;
+						memcpy(edge.start, &tile->verts[poly->verts[j] * 3], sizeof(edge.start));
+						memcpy(edge.end, &tile->verts[poly->verts[(j + 1) % poly->vertCount] * 3], sizeof(edge.end));
+						edge.covered = covered / 255.0f;
+					}
+					n++;
+				}
+			}
+		}
+
+		*edge_count = n;
+		if (n > max_edges)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_tile_polys(struct recastc_Query* query, int32_t tx, int32_t ty, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(poly_count);
+
+		*poly_count = 0;
+
+		const dtMeshTile* tile = query->mesh->getTileAt(tx, ty, 0);
+		if (!tile || !tile->header)
+		{
+			return 1;
+		}
+
+		const dtPolyRef base = query->mesh->getPolyRefBase(tile);
+		int n = 0;
+		for (int i = 0; i < tile->header->polyCount; i++)
+		{
+			const dtPolyRef ref = base | (dtPolyRef)i;
+			if (!query->filter.passFilter(ref, tile, &tile->polys[i]))
+				continue;
+			if (n < max_polys)
+				polys[n] = ref;
+			n++;
+		}
+
+		// The needed size is reported so the caller can retry with a larger buffer
+		*poly_count = n;
+		if (n > max_polys)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_polys(struct recastc_Query* query, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(poly_count);
+
+		const dtNavMesh* mesh = query->mesh;
+		int n = 0;
+		for (int t = 0; t < mesh->getMaxTiles(); t++)
+		{
+			const dtMeshTile* tile = mesh->getTile(t);
+			if (!tile || !tile->header)
+				continue;
+
+			const dtPolyRef base = mesh->getPolyRefBase(tile);
+			for (int i = 0; i < tile->header->polyCount; i++)
+			{
+				const dtPolyRef ref = base | (dtPolyRef)i;
+				if (!query->filter.passFilter(ref, tile, &tile->polys[i]))
+					continue;
+				if (n < max_polys)
+					polys[n] = ref;
+				n++;
+			}
+		}
+
+		*poly_count = n;
+		if (n > max_polys)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_neighbours(struct recastc_Query* query, uint32_t poly, recastc_PolysResult* result, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(result);
+
+		const dtMeshTile* tile = 0;
+		const dtPoly* p = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(poly, &tile, &p)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		const int max_polys = sizeof(result->polys) / sizeof(uint32_t);
+		int n = 0;
+		for (unsigned int i = p->firstLink; i != DT_NULL_LINK && n < max_polys; i = tile->links[i].next)
+		{
+			const dtPolyRef ref = tile->links[i].ref;
+			if (!ref)
+				continue;
+
+			const dtMeshTile* ntile = 0;
+			const dtPoly* npoly = 0;
+			query->mesh->getTileAndPolyByRefUnsafe(ref, &ntile, &npoly);
+			if (!query->filter.passFilter(ref, ntile, npoly))
+				continue;
+
+			result->polys[n++] = ref;
+		}
+
+		result->poly_count = n;
+		return 1;
+	}
+
+	int32_t recastc_get_poly_detail_triangles(struct recastc_Query* query, uint32_t poly, recastc_DetailTrianglesResult* result, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(result);
+
+		const dtMeshTile* tile = 0;
+		const dtPoly* p = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(poly, &tile, &p)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		result->tri_count = 0;
+		if (p->getType() == DT_POLYTYPE_OFFMESH_CONNECTION)
+		{
+			return 1;
+		}
+
+		// Same lookup as dtNavMeshQuery::getPolyHeight
+		const dtPolyDetail* pd = &tile->detailMeshes[p - tile->polys];
+		for (int j = 0; j < (int)pd->triCount; j++)
+		{
+			const unsigned char* t = &tile->detailTris[(pd->triBase + j) * 4];
+			for (int k = 0; k < 3; k++)
+			{
+				const float* v = t[k] < p->vertCount
+					? &tile->verts[p->verts[t[k]] * 3]
+					: &tile->detailVerts[(pd->vertBase + (t[k] - p->vertCount)) * 3];
+				memcpy(&result->verts[(j * 3 + k) * 3], v, sizeof(float) * 3);
+			}
+		}
+		result->tri_count = pd->triCount;
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_center(struct recastc_Query* query, uint32_t poly, float* center, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(center);
+
+		const dtMeshTile* tile = 0;
+		const dtPoly* p = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(poly, &tile, &p)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		center[0] = center[1] = center[2] = 0;
+		for (int j = 0; j < (int)p->vertCount; j++)
+		{
+			const float* v = &tile->verts[p->verts[j] * 3];
+			center[0] += v[0];
+			center[1] += v[1];
+			center[2] += v[2];
+		}
+		const float s = 1.0f / p->vertCount;
+		center[0] *= s;
+		center[1] *= s;
+		center[2] *= s;
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_vertices(struct recastc_Query* query, uint32_t poly, float* verts, int32_t* vert_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(verts);
+		assert(vert_count);
+
+		const dtMeshTile* tile = 0;
+		const dtPoly* p = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(poly, &tile, &p)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		for (int j = 0; j < (int)p->vertCount; j++)
+		{
+			dtVcopy(&verts[j * 3], &tile->verts[p->verts[j] * 3]);
+		}
+		*vert_count = p->vertCount;
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_edge_kinds(struct recastc_Query* query, uint32_t poly, uint8_t* kinds, int32_t* edge_count, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(kinds);
+		assert(edge_count);
+
+		const dtMeshTile* tile = 0;
+		const dtPoly* p = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(poly, &tile, &p)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		if (p->getType() == DT_POLYTYPE_OFFMESH_CONNECTION)
+		{
+			*edge_count = 0;
+			return 1;
+		}
+
+		// Reference: duDebugDrawNavMesh, the tile border edges are only linked once the neighbour tile is added
+		for (int j = 0; j < (int)p->vertCount; j++)
+		{
+			if (!(p->neis[j] & DT_EXT_LINK))
+			{
+				kinds[j] = p->neis[j] ? RECASTC_EDGE_INTERNAL : RECASTC_EDGE_BOUNDARY;
+				continue;
+			}
+
+			kinds[j] = RECASTC_EDGE_BOUNDARY;
+			for (unsigned int k = p->firstLink; k != DT_NULL_LINK; k = tile->links[k].next)
+			{
+				if (tile->links[k].edge == j && tile->links[k].ref)
+				{
+					kinds[j] = RECASTC_EDGE_PORTAL;
+					break;
+				}
+			}
+		}
+		*edge_count = p->vertCount;
+
+		return 1;
+	}
+
+	int32_t recastc_get_portal(struct recastc_Query* query, uint32_t from, uint32_t to, float* left, float* right, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+		assert(left);
+		assert(right);
+
+		const dtMeshTile* fromTile = 0;
+		const dtPoly* fromPoly = 0;
+		const dtMeshTile* toTile = 0;
+		const dtPoly* toPoly = 0;
+		if (dtStatusFailed(query->mesh->getTileAndPolyByRef(from, &fromTile, &fromPoly)) ||
+			dtStatusFailed(query->mesh->getTileAndPolyByRef(to, &toTile, &toPoly)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		// Reference: dtNavMeshQuery::getPortalPoints, private in Detour
+		const dtLink* link = 0;
+		for (unsigned int k = fromPoly->firstLink; k != DT_NULL_LINK; k = fromTile->links[k].next)
+		{
+			if (fromTile->links[k].ref == to)
+			{
+				link = &fromTile->links[k];
+				break;
+			}
+		}
+		if (!link)
+		{
+			RETURN_ERROR("NOT_NEIGHBOURS");
+		}
+
+		// The portal of an off-mesh connection is the end at the ground poly
+		if (fromPoly->getType() == DT_POLYTYPE_OFFMESH_CONNECTION || toPoly->getType() == DT_POLYTYPE_OFFMESH_CONNECTION)
+		{
+			const bool fromOffMesh = fromPoly->getType() == DT_POLYTYPE_OFFMESH_CONNECTION;
+			const dtMeshTile* tile = fromOffMesh ? fromTile : toTile;
+			const dtPoly* con = fromOffMesh ? fromPoly : toPoly;
+			const dtPolyRef ground = fromOffMesh ? to : from;
+			for (unsigned int k = con->firstLink; k != DT_NULL_LINK; k = tile->links[k].next)
+			{
+				if (tile->links[k].ref == ground)
+				{
+					const float* v = &tile->verts[con->verts[tile->links[k].edge] * 3];
+					dtVcopy(left, v);
+					dtVcopy(right, v);
+					return 1;
+				}
+			}
+			RETURN_ERROR("NOT_NEIGHBOURS");
+		}
+
+		const int v0 = fromPoly->verts[link->edge];
+		const int v1 = fromPoly->verts[(link->edge + 1) % (int)fromPoly->vertCount];
+		dtVcopy(left, &fromTile->verts[v0 * 3]);
+		dtVcopy(right, &fromTile->verts[v1 * 3]);
+
+		// Only part of a tile border edge may face the neighbour poly
+		if (link->side != 0xff && (link->bmin != 0 || link->bmax != 255))
+		{
+			const float s = 1.0f / 255.0f;
+			dtVlerp(left, &fromTile->verts[v0 * 3], &fromTile->verts[v1 * 3], link->bmin * s);
+			dtVlerp(right, &fromTile->verts[v0 * 3], &fromTile->verts[v1 * 3], link->bmax * s);
+		}
+		return 1;
+	}
+
+	int32_t recastc_raycast(struct recastc_Query* query, uint32_t poly, const float* start, const float* end,
+		float* t, float* hit_pos, float* hit_normal, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(start);
+		assert(end);
+		assert(t);
+		assert(hit_pos);
+		assert(hit_normal);
+
+		dtRaycastHit hit;
+		memset(&hit, 0, sizeof(hit));
+		dtStatus status = query->q->raycast(poly, start, end, &query->filter, 0, &hit);
+
+		if (dtStatusDetail(status, DT_INVALID_PARAM))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("Fail to raycast: reason[unknown]");
+		}
+
+		*t = hit.t;
+		dtVlerp(hit_pos, start, end, dtMin(hit.t, 1.0f));
+		dtVcopy(hit_normal, hit.hitNormal);
+		return 1;
+	}
+
+	int32_t recastc_move_along_surface(struct recastc_Query* query, uint32_t poly, const float* start, const float* end,
+		float* result_pos, recastc_PolysResult* visited, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(start);
+		assert(end);
+		assert(result_pos);
+		assert(visited);
 
-		/// NOTE:
-		/// NavMesh use unsigned short to store position data
-		/// Which means we have to normalize a float to unsigned short
-		int pm_vert_count = qparam->vert_count;							///< The number vertices in the polygon mesh. [Limit: >= 3]
-		int pm_nvp = 3;												    ///< Number maximum number of vertices per polygon. [Limit: >= 3]
-		int pm_polyCount = qparam->triangles_count;					    ///< Number of polygons in the mesh. [Limit: >= 1]		
-		
-		auto pm_polys = std::unique_ptr<unsigned short[]>(new unsigned short[pm_polyCount * 2 * pm_nvp]);  ///< The polygon data. [Size: #polyCount * 2 * #nvp]		
-		memset(pm_polys.get(), 0, pm_polyCount * 2 * pm_nvp * sizeof(unsigned short));
+		const int max_visited = (int)(sizeof(visited->polys) / sizeof(visited->polys[0]));
+		int n = 0;
+		dtStatus status = query->q->moveAlongSurface(poly, start, end, &query->filter, result_pos, visited->polys, &n, max_visited);
 
-		// fill the first part of the pm_polys with triangle indices
-		for (int i = 0; i < pm_polyCount; i++)
+		if (dtStatusDetail(status, DT_INVALID_PARAM))
 		{
-			pm_polys[i*pm_nvp*2+0] = qparam->indices[i*pm_nvp+0];
-			pm_polys[i*pm_nvp*2+1] = qparam->indices[i*pm_nvp+1];
-			pm_polys[i*pm_nvp*2+2] = qparam->indices[i*pm_nvp+2];
-			pm_polys[i*pm_nvp*2+3] = 0;
-			pm_polys[i*pm_nvp*2+4] = 0;
-			pm_polys[i*pm_nvp*2+5] = 0;
+			RETURN_ERROR("INVALID_POLY");
 		}
 
-		if(!buildMeshAdjacency(pm_polys.get(), pm_polyCount, pm_vert_count, pm_nvp)) {
-			RETURN_ERROR("dtCreateNavMeshdata failed!");
+		if (dtStatusFailed(status) || n == 0)
+		{
+			RETURN_ERROR("Fail to move along surface: reason[unknown]");
 		}
 
-		auto pm_polyFlags = std::unique_ptr<uint16_t[]>(new uint16_t[pm_polyCount]);		    ///< The user defined flags assigned to each polygon. [Size: #polyCount]
-		memset(pm_polyFlags.get(), 0, sizeof(uint16_t) * pm_polyCount);		
-		
-		// Referece: Sample_SoloMesh.cpp Line:667
-		for(auto i = 0; i < pm_polyCount; i++)
-			pm_polyFlags[i] = POLYFLAGS_WALK;
-
-
-		auto pm_polyAreas = std::unique_ptr<unsigned char[]>(new unsigned char[pm_polyCount]);		    ///< The user defined area ids assigned to each polygon. [Size: #polyCount]		
-		// TODO(edwin):
-		// We assume all polygon are in the same area
-		memset(pm_polyAreas.get(), 0, sizeof(unsigned char) * pm_polyCount);		
-
-		//< The polygon mesh vertices. [(x, y, z) * #vertCount] [Unit: vx]
-		auto pm_verts_p = std::unique_ptr<uint16_t[]>(new uint16_t[pm_vert_count*3]);
-		memcpy(pm_verts_p.get(), qparam->verts, sizeof(uint16_t) * pm_vert_count * 3);
-
-		params.verts = pm_verts_p.get();
-		params.vertCount = pm_vert_count;
-		params.polys = pm_polys.get();
-		params.polyCount = pm_polyCount;
-		params.polyAreas = pm_polyAreas.get();
-		params.polyFlags = pm_polyFlags.get();
-		params.nvp = pm_nvp;
-		params.detailMeshes = 0;
-		params.offMeshConAreas = 0;
-		params.offMeshConCount = 0;
-		params.offMeshConDir = 0;
-		params.offMeshConFlags = 0;
-		params.offMeshConRad = 0;
-		params.offMeshConUserID = 0;
-		params.offMeshConVerts = 0;
-		params.walkableHeight = qparam->walkable_height;
-		params.walkableRadius = qparam->walkable_radius;
-		params.walkableClimb = qparam->walkable_climb;
-		params.cs = qparam->cell_size;
-		params.ch = qparam->cell_height;
-		memcpy(params.bmin, &qparam->bmin, sizeof(params.bmin));
-		memcpy(params.bmax, &qparam->bmax, sizeof(params.bmax));
-		params.buildBvTree = true;
+		// Same as dtPathCorridor::movePosition, the height of the poly reached
+		float h = result_pos[1];
+		query->q->getPolyHeight(visited->polys[n - 1], result_pos, &h);
+		result_pos[1] = h;
+		visited->poly_count = (uint32_t)n;
+		return 1;
+	}
 
-		unsigned char* navData = 0;
-		int navDataSize = 0;
+	int32_t recastc_find_distance_to_wall(struct recastc_Query* query, uint32_t poly, const float* pos, float max_radius,
+		float* distance, float* hit_pos, float* hit_normal, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(pos);
+		assert(distance);
+		assert(hit_pos);
+		assert(hit_normal);
 
-		if (!dtCreateNavMeshData(&params, &navData, &navDataSize)) {
-			RETURN_ERROR("dtCreateNavMeshdata failed!");
-		}
+		dtStatus status = query->q->findDistanceToWall(poly, pos, max_radius, &query->filter, distance, hit_pos, hit_normal);
 
-		if(navData == NULL) {
-			RETURN_ERROR("dtCreateNavMeshdata return null data");
-		}
-		if(navDataSize == 0) {
-			RETURN_ERROR("dtCreateNavMeshdata return zero size data");
+		if (dtStatusDetail(status, DT_INVALID_PARAM))
+		{
+			RETURN_ERROR("INVALID_POLY");
 		}
 
-		dtNavMesh *mesh = dtAllocNavMesh();
-
-		dtStatus status = mesh->init(navData, navDataSize, DT_TILE_FREE_DATA);
 		if (dtStatusFailed(status))
 		{
-			dtFree(navData);
-			dtFreeNavMesh(mesh);
-
-			RETURN_ERROR("Could not init Detour navmesh");
+			RETURN_ERROR("Fail to find distance to wall: reason[unknown]");
 		}
 
-		auto q = dtAllocNavMeshQuery();
-		status = q->init(mesh, 4096);
-		if (dtStatusFailed(status))
-		{
-			dtFreeNavMeshQuery(q);
-			dtFreeNavMesh(mesh);		
+		return 1;
+	}
 
-			RETURN_ERROR("Could not init Detour navmesh");
-		}
-			
-		auto query = new recastc_Query();
-		query->q = q;
-		query->mesh = mesh;
+	uint8_t* recastc_alloc_tile_data(int32_t data_size)
+	{
+		return (uint8_t*)dtAlloc(data_size, DT_ALLOC_PERM);
+	}
 
-		return query;
+	void recastc_free_tile_data(uint8_t* data)
+	{
+		dtFree(data);
 	}
 
 	void recastc_free_query(struct recastc_Query *query)
@@ -168,7 +1036,6 @@ extern "C"
 		assert(result);		
 		assert(sizeof(dtPolyRef) == sizeof(uint32_t));
 
-		query->filter.setIncludeFlags(POLYFLAGS_WALK);
 		dtStatus status = query->q->findNearestPoly(input->center, input->half_extents, &query->filter, &result->poly, result->pos);
 
 		if (dtStatusFailed(status))
@@ -209,8 +1076,6 @@ extern "C"
 		assert(input);
 		assert(result);
 
-		query->filter.setIncludeFlags(POLYFLAGS_WALK);
-
 		dtStatus status = query->q->closestPointOnPoly(input->poly, input->pos, result->pos, 0);
 
 		if( dtStatusDetail(status, DT_INVALID_PARAM) )
@@ -264,6 +1129,35 @@ extern "C"
 	// 	return 1;
 	// }
 
+	/// Run the funnel over the first `result->path_count` polys of `result->path`
+	/// A straight path stopped after `max_points` points is a success, the rest is computed later
+	static dtStatus straight_path(struct recastc_Query* query, const float* start_pos, const float* end_pos, int32_t options, uint32_t max_points, recastc_PathResult* result)
+	{
+		int n_straight_polys = 0;
+		int max_straight_path = sizeof(result->path2_polys) / sizeof(uint32_t);
+		const bool limited = max_points > 0 && max_points < (uint32_t)max_straight_path;
+		if (limited)
+			max_straight_path = (int)max_points;
+
+		dtStatus status = query->q->findStraightPath(start_pos, end_pos, result->path, (int)result->path_count, result->path2, result->path2_flags, result->path2_polys, &n_straight_polys, max_straight_path, options);
+		result->path2_count = n_straight_polys;
+
+		// Straight path points are on the flat poly planes, snap them to the detail mesh
+		for (int i = 0; i < n_straight_polys && dtStatusSucceed(status); i++)
+		{
+			if (result->path2_flags[i] & DT_STRAIGHTPATH_OFFMESH_CONNECTION)
+				continue;
+
+			float h = 0;
+			if (dtStatusSucceed(query->q->getPolyHeight(result->path2_polys[i], &result->path2[i * 3], &h)))
+				result->path2[i * 3 + 1] = h;
+		}
+
+		if (limited)
+			status &= ~DT_BUFFER_TOO_SMALL;
+		return status;
+	}
+
 	int32_t recastc_find_path(struct recastc_Query* query, 
         const recastc_PathInput* input,
         recastc_PathResult* result,
@@ -274,32 +1168,38 @@ extern "C"
 		assert(input);
 		assert(result);
 
-		query->filter.setIncludeFlags(POLYFLAGS_WALK);	
-
 		int count = 0;
+		const int max_path = sizeof(result->path) / sizeof(uint32_t);
 
-		dtStatus status = query->q->findPath(
-			input->start_poly, 
-			input->end_poly, 
-			input->start_pos,
-			input->end_pos,			
-			&query->filter,
-			result->path,
-			&count,
-			sizeof(result->path) / sizeof(uint32_t)
-		);
+		dtStatus status;
+		if (input->find_path_options & DT_FINDPATH_ANY_ANGLE)
+		{
+			// Any angle search is only available in the sliced path finder
+			status = query->q->initSlicedFindPath(input->start_poly, input->end_poly, input->start_pos, input->end_pos,
+				&query->filter, DT_FINDPATH_ANY_ANGLE);
+			while (dtStatusInProgress(status))
+				status = query->q->updateSlicedFindPath(max_path, 0);
+			if (dtStatusSucceed(status))
+				status = query->q->finalizeSlicedFindPath(result->path, &count, max_path);
+		}
+		else
+		{
+			status = query->q->findPath(
+				input->start_poly, 
+				input->end_poly, 
+				input->start_pos,
+				input->end_pos,			
+				&query->filter,
+				result->path,
+				&count,
+				max_path
+			);
+		}
 		
 		result->path_count = count;
 
-		float straight_path[2048 * 3];
-		unsigned char straight_path_flags[2048];
-
-		int n_straight_polys;
-		dtPolyRef straight_path_polys[2048];
-
 		if (count) {
-			status = query->q->findStraightPath(input->start_pos, input->end_pos, result->path, count, result->path2, straight_path_flags, straight_path_polys, &n_straight_polys, 2048, DT_STRAIGHTPATH_AREA_CROSSINGS);
-			result->path2_count = n_straight_polys;
+			status = straight_path(query, input->start_pos, input->end_pos, input->straight_path_options, input->max_points, result);
 		}
 
 
@@ -330,6 +1230,237 @@ extern "C"
 
 		return 1;
 	}
+
+	int32_t recastc_find_straight_path(struct recastc_Query* query,
+		const recastc_StraightPathInput* input,
+		recastc_PathResult* result,
+		recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(input);
+		assert(result);
+
+		const uint32_t max_path = sizeof(result->path) / sizeof(uint32_t);
+		if (input->corridor_count == 0)
+		{
+			RETURN_ERROR("EMPTY_CORRIDOR");
+		}
+		if (input->corridor_count > max_path)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		memcpy(result->path, input->corridor, input->corridor_count * sizeof(uint32_t));
+		result->path_count = input->corridor_count;
+
+		dtStatus status = straight_path(query, input->start_pos, input->end_pos, input->straight_path_options, input->max_points, result);
+
+		if( dtStatusDetail(status, DT_INVALID_PARAM) )
+		{
+			RETURN_ERROR("INVALID_PARAM");
+		}
+
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_FIND_PATH");
+		}
+
+		if( dtStatusDetail(status, DT_BUFFER_TOO_SMALL) )
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_find_polys_around_circle(struct recastc_Query* query,
+		const recastc_PolysAroundCircleInput* input,
+		recastc_PolysResult* result,
+		recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(input);
+		assert(result);
+
+		dtPolyRef start = 0;
+		float nearest[3];
+		dtStatus status = query->q->findNearestPoly(input->center, input->half_extents, &query->filter, &start, nearest);
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_FIND_POLY");
+		}
+
+		result->poly_count = 0;
+		if (!start)
+		{
+			return 1;
+		}
+
+		const int max_polys = sizeof(result->polys) / sizeof(uint32_t);
+		int count = 0;
+		// Polys beyond the buffer size are ignored (DT_BUFFER_TOO_SMALL)
+		status = query->q->findPolysAroundCircle(start, nearest, input->radius, &query->filter, result->polys, 0, 0, &count, max_polys);
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_FIND_POLYS");
+		}
+
+		result->poly_count = count;
+		return 1;
+	}
+
+	int32_t recastc_find_random_point(struct recastc_Query* query,
+		recastc_Rand frand,
+		void* frand_user,
+		recastc_NearestPolyResult* result,
+		recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(frand);
+		assert(result);
+
+		ScopedRand scoped(frand, frand_user);
+		dtStatus status = query->q->findRandomPoint(&query->filter, frand_trampoline, &result->poly, result->pos);
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_FIND_RANDOM_POINT");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_find_random_point_around_circle(struct recastc_Query* query,
+		const recastc_PolysAroundCircleInput* input,
+		recastc_Rand frand,
+		void* frand_user,
+		recastc_NearestPolyResult* result,
+		recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(input);
+		assert(frand);
+		assert(result);
+
+		dtPolyRef start = 0;
+		float nearest[3];
+		dtStatus status = query->q->findNearestPoly(input->center, input->half_extents, &query->filter, &start, nearest);
+		if (dtStatusFailed(status) || !start)
+		{
+			RETURN_ERROR("FAIL_TO_FIND_POLY");
+		}
+
+		ScopedRand scoped(frand, frand_user);
+		status = query->q->findRandomPointAroundCircle(start, nearest, input->radius, &query->filter, frand_trampoline, &result->poly, result->pos);
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_FIND_RANDOM_POINT");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_height(struct recastc_Query* query, uint32_t poly, const float* pos, float* height, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->q);
+		assert(pos);
+		assert(height);
+
+		dtStatus status = query->q->getPolyHeight(poly, pos, height);
+		if (dtStatusFailed(status))
+		{
+			RETURN_ERROR("FAIL_TO_GET_HEIGHT");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_get_poly_area(struct recastc_Query* query, uint32_t poly, uint8_t* area, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+
+		if (dtStatusFailed(query->mesh->getPolyArea(poly, area)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_set_poly_area(struct recastc_Query* query, uint32_t poly, uint8_t area, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+
+		if (area >= DT_MAX_AREAS)
+		{
+			RETURN_ERROR("INVALID_AREA");
+		}
+
+		if (dtStatusFailed(query->mesh->setPolyArea(poly, area)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_set_area_cost(struct recastc_Query* query, uint8_t area, float cost, recastc_Error* error)
+	{
+		assert(query);
+
+		if (area >= DT_MAX_AREAS)
+		{
+			RETURN_ERROR("INVALID_AREA");
+		}
+
+		query->filter.setAreaCost(area, cost);
+		return 1;
+	}
+
+	int32_t recastc_get_poly_flags(struct recastc_Query* query, uint32_t poly, uint16_t* flags, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+
+		if (dtStatusFailed(query->mesh->getPolyFlags(poly, flags)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_set_poly_flags(struct recastc_Query* query, uint32_t poly, uint16_t flags, recastc_Error* error)
+	{
+		assert(query);
+		assert(query->mesh);
+
+		if (dtStatusFailed(query->mesh->setPolyFlags(poly, flags)))
+		{
+			RETURN_ERROR("INVALID_POLY");
+		}
+
+		return 1;
+	}
+
+	void recastc_set_filter(struct recastc_Query* query, const recastc_QueryFilter* filter)
+	{
+		assert(query);
+		assert(filter);
+
+		query->filter.setIncludeFlags(filter->include_flags);
+		query->filter.setExcludeFlags(filter->exclude_flags);
+		for (int i = 0; i < DT_MAX_AREAS; i++)
+			query->filter.setAreaCost(i, filter->area_costs[i]);
+		query->filter.uphillCost = filter->uphill_cost;
+		query->filter.downhillCost = filter->downhill_cost;
+	}
 }
 
 struct rcEdge
@@ -433,3 +1564,180 @@ static bool buildMeshAdjacency(unsigned short* polys, const int npolys,
 	
 	return true;
 }
+
+/// Mark the unconnected edges lying on the tile bounds as portals to the neighbour tiles, like rcBuildPolyMesh does for tiles
+static void mark_portal_edges(unsigned short* polys, const int npolys, const uint16_t* verts, const int vertsPerPoly, const int tileSize)
+{
+	for (int i = 0; i < npolys; ++i)
+	{
+		unsigned short* p = &polys[i*vertsPerPoly*2];
+		for (int j = 0; j < vertsPerPoly; ++j)
+		{
+			if (p[vertsPerPoly + j] != RC_MESH_NULL_IDX)
+				continue;
+			const uint16_t* va = &verts[p[j]*3];
+			const uint16_t* vb = &verts[p[(j+1) % vertsPerPoly]*3];
+
+			if (va[0] == 0 && vb[0] == 0)
+				p[vertsPerPoly + j] = 0x8000 | 0;
+			else if (va[2] == tileSize && vb[2] == tileSize)
+				p[vertsPerPoly + j] = 0x8000 | 1;
+			else if (va[0] == tileSize && vb[0] == tileSize)
+				p[vertsPerPoly + j] = 0x8000 | 2;
+			else if (va[2] == 0 && vb[2] == 0)
+				p[vertsPerPoly + j] = 0x8000 | 3;
+		}
+	}
+}
+
+/// The Detour data of a triangle mesh, a single tile of a tiled navmesh when `tile` is set
+static bool create_mesh_data(const recastc_NavMesh* qparam, const recastc_MeshTileInput* tile, unsigned char** navData, int* navDataSize, recastc_Error* error)
+{
+	dtNavMeshCreateParams params;
+	memset(&params, 0, sizeof(params));
+
+	/// NOTE:
+	/// NavMesh use unsigned short to store position data
+	/// Which means we have to normalize a float to unsigned short
+	int pm_vert_count = qparam->vert_count;							///< The number vertices in the polygon mesh. [Limit: >= 3]
+	int pm_nvp = 3;												    ///< Number maximum number of vertices per polygon. [Limit: >= 3]
+	int pm_polyCount = qparam->triangles_count;					    ///< Number of polygons in the mesh. [Limit: >= 1]		
+	
+	auto pm_polys = std::unique_ptr<unsigned short[]>(new unsigned short[pm_polyCount * 2 * pm_nvp]);  ///< The polygon data. [Size: #polyCount * 2 * #nvp]		
+	memset(pm_polys.get(), 0, pm_polyCount * 2 * pm_nvp * sizeof(unsigned short));
+
+	// fill the first part of the pm_polys with triangle indices
+	for (int i = 0; i < pm_polyCount; i++)
+	{
+		pm_polys[i*pm_nvp*2+0] = qparam->indices[i*pm_nvp+0];
+		pm_polys[i*pm_nvp*2+1] = qparam->indices[i*pm_nvp+1];
+		pm_polys[i*pm_nvp*2+2] = qparam->indices[i*pm_nvp+2];
+		pm_polys[i*pm_nvp*2+3] = 0;
+		pm_polys[i*pm_nvp*2+4] = 0;
+		pm_polys[i*pm_nvp*2+5] = 0;
+	}
+
+	if (tile)
+	{
+		// Unconnected edges are borders, until they turn out to be on the tile bounds
+		for (int i = 0; i < pm_polyCount; i++)
+			for (int j = 0; j < pm_nvp; j++)
+				pm_polys[i*pm_nvp*2+pm_nvp+j] = RC_MESH_NULL_IDX;
+	}
+
+	if(!buildMeshAdjacency(pm_polys.get(), pm_polyCount, pm_vert_count, pm_nvp)) {
+		RETURN_ERROR("dtCreateNavMeshdata failed!");
+	}
+
+	if (tile)
+		mark_portal_edges(pm_polys.get(), pm_polyCount, qparam->verts, pm_nvp, tile->tile_size);
+
+	auto pm_polyFlags = std::unique_ptr<uint16_t[]>(new uint16_t[pm_polyCount]);		    ///< The user defined flags assigned to each polygon. [Size: #polyCount]
+	memset(pm_polyFlags.get(), 0, sizeof(uint16_t) * pm_polyCount);		
+	
+	// Referece: Sample_SoloMesh.cpp Line:667
+	for(auto i = 0; i < pm_polyCount; i++)
+		pm_polyFlags[i] = RECASTC_POLYFLAGS_WALK;
+
+
+	auto pm_polyAreas = std::unique_ptr<unsigned char[]>(new unsigned char[pm_polyCount]);		    ///< The user defined area ids assigned to each polygon. [Size: #polyCount]		
+	// Each triangle is a poly
+	if (qparam->areas)
+		memcpy(pm_polyAreas.get(), qparam->areas, sizeof(unsigned char) * pm_polyCount);
+	else
+		memset(pm_polyAreas.get(), 0, sizeof(unsigned char) * pm_polyCount);
+
+	//< The polygon mesh vertices. [(x, y, z) * #vertCount] [Unit: vx]
+	auto pm_verts_p = std::unique_ptr<uint16_t[]>(new uint16_t[pm_vert_count*3]);
+	memcpy(pm_verts_p.get(), qparam->verts, sizeof(uint16_t) * pm_vert_count * 3);
+
+	params.verts = pm_verts_p.get();
+	params.vertCount = pm_vert_count;
+	params.polys = pm_polys.get();
+	params.polyCount = pm_polyCount;
+	params.polyAreas = pm_polyAreas.get();
+	params.polyFlags = pm_polyFlags.get();
+	params.nvp = pm_nvp;
+	params.detailMeshes = 0;
+	OffMeshParams offMesh;
+	offMesh.fill(qparam->off_mesh_connections, qparam->off_mesh_connection_count, params);
+	params.walkableHeight = qparam->walkable_height;
+	params.walkableRadius = qparam->walkable_radius;
+	params.walkableClimb = qparam->walkable_climb;
+	params.cs = qparam->cell_size;
+	params.ch = qparam->cell_height;
+	memcpy(params.bmin, &qparam->bmin, sizeof(params.bmin));
+	memcpy(params.bmax, &qparam->bmax, sizeof(params.bmax));
+	params.buildBvTree = true;
+	if (tile)
+	{
+		params.tileX = tile->tx;
+		params.tileY = tile->ty;
+	}
+
+	if (!dtCreateNavMeshData(&params, navData, navDataSize)) {
+		RETURN_ERROR("dtCreateNavMeshdata failed!");
+	}
+
+	if(*navData == NULL) {
+		RETURN_ERROR("dtCreateNavMeshdata return null data");
+	}
+	if(*navDataSize == 0) {
+		RETURN_ERROR("dtCreateNavMeshdata return zero size data");
+	}
+
+	return true;
+}
+
+static bool restore_tile(struct recastc_Query* query, uint8_t* data, int32_t data_size, uint64_t tile_ref, int flags, recastc_Error* error)
+{
+	assert(query);
+	assert(query->mesh);
+	assert(data);
+
+	// Detour trusts the counts of the header, saved data may come from anywhere
+	if (!recastc_validate_tile_data(data, data_size, error))
+	{
+		return false;
+	}
+
+	// A ref which does not fit the refs of this build would be truncated to another tile
+	if ((uint64_t)(dtTileRef)tile_ref != tile_ref)
+	{
+		RETURN_ERROR("INVALID_TILE_REF");
+	}
+
+	dtStatus status = query->mesh->addTile(data, data_size, flags, (dtTileRef)tile_ref, 0);
+	if (dtStatusFailed(status))
+	{
+		if (dtStatusDetail(status, DT_ALREADY_OCCUPIED))
+			RETURN_ERROR("TILE_ALREADY_OCCUPIED");
+		if (dtStatusDetail(status, DT_OUT_OF_MEMORY))
+			RETURN_ERROR("INVALID_TILE_REF");
+		RETURN_ERROR("INVALID_TILE_DATA");
+	}
+
+	return true;
+}
+
+/// The offsets of the sections of a tile in the order dtNavMesh::addTile cuts them out of the data,
+/// each 4 byte aligned, and the end of the last one in offsets[9]. False for a negative count
+static bool tile_sections(const dtMeshHeader* h, int64_t* offsets)
+{
+	const int64_t counts[] = { 1, h->vertCount, h->polyCount, h->maxLinkCount, h->detailMeshCount,
+		h->detailVertCount, h->detailTriCount, h->bvNodeCount, h->offMeshConCount };
+	const int64_t sizes[] = { sizeof(dtMeshHeader), sizeof(float) * 3, sizeof(dtPoly), sizeof(dtLink), sizeof(dtPolyDetail),
+		sizeof(float) * 3, sizeof(unsigned char) * 4, sizeof(dtBVNode), sizeof(dtOffMeshConnection) };
+	int64_t end = 0;
+	for (int i = 0; i < 9; i++)
+	{
+		if (counts[i] < 0)
+		{
+			return false;
+		}
+		offsets[i] = end;
+		end += (counts[i] * sizes[i] + 3) & ~(int64_t)3;
+	}
+	offsets[9] = end;
+	return true;
+}
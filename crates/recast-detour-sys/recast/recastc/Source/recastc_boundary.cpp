@@ -0,0 +1,74 @@
+#include "recastc_internal.h"
+#include <DetourLocalBoundary.h>
+
+/// Reference: dtCrowd::update, which updates the boundary of its agents the same way
+
+struct recastc_LocalBoundary
+{
+	dtLocalBoundary boundary;
+};
+
+extern "C"
+{
+	struct recastc_LocalBoundary* recastc_create_local_boundary()
+	{
+		return new recastc_LocalBoundary();
+	}
+
+	int32_t recastc_local_boundary_update(struct recastc_LocalBoundary* boundary, struct recastc_Query* query, uint32_t poly, const float* pos, float range, recastc_Error* error)
+	{
+		assert(boundary);
+		assert(query);
+
+		if (!query->mesh->isValidPolyRef(poly))
+		{
+			RETURN_ERROR("Invalid poly");
+		}
+		if (!(range > 0.0f))
+		{
+			RETURN_ERROR("Invalid range");
+		}
+
+		boundary->boundary.update(poly, pos, range, query->q, &query->filter);
+		return 1;
+	}
+
+	int32_t recastc_local_boundary_is_valid(struct recastc_LocalBoundary* boundary, struct recastc_Query* query, uint8_t* valid, recastc_Error* error)
+	{
+		assert(boundary);
+		assert(query);
+		assert(valid);
+
+		*valid = boundary->boundary.isValid(query->q, &query->filter) ? 1 : 0;
+		return 1;
+	}
+
+	int32_t recastc_local_boundary_get_segments(struct recastc_LocalBoundary* boundary, float* center, float* segments, int32_t max_segments, int32_t* count, recastc_Error* error)
+	{
+		assert(boundary);
+		assert(count);
+
+		*count = boundary->boundary.getSegmentCount();
+		if (*count > max_segments)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		memcpy(center, boundary->boundary.getCenter(), sizeof(float) * 3);
+		for (int i = 0; i < *count; i++)
+		{
+			memcpy(&segments[i * 6], boundary->boundary.getSegment(i), sizeof(float) * 6);
+		}
+		return 1;
+	}
+
+	void recastc_local_boundary_reset(struct recastc_LocalBoundary* boundary)
+	{
+		boundary->boundary.reset();
+	}
+
+	void recastc_free_local_boundary(struct recastc_LocalBoundary* boundary)
+	{
+		delete boundary;
+	}
+}
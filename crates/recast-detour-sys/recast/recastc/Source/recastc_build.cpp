@@ -0,0 +1,765 @@
+#include "recastc_internal.h"
+#include <Recast.h>
+#include <DetourNavMeshBuilder.h>
+
+#include <math.h>
+#include <vector>
+
+/// Reference: Sample_SoloMesh.cpp handleBuild
+
+/// Owns the intermediate Recast objects of a build
+struct BuildState
+{
+	rcCompactHeightfield* chf = nullptr;
+	rcContourSet* cset = nullptr;
+	rcPolyMesh* pmesh = nullptr;
+	rcPolyMeshDetail* dmesh = nullptr;
+
+	~BuildState()
+	{
+		rcFreeCompactHeightfield(chf);
+		rcFreeContourSet(cset);
+		rcFreePolyMesh(pmesh);
+		rcFreePolyMeshDetail(dmesh);
+	}
+};
+
+void init_config(const recastc_BuildConfig* config, rcConfig& cfg)
+{
+	memset(&cfg, 0, sizeof(cfg));
+	cfg.cs = config->cell_size;
+	cfg.ch = config->cell_height;
+	cfg.walkableSlopeAngle = config->walkable_slope_angle;
+	cfg.walkableHeight = (int)ceilf(config->walkable_height / cfg.ch);
+	cfg.walkableClimb = (int)floorf(config->walkable_climb / cfg.ch);
+	cfg.walkableRadius = (int)ceilf(config->walkable_radius / cfg.cs);
+	cfg.maxEdgeLen = (int)(config->max_edge_len / cfg.cs);
+	cfg.maxSimplificationError = config->max_simplification_error;
+	cfg.minRegionArea = (int)rcSqr(config->min_region_size);
+	cfg.mergeRegionArea = (int)rcSqr(config->merge_region_size);
+	cfg.maxVertsPerPoly = config->max_verts_per_poly;
+	cfg.detailSampleDist = config->detail_sample_dist;
+	cfg.detailSampleMaxError = config->detail_sample_max_error;
+}
+
+int tile_border_size(const recastc_BuildConfig* config, const rcConfig& cfg)
+{
+	return config->tile_border_size > 0 ? config->tile_border_size : cfg.walkableRadius + 3;
+}
+
+float border_stitch_tolerance(const recastc_BuildConfig* config)
+{
+	return config->border_stitch_tolerance > 0.0f ? config->border_stitch_tolerance : config->walkable_climb;
+}
+
+void init_tile_config(const recastc_TileInput* tile, const recastc_BuildConfig* config, rcConfig& cfg)
+{
+	// Rasterize a border around the tile so the regions and contours match the neighbours
+	cfg.tileSize = tile->tile_size;
+	cfg.borderSize = tile_border_size(config, cfg);
+	cfg.width = cfg.tileSize + cfg.borderSize * 2;
+	cfg.height = cfg.tileSize + cfg.borderSize * 2;
+
+	const float tcs = cfg.tileSize * cfg.cs;
+	cfg.bmin[0] = tile->bmin[0] + tile->tx * tcs - cfg.borderSize * cfg.cs;
+	cfg.bmin[1] = tile->bmin[1];
+	cfg.bmin[2] = tile->bmin[2] + tile->ty * tcs - cfg.borderSize * cfg.cs;
+	cfg.bmax[0] = tile->bmin[0] + (tile->tx + 1) * tcs + cfg.borderSize * cfg.cs;
+	cfg.bmax[1] = tile->bmax[1];
+	cfg.bmax[2] = tile->bmin[2] + (tile->ty + 1) * tcs + cfg.borderSize * cfg.cs;
+}
+
+/// Crossing test of the point against a polygon in the xz-plane, as in RecastArea.cpp
+static bool point_in_poly(int nvert, const float* verts, const float x, const float z)
+{
+	bool c = false;
+	for (int i = 0, j = nvert - 1; i < nvert; j = i++)
+	{
+		const float* vi = &verts[i * 3];
+		const float* vj = &verts[j * 3];
+		if (((vi[2] > z) != (vj[2] > z)) &&
+			(x < (vj[0] - vi[0]) * (z - vi[2]) / (vj[2] - vi[2]) + vi[0]))
+			c = !c;
+	}
+	return c;
+}
+
+/// Mark the walkable spans below a water surface as shallow or deep water by their depth
+static void mark_water(const recastc_BuildInput* input, const recastc_BuildConfig* config, rcCompactHeightfield& chf)
+{
+	if (input->water_count == 0)
+		return;
+
+	for (int z = 0; z < chf.height; z++)
+	{
+		for (int x = 0; x < chf.width; x++)
+		{
+			const float px = chf.bmin[0] + (x + 0.5f) * chf.cs;
+			const float pz = chf.bmin[2] + (z + 0.5f) * chf.cs;
+
+			// The highest surface covering the cell
+			bool wet = false;
+			float surface = 0;
+			for (uint32_t w = 0; w < input->water_count; w++)
+			{
+				const recastc_WaterVolume& water = input->water[w];
+				if (water.nverts > 0 && !point_in_poly(water.nverts, water.verts, px, pz))
+					continue;
+				if (!wet || water.surface > surface)
+					surface = water.surface;
+				wet = true;
+			}
+			if (!wet)
+				continue;
+
+			const rcCompactCell& c = chf.cells[x + z * chf.width];
+			for (int i = (int)c.index, ni = (int)(c.index + c.count); i < ni; i++)
+			{
+				if (chf.areas[i] == RC_NULL_AREA)
+					continue;
+
+				const float depth = surface - (chf.bmin[1] + chf.spans[i].y * chf.ch);
+				if (depth <= 0)
+					continue;
+
+				chf.areas[i] = depth <= config->shallow_water_depth ? config->shallow_water_area : config->deep_water_area;
+			}
+		}
+	}
+}
+
+/// Filter the solid heightfield, erode the walkable area and mark the water and convex volume areas
+static rcCompactHeightfield* compact_heightfield(rcContext* ctx, rcHeightfield& solid, const recastc_BuildInput* input, const recastc_BuildConfig* config, const rcConfig& cfg, recastc_Error* error)
+{
+	if (config->filter_flags & RECASTC_FILTER_LOW_HANGING_OBSTACLES)
+		rcFilterLowHangingWalkableObstacles(ctx, cfg.walkableClimb, solid);
+	if (config->filter_flags & RECASTC_FILTER_LEDGE_SPANS)
+		rcFilterLedgeSpans(ctx, cfg.walkableHeight, cfg.walkableClimb, solid);
+	if (config->filter_flags & RECASTC_FILTER_WALKABLE_LOW_HEIGHT_SPANS)
+		rcFilterWalkableLowHeightSpans(ctx, cfg.walkableHeight, solid);
+
+	rcCompactHeightfield* chf = rcAllocCompactHeightfield();
+	if (!chf)
+		RETURN_ERROR("Out of memory 'chf'");
+	if (!rcBuildCompactHeightfield(ctx, cfg.walkableHeight, cfg.walkableClimb, solid, *chf) ||
+		!rcErodeWalkableArea(ctx, cfg.walkableRadius, *chf))
+	{
+		rcFreeCompactHeightfield(chf);
+		RETURN_ERROR("Could not build compact data");
+	}
+
+	// Water first, so the user volumes can still override it
+	mark_water(input, config, *chf);
+
+	for (uint32_t i = 0; i < input->volume_count; i++)
+	{
+		const recastc_ConvexVolume& vol = input->volumes[i];
+		rcMarkConvexPolyArea(ctx, vol.verts, vol.nverts, vol.hmin, vol.hmax, vol.area, *chf);
+	}
+
+	return chf;
+}
+
+rcCompactHeightfield* build_compact_heightfield(rcContext* ctx, const recastc_BuildInput* input, const recastc_BuildConfig* config, const rcConfig& cfg, recastc_Error* error,
+	rcHeightfield** solidOut)
+{
+	const int nverts = (int)input->vert_count;
+	const int ntris = (int)input->tri_count;
+
+	struct SolidHolder
+	{
+		rcHeightfield* hf = rcAllocHeightfield();
+		~SolidHolder() { rcFreeHeightField(hf); }
+	} solid;
+	if (!solid.hf)
+		RETURN_ERROR("Out of memory 'solid'");
+	if (!rcCreateHeightfield(ctx, *solid.hf, cfg.width, cfg.height, cfg.bmin, cfg.bmax, cfg.cs, cfg.ch))
+		RETURN_ERROR("Could not create solid heightfield");
+
+	// Only the triangles overlapping the heightfield, a tile is much smaller than the world
+	std::vector<int> tris;
+	std::vector<int> sourceTris;
+	tris.reserve(ntris * 3);
+	for (int i = 0; i < ntris; i++)
+	{
+		const int* t = &input->tris[i * 3];
+		float tmin[3], tmax[3];
+		rcVcopy(tmin, &input->verts[t[0] * 3]);
+		rcVcopy(tmax, &input->verts[t[0] * 3]);
+		for (int j = 1; j < 3; j++)
+		{
+			rcVmin(tmin, &input->verts[t[j] * 3]);
+			rcVmax(tmax, &input->verts[t[j] * 3]);
+		}
+		if (tmin[0] > cfg.bmax[0] || tmax[0] < cfg.bmin[0] || tmin[2] > cfg.bmax[2] || tmax[2] < cfg.bmin[2])
+			continue;
+		tris.insert(tris.end(), t, t + 3);
+		sourceTris.push_back(i);
+	}
+	const int ntiletris = (int)tris.size() / 3;
+
+	std::vector<unsigned char> areas(ntiletris, RC_NULL_AREA);
+	rcMarkWalkableTriangles(ctx, cfg.walkableSlopeAngle, input->verts, nverts, tris.data(), ntiletris, areas.data());
+	// The slope decides what is walkable, the input areas what kind of walkable
+	if (input->tri_areas)
+	{
+		for (int i = 0; i < ntiletris; i++)
+		{
+			const uint8_t area = input->tri_areas[sourceTris[i]];
+			if (area == RECASTC_SPAN_NOT_WALKABLE)
+				areas[i] = RC_NULL_AREA;
+			else if (areas[i] == RC_WALKABLE_AREA && area != 0)
+				areas[i] = area;
+		}
+	}
+	if (!rcRasterizeTriangles(ctx, input->verts, nverts, tris.data(), areas.data(), ntiletris, *solid.hf, cfg.walkableClimb))
+		RETURN_ERROR("Could not rasterize triangles");
+
+	rcCompactHeightfield* chf = compact_heightfield(ctx, *solid.hf, input, config, cfg, error);
+	if (chf && solidOut)
+	{
+		*solidOut = solid.hf;
+		solid.hf = nullptr;
+	}
+	return chf;
+}
+
+/// Add the spans of the input columns to an empty heightfield instead of rasterizing triangles
+static rcCompactHeightfield* build_compact_heightfield_from_spans(rcContext* ctx, const recastc_HeightfieldInput* heightfield, const recastc_BuildInput* input,
+	const recastc_BuildConfig* config, const rcConfig& cfg, recastc_Error* error)
+{
+	struct SolidHolder
+	{
+		rcHeightfield* hf = rcAllocHeightfield();
+		~SolidHolder() { rcFreeHeightField(hf); }
+	} solid;
+	if (!solid.hf)
+		RETURN_ERROR("Out of memory 'solid'");
+	if (!rcCreateHeightfield(ctx, *solid.hf, cfg.width, cfg.height, cfg.bmin, cfg.bmax, cfg.cs, cfg.ch))
+		RETURN_ERROR("Could not create solid heightfield");
+
+	for (int z = 0; z < cfg.height; z++)
+	{
+		for (int x = 0; x < cfg.width; x++)
+		{
+			const int col = x + z * cfg.width;
+			for (uint32_t i = heightfield->columns[col]; i < heightfield->columns[col + 1]; i++)
+			{
+				const recastc_Span& span = heightfield->spans[i];
+				// Area 0 is the default walkable area, it becomes RC_WALKABLE_AREA until the poly mesh is built
+				unsigned char area = span.area;
+				if (area == RECASTC_SPAN_NOT_WALKABLE)
+					area = RC_NULL_AREA;
+				else if (area == 0)
+					area = RC_WALKABLE_AREA;
+
+				if (!rcAddSpan(ctx, *solid.hf, x, z, span.smin, span.smax, area, cfg.walkableClimb))
+					RETURN_ERROR("Could not add span");
+			}
+		}
+	}
+
+	return compact_heightfield(ctx, *solid.hf, input, config, cfg, error);
+}
+
+/// Build the poly and detail meshes of `state.chf`
+static bool build_poly_mesh(rcContext* ctx, const recastc_BuildConfig* config, const rcConfig& cfg, BuildState& state, recastc_Error* error)
+{
+	switch (config->partition_type)
+	{
+	case RECASTC_PARTITION_WATERSHED:
+		if (!rcBuildDistanceField(ctx, *state.chf))
+			RETURN_ERROR("Could not build distance field");
+		if (!rcBuildRegions(ctx, *state.chf, cfg.borderSize, cfg.minRegionArea, cfg.mergeRegionArea))
+			RETURN_ERROR("Could not build watershed regions");
+		break;
+	case RECASTC_PARTITION_MONOTONE:
+		if (!rcBuildRegionsMonotone(ctx, *state.chf, cfg.borderSize, cfg.minRegionArea, cfg.mergeRegionArea))
+			RETURN_ERROR("Could not build monotone regions");
+		break;
+	case RECASTC_PARTITION_LAYERS:
+		if (!rcBuildLayerRegions(ctx, *state.chf, cfg.borderSize, cfg.minRegionArea))
+			RETURN_ERROR("Could not build layer regions");
+		break;
+	default:
+		RETURN_ERROR("Unknown partition type");
+	}
+
+	state.cset = rcAllocContourSet();
+	if (!state.cset)
+		RETURN_ERROR("Out of memory 'cset'");
+	if (!rcBuildContours(ctx, *state.chf, cfg.maxSimplificationError, cfg.maxEdgeLen, *state.cset))
+		RETURN_ERROR("Could not create contours");
+
+	state.pmesh = rcAllocPolyMesh();
+	if (!state.pmesh)
+		RETURN_ERROR("Out of memory 'pmesh'");
+	if (!rcBuildPolyMesh(ctx, *state.cset, cfg.maxVertsPerPoly, *state.pmesh))
+		RETURN_ERROR("Could not triangulate contours");
+
+	if (state.pmesh->nverts >= 0xffff)
+		RETURN_ERROR("Too many vertices in the poly mesh");
+
+	// The detail mesh lets height queries and path points follow the actual surface
+	state.dmesh = rcAllocPolyMeshDetail();
+	if (!state.dmesh)
+		RETURN_ERROR("Out of memory 'dmesh'");
+	if (!rcBuildPolyMeshDetail(ctx, *state.pmesh, *state.chf, cfg.detailSampleDist, cfg.detailSampleMaxError, *state.dmesh))
+		RETURN_ERROR("Could not build detail mesh");
+
+	// Recast marks walkable polys with RC_WALKABLE_AREA,
+	// we use area 0 for the default walkable area like the solo mesh created by `recastc_create_query`
+	for (int i = 0; i < state.pmesh->npolys; i++)
+	{
+		if (state.pmesh->areas[i] == RC_WALKABLE_AREA)
+			state.pmesh->areas[i] = 0;
+		state.pmesh->flags[i] = config->area_flags[state.pmesh->areas[i]];
+	}
+
+	return true;
+}
+
+static bool validate_config(const recastc_BuildConfig* config, recastc_Error* error)
+{
+	if (config->max_verts_per_poly < 3 || config->max_verts_per_poly > DT_VERTS_PER_POLYGON)
+		RETURN_ERROR("max_verts_per_poly must be in 3..=6");
+
+	return true;
+}
+
+bool validate_input(const recastc_BuildInput* input, const recastc_BuildConfig* config, recastc_Error* error)
+{
+	if (input->vert_count == 0 || input->tri_count == 0)
+		RETURN_ERROR("Input geometry is empty");
+
+	return validate_config(config, error);
+}
+
+/// Fill the Detour tile data from the poly mesh of a finished build
+static bool create_nav_data(const recastc_BuildInput* input, const recastc_BuildConfig* config, const rcConfig& cfg, const BuildState& state,
+	int tx, int ty, unsigned char** navData, int* navDataSize, recastc_Error* error)
+{
+	const rcPolyMesh* pmesh = state.pmesh;
+	const rcPolyMeshDetail* dmesh = state.dmesh;
+
+	dtNavMeshCreateParams params;
+	memset(&params, 0, sizeof(params));
+	params.verts = pmesh->verts;
+	params.vertCount = pmesh->nverts;
+	params.polys = pmesh->polys;
+	params.polyAreas = pmesh->areas;
+	params.polyFlags = pmesh->flags;
+	params.polyCount = pmesh->npolys;
+	params.nvp = pmesh->nvp;
+	params.detailMeshes = dmesh->meshes;
+	params.detailVerts = dmesh->verts;
+	params.detailVertsCount = dmesh->nverts;
+	params.detailTris = dmesh->tris;
+	params.detailTriCount = dmesh->ntris;
+	params.walkableHeight = config->walkable_height;
+	params.walkableRadius = config->walkable_radius;
+	// Detour connects the border edges of neighbour tiles and the off-mesh connection ends within this height
+	params.walkableClimb = border_stitch_tolerance(config);
+	rcVcopy(params.bmin, pmesh->bmin);
+	rcVcopy(params.bmax, pmesh->bmax);
+	params.cs = cfg.cs;
+	params.ch = cfg.ch;
+	params.buildBvTree = true;
+	params.tileX = tx;
+	params.tileY = ty;
+
+	OffMeshParams offMesh;
+	offMesh.fill(input->off_mesh_connections, input->off_mesh_connection_count, params);
+
+	if (!dtCreateNavMeshData(&params, navData, navDataSize))
+		RETURN_ERROR("dtCreateNavMeshdata failed!");
+
+	return true;
+}
+
+/// Area ids of the views, the default walkable area is 0 like in the poly mesh
+static uint8_t view_area(unsigned char area)
+{
+	if (area == RC_NULL_AREA)
+		return RECASTC_SPAN_NOT_WALKABLE;
+	if (area == RC_WALKABLE_AREA)
+		return 0;
+	return area;
+}
+
+/// A finished build and the copies behind its views
+struct recastc_Build
+{
+	BuildState state;
+	rcHeightfield* solid = nullptr;
+	std::vector<uint32_t> solidColumns;
+	std::vector<recastc_Span> solidSpans;
+	std::vector<uint32_t> compactColumns;
+	std::vector<recastc_CompactSpan> compactSpans;
+	std::vector<recastc_Contour> contours;
+
+	// What the conversion of the poly mesh to tile data needs from the build
+	recastc_BuildConfig config;
+	rcConfig cfg;
+	bool tiled = false;
+	int tx = 0;
+	int ty = 0;
+	std::vector<recastc_OffMeshConnection> offMesh;
+
+	~recastc_Build()
+	{
+		rcFreeHeightField(solid);
+	}
+
+	void fill(recastc_BuildArtifacts* artifacts)
+	{
+		memset(artifacts, 0, sizeof(*artifacts));
+		artifacts->cell_size = solid->cs;
+		artifacts->cell_height = solid->ch;
+
+		const int ncols = solid->width * solid->height;
+		for (int i = 0; i < ncols; i++)
+		{
+			solidColumns.push_back((uint32_t)solidSpans.size());
+			for (const rcSpan* s = solid->spans[i]; s; s = s->next)
+			{
+				recastc_Span span;
+				span.smin = (uint16_t)s->smin;
+				span.smax = (uint16_t)s->smax;
+				span.area = view_area((unsigned char)s->area);
+				solidSpans.push_back(span);
+			}
+		}
+		solidColumns.push_back((uint32_t)solidSpans.size());
+
+		recastc_HeightfieldInput& hf = artifacts->heightfield;
+		rcVcopy(hf.bmin, solid->bmin);
+		rcVcopy(hf.bmax, solid->bmax);
+		hf.width = solid->width;
+		hf.height = solid->height;
+		hf.columns = solidColumns.data();
+		hf.spans = solidSpans.data();
+		hf.span_count = (uint32_t)solidSpans.size();
+
+		const rcCompactHeightfield* chf = state.chf;
+		for (int i = 0; i < chf->width * chf->height; i++)
+		{
+			const rcCompactCell& c = chf->cells[i];
+			compactColumns.push_back((uint32_t)c.index);
+			for (int j = (int)c.index, nj = (int)(c.index + c.count); j < nj; j++)
+			{
+				const rcCompactSpan& s = chf->spans[j];
+				recastc_CompactSpan span;
+				span.y = s.y;
+				span.h = (uint16_t)s.h;
+				span.reg = s.reg;
+				span.dist = chf->dist ? chf->dist[j] : 0;
+				span.area = view_area(chf->areas[j]);
+				for (int dir = 0; dir < 4; dir++)
+				{
+					const int con = rcGetCon(s, dir);
+					span.con[dir] = con == RC_NOT_CONNECTED ? RECASTC_NOT_CONNECTED : (uint8_t)con;
+				}
+				compactSpans.push_back(span);
+			}
+		}
+		compactColumns.push_back((uint32_t)chf->spanCount);
+
+		recastc_CompactHeightfieldView& compact = artifacts->compact;
+		rcVcopy(compact.bmin, chf->bmin);
+		rcVcopy(compact.bmax, chf->bmax);
+		compact.width = chf->width;
+		compact.height = chf->height;
+		compact.border_size = chf->borderSize;
+		compact.max_regions = chf->maxRegions;
+		compact.columns = compactColumns.data();
+		compact.spans = compactSpans.data();
+		compact.span_count = (uint32_t)compactSpans.size();
+
+		const rcContourSet* cset = state.cset;
+		for (int i = 0; i < cset->nconts; i++)
+		{
+			const rcContour& c = cset->conts[i];
+			recastc_Contour contour;
+			contour.verts = c.verts;
+			contour.vert_count = c.nverts;
+			contour.raw_verts = c.rverts;
+			contour.raw_vert_count = c.nrverts;
+			contour.reg = c.reg;
+			contour.area = view_area(c.area);
+			contours.push_back(contour);
+		}
+
+		recastc_ContourSetView& cview = artifacts->contours;
+		rcVcopy(cview.bmin, cset->bmin);
+		rcVcopy(cview.bmax, cset->bmax);
+		cview.border_size = cset->borderSize;
+		cview.contours = contours.data();
+		cview.contour_count = (int32_t)contours.size();
+
+		const rcPolyMesh* pmesh = state.pmesh;
+		recastc_PolyMeshView& mesh = artifacts->poly_mesh;
+		rcVcopy(mesh.bmin, pmesh->bmin);
+		rcVcopy(mesh.bmax, pmesh->bmax);
+		mesh.verts = pmesh->verts;
+		mesh.vert_count = pmesh->nverts;
+		mesh.polys = pmesh->polys;
+		mesh.regs = pmesh->regs;
+		mesh.flags = pmesh->flags;
+		mesh.areas = pmesh->areas;
+		mesh.poly_count = pmesh->npolys;
+		mesh.nvp = pmesh->nvp;
+	}
+};
+
+extern "C"
+{
+	struct recastc_Query *recastc_build_query(const recastc_BuildInput* input, 
+        const recastc_BuildConfig* config, 
+        recastc_Error* error)
+	{
+		assert(input);
+		assert(config);
+
+		if (!validate_input(input, config, error)) {
+			return 0;
+		}
+
+		rcContext ctx(false);
+		rcConfig cfg;
+		init_config(config, cfg);
+
+		rcCalcBounds(input->verts, (int)input->vert_count, cfg.bmin, cfg.bmax);
+		rcCalcGridSize(cfg.bmin, cfg.bmax, cfg.cs, &cfg.width, &cfg.height);
+
+		BuildState state;
+		state.chf = build_compact_heightfield(&ctx, input, config, cfg, error);
+		if (!state.chf || !build_poly_mesh(&ctx, config, cfg, state, error)) {
+			return 0;
+		}
+
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+		if (!create_nav_data(input, config, cfg, state, 0, 0, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		return recastc_create_query_from_data(navData, navDataSize, error);
+	}
+
+	struct recastc_Query *recastc_build_query_from_heightfield(const recastc_HeightfieldInput* heightfield,
+		const recastc_BuildInput* input,
+		const recastc_BuildConfig* config,
+		recastc_Error* error)
+	{
+		assert(heightfield);
+		assert(input);
+		assert(config);
+
+		if (!validate_config(config, error)) {
+			return 0;
+		}
+		if (heightfield->width <= 0 || heightfield->height <= 0) {
+			RETURN_ERROR("Heightfield is empty");
+		}
+
+		const int ncols = heightfield->width * heightfield->height;
+		for (int i = 0; i < ncols; i++) {
+			if (heightfield->columns[i] > heightfield->columns[i + 1]) {
+				RETURN_ERROR("Heightfield column offsets must be increasing");
+			}
+		}
+		if (heightfield->columns[ncols] > heightfield->span_count) {
+			RETURN_ERROR("Heightfield column offsets out of the spans");
+		}
+		for (uint32_t i = 0; i < heightfield->span_count; i++) {
+			const recastc_Span& span = heightfield->spans[i];
+			if (span.smin > span.smax || span.smax > RC_SPAN_MAX_HEIGHT) {
+				RETURN_ERROR("Span heights out of range");
+			}
+		}
+
+		rcContext ctx(false);
+		rcConfig cfg;
+		init_config(config, cfg);
+
+		cfg.width = heightfield->width;
+		cfg.height = heightfield->height;
+		rcVcopy(cfg.bmin, heightfield->bmin);
+		rcVcopy(cfg.bmax, heightfield->bmax);
+		cfg.bmax[0] = cfg.bmin[0] + cfg.width * cfg.cs;
+		cfg.bmax[2] = cfg.bmin[2] + cfg.height * cfg.cs;
+
+		BuildState state;
+		state.chf = build_compact_heightfield_from_spans(&ctx, heightfield, input, config, cfg, error);
+		if (!state.chf || !build_poly_mesh(&ctx, config, cfg, state, error)) {
+			return 0;
+		}
+
+		if (state.pmesh->npolys == 0) {
+			RETURN_ERROR("Heightfield has no walkable surface");
+		}
+
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+		if (!create_nav_data(input, config, cfg, state, 0, 0, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		return recastc_create_query_from_data(navData, navDataSize, error);
+	}
+
+	/// Reference: Sample_TileMesh.cpp buildTileMesh
+	int32_t recastc_build_tile(const recastc_BuildInput* input,
+		const recastc_BuildConfig* config,
+		const recastc_TileInput* tile,
+		uint8_t** data,
+		int32_t* data_size,
+		recastc_Error* error)
+	{
+		assert(input);
+		assert(config);
+		assert(tile);
+		assert(data);
+		assert(data_size);
+
+		*data = 0;
+		*data_size = 0;
+
+		if (!validate_input(input, config, error)) {
+			return 0;
+		}
+		if (tile->tile_size <= 0) {
+			RETURN_ERROR("tile_size must be positive");
+		}
+
+		rcContext ctx(false);
+		rcConfig cfg;
+		init_config(config, cfg);
+
+		init_tile_config(tile, config, cfg);
+
+		BuildState state;
+		state.chf = build_compact_heightfield(&ctx, input, config, cfg, error);
+		if (!state.chf || !build_poly_mesh(&ctx, config, cfg, state, error)) {
+			return 0;
+		}
+
+		if (state.pmesh->npolys == 0) {
+			return 1;
+		}
+
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+		if (!create_nav_data(input, config, cfg, state, tile->tx, tile->ty, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		*data = navData;
+		*data_size = navDataSize;
+		return 1;
+	}
+
+	struct recastc_Build* recastc_build_artifacts(const recastc_BuildInput* input,
+		const recastc_BuildConfig* config,
+		const recastc_TileInput* tile,
+		recastc_BuildArtifacts* artifacts,
+		recastc_Error* error)
+	{
+		assert(input);
+		assert(config);
+		assert(artifacts);
+
+		if (!validate_input(input, config, error)) {
+			return 0;
+		}
+		if (tile && tile->tile_size <= 0) {
+			RETURN_ERROR("tile_size must be positive");
+		}
+
+		rcContext ctx(false);
+		rcConfig cfg;
+		init_config(config, cfg);
+
+		if (tile) {
+			init_tile_config(tile, config, cfg);
+		} else {
+			rcCalcBounds(input->verts, (int)input->vert_count, cfg.bmin, cfg.bmax);
+			rcCalcGridSize(cfg.bmin, cfg.bmax, cfg.cs, &cfg.width, &cfg.height);
+		}
+
+		recastc_Build* build = new recastc_Build;
+		build->state.chf = build_compact_heightfield(&ctx, input, config, cfg, error, &build->solid);
+		if (!build->state.chf || !build_poly_mesh(&ctx, config, cfg, build->state, error)) {
+			delete build;
+			return 0;
+		}
+
+		build->config = *config;
+		build->cfg = cfg;
+		if (tile) {
+			build->tiled = true;
+			build->tx = tile->tx;
+			build->ty = tile->ty;
+		}
+		build->offMesh.assign(input->off_mesh_connections, input->off_mesh_connections + input->off_mesh_connection_count);
+
+		build->fill(artifacts);
+		return build;
+	}
+
+	int32_t recastc_build_tile_data(const struct recastc_Build* build, uint8_t** data, int32_t* data_size, recastc_Error* error)
+	{
+		assert(build);
+		assert(data);
+		assert(data_size);
+
+		*data = 0;
+		*data_size = 0;
+
+		if (build->state.pmesh->npolys == 0) {
+			return 1;
+		}
+
+		// The off-mesh connections are all the conversion reads of the input
+		recastc_BuildInput input;
+		memset(&input, 0, sizeof(input));
+		input.off_mesh_connections = build->offMesh.data();
+		input.off_mesh_connection_count = (uint32_t)build->offMesh.size();
+
+		unsigned char* navData = 0;
+		int navDataSize = 0;
+		if (!create_nav_data(&input, &build->config, build->cfg, build->state, build->tx, build->ty, &navData, &navDataSize, error)) {
+			return 0;
+		}
+
+		*data = navData;
+		*data_size = navDataSize;
+		return 1;
+	}
+
+	struct recastc_Query* recastc_create_query_from_build(const struct recastc_Build* build, recastc_Error* error)
+	{
+		assert(build);
+
+		if (build->tiled) {
+			RETURN_ERROR("Build of a tile, add its tile data to a tiled navmesh");
+		}
+
+		uint8_t* navData = 0;
+		int32_t navDataSize = 0;
+		if (!recastc_build_tile_data(build, &navData, &navDataSize, error)) {
+			return 0;
+		}
+		if (!navData) {
+			RETURN_ERROR("Build has no walkable surface");
+		}
+
+		return recastc_create_query_from_data(navData, navDataSize, error);
+	}
+
+	void recastc_free_build(struct recastc_Build* build)
+	{
+		delete build;
+	}
+}
@@ -0,0 +1,237 @@
+#include "recastc_internal.h"
+#include <DetourPathCorridor.h>
+
+/// Reference: dtCrowd::update, which moves the corridors of its agents the same way
+
+struct recastc_Corridor
+{
+	dtPathCorridor corridor;
+	int32_t max_path = 0;
+	/// A corridor without poly, set by reset or set_path
+	bool empty = true;
+};
+
+extern "C"
+{
+	struct recastc_Corridor* recastc_create_corridor(int32_t max_path, recastc_Error* error)
+	{
+		if (max_path < 2)
+		{
+			RETURN_ERROR("A corridor needs room for at least 2 polys");
+		}
+
+		recastc_Corridor* corridor = new recastc_Corridor();
+		if (!corridor->corridor.init(max_path))
+		{
+			delete corridor;
+			RETURN_ERROR("Could not init corridor");
+		}
+		corridor->max_path = max_path;
+		return corridor;
+	}
+
+	int32_t recastc_corridor_reset(struct recastc_Corridor* corridor, uint32_t poly, const float* pos, recastc_Error* error)
+	{
+		assert(corridor);
+
+		if (!poly)
+		{
+			RETURN_ERROR("Invalid poly");
+		}
+
+		corridor->corridor.reset(poly, pos);
+		corridor->empty = false;
+		return 1;
+	}
+
+	int32_t recastc_corridor_set_path(struct recastc_Corridor* corridor, const float* target, const uint32_t* polys, int32_t count, recastc_Error* error)
+	{
+		assert(corridor);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+		// dtPathCorridor::setCorridor asserts npath < maxPath
+		if (count <= 0 || count >= corridor->max_path)
+		{
+			RETURN_ERROR("Path does not fit the corridor");
+		}
+		if (polys[0] != corridor->corridor.getFirstPoly())
+		{
+			RETURN_ERROR("Path does not start at the poly of the position");
+		}
+
+		corridor->corridor.setCorridor(target, polys, count);
+		return 1;
+	}
+
+	int32_t recastc_corridor_move_position(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* pos, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+		if (!corridor->corridor.movePosition(pos, query->q, &query->filter))
+		{
+			RETURN_ERROR("Could not move along the corridor");
+		}
+		return 1;
+	}
+
+	int32_t recastc_corridor_move_target_position(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* pos, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+		if (!corridor->corridor.moveTargetPosition(pos, query->q, &query->filter))
+		{
+			RETURN_ERROR("Could not move the target");
+		}
+		return 1;
+	}
+
+	int32_t recastc_corridor_optimize_visibility(struct recastc_Corridor* corridor, struct recastc_Query* query, const float* next, float range, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+
+		if (corridor->empty || !(range > 0.0f))
+		{
+			RETURN_ERROR("Invalid corridor or range");
+		}
+
+		corridor->corridor.optimizePathVisibility(next, range, query->q, &query->filter);
+		return 1;
+	}
+
+	int32_t recastc_corridor_optimize_topology(struct recastc_Corridor* corridor, struct recastc_Query* query, uint8_t* optimized, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+		assert(optimized);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+
+		*optimized = corridor->corridor.optimizePathTopology(query->q, &query->filter) ? 1 : 0;
+		return 1;
+	}
+
+	int32_t recastc_corridor_find_corners(struct recastc_Corridor* corridor, struct recastc_Query* query,
+		float* corners, uint8_t* flags, uint32_t* polys, int32_t max_corners, int32_t* count, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+		assert(count);
+
+		*count = 0;
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+		if (max_corners <= 0)
+		{
+			RETURN_ERROR("Invalid max corners");
+		}
+
+		*count = corridor->corridor.findCorners(corners, flags, polys, max_corners, query->q, &query->filter);
+		return 1;
+	}
+
+	int32_t recastc_corridor_move_over_off_mesh_connection(struct recastc_Corridor* corridor, struct recastc_Query* query,
+		uint32_t link, float* start, float* end, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+
+		dtPolyRef refs[2];
+		if (!corridor->corridor.moveOverOffmeshConnection(link, refs, start, end, query->q))
+		{
+			RETURN_ERROR("Off-mesh connection is not next in the corridor");
+		}
+		return 1;
+	}
+
+	int32_t recastc_corridor_is_valid(struct recastc_Corridor* corridor, struct recastc_Query* query, int32_t max_look_ahead, uint8_t* valid, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+		assert(valid);
+
+		if (corridor->empty)
+		{
+			RETURN_ERROR("Corridor is not reset");
+		}
+
+		*valid = corridor->corridor.isValid(max_look_ahead, query->q, &query->filter) ? 1 : 0;
+		return 1;
+	}
+
+	int32_t recastc_corridor_trim_invalid_path(struct recastc_Corridor* corridor, struct recastc_Query* query, uint32_t safe_poly, const float* safe_pos, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(query);
+
+		if (corridor->empty || !safe_poly)
+		{
+			RETURN_ERROR("Invalid corridor or poly");
+		}
+		if (!corridor->corridor.trimInvalidPath(safe_poly, safe_pos, query->q, &query->filter))
+		{
+			RETURN_ERROR("Could not trim the corridor");
+		}
+		return 1;
+	}
+
+	int32_t recastc_corridor_get_state(struct recastc_Corridor* corridor, recastc_CorridorState* state, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(state);
+
+		memset(state, 0, sizeof(*state));
+		if (corridor->empty)
+			return 1;
+
+		memcpy(state->pos, corridor->corridor.getPos(), sizeof(state->pos));
+		memcpy(state->target, corridor->corridor.getTarget(), sizeof(state->target));
+		state->first_poly = corridor->corridor.getFirstPoly();
+		state->last_poly = corridor->corridor.getLastPoly();
+		state->path_count = corridor->corridor.getPathCount();
+		return 1;
+	}
+
+	int32_t recastc_corridor_get_path(struct recastc_Corridor* corridor, uint32_t* polys, int32_t max_polys, int32_t* count, recastc_Error* error)
+	{
+		assert(corridor);
+		assert(count);
+
+		*count = corridor->empty ? 0 : corridor->corridor.getPathCount();
+		if (*count > max_polys)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		memcpy(polys, corridor->corridor.getPath(), sizeof(uint32_t) * *count);
+		return 1;
+	}
+
+	void recastc_free_corridor(struct recastc_Corridor* corridor)
+	{
+		delete corridor;
+	}
+}
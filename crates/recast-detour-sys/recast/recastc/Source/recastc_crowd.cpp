@@ -0,0 +1,1045 @@
+#include "recastc_internal.h"
+#include <DetourCrowd.h>
+#include <DetourCommon.h>
+
+#include <algorithm>
+#include <float.h>
+#include <math.h>
+
+/// Reference: CrowdTool.cpp
+
+struct recastc_Crowd
+{
+	dtCrowd* crowd = nullptr;
+	/// The navmesh of the query, to recreate the crowd
+	dtNavMesh* mesh = nullptr;
+	float max_agent_radius = 0.0f;
+	/// Agents out of crowd control per slot, their position is owned by the caller
+	std::vector<uint8_t> detached;
+	std::vector<float> detached_pos;
+	/// The off-mesh connection of each agent, dtCrowd keeps its own animation state private
+	std::vector<recastc_OffMeshTraversal> traversals;
+	/// The user ids of the closed off-mesh connections, the crowd holds the agents about to take them
+	std::vector<uint32_t> closed_gates;
+	/// The closed gate each agent waits at per slot, 0 for none
+	std::vector<uint32_t> waiting;
+	/// The slot of the agent whose avoidance samples and visibility optimization the updates record, -1 for none
+	int32_t debug_agent = -1;
+	/// `idx` is the index of the debug agent in the active list during an update
+	dtCrowdAgentDebugInfo debug;
+	/// The slot of each agent in the active list of the last update, -1 once removed.
+	/// The neighbours and the proximity grid of the crowd hold indices into that list
+	std::vector<int32_t> active;
+
+	~recastc_Crowd()
+	{
+		dtFreeObstacleAvoidanceDebugData(debug.vod);
+		dtFreeCrowd(crowd);
+	}
+};
+
+static void init_avoidance(dtCrowd* crowd)
+{
+	// The low to high quality settings of the crowd sample
+	dtObstacleAvoidanceParams params;
+	memcpy(&params, crowd->getObstacleAvoidanceParams(0), sizeof(dtObstacleAvoidanceParams));
+
+	params.velBias = 0.5f;
+	params.adaptiveDivs = 5;
+	params.adaptiveRings = 2;
+	params.adaptiveDepth = 1;
+	crowd->setObstacleAvoidanceParams(0, &params);
+
+	params.adaptiveDivs = 5;
+	params.adaptiveRings = 2;
+	params.adaptiveDepth = 2;
+	crowd->setObstacleAvoidanceParams(1, &params);
+
+	params.adaptiveDivs = 7;
+	params.adaptiveRings = 2;
+	params.adaptiveDepth = 3;
+	crowd->setObstacleAvoidanceParams(2, &params);
+
+	params.adaptiveDivs = 7;
+	params.adaptiveRings = 3;
+	params.adaptiveDepth = 3;
+	crowd->setObstacleAvoidanceParams(3, &params);
+}
+
+static void to_dt_params(const recastc_CrowdAgentParams* params, dtCrowdAgentParams& ap)
+{
+	memset(&ap, 0, sizeof(ap));
+	ap.radius = params->radius;
+	ap.height = params->height;
+	ap.maxAcceleration = params->max_acceleration;
+	ap.maxSpeed = params->max_speed;
+	ap.collisionQueryRange = params->collision_query_range;
+	ap.pathOptimizationRange = params->path_optimization_range;
+	ap.separationWeight = params->separation_weight;
+	ap.updateFlags = params->update_flags;
+	ap.obstacleAvoidanceType = params->obstacle_avoidance_type;
+	ap.queryFilterType = params->query_filter_type;
+}
+
+static bool validate_params(const recastc_Crowd* crowd, const recastc_CrowdAgentParams* params, recastc_Error* error)
+{
+	if (!(params->radius > 0.0f) || params->radius > crowd->max_agent_radius)
+	{
+		RETURN_ERROR("Agent radius must be in (0, max agent radius]");
+	}
+	if (!(params->height > 0.0f) || !(params->max_speed >= 0.0f))
+	{
+		RETURN_ERROR("Invalid agent height or speed");
+	}
+	if (params->obstacle_avoidance_type >= DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS)
+	{
+		RETURN_ERROR("Invalid obstacle avoidance type");
+	}
+	if (params->query_filter_type >= DT_CROWD_MAX_QUERY_FILTER_TYPE)
+	{
+		RETURN_ERROR("Invalid query filter type");
+	}
+	return true;
+}
+
+static bool valid_agent(recastc_Crowd* crowd, int32_t agent)
+{
+	return agent >= 0 && agent < crowd->crowd->getAgentCount() && crowd->crowd->getAgent(agent)->active;
+}
+
+/// The active list of dtCrowd::update, the active agents in slot order as dtCrowd::getActiveAgents lists them
+static void record_active(recastc_Crowd* crowd)
+{
+	crowd->active.clear();
+	for (int i = 0; i < crowd->crowd->getAgentCount(); i++)
+	{
+		if (crowd->crowd->getAgent(i)->active)
+			crowd->active.push_back(i);
+	}
+}
+
+/// The slot of an index into the active list of the last update, -1 if the agent was removed since
+static int32_t active_slot(const recastc_Crowd* crowd, int idx)
+{
+	return idx >= 0 && idx < (int)crowd->active.size() ? crowd->active[idx] : -1;
+}
+
+/// Not moved by the crowd, the others still steer around it
+static void hold_detached(recastc_Crowd* crowd, int32_t agent)
+{
+	dtCrowdAgent* ag = crowd->crowd->getEditableAgent(agent);
+	ag->state = DT_CROWDAGENT_STATE_INVALID;
+	ag->targetState = DT_CROWDAGENT_TARGET_NONE;
+	ag->nneis = 0;
+	ag->desiredSpeed = 0;
+	memcpy(ag->npos, &crowd->detached_pos[agent * 3], sizeof(ag->npos));
+	memset(ag->vel, 0, sizeof(ag->vel));
+	memset(ag->dvel, 0, sizeof(ag->dvel));
+	memset(ag->nvel, 0, sizeof(ag->nvel));
+}
+
+/// Put the agent on the navmesh near `pos` with a new corridor and boundary, standing without target
+static bool place_agent(recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error)
+{
+	dtCrowdAgent* ag = crowd->crowd->getEditableAgent(agent);
+	// The same placement as dtCrowd::addAgent
+	const float r = crowd->max_agent_radius;
+	const float half_extents[3] = { r * 2.0f, r * 1.5f, r * 2.0f };
+	float nearest[3];
+	memcpy(nearest, pos, sizeof(nearest));
+	dtPolyRef ref = 0;
+	const dtQueryFilter* filter = crowd->crowd->getFilter(ag->params.queryFilterType);
+	if (dtStatusFailed(crowd->crowd->getNavMeshQuery()->findNearestPoly(pos, half_extents, filter, &ref, nearest)) || !ref)
+	{
+		RETURN_ERROR("Could not find a poly near the position");
+	}
+
+	ag->corridor.reset(ref, nearest);
+	ag->boundary.reset();
+	ag->partial = false;
+	ag->topologyOptTime = 0;
+	ag->targetReplanTime = 0;
+	ag->nneis = 0;
+	ag->ncorners = 0;
+	ag->desiredSpeed = 0;
+	memcpy(ag->npos, nearest, sizeof(ag->npos));
+	memset(ag->vel, 0, sizeof(ag->vel));
+	memset(ag->dvel, 0, sizeof(ag->dvel));
+	memset(ag->nvel, 0, sizeof(ag->nvel));
+	ag->state = DT_CROWDAGENT_STATE_WALKING;
+	ag->targetState = DT_CROWDAGENT_TARGET_NONE;
+	return true;
+}
+
+/// The off-mesh connection ahead of a walking agent, the crowd steps over it once close enough
+static dtPolyRef next_off_mesh_connection(const dtCrowdAgent* ag)
+{
+	if (ag->state != DT_CROWDAGENT_STATE_WALKING || ag->ncorners == 0)
+		return 0;
+	if (!(ag->cornerFlags[ag->ncorners - 1] & DT_STRAIGHTPATH_OFFMESH_CONNECTION))
+		return 0;
+	return ag->cornerPolys[ag->ncorners - 1];
+}
+
+/// The off-mesh connection dtCrowd::update moved the agent over, 0 if unknown. Its corridor already
+/// starts after the connection, the crowd only resets the count of the corners it stepped over with,
+/// and the connection ends the corners
+static dtPolyRef moved_over_connection(const dtCrowdAgent* ag)
+{
+	for (int i = 0; i < DT_CROWDAGENT_MAX_CORNERS; i++)
+	{
+		if (ag->cornerFlags[i] & DT_STRAIGHTPATH_OFFMESH_CONNECTION)
+			return ag->cornerPolys[i];
+	}
+	return 0;
+}
+
+/// Start the traversal as dtCrowd::update does, entering at the end nearest to where the agent was
+static void start_traversal(recastc_Crowd* crowd, const dtCrowdAgent* ag, dtPolyRef ref, const float* prev_pos, recastc_OffMeshTraversal& tr)
+{
+	memset(&tr, 0, sizeof(tr));
+	tr.phase = RECASTC_OFFMESH_ENTERING;
+	tr.poly = ref;
+
+	const dtOffMeshConnection* con = crowd->crowd->getNavMeshQuery()->getAttachedNavMesh()->getOffMeshConnectionByRef(ref);
+	if (con)
+	{
+		const bool reverse = dtVdistSqr(prev_pos, &con->pos[3]) < dtVdistSqr(prev_pos, &con->pos[0]);
+		dtVcopy(tr.start, &con->pos[reverse ? 3 : 0]);
+		dtVcopy(tr.end, &con->pos[reverse ? 0 : 3]);
+		tr.user_id = con->userId;
+	}
+	// dtCrowd divides by the speed too, an agent without speed is over the connection at once here
+	tr.duration = ag->params.maxSpeed > 0.0f ? (dtVdist2D(tr.start, tr.end) / ag->params.maxSpeed) * 0.5f : 0.0f;
+}
+
+/// Follow the traversals through an update of `dt`, with the timing of dtCrowd::update
+static void update_traversals(recastc_Crowd* crowd, const std::vector<dtPolyRef>& next, const std::vector<float>& prev_pos, float dt)
+{
+	for (int i = 0; i < crowd->crowd->getAgentCount(); i++)
+	{
+		const dtCrowdAgent* ag = crowd->crowd->getAgent(i);
+		recastc_OffMeshTraversal& tr = crowd->traversals[i];
+		const bool was_on = tr.phase == RECASTC_OFFMESH_ENTERING || tr.phase == RECASTC_OFFMESH_TRAVERSING;
+
+		if (!ag->active || ag->state != DT_CROWDAGENT_STATE_OFFMESH)
+		{
+			if (was_on)
+				tr.phase = RECASTC_OFFMESH_EXITING;
+			else
+				memset(&tr, 0, sizeof(tr));
+			continue;
+		}
+
+		// The corners of an agent can lead to the connection in the update that found them,
+		// with no connection ahead before the update
+		if (!was_on)
+		{
+			const dtPolyRef ref = moved_over_connection(ag);
+			start_traversal(crowd, ag, ref ? ref : next[i], &prev_pos[i * 3], tr);
+		}
+		tr.elapsed += dt;
+		// The agent reaches the start of the connection in the first 15% of the time
+		tr.phase = tr.elapsed < tr.duration * 0.15f ? RECASTC_OFFMESH_ENTERING : RECASTC_OFFMESH_TRAVERSING;
+	}
+}
+
+static bool gate_closed(const recastc_Crowd* crowd, uint32_t user_id)
+{
+	return std::find(crowd->closed_gates.begin(), crowd->closed_gates.end(), user_id) != crowd->closed_gates.end();
+}
+
+/// The closed gate a walking agent could reach in an update of `dt`, 0 for none.
+/// dtCrowd::update steps over the connection once within 2.25 radius of its start
+static uint32_t closed_gate_ahead(const recastc_Crowd* crowd, const dtCrowdAgent* ag, float dt)
+{
+	const dtPolyRef ref = next_off_mesh_connection(ag);
+	if (!ref || crowd->closed_gates.empty())
+		return 0;
+	const dtOffMeshConnection* con = crowd->crowd->getNavMeshQuery()->getAttachedNavMesh()->getOffMeshConnectionByRef(ref);
+	if (!con || !gate_closed(crowd, con->userId))
+		return 0;
+
+	const float reach = ag->params.radius * 2.25f + ag->params.maxSpeed * dt;
+	if (dtVdist2DSqr(ag->npos, &ag->cornerVerts[(ag->ncorners - 1) * 3]) >= reach * reach)
+		return 0;
+	return con->userId;
+}
+
+/// Give the agent of a new crowd the state of the agent of the same slot, the new crowd added it at its position
+static void copy_agent(const dtCrowdAgent* from, dtCrowd* crowd, int idx)
+{
+	dtCrowdAgent* to = crowd->getEditableAgent(idx);
+	if (from->corridor.getPathCount() > 0)
+	{
+		to->corridor.reset(from->corridor.getFirstPoly(), from->corridor.getPos());
+		to->corridor.setCorridor(from->corridor.getTarget(), from->corridor.getPath(), from->corridor.getPathCount());
+	}
+
+	to->state = from->state;
+	to->partial = from->partial;
+	to->topologyOptTime = from->topologyOptTime;
+	to->desiredSpeed = from->desiredSpeed;
+	dtVcopy(to->npos, from->npos);
+	dtVcopy(to->disp, from->disp);
+	dtVcopy(to->dvel, from->dvel);
+	dtVcopy(to->nvel, from->nvel);
+	dtVcopy(to->vel, from->vel);
+	memcpy(to->cornerVerts, from->cornerVerts, sizeof(to->cornerVerts));
+	memcpy(to->cornerFlags, from->cornerFlags, sizeof(to->cornerFlags));
+	memcpy(to->cornerPolys, from->cornerPolys, sizeof(to->cornerPolys));
+	to->ncorners = from->ncorners;
+
+	// The animation of the old crowd is private, land the agent at the end of the connection.
+	// Its corridor already starts after it
+	if (from->state == DT_CROWDAGENT_STATE_OFFMESH)
+	{
+		to->state = DT_CROWDAGENT_STATE_WALKING;
+		dtVcopy(to->npos, from->corridor.getPos());
+		to->ncorners = 0;
+	}
+
+	// The path requests in the queue of the old crowd are made again
+	if (from->targetState == DT_CROWDAGENT_TARGET_REQUESTING ||
+		from->targetState == DT_CROWDAGENT_TARGET_WAITING_FOR_QUEUE ||
+		from->targetState == DT_CROWDAGENT_TARGET_WAITING_FOR_PATH)
+	{
+		crowd->requestMoveTarget(idx, from->targetRef, from->targetPos);
+	}
+	else
+	{
+		to->targetState = from->targetState;
+		to->targetRef = from->targetRef;
+		dtVcopy(to->targetPos, from->targetPos);
+		to->targetReplan = from->targetReplan;
+		to->targetReplanTime = from->targetReplanTime;
+	}
+}
+
+extern "C"
+{
+	struct recastc_Crowd* recastc_create_crowd(struct recastc_Query* query, int32_t max_agents, float max_agent_radius, recastc_Error* error)
+	{
+		assert(query);
+
+		if (max_agents <= 0 || !(max_agent_radius > 0.0f))
+		{
+			RETURN_ERROR("Invalid crowd size");
+		}
+
+		recastc_Crowd* crowd = new recastc_Crowd();
+		crowd->crowd = dtAllocCrowd();
+		crowd->mesh = query->mesh;
+		crowd->max_agent_radius = max_agent_radius;
+		memset(&crowd->debug, 0, sizeof(crowd->debug));
+		crowd->debug.idx = -1;
+		crowd->debug.optStart[0] = FLT_MAX;
+		// The samples of the debug agent, as many as the crowd tool records
+		crowd->debug.vod = dtAllocObstacleAvoidanceDebugData();
+
+		if (!crowd->crowd || !crowd->crowd->init(max_agents, max_agent_radius, query->mesh) ||
+			!crowd->debug.vod || !crowd->debug.vod->init(2048))
+		{
+			delete crowd;
+			RETURN_ERROR("Could not init crowd");
+		}
+
+		init_avoidance(crowd->crowd);
+		crowd->detached.assign(max_agents, 0);
+		crowd->detached_pos.assign(max_agents * 3, 0.0f);
+		crowd->traversals.assign(max_agents, recastc_OffMeshTraversal());
+		crowd->waiting.assign(max_agents, 0);
+		// Only the dtQueryFilter part, the crowd filters have no height costs
+		*crowd->crowd->getEditableFilter(0) = query->filter;
+
+		return crowd;
+	}
+
+	int32_t recastc_crowd_set_capacity(struct recastc_Crowd* crowd, int32_t max_agents, float max_agent_radius, recastc_Error* error)
+	{
+		assert(crowd);
+
+		dtCrowd* old = crowd->crowd;
+		const int n = old->getAgentCount();
+		if (max_agents < n || !(max_agent_radius >= crowd->max_agent_radius))
+		{
+			RETURN_ERROR("The crowd can only grow");
+		}
+
+		dtCrowd* grown = dtAllocCrowd();
+		if (!grown || !grown->init(max_agents, max_agent_radius, crowd->mesh))
+		{
+			dtFreeCrowd(grown);
+			RETURN_ERROR("Could not init crowd");
+		}
+		for (int i = 0; i < DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS; i++)
+			grown->setObstacleAvoidanceParams(i, old->getObstacleAvoidanceParams(i));
+		for (int i = 0; i < DT_CROWD_MAX_QUERY_FILTER_TYPE; i++)
+			*grown->getEditableFilter(i) = *old->getFilter(i);
+
+		// The crowd adds an agent to the first free slot, the free slots get a placeholder
+		// until every agent is in its slot
+		dtCrowdAgentParams placeholder;
+		memset(&placeholder, 0, sizeof(placeholder));
+		placeholder.radius = max_agent_radius;
+		for (int i = 0; i < n; i++)
+		{
+			const dtCrowdAgent* ag = old->getAgent(i);
+			const int idx = grown->addAgent(ag->npos, ag->active ? &ag->params : &placeholder);
+			assert(idx == i);
+			if (ag->active)
+				copy_agent(ag, grown, idx);
+		}
+		for (int i = 0; i < n; i++)
+		{
+			if (!old->getAgent(i)->active)
+				grown->removeAgent(i);
+		}
+
+		dtFreeCrowd(old);
+		crowd->crowd = grown;
+		crowd->max_agent_radius = max_agent_radius;
+		crowd->detached.resize(max_agents, 0);
+		crowd->detached_pos.resize(max_agents * 3, 0.0f);
+		crowd->traversals.resize(max_agents, recastc_OffMeshTraversal());
+		crowd->waiting.resize(max_agents, 0);
+		return 1;
+	}
+
+	int32_t recastc_crowd_set_navmesh(struct recastc_Crowd* crowd, struct recastc_Query* query, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(query);
+
+		dtCrowd* old = crowd->crowd;
+		const int n = old->getAgentCount();
+		dtCrowd* moved = dtAllocCrowd();
+		if (!moved || !moved->init(n, crowd->max_agent_radius, query->mesh))
+		{
+			dtFreeCrowd(moved);
+			RETURN_ERROR("Could not init crowd");
+		}
+		for (int i = 0; i < DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS; i++)
+			moved->setObstacleAvoidanceParams(i, old->getObstacleAvoidanceParams(i));
+		for (int i = 0; i < DT_CROWD_MAX_QUERY_FILTER_TYPE; i++)
+			*moved->getEditableFilter(i) = *old->getFilter(i);
+		*moved->getEditableFilter(0) = query->filter;
+
+		// Added again in the same slots as in `recastc_crowd_set_capacity`. The corridors are
+		// polys of the old navmesh, the new crowd places the agents and they start without one
+		dtCrowdAgentParams placeholder;
+		memset(&placeholder, 0, sizeof(placeholder));
+		placeholder.radius = crowd->max_agent_radius;
+		for (int i = 0; i < n; i++)
+		{
+			const dtCrowdAgent* ag = old->getAgent(i);
+			// Agents crossing an off-mesh connection land at its end
+			const float* pos = ag->state == DT_CROWDAGENT_STATE_OFFMESH ? ag->corridor.getPos() : ag->npos;
+			const int idx = moved->addAgent(pos, ag->active ? &ag->params : &placeholder);
+			assert(idx == i);
+			if (ag->active && ag->targetState == DT_CROWDAGENT_TARGET_VELOCITY)
+				moved->requestMoveVelocity(idx, ag->targetPos);
+		}
+		for (int i = 0; i < n; i++)
+		{
+			if (!old->getAgent(i)->active)
+				moved->removeAgent(i);
+		}
+
+		dtFreeCrowd(old);
+		crowd->crowd = moved;
+		crowd->mesh = query->mesh;
+		crowd->traversals.assign(n, recastc_OffMeshTraversal());
+		crowd->waiting.assign(n, 0);
+		return 1;
+	}
+
+	int32_t recastc_crowd_set_filter(struct recastc_Crowd* crowd, const struct recastc_Query* query, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(query);
+
+		if (query->mesh != crowd->mesh)
+		{
+			RETURN_ERROR("The query has another navmesh than the crowd");
+		}
+		*crowd->crowd->getEditableFilter(0) = query->filter;
+		return 1;
+	}
+
+	int32_t recastc_crowd_add_agent(struct recastc_Crowd* crowd, const float* pos, const recastc_CrowdAgentParams* params, int32_t* agent, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(agent);
+
+		if (!validate_params(crowd, params, error))
+			return 0;
+
+		dtCrowdAgentParams ap;
+		to_dt_params(params, ap);
+
+		int idx = crowd->crowd->addAgent(pos, &ap);
+		if (idx < 0)
+		{
+			RETURN_ERROR("Crowd is full");
+		}
+
+		crowd->detached[idx] = 0;
+		crowd->waiting[idx] = 0;
+		memset(&crowd->traversals[idx], 0, sizeof(recastc_OffMeshTraversal));
+		*agent = idx;
+		return 1;
+	}
+
+	int32_t recastc_crowd_update_agent(struct recastc_Crowd* crowd, int32_t agent, const recastc_CrowdAgentParams* params, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		if (!validate_params(crowd, params, error))
+			return 0;
+
+		dtCrowdAgentParams ap;
+		to_dt_params(params, ap);
+		crowd->crowd->updateAgentParameters(agent, &ap);
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_agent_params(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdAgentParams* params, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(params);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		const dtCrowdAgentParams& ap = crowd->crowd->getAgent(agent)->params;
+		params->radius = ap.radius;
+		params->height = ap.height;
+		params->max_acceleration = ap.maxAcceleration;
+		params->max_speed = ap.maxSpeed;
+		params->collision_query_range = ap.collisionQueryRange;
+		params->path_optimization_range = ap.pathOptimizationRange;
+		params->separation_weight = ap.separationWeight;
+		params->update_flags = ap.updateFlags;
+		params->obstacle_avoidance_type = ap.obstacleAvoidanceType;
+		params->query_filter_type = ap.queryFilterType;
+		return 1;
+	}
+
+	int32_t recastc_crowd_remove_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		crowd->crowd->removeAgent(agent);
+		// A new agent in the slot is not the neighbour of anyone until the next update
+		std::replace(crowd->active.begin(), crowd->active.end(), agent, -1);
+		crowd->detached[agent] = 0;
+		crowd->waiting[agent] = 0;
+		if (crowd->debug_agent == agent)
+			crowd->debug_agent = -1;
+		return 1;
+	}
+
+	int32_t recastc_crowd_request_move_target(struct recastc_Crowd* crowd, int32_t agent, uint32_t poly, const float* pos, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		if (crowd->detached[agent])
+		{
+			RETURN_ERROR("Agent is detached");
+		}
+		if (!crowd->crowd->requestMoveTarget(agent, poly, pos))
+		{
+			RETURN_ERROR("Invalid move target");
+		}
+		return 1;
+	}
+
+	int32_t recastc_crowd_request_move_velocity(struct recastc_Crowd* crowd, int32_t agent, const float* vel, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		if (crowd->detached[agent])
+		{
+			RETURN_ERROR("Agent is detached");
+		}
+		if (!crowd->crowd->requestMoveVelocity(agent, vel))
+		{
+			RETURN_ERROR("Invalid move velocity");
+		}
+		return 1;
+	}
+
+	int32_t recastc_crowd_reset_move_target(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		if (crowd->detached[agent])
+		{
+			RETURN_ERROR("Agent is detached");
+		}
+		if (!crowd->crowd->resetMoveTarget(agent))
+		{
+			RETURN_ERROR("Could not reset move target");
+		}
+		return 1;
+	}
+
+	int32_t recastc_crowd_update(struct recastc_Crowd* crowd, float dt, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!(dt >= 0.0f))
+		{
+			RETURN_ERROR("Invalid time step");
+		}
+
+		const int n = crowd->crowd->getAgentCount();
+		std::vector<dtPolyRef> next(n, 0);
+		std::vector<float> prev_pos(n * 3, 0.0f);
+		std::vector<float> max_speed(n, 0.0f);
+		for (int i = 0; i < n; i++)
+		{
+			dtCrowdAgent* ag = crowd->crowd->getEditableAgent(i);
+			next[i] = next_off_mesh_connection(ag);
+			dtVcopy(&prev_pos[i * 3], ag->npos);
+
+			// The agents at a closed gate stand still for the update, their path requests and
+			// corners are still updated so they leave the link when the target changes
+			crowd->waiting[i] = ag->active && !crowd->detached[i] ? closed_gate_ahead(crowd, ag, dt) : 0;
+			if (crowd->waiting[i])
+			{
+				max_speed[i] = ag->params.maxSpeed;
+				ag->params.maxSpeed = 0;
+				memset(ag->vel, 0, sizeof(ag->vel));
+			}
+		}
+
+		record_active(crowd);
+		dtCrowdAgentDebugInfo* debug = nullptr;
+		if (crowd->debug_agent >= 0)
+		{
+			debug = &crowd->debug;
+			debug->idx = (int)(std::find(crowd->active.begin(), crowd->active.end(), crowd->debug_agent) - crowd->active.begin());
+			debug->vod->reset();
+			// Overwritten when the visibility optimization runs for the agent
+			debug->optStart[0] = FLT_MAX;
+		}
+
+		crowd->crowd->update(dt, debug);
+		for (int i = 0; i < n; i++)
+		{
+			if (crowd->detached[i])
+				hold_detached(crowd, i);
+			if (crowd->waiting[i])
+				crowd->crowd->getEditableAgent(i)->params.maxSpeed = max_speed[i];
+		}
+		update_traversals(crowd, next, prev_pos, dt);
+		return 1;
+	}
+
+	int32_t recastc_crowd_detach_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		const dtCrowdAgent* ag = crowd->crowd->getAgent(agent);
+		// The crowd would finish the traversal and take the agent back
+		if (ag->state == DT_CROWDAGENT_STATE_OFFMESH)
+		{
+			RETURN_ERROR("Agent is crossing an off-mesh connection");
+		}
+
+		crowd->detached[agent] = 1;
+		memcpy(&crowd->detached_pos[agent * 3], ag->npos, sizeof(float) * 3);
+		hold_detached(crowd, agent);
+		return 1;
+	}
+
+	int32_t recastc_crowd_move_detached_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent) || !crowd->detached[agent])
+		{
+			RETURN_ERROR("Unknown detached agent");
+		}
+
+		memcpy(&crowd->detached_pos[agent * 3], pos, sizeof(float) * 3);
+		hold_detached(crowd, agent);
+		return 1;
+	}
+
+	int32_t recastc_crowd_attach_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent) || !crowd->detached[agent])
+		{
+			RETURN_ERROR("Unknown detached agent");
+		}
+
+		if (!place_agent(crowd, agent, pos, error))
+			return 0;
+		crowd->detached[agent] = 0;
+		return 1;
+	}
+
+	int32_t recastc_crowd_teleport_agent(struct recastc_Crowd* crowd, int32_t agent, const float* pos, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+		if (crowd->detached[agent])
+		{
+			RETURN_ERROR("Agent is detached");
+		}
+		// The crowd would go on moving it along the connection
+		if (crowd->crowd->getAgent(agent)->state == DT_CROWDAGENT_STATE_OFFMESH)
+		{
+			RETURN_ERROR("Agent is crossing an off-mesh connection");
+		}
+
+		if (!place_agent(crowd, agent, pos, error))
+			return 0;
+		memset(&crowd->traversals[agent], 0, sizeof(recastc_OffMeshTraversal));
+		return 1;
+	}
+
+	int32_t recastc_crowd_set_gate(struct recastc_Crowd* crowd, uint32_t user_id, int32_t open, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (user_id == 0)
+		{
+			RETURN_ERROR("Gates need a user id");
+		}
+
+		std::vector<uint32_t>& closed = crowd->closed_gates;
+		closed.erase(std::remove(closed.begin(), closed.end(), user_id), closed.end());
+		if (!open)
+			closed.push_back(user_id);
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_agent_gate(struct recastc_Crowd* crowd, int32_t agent, uint32_t* user_id, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(user_id);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		*user_id = crowd->waiting[agent];
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_neighbours(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdNeighbour* neighbours, int32_t max_neighbours, int32_t* count, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(count);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		// The crowd keeps indices into its active list and the squared distances
+		const dtCrowdAgent* ag = crowd->crowd->getAgent(agent);
+		std::vector<recastc_CrowdNeighbour> found;
+		for (int i = 0; i < ag->nneis; i++)
+		{
+			const int32_t slot = active_slot(crowd, ag->neis[i].idx);
+			if (slot < 0)
+				continue;
+			recastc_CrowdNeighbour nei = { slot, sqrtf(ag->neis[i].dist) };
+			found.push_back(nei);
+		}
+
+		*count = (int32_t)found.size();
+		if (*count > max_neighbours)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+		if (*count > 0)
+			memcpy(neighbours, found.data(), sizeof(recastc_CrowdNeighbour) * found.size());
+		return 1;
+	}
+
+	int32_t recastc_crowd_query_agents(struct recastc_Crowd* crowd, const float* center, float radius, recastc_CrowdNeighbour* agents, int32_t max_agents, int32_t* count, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(count);
+
+		if (!(radius >= 0.0f))
+		{
+			RETURN_ERROR("Invalid radius");
+		}
+
+		// The grid holds the bounds of the agents by their index in the active list of the last update,
+		// it finds every agent overlapping the box once
+		std::vector<unsigned short> ids(crowd->crowd->getAgentCount());
+		const int n = crowd->crowd->getGrid()->queryItems(center[0] - radius, center[2] - radius,
+			center[0] + radius, center[2] + radius, ids.data(), (int)ids.size());
+
+		std::vector<recastc_CrowdNeighbour> found;
+		for (int i = 0; i < n; i++)
+		{
+			const int32_t slot = active_slot(crowd, ids[i]);
+			if (slot < 0)
+				continue;
+			const float d = sqrtf(dtVdist2DSqr(center, crowd->crowd->getAgent(slot)->npos));
+			if (d <= radius)
+			{
+				recastc_CrowdNeighbour nei = { slot, d };
+				found.push_back(nei);
+			}
+		}
+		std::sort(found.begin(), found.end(), [](const recastc_CrowdNeighbour& a, const recastc_CrowdNeighbour& b) {
+			return a.distance < b.distance;
+		});
+
+		*count = (int32_t)found.size();
+		if (*count > max_agents)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+		if (*count > 0)
+			memcpy(agents, found.data(), sizeof(recastc_CrowdNeighbour) * found.size());
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_CrowdAgentState* state, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(state);
+
+		if (agent < 0 || agent >= crowd->crowd->getAgentCount())
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		const dtCrowdAgent* ag = crowd->crowd->getAgent(agent);
+		memset(state, 0, sizeof(*state));
+		state->active = ag->active ? 1 : 0;
+		if (!ag->active)
+			return 1;
+
+		state->detached = crowd->detached[agent];
+		memcpy(state->pos, ag->npos, sizeof(state->pos));
+		memcpy(state->vel, ag->vel, sizeof(state->vel));
+		memcpy(state->desired_vel, ag->dvel, sizeof(state->desired_vel));
+		// The target of a velocity request is the velocity
+		if (ag->targetState == DT_CROWDAGENT_TARGET_VALID)
+			memcpy(state->target_pos, ag->corridor.getTarget(), sizeof(state->target_pos));
+		else if (ag->targetState != DT_CROWDAGENT_TARGET_VELOCITY)
+			memcpy(state->target_pos, ag->targetPos, sizeof(state->target_pos));
+		state->poly = ag->corridor.getFirstPoly();
+		state->state = ag->state;
+		state->target_state = ag->targetState;
+		state->partial = ag->partial ? 1 : 0;
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_off_mesh_traversal(struct recastc_Crowd* crowd, int32_t agent, recastc_OffMeshTraversal* traversal, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(traversal);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		*traversal = crowd->traversals[agent];
+		return 1;
+	}
+
+	int32_t recastc_crowd_set_debug_agent(struct recastc_Crowd* crowd, int32_t agent, recastc_Error* error)
+	{
+		assert(crowd);
+
+		if (agent != -1 && !valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		crowd->debug_agent = agent;
+		crowd->debug.optStart[0] = FLT_MAX;
+		crowd->debug.vod->reset();
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_debug_info(struct recastc_Crowd* crowd, recastc_CrowdDebugInfo* info, recastc_AvoidanceSample* samples, int32_t max_samples, int32_t* sample_count, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(info);
+		assert(sample_count);
+
+		const dtObstacleAvoidanceDebugData* vod = crowd->debug.vod;
+		info->agent = crowd->debug_agent;
+		info->optimized = crowd->debug_agent >= 0 && crowd->debug.optStart[0] != FLT_MAX ? 1 : 0;
+		memcpy(info->opt_start, crowd->debug.optStart, sizeof(info->opt_start));
+		memcpy(info->opt_end, crowd->debug.optEnd, sizeof(info->opt_end));
+
+		*sample_count = crowd->debug_agent >= 0 ? vod->getSampleCount() : 0;
+		if (*sample_count > max_samples)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		for (int i = 0; i < *sample_count; i++)
+		{
+			recastc_AvoidanceSample& sample = samples[i];
+			memcpy(sample.vel, vod->getSampleVelocity(i), sizeof(sample.vel));
+			sample.size = vod->getSampleSize(i);
+			sample.penalty = vod->getSamplePenalty(i);
+			sample.desired_vel_penalty = vod->getSampleDesiredVelocityPenalty(i);
+			sample.current_vel_penalty = vod->getSampleCurrentVelocityPenalty(i);
+			sample.side_penalty = vod->getSamplePreferredSidePenalty(i);
+			sample.toi_penalty = vod->getSampleCollisionTimePenalty(i);
+		}
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_agent_corridor(struct recastc_Crowd* crowd, int32_t agent, uint32_t* polys, int32_t max_polys, int32_t* poly_count, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(poly_count);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		const dtPathCorridor& corridor = crowd->crowd->getAgent(agent)->corridor;
+		*poly_count = corridor.getPathCount();
+		if (*poly_count > max_polys)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		memcpy(polys, corridor.getPath(), sizeof(uint32_t) * *poly_count);
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_agent_walls(struct recastc_Crowd* crowd, int32_t agent, float* segments, int32_t max_segments, int32_t* count, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(count);
+
+		if (!valid_agent(crowd, agent))
+		{
+			RETURN_ERROR("Unknown agent");
+		}
+
+		const dtCrowdAgent* ag = crowd->crowd->getAgent(agent);
+		int n = 0;
+		for (int i = 0; i < ag->boundary.getSegmentCount(); i++)
+		{
+			// The avoidance skips the walls the agent is behind, as dtCrowd::update does
+			const float* s = ag->boundary.getSegment(i);
+			if (dtTriArea2D(ag->npos, s, s + 3) < 0.0f)
+				continue;
+			if (n < max_segments)
+				memcpy(&segments[n * 6], s, sizeof(float) * 6);
+			n++;
+		}
+
+		*count = n;
+		if (n > max_segments)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+		return 1;
+	}
+
+	int32_t recastc_crowd_set_avoidance_params(struct recastc_Crowd* crowd, int32_t slot, const recastc_ObstacleAvoidanceParams* params, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(params);
+
+		if (slot < 0 || slot >= DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS)
+		{
+			RETURN_ERROR("Invalid avoidance params slot");
+		}
+		if (params->adaptive_divs == 0 || params->adaptive_rings == 0 || params->adaptive_depth == 0)
+		{
+			RETURN_ERROR("Adaptive sampling needs at least one division, ring and level");
+		}
+
+		dtObstacleAvoidanceParams op;
+		op.velBias = params->vel_bias;
+		op.weightDesVel = params->weight_desired_vel;
+		op.weightCurVel = params->weight_current_vel;
+		op.weightSide = params->weight_side;
+		op.weightToi = params->weight_toi;
+		op.horizTime = params->horizon_time;
+		op.gridSize = params->grid_size;
+		op.adaptiveDivs = params->adaptive_divs;
+		op.adaptiveRings = params->adaptive_rings;
+		op.adaptiveDepth = params->adaptive_depth;
+		crowd->crowd->setObstacleAvoidanceParams(slot, &op);
+		return 1;
+	}
+
+	int32_t recastc_crowd_get_avoidance_params(struct recastc_Crowd* crowd, int32_t slot, recastc_ObstacleAvoidanceParams* params, recastc_Error* error)
+	{
+		assert(crowd);
+		assert(params);
+
+		if (slot < 0 || slot >= DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS)
+		{
+			RETURN_ERROR("Invalid avoidance params slot");
+		}
+
+		const dtObstacleAvoidanceParams* op = crowd->crowd->getObstacleAvoidanceParams(slot);
+		params->vel_bias = op->velBias;
+		params->weight_desired_vel = op->weightDesVel;
+		params->weight_current_vel = op->weightCurVel;
+		params->weight_side = op->weightSide;
+		params->weight_toi = op->weightToi;
+		params->horizon_time = op->horizTime;
+		params->grid_size = op->gridSize;
+		params->adaptive_divs = op->adaptiveDivs;
+		params->adaptive_rings = op->adaptiveRings;
+		params->adaptive_depth = op->adaptiveDepth;
+		return 1;
+	}
+
+	void recastc_free_crowd(struct recastc_Crowd* crowd)
+	{
+		delete crowd;
+	}
+}
@@ -0,0 +1,110 @@
+#ifndef __RECASTC_INTERNAL_HH
+#define __RECASTC_INTERNAL_HH
+
+#include "recastc.h"
+#include <DetourNavMesh.h>
+#include <DetourNavMeshQuery.h>
+#include <DetourNavMeshBuilder.h>
+
+#include <assert.h>
+#include <cstring>
+#include <vector>
+
+/// The default filter with extra costs for the height gained and lost between two points,
+/// needs Detour built with DT_VIRTUAL_QUERYFILTER
+struct recastc_Filter : public dtQueryFilter
+{
+	float uphillCost = 0.0f;
+	float downhillCost = 0.0f;
+
+	float getCost(const float* pa, const float* pb,
+		const dtPolyRef prevRef, const dtMeshTile* prevTile, const dtPoly* prevPoly,
+		const dtPolyRef curRef, const dtMeshTile* curTile, const dtPoly* curPoly,
+		const dtPolyRef nextRef, const dtMeshTile* nextTile, const dtPoly* nextPoly) const override
+	{
+		float cost = dtQueryFilter::getCost(pa, pb, prevRef, prevTile, prevPoly, curRef, curTile, curPoly, nextRef, nextTile, nextPoly);
+		float dy = pb[1] - pa[1];
+		return cost + (dy > 0 ? dy * uphillCost : -dy * downhillCost);
+	}
+};
+
+struct recastc_Query
+{
+	dtNavMeshQuery *q;
+	dtNavMesh* mesh;
+	recastc_Filter filter;
+};
+
+static inline void write_error(const char* msg, recastc_Error* error) {
+	assert(strlen(msg) < 255);
+	strcpy(error->msg, msg);	
+}
+
+#define RETURN_ERROR(MSG)		do {  write_error(MSG, error); return 0;  } while(0)
+
+/// The off-mesh connections split into the arrays of `dtNavMeshCreateParams`,
+/// must outlive `dtCreateNavMeshData`
+struct OffMeshParams
+{
+	std::vector<float> verts;
+	std::vector<float> rads;
+	std::vector<unsigned short> flags;
+	std::vector<unsigned char> areas;
+	std::vector<unsigned char> dirs;
+	std::vector<unsigned int> ids;
+
+	void fill(const recastc_OffMeshConnection* cons, uint32_t count, dtNavMeshCreateParams& params)
+	{
+		for (uint32_t i = 0; i < count; i++)
+		{
+			const recastc_OffMeshConnection& con = cons[i];
+			verts.insert(verts.end(), con.start, con.start + 3);
+			verts.insert(verts.end(), con.end, con.end + 3);
+			rads.push_back(con.radius);
+			flags.push_back(con.flags);
+			areas.push_back(con.area);
+			dirs.push_back(con.bidirectional ? DT_OFFMESH_CON_BIDIR : 0);
+			ids.push_back(con.user_id);
+		}
+
+		params.offMeshConVerts = count ? verts.data() : 0;
+		params.offMeshConRad = count ? rads.data() : 0;
+		params.offMeshConFlags = count ? flags.data() : 0;
+		params.offMeshConAreas = count ? areas.data() : 0;
+		params.offMeshConDir = count ? dirs.data() : 0;
+		params.offMeshConUserID = count ? ids.data() : 0;
+		params.offMeshConCount = (int)count;
+	}
+};
+
+// Not including Recast.h, recastc.cpp has its own copy of some Recast symbols
+struct rcConfig;
+struct rcHeightfield;
+struct rcCompactHeightfield;
+class rcContext;
+
+/// Shared by the builds in recastc_build.cpp and recastc_tilecache.cpp
+void init_config(const recastc_BuildConfig* config, rcConfig& cfg);
+/// The border of a tile build in cells, `tile_border_size` or the walkable radius and 3 cells
+int tile_border_size(const recastc_BuildConfig* config, const rcConfig& cfg);
+/// The height within which Detour connects tile borders, `border_stitch_tolerance` or the walkable climb
+float border_stitch_tolerance(const recastc_BuildConfig* config);
+/// The bounds and border of a tile build, `cfg` must be initialized by `init_config`
+void init_tile_config(const recastc_TileInput* tile, const recastc_BuildConfig* config, rcConfig& cfg);
+bool validate_input(const recastc_BuildInput* input, const recastc_BuildConfig* config, recastc_Error* error);
+/// Rasterize the input within the bounds of `cfg`, erode the walkable area and mark the water and convex volume areas.
+/// The solid heightfield is given to `solid` when set, freed otherwise
+rcCompactHeightfield* build_compact_heightfield(rcContext* ctx, const recastc_BuildInput* input, const recastc_BuildConfig* config, const rcConfig& cfg, recastc_Error* error,
+	rcHeightfield** solid = nullptr);
+
+extern "C"
+{
+	/// Create the navmesh and query objects from the data of a single tile,
+	/// the navmesh takes the ownership of `navData`
+	struct recastc_Query *recastc_create_query_from_data(unsigned char* navData, int navDataSize, recastc_Error* error);
+
+	/// Create the query object of an initialized navmesh, the query takes the ownership of `mesh`
+	struct recastc_Query *recastc_create_query_from_mesh(dtNavMesh* mesh, recastc_Error* error);
+}
+
+#endif
@@ -0,0 +1,134 @@
+#include "recastc_internal.h"
+#include <DetourProximityGrid.h>
+
+#include <math.h>
+#include <vector>
+
+/// Reference: dtCrowd::update, which fills its grid with the bounds of the agents
+/// and queries it for their neighbours
+
+struct recastc_ProximityGrid
+{
+	dtProximityGrid* grid = nullptr;
+	int32_t pool_size = 0;
+	float cell_size = 0.0f;
+	/// Pool entries taken, one per cell an item covers. The grid drops items silently when full
+	int32_t used = 0;
+	/// Items added since the last clear, the most ids a query can find
+	int32_t items = 0;
+	std::vector<unsigned short> ids;
+
+	~recastc_ProximityGrid()
+	{
+		dtFreeProximityGrid(grid);
+	}
+};
+
+/// The cell coordinates are shorts in the grid
+static bool cell_range(const recastc_ProximityGrid* grid, const float* bounds, int* cells, recastc_Error* error)
+{
+	for (int i = 0; i < 4; i++)
+	{
+		if (!(fabsf(bounds[i] / grid->cell_size) < 32767.0f))
+		{
+			RETURN_ERROR("Bounds out of the grid range");
+		}
+	}
+	if (bounds[0] > bounds[2] || bounds[1] > bounds[3])
+	{
+		RETURN_ERROR("Invalid bounds");
+	}
+
+	const int w = (int)floorf(bounds[2] / grid->cell_size) - (int)floorf(bounds[0] / grid->cell_size) + 1;
+	const int h = (int)floorf(bounds[3] / grid->cell_size) - (int)floorf(bounds[1] / grid->cell_size) + 1;
+	*cells = w * h;
+	return true;
+}
+
+extern "C"
+{
+	struct recastc_ProximityGrid* recastc_create_proximity_grid(int32_t pool_size, float cell_size, recastc_Error* error)
+	{
+		// The pool is indexed by shorts, 0xffff is the end of a bucket
+		if (pool_size <= 0 || pool_size >= 0xffff || !(cell_size > 0.0f))
+		{
+			RETURN_ERROR("Invalid grid size");
+		}
+
+		recastc_ProximityGrid* grid = new recastc_ProximityGrid();
+		grid->grid = dtAllocProximityGrid();
+		if (!grid->grid || !grid->grid->init(pool_size, cell_size))
+		{
+			delete grid;
+			RETURN_ERROR("Could not init grid");
+		}
+		grid->pool_size = pool_size;
+		grid->cell_size = cell_size;
+		return grid;
+	}
+
+	int32_t recastc_proximity_grid_add_item(struct recastc_ProximityGrid* grid, uint16_t id, const float* bounds, recastc_Error* error)
+	{
+		assert(grid);
+
+		int cells = 0;
+		if (!cell_range(grid, bounds, &cells, error))
+			return 0;
+		if (grid->used + cells > grid->pool_size)
+		{
+			RETURN_ERROR("Grid is full");
+		}
+
+		grid->grid->addItem(id, bounds[0], bounds[1], bounds[2], bounds[3]);
+		grid->used += cells;
+		grid->items++;
+		return 1;
+	}
+
+	int32_t recastc_proximity_grid_query_items(struct recastc_ProximityGrid* grid, const float* bounds, uint16_t* ids, int32_t max_ids, int32_t* count, recastc_Error* error)
+	{
+		assert(grid);
+		assert(count);
+
+		int cells = 0;
+		if (!cell_range(grid, bounds, &cells, error))
+			return 0;
+
+		// Each item is found once, so the items added are enough room
+		grid->ids.resize(grid->items > 0 ? grid->items : 1);
+		*count = grid->grid->queryItems(bounds[0], bounds[1], bounds[2], bounds[3], grid->ids.data(), (int)grid->ids.size());
+		if (*count > max_ids)
+		{
+			RETURN_ERROR("BUFFER_TOO_SMALL");
+		}
+
+		memcpy(ids, grid->ids.data(), sizeof(uint16_t) * *count);
+		return 1;
+	}
+
+	int32_t recastc_proximity_grid_item_count_at(struct recastc_ProximityGrid* grid, float x, float y, int32_t* count, recastc_Error* error)
+	{
+		assert(grid);
+		assert(count);
+
+		const float bounds[4] = { x, y, x, y };
+		int cells = 0;
+		if (!cell_range(grid, bounds, &cells, error))
+			return 0;
+
+		*count = grid->grid->getItemCountAt((int)floorf(x / grid->cell_size), (int)floorf(y / grid->cell_size));
+		return 1;
+	}
+
+	void recastc_proximity_grid_clear(struct recastc_ProximityGrid* grid)
+	{
+		grid->grid->clear();
+		grid->used = 0;
+		grid->items = 0;
+	}
+
+	void recastc_free_proximity_grid(struct recastc_ProximityGrid* grid)
+	{
+		delete grid;
+	}
+}
@@ -0,0 +1,532 @@
+#include "recastc_internal.h"
+#include <Recast.h>
+#include <DetourCommon.h>
+#include <DetourTileCache.h>
+#include <DetourTileCacheBuilder.h>
+#include <fastlz.h>
+
+#include <vector>
+
+/// Reference: Sample_TempObstacles.cpp
+
+struct FastLZCompressor : public dtTileCacheCompressor
+{
+	virtual int maxCompressedSize(const int bufferSize)
+	{
+		return (int)(bufferSize * 1.05f);
+	}
+
+	virtual dtStatus compress(const unsigned char* buffer, const int bufferSize,
+		unsigned char* compressed, const int /*maxCompressedSize*/, int* compressedSize)
+	{
+		*compressedSize = fastlz_compress((const void*)buffer, bufferSize, compressed);
+		return DT_SUCCESS;
+	}
+
+	virtual dtStatus decompress(const unsigned char* compressed, const int compressedSize,
+		unsigned char* buffer, const int maxBufferSize, int* bufferSize)
+	{
+		*bufferSize = fastlz_decompress(compressed, compressedSize, buffer, maxBufferSize);
+		return *bufferSize < 0 ? DT_FAILURE : DT_SUCCESS;
+	}
+};
+
+/// Compression implemented by the caller
+struct CallbackCompressor : public dtTileCacheCompressor
+{
+	recastc_Compressor callbacks;
+
+	virtual int maxCompressedSize(const int bufferSize)
+	{
+		return callbacks.max_compressed_size(callbacks.user, bufferSize);
+	}
+
+	virtual dtStatus compress(const unsigned char* buffer, const int bufferSize,
+		unsigned char* compressed, const int maxCompressedSize, int* compressedSize)
+	{
+		if (!callbacks.compress(callbacks.user, buffer, bufferSize, compressed, maxCompressedSize, compressedSize))
+			return DT_FAILURE;
+		return DT_SUCCESS;
+	}
+
+	virtual dtStatus decompress(const unsigned char* compressed, const int compressedSize,
+		unsigned char* buffer, const int maxBufferSize, int* bufferSize)
+	{
+		if (!callbacks.decompress(callbacks.user, compressed, compressedSize, buffer, maxBufferSize, bufferSize))
+			return DT_FAILURE;
+		return DT_SUCCESS;
+	}
+};
+
+/// Scratch memory of a tile rebuild, everything is freed on reset
+struct ScratchAllocator : public dtTileCacheAlloc
+{
+	std::vector<void*> blocks;
+
+	~ScratchAllocator()
+	{
+		reset();
+	}
+
+	virtual void reset()
+	{
+		for (void* block : blocks)
+			dtFree(block);
+		blocks.clear();
+	}
+
+	virtual void* alloc(const size_t size)
+	{
+		void* block = dtAlloc(size, DT_ALLOC_TEMP);
+		if (block)
+			blocks.push_back(block);
+		return block;
+	}
+
+	virtual void free(void*)
+	{
+	}
+};
+
+/// Same area and flags conventions as `recastc_build_tile`
+struct MeshProcess : public dtTileCacheMeshProcess
+{
+	uint16_t area_flags[64];
+	std::vector<recastc_OffMeshConnection> cons;
+	OffMeshParams offMesh;
+
+	virtual void process(struct dtNavMeshCreateParams* params, unsigned char* polyAreas, unsigned short* polyFlags)
+	{
+		for (int i = 0; i < params->polyCount; i++)
+		{
+			if (polyAreas[i] == DT_TILECACHE_WALKABLE_AREA)
+				polyAreas[i] = 0;
+			// Layers loaded from a file may have any area, those past the 64 of Detour get no flags
+			polyFlags[i] = polyAreas[i] < 64 ? area_flags[polyAreas[i]] : 0;
+		}
+
+		// Detour keeps the connections starting in the tile
+		offMesh = OffMeshParams();
+		offMesh.fill(cons.data(), (uint32_t)cons.size(), *params);
+	}
+};
+
+struct recastc_TileCache
+{
+	dtTileCache* cache = nullptr;
+	dtNavMesh* mesh = nullptr;	///< Owned by the query
+	FastLZCompressor fastlz;
+	CallbackCompressor callbacks;
+	dtTileCacheCompressor* comp = &fastlz;
+	ScratchAllocator alloc;
+	MeshProcess proc;
+
+	~recastc_TileCache()
+	{
+		dtFreeTileCache(cache);
+	}
+};
+
+/// Rasterize a tile into heightfield layers and add them to the tile cache
+static bool add_tile_layers(const recastc_BuildInput* input, const recastc_BuildConfig* config, const rcConfig& cfg,
+	int tx, int ty, recastc_TileCache* tc, recastc_Error* error)
+{
+	rcContext ctx(false);
+
+	rcCompactHeightfield* chf = build_compact_heightfield(&ctx, input, config, cfg, error);
+	if (!chf)
+		return false;
+
+	rcHeightfieldLayerSet* lset = rcAllocHeightfieldLayerSet();
+	if (!lset)
+	{
+		rcFreeCompactHeightfield(chf);
+		RETURN_ERROR("Out of memory 'lset'");
+	}
+
+	const bool built = rcBuildHeightfieldLayers(&ctx, *chf, cfg.borderSize, cfg.walkableHeight, *lset);
+	rcFreeCompactHeightfield(chf);
+	if (!built)
+	{
+		rcFreeHeightfieldLayerSet(lset);
+		RETURN_ERROR("Could not build heightfield layers");
+	}
+
+	bool ok = true;
+	for (int i = 0; i < lset->nlayers && ok; i++)
+	{
+		const rcHeightfieldLayer* layer = &lset->layers[i];
+
+		dtTileCacheLayerHeader header;
+		header.magic = DT_TILECACHE_MAGIC;
+		header.version = DT_TILECACHE_VERSION;
+		header.tx = tx;
+		header.ty = ty;
+		header.tlayer = i;
+		rcVcopy(header.bmin, layer->bmin);
+		rcVcopy(header.bmax, layer->bmax);
+		header.width = (unsigned char)layer->width;
+		header.height = (unsigned char)layer->height;
+		header.minx = (unsigned char)layer->minx;
+		header.maxx = (unsigned char)layer->maxx;
+		header.miny = (unsigned char)layer->miny;
+		header.maxy = (unsigned char)layer->maxy;
+		header.hmin = (unsigned short)layer->hmin;
+		header.hmax = (unsigned short)layer->hmax;
+
+		unsigned char* data = 0;
+		int dataSize = 0;
+		if (dtStatusFailed(dtBuildTileCacheLayer(tc->comp, &header, layer->heights, layer->areas, layer->cons, &data, &dataSize)))
+		{
+			write_error("Could not build tile cache layer", error);
+			ok = false;
+		}
+		else if (dtStatusFailed(tc->cache->addTile(data, dataSize, DT_COMPRESSEDTILE_FREE_DATA, 0)))
+		{
+			dtFree(data);
+			write_error("Could not add tile cache layer, too many layers", error);
+			ok = false;
+		}
+	}
+
+	rcFreeHeightfieldLayerSet(lset);
+	return ok;
+}
+
+extern "C"
+{
+	struct recastc_TileCache* recastc_build_tile_cache(const recastc_BuildInput* input,
+		const recastc_BuildConfig* config,
+		const recastc_TileCacheParams* params,
+		struct recastc_Query** query,
+		recastc_Error* error)
+	{
+		assert(input);
+		assert(config);
+		assert(params);
+		assert(query);
+
+		*query = 0;
+
+		if (!validate_input(input, config, error)) {
+			return 0;
+		}
+		if (params->tile_size <= 0 || params->tile_size > 255) {
+			RETURN_ERROR("tile_size must be in 1..=255");
+		}
+
+		rcConfig cfg;
+		init_config(config, cfg);
+
+		// The layers of a tile store the tile border as well
+		const int borderSize = tile_border_size(config, cfg);
+		if (params->tile_size + borderSize * 2 > 255) {
+			RETURN_ERROR("tile_size is too large for the tile cache layers");
+		}
+
+		auto tc = new recastc_TileCache();
+		memcpy(tc->proc.area_flags, config->area_flags, sizeof(tc->proc.area_flags));
+		if (params->compressor)
+		{
+			tc->callbacks.callbacks = *params->compressor;
+			tc->comp = &tc->callbacks;
+		}
+		tc->proc.cons.assign(input->off_mesh_connections, input->off_mesh_connections + input->off_mesh_connection_count);
+
+		dtTileCacheParams tcparams;
+		memset(&tcparams, 0, sizeof(tcparams));
+		rcVcopy(tcparams.orig, params->bmin);
+		tcparams.cs = cfg.cs;
+		tcparams.ch = cfg.ch;
+		tcparams.width = params->tile_size;
+		tcparams.height = params->tile_size;
+		tcparams.walkableHeight = config->walkable_height;
+		tcparams.walkableRadius = config->walkable_radius;
+		tcparams.walkableClimb = border_stitch_tolerance(config);
+		tcparams.maxSimplificationError = config->max_simplification_error;
+		tcparams.maxTiles = params->mesh.max_tiles;
+		tcparams.maxObstacles = params->max_obstacles;
+
+		tc->cache = dtAllocTileCache();
+		if (!tc->cache || dtStatusFailed(tc->cache->init(&tcparams, &tc->alloc, tc->comp, &tc->proc)))
+		{
+			delete tc;
+			RETURN_ERROR("Could not init tile cache");
+		}
+
+		recastc_Query* q = recastc_create_tiled_query(&params->mesh, error);
+		if (!q)
+		{
+			delete tc;
+			return 0;
+		}
+		tc->mesh = q->mesh;
+
+		int gw = 0, gh = 0;
+		rcCalcGridSize(params->bmin, params->bmax, cfg.cs, &gw, &gh);
+		const int tw = (gw + params->tile_size - 1) / params->tile_size;
+		const int th = (gh + params->tile_size - 1) / params->tile_size;
+
+		for (int ty = 0; ty < th; ty++)
+		{
+			for (int tx = 0; tx < tw; tx++)
+			{
+				recastc_TileInput tile;
+				tile.tile_size = params->tile_size;
+				rcVcopy(tile.bmin, params->bmin);
+				rcVcopy(tile.bmax, params->bmax);
+				tile.tx = tx;
+				tile.ty = ty;
+
+				rcConfig tcfg = cfg;
+				init_tile_config(&tile, config, tcfg);
+
+				bool ok = add_tile_layers(input, config, tcfg, tx, ty, tc, error);
+				if (ok && dtStatusFailed(tc->cache->buildNavMeshTilesAt(tx, ty, tc->mesh)))
+				{
+					write_error("Could not build the navmesh tiles of the layers", error);
+					ok = false;
+				}
+				if (!ok)
+				{
+					delete tc;
+					recastc_free_query(q);
+					return 0;
+				}
+			}
+		}
+
+		*query = q;
+		return tc;
+	}
+
+	struct recastc_TileCache* recastc_create_tile_cache(const recastc_SavedTileCacheParams* params,
+		const recastc_TiledMeshParams* mesh,
+		const uint16_t* area_flags,
+		struct recastc_Query** query,
+		recastc_Error* error)
+	{
+		assert(params);
+		assert(mesh);
+		assert(area_flags);
+		assert(query);
+
+		*query = 0;
+
+		if (!(params->cs > 0) || !(params->ch > 0)) {
+			RETURN_ERROR("Cell size and height must be positive");
+		}
+		if (params->width <= 0 || params->width > 255 || params->height <= 0 || params->height > 255) {
+			RETURN_ERROR("Tile width and height must be in 1..=255");
+		}
+		if (params->max_tiles <= 0 || params->max_obstacles <= 0) {
+			RETURN_ERROR("max_tiles and max_obstacles must be positive");
+		}
+
+		auto tc = new recastc_TileCache();
+		memcpy(tc->proc.area_flags, area_flags, sizeof(tc->proc.area_flags));
+
+		dtTileCacheParams tcparams;
+		memset(&tcparams, 0, sizeof(tcparams));
+		rcVcopy(tcparams.orig, params->orig);
+		tcparams.cs = params->cs;
+		tcparams.ch = params->ch;
+		tcparams.width = params->width;
+		tcparams.height = params->height;
+		tcparams.walkableHeight = params->walkable_height;
+		tcparams.walkableRadius = params->walkable_radius;
+		tcparams.walkableClimb = params->walkable_climb;
+		tcparams.maxSimplificationError = params->max_simplification_error;
+		tcparams.maxTiles = params->max_tiles;
+		tcparams.maxObstacles = params->max_obstacles;
+
+		tc->cache = dtAllocTileCache();
+		if (!tc->cache || dtStatusFailed(tc->cache->init(&tcparams, &tc->alloc, tc->comp, &tc->proc)))
+		{
+			delete tc;
+			RETURN_ERROR("Could not init tile cache");
+		}
+
+		recastc_Query* q = recastc_create_tiled_query(mesh, error);
+		if (!q)
+		{
+			delete tc;
+			return 0;
+		}
+		tc->mesh = q->mesh;
+
+		*query = q;
+		return tc;
+	}
+
+	int32_t recastc_add_tile_cache_layer(struct recastc_TileCache* cache, const uint8_t* data, int32_t size, recastc_Error* error)
+	{
+		assert(cache);
+		assert(data);
+
+		// addTile reads the header without a size
+		if (size < dtAlign4(sizeof(dtTileCacheLayerHeader))) {
+			RETURN_ERROR("Layer data is truncated");
+		}
+
+		unsigned char* copy = (unsigned char*)dtAlloc(size, DT_ALLOC_PERM);
+		if (!copy) {
+			RETURN_ERROR("Out of memory 'layer'");
+		}
+		memcpy(copy, data, size);
+
+		dtCompressedTileRef ref = 0;
+		const dtStatus status = cache->cache->addTile(copy, size, DT_COMPRESSEDTILE_FREE_DATA, &ref);
+		if (dtStatusFailed(status))
+		{
+			dtFree(copy);
+			if (dtStatusDetail(status, DT_WRONG_MAGIC)) {
+				RETURN_ERROR("Not tile cache layer data");
+			}
+			if (dtStatusDetail(status, DT_WRONG_VERSION)) {
+				RETURN_ERROR("Tile cache layer of another Detour version");
+			}
+			RETURN_ERROR("Could not add tile cache layer, already added or too many layers");
+		}
+
+		if (dtStatusFailed(cache->cache->buildNavMeshTile(ref, cache->mesh)))
+		{
+			cache->cache->removeTile(ref, 0, 0);
+			RETURN_ERROR("Could not build the navmesh tile of the layer");
+		}
+
+		return 1;
+	}
+
+	void recastc_get_tile_cache_params(const struct recastc_TileCache* cache, recastc_SavedTileCacheParams* params)
+	{
+		assert(cache);
+		assert(params);
+
+		const dtTileCacheParams* tcparams = cache->cache->getParams();
+		rcVcopy(params->orig, tcparams->orig);
+		params->cs = tcparams->cs;
+		params->ch = tcparams->ch;
+		params->width = tcparams->width;
+		params->height = tcparams->height;
+		params->walkable_height = tcparams->walkableHeight;
+		params->walkable_radius = tcparams->walkableRadius;
+		params->walkable_climb = tcparams->walkableClimb;
+		params->max_simplification_error = tcparams->maxSimplificationError;
+		params->max_tiles = tcparams->maxTiles;
+		params->max_obstacles = tcparams->maxObstacles;
+	}
+
+	int32_t recastc_get_tile_cache_layer(const struct recastc_TileCache* cache, int32_t index, uint32_t* layer_ref, const uint8_t** data, int32_t* size, recastc_Error* error)
+	{
+		assert(cache);
+		assert(layer_ref);
+		assert(data);
+		assert(size);
+
+		const dtTileCache* tc = cache->cache;
+		if (index < 0 || index >= tc->getTileCount()) {
+			RETURN_ERROR("Layer index out of range");
+		}
+
+		const dtCompressedTile* tile = tc->getTile(index);
+		if (!tile || !tile->header || !tile->dataSize)
+		{
+			*layer_ref = 0;
+			*data = 0;
+			*size = 0;
+			return 1;
+		}
+
+		*layer_ref = tc->getTileRef(tile);
+		*data = tile->data;
+		*size = tile->dataSize;
+		return 1;
+	}
+
+	int32_t recastc_add_cylinder_obstacle(struct recastc_TileCache* cache, const float* pos, float radius, float height, uint32_t* obstacle, recastc_Error* error)
+	{
+		assert(cache);
+		assert(pos);
+		assert(obstacle);
+
+		dtObstacleRef ref = 0;
+		if (dtStatusFailed(cache->cache->addObstacle(pos, radius, height, &ref)))
+		{
+			RETURN_ERROR("Too many obstacles or queued changes");
+		}
+
+		*obstacle = ref;
+		return 1;
+	}
+
+	int32_t recastc_add_box_obstacle(struct recastc_TileCache* cache, const float* bmin, const float* bmax, uint32_t* obstacle, recastc_Error* error)
+	{
+		assert(cache);
+		assert(bmin);
+		assert(bmax);
+		assert(obstacle);
+
+		dtObstacleRef ref = 0;
+		if (dtStatusFailed(cache->cache->addBoxObstacle(bmin, bmax, &ref)))
+		{
+			RETURN_ERROR("Too many obstacles or queued changes");
+		}
+
+		*obstacle = ref;
+		return 1;
+	}
+
+	int32_t recastc_add_oriented_box_obstacle(struct recastc_TileCache* cache, const float* center, const float* half_extents, float y_radians, uint32_t* obstacle, recastc_Error* error)
+	{
+		assert(cache);
+		assert(center);
+		assert(half_extents);
+		assert(obstacle);
+
+		dtObstacleRef ref = 0;
+		if (dtStatusFailed(cache->cache->addBoxObstacle(center, half_extents, y_radians, &ref)))
+		{
+			RETURN_ERROR("Too many obstacles or queued changes");
+		}
+
+		*obstacle = ref;
+		return 1;
+	}
+
+	int32_t recastc_remove_obstacle(struct recastc_TileCache* cache, uint32_t obstacle, recastc_Error* error)
+	{
+		assert(cache);
+
+		if (!cache->cache->getObstacleByRef(obstacle))
+		{
+			RETURN_ERROR("Unknown obstacle");
+		}
+		if (dtStatusFailed(cache->cache->removeObstacle(obstacle)))
+		{
+			RETURN_ERROR("Too many queued changes");
+		}
+
+		return 1;
+	}
+
+	int32_t recastc_update_tile_cache(struct recastc_TileCache* cache, float dt, uint8_t* up_to_date, recastc_Error* error)
+	{
+		assert(cache);
+		assert(up_to_date);
+
+		bool done = false;
+		if (dtStatusFailed(cache->cache->update(dt, cache->mesh, &done)))
+		{
+			RETURN_ERROR("Could not rebuild a tile cache tile");
+		}
+
+		*up_to_date = done ? 1 : 0;
+		return 1;
+	}
+
+	void recastc_free_tile_cache(struct recastc_TileCache* cache)
+	{
+		delete cache;
+	}
+}
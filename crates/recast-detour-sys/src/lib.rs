@@ -17,6 +17,22 @@ pub struct RecastNavMeshData {
     pub walkable_climb: f32,
     pub cell_size: f32,
     pub cell_height: f32,
+    pub off_mesh_connections: *const RecastOffMeshConnection,
+    pub off_mesh_connection_count: u32,
+    /// Area of each triangle, null for area 0
+    pub areas: *const u8,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastOffMeshConnection {
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub radius: f32,
+    pub bidirectional: u8,
+    pub area: u8,
+    pub flags: u16,
+    pub user_id: u32,
 }
 
 #[derive(Debug)]
@@ -53,12 +69,39 @@ pub struct RecastPathInput {
     pub end_poly: u32,
     pub start_pos: [f32; 3],
     pub end_pos: [f32; 3],
+    pub straight_path_options: i32,
+    pub find_path_options: i32,
+    pub max_points: u32,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct RecastStraightPathInput {
+    pub corridor: *const u32,
+    pub corridor_count: u32,
+    pub start_pos: [f32; 3],
+    pub end_pos: [f32; 3],
+    pub straight_path_options: i32,
+    pub max_points: u32,
 }
 
+pub const DT_STRAIGHTPATH_START: u8 = 0x01;
+pub const DT_STRAIGHTPATH_END: u8 = 0x02;
+pub const DT_STRAIGHTPATH_OFFMESH_CONNECTION: u8 = 0x04;
+
+pub const DT_STRAIGHTPATH_AREA_CROSSINGS: i32 = 0x01;
+pub const DT_STRAIGHTPATH_ALL_CROSSINGS: i32 = 0x02;
+
+pub const DT_FINDPATH_ANY_ANGLE: i32 = 0x02;
+
+pub const DT_VERTS_PER_POLYGON: usize = 6;
+
 #[repr(C)]
 pub struct RecastPathResult {
     pub path: [u32; 1024],
     pub path2: [f32; 2048 * 3],
+    pub path2_polys: [u32; 2048],
+    pub path2_flags: [u8; 2048],
     pub path_count: u32,
     pub path2_count: u32,
 }
@@ -68,6 +111,8 @@ impl Default for RecastPathResult {
         RecastPathResult {
             path: [0; 1024],
             path2: [0.0; 2048 * 3],
+            path2_polys: [0; 2048],
+            path2_flags: [0; 2048],
             path_count: 0,
             path2_count: 0,
         }
@@ -113,15 +158,545 @@ impl fmt::Debug for RecastNavError {
     }
 }
 
+/// Returns a random number in [0, 1)
+pub type RecastRand = extern "C" fn(user: *mut c_void) -> f32;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct RecastPolysAroundCircleInput {
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub half_extents: [f32; 3],
+}
+
+#[repr(C)]
+pub struct RecastPolysResult {
+    pub polys: [u32; 256],
+    pub poly_count: u32,
+}
+
+impl Default for RecastPolysResult {
+    fn default() -> RecastPolysResult {
+        RecastPolysResult {
+            polys: [0; 256],
+            poly_count: 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for RecastPolysResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RecastPolysResult {{ poly_count: {}, polys: {:?} }}",
+            self.poly_count,
+            &self.polys[0..(self.poly_count as usize)]
+        )
+    }
+}
+
+#[repr(C)]
+pub struct RecastDetailTrianglesResult {
+    pub verts: [f32; 255 * 9],
+    pub tri_count: u32,
+}
+
+impl Default for RecastDetailTrianglesResult {
+    fn default() -> RecastDetailTrianglesResult {
+        RecastDetailTrianglesResult {
+            verts: [0.0; 255 * 9],
+            tri_count: 0,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct RecastConvexVolume {
+    pub verts: [f32; 12 * 3],
+    pub nverts: i32,
+    pub hmin: f32,
+    pub hmax: f32,
+    pub area: u8,
+}
+
+impl std::fmt::Debug for RecastConvexVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RecastConvexVolume {{ verts: {:?}, hmin: {}, hmax: {}, area: {} }}",
+            &self.verts[0..(self.nverts as usize * 3)],
+            self.hmin,
+            self.hmax,
+            self.area
+        )
+    }
+}
+
+#[repr(C)]
+pub struct RecastWaterVolume {
+    pub verts: [f32; 12 * 3],
+    pub nverts: i32,
+    pub surface: f32,
+}
+
+impl std::fmt::Debug for RecastWaterVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RecastWaterVolume {{ verts: {:?}, surface: {} }}",
+            &self.verts[0..(self.nverts as usize * 3)],
+            self.surface
+        )
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct RecastBuildInput {
+    pub verts: *const f32,
+    pub vert_count: u32,
+    pub tris: *const i32,
+    pub tri_count: u32,
+    pub tri_areas: *const u8,
+    pub volumes: *const RecastConvexVolume,
+    pub volume_count: u32,
+    pub off_mesh_connections: *const RecastOffMeshConnection,
+    pub off_mesh_connection_count: u32,
+    pub water: *const RecastWaterVolume,
+    pub water_count: u32,
+}
+
+pub const RECASTC_SPAN_NOT_WALKABLE: u8 = 0xff;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RecastSpan {
+    pub smin: u16,
+    pub smax: u16,
+    pub area: u8,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastHeightfieldInput {
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub width: i32,
+    pub height: i32,
+    pub columns: *const u32,
+    pub spans: *const RecastSpan,
+    pub span_count: u32,
+}
+
+pub const RECASTC_NOT_CONNECTED: u8 = 0xff;
+pub const RECASTC_MESH_NULL_IDX: u16 = 0xffff;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RecastCompactSpan {
+    pub y: u16,
+    pub h: u16,
+    pub reg: u16,
+    pub dist: u16,
+    pub area: u8,
+    pub con: [u8; 4],
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastCompactHeightfieldView {
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub width: i32,
+    pub height: i32,
+    pub border_size: i32,
+    pub max_regions: u16,
+    pub columns: *const u32,
+    pub spans: *const RecastCompactSpan,
+    pub span_count: u32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastContour {
+    pub verts: *const i32,
+    pub vert_count: i32,
+    pub raw_verts: *const i32,
+    pub raw_vert_count: i32,
+    pub reg: u16,
+    pub area: u8,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastContourSetView {
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub border_size: i32,
+    pub contours: *const RecastContour,
+    pub contour_count: i32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastPolyMeshView {
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub verts: *const u16,
+    pub vert_count: i32,
+    pub polys: *const u16,
+    pub regs: *const u16,
+    pub flags: *const u16,
+    pub areas: *const u8,
+    pub poly_count: i32,
+    pub nvp: i32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastBuildArtifacts {
+    pub cell_size: f32,
+    pub cell_height: f32,
+    pub heightfield: RecastHeightfieldInput,
+    pub compact: RecastCompactHeightfieldView,
+    pub contours: RecastContourSetView,
+    pub poly_mesh: RecastPolyMeshView,
+}
+
+impl Default for RecastBuildArtifacts {
+    fn default() -> RecastBuildArtifacts {
+        // Plain numbers and pointers, null until filled by `recastc_build_artifacts`
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastBuildConfig {
+    pub cell_size: f32,
+    pub cell_height: f32,
+    pub walkable_slope_angle: f32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+    pub max_edge_len: f32,
+    pub max_simplification_error: f32,
+    pub min_region_size: i32,
+    pub merge_region_size: i32,
+    pub max_verts_per_poly: i32,
+    pub detail_sample_dist: f32,
+    pub detail_sample_max_error: f32,
+    pub partition_type: i32,
+    pub filter_flags: i32,
+    pub area_flags: [u16; 64],
+    pub shallow_water_depth: f32,
+    pub shallow_water_area: u8,
+    pub deep_water_area: u8,
+    pub tile_border_size: i32,
+    pub border_stitch_tolerance: f32,
+}
+
+pub const RECASTC_PARTITION_WATERSHED: i32 = 0;
+pub const RECASTC_PARTITION_MONOTONE: i32 = 1;
+pub const RECASTC_PARTITION_LAYERS: i32 = 2;
+
+pub const RECASTC_FILTER_LOW_HANGING_OBSTACLES: i32 = 0x1;
+pub const RECASTC_FILTER_LEDGE_SPANS: i32 = 0x2;
+pub const RECASTC_FILTER_WALKABLE_LOW_HEIGHT_SPANS: i32 = 0x4;
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastTiledMeshParams {
+    pub orig: [f32; 3],
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub max_tiles: i32,
+    pub max_polys: i32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastTileInput {
+    pub tile_size: i32,
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub tx: i32,
+    pub ty: i32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastMeshTileInput {
+    pub tile_size: i32,
+    pub tx: i32,
+    pub ty: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastTileHeader {
+    pub tx: i32,
+    pub ty: i32,
+    pub layer: i32,
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub poly_count: i32,
+    pub vert_count: i32,
+    pub off_mesh_connection_count: i32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastBorderEdge {
+    pub poly: u32,
+    pub tx: i32,
+    pub ty: i32,
+    pub side: i32,
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub covered: f32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastCompressor {
+    pub user: *mut c_void,
+    pub max_compressed_size: extern "C" fn(user: *mut c_void, size: i32) -> i32,
+    pub compress: extern "C" fn(
+        user: *mut c_void,
+        buffer: *const u8,
+        size: i32,
+        compressed: *mut u8,
+        max_compressed_size: i32,
+        compressed_size: *mut i32,
+    ) -> i32,
+    pub decompress: extern "C" fn(
+        user: *mut c_void,
+        compressed: *const u8,
+        size: i32,
+        buffer: *mut u8,
+        max_size: i32,
+        size_out: *mut i32,
+    ) -> i32,
+}
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastTileCacheParams {
+    pub bmin: [f32; 3],
+    pub bmax: [f32; 3],
+    pub tile_size: i32,
+    pub max_layers: i32,
+    pub max_obstacles: i32,
+    pub mesh: RecastTiledMeshParams,
+    /// Null for fastlz
+    pub compressor: *const RecastCompressor,
+}
+
+/// dtTileCacheParams as RecastDemo saves them
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastSavedTileCacheParams {
+    pub orig: [f32; 3],
+    pub cs: f32,
+    pub ch: f32,
+    pub width: i32,
+    pub height: i32,
+    pub walkable_height: f32,
+    pub walkable_radius: f32,
+    pub walkable_climb: f32,
+    pub max_simplification_error: f32,
+    pub max_tiles: i32,
+    pub max_obstacles: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastCrowdAgentParams {
+    pub radius: f32,
+    pub height: f32,
+    pub max_acceleration: f32,
+    pub max_speed: f32,
+    pub collision_query_range: f32,
+    pub path_optimization_range: f32,
+    pub separation_weight: f32,
+    pub update_flags: u8,
+    pub obstacle_avoidance_type: u8,
+    pub query_filter_type: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastObstacleAvoidanceParams {
+    pub vel_bias: f32,
+    pub weight_desired_vel: f32,
+    pub weight_current_vel: f32,
+    pub weight_side: f32,
+    pub weight_toi: f32,
+    pub horizon_time: f32,
+    pub grid_size: u8,
+    pub adaptive_divs: u8,
+    pub adaptive_rings: u8,
+    pub adaptive_depth: u8,
+}
+
+pub const DT_CROWD_MAX_OBSTAVOIDANCE_PARAMS: u8 = 8;
+pub const DT_CROWDAGENT_MAX_NEIGHBOURS: usize = 6;
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct RecastCrowdNeighbour {
+    pub agent: i32,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastCrowdAgentState {
+    pub pos: [f32; 3],
+    pub vel: [f32; 3],
+    pub desired_vel: [f32; 3],
+    pub target_pos: [f32; 3],
+    pub poly: u32,
+    pub active: u8,
+    pub state: u8,
+    pub target_state: u8,
+    pub partial: u8,
+    pub detached: u8,
+}
+
+pub const DT_CROWD_ANTICIPATE_TURNS: u8 = 0x01;
+pub const DT_CROWD_OBSTACLE_AVOIDANCE: u8 = 0x02;
+pub const DT_CROWD_SEPARATION: u8 = 0x04;
+pub const DT_CROWD_OPTIMIZE_VIS: u8 = 0x08;
+pub const DT_CROWD_OPTIMIZE_TOPO: u8 = 0x10;
+
+pub const DT_CROWDAGENT_STATE_INVALID: u8 = 0;
+pub const DT_CROWDAGENT_STATE_WALKING: u8 = 1;
+pub const DT_CROWDAGENT_STATE_OFFMESH: u8 = 2;
+
+pub const DT_CROWDAGENT_TARGET_NONE: u8 = 0;
+pub const DT_CROWDAGENT_TARGET_FAILED: u8 = 1;
+pub const DT_CROWDAGENT_TARGET_VALID: u8 = 2;
+pub const DT_CROWDAGENT_TARGET_REQUESTING: u8 = 3;
+pub const DT_CROWDAGENT_TARGET_WAITING_FOR_QUEUE: u8 = 4;
+pub const DT_CROWDAGENT_TARGET_WAITING_FOR_PATH: u8 = 5;
+pub const DT_CROWDAGENT_TARGET_VELOCITY: u8 = 6;
+
+pub const RECASTC_OFFMESH_NONE: u8 = 0;
+pub const RECASTC_OFFMESH_ENTERING: u8 = 1;
+pub const RECASTC_OFFMESH_TRAVERSING: u8 = 2;
+pub const RECASTC_OFFMESH_EXITING: u8 = 3;
+
+pub const RECASTC_EDGE_BOUNDARY: u8 = 0;
+pub const RECASTC_EDGE_INTERNAL: u8 = 1;
+pub const RECASTC_EDGE_PORTAL: u8 = 2;
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastOffMeshTraversal {
+    pub phase: u8,
+    pub poly: u32,
+    pub user_id: u32,
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct RecastAvoidanceSample {
+    pub vel: [f32; 3],
+    pub size: f32,
+    pub penalty: f32,
+    pub desired_vel_penalty: f32,
+    pub current_vel_penalty: f32,
+    pub side_penalty: f32,
+    pub toi_penalty: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastCrowdDebugInfo {
+    pub agent: i32,
+    pub optimized: u8,
+    pub opt_start: [f32; 3],
+    pub opt_end: [f32; 3],
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastCorridorState {
+    pub pos: [f32; 3],
+    pub target: [f32; 3],
+    pub first_poly: u32,
+    pub last_poly: u32,
+    pub path_count: i32,
+}
+
+pub const RECASTC_MAX_BOUNDARY_SEGMENTS: usize = 8;
+
+pub const RECASTC_POLYFLAGS_WALK: u16 = 0x01;
+pub const RECASTC_POLYFLAGS_SWIM: u16 = 0x02;
+pub const RECASTC_POLYFLAGS_DOOR: u16 = 0x04;
+pub const RECASTC_POLYFLAGS_JUMP: u16 = 0x08;
+pub const RECASTC_POLYFLAGS_DISABLED: u16 = 0x10;
+pub const RECASTC_POLYFLAGS_DROP: u16 = 0x20;
+pub const RECASTC_POLYFLAGS_ALL: u16 = 0xffff;
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct RecastQueryFilter {
+    pub include_flags: u16,
+    pub exclude_flags: u16,
+    pub area_costs: [f32; 64],
+    pub uphill_cost: f32,
+    pub downhill_cost: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+#[repr(C)]
+pub struct RecastBuildInfo {
+    pub navmesh_magic: i32,
+    pub navmesh_version: i32,
+    pub tile_cache_magic: i32,
+    pub tile_cache_version: i32,
+    pub poly_ref_bits: u32,
+    pub little_endian: u8,
+    pub virtual_query_filter: u8,
+}
+
+/// Commit of the vendored recastnavigation, "unknown" when the build had no git checkout
+pub const RECASTNAVIGATION_COMMIT: &str = env!("RECASTNAVIGATION_COMMIT");
+
 #[link(name = "RecastC", kind = "static")]
 extern "C" {
     pub fn recastc_version() -> *const c_char;
 
+    pub fn recastc_build_info(info: *mut RecastBuildInfo);
+
     pub fn recastc_create_query(
         qparam: *const RecastNavMeshData,
         error: *mut RecastNavError,
     ) -> *const c_void;
 
+    pub fn recastc_build_query(
+        input: *const RecastBuildInput,
+        config: *const RecastBuildConfig,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_build_query_from_heightfield(
+        heightfield: *const RecastHeightfieldInput,
+        input: *const RecastBuildInput,
+        config: *const RecastBuildConfig,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
     /// Return 0 if fail
     pub fn recastc_find_nearest_poly(
         query: *const c_void,
@@ -145,6 +720,780 @@ extern "C" {
         error: *mut RecastNavError,
     ) -> i32;
 
+    pub fn recastc_find_straight_path(
+        query: *const c_void,
+        input: *const RecastStraightPathInput,
+        result: *mut RecastPathResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_find_polys_around_circle(
+        query: *const c_void,
+        input: *const RecastPolysAroundCircleInput,
+        result: *mut RecastPolysResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_find_random_point(
+        query: *const c_void,
+        frand: RecastRand,
+        frand_user: *mut c_void,
+        result: *mut RecastNearestPolyResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_find_random_point_around_circle(
+        query: *const c_void,
+        input: *const RecastPolysAroundCircleInput,
+        frand: RecastRand,
+        frand_user: *mut c_void,
+        result: *mut RecastNearestPolyResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_height(
+        query: *const c_void,
+        poly: u32,
+        pos: *const f32,
+        height: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_area(
+        query: *const c_void,
+        poly: u32,
+        area: *mut u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_set_poly_area(
+        query: *const c_void,
+        poly: u32,
+        area: u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_set_area_cost(
+        query: *const c_void,
+        area: u8,
+        cost: f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_flags(
+        query: *const c_void,
+        poly: u32,
+        flags: *mut u16,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_set_poly_flags(
+        query: *const c_void,
+        poly: u32,
+        flags: u16,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_set_filter(query: *const c_void, filter: *const RecastQueryFilter);
+
+    pub fn recastc_get_off_mesh_connection(
+        query: *const c_void,
+        poly: u32,
+        connection: *mut RecastOffMeshConnection,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_create_tiled_query(
+        params: *const RecastTiledMeshParams,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_create_mesh_tile(
+        mesh: *const RecastNavMeshData,
+        tile: *const RecastMeshTileInput,
+        data: *mut *mut u8,
+        data_size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_build_tile(
+        input: *const RecastBuildInput,
+        config: *const RecastBuildConfig,
+        tile: *const RecastTileInput,
+        data: *mut *mut u8,
+        data_size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_add_tile(
+        query: *const c_void,
+        data: *mut u8,
+        data_size: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_remove_tile(
+        query: *const c_void,
+        tx: i32,
+        ty: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_remove_tile_layer(
+        query: *const c_void,
+        tx: i32,
+        ty: i32,
+        layer: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_data(
+        query: *const c_void,
+        tx: i32,
+        ty: i32,
+        data: *mut *const u8,
+        data_size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_at_index(
+        query: *const c_void,
+        index: i32,
+        tile_ref: *mut u64,
+        data: *mut *const u8,
+        data_size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_restore_tile(
+        query: *const c_void,
+        data: *mut u8,
+        data_size: i32,
+        tile_ref: u64,
+        error: *mut RecastNavError,
+    ) -> i32;
+    pub fn recastc_restore_tile_in_place(
+        query: *const c_void,
+        data: *mut u8,
+        data_size: i32,
+        tile_ref: u64,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_nav_mesh_params(
+        query: *const c_void,
+        params: *mut RecastTiledMeshParams,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tiles(
+        query: *const c_void,
+        coords: *mut i32,
+        max_tiles: i32,
+        tile_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_header(
+        data: *const u8,
+        data_size: i32,
+        header: *mut RecastTileHeader,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_validate_tile_data(
+        data: *const u8,
+        data_size: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_mesh_hash(
+        data: *const u8,
+        data_size: i32,
+        hash: *mut u64,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_seam_links(
+        query: *const c_void,
+        tx: i32,
+        ty: i32,
+        counts: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_find_unmatched_border_edges(
+        query: *const c_void,
+        edges: *mut RecastBorderEdge,
+        max_edges: i32,
+        edge_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_polys(
+        query: *const c_void,
+        tx: i32,
+        ty: i32,
+        polys: *mut u32,
+        max_polys: i32,
+        poly_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_polys(
+        query: *const c_void,
+        polys: *mut u32,
+        max_polys: i32,
+        poly_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_neighbours(
+        query: *const c_void,
+        poly: u32,
+        result: *mut RecastPolysResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_detail_triangles(
+        query: *const c_void,
+        poly: u32,
+        result: *mut RecastDetailTrianglesResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_center(
+        query: *const c_void,
+        poly: u32,
+        center: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_vertices(
+        query: *const c_void,
+        poly: u32,
+        verts: *mut f32,
+        vert_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_poly_edge_kinds(
+        query: *const c_void,
+        poly: u32,
+        kinds: *mut u8,
+        edge_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_portal(
+        query: *const c_void,
+        from: u32,
+        to: u32,
+        left: *mut f32,
+        right: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_raycast(
+        query: *const c_void,
+        poly: u32,
+        start: *const f32,
+        end: *const f32,
+        t: *mut f32,
+        hit_pos: *mut f32,
+        hit_normal: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_move_along_surface(
+        query: *const c_void,
+        poly: u32,
+        start: *const f32,
+        end: *const f32,
+        result_pos: *mut f32,
+        visited: *mut RecastPolysResult,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_find_distance_to_wall(
+        query: *const c_void,
+        poly: u32,
+        pos: *const f32,
+        max_radius: f32,
+        distance: *mut f32,
+        hit_pos: *mut f32,
+        hit_normal: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_alloc_tile_data(data_size: i32) -> *mut u8;
+
+    pub fn recastc_free_tile_data(data: *mut u8);
+
+    pub fn recastc_build_tile_cache(
+        input: *const RecastBuildInput,
+        config: *const RecastBuildConfig,
+        params: *const RecastTileCacheParams,
+        query: *mut *const c_void,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_create_tile_cache(
+        params: *const RecastSavedTileCacheParams,
+        mesh: *const RecastTiledMeshParams,
+        area_flags: *const u16,
+        query: *mut *const c_void,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_add_tile_cache_layer(
+        cache: *const c_void,
+        data: *const u8,
+        size: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_get_tile_cache_params(
+        cache: *const c_void,
+        params: *mut RecastSavedTileCacheParams,
+    );
+
+    pub fn recastc_get_tile_cache_layer(
+        cache: *const c_void,
+        index: i32,
+        layer_ref: *mut u32,
+        data: *mut *const u8,
+        size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_add_cylinder_obstacle(
+        cache: *const c_void,
+        pos: *const f32,
+        radius: f32,
+        height: f32,
+        obstacle: *mut u32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_add_box_obstacle(
+        cache: *const c_void,
+        bmin: *const f32,
+        bmax: *const f32,
+        obstacle: *mut u32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_add_oriented_box_obstacle(
+        cache: *const c_void,
+        center: *const f32,
+        half_extents: *const f32,
+        y_radians: f32,
+        obstacle: *mut u32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_remove_obstacle(
+        cache: *const c_void,
+        obstacle: u32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_update_tile_cache(
+        cache: *const c_void,
+        dt: f32,
+        up_to_date: *mut u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_free_tile_cache(cache: *const c_void);
+
+    pub fn recastc_create_crowd(
+        query: *const c_void,
+        max_agents: i32,
+        max_agent_radius: f32,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_crowd_set_capacity(
+        crowd: *const c_void,
+        max_agents: i32,
+        max_agent_radius: f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_set_navmesh(
+        crowd: *const c_void,
+        query: *const c_void,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_set_filter(
+        crowd: *const c_void,
+        query: *const c_void,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_add_agent(
+        crowd: *const c_void,
+        pos: *const f32,
+        params: *const RecastCrowdAgentParams,
+        agent: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_update_agent(
+        crowd: *const c_void,
+        agent: i32,
+        params: *const RecastCrowdAgentParams,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_agent_params(
+        crowd: *const c_void,
+        agent: i32,
+        params: *mut RecastCrowdAgentParams,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_remove_agent(
+        crowd: *const c_void,
+        agent: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_request_move_target(
+        crowd: *const c_void,
+        agent: i32,
+        poly: u32,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_request_move_velocity(
+        crowd: *const c_void,
+        agent: i32,
+        vel: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_reset_move_target(
+        crowd: *const c_void,
+        agent: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_update(crowd: *const c_void, dt: f32, error: *mut RecastNavError)
+        -> i32;
+
+    pub fn recastc_crowd_detach_agent(
+        crowd: *const c_void,
+        agent: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_move_detached_agent(
+        crowd: *const c_void,
+        agent: i32,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_attach_agent(
+        crowd: *const c_void,
+        agent: i32,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_teleport_agent(
+        crowd: *const c_void,
+        agent: i32,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_set_gate(
+        crowd: *const c_void,
+        user_id: u32,
+        open: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_agent_gate(
+        crowd: *const c_void,
+        agent: i32,
+        user_id: *mut u32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_set_avoidance_params(
+        crowd: *const c_void,
+        slot: i32,
+        params: *const RecastObstacleAvoidanceParams,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_avoidance_params(
+        crowd: *const c_void,
+        slot: i32,
+        params: *mut RecastObstacleAvoidanceParams,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_neighbours(
+        crowd: *const c_void,
+        agent: i32,
+        neighbours: *mut RecastCrowdNeighbour,
+        max_neighbours: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_query_agents(
+        crowd: *const c_void,
+        center: *const f32,
+        radius: f32,
+        agents: *mut RecastCrowdNeighbour,
+        max_agents: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_agent(
+        crowd: *const c_void,
+        agent: i32,
+        state: *mut RecastCrowdAgentState,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_off_mesh_traversal(
+        crowd: *const c_void,
+        agent: i32,
+        traversal: *mut RecastOffMeshTraversal,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_set_debug_agent(
+        crowd: *const c_void,
+        agent: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_debug_info(
+        crowd: *const c_void,
+        info: *mut RecastCrowdDebugInfo,
+        samples: *mut RecastAvoidanceSample,
+        max_samples: i32,
+        sample_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_agent_corridor(
+        crowd: *const c_void,
+        agent: i32,
+        polys: *mut u32,
+        max_polys: i32,
+        poly_count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_crowd_get_agent_walls(
+        crowd: *const c_void,
+        agent: i32,
+        segments: *mut f32,
+        max_segments: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_free_crowd(crowd: *const c_void);
+
+    pub fn recastc_create_corridor(max_path: i32, error: *mut RecastNavError) -> *const c_void;
+
+    pub fn recastc_corridor_reset(
+        corridor: *const c_void,
+        poly: u32,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_set_path(
+        corridor: *const c_void,
+        target: *const f32,
+        polys: *const u32,
+        count: i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_move_position(
+        corridor: *const c_void,
+        query: *const c_void,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_move_target_position(
+        corridor: *const c_void,
+        query: *const c_void,
+        pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_optimize_visibility(
+        corridor: *const c_void,
+        query: *const c_void,
+        next: *const f32,
+        range: f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_optimize_topology(
+        corridor: *const c_void,
+        query: *const c_void,
+        optimized: *mut u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_find_corners(
+        corridor: *const c_void,
+        query: *const c_void,
+        corners: *mut f32,
+        flags: *mut u8,
+        polys: *mut u32,
+        max_corners: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_move_over_off_mesh_connection(
+        corridor: *const c_void,
+        query: *const c_void,
+        link: u32,
+        start: *mut f32,
+        end: *mut f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_is_valid(
+        corridor: *const c_void,
+        query: *const c_void,
+        max_look_ahead: i32,
+        valid: *mut u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_trim_invalid_path(
+        corridor: *const c_void,
+        query: *const c_void,
+        safe_poly: u32,
+        safe_pos: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_get_state(
+        corridor: *const c_void,
+        state: *mut RecastCorridorState,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_corridor_get_path(
+        corridor: *const c_void,
+        polys: *mut u32,
+        max_polys: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_free_corridor(corridor: *const c_void);
+
+    pub fn recastc_create_local_boundary() -> *const c_void;
+
+    pub fn recastc_local_boundary_update(
+        boundary: *const c_void,
+        query: *const c_void,
+        poly: u32,
+        pos: *const f32,
+        range: f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_local_boundary_is_valid(
+        boundary: *const c_void,
+        query: *const c_void,
+        valid: *mut u8,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_local_boundary_get_segments(
+        boundary: *const c_void,
+        center: *mut f32,
+        segments: *mut f32,
+        max_segments: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_local_boundary_reset(boundary: *const c_void);
+
+    pub fn recastc_free_local_boundary(boundary: *const c_void);
+
+    pub fn recastc_create_proximity_grid(
+        pool_size: i32,
+        cell_size: f32,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_proximity_grid_add_item(
+        grid: *const c_void,
+        id: u16,
+        bounds: *const f32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_proximity_grid_query_items(
+        grid: *const c_void,
+        bounds: *const f32,
+        ids: *mut u16,
+        max_ids: i32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_proximity_grid_item_count_at(
+        grid: *const c_void,
+        x: f32,
+        y: f32,
+        count: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_proximity_grid_clear(grid: *const c_void);
+
+    pub fn recastc_free_proximity_grid(grid: *const c_void);
+
+    pub fn recastc_build_artifacts(
+        input: *const RecastBuildInput,
+        config: *const RecastBuildConfig,
+        tile: *const RecastTileInput,
+        artifacts: *mut RecastBuildArtifacts,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_build_tile_data(
+        build: *const c_void,
+        data: *mut *mut u8,
+        data_size: *mut i32,
+        error: *mut RecastNavError,
+    ) -> i32;
+
+    pub fn recastc_create_query_from_build(
+        build: *const c_void,
+        error: *mut RecastNavError,
+    ) -> *const c_void;
+
+    pub fn recastc_free_build(build: *const c_void);
+
     pub fn recastc_free_query(query: *const c_void);
 }
 
@@ -192,6 +1541,9 @@ mod tests {
             walkable_climb: 0.1,
             cell_size,
             cell_height,
+            off_mesh_connections: ptr::null(),
+            off_mesh_connection_count: 0,
+            areas: ptr::null(),
         };
 
         let mut err = RecastNavError::zeros();
@@ -316,6 +1668,9 @@ mod tests {
             start_pos: [0.2, 0.1, 0.5],
             end_poly: 2,
             end_pos: [0.8, 0.1, 0.5],
+            straight_path_options: DT_STRAIGHTPATH_AREA_CROSSINGS,
+            find_path_options: 0,
+            max_points: 0,
         };
 
         let mut result = RecastPathResult::default();
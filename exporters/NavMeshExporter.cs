@@ -61,6 +61,38 @@ namespace Editor
             error = null;
         }
 
+        void ExportTriangulation(out string error)
+        {
+            var tgn = NavMesh.CalculateTriangulation();
+            if (tgn.areas.Length == 0)
+            {
+                error = "No navmesh was found.";
+                lastStatus = null;
+                return;
+            }
+
+            // We only use the first settings
+            var settings = NavMesh.GetSettingsByID(0);
+
+            var path = EditorUtility.SaveFilePanel("Save NavMesh Triangulation", "", "navmesh" + ".bytes", "bytes");
+            if (string.IsNullOrEmpty(path))
+            {
+                error = null;
+                return;
+            }
+
+            TriangulationToFile(tgn, settings, path);
+
+            var builder = new StringBuilder();
+            builder.AppendFormat("last export: {0} \n", System.DateTime.Now);
+            builder.AppendFormat("triangles count: {0} \n", tgn.areas.Length);
+            builder.AppendFormat("vertices count: {0} \n", tgn.vertices.Length);
+            builder.AppendFormat("path: {0} \n", path);
+
+            lastStatus = builder.ToString();
+            error = null;
+        }
+
         void OnGUI()
         {
             currentPickerWindow = EditorGUIUtility.GetControlID(FocusType.Passive) + 100;
@@ -77,6 +109,17 @@ namespace Editor
                 }
             }
 
+            // Keeps the areas and their costs, see `UnityTriangulation` of recast-detour-rs
+            if (GUILayout.Button("Export Triangulation"))
+            {
+                string error;
+                ExportTriangulation(out error);
+                if (!string.IsNullOrEmpty(error))
+                {
+                    EditorUtility.DisplayDialog("Error", error, "Ok");
+                }
+            }
+
             if (!string.IsNullOrEmpty(lastStatus))
             {
                 EditorGUILayout.HelpBox(lastStatus, MessageType.Info);
@@ -133,5 +176,45 @@ namespace Editor
                 sw.Write(MeshToObjString(tgn, settings));
             }
         }
+
+        // The layout read by `UnityTriangulation::read`, BinaryWriter writes little endian
+        static void TriangulationToFile(NavMeshTriangulation tgn, NavMeshBuildSettings settings, string filename)
+        {
+            using (var w = new BinaryWriter(File.Open(filename, FileMode.Create)))
+            {
+                w.Write(Encoding.ASCII.GetBytes("RDUN"));
+                w.Write(1u);
+
+                w.Write(settings.agentHeight);
+                w.Write(settings.agentRadius);
+                w.Write(settings.agentClimb);
+                w.Write(settings.voxelSize);
+
+                w.Write((uint)tgn.vertices.Length);
+                foreach (Vector3 v in tgn.vertices)
+                {
+                    w.Write(v.x);
+                    w.Write(v.y);
+                    w.Write(v.z);
+                }
+
+                w.Write((uint)tgn.areas.Length);
+                foreach (int i in tgn.indices)
+                {
+                    w.Write((uint)i);
+                }
+                foreach (int area in tgn.areas)
+                {
+                    w.Write((uint)area);
+                }
+
+                // Unity has 32 areas
+                w.Write(32u);
+                for (var area = 0; area < 32; area++)
+                {
+                    w.Write(NavMesh.GetAreaCost(area));
+                }
+            }
+        }
     }
 }
\ No newline at end of file